@@ -176,8 +176,8 @@ pub struct BlockChain {
 	// Only updated with `insert_unordered_block`.
 	best_ancient_block: RwLock<Option<BestAncientBlock>>,
 	// Stores the last block of the last sequence of blocks. `None` if there are no gaps.
-	// This is calculated on start and does not get updated.
-	first_block: Option<H256>,
+	// This is calculated on start and only moves back with `insert_first_block_parent`.
+	first_block: RwLock<Option<H256>>,
 
 	// block cache
 	block_headers: RwLock<HashMap<H256, Bytes>>,
@@ -207,7 +207,7 @@ impl BlockProvider for BlockChain {
 	}
 
 	fn first_block(&self) -> Option<H256> {
-		self.first_block.clone()
+		self.first_block.read().clone()
 	}
 
 	fn best_ancient_block(&self) -> Option<H256> {
@@ -419,12 +419,12 @@ impl BlockChain {
 		// 400 is the avarage size of the key
 		let cache_man = CacheManager::new(config.pref_cache_size, config.max_cache_size, 400);
 
-		let mut bc = BlockChain {
+		let bc = BlockChain {
 			blooms_config: bc::Config {
 				levels: LOG_BLOOMS_LEVELS,
 				elements_per_index: LOG_BLOOMS_ELEMENTS_PER_INDEX,
 			},
-			first_block: None,
+			first_block: RwLock::new(None),
 			best_block: RwLock::new(BestBlock::default()),
 			best_ancient_block: RwLock::new(None),
 			block_headers: RwLock::new(HashMap::new()),
@@ -512,11 +512,11 @@ impl BlockChain {
 						let mut batch = db.transaction();
 						batch.put(db::COL_EXTRA, b"first", &hash);
 						db.write(batch).expect("Low level database error.");
-						bc.first_block = Some(hash);
+						*bc.first_block.write() = Some(hash);
 					}
 				},
 				Some(raw_first) => {
-					bc.first_block = Some(H256::from_slice(&raw_first));
+					*bc.first_block.write() = Some(H256::from_slice(&raw_first));
 				},
 			}
 
@@ -785,6 +785,97 @@ impl BlockChain {
 		}
 	}
 
+	/// Get the parent hash of the first block. This is the next block needed to
+	/// extend the last block sequence backwards. `None` if there are no gaps.
+	pub fn first_block_parent(&self) -> Option<H256> {
+		self.first_block().and_then(|h| self.block_details(&h)).map(|d| d.parent)
+	}
+
+	/// Inserts the parent of the first block, extending the last block sequence backwards.
+	///
+	/// The block must be known to belong to the canonical chain, its total difficulty is derived from its child.
+	/// Once the inserted block is a child of a known block the gap is closed and both the first and
+	/// the best ancient block are cleared.
+	/// Returns true if the gap has been closed. Returns false without inserting anything
+	/// if there is no gap or the block isn't the parent of the first block.
+	pub fn insert_first_block_parent(&self, batch: &mut DBTransaction, bytes: &[u8], receipts: Vec<Receipt>) -> bool {
+		let block = BlockView::new(bytes);
+		let header = block.header_view();
+		let hash = header.sha3();
+
+		let first_hash = match self.first_block() {
+			Some(hash) => hash,
+			None => {
+				warn!("Attempted to insert first block parent {:?} with no gap in the chain", hash);
+				return false;
+			}
+		};
+		let first_details = self.block_details(&first_hash).expect("first block is always in the chain; qed");
+		let first_header = self.block_header(&first_hash).expect("first block is always in the chain; qed");
+		if first_details.parent != hash || self.pending_best_block.read().is_some() {
+			warn!("Block {:?} is not the parent of the first block {:?}", hash, first_hash);
+			return false;
+		}
+
+		let block_rlp = UntrustedRlp::new(bytes);
+		let compressed_header = block_rlp.at(0).unwrap().compress(RlpType::Blocks);
+		let compressed_body = UntrustedRlp::new(&Self::block_to_body(bytes)).compress(RlpType::Blocks);
+
+		// store block in db
+		batch.put(db::COL_HEADERS, &hash, &compressed_header);
+		batch.put(db::COL_BODIES, &hash, &compressed_body);
+
+		let info = BlockInfo {
+			hash: hash.clone(),
+			number: header.number(),
+			total_difficulty: first_details.total_difficulty - *first_header.difficulty(),
+			location: BlockLocation::CanonChain,
+		};
+
+		let mut update = HashMap::new();
+		update.insert(hash.clone(), BlockDetails {
+			number: header.number(),
+			total_difficulty: info.total_difficulty,
+			parent: header.parent_hash(),
+			children: vec![first_hash],
+		});
+
+		let maybe_parent = self.block_details(&header.parent_hash());
+		let connected = maybe_parent.is_some();
+		if let Some(mut parent_details) = maybe_parent {
+			if !parent_details.children.contains(&hash) {
+				parent_details.children.push(hash.clone());
+			}
+			update.insert(header.parent_hash(), parent_details);
+		}
+
+		let mut best_ancient_block = self.best_ancient_block.write();
+		let mut first_block = self.first_block.write();
+
+		self.prepare_update(batch, ExtrasUpdate {
+			block_hashes: self.prepare_block_hashes_update(bytes, &info),
+			block_details: update,
+			block_receipts: self.prepare_block_receipts_update(receipts, &info),
+			blocks_blooms: self.prepare_block_blooms_update(bytes, &info),
+			transactions_addresses: self.prepare_transaction_addresses_update(bytes, &info),
+			info: info,
+			block: bytes,
+		}, false);
+
+		if connected {
+			trace!("Chain gap closed at {:?}", hash);
+			batch.delete(db::COL_EXTRA, b"first");
+			batch.delete(db::COL_EXTRA, b"ancient");
+			*first_block = None;
+			*best_ancient_block = None;
+		} else {
+			batch.put(db::COL_EXTRA, b"first", &hash);
+			*first_block = Some(hash);
+		}
+
+		connected
+	}
+
 	/// Add a child to a given block. Assumes that the block hash is in
 	/// the chain and the child's parent is this block.
 	///
@@ -2091,4 +2182,57 @@ mod tests {
 		assert_eq!(bc.rewind(), Some(genesis_hash.clone()));
 		assert_eq!(bc.rewind(), None);
 	}
+
+	#[test]
+	fn backfills_ancient_blocks_backwards() {
+		let mut canon_chain = ChainGenerator::default();
+		let mut finalizer = BlockFinalizer::default();
+		let genesis = canon_chain.generate(&mut finalizer).unwrap();
+		let blocks: Vec<_> = (0..5).map(|_| canon_chain.generate(&mut finalizer).unwrap()).collect();
+		let hashes: Vec<_> = blocks.iter().map(|b| BlockView::new(b).header_view().sha3()).collect();
+		let genesis_hash = BlockView::new(&genesis).header_view().sha3();
+
+		let ref_temp = RandomTempPath::new();
+		let ref_db = new_db(ref_temp.as_str());
+		let ref_bc = BlockChain::new(Config::default(), &genesis, ref_db.clone());
+		for block in &blocks {
+			insert_block(&ref_db, &ref_bc, block, vec![]);
+		}
+
+		// restore the last two blocks only, as after a snapshot restoration.
+		let temp = RandomTempPath::new();
+		let db = new_db(temp.as_str());
+		{
+			let bc = BlockChain::new(Config::default(), &genesis, db.clone());
+			let mut batch = db.transaction();
+			let parent_td = ref_bc.block_details(&hashes[2]).unwrap().total_difficulty;
+			bc.insert_unordered_block(&mut batch, &blocks[3], vec![], Some(parent_td), true, false);
+			bc.commit();
+			bc.insert_unordered_block(&mut batch, &blocks[4], vec![], None, true, false);
+			bc.commit();
+			db.write(batch).unwrap();
+		}
+
+		let bc = BlockChain::new(Config::default(), &genesis, db.clone());
+		assert_eq!(bc.first_block(), Some(hashes[3].clone()));
+		assert_eq!(bc.best_ancient_block(), Some(genesis_hash.clone()));
+		assert_eq!(bc.first_block_parent(), Some(hashes[2].clone()));
+
+		for i in (0..3).rev() {
+			let mut batch = db.transaction();
+			let closed = bc.insert_first_block_parent(&mut batch, &blocks[i], vec![]);
+			db.write(batch).unwrap();
+			bc.commit();
+			assert_eq!(closed, i == 0);
+		}
+
+		assert_eq!(bc.first_block(), None);
+		assert_eq!(bc.best_ancient_block(), None);
+		assert_eq!(bc.block_details(&genesis_hash).unwrap().children, vec![hashes[0].clone()]);
+		for (i, hash) in hashes.iter().enumerate() {
+			assert_eq!(bc.block_hash(i as u64 + 1), Some(hash.clone()));
+			assert_eq!(bc.block_details(hash).unwrap().total_difficulty, ref_bc.block_details(hash).unwrap().total_difficulty);
+		}
+		assert_eq!(bc.block_details(&hashes[2]).unwrap().children, vec![hashes[3].clone()]);
+	}
 }
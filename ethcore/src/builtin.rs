@@ -14,24 +14,40 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+//! Built-in (precompiled) contracts and the registry they are created from.
+
 use crypto::sha2::Sha256 as Sha256Digest;
 use crypto::ripemd160::Ripemd160 as Ripemd160Digest;
 use crypto::digest::Digest;
-use std::cmp::min;
-use util::{U256, H256, Hashable, FixedHash, BytesRef};
+use std::cmp::{min, max};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read};
+use byteorder::{BigEndian, ByteOrder};
+use num::{BigUint, Zero, One, Integer};
+use util::{U256, H256, Uint, Hashable, FixedHash, BytesRef};
 use ethkey::{Signature, recover as ec_recover};
 use ethjson;
 
+/// Failure of a built-in contract, consuming all the gas given to the call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Error(pub &'static str);
+
+impl From<&'static str> for Error {
+	fn from(val: &'static str) -> Self {
+		Error(val)
+	}
+}
+
 /// Native implementation of a built-in contract.
 pub trait Impl: Send + Sync {
 	/// execute this built-in on the given input, writing to the given output.
-	fn execute(&self, input: &[u8], output: &mut BytesRef);
+	fn execute(&self, input: &[u8], output: &mut BytesRef) -> Result<(), Error>;
 }
 
 /// A gas pricing scheme for built-in contracts.
 pub trait Pricer: Send + Sync {
-	/// The gas cost of running this built-in for the given size of input data.
-	fn cost(&self, in_size: usize) -> U256;
+	/// The gas cost of running this built-in for the given input data.
+	fn cost(&self, input: &[u8]) -> U256;
 }
 
 /// A linear pricing model. This computes a price using a base cost and a cost per-word.
@@ -41,8 +57,90 @@ struct Linear {
 }
 
 impl Pricer for Linear {
-	fn cost(&self, in_size: usize) -> U256 {
-		U256::from(self.base) + U256::from(self.word) * U256::from((in_size + 31) / 32)
+	fn cost(&self, input: &[u8]) -> U256 {
+		U256::from(self.base) + U256::from(self.word) * U256::from((input.len() + 31) / 32)
+	}
+}
+
+/// Pricing of `modexp` as specified by EIP-198: the multiplication complexity of the
+/// longer of the base and modulus times the adjusted exponent length, over `divisor`.
+struct ModexpPricer {
+	divisor: usize,
+}
+
+impl Pricer for ModexpPricer {
+	fn cost(&self, input: &[u8]) -> U256 {
+		let mut reader = input.chain(io::repeat(0));
+		let mut read_len = || {
+			let mut buf = [0; 32];
+			reader.read_exact(&mut buf[..]).expect("reading from zero-extended memory cannot fail; qed");
+			U256::from(H256::from_slice(&buf[..]))
+		};
+		let base_len = read_len();
+		let exp_len = read_len();
+		let mod_len = read_len();
+
+		if base_len.is_zero() && mod_len.is_zero() {
+			return U256::zero();
+		}
+
+		// lengths this large could never be paid for anyway.
+		let max_len = U256::from(u32::max_value() / 2);
+		if base_len > max_len || exp_len > max_len || mod_len > max_len {
+			return U256::max_value();
+		}
+		let (base_len, exp_len, mod_len) = (base_len.low_u64(), exp_len.low_u64(), mod_len.low_u64());
+
+		// the first 32 bytes of the exponent.
+		let exp_low = if base_len + 96 >= input.len() as u64 {
+			U256::zero()
+		} else {
+			let mut buf = [0; 32];
+			let mut reader = input[(96 + base_len as usize)..].chain(io::repeat(0));
+			let len = min(exp_len, 32) as usize;
+			reader.read_exact(&mut buf[(32 - len)..]).expect("reading from zero-extended memory cannot fail; qed");
+			U256::from(H256::from_slice(&buf[..]))
+		};
+
+		let complexity = Self::mult_complexity(max(base_len, mod_len));
+		match complexity.overflowing_mul(max(Self::adjusted_exp_len(exp_len, exp_low), 1)) {
+			(_, true) => U256::max_value(),
+			(gas, false) => U256::from(gas / self.divisor as u64),
+		}
+	}
+}
+
+impl ModexpPricer {
+	fn adjusted_exp_len(len: u64, exp_low: U256) -> u64 {
+		let bit_index = match exp_low.bits() {
+			0 => 0,
+			bits => bits as u64 - 1,
+		};
+		if len <= 32 {
+			bit_index
+		} else {
+			8 * (len - 32) + bit_index
+		}
+	}
+
+	fn mult_complexity(x: u64) -> u64 {
+		match x {
+			x if x <= 64 => x * x,
+			x if x <= 1024 => (x * x) / 4 + 96 * x - 3072,
+			x => (x * x) / 16 + 480 * x - 199680,
+		}
+	}
+}
+
+/// Pricing of `alt_bn128_pairing`: a base cost plus a cost per pair of points.
+struct PairingPricer {
+	base: usize,
+	pair: usize,
+}
+
+impl Pricer for PairingPricer {
+	fn cost(&self, input: &[u8]) -> U256 {
+		U256::from(self.base) + U256::from(self.pair) * U256::from(input.len() / 192)
 	}
 }
 
@@ -50,42 +148,136 @@ impl Pricer for Linear {
 pub struct Builtin {
 	pricer: Box<Pricer>,
 	native: Box<Impl>,
+	activate_at: u64,
 }
 
 impl Builtin {
+	/// Create a builtin from its pricer and implementation, active from block `activate_at`.
+	pub fn new(pricer: Box<Pricer>, native: Box<Impl>, activate_at: u64) -> Self {
+		Builtin {
+			pricer: pricer,
+			native: native,
+			activate_at: activate_at,
+		}
+	}
+
 	/// Simple forwarder for cost.
-	pub fn cost(&self, s: usize) -> U256 { self.pricer.cost(s) }
+	pub fn cost(&self, input: &[u8]) -> U256 { self.pricer.cost(input) }
 
 	/// Simple forwarder for execute.
-	pub fn execute(&self, input: &[u8], output: &mut BytesRef) { self.native.execute(input, output) }
+	pub fn execute(&self, input: &[u8], output: &mut BytesRef) -> Result<(), Error> { self.native.execute(input, output) }
+
+	/// Whether the builtin is active at the given block.
+	pub fn is_active(&self, at: u64) -> bool { at >= self.activate_at }
 }
 
 impl From<ethjson::spec::Builtin> for Builtin {
 	fn from(b: ethjson::spec::Builtin) -> Self {
+		BuiltinRegistry::default().create(b).unwrap_or_else(|e| panic!("{}", e))
+	}
+}
+
+/// Creates a native implementation of a builtin.
+pub type ImplFactory = Box<Fn() -> Box<Impl>>;
+
+/// Creates a pricer of a builtin from its spec parameters.
+pub type PricerFactory = Box<Fn(&BTreeMap<String, U256>) -> Result<Box<Pricer>, String>>;
+
+/// Named builtin implementations and pricers which spec builtins are created from.
+///
+/// The default registry knows the Ethereum builtins and the `linear` pricer;
+/// chain-specific precompiles can be registered on top of it.
+pub struct BuiltinRegistry {
+	impls: HashMap<String, ImplFactory>,
+	pricers: HashMap<String, PricerFactory>,
+}
+
+impl Default for BuiltinRegistry {
+	fn default() -> Self {
+		let mut registry = BuiltinRegistry::new();
+		registry.register_impl("identity", || Box::new(Identity));
+		registry.register_impl("ecrecover", || Box::new(EcRecover));
+		registry.register_impl("sha256", || Box::new(Sha256));
+		registry.register_impl("ripemd160", || Box::new(Ripemd160));
+		registry.register_impl("modexp", || Box::new(Modexp));
+		registry.register_impl("alt_bn128_add", || Box::new(Bn128Add));
+		registry.register_impl("alt_bn128_mul", || Box::new(Bn128Mul));
+		registry.register_impl("alt_bn128_pairing", || Box::new(Bn128Pairing));
+		registry.register_pricer("linear", |params| Ok(Box::new(Linear {
+			base: try!(pricer_param(params, "base")),
+			word: try!(pricer_param(params, "word")),
+		})));
+		registry.register_pricer("modexp", |params| Ok(Box::new(ModexpPricer {
+			divisor: match try!(pricer_param(params, "divisor")) {
+				0 => return Err("modexp pricer divisor must be non-zero".into()),
+				divisor => divisor,
+			},
+		})));
+		registry.register_pricer("alt_bn128_pairing", |params| Ok(Box::new(PairingPricer {
+			base: try!(pricer_param(params, "base")),
+			pair: try!(pricer_param(params, "pair")),
+		})));
+		registry
+	}
+}
+
+impl BuiltinRegistry {
+	/// Create an empty registry.
+	pub fn new() -> Self {
+		BuiltinRegistry {
+			impls: HashMap::new(),
+			pricers: HashMap::new(),
+		}
+	}
+
+	/// Register a native implementation under `name`, replacing any previous one.
+	pub fn register_impl<F>(&mut self, name: &str, factory: F) where F: Fn() -> Box<Impl> + 'static {
+		self.impls.insert(name.to_owned(), Box::new(factory));
+	}
+
+	/// Register a pricer under `name`, replacing any previous one.
+	pub fn register_pricer<F>(&mut self, name: &str, factory: F)
+		where F: Fn(&BTreeMap<String, U256>) -> Result<Box<Pricer>, String> + 'static {
+		self.pricers.insert(name.to_owned(), Box::new(factory));
+	}
+
+	/// Create the builtin described by the spec.
+	pub fn create(&self, b: ethjson::spec::Builtin) -> Result<Builtin, String> {
 		let pricer = match b.pricing {
 			ethjson::spec::Pricing::Linear(linear) => {
 				Box::new(Linear {
 					base: linear.base,
 					word: linear.word,
-				})
-			}
+				}) as Box<Pricer>
+			},
+			ethjson::spec::Pricing::Custom(custom) => {
+				let params: BTreeMap<String, U256> = custom.params.unwrap_or_else(BTreeMap::new).into_iter().map(|(k, v)| (k, v.into())).collect();
+				let factory = try!(self.pricers.get(&custom.name).ok_or_else(|| format!("invalid builtin pricer: {}", custom.name)));
+				try!(factory(&params))
+			},
 		};
+		let native = try!(self.impls.get(&b.name).ok_or_else(|| format!("invalid builtin name: {}", b.name)));
 
-		Builtin {
-			pricer: pricer,
-			native: ethereum_builtin(&b.name),
-		}
+		Ok(Builtin::new(pricer, native(), b.activate_at.map_or(0, Into::into)))
+	}
+}
+
+/// Read a numeric pricer parameter.
+pub fn pricer_param(params: &BTreeMap<String, U256>, name: &str) -> Result<usize, String> {
+	match params.get(name) {
+		Some(v) if *v <= U256::from(usize::max_value()) => Ok(v.low_u64() as usize),
+		Some(_) => Err(format!("builtin pricer parameter out of range: {}", name)),
+		None => Err(format!("missing builtin pricer parameter: {}", name)),
 	}
 }
 
 // Ethereum builtin creator.
+#[cfg(test)]
 fn ethereum_builtin(name: &str) -> Box<Impl> {
-	match name {
-		"identity" => Box::new(Identity) as Box<Impl>,
-		"ecrecover" => Box::new(EcRecover) as Box<Impl>,
-		"sha256" => Box::new(Sha256) as Box<Impl>,
-		"ripemd160" => Box::new(Ripemd160) as Box<Impl>,
-		_ => panic!("invalid builtin name: {}", name),
+	let registry = BuiltinRegistry::default();
+	match registry.impls.get(name) {
+		Some(native) => native(),
+		None => panic!("invalid builtin name: {}", name),
 	}
 }
 
@@ -95,6 +287,8 @@ fn ethereum_builtin(name: &str) -> Box<Impl> {
 // - ec recovery
 // - sha256
 // - ripemd160
+// - big integer modular exponentiation
+// - alt_bn128 point addition, scalar multiplication and pairing check
 
 #[derive(Debug)]
 struct Identity;
@@ -108,14 +302,27 @@ struct Sha256;
 #[derive(Debug)]
 struct Ripemd160;
 
+#[derive(Debug)]
+struct Modexp;
+
+#[derive(Debug)]
+struct Bn128Add;
+
+#[derive(Debug)]
+struct Bn128Mul;
+
+#[derive(Debug)]
+struct Bn128Pairing;
+
 impl Impl for Identity {
-	fn execute(&self, input: &[u8], output: &mut BytesRef) {
+	fn execute(&self, input: &[u8], output: &mut BytesRef) -> Result<(), Error> {
 		output.write(0, input);
+		Ok(())
 	}
 }
 
 impl Impl for EcRecover {
-	fn execute(&self, i: &[u8], output: &mut BytesRef) {
+	fn execute(&self, i: &[u8], output: &mut BytesRef) -> Result<(), Error> {
 		let len = min(i.len(), 128);
 
 		let mut input = [0; 128];
@@ -128,7 +335,7 @@ impl Impl for EcRecover {
 
 		let bit = match v[31] {
 			27 | 28 if &v.0[..31] == &[0; 31] => v[31] - 27,
-			_ => return,
+			_ => return Ok(()),
 		};
 
 		let s = Signature::from_rsv(&r, &s, bit);
@@ -139,11 +346,13 @@ impl Impl for EcRecover {
 				output.write(12, &r[12..r.len()]);
 			}
 		}
+
+		Ok(())
 	}
 }
 
 impl Impl for Sha256 {
-	fn execute(&self, input: &[u8], output: &mut BytesRef) {
+	fn execute(&self, input: &[u8], output: &mut BytesRef) -> Result<(), Error> {
 		let mut sha = Sha256Digest::new();
 		sha.input(input);
 
@@ -151,11 +360,12 @@ impl Impl for Sha256 {
 		sha.result(&mut out);
 
 		output.write(0, &out);
+		Ok(())
 	}
 }
 
 impl Impl for Ripemd160 {
-	fn execute(&self, input: &[u8], output: &mut BytesRef) {
+	fn execute(&self, input: &[u8], output: &mut BytesRef) -> Result<(), Error> {
 		let mut sha = Ripemd160Digest::new();
 		sha.input(input);
 
@@ -163,12 +373,178 @@ impl Impl for Ripemd160 {
 		sha.result(&mut out[12..32]);
 
 		output.write(0, &out);
+		Ok(())
+	}
+}
+
+impl Impl for Modexp {
+	fn execute(&self, input: &[u8], output: &mut BytesRef) -> Result<(), Error> {
+		let mut reader = input.chain(io::repeat(0));
+		let mut buf = [0; 32];
+
+		// lengths too large for usize could never have been paid for.
+		let mut read_len = |reader: &mut io::Chain<&[u8], io::Repeat>| {
+			reader.read_exact(&mut buf[..]).expect("reading from zero-extended memory cannot fail; qed");
+			BigEndian::read_u64(&buf[24..]) as usize
+		};
+		let base_len = read_len(&mut reader);
+		let exp_len = read_len(&mut reader);
+		let mod_len = read_len(&mut reader);
+
+		// the exponent may be arbitrarily long when both base and modulus are empty.
+		let result = if base_len == 0 && mod_len == 0 {
+			BigUint::zero()
+		} else {
+			let mut buf = vec![0; max(mod_len, max(base_len, exp_len))];
+			let mut read_num = |len| {
+				reader.read_exact(&mut buf[..len]).expect("reading from zero-extended memory cannot fail; qed");
+				BigUint::from_bytes_be(&buf[..len])
+			};
+
+			let base = read_num(base_len);
+			let exp = read_num(exp_len);
+			let modulus = read_num(mod_len);
+			modexp(base, exp, modulus)
+		};
+
+		// the output is left-padded to the length of the modulus.
+		let bytes = result.to_bytes_be();
+		if bytes.len() <= mod_len {
+			let mut out = vec![0; mod_len];
+			out[(mod_len - bytes.len())..].copy_from_slice(&bytes);
+			output.write(0, &out);
+		}
+		Ok(())
+	}
+}
+
+fn modexp(mut base: BigUint, mut exp: BigUint, modulus: BigUint) -> BigUint {
+	if modulus <= BigUint::one() {
+		return BigUint::zero();
+	}
+
+	let mut result = BigUint::one();
+	base = base % &modulus;
+	while !exp.is_zero() {
+		if exp.is_odd() {
+			result = (result * &base) % &modulus;
+		}
+		exp = exp >> 1;
+		base = (base.clone() * &base) % &modulus;
+	}
+	result
+}
+
+lazy_static! {
+	/// Order of the bn128 curve group.
+	static ref BN128_GROUP_ORDER: BigUint = BigUint::parse_bytes(
+		b"21888242871839275222246405745257275088548364400416034343698204186575808495617", 10
+	).expect("group order is a valid decimal number; qed");
+}
+
+// any 256-bit scalar is accepted: multiplying a point of the group by it is the same as
+// multiplying by the scalar reduced modulo the group order.
+fn read_fr(reader: &mut io::Chain<&[u8], io::Repeat>) -> Result<::bn::Fr, Error> {
+	let mut buf = [0u8; 32];
+	reader.read_exact(&mut buf[..]).expect("reading from zero-extended memory cannot fail; qed");
+
+	let reduced = (BigUint::from_bytes_be(&buf[..]) % &*BN128_GROUP_ORDER).to_bytes_be();
+	let mut buf = [0u8; 32];
+	buf[(32 - reduced.len())..].copy_from_slice(&reduced);
+	::bn::Fr::from_slice(&buf[..]).map_err(|_| Error::from("Invalid field element"))
+}
+
+fn read_point(reader: &mut io::Chain<&[u8], io::Repeat>) -> Result<::bn::G1, Error> {
+	use bn::{Fq, AffineG1, G1, Group};
+
+	let mut buf = [0u8; 32];
+	reader.read_exact(&mut buf[..]).expect("reading from zero-extended memory cannot fail; qed");
+	let px = try!(Fq::from_slice(&buf[..]).map_err(|_| Error::from("Invalid point x coordinate")));
+	reader.read_exact(&mut buf[..]).expect("reading from zero-extended memory cannot fail; qed");
+	let py = try!(Fq::from_slice(&buf[..]).map_err(|_| Error::from("Invalid point y coordinate")));
+
+	if px == Fq::zero() && py == Fq::zero() {
+		Ok(G1::zero())
+	} else {
+		AffineG1::new(px, py).map(Into::into).map_err(|_| Error::from("Invalid curve point"))
+	}
+}
+
+fn write_point(point: ::bn::G1, output: &mut BytesRef) {
+	use bn::AffineG1;
+
+	// the point at infinity is encoded as zeroes.
+	let mut buf = [0u8; 64];
+	if let Some(point) = AffineG1::from_jacobian(point) {
+		point.x().to_big_endian(&mut buf[0..32]).expect("slice is 32 bytes long; qed");
+		point.y().to_big_endian(&mut buf[32..64]).expect("slice is 32 bytes long; qed");
+	}
+	output.write(0, &buf);
+}
+
+impl Impl for Bn128Add {
+	fn execute(&self, input: &[u8], output: &mut BytesRef) -> Result<(), Error> {
+		let mut reader = input.chain(io::repeat(0));
+		let p1 = try!(read_point(&mut reader));
+		let p2 = try!(read_point(&mut reader));
+		write_point(p1 + p2, output);
+		Ok(())
+	}
+}
+
+impl Impl for Bn128Mul {
+	fn execute(&self, input: &[u8], output: &mut BytesRef) -> Result<(), Error> {
+		let mut reader = input.chain(io::repeat(0));
+		let p = try!(read_point(&mut reader));
+		let fr = try!(read_fr(&mut reader));
+		write_point(p * fr, output);
+		Ok(())
+	}
+}
+
+impl Impl for Bn128Pairing {
+	fn execute(&self, input: &[u8], output: &mut BytesRef) -> Result<(), Error> {
+		use bn::{AffineG1, AffineG2, Fq, Fq2, G1, G2, Gt, Group, pairing};
+
+		if input.len() % 192 != 0 {
+			return Err("Invalid input length, must be a multiple of 192".into());
+		}
+
+		let mut product = Gt::one();
+		for chunk in input.chunks(192) {
+			let read_fq = |offset: usize| Fq::from_slice(&chunk[offset..offset + 32]).map_err(|_| Error::from("Invalid field element"));
+			let a_x = try!(read_fq(0));
+			let a_y = try!(read_fq(32));
+			// G2 coordinates are encoded with the imaginary part first.
+			let b_x = Fq2::new(try!(read_fq(96)), try!(read_fq(64)));
+			let b_y = Fq2::new(try!(read_fq(160)), try!(read_fq(128)));
+
+			let a = if a_x.is_zero() && a_y.is_zero() {
+				G1::zero()
+			} else {
+				try!(AffineG1::new(a_x, a_y).map(G1::from).map_err(|_| Error::from("Invalid a argument - not on curve")))
+			};
+			let b = if b_x.is_zero() && b_y.is_zero() {
+				G2::zero()
+			} else {
+				try!(AffineG2::new(b_x, b_y).map(G2::from).map_err(|_| Error::from("Invalid b argument - not on curve")))
+			};
+			product = product * pairing(a, b);
+		}
+
+		let mut buf = [0u8; 32];
+		if product == Gt::one() {
+			U256::one().to_big_endian(&mut buf);
+		}
+		output.write(0, &buf);
+		Ok(())
 	}
 }
 
 #[cfg(test)]
 mod tests {
-	use super::{Builtin, Linear, ethereum_builtin, Pricer};
+	use std::collections::BTreeMap;
+	use super::{Builtin, BuiltinRegistry, Linear, ModexpPricer, PairingPricer, ethereum_builtin, Pricer, Impl, Error};
 	use ethjson;
 	use util::{U256, BytesRef};
 
@@ -179,15 +555,15 @@ mod tests {
 		let i = [0u8, 1, 2, 3];
 
 		let mut o2 = [255u8; 2];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o2[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o2[..])).expect("Builtin should not fail");
 		assert_eq!(i[0..2], o2);
 
 		let mut o4 = [255u8; 4];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o4[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o4[..])).expect("Builtin should not fail");
 		assert_eq!(i, o4);
 
 		let mut o8 = [255u8; 8];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o8[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o8[..])).expect("Builtin should not fail");
 		assert_eq!(i, o8[..4]);
 		assert_eq!([255u8; 4], o8[4..]);
 	}
@@ -200,19 +576,19 @@ mod tests {
 		let i = [0u8; 0];
 
 		let mut o = [255u8; 32];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
 		assert_eq!(&o[..], &(FromHex::from_hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855").unwrap())[..]);
 
 		let mut o8 = [255u8; 8];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o8[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o8[..])).expect("Builtin should not fail");
 		assert_eq!(&o8[..], &(FromHex::from_hex("e3b0c44298fc1c14").unwrap())[..]);
 
 		let mut o34 = [255u8; 34];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o34[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o34[..])).expect("Builtin should not fail");
 		assert_eq!(&o34[..], &(FromHex::from_hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855ffff").unwrap())[..]);
 
 		let mut ov = vec![];
-		f.execute(&i[..], &mut BytesRef::Flexible(&mut ov));
+		f.execute(&i[..], &mut BytesRef::Flexible(&mut ov)).expect("Builtin should not fail");
 		assert_eq!(&ov[..], &(FromHex::from_hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855").unwrap())[..]);
 	}
 
@@ -224,15 +600,15 @@ mod tests {
 		let i = [0u8; 0];
 
 		let mut o = [255u8; 32];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
 		assert_eq!(&o[..], &(FromHex::from_hex("0000000000000000000000009c1185a5c5e9fc54612808977ee8f548b2258d31").unwrap())[..]);
 
 		let mut o8 = [255u8; 8];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o8[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o8[..])).expect("Builtin should not fail");
 		assert_eq!(&o8[..], &(FromHex::from_hex("0000000000000000").unwrap())[..]);
 
 		let mut o34 = [255u8; 34];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o34[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o34[..])).expect("Builtin should not fail");
 		assert_eq!(&o34[..], &(FromHex::from_hex("0000000000000000000000009c1185a5c5e9fc54612808977ee8f548b2258d31ffff").unwrap())[..]);
 	}
 
@@ -252,46 +628,46 @@ mod tests {
 		let i = FromHex::from_hex("47173285a8d7341e5e972fc677286384f802f8ef42a5ec5f03bbfa254cb01fad000000000000000000000000000000000000000000000000000000000000001b650acf9d3f5f0a2c799776a1254355d5f4061762a237396a99a0e0e3fc2bcd6729514a0dacb2e623ac4abd157cb18163ff942280db4d5caad66ddf941ba12e03").unwrap();
 
 		let mut o = [255u8; 32];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
 		assert_eq!(&o[..], &(FromHex::from_hex("000000000000000000000000c08b5542d177ac6686946920409741463a15dddb").unwrap())[..]);
 
 		let mut o8 = [255u8; 8];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o8[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o8[..])).expect("Builtin should not fail");
 		assert_eq!(&o8[..], &(FromHex::from_hex("0000000000000000").unwrap())[..]);
 
 		let mut o34 = [255u8; 34];
-		f.execute(&i[..], &mut BytesRef::Fixed(&mut o34[..]));
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o34[..])).expect("Builtin should not fail");
 		assert_eq!(&o34[..], &(FromHex::from_hex("000000000000000000000000c08b5542d177ac6686946920409741463a15dddbffff").unwrap())[..]);
 
 		let i_bad = FromHex::from_hex("47173285a8d7341e5e972fc677286384f802f8ef42a5ec5f03bbfa254cb01fad000000000000000000000000000000000000000000000000000000000000001a650acf9d3f5f0a2c799776a1254355d5f4061762a237396a99a0e0e3fc2bcd6729514a0dacb2e623ac4abd157cb18163ff942280db4d5caad66ddf941ba12e03").unwrap();
 		let mut o = [255u8; 32];
-		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..]));
+		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
 		assert_eq!(&o[..], &(FromHex::from_hex("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap())[..]);
 
 		let i_bad = FromHex::from_hex("47173285a8d7341e5e972fc677286384f802f8ef42a5ec5f03bbfa254cb01fad000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000000").unwrap();
 		let mut o = [255u8; 32];
-		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..]));
+		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
 		assert_eq!(&o[..], &(FromHex::from_hex("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap())[..]);
 
 		let i_bad = FromHex::from_hex("47173285a8d7341e5e972fc677286384f802f8ef42a5ec5f03bbfa254cb01fad000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000001b").unwrap();
 		let mut o = [255u8; 32];
-		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..]));
+		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
 		assert_eq!(&o[..], &(FromHex::from_hex("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap())[..]);
 
 		let i_bad = FromHex::from_hex("47173285a8d7341e5e972fc677286384f802f8ef42a5ec5f03bbfa254cb01fad000000000000000000000000000000000000000000000000000000000000001bffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff000000000000000000000000000000000000000000000000000000000000001b").unwrap();
 		let mut o = [255u8; 32];
-		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..]));
+		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
 		assert_eq!(&o[..], &(FromHex::from_hex("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap())[..]);
 
 		let i_bad = FromHex::from_hex("47173285a8d7341e5e972fc677286384f802f8ef42a5ec5f03bbfa254cb01fad000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001bffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap();
 		let mut o = [255u8; 32];
-		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..]));
+		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
 		assert_eq!(&o[..], &(FromHex::from_hex("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap())[..]);
 
 		// TODO: Should this (corrupted version of the above) fail rather than returning some address?
 	/*	let i_bad = FromHex::from_hex("48173285a8d7341e5e972fc677286384f802f8ef42a5ec5f03bbfa254cb01fad000000000000000000000000000000000000000000000000000000000000001b650acf9d3f5f0a2c799776a1254355d5f4061762a237396a99a0e0e3fc2bcd6729514a0dacb2e623ac4abd157cb18163ff942280db4d5caad66ddf941ba12e03").unwrap();
 		let mut o = [255u8; 32];
-		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..]));
+		f.execute(&i_bad[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
 		assert_eq!(&o[..], &(FromHex::from_hex("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap())[..]);*/
 	}
 
@@ -304,19 +680,16 @@ mod tests {
 	#[test]
 	fn from_named_linear() {
 		let pricer = Box::new(Linear { base: 10, word: 20 });
-		let b = Builtin {
-			pricer: pricer as Box<Pricer>,
-			native: ethereum_builtin("identity"),
-		};
+		let b = Builtin::new(pricer as Box<Pricer>, ethereum_builtin("identity"), 0);
 
-		assert_eq!(b.cost(0), U256::from(10));
-		assert_eq!(b.cost(1), U256::from(30));
-		assert_eq!(b.cost(32), U256::from(30));
-		assert_eq!(b.cost(33), U256::from(50));
+		assert_eq!(b.cost(&[0; 0]), U256::from(10));
+		assert_eq!(b.cost(&[0; 1]), U256::from(30));
+		assert_eq!(b.cost(&[0; 32]), U256::from(30));
+		assert_eq!(b.cost(&[0; 33]), U256::from(50));
 
 		let i = [0u8, 1, 2, 3];
 		let mut o = [255u8; 4];
-		b.execute(&i[..], &mut BytesRef::Fixed(&mut o[..]));
+		b.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
 		assert_eq!(i, o);
 	}
 
@@ -327,17 +700,256 @@ mod tests {
 			pricing: ethjson::spec::Pricing::Linear(ethjson::spec::Linear {
 				base: 10,
 				word: 20,
-			})
+			}),
+			activate_at: None,
 		});
 
-		assert_eq!(b.cost(0), U256::from(10));
-		assert_eq!(b.cost(1), U256::from(30));
-		assert_eq!(b.cost(32), U256::from(30));
-		assert_eq!(b.cost(33), U256::from(50));
+		assert_eq!(b.cost(&[0; 0]), U256::from(10));
+		assert_eq!(b.cost(&[0; 1]), U256::from(30));
+		assert_eq!(b.cost(&[0; 32]), U256::from(30));
+		assert_eq!(b.cost(&[0; 33]), U256::from(50));
 
 		let i = [0u8, 1, 2, 3];
 		let mut o = [255u8; 4];
-		b.execute(&i[..], &mut BytesRef::Fixed(&mut o[..]));
+		b.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
 		assert_eq!(i, o);
 	}
+
+	#[test]
+	fn activation() {
+		let b = Builtin::from(ethjson::spec::Builtin {
+			name: "identity".to_owned(),
+			pricing: ethjson::spec::Pricing::Linear(ethjson::spec::Linear { base: 10, word: 20 }),
+			activate_at: Some(ethjson::uint::Uint(U256::from(100))),
+		});
+
+		assert!(!b.is_active(99));
+		assert!(b.is_active(100));
+		assert!(b.is_active(101));
+	}
+
+	#[test]
+	fn from_registry() {
+		struct Double;
+		impl Impl for Double {
+			fn execute(&self, input: &[u8], output: &mut BytesRef) -> Result<(), Error> {
+				let doubled: Vec<u8> = input.iter().map(|b| b.wrapping_mul(2)).collect();
+				output.write(0, &doubled);
+				Ok(())
+			}
+		}
+
+		let mut registry = BuiltinRegistry::default();
+		registry.register_impl("double", || Box::new(Double));
+
+		let mut params = BTreeMap::new();
+		params.insert("base".to_owned(), ethjson::uint::Uint(U256::from(7)));
+		params.insert("word".to_owned(), ethjson::uint::Uint(U256::from(1)));
+		let b = registry.create(ethjson::spec::Builtin {
+			name: "double".to_owned(),
+			pricing: ethjson::spec::Pricing::Custom(ethjson::spec::Custom {
+				name: "linear".to_owned(),
+				params: Some(params),
+			}),
+			activate_at: None,
+		}).unwrap();
+
+		assert_eq!(b.cost(&[0; 33]), U256::from(9));
+		let i = [1u8, 2, 3];
+		let mut o = [0u8; 3];
+		b.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(o, [2u8, 4, 6]);
+
+		assert!(BuiltinRegistry::new().create(ethjson::spec::Builtin {
+			name: "double".to_owned(),
+			pricing: ethjson::spec::Pricing::Linear(ethjson::spec::Linear { base: 0, word: 0 }),
+			activate_at: None,
+		}).is_err());
+	}
+
+	#[test]
+	fn modexp() {
+		use rustc_serialize::hex::FromHex;
+		let f = ethereum_builtin("modexp");
+		let pricer = ModexpPricer { divisor: 20 };
+
+		// 3 ^ (p - 2) mod p = 1 for prime p.
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000020\
+			0000000000000000000000000000000000000000000000000000000000000020\
+			03\
+			fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e\
+			fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f").unwrap();
+		let mut o = [255u8; 32];
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(&o[..], &(FromHex::from_hex("0000000000000000000000000000000000000000000000000000000000000001").unwrap())[..]);
+		assert_eq!(pricer.cost(&i[..]), U256::from(13056));
+
+		// 2 ^ 10 mod 1000 = 24, padded to the modulus length.
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000002\
+			02\
+			0a\
+			03e8").unwrap();
+		let mut o = vec![];
+		f.execute(&i[..], &mut BytesRef::Flexible(&mut o)).expect("Builtin should not fail");
+		assert_eq!(o, vec![0x00, 0x18]);
+
+		// zero modulus.
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000000\
+			0000000000000000000000000000000000000000000000000000000000000020\
+			0000000000000000000000000000000000000000000000000000000000000020\
+			fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e").unwrap();
+		let mut o = [255u8; 32];
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(&o[..], &[0u8; 32][..]);
+
+		// empty base and modulus are free regardless of the exponent length.
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000000\
+			ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff\
+			0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+		assert_eq!(pricer.cost(&i[..]), U256::zero());
+		let mut o = vec![];
+		f.execute(&i[..], &mut BytesRef::Flexible(&mut o)).expect("Builtin should not fail");
+		assert!(o.is_empty());
+	}
+
+	#[test]
+	fn bn128_add() {
+		use rustc_serialize::hex::FromHex;
+		let f = ethereum_builtin("alt_bn128_add");
+
+		// zero + zero = zero
+		let i = [0u8; 128];
+		let mut o = [255u8; 64];
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(&o[..], &[0u8; 64][..]);
+
+		// G + G = 2G
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000002\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000002").unwrap();
+		let mut o = [255u8; 64];
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(&o[..], &(FromHex::from_hex("\
+			030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3\
+			15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4").unwrap())[..]);
+
+		// point not on curve
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000001").unwrap();
+		let mut o = [255u8; 64];
+		assert_eq!(f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])), Err(Error("Invalid curve point")));
+	}
+
+	#[test]
+	fn bn128_mul() {
+		use rustc_serialize::hex::FromHex;
+		let f = ethereum_builtin("alt_bn128_mul");
+
+		// 2 * G = 2G
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000002\
+			0000000000000000000000000000000000000000000000000000000000000002").unwrap();
+		let mut o = [255u8; 64];
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(&o[..], &(FromHex::from_hex("\
+			030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3\
+			15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4").unwrap())[..]);
+
+		// (r + 2) * G = 2G, scalars are reduced modulo the group order r
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000002\
+			30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000003").unwrap();
+		let mut o = [255u8; 64];
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(&o[..], &(FromHex::from_hex("\
+			030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3\
+			15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4").unwrap())[..]);
+
+		// 0 * G = zero
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000002").unwrap();
+		let mut o = [255u8; 64];
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(&o[..], &[0u8; 64][..]);
+	}
+
+	#[test]
+	fn bn128_pairing() {
+		use rustc_serialize::hex::FromHex;
+		let f = ethereum_builtin("alt_bn128_pairing");
+		let pricer = PairingPricer { base: 100000, pair: 80000 };
+
+		// empty input is trivially true.
+		let mut o = [255u8; 32];
+		f.execute(&[], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(o[31], 1);
+		assert_eq!(&o[..31], &[0u8; 31][..]);
+
+		// pairing of points at infinity.
+		let i = [0u8; 192];
+		let mut o = [255u8; 32];
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(o[31], 1);
+		assert_eq!(pricer.cost(&i[..]), U256::from(180000));
+
+		let i = [0u8; 191];
+		let mut o = [255u8; 32];
+		assert!(f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).is_err());
+
+		// e(G1, 2 * G2) * e(-2 * G1, G2) = 1
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000002\
+			203e205db4f19b37b60121b83a7333706db86431c6d835849957ed8c3928ad79\
+			27dc7234fd11d3e8c36c59277c3e6f149d5cd3cfa9a62aee49f8130962b4b3b9\
+			195e8aa5b7827463722b8c153931579d3505566b4edf48d498e185f0509de152\
+			04bb53b8977e5f92a0bc372742c4830944a59b4fe6b1c0466e2a6dad122b5d2e\
+			030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3\
+			1a76dae6d3272396d0cbe61fced2bc532edac647851e3ac53ce1cc9c7e645a83\
+			198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2\
+			1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed\
+			090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b\
+			12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa").unwrap();
+		let mut o = [255u8; 32];
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(o[31], 1);
+		assert_eq!(&o[..31], &[0u8; 31][..]);
+		assert_eq!(pricer.cost(&i[..]), U256::from(260000));
+
+		// e(G1, G2) != 1
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000002\
+			198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2\
+			1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed\
+			090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b\
+			12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa").unwrap();
+		let mut o = [255u8; 32];
+		f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])).expect("Builtin should not fail");
+		assert_eq!(&o[..], &[0u8; 32][..]);
+
+		// G2 point not on the twist curve
+		let i = FromHex::from_hex("\
+			0000000000000000000000000000000000000000000000000000000000000001\
+			0000000000000000000000000000000000000000000000000000000000000002\
+			198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2\
+			1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed\
+			090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b\
+			12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7dab").unwrap();
+		let mut o = [255u8; 32];
+		assert_eq!(f.execute(&i[..], &mut BytesRef::Fixed(&mut o[..])), Err(Error("Invalid b argument - not on curve")));
+	}
 }
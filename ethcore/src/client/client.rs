@@ -25,7 +25,7 @@ use time::precise_time_ns;
 use util::{Bytes, PerfTimer, Itertools, Mutex, RwLock};
 use util::{journaldb, TrieFactory, Trie};
 use util::trie::TrieSpec;
-use util::{U256, H256, Address, H2048, Uint, FixedHash};
+use util::{U256, H256, Address, H2048, Uint, FixedHash, Mismatch};
 use util::kvdb::*;
 
 // other
@@ -50,14 +50,14 @@ use verification::queue::BlockQueue;
 use blockchain::{BlockChain, BlockProvider, TreeRoute, ImportRoute};
 use client::{
 	BlockID, TransactionID, UncleID, TraceId, ClientConfig, BlockChainClient,
-	MiningBlockChainClient, TraceFilter, CallAnalytics, BlockImportError, Mode,
+	MiningBlockChainClient, TraceFilter, CallAnalytics, StateOverride, BlockImportError, Mode,
 	ChainNotify,
 };
 use client::Error as ClientError;
 use env_info::EnvInfo;
 use executive::{Executive, Executed, TransactOptions, contract_address};
 use receipt::LocalizedReceipt;
-use trace::{TraceDB, ImportRequest as TraceImportRequest, LocalizedTrace, Database as TraceDatabase};
+use trace::{TraceDB, ImportRequest as TraceImportRequest, LocalizedTrace, Database as TraceDatabase, GasProfile};
 use trace;
 use trace::FlatTransactionTraces;
 use evm::Factory as EvmFactory;
@@ -66,6 +66,7 @@ use snapshot::{self, io as snapshot_io};
 use factory::Factories;
 use rlp::{View, UntrustedRlp};
 use state_db::StateDB;
+use light::proof::RecordingDB;
 
 // re-export
 pub use types::blockchain_info::BlockChainInfo;
@@ -76,6 +77,7 @@ pub use verification::queue::QueueInfo as BlockQueueInfo;
 const MAX_TX_QUEUE_SIZE: usize = 4096;
 const MAX_QUEUE_SIZE_TO_SLEEP_ON: usize = 2;
 const MIN_HISTORY_SIZE: u64 = 8;
+const CODE_CACHE_FILE: &'static str = "code_cache.rlp";
 
 impl fmt::Display for BlockChainInfo {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -211,7 +213,10 @@ impl Client {
 		let awake = match config.mode { Mode::Dark(..) => false, _ => true };
 
 		let factories = Factories {
-			vm: EvmFactory::new(config.vm_type.clone(), config.jump_table_size),
+			vm: match config.persist_code_cache {
+				true => EvmFactory::with_cache_path(config.vm_type.clone(), config.jump_table_size, path.join(CODE_CACHE_FILE)),
+				false => EvmFactory::new(config.vm_type.clone(), config.jump_table_size),
+			},
 			trie: TrieFactory::new(trie_spec),
 			accountdb: Default::default(),
 		};
@@ -445,7 +450,11 @@ impl Client {
 			// Commit results
 			let receipts = ::rlp::decode(&receipts_bytes);
 			let mut batch = DBTransaction::new(&self.db.read());
-			chain.insert_unordered_block(&mut batch, &block_bytes, receipts, None, false, true);
+			if chain.first_block_parent().map_or(false, |p| p == hash) {
+				chain.insert_first_block_parent(&mut batch, &block_bytes, receipts);
+			} else {
+				chain.insert_unordered_block(&mut batch, &block_bytes, receipts, None, false, true);
+			}
 			// Final commit to the DB
 			self.db.read().write_buffered(batch);
 			chain.commit();
@@ -568,6 +577,39 @@ impl Client {
 		}
 	}
 
+	/// Execute transactions one after another on top of the state of the given block, in the
+	/// same environment as `call`, returning the result of each. Unlike `call`, nonces are checked
+	/// and senders' balances are not topped up. Nothing is committed.
+	///
+	/// Fails on the first transaction which could not be executed.
+	pub fn dry_run(&self, transactions: &[SignedTransaction], block: BlockID, analytics: CallAnalytics) -> Result<Vec<Executed>, CallError> {
+		let header = try!(self.block_header(block).ok_or(CallError::StatePruned));
+		let view = HeaderView::new(&header);
+		let mut env_info = EnvInfo {
+			number: view.number(),
+			author: view.author(),
+			timestamp: view.timestamp(),
+			difficulty: view.difficulty(),
+			last_hashes: self.build_last_hashes(view.hash()),
+			gas_used: U256::zero(),
+			gas_limit: U256::max_value(),
+		};
+		// that's just a copy of the state.
+		let mut state = try!(self.state_at(block).ok_or(CallError::StatePruned));
+
+		let mut results = Vec::with_capacity(transactions.len());
+		for t in transactions {
+			let original_state = if analytics.state_diffing { Some(state.clone()) } else { None };
+			let options = TransactOptions { tracing: analytics.transaction_tracing, vm_tracing: analytics.vm_tracing, check_nonce: true, step_logging: analytics.step_logging, gas_profiling: analytics.gas_profiling, state_accessing: analytics.state_accessing };
+			let mut ret = try!(Executive::new(&mut state, &env_info, &*self.engine, &self.factories.vm).transact(t, options));
+			ret.state_diff = original_state.map(|original| state.diff_from(original));
+			env_info.gas_used = env_info.gas_used + ret.gas_used;
+			results.push(ret);
+		}
+
+		Ok(results)
+	}
+
 	/// Get a copy of the best block's state.
 	pub fn state(&self) -> State {
 		let header = self.best_block_header();
@@ -677,6 +719,112 @@ impl Client {
 		Ok(())
 	}
 
+	/// Take a snapshot at a block whose state is no longer available in the database.
+	///
+	/// The state at the given block is reconstructed in a scratch database at `scratch_path`
+	/// by replaying canonical blocks on top of `base`, a snapshot of an earlier canonical block,
+	/// or on top of the genesis state if no base is given. The scratch database is left
+	/// for the caller to remove.
+	pub fn take_snapshot_replayed<W: snapshot_io::SnapshotWriter + Send>(
+		&self,
+		writer: W,
+		at: BlockID,
+		spec: &Spec,
+		base: Option<&snapshot_io::SnapshotReader>,
+		scratch_path: &Path,
+		p: &snapshot::Progress,
+	) -> Result<(), EthcoreError> {
+		let chain = self.chain.read().clone();
+		let target_hash = try!(Self::block_hash(&chain, at.clone()).ok_or(snapshot::Error::InvalidStartingBlock(at.clone())));
+		let target_number = try!(chain.block_number(&target_hash).ok_or(snapshot::Error::InvalidStartingBlock(at)));
+
+		// don't reuse the state of an earlier, interrupted replay.
+		if scratch_path.exists() {
+			try!(::std::fs::remove_dir_all(scratch_path));
+		}
+		let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+		let scratch = Arc::new(try!(Database::open(&db_config, &*scratch_path.to_string_lossy()).map_err(ClientError::Database)));
+
+		// seed the scratch database with the base state.
+		let base_number = match base {
+			Some(reader) => {
+				let manifest = reader.manifest();
+				if manifest.block_number > target_number || chain.block_hash(manifest.block_number) != Some(manifest.block_hash) {
+					return Err(snapshot::Error::ReplayBaseNotAncestor(manifest.block_hash).into());
+				}
+
+				info!("Restoring base state from snapshot at block #{}", manifest.block_number);
+				let mut rebuilder = snapshot::StateRebuilder::new(scratch.clone(), journaldb::Algorithm::OverlayRecent);
+				for &state_hash in &manifest.state_hashes {
+					let chunk = try!(reader.chunk(state_hash).map_err(snapshot::Error::Io));
+					let raw = try!(::util::snappy::decompress(&chunk));
+					try!(rebuilder.feed(&raw));
+				}
+
+				if rebuilder.state_root() != manifest.state_root {
+					return Err(BlockError::InvalidStateRoot(Mismatch { expected: manifest.state_root, found: rebuilder.state_root() }).into());
+				}
+				try!(rebuilder.check_missing());
+
+				manifest.block_number
+			}
+			None => {
+				info!("Building genesis state");
+				let mut state_db = StateDB::new(journaldb::new(scratch.clone(), journaldb::Algorithm::OverlayRecent, ::db::COL_STATE), 0);
+				try!(spec.ensure_db_good(&mut state_db));
+				let mut batch = DBTransaction::new(&scratch);
+				try!(state_db.journal_under(&mut batch, 0, &spec.genesis_header().hash()));
+				try!(scratch.write(batch).map_err(ClientError::Database));
+
+				0
+			}
+		};
+
+		// replay all blocks between the base and the target.
+		let mut state_db = StateDB::new(journaldb::new(scratch.clone(), journaldb::Algorithm::OverlayRecent, ::db::COL_STATE), 0);
+		let mut last_hashes = VecDeque::new();
+		for n in base_number.saturating_sub(255)..(base_number + 1) {
+			last_hashes.push_front(try!(chain.block_hash(n).ok_or(snapshot::Error::IncompleteChain)));
+		}
+
+		info!("Replaying {} blocks to reconstruct the state at block #{}", target_number - base_number, target_number);
+		for number in (base_number + 1)..(target_number + 1) {
+			let hash = try!(chain.block_hash(number).ok_or(snapshot::Error::IncompleteChain));
+			let bytes = try!(chain.block(&hash).ok_or(snapshot::Error::BlockNotFound(hash)));
+			let parent = try!(chain.block_header(&last_hashes[0]).ok_or(snapshot::Error::BlockNotFound(last_hashes[0])));
+
+			let view = BlockView::new(&bytes);
+			let header = view.header();
+
+			let mut hashes = Vec::from(last_hashes.clone());
+			hashes.resize(256, H256::default());
+
+			let locked_block = try!(enact(&header, &view.transactions(), &view.uncles(), &*self.engine, false, state_db, &parent, Arc::new(hashes), self.factories.clone()));
+			try!(self.verifier.verify_block_final(&header, locked_block.block().header()));
+
+			let mut batch = DBTransaction::new(&scratch);
+			state_db = locked_block.drain();
+			try!(state_db.journal_under(&mut batch, number, &hash));
+			try!(state_db.mark_canonical(&mut batch, number - 1, &last_hashes[0]));
+			scratch.write_buffered(batch);
+
+			if last_hashes.len() > 255 {
+				last_hashes.pop_back();
+			}
+			last_hashes.push_front(hash);
+
+			if number % 10000 == 0 {
+				try!(scratch.flush().map_err(ClientError::Database));
+				info!("Replayed block #{}/{}", number, target_number);
+			}
+		}
+		try!(scratch.flush().map_err(ClientError::Database));
+
+		try!(snapshot::take_snapshot(&chain, target_hash, state_db.journal_db().as_hashdb(), writer, p));
+
+		Ok(())
+	}
+
 	/// Ask the client what the history parameter is.
 	pub fn pruning_history(&self) -> u64 {
 		self.history
@@ -748,7 +896,7 @@ impl snapshot::DatabaseRestore for Client {
 
 
 impl BlockChainClient for Client {
-	fn call(&self, t: &SignedTransaction, block: BlockID, analytics: CallAnalytics) -> Result<Executed, CallError> {
+	fn call(&self, t: &SignedTransaction, block: BlockID, state_override: &StateOverride, analytics: CallAnalytics) -> Result<Executed, CallError> {
 		let header = try!(self.block_header(block).ok_or(CallError::StatePruned));
 		let view = HeaderView::new(&header);
 		let last_hashes = self.build_last_hashes(view.hash());
@@ -763,6 +911,7 @@ impl BlockChainClient for Client {
 		};
 		// that's just a copy of the state.
 		let mut state = try!(self.state_at(block).ok_or(CallError::StatePruned));
+		state.apply_override(state_override);
 		let original_state = if analytics.state_diffing { Some(state.clone()) } else { None };
 
 		let sender = try!(t.sender().map_err(|e| {
@@ -775,7 +924,7 @@ impl BlockChainClient for Client {
 			// give the sender a sufficient balance
 			state.add_balance(&sender, &(needed_balance - balance));
 		}
-		let options = TransactOptions { tracing: analytics.transaction_tracing, vm_tracing: analytics.vm_tracing, check_nonce: false };
+		let options = TransactOptions { tracing: analytics.transaction_tracing, vm_tracing: analytics.vm_tracing, check_nonce: false, step_logging: analytics.step_logging, gas_profiling: analytics.gas_profiling, state_accessing: analytics.state_accessing };
 		let mut ret = try!(Executive::new(&mut state, &env_info, &*self.engine, &self.factories.vm).transact(t, options));
 
 		// TODO gav move this into Executive.
@@ -795,7 +944,7 @@ impl BlockChainClient for Client {
 			return Err(CallError::TransactionNotFound);
 		}
 
-		let options = TransactOptions { tracing: analytics.transaction_tracing, vm_tracing: analytics.vm_tracing, check_nonce: false };
+		let options = TransactOptions { tracing: analytics.transaction_tracing, vm_tracing: analytics.vm_tracing, check_nonce: false, step_logging: analytics.step_logging, gas_profiling: analytics.gas_profiling, state_accessing: analytics.state_accessing };
 		let view = HeaderView::new(&header_data);
 		let last_hashes = self.build_last_hashes(view.hash());
 		let mut env_info = EnvInfo {
@@ -822,6 +971,40 @@ impl BlockChainClient for Client {
 		Ok(ret)
 	}
 
+	fn profile(&self, from: BlockID, to: BlockID) -> Result<GasProfile, CallError> {
+		let from = try!(self.block_number(from).ok_or(CallError::StatePruned));
+		let to = try!(self.block_number(to).ok_or(CallError::StatePruned));
+		let mut profile = GasProfile::default();
+
+		for number in from..to + 1 {
+			let id = BlockID::Number(number);
+			let header_data = try!(self.block_header(id).ok_or(CallError::StatePruned));
+			let body_data = try!(self.block_body(id).ok_or(CallError::StatePruned));
+			let mut state = try!(self.state_at_beginning(id).ok_or(CallError::StatePruned));
+
+			let view = HeaderView::new(&header_data);
+			let mut env_info = EnvInfo {
+				number: view.number(),
+				author: view.author(),
+				timestamp: view.timestamp(),
+				difficulty: view.difficulty(),
+				last_hashes: self.build_last_hashes(view.hash()),
+				gas_used: U256::default(),
+				gas_limit: view.gas_limit(),
+			};
+			for t in BodyView::new(&body_data).transactions() {
+				let options = TransactOptions { tracing: false, vm_tracing: false, check_nonce: false, step_logging: None, gas_profiling: true, state_accessing: false };
+				let executed = try!(Executive::new(&mut state, &env_info, &*self.engine, &self.factories.vm).transact(&t, options));
+				env_info.gas_used = env_info.gas_used + executed.gas_used;
+				if let Some(p) = executed.gas_profile {
+					profile.merge(p);
+				}
+			}
+		}
+
+		Ok(profile)
+	}
+
 	fn keep_alive(&self) {
 		if self.mode != Mode::Active {
 			self.wake_up();
@@ -1007,6 +1190,62 @@ impl BlockChainClient for Client {
 		self.chain.read().block_receipts(hash).map(|receipts| ::rlp::encode(&receipts).to_vec())
 	}
 
+	fn prove_account(&self, block: H256, account_key: H256, from_level: u32) -> Option<Vec<Bytes>> {
+		self.state_at(BlockID::Hash(block)).and_then(|s| s.prove_account(account_key, from_level).ok())
+	}
+
+	fn prove_storage(&self, block: H256, account_key: H256, key: H256, from_level: u32) -> Option<Vec<Bytes>> {
+		self.state_at(BlockID::Hash(block)).and_then(|s| s.prove_storage(account_key, key, from_level).ok())
+	}
+
+	fn contract_code(&self, block: H256, account_key: H256) -> Option<Bytes> {
+		self.state_at(BlockID::Hash(block)).and_then(|s| s.code_by_address_hash(account_key).ok()).and_then(|c| c)
+	}
+
+	fn prove_transaction(&self, t: SignedTransaction, block: H256) -> Option<Vec<Bytes>> {
+		// make sure the state is still available before recording anything.
+		if self.state_at(BlockID::Hash(block)).is_none() {
+			return None;
+		}
+		let header = match self.block_header(BlockID::Hash(block)) {
+			Some(header) => header,
+			None => return None,
+		};
+		let view = HeaderView::new(&header);
+
+		// a fresh `StateDB` without a shared cache, so every node read goes through the recorder.
+		let recorder = RecordingDB::new(self.state_db.lock().journal_db().boxed_clone());
+		let recorded = recorder.recorded();
+		let mut state = match State::from_existing(StateDB::new_without_bloom(Box::new(recorder), 0), view.state_root(), self.engine.account_start_nonce(), self.factories.clone()) {
+			Ok(state) => state,
+			Err(_) => return None,
+		};
+
+		let env_info = EnvInfo {
+			number: view.number(),
+			author: view.author(),
+			timestamp: view.timestamp(),
+			difficulty: view.difficulty(),
+			last_hashes: self.build_last_hashes(view.hash()),
+			gas_used: U256::zero(),
+			gas_limit: U256::max_value(),
+		};
+		let sender = match t.sender() {
+			Ok(sender) => sender,
+			Err(_) => return None,
+		};
+		let balance = state.balance(&sender);
+		let needed_balance = t.value + t.gas * t.gas_price;
+		if balance < needed_balance {
+			state.add_balance(&sender, &(needed_balance - balance));
+		}
+		let options = TransactOptions { tracing: false, vm_tracing: false, check_nonce: false, step_logging: None, gas_profiling: false, state_accessing: false };
+		let _ = Executive::new(&mut state, &env_info, &*self.engine, &self.factories.vm).transact(&t, options);
+
+		let proof = recorded.lock().values().cloned().collect();
+		Some(proof)
+	}
+
 	fn import_block(&self, bytes: Bytes) -> Result<H256, BlockImportError> {
 		use verification::queue::kind::HasHash;
 		use verification::queue::kind::blocks::Unverified;
@@ -1029,10 +1268,16 @@ impl BlockChainClient for Client {
 		{
 			// check block order
 			let header = BlockView::new(&block_bytes).header_view();
-			if self.chain.read().is_known(&header.hash()) {
-				return Err(BlockImportError::Import(ImportError::AlreadyInChain));
-			}
-			if self.block_status(BlockID::Hash(header.parent_hash())) == BlockStatus::Unknown {
+			let extends_first = {
+				let chain = self.chain.read();
+				if chain.is_known(&header.hash()) {
+					return Err(BlockImportError::Import(ImportError::AlreadyInChain));
+				}
+				// blocks below the gap may be imported backwards, down to the best ancient block.
+				chain.first_block_parent().map_or(false, |p| p == header.hash())
+					&& chain.best_ancient_number().map_or(true, |n| n + 1 < header.number())
+			};
+			if !extends_first && self.block_status(BlockID::Hash(header.parent_hash())) == BlockStatus::Unknown {
 				return Err(BlockImportError::Block(BlockError::UnknownParent(header.parent_hash())));
 			}
 		}
@@ -1138,6 +1383,10 @@ impl BlockChainClient for Client {
 	fn pending_transactions(&self) -> Vec<SignedTransaction> {
 		self.miner.pending_transactions(self.chain.read().best_block_number())
 	}
+
+	fn is_local_transaction(&self, hash: &H256) -> bool {
+		self.miner.is_local_transaction(hash)
+	}
 }
 
 impl MiningBlockChainClient for Client {
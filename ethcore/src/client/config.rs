@@ -115,6 +115,8 @@ pub struct ClientConfig {
 	pub state_cache_size: usize,
 	/// EVM jump-tables cache size.
 	pub jump_table_size: usize,
+	/// Keep the EVM's analysed code cache in the database directory across restarts.
+	pub persist_code_cache: bool,
 	/// State pruning history size.
 	pub history: u64,
 	/// Check seal valididity on block import
@@ -32,7 +32,8 @@ pub use executive::{Executed, Executive, TransactOptions};
 pub use env_info::{LastHashes, EnvInfo};
 pub use self::chain_notify::ChainNotify;
 
-pub use types::call_analytics::CallAnalytics;
+pub use types::call_analytics::{CallAnalytics, StepLogOptions};
+pub use types::state_override::{StateOverride, AccountOverride};
 pub use block_import_error::BlockImportError;
 pub use transaction_import::TransactionImportResult;
 pub use transaction_import::TransactionImportError;
@@ -25,7 +25,7 @@ use transaction::{Transaction, LocalizedTransaction, SignedTransaction, Action};
 use blockchain::TreeRoute;
 use client::{
 	BlockChainClient, MiningBlockChainClient, BlockChainInfo, BlockStatus, BlockID,
-	TransactionID, UncleID, TraceId, TraceFilter, LastHashes, CallAnalytics, BlockImportError,
+	TransactionID, UncleID, TraceId, TraceFilter, LastHashes, CallAnalytics, StateOverride, BlockImportError,
 };
 use db::{NUM_COLUMNS, COL_STATE};
 use header::{Header as BlockHeader, BlockNumber};
@@ -42,7 +42,7 @@ use verification::queue::QueueInfo;
 use block::{OpenBlock, SealedBlock};
 use executive::Executed;
 use error::CallError;
-use trace::LocalizedTrace;
+use trace::{LocalizedTrace, GasProfile};
 use state_db::StateDB;
 
 /// Test client.
@@ -69,6 +69,8 @@ pub struct TestBlockChainClient {
 	pub code: RwLock<HashMap<Address, Bytes>>,
 	/// Execution result.
 	pub execution_result: RwLock<Option<Result<Executed, CallError>>>,
+	/// State override given to the last call.
+	pub last_state_override: RwLock<Option<StateOverride>>,
 	/// Transaction receipts.
 	pub receipts: RwLock<HashMap<TransactionID, LocalizedReceipt>>,
 	/// Logs
@@ -125,6 +127,7 @@ impl TestBlockChainClient {
 			storage: RwLock::new(HashMap::new()),
 			code: RwLock::new(HashMap::new()),
 			execution_result: RwLock::new(None),
+			last_state_override: RwLock::new(None),
 			receipts: RwLock::new(HashMap::new()),
 			logs: RwLock::new(Vec::new()),
 			queue_size: AtomicUsize::new(0),
@@ -277,6 +280,20 @@ impl TestBlockChainClient {
 
 	/// Inserts a transaction to miners transactions queue.
 	pub fn insert_transaction_to_queue(&self) {
+		let signed_tx = self.new_funded_transaction();
+		let res = self.miner.import_external_transactions(self, vec![signed_tx]);
+		let res = res.into_iter().next().unwrap().expect("Successful import");
+		assert_eq!(res, TransactionImportResult::Current);
+	}
+
+	/// Inserts a transaction submitted to this node to miners transactions queue.
+	pub fn insert_local_transaction_to_queue(&self) {
+		let signed_tx = self.new_funded_transaction();
+		let res = self.miner.import_own_transaction(self, signed_tx).expect("Successful import");
+		assert_eq!(res, TransactionImportResult::Current);
+	}
+
+	fn new_funded_transaction(&self) -> SignedTransaction {
 		let keypair = Random.generate().unwrap();
 		let tx = Transaction {
 			action: Action::Create,
@@ -288,9 +305,7 @@ impl TestBlockChainClient {
 		};
 		let signed_tx = tx.sign(keypair.secret());
 		self.set_balance(signed_tx.sender().unwrap(), 10_000_000.into());
-		let res = self.miner.import_external_transactions(self, vec![signed_tx]);
-		let res = res.into_iter().next().unwrap().expect("Successful import");
-		assert_eq!(res, TransactionImportResult::Current);
+		signed_tx
 	}
 }
 
@@ -340,7 +355,8 @@ impl MiningBlockChainClient for TestBlockChainClient {
 }
 
 impl BlockChainClient for TestBlockChainClient {
-	fn call(&self, _t: &SignedTransaction, _block: BlockID, _analytics: CallAnalytics) -> Result<Executed, CallError> {
+	fn call(&self, _t: &SignedTransaction, _block: BlockID, state_override: &StateOverride, _analytics: CallAnalytics) -> Result<Executed, CallError> {
+		*self.last_state_override.write() = Some(state_override.clone());
 		self.execution_result.read().clone().unwrap()
 	}
 
@@ -348,6 +364,10 @@ impl BlockChainClient for TestBlockChainClient {
 		self.execution_result.read().clone().unwrap()
 	}
 
+	fn profile(&self, _from: BlockID, _to: BlockID) -> Result<GasProfile, CallError> {
+		self.execution_result.read().clone().unwrap().map(|e| e.gas_profile.unwrap_or_default())
+	}
+
 	fn block_total_difficulty(&self, _id: BlockID) -> Option<U256> {
 		Some(U256::zero())
 	}
@@ -491,6 +511,22 @@ impl BlockChainClient for TestBlockChainClient {
 	}
 
 	// TODO: returns just hashes instead of node state rlp(?)
+	fn prove_account(&self, _block: H256, _account_key: H256, _from_level: u32) -> Option<Vec<Bytes>> {
+		None
+	}
+
+	fn prove_storage(&self, _block: H256, _account_key: H256, _key: H256, _from_level: u32) -> Option<Vec<Bytes>> {
+		None
+	}
+
+	fn contract_code(&self, _block: H256, _account_key: H256) -> Option<Bytes> {
+		None
+	}
+
+	fn prove_transaction(&self, _t: SignedTransaction, _block: H256) -> Option<Vec<Bytes>> {
+		None
+	}
+
 	fn state_data(&self, hash: &H256) -> Option<Bytes> {
 		// starts with 'f' ?
 		if *hash > H256::from("f000000000000000000000000000000000000000000000000000000000000000") {
@@ -621,4 +657,8 @@ impl BlockChainClient for TestBlockChainClient {
 	fn pending_transactions(&self) -> Vec<SignedTransaction> {
 		self.miner.pending_transactions(self.chain_info().best_block_number)
 	}
+
+	fn is_local_transaction(&self, hash: &H256) -> bool {
+		self.miner.is_local_transaction(hash)
+	}
 }
@@ -26,13 +26,14 @@ use filter::Filter;
 use views::{BlockView};
 use error::{ImportResult, CallError};
 use receipt::LocalizedReceipt;
-use trace::LocalizedTrace;
+use trace::{LocalizedTrace, GasProfile};
 use evm::Factory as EvmFactory;
 use types::ids::*;
 use types::trace_filter::Filter as TraceFilter;
 use executive::Executed;
 use env_info::LastHashes;
 use types::call_analytics::CallAnalytics;
+use types::state_override::StateOverride;
 use block_import_error::BlockImportError;
 use ipc::IpcConfig;
 use types::blockchain_info::BlockChainInfo;
@@ -136,6 +137,21 @@ pub trait BlockChainClient : Sync + Send {
 	/// Get raw block receipts data by block header hash.
 	fn block_receipts(&self, hash: &H256) -> Option<Bytes>;
 
+	/// Prove an account's existence or nonexistence in the state of the given block.
+	/// `account_key` is the hash of the address. Returns the encoded trie nodes,
+	/// omitting those at depths below `from_level`, or `None` if the state is unavailable.
+	fn prove_account(&self, block: H256, account_key: H256, from_level: u32) -> Option<Vec<Bytes>>;
+
+	/// Prove a storage value's existence or nonexistence in the state of the given block.
+	/// `account_key` and `key` are the hashes of the address and storage key respectively.
+	fn prove_storage(&self, block: H256, account_key: H256, key: H256, from_level: u32) -> Option<Vec<Bytes>>;
+
+	/// Get the code of an account in the state of the given block by the hash of its address.
+	fn contract_code(&self, block: H256, account_key: H256) -> Option<Bytes>;
+
+	/// Get the state trie nodes needed to execute a transaction on top of the state of the given block.
+	fn prove_transaction(&self, t: SignedTransaction, block: H256) -> Option<Vec<Bytes>>;
+
 	/// Import a block into the blockchain.
 	fn import_block(&self, bytes: Bytes) -> Result<H256, BlockImportError>;
 
@@ -163,12 +179,15 @@ pub trait BlockChainClient : Sync + Send {
 	/// Returns logs matching given filter.
 	fn logs(&self, filter: Filter) -> Vec<LocalizedLogEntry>;
 
-	/// Makes a non-persistent transaction call.
-	fn call(&self, t: &SignedTransaction, block: BlockID, analytics: CallAnalytics) -> Result<Executed, CallError>;
+	/// Makes a non-persistent transaction call, against the state of the given block with `state_override` applied.
+	fn call(&self, t: &SignedTransaction, block: BlockID, state_override: &StateOverride, analytics: CallAnalytics) -> Result<Executed, CallError>;
 
 	/// Replays a given transaction for inspection.
 	fn replay(&self, t: TransactionID, analytics: CallAnalytics) -> Result<Executed, CallError>;
 
+	/// Replays all transactions in the given range of blocks, profiling the gas spent by executed code.
+	fn profile(&self, from: BlockID, to: BlockID) -> Result<GasProfile, CallError>;
+
 	/// Returns traces matching given filter.
 	fn filter_traces(&self, filter: TraceFilter) -> Option<Vec<LocalizedTrace>>;
 
@@ -190,6 +209,9 @@ pub trait BlockChainClient : Sync + Send {
 	/// list all transactions
 	fn pending_transactions(&self) -> Vec<SignedTransaction>;
 
+	/// Returns true if pending transaction with given hash was submitted to this node.
+	fn is_local_transaction(&self, hash: &H256) -> bool;
+
 	/// Get the gas price distribution.
 	fn gas_price_statistics(&self, sample_size: usize, distribution_size: usize) -> Result<Vec<U256>, ()> {
 		let mut h = self.chain_info().best_block_hash;
@@ -54,8 +54,10 @@ impl Engine for InstantSeal {
 		&self.builtins
 	}
 
-	fn schedule(&self, _env_info: &EnvInfo) -> Schedule {
-		Schedule::new_homestead()
+	fn schedule(&self, env_info: &EnvInfo) -> Schedule {
+		let mut schedule = Schedule::new_homestead();
+		self.params.override_schedule(env_info.number, &mut schedule);
+		schedule
 	}
 
 	fn is_sealer(&self, _author: &Address) -> Option<bool> { Some(true) }
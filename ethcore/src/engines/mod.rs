@@ -27,12 +27,12 @@ pub use self::basic_authority::BasicAuthority;
 use util::*;
 use account_provider::AccountProvider;
 use block::ExecutedBlock;
-use builtin::Builtin;
+use builtin::{self, Builtin};
 use env_info::EnvInfo;
 use error::Error;
 use spec::CommonParams;
 use evm::Schedule;
-use header::Header;
+use header::{Header, BlockNumber};
 use transaction::SignedTransaction;
 
 /// A consensus mechanism for the chain. Generally either proof-of-work or proof-of-stake-based.
@@ -126,15 +126,19 @@ pub trait Engine : Sync + Send {
 	// from Spec into here and removing the Spec::builtins field.
 	/// Determine whether a particular address is a builtin contract.
 	fn is_builtin(&self, a: &Address) -> bool { self.builtins().contains_key(a) }
+	/// Determine whether a particular address is a builtin contract active at block `number`.
+	fn is_active_builtin(&self, a: &Address, number: BlockNumber) -> bool {
+		self.builtins().get(a).map_or(false, |b| b.is_active(number))
+	}
 	/// Determine the code execution cost of the builtin contract with address `a`.
 	/// Panics if `is_builtin(a)` is not true.
 	fn cost_of_builtin(&self, a: &Address, input: &[u8]) -> U256 {
-		self.builtins().get(a).expect("queried cost of nonexistent builtin").cost(input.len())
+		self.builtins().get(a).expect("queried cost of nonexistent builtin").cost(input)
 	}
 	/// Execution the builtin contract `a` on `input` and return `output`.
 	/// Panics if `is_builtin(a)` is not true.
-	fn execute_builtin(&self, a: &Address, input: &[u8], output: &mut BytesRef) {
-		self.builtins().get(a).expect("attempted to execute nonexistent builtin").execute(input, output);
+	fn execute_builtin(&self, a: &Address, input: &[u8], output: &mut BytesRef) -> Result<(), builtin::Error> {
+		self.builtins().get(a).expect("attempted to execute nonexistent builtin").execute(input, output)
 	}
 
 	// TODO: sealing stuff - though might want to leave this for later.
@@ -127,13 +127,15 @@ impl Engine for Ethash {
 	fn schedule(&self, env_info: &EnvInfo) -> Schedule {
 		trace!(target: "client", "Creating schedule. fCML={}, bGCML={}", self.ethash_params.homestead_transition, self.ethash_params.eip150_transition);
 
-		if env_info.number < self.ethash_params.homestead_transition {
+		let mut schedule = if env_info.number < self.ethash_params.homestead_transition {
 			Schedule::new_frontier()
 		} else if env_info.number < self.ethash_params.eip150_transition {
 			Schedule::new_homestead()
 		} else {
 			Schedule::new_homestead_gas_fix()
-		}
+		};
+		self.params.override_schedule(env_info.number, &mut schedule);
+		schedule
 	}
 
 	fn populate_from_parent(&self, header: &mut Header, parent: &Header, gas_floor_target: U256, gas_ceil_target: U256) {
@@ -18,6 +18,7 @@
 
 use util::*;
 use evm::{self, Schedule};
+use evm::interpreter::debugger::Step;
 use env_info::*;
 use types::executed::CallType;
 
@@ -117,4 +118,10 @@ pub trait Ext {
 
 	/// Trace the finalised execution of a single instruction.
 	fn trace_executed(&mut self, _gas_used: U256, _stack_push: &[U256], _mem_diff: Option<(usize, &[u8])>, _store_diff: Option<(U256, U256)>) {}
+
+	/// Returns true if `trace_step` should be called before every instruction.
+	fn trace_steps(&self) -> bool { false }
+
+	/// Trace the full interpreter state before executing a single instruction. Passthrough for the VM trace.
+	fn trace_step(&mut self, _step: &Step) {}
 }
@@ -18,6 +18,7 @@
 //!
 //! TODO: consider spliting it into two separate files.
 use std::fmt;
+use std::path::PathBuf;
 use std::sync::Arc;
 use evm::Evm;
 use util::{U256, Uint};
@@ -119,7 +120,7 @@ impl Factory {
 	}
 
 	/// Create new instance of specific `VMType` factory, with a size in bytes
-	/// for caching jump destinations.
+	/// for caching analysed code.
 	pub fn new(evm: VMType, cache_size: usize) -> Self {
 		Factory {
 			evm: evm,
@@ -127,6 +128,15 @@ impl Factory {
 		}
 	}
 
+	/// Create new instance of specific `VMType` factory, with a size in bytes
+	/// for caching analysed code and a file the cache is kept in across runs.
+	pub fn with_cache_path(evm: VMType, cache_size: usize, cache_path: PathBuf) -> Self {
+		Factory {
+			evm: evm,
+			evm_cache: Arc::new(SharedCache::persistent(cache_size, cache_path)),
+		}
+	}
+
 	fn can_fit_in_usize(gas: U256) -> bool {
 		gas == U256::from(gas.low_u64() as usize)
 	}
@@ -0,0 +1,272 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Static analysis of contract code.
+
+use std::mem;
+use rlp::*;
+use bit_set::BitSet;
+use evm::instructions::{self, Instruction, InstructionInfo};
+use evm::schedule::Schedule;
+
+/// Number of price tiers with a fixed price per instruction (`Zero` to `Ext`).
+const FIXED_TIERS: usize = 7;
+
+/// Version of the analysis. Must be increased whenever `AnalysedCode::new` gives different results,
+/// so that analyses persisted by older versions are not used.
+pub const ANALYSIS_VERSION: u32 = 1;
+
+/// A run of consecutive instructions with fixed gas prices which, unless execution fails,
+/// is always executed from its first instruction to its last.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BasicBlock {
+	/// Position of the first instruction.
+	pub start: usize,
+	/// Position just after the last instruction.
+	pub end: usize,
+	/// Whether the block starts with `JUMPDEST`.
+	pub jumpdest: bool,
+	/// Number of instructions in each fixed price tier.
+	pub tiers: [usize; FIXED_TIERS],
+}
+
+impl BasicBlock {
+	/// Gas used by all instructions of the block under the given schedule.
+	pub fn static_gas(&self, schedule: &Schedule) -> usize {
+		let jumpdest_gas = if self.jumpdest { schedule.jumpdest_gas } else { 0 };
+		self.tiers.iter()
+			.zip(schedule.tier_step_gas.iter())
+			.fold(jumpdest_gas, |gas, (count, price)| gas + count * price)
+	}
+}
+
+impl Encodable for BasicBlock {
+	fn rlp_append(&self, s: &mut RlpStream) {
+		s.begin_list(3 + FIXED_TIERS);
+		s.append(&self.start);
+		s.append(&self.end);
+		s.append(&self.jumpdest);
+		for count in &self.tiers {
+			s.append(count);
+		}
+	}
+}
+
+impl Decodable for BasicBlock {
+	fn decode<D>(decoder: &D) -> Result<Self, DecoderError> where D: Decoder {
+		let d = decoder.as_rlp();
+		if d.item_count() != 3 + FIXED_TIERS {
+			return Err(DecoderError::RlpIncorrectListLen);
+		}
+		let mut block = BasicBlock {
+			start: try!(d.val_at(0)),
+			end: try!(d.val_at(1)),
+			jumpdest: try!(d.val_at(2)),
+			tiers: [0; FIXED_TIERS],
+		};
+		for (i, count) in block.tiers.iter_mut().enumerate() {
+			*count = try!(d.val_at(3 + i));
+		}
+		Ok(block)
+	}
+}
+
+/// Results of analysing a piece of code before executing it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AnalysedCode {
+	/// Positions of valid jump destinations.
+	pub jump_destinations: BitSet,
+	/// Basic blocks, ordered by position.
+	pub blocks: Vec<BasicBlock>,
+}
+
+impl AnalysedCode {
+	/// Analyse the given code.
+	pub fn new(code: &[u8]) -> Self {
+		let infos = &*instructions::INSTRUCTIONS;
+		let mut jump_destinations = BitSet::with_capacity(code.len());
+		let mut blocks = Vec::new();
+		let mut block: Option<BasicBlock> = None;
+		let mut position = 0;
+
+		while position < code.len() {
+			let instruction = code[position];
+			let next = match instructions::is_push(instruction) {
+				true => position + 1 + instructions::get_push_bytes(instruction),
+				false => position + 1,
+			};
+
+			if instruction == instructions::JUMPDEST {
+				jump_destinations.insert(position);
+				blocks.extend(block.take());
+				block = Some(BasicBlock { start: position, end: next, jumpdest: true, ..Default::default() });
+			} else {
+				match fixed_tier(instruction, &infos[instruction as usize]) {
+					Some(tier) => {
+						let b = block.take().unwrap_or_else(|| BasicBlock { start: position, ..Default::default() });
+						let mut b = BasicBlock { end: next, ..b };
+						b.tiers[tier] += 1;
+						block = Some(b);
+					},
+					None => blocks.extend(block.take()),
+				}
+			}
+
+			if ends_block(instruction) {
+				blocks.extend(block.take());
+			}
+			position = next;
+		}
+		blocks.extend(block.take());
+
+		jump_destinations.shrink_to_fit();
+		blocks.shrink_to_fit();
+		AnalysedCode {
+			jump_destinations: jump_destinations,
+			blocks: blocks,
+		}
+	}
+
+	/// The basic block starting at the given position, if any.
+	pub fn block_at(&self, position: usize) -> Option<&BasicBlock> {
+		self.blocks.binary_search_by_key(&position, |b| b.start).ok().map(|i| &self.blocks[i])
+	}
+
+	/// Whether the analysis could have been made for code of the given length: jump destinations
+	/// and basic blocks start within the code, and blocks are ordered and don't overlap.
+	pub fn is_consistent(&self, code_len: usize) -> bool {
+		let jumps_valid = self.jump_destinations.iter().all(|p| p < code_len);
+		let mut next_start = 0;
+		let blocks_valid = self.blocks.iter().all(|b| {
+			let valid = b.start >= next_start && b.start < b.end && b.start < code_len;
+			next_start = b.end;
+			valid
+		});
+		jumps_valid && blocks_valid
+	}
+
+	/// Approximate size of the analysis in memory, in bytes.
+	pub fn heap_size(&self) -> usize {
+		self.jump_destinations.capacity() / 8 + self.blocks.capacity() * mem::size_of::<BasicBlock>()
+	}
+}
+
+impl Encodable for AnalysedCode {
+	fn rlp_append(&self, s: &mut RlpStream) {
+		s.begin_list(2);
+		s.append(&self.jump_destinations.get_ref().to_bytes());
+		s.append(&self.blocks);
+	}
+}
+
+impl Decodable for AnalysedCode {
+	fn decode<D>(decoder: &D) -> Result<Self, DecoderError> where D: Decoder {
+		let d = decoder.as_rlp();
+		let jump_destinations: Vec<u8> = try!(d.val_at(0));
+		Ok(AnalysedCode {
+			jump_destinations: BitSet::from_bytes(&jump_destinations),
+			blocks: try!(d.val_at(1)),
+		})
+	}
+}
+
+/// Gas price of an instruction that may be part of a basic block.
+pub fn fixed_gas(instruction: Instruction, info: &InstructionInfo, schedule: &Schedule) -> usize {
+	match instruction {
+		instructions::JUMPDEST => schedule.jumpdest_gas,
+		_ => schedule.tier_step_gas[instructions::get_tier_idx(info.tier)],
+	}
+}
+
+/// Price tier of an instruction which always costs its tier's price and does not observe
+/// the gas left. Must agree with `Gasometer::get_gas_cost_mem`.
+fn fixed_tier(instruction: Instruction, info: &InstructionInfo) -> Option<usize> {
+	match instruction {
+		instructions::SSTORE | instructions::SLOAD | instructions::BALANCE | instructions::EXTCODESIZE |
+		instructions::SUICIDE | instructions::MSTORE | instructions::MLOAD | instructions::MSTORE8 |
+		instructions::RETURN | instructions::SHA3 | instructions::CALLDATACOPY | instructions::CODECOPY |
+		instructions::EXTCODECOPY | instructions::LOG0...instructions::LOG4 | instructions::CALL |
+		instructions::CALLCODE | instructions::DELEGATECALL | instructions::CREATE | instructions::EXP |
+		instructions::GAS => None,
+		_ => match instructions::get_tier_idx(info.tier) {
+			tier if tier < FIXED_TIERS => Some(tier),
+			_ => None,
+		},
+	}
+}
+
+/// Whether execution may not continue with the next instruction.
+fn ends_block(instruction: Instruction) -> bool {
+	match instruction {
+		instructions::JUMP | instructions::JUMPI | instructions::STOP => true,
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rlp;
+	use util::FromHex;
+	use evm::schedule::Schedule;
+	use super::*;
+
+	#[test]
+	fn test_find_jump_destinations() {
+		// given
+		let code = "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff5b01600055".from_hex().unwrap();
+
+		// when
+		let valid_jump_destinations = AnalysedCode::new(&code).jump_destinations;
+
+		// then
+		assert!(valid_jump_destinations.contains(66));
+	}
+
+	#[test]
+	fn should_split_code_into_basic_blocks() {
+		// given
+		// PUSH1 3 PUSH1 4 ADD PUSH1 0 SSTORE JUMPDEST PUSH1 8 JUMP
+		let code = "60036004016000555b600856".from_hex().unwrap();
+		let schedule = Schedule::new_homestead();
+
+		// when
+		let analysed = AnalysedCode::new(&code);
+
+		// then
+		assert_eq!(analysed.blocks.len(), 2);
+		let first = analysed.block_at(0).unwrap();
+		assert_eq!(first.end, 7);
+		assert_eq!(first.static_gas(&schedule), 12);
+		assert!(analysed.block_at(7).is_none());
+		let second = analysed.block_at(8).unwrap();
+		assert_eq!(second.end, 12);
+		assert_eq!(second.static_gas(&schedule), 1 + 3 + 8);
+	}
+
+	#[test]
+	fn should_roundtrip_analysed_code() {
+		// given
+		let code = "60036004016000555b600856".from_hex().unwrap();
+		let analysed = AnalysedCode::new(&code);
+
+		// when
+		let decoded: AnalysedCode = rlp::decode(&rlp::encode(&analysed));
+
+		// then
+		assert!(decoded.jump_destinations.contains(8));
+		assert_eq!(decoded.blocks, analysed.blocks);
+	}
+}
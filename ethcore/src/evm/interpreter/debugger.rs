@@ -0,0 +1,176 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Step-through debugging support for the interpreter.
+
+use util::U256;
+use evm::Ext;
+use evm::instructions::{self, Instruction};
+
+/// Interpreter state just before an instruction is executed.
+pub struct Step<'a> {
+	/// Program counter of the instruction.
+	pub pc: usize,
+	/// Opcode of the instruction.
+	pub instruction: Instruction,
+	/// Mnemonic of the instruction.
+	pub name: &'static str,
+	/// Gas left before the instruction is executed.
+	pub gas: U256,
+	/// Gas the instruction is going to cost.
+	pub gas_cost: U256,
+	/// Call depth, 1 for the outermost call.
+	pub depth: usize,
+	/// Stack, bottom first.
+	pub stack: &'a [U256],
+	/// Memory contents.
+	pub memory: &'a [u8],
+}
+
+/// Receives interpreter state before each executed instruction.
+pub trait Debugger {
+	/// Called before the instruction described by `step` is executed.
+	/// `ext` can be used to inspect storage and account state.
+	fn step(&mut self, step: &Step, ext: &Ext);
+}
+
+/// Execution breakpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+	/// Break at the given program counter.
+	Pc(usize),
+	/// Break before every occurrence of the given opcode.
+	Instruction(Instruction),
+}
+
+impl Breakpoint {
+	fn matches(&self, step: &Step) -> bool {
+		match *self {
+			Breakpoint::Pc(pc) => step.pc == pc,
+			Breakpoint::Instruction(instruction) => step.instruction == instruction,
+		}
+	}
+}
+
+/// Decides when a debugger should pause execution.
+/// Starts in single-stepping mode.
+pub struct Breakpoints {
+	breakpoints: Vec<Breakpoint>,
+	stepping: bool,
+}
+
+impl Default for Breakpoints {
+	fn default() -> Self {
+		Breakpoints {
+			breakpoints: Vec::new(),
+			stepping: true,
+		}
+	}
+}
+
+impl Breakpoints {
+	/// Add a breakpoint. Returns false if it is already set.
+	pub fn add(&mut self, breakpoint: Breakpoint) -> bool {
+		if self.breakpoints.contains(&breakpoint) {
+			return false;
+		}
+		self.breakpoints.push(breakpoint);
+		true
+	}
+
+	/// Remove a breakpoint. Returns false if it was not set.
+	pub fn remove(&mut self, breakpoint: &Breakpoint) -> bool {
+		let len = self.breakpoints.len();
+		self.breakpoints.retain(|b| b != breakpoint);
+		len != self.breakpoints.len()
+	}
+
+	/// Breakpoints currently set.
+	pub fn list(&self) -> &[Breakpoint] {
+		&self.breakpoints
+	}
+
+	/// Pause before the next instruction.
+	pub fn single_step(&mut self) {
+		self.stepping = true;
+	}
+
+	/// Run until the next breakpoint.
+	pub fn resume(&mut self) {
+		self.stepping = false;
+	}
+
+	/// Returns true if execution should pause before `step`.
+	pub fn should_break(&self, step: &Step) -> bool {
+		self.stepping || self.breakpoints.iter().any(|b| b.matches(step))
+	}
+}
+
+/// Returns the mnemonic of an opcode or `None` if it is not a valid instruction.
+pub fn instruction_name(instruction: Instruction) -> Option<&'static str> {
+	let info = &instructions::INSTRUCTIONS[instruction as usize];
+	match info.tier {
+		instructions::GasPriceTier::Invalid => None,
+		_ => Some(info.name),
+	}
+}
+
+/// Returns the opcode of a mnemonic, ignoring case.
+pub fn instruction_from_name(name: &str) -> Option<Instruction> {
+	let name = name.to_uppercase();
+	(0..0x100usize)
+		.find(|i| instruction_name(*i as Instruction) == Some(&name[..]))
+		.map(|i| i as Instruction)
+}
+
+#[test]
+fn test_instruction_names() {
+	assert_eq!(instruction_name(instructions::SSTORE), Some("SSTORE"));
+	assert_eq!(instruction_name(0xef), None);
+	assert_eq!(instruction_from_name("sstore"), Some(instructions::SSTORE));
+	assert_eq!(instruction_from_name("PUSH32"), Some(instructions::PUSH32));
+	assert_eq!(instruction_from_name("FOO"), None);
+}
+
+#[test]
+fn test_breakpoints() {
+	let stack: [U256; 0] = [];
+	let step = |pc, instruction| Step {
+		pc: pc,
+		instruction: instruction,
+		name: "",
+		gas: U256::zero(),
+		gas_cost: U256::zero(),
+		depth: 0,
+		stack: &stack,
+		memory: &[],
+	};
+	let mut breakpoints = Breakpoints::default();
+	assert!(breakpoints.should_break(&step(0, instructions::STOP)));
+
+	breakpoints.resume();
+	assert!(breakpoints.add(Breakpoint::Pc(5)));
+	assert!(!breakpoints.add(Breakpoint::Pc(5)));
+	assert!(breakpoints.add(Breakpoint::Instruction(instructions::SSTORE)));
+	assert!(!breakpoints.should_break(&step(0, instructions::STOP)));
+	assert!(breakpoints.should_break(&step(5, instructions::STOP)));
+	assert!(breakpoints.should_break(&step(7, instructions::SSTORE)));
+
+	assert!(breakpoints.remove(&Breakpoint::Pc(5)));
+	assert!(!breakpoints.remove(&Breakpoint::Pc(5)));
+	assert!(!breakpoints.should_break(&step(5, instructions::STOP)));
+	assert_eq!(breakpoints.list(), &[Breakpoint::Instruction(instructions::SSTORE)]);
+}
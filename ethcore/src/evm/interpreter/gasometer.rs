@@ -102,7 +102,7 @@ impl<Gas: CostType> Gasometer<Gas> {
 
 		let cost = match instruction {
 			instructions::JUMPDEST => {
-				InstructionCost::Gas(Gas::from(1))
+				InstructionCost::Gas(Gas::from(schedule.jumpdest_gas))
 			},
 			instructions::SSTORE => {
 				let address = H256::from(stack.peek(0));
@@ -255,9 +255,12 @@ impl<Gas: CostType> Gasometer<Gas> {
 			// s * memory_gas + s * s / quad_coeff_div
 			let a = overflowing!(s.overflow_mul(Gas::from(schedule.memory_gas)));
 
-			// Calculate s*s/quad_coeff_div
-			debug_assert_eq!(schedule.quad_coeff_div, 512);
-			let b = overflowing!(s.overflow_mul_shr(s, 9));
+			// Calculate s*s/quad_coeff_div, shifting when it is a power of two (512 on all public chains)
+			let b = if schedule.quad_coeff_div.is_power_of_two() {
+				overflowing!(s.overflow_mul_shr(s, schedule.quad_coeff_div.trailing_zeros() as usize))
+			} else {
+				overflowing!(s.overflow_mul(s)) / Gas::from(schedule.quad_coeff_div)
+			};
 			Ok(overflowing!(a.overflow_add(b)))
 		};
 
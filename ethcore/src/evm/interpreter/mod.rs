@@ -22,10 +22,13 @@ mod gasometer;
 mod stack;
 mod memory;
 mod shared_cache;
+pub mod analysis;
+pub mod debugger;
 
 use self::gasometer::Gasometer;
 use self::stack::{Stack, VecStack};
 use self::memory::Memory;
+use self::debugger::{Debugger, Step};
 pub use self::shared_cache::SharedCache;
 
 use std::marker::PhantomData;
@@ -100,34 +103,103 @@ pub struct Interpreter<Cost: CostType> {
 
 impl<Cost: CostType> evm::Evm for Interpreter<Cost> {
 	fn exec(&mut self, params: ActionParams, ext: &mut evm::Ext) -> evm::Result<GasLeft> {
+		self.run(params, ext, None)
+	}
+}
+
+impl<Cost: CostType> Interpreter<Cost> {
+	/// Create a new `Interpreter` instance with shared cache.
+	pub fn new(cache: Arc<SharedCache>) -> Interpreter<Cost> {
+		Interpreter {
+			mem: Vec::new(),
+			cache: cache,
+			_type: PhantomData::default(),
+		}
+	}
+
+	/// Execute code, handing the interpreter state to `debugger` before each instruction.
+	pub fn exec_debug(&mut self, params: ActionParams, ext: &mut evm::Ext, debugger: &mut Debugger) -> evm::Result<GasLeft> {
+		self.run(params, ext, Some(debugger))
+	}
+
+	fn run(&mut self, params: ActionParams, ext: &mut evm::Ext, mut debugger: Option<&mut Debugger>) -> evm::Result<GasLeft> {
 		self.mem.clear();
 
 		let mut informant = informant::EvmInformant::new(ext.depth());
 
 		let code = &params.code.as_ref().expect("exec always called with code; qed");
-		let valid_jump_destinations = self.cache.jump_destinations(&params.code_hash, code);
+		let analysis = self.cache.analysis(&params.code_hash, code);
 
 		let mut gasometer = Gasometer::<Cost>::new(try!(Cost::from_u256(params.gas)));
 		let mut stack = VecStack::with_capacity(ext.schedule().stack_limit, U256::zero());
 		let mut reader = CodeReader::new(code);
 		let infos = &*instructions::INSTRUCTIONS;
+		let trace_steps = ext.trace_steps();
+
+		// Static gas of a basic block is charged up front when entering it. `prepaid` is the
+		// part charged for instructions of the current block which have not been executed yet;
+		// it is added back whenever the gas left is observed.
+		let mut block_end = 0;
+		let mut prepaid = Cost::from(0);
 
 		while reader.position < code.len() {
 			let instruction = code[reader.position];
 			reader.position += 1;
 
+			let pc = reader.position - 1;
+			if pc >= block_end {
+				if let Some(block) = analysis.block_at(pc) {
+					let block_gas = Cost::from(block.static_gas(ext.schedule()));
+					// without enough gas for the whole block, charge per instruction to fail at the right one.
+					if gasometer.current_gas >= block_gas {
+						gasometer.current_gas = gasometer.current_gas - block_gas;
+						prepaid = block_gas;
+						block_end = block.end;
+					}
+				}
+			}
+			let in_block = pc < block_end;
+
 			let info = &infos[instruction as usize];
 			try!(self.verify_instruction(ext, instruction, info, &stack));
 
 			// Calculate gas cost
-			let (gas_cost, mem_gas, mem_size, provided) = try!(gasometer.get_gas_cost_mem(ext, instruction, info, &stack, self.mem.size()));
+			let (gas_cost, mem_gas, mem_size, provided) = match in_block {
+				true => (Cost::from(analysis::fixed_gas(instruction, info, ext.schedule())), gasometer.current_mem_gas, 0, None),
+				false => try!(gasometer.get_gas_cost_mem(ext, instruction, info, &stack, self.mem.size())),
+			};
+
+			if trace_steps || debugger.is_some() {
+				let step = Step {
+					pc: pc,
+					instruction: instruction,
+					name: info.name,
+					gas: (gasometer.current_gas + prepaid).as_u256(),
+					gas_cost: gas_cost.as_u256(),
+					depth: ext.depth() + 1,
+					stack: stack.peek_top(stack.size()),
+					memory: &self.mem,
+				};
+				if trace_steps {
+					ext.trace_step(&step);
+				}
+				if let Some(ref mut debugger) = debugger {
+					debugger.step(&step, ext);
+				}
+			}
+
 			// TODO: make compile-time removable if too much of a performance hit.
-			let trace_executed = ext.trace_prepare_execute(reader.position - 1, instruction, &gas_cost.as_u256());
+			let trace_executed = ext.trace_prepare_execute(pc, instruction, &gas_cost.as_u256());
 
-			try!(gasometer.verify_gas(&gas_cost));
+			match in_block {
+				true => prepaid = prepaid - gas_cost,
+				false => {
+					try!(gasometer.verify_gas(&gas_cost));
+					gasometer.current_gas = gasometer.current_gas - gas_cost;
+				},
+			}
 			self.mem.expand(mem_size);
 			gasometer.current_mem_gas = mem_gas;
-			gasometer.current_gas = gasometer.current_gas - gas_cost;
 
 			evm_debug!({ informant.before_instruction(reader.position, instruction, info, &gasometer.current_gas, &stack) });
 
@@ -148,14 +220,16 @@ impl<Cost: CostType> evm::Evm for Interpreter<Cost> {
 			}
 
 			if trace_executed {
-				ext.trace_executed(gasometer.current_gas.as_u256(), stack.peek_top(info.ret), mem_written.map(|(o, s)| (o, &(self.mem[o..(o + s)]))), store_written);
+				ext.trace_executed((gasometer.current_gas + prepaid).as_u256(), stack.peek_top(info.ret), mem_written.map(|(o, s)| (o, &(self.mem[o..(o + s)]))), store_written);
 			}
 
 			// Advance
 			match result {
 				InstructionResult::JumpToPosition(position) => {
-					let pos = try!(self.verify_jump(position, &valid_jump_destinations));
+					let pos = try!(self.verify_jump(position, &analysis.jump_destinations));
 					reader.position = pos;
+					// jumps always end a block; the destination may lie within the one just left.
+					block_end = 0;
 				},
 				InstructionResult::StopExecutionNeedsReturn(gas, off, size) => {
 					informant.done();
@@ -168,17 +242,6 @@ impl<Cost: CostType> evm::Evm for Interpreter<Cost> {
 		informant.done();
 		Ok(GasLeft::Known(gasometer.current_gas.as_u256()))
 	}
-}
-
-impl<Cost: CostType> Interpreter<Cost> {
-	/// Create a new `Interpreter` instance with shared cache.
-	pub fn new(cache: Arc<SharedCache>) -> Interpreter<Cost> {
-		Interpreter {
-			mem: Vec::new(),
-			cache: cache,
-			_type: PhantomData::default(),
-		}
-	}
 
 	fn verify_instruction(&self, ext: &evm::Ext, instruction: Instruction, info: &InstructionInfo, stack: &Stack<U256>) -> evm::Result<()> {
 		let schedule = ext.schedule();
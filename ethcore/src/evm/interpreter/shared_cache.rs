@@ -14,112 +14,273 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::sync::Arc;
 use lru_cache::LruCache;
+use rlp::{self, RlpStream, UntrustedRlp, View, Stream};
 use util::{H256, Mutex};
 use util::sha3::*;
-use bit_set::BitSet;
-use super::super::instructions;
+use super::analysis::{AnalysedCode, ANALYSIS_VERSION};
 
 const INITIAL_CAPACITY: usize = 32;
 const DEFAULT_CACHE_SIZE: usize = 4 * 1024 * 1024;
+/// Version of the cache file format; files of other versions are ignored.
+const CACHE_FILE_VERSION: u32 = 2;
 
 /// Global cache for EVM interpreter
 pub struct SharedCache {
-	jump_destinations: Mutex<LruCache<H256, Arc<BitSet>>>,
+	// analyses along with the length of the code they were made for.
+	analysed: Mutex<LruCache<H256, (usize, Arc<AnalysedCode>)>>,
 	max_size: usize,
 	cur_size: Mutex<usize>,
+	path: Option<PathBuf>,
 }
 
 impl SharedCache {
-	/// Create a jump destinations cache with a maximum size in bytes
+	/// Create an analysed code cache with a maximum size in bytes
 	/// to cache.
 	pub fn new(max_size: usize) -> Self {
 		SharedCache {
-			jump_destinations: Mutex::new(LruCache::new(INITIAL_CAPACITY)),
-			max_size: max_size * 8, // dealing with bits here.
+			analysed: Mutex::new(LruCache::new(INITIAL_CAPACITY)),
+			max_size: max_size,
 			cur_size: Mutex::new(0),
+			path: None,
 		}
 	}
 
-	/// Get jump destinations bitmap for a contract.
-	pub fn jump_destinations(&self, code_hash: &H256, code: &[u8]) -> Arc<BitSet> {
+	/// Create an analysed code cache with a maximum size in bytes to cache,
+	/// loaded from the given file and saved back to it when dropped.
+	/// Persisted analyses made by a different analysis version are discarded.
+	pub fn persistent(max_size: usize, path: PathBuf) -> Self {
+		let cache = SharedCache {
+			path: Some(path),
+			..SharedCache::new(max_size)
+		};
+		cache.load();
+		cache
+	}
+
+	/// Get the analysis of a contract's code.
+	pub fn analysis(&self, code_hash: &H256, code: &[u8]) -> Arc<AnalysedCode> {
 		if code_hash == &SHA3_EMPTY {
-			return Self::find_jump_destinations(code);
+			return Arc::new(AnalysedCode::new(code));
 		}
 
-		if let Some(d) = self.jump_destinations.lock().get_mut(code_hash) {
-			return d.clone();
+		if let Some(&mut (len, ref d)) = self.analysed.lock().get_mut(code_hash) {
+			if len == code.len() {
+				return d.clone();
+			}
 		}
 
-		let d = Self::find_jump_destinations(code);
+		let d = Arc::new(AnalysedCode::new(code));
+		self.insert(code_hash.clone(), code.len(), d.clone());
+		d
+	}
 
-		{
-			let mut cur_size = self.cur_size.lock();
-			*cur_size += d.capacity();
+	/// Save the cache to its file, if it has one.
+	pub fn save(&self) {
+		let path = match self.path {
+			Some(ref path) => path,
+			None => return,
+		};
+		if let Some(dir) = path.parent() {
+			if let Err(e) = fs::create_dir_all(dir) {
+				warn!("Error creating code cache directory: {:?}", e);
+				return;
+			}
+		}
 
-			let mut jump_dests = self.jump_destinations.lock();
-			let cap = jump_dests.capacity();
+		let analysed = self.analysed.lock();
+		let mut stream = RlpStream::new_list(2);
+		stream.append(&CACHE_FILE_VERSION);
+		stream.begin_list(analysed.len());
+		// least recently used first, so that loading restores the order.
+		for (code_hash, &(len, ref code)) in analysed.iter() {
+			stream.begin_list(4).append(code_hash).append(&len).append(&**code).append(&checksum(code_hash, len, code));
+		}
 
-			// grow the cache as necessary; it operates on amount of items
-			// but we're working based on memory usage.
-			if jump_dests.len() == cap && *cur_size < self.max_size {
-				jump_dests.set_capacity(cap * 2);
-			}
+		if let Err(e) = fs::File::create(path).and_then(|mut file| file.write_all(&stream.out())) {
+			warn!("Error writing code cache file: {:?}", e);
+		}
+	}
 
-			// account for any element displaced from the cache.
-			if let Some(lru) = jump_dests.insert(code_hash.clone(), d.clone()) {
-				*cur_size -= lru.capacity();
-			}
+	fn load(&self) {
+		let path = self.path.as_ref().expect("only called for persistent caches; qed");
+		let mut bytes = Vec::new();
+		if let Err(e) = fs::File::open(path).and_then(|mut file| file.read_to_end(&mut bytes)) {
+			debug!("Error opening code cache file: {:?}", e);
+			return;
+		}
 
-			// remove elements until we are below the memory target.
-			while *cur_size > self.max_size {
-				match jump_dests.remove_lru() {
-					Some((_, v)) => *cur_size -= v.capacity(),
-					_ => break,
-				}
-			}
+		let rlp = UntrustedRlp::new(&bytes);
+		match rlp.val_at::<u32>(0) {
+			Ok(CACHE_FILE_VERSION) => {},
+			Ok(version) => {
+				debug!("Ignoring code cache file of version {}", version);
+				return;
+			},
+			Err(e) => {
+				warn!("Error parsing code cache file: {:?}", e);
+				return;
+			},
 		}
 
-		d
+		let entries = match rlp.at(1) {
+			Ok(entries) => entries,
+			Err(e) => {
+				warn!("Error parsing code cache file: {:?}", e);
+				return;
+			},
+		};
+		// an entry is only used if its checksum matches, which ties it to the current analysis
+		// version and catches corrupted files.
+		let mut dropped = 0;
+		for entry in entries.iter() {
+			let decoded: Result<(H256, usize, AnalysedCode, H256), rlp::DecoderError> = entry.val_at(0)
+				.and_then(|hash| entry.val_at(1).map(|len| (hash, len)))
+				.and_then(|(hash, len)| entry.val_at(2).map(|code| (hash, len, code)))
+				.and_then(|(hash, len, code)| entry.val_at(3).map(|sum| (hash, len, code, sum)));
+			match decoded {
+				Ok((code_hash, len, ref code, ref sum)) if code_hash != SHA3_EMPTY && *sum == checksum(&code_hash, len, code) && code.is_consistent(len) => {
+					self.insert(code_hash, len, Arc::new(code.clone()));
+				},
+				_ => dropped += 1,
+			}
+		}
+		if dropped > 0 {
+			warn!("Dropped {} invalid entries from the code cache file", dropped);
+		}
 	}
 
-	fn find_jump_destinations(code: &[u8]) -> Arc<BitSet> {
-		let mut jump_dests = BitSet::with_capacity(code.len());
-		let mut position = 0;
+	fn insert(&self, code_hash: H256, len: usize, d: Arc<AnalysedCode>) {
+		let mut cur_size = self.cur_size.lock();
+		*cur_size += d.heap_size();
 
-		while position < code.len() {
-			let instruction = code[position];
+		let mut analysed = self.analysed.lock();
+		let cap = analysed.capacity();
 
-			if instruction == instructions::JUMPDEST {
-				jump_dests.insert(position);
-			} else if instructions::is_push(instruction) {
-				position += instructions::get_push_bytes(instruction);
-			}
-			position += 1;
+		// grow the cache as necessary; it operates on amount of items
+		// but we're working based on memory usage.
+		if analysed.len() == cap && *cur_size < self.max_size {
+			analysed.set_capacity(cap * 2);
+		}
+
+		// account for any element displaced from the cache.
+		if let Some((_, lru)) = analysed.insert(code_hash, (len, d)) {
+			*cur_size -= lru.heap_size();
 		}
 
-		jump_dests.shrink_to_fit();
-		Arc::new(jump_dests)
+		// remove elements until we are below the memory target.
+		while *cur_size > self.max_size {
+			match analysed.remove_lru() {
+				Some((_, (_, v))) => *cur_size -= v.heap_size(),
+				_ => break,
+			}
+		}
 	}
 }
 
+/// Checksum of a persisted analysis, covering the analysis version.
+fn checksum(code_hash: &H256, len: usize, code: &AnalysedCode) -> H256 {
+	let mut stream = RlpStream::new_list(4);
+	stream.append(&ANALYSIS_VERSION).append(code_hash).append(&len).append(code);
+	stream.out().sha3()
+}
+
 impl Default for SharedCache {
 	fn default() -> Self {
 		SharedCache::new(DEFAULT_CACHE_SIZE)
 	}
 }
 
+impl Drop for SharedCache {
+	fn drop(&mut self) {
+		self.save();
+	}
+}
+
+#[test]
+fn should_persist_analysed_code() {
+	use devtools::RandomTempPath;
+	use util::FromHex;
+	// given
+	let path = RandomTempPath::new();
+	let code = "60036004016000555b600856".from_hex().unwrap();
+	let code_hash = code.sha3();
+	let analysed = {
+		let cache = SharedCache::persistent(DEFAULT_CACHE_SIZE, path.as_path().to_owned());
+		cache.analysis(&code_hash, &code)
+	};
+
+	// when
+	let cache = SharedCache::persistent(DEFAULT_CACHE_SIZE, path.as_path().to_owned());
+
+	// then
+	assert_eq!(cache.analysed.lock().get_mut(&code_hash).map(|&mut (_, ref d)| (**d).clone()), Some((*analysed).clone()));
+	assert_eq!(*analysed, AnalysedCode::new(&code));
+}
+
+#[test]
+fn should_drop_invalid_persisted_code() {
+	use devtools::RandomTempPath;
+	use util::FromHex;
+	// given
+	let path = RandomTempPath::new();
+	let code = "60036004016000555b600856".from_hex().unwrap();
+	let code_hash = code.sha3();
+	let mut stream = RlpStream::new_list(2);
+	stream.append(&CACHE_FILE_VERSION);
+	stream.begin_list(1);
+	// an analysis which does not fit the code length.
+	let analysed = AnalysedCode::new(&code);
+	stream.begin_list(4).append(&code_hash).append(&3usize).append(&analysed).append(&checksum(&code_hash, 3, &analysed));
+	fs::File::create(path.as_path()).and_then(|mut file| file.write_all(&stream.out())).unwrap();
+
+	// when
+	let cache = SharedCache::persistent(DEFAULT_CACHE_SIZE, path.as_path().to_owned());
+
+	// then
+	assert!(cache.analysed.lock().get_mut(&code_hash).is_none());
+}
+
+#[test]
+fn should_drop_persisted_code_with_wrong_checksum() {
+	use devtools::RandomTempPath;
+	use util::FromHex;
+	// given
+	let path = RandomTempPath::new();
+	let code = "60036004016000555b600856".from_hex().unwrap();
+	let code_hash = code.sha3();
+	let mut stream = RlpStream::new_list(2);
+	stream.append(&CACHE_FILE_VERSION);
+	stream.begin_list(1);
+	// a consistent analysis which was not made for this code.
+	let analysed = AnalysedCode::new(&code);
+	stream.begin_list(4).append(&code_hash).append(&code.len()).append(&AnalysedCode::default()).append(&checksum(&code_hash, code.len(), &analysed));
+	fs::File::create(path.as_path()).and_then(|mut file| file.write_all(&stream.out())).unwrap();
+
+	// when
+	let cache = SharedCache::persistent(DEFAULT_CACHE_SIZE, path.as_path().to_owned());
+
+	// then
+	assert!(cache.analysed.lock().get_mut(&code_hash).is_none());
+	assert_eq!(*cache.analysis(&code_hash, &code), analysed);
+}
 
 #[test]
-fn test_find_jump_destinations() {
+fn should_reanalyse_code_of_different_length() {
 	use util::FromHex;
 	// given
-	let code = "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff5b01600055".from_hex().unwrap();
+	let cache = SharedCache::default();
+	let code = "60036004016000555b600856".from_hex().unwrap();
+	let code_hash = code.sha3();
+	cache.analysis(&code_hash, &code[..5]);
 
 	// when
-	let valid_jump_destinations = SharedCache::find_jump_destinations(&code);
+	let analysed = cache.analysis(&code_hash, &code);
 
 	// then
-	assert!(valid_jump_destinations.contains(66));
+	assert_eq!(*analysed, AnalysedCode::new(&code));
 }
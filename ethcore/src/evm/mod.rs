@@ -34,5 +34,5 @@ mod benches;
 pub use self::evm::{Evm, Error, Finalize, GasLeft, Result, CostType};
 pub use self::ext::{Ext, ContractCreateResult, MessageCallResult};
 pub use self::factory::{Factory, VMType};
-pub use self::schedule::Schedule;
+pub use self::schedule::{Schedule, ScheduleOverride};
 pub use types::executed::CallType;
@@ -192,13 +192,135 @@ impl Schedule {
 	}
 }
 
+/// Values replacing those of an engine's `Schedule`. `None` keeps the engine's value.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ScheduleOverride {
+	/// See `Schedule`.
+	pub exceptional_failed_code_deposit: Option<bool>,
+	/// See `Schedule`.
+	pub have_delegate_call: Option<bool>,
+	/// See `Schedule`.
+	pub stack_limit: Option<usize>,
+	/// See `Schedule`.
+	pub max_depth: Option<usize>,
+	/// See `Schedule`.
+	pub tier_step_gas: Option<[usize; 8]>,
+	/// See `Schedule`.
+	pub exp_gas: Option<usize>,
+	/// See `Schedule`.
+	pub exp_byte_gas: Option<usize>,
+	/// See `Schedule`.
+	pub sha3_gas: Option<usize>,
+	/// See `Schedule`.
+	pub sha3_word_gas: Option<usize>,
+	/// See `Schedule`.
+	pub sload_gas: Option<usize>,
+	/// See `Schedule`.
+	pub sstore_set_gas: Option<usize>,
+	/// See `Schedule`.
+	pub sstore_reset_gas: Option<usize>,
+	/// See `Schedule`.
+	pub sstore_refund_gas: Option<usize>,
+	/// See `Schedule`.
+	pub jumpdest_gas: Option<usize>,
+	/// See `Schedule`.
+	pub log_gas: Option<usize>,
+	/// See `Schedule`.
+	pub log_data_gas: Option<usize>,
+	/// See `Schedule`.
+	pub log_topic_gas: Option<usize>,
+	/// See `Schedule`.
+	pub create_gas: Option<usize>,
+	/// See `Schedule`.
+	pub call_gas: Option<usize>,
+	/// See `Schedule`.
+	pub call_stipend: Option<usize>,
+	/// See `Schedule`.
+	pub call_value_transfer_gas: Option<usize>,
+	/// See `Schedule`.
+	pub call_new_account_gas: Option<usize>,
+	/// See `Schedule`.
+	pub suicide_refund_gas: Option<usize>,
+	/// See `Schedule`.
+	pub memory_gas: Option<usize>,
+	/// See `Schedule`.
+	pub quad_coeff_div: Option<usize>,
+	/// See `Schedule`.
+	pub create_data_gas: Option<usize>,
+	/// See `Schedule`.
+	pub tx_gas: Option<usize>,
+	/// See `Schedule`.
+	pub tx_create_gas: Option<usize>,
+	/// See `Schedule`.
+	pub tx_data_zero_gas: Option<usize>,
+	/// See `Schedule`.
+	pub tx_data_non_zero_gas: Option<usize>,
+	/// See `Schedule`.
+	pub copy_gas: Option<usize>,
+	/// See `Schedule`.
+	pub extcodesize_gas: Option<usize>,
+	/// See `Schedule`.
+	pub extcodecopy_base_gas: Option<usize>,
+	/// See `Schedule`.
+	pub balance_gas: Option<usize>,
+	/// See `Schedule`.
+	pub suicide_gas: Option<usize>,
+	/// See `Schedule`.
+	pub suicide_to_new_account_cost: Option<usize>,
+	/// See `Schedule`.
+	pub sub_gas_cap_divisor: Option<Option<usize>>,
+}
+
+impl ScheduleOverride {
+	/// Replace the overridden values of `s`.
+	pub fn apply(&self, s: &mut Schedule) {
+		if let Some(v) = self.exceptional_failed_code_deposit { s.exceptional_failed_code_deposit = v; }
+		if let Some(v) = self.have_delegate_call { s.have_delegate_call = v; }
+		if let Some(v) = self.stack_limit { s.stack_limit = v; }
+		if let Some(v) = self.max_depth { s.max_depth = v; }
+		if let Some(v) = self.tier_step_gas { s.tier_step_gas = v; }
+		if let Some(v) = self.exp_gas { s.exp_gas = v; }
+		if let Some(v) = self.exp_byte_gas { s.exp_byte_gas = v; }
+		if let Some(v) = self.sha3_gas { s.sha3_gas = v; }
+		if let Some(v) = self.sha3_word_gas { s.sha3_word_gas = v; }
+		if let Some(v) = self.sload_gas { s.sload_gas = v; }
+		if let Some(v) = self.sstore_set_gas { s.sstore_set_gas = v; }
+		if let Some(v) = self.sstore_reset_gas { s.sstore_reset_gas = v; }
+		if let Some(v) = self.sstore_refund_gas { s.sstore_refund_gas = v; }
+		if let Some(v) = self.jumpdest_gas { s.jumpdest_gas = v; }
+		if let Some(v) = self.log_gas { s.log_gas = v; }
+		if let Some(v) = self.log_data_gas { s.log_data_gas = v; }
+		if let Some(v) = self.log_topic_gas { s.log_topic_gas = v; }
+		if let Some(v) = self.create_gas { s.create_gas = v; }
+		if let Some(v) = self.call_gas { s.call_gas = v; }
+		if let Some(v) = self.call_stipend { s.call_stipend = v; }
+		if let Some(v) = self.call_value_transfer_gas { s.call_value_transfer_gas = v; }
+		if let Some(v) = self.call_new_account_gas { s.call_new_account_gas = v; }
+		if let Some(v) = self.suicide_refund_gas { s.suicide_refund_gas = v; }
+		if let Some(v) = self.memory_gas { s.memory_gas = v; }
+		if let Some(v) = self.quad_coeff_div { s.quad_coeff_div = v; }
+		if let Some(v) = self.create_data_gas { s.create_data_gas = v; }
+		if let Some(v) = self.tx_gas { s.tx_gas = v; }
+		if let Some(v) = self.tx_create_gas { s.tx_create_gas = v; }
+		if let Some(v) = self.tx_data_zero_gas { s.tx_data_zero_gas = v; }
+		if let Some(v) = self.tx_data_non_zero_gas { s.tx_data_non_zero_gas = v; }
+		if let Some(v) = self.copy_gas { s.copy_gas = v; }
+		if let Some(v) = self.extcodesize_gas { s.extcodesize_gas = v; }
+		if let Some(v) = self.extcodecopy_base_gas { s.extcodecopy_base_gas = v; }
+		if let Some(v) = self.balance_gas { s.balance_gas = v; }
+		if let Some(v) = self.suicide_gas { s.suicide_gas = v; }
+		if let Some(v) = self.suicide_to_new_account_cost { s.suicide_to_new_account_cost = v; }
+		if let Some(v) = self.sub_gas_cap_divisor { s.sub_gas_cap_divisor = v; }
+	}
+}
+
 #[test]
 #[cfg(test)]
 fn schedule_evm_assumptions() {
 	let s1 = Schedule::new_frontier();
 	let s2 = Schedule::new_homestead();
 
-	// To optimize division we assume 2**9 for quad_coeff_div
+	// Division is optimized to a shift for power-of-two quad_coeff_div
 	assert_eq!(s1.quad_coeff_div, 512);
 	assert_eq!(s2.quad_coeff_div, 512);
 }
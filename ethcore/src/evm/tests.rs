@@ -205,6 +205,110 @@ fn test_stack_underflow() {
 	};
 }
 
+#[test]
+fn test_debugger_steps() {
+	use evm::interpreter::debugger::{Debugger, Step, Breakpoints, Breakpoint};
+
+	struct Recorder {
+		breakpoints: Breakpoints,
+		steps: Vec<(usize, &'static str, Vec<U256>)>,
+		stored_before: Option<H256>,
+	}
+
+	impl Debugger for Recorder {
+		fn step(&mut self, step: &Step, ext: &Ext) {
+			if self.breakpoints.should_break(step) {
+				self.steps.push((step.pc, step.name, step.stack.to_vec()));
+				if step.name == "SSTORE" {
+					self.stored_before = Some(ext.storage_at(&H256::from(0)));
+				}
+			}
+		}
+	}
+
+	let code = "6001600201600055".from_hex().unwrap();
+
+	let mut params = ActionParams::default();
+	params.gas = U256::from(100_000);
+	params.code = Some(Arc::new(code));
+	let mut ext = FakeExt::new();
+
+	let mut recorder = Recorder {
+		breakpoints: Breakpoints::default(),
+		steps: Vec::new(),
+		stored_before: None,
+	};
+	recorder.breakpoints.resume();
+	recorder.breakpoints.add(Breakpoint::Pc(4));
+	recorder.breakpoints.add(Breakpoint::Instruction(0x55));
+
+	{
+		let mut vm = super::interpreter::Interpreter::<usize>::new(Arc::new(super::interpreter::SharedCache::default()));
+		test_finalize(vm.exec_debug(params, &mut ext, &mut recorder)).unwrap();
+	}
+
+	assert_eq!(recorder.steps, vec![
+		(4, "ADD", vec![U256::from(1), U256::from(2)]),
+		(7, "SSTORE", vec![U256::from(3), U256::from(0)]),
+	]);
+	assert_eq!(recorder.stored_before, Some(H256::new()));
+	assert_store(&ext, 0, "0000000000000000000000000000000000000000000000000000000000000003");
+}
+
+#[test]
+fn test_block_gas_is_observed_per_instruction() {
+	use evm::interpreter::debugger::{Debugger, Step};
+
+	struct Recorder(Vec<(usize, U256)>);
+
+	impl Debugger for Recorder {
+		fn step(&mut self, step: &Step, _ext: &Ext) {
+			self.0.push((step.pc, step.gas));
+		}
+	}
+
+	let code = "6001600201600055".from_hex().unwrap();
+
+	let mut params = ActionParams::default();
+	params.gas = U256::from(100_000);
+	params.code = Some(Arc::new(code));
+	let mut ext = FakeExt::new();
+	let mut recorder = Recorder(Vec::new());
+
+	let gas_left = {
+		let mut vm = super::interpreter::Interpreter::<usize>::new(Arc::new(super::interpreter::SharedCache::default()));
+		test_finalize(vm.exec_debug(params, &mut ext, &mut recorder)).unwrap()
+	};
+
+	assert_eq!(recorder.0, vec![
+		(0, U256::from(100_000)),
+		(2, U256::from(99_997)),
+		(4, U256::from(99_994)),
+		(5, U256::from(99_991)),
+		(7, U256::from(99_988)),
+	]);
+	assert_eq!(gas_left, U256::from(79_988));
+}
+
+#[test]
+fn test_block_gas_exhausted_within_block() {
+	let code = "6001600201".from_hex().unwrap();
+	let run = |gas: usize| {
+		let mut params = ActionParams::default();
+		params.gas = U256::from(gas);
+		params.code = Some(Arc::new(code.clone()));
+		let mut ext = FakeExt::new();
+		let mut vm = super::interpreter::Interpreter::<usize>::new(Arc::new(super::interpreter::SharedCache::default()));
+		test_finalize(vm.exec(params, &mut ext))
+	};
+
+	assert_eq!(run(9).unwrap(), U256::zero());
+	match run(8).unwrap_err() {
+		evm::Error::OutOfGas => {},
+		e => panic!("Expected OutOfGas, got {:?}", e),
+	}
+}
+
 evm_test!{test_add: test_add_jit, test_add_int}
 fn test_add(factory: super::Factory) {
   let address = Address::from_str("0f572e5295c57f15886f9b263e2f6d2d6c7b5ec6").unwrap();
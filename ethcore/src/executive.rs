@@ -24,7 +24,9 @@ use env_info::EnvInfo;
 use error::ExecutionError;
 use evm::{self, Ext, Factory, Finalize};
 use externalities::*;
-use trace::{FlatTrace, Tracer, NoopTracer, ExecutiveTracer, VMTrace, VMTracer, ExecutiveVMTracer, NoopVMTracer};
+use trace::{FlatTrace, Tracer, NoopTracer, ExecutiveTracer, VMTrace, VMTracer, ExecutiveVMTracer, NoopVMTracer, StepLogVMTracer, GasProfilerVMTracer, GasProfile};
+use types::call_analytics::StepLogOptions;
+use types::state_accesses::StateAccesses;
 use transaction::{Action, SignedTransaction};
 use crossbeam;
 pub use types::executed::{Executed, ExecutionResult};
@@ -53,6 +55,12 @@ pub struct TransactOptions {
 	pub vm_tracing: bool,
 	/// Check transaction nonce before execution.
 	pub check_nonce: bool,
+	/// Record a step log. Can't be combined with gas profiling or VM tracing.
+	pub step_logging: Option<StepLogOptions>,
+	/// Record a gas profile. Can't be combined with VM tracing.
+	pub gas_profiling: bool,
+	/// Record accessed accounts and storage.
+	pub state_accessing: bool,
 }
 
 /// Transaction executor.
@@ -101,19 +109,68 @@ impl<'a> Executive<'a> {
 
 	/// This function should be used to execute transaction.
 	pub fn transact(&'a mut self, t: &SignedTransaction, options: TransactOptions) -> Result<Executed, ExecutionError> {
+		match options.state_accessing {
+			true => self.transact_with_state_accesses(t, options),
+			false => self.transact_with_options(t, options),
+		}
+	}
+
+	fn transact_with_state_accesses(&'a mut self, t: &SignedTransaction, options: TransactOptions) -> Result<Executed, ExecutionError> {
+		// the state only holds a weak reference, so recording stops once this returns.
+		let accesses = Arc::new(Mutex::new(StateAccesses::default()));
+		self.state.record_accesses(&accesses);
+		let mut executed = try!(self.transact_with_options(t, options));
+		let mut recorded = mem::replace(&mut *accesses.lock(), StateAccesses::default());
+		recorded.sender = t.sender().ok();
+		recorded.fee_recipient = Some(self.info.author);
+		executed.state_accesses = Some(recorded);
+		Ok(executed)
+	}
+
+	fn transact_with_options(&'a mut self, t: &SignedTransaction, options: TransactOptions) -> Result<Executed, ExecutionError> {
 		let check = options.check_nonce;
-		match options.tracing {
-			true => match options.vm_tracing {
-				true => self.transact_with_tracer(t, check, ExecutiveTracer::default(), ExecutiveVMTracer::toplevel()),
-				false => self.transact_with_tracer(t, check, ExecutiveTracer::default(), NoopVMTracer),
-			},
-			false => match options.vm_tracing {
-				true => self.transact_with_tracer(t, check, NoopTracer, ExecutiveVMTracer::toplevel()),
-				false => self.transact_with_tracer(t, check, NoopTracer, NoopVMTracer),
+		debug_assert!(
+			[options.vm_tracing, options.gas_profiling, options.step_logging.is_some()].iter().filter(|t| **t).count() <= 1,
+			"Only one VM tracer can run at a time"
+		);
+		match options.step_logging {
+			Some(step_options) => self.transact_with_step_log(t, check, options.tracing, step_options),
+			None if options.gas_profiling => self.transact_with_gas_profile(t, check, options.tracing),
+			None => match options.tracing {
+				true => match options.vm_tracing {
+					true => self.transact_with_tracer(t, check, ExecutiveTracer::default(), ExecutiveVMTracer::toplevel()),
+					false => self.transact_with_tracer(t, check, ExecutiveTracer::default(), NoopVMTracer),
+				},
+				false => match options.vm_tracing {
+					true => self.transact_with_tracer(t, check, NoopTracer, ExecutiveVMTracer::toplevel()),
+					false => self.transact_with_tracer(t, check, NoopTracer, NoopVMTracer),
+				},
 			},
 		}
 	}
 
+	fn transact_with_step_log(&'a mut self, t: &SignedTransaction, check_nonce: bool, tracing: bool, options: StepLogOptions) -> Result<Executed, ExecutionError> {
+		let log = Arc::new(Mutex::new(Vec::new()));
+		let vm_tracer = StepLogVMTracer::new(log.clone(), options);
+		let mut executed = try!(match tracing {
+			true => self.transact_with_tracer(t, check_nonce, ExecutiveTracer::default(), vm_tracer),
+			false => self.transact_with_tracer(t, check_nonce, NoopTracer, vm_tracer),
+		});
+		executed.step_log = Some(mem::replace(&mut *log.lock(), Vec::new()));
+		Ok(executed)
+	}
+
+	fn transact_with_gas_profile(&'a mut self, t: &SignedTransaction, check_nonce: bool, tracing: bool) -> Result<Executed, ExecutionError> {
+		let profile = Arc::new(Mutex::new(GasProfile::default()));
+		let vm_tracer = GasProfilerVMTracer::new(profile.clone());
+		let mut executed = try!(match tracing {
+			true => self.transact_with_tracer(t, check_nonce, ExecutiveTracer::default(), vm_tracer),
+			false => self.transact_with_tracer(t, check_nonce, NoopTracer, vm_tracer),
+		});
+		executed.gas_profile = Some(mem::replace(&mut *profile.lock(), GasProfile::default()));
+		Ok(executed)
+	}
+
 	/// Execute transaction/call with tracing enabled
 	pub fn transact_with_tracer<T, V>(
 		&'a mut self,
@@ -126,7 +183,8 @@ impl<'a> Executive<'a> {
 			let message = format!("Transaction malformed: {:?}", e);
 			ExecutionError::TransactionMalformed(message)
 		}));
-		let nonce = self.state.nonce(&sender);
+		// Sender and fee bookkeeping is kept out of the access log, see `StateAccesses::sender`.
+		let nonce = self.state.without_access_recording(|state| state.nonce(&sender));
 
 		let schedule = self.engine.schedule(self.info);
 		let base_gas_required = U256::from(t.gas_required(&schedule));
@@ -152,7 +210,7 @@ impl<'a> Executive<'a> {
 		}
 
 		// TODO: we might need bigints here, or at least check overflows.
-		let balance = self.state.balance(&sender);
+		let balance = self.state.without_access_recording(|state| state.balance(&sender));
 		let gas_cost = t.gas.full_mul(t.gas_price);
 		let total_cost = U512::from(t.value) + gas_cost;
 
@@ -163,8 +221,10 @@ impl<'a> Executive<'a> {
 		}
 
 		// NOTE: there can be no invalid transactions from this point.
-		self.state.inc_nonce(&sender);
-		self.state.sub_balance(&sender, &U256::from(gas_cost));
+		self.state.without_access_recording(|state| {
+			state.inc_nonce(&sender);
+			state.sub_balance(&sender, &U256::from(gas_cost));
+		});
 
 		let mut substate = Substate::new();
 
@@ -262,7 +322,7 @@ impl<'a> Executive<'a> {
 		}
 		trace!("Executive::call(params={:?}) self.env_info={:?}", params, self.info);
 
-		if self.engine.is_builtin(&params.code_address) {
+		if self.engine.is_active_builtin(&params.code_address, self.info.number) {
 			// if destination is builtin, try to execute it
 
 			let default = [];
@@ -272,7 +332,13 @@ impl<'a> Executive<'a> {
 
 			let cost = self.engine.cost_of_builtin(&params.code_address, data);
 			if cost <= params.gas {
-				self.engine.execute_builtin(&params.code_address, data, &mut output);
+				if let Err(e) = self.engine.execute_builtin(&params.code_address, data, &mut output) {
+					// failing builtins consume all the gas
+					self.state.revert_to_checkpoint();
+					let error = evm::Error::BuiltIn(e.0);
+					tracer.trace_failed_call(trace_info, vec![], error.into());
+					return Err(error);
+				}
 				self.state.discard_checkpoint();
 
 				// trace only top level calls to builtins to avoid DDoS attacks
@@ -440,9 +506,10 @@ impl<'a> Executive<'a> {
 		};
 
 		trace!("exec::finalize: Refunding refund_value={}, sender={}\n", refund_value, sender);
-		self.state.add_balance(&sender, &refund_value);
+		self.state.without_access_recording(|state| state.add_balance(&sender, &refund_value));
 		trace!("exec::finalize: Compensating author: fees_value={}, author={}\n", fees_value, &self.info.author);
-		self.state.add_balance(&self.info.author, &fees_value);
+		let author = self.info.author;
+		self.state.without_access_recording(|state| state.add_balance(&author, &fees_value));
 
 		// perform suicides
 		for address in &substate.suicides {
@@ -463,6 +530,9 @@ impl<'a> Executive<'a> {
 					trace: trace,
 					vm_trace: vm_trace,
 					state_diff: None,
+					step_log: None,
+					gas_profile: None,
+					state_accesses: None,
 				})
 			},
 			_ => {
@@ -477,6 +547,9 @@ impl<'a> Executive<'a> {
 					trace: trace,
 					vm_trace: vm_trace,
 					state_diff: None,
+					step_log: None,
+					gas_profile: None,
+					state_accesses: None,
 				})
 			},
 		}
@@ -488,7 +561,8 @@ impl<'a> Executive<'a> {
 				| Err(evm::Error::BadJumpDestination {..})
 				| Err(evm::Error::BadInstruction {.. })
 				| Err(evm::Error::StackUnderflow {..})
-				| Err(evm::Error::OutOfStack {..}) => {
+				| Err(evm::Error::OutOfStack {..})
+				| Err(evm::Error::BuiltIn(_)) => {
 					self.state.revert_to_checkpoint();
 			},
 			Ok(_) | Err(evm::Error::Internal) => {
@@ -517,6 +591,8 @@ mod tests {
 	use transaction::{Action, Transaction};
 
 	use types::executed::CallType;
+	use types::call_analytics::StepLogOptions;
+	use types::state_accesses::Access;
 
 	#[test]
 	fn test_contract_address() {
@@ -1053,7 +1129,7 @@ mod tests {
 
 		let executed = {
 			let mut ex = Executive::new(&mut state, &info, &engine, &factory);
-			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false };
+			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false, step_logging: None, gas_profiling: false, state_accessing: false };
 			ex.transact(&t, opts).unwrap()
 		};
 
@@ -1069,6 +1145,113 @@ mod tests {
 		assert_eq!(state.storage_at(&contract, &H256::new()), H256::from(&U256::from(1)));
 	}
 
+	#[test]
+	fn test_transact_with_step_log() {
+		// 60 01 - push 1
+		// 60 00 - push 0
+		// 55 - sstore
+		let code = "6001600055".from_hex().unwrap();
+		let keypair = Random.generate().unwrap();
+		let t = Transaction {
+			action: Action::Create,
+			value: U256::zero(),
+			data: code,
+			gas: U256::from(100_000),
+			gas_price: U256::zero(),
+			nonce: U256::zero()
+		}.sign(keypair.secret());
+
+		let mut state_result = get_temp_state();
+		let mut state = state_result.reference_mut();
+		let mut info = EnvInfo::default();
+		info.gas_limit = U256::from(100_000);
+		let engine = TestEngine::new(0);
+		let factory = Factory::new(VMType::Interpreter, 1024 * 32);
+
+		let executed = {
+			let mut ex = Executive::new(&mut state, &info, &engine, &factory);
+			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false, step_logging: Some(StepLogOptions { memory: false, storage: true }), gas_profiling: false, state_accessing: false };
+			ex.transact(&t, opts).unwrap()
+		};
+
+		let log = executed.step_log.unwrap();
+		assert_eq!(log.iter().map(|e| e.pc).collect::<Vec<_>>(), vec![0, 2, 4]);
+		assert!(log.iter().all(|e| e.depth == 1));
+		assert_eq!(log[2].instruction, 0x55);
+		assert_eq!(log[2].stack, vec![U256::one(), U256::zero()]);
+		assert_eq!(log[2].gas, log[0].gas - U256::from(6));
+		assert_eq!(log[2].gas_cost, U256::from(20_000));
+		assert_eq!(log[2].memory, None);
+		assert_eq!(log[2].storage, Some(vec![StorageDiff { location: U256::zero(), value: U256::one() }]));
+	}
+
+	#[test]
+	fn test_transact_with_state_accesses() {
+		// 60 00 - push 0
+		// 54 - sload
+		// 60 01 - push 1
+		// 60 01 - push 1
+		// 55 - sstore
+		let code = "6000546001600155".from_hex().unwrap();
+		let keypair = Random.generate().unwrap();
+		let t = Transaction {
+			action: Action::Create,
+			value: U256::zero(),
+			data: code,
+			gas: U256::from(100_000),
+			gas_price: U256::zero(),
+			nonce: U256::zero()
+		}.sign(keypair.secret());
+		let sender = t.sender().unwrap();
+		let contract = contract_address(&sender, &U256::zero());
+
+		let mut state_result = get_temp_state();
+		let mut state = state_result.reference_mut();
+		let mut info = EnvInfo::default();
+		info.gas_limit = U256::from(100_000);
+		let engine = TestEngine::new(0);
+		let factory = Factory::new(VMType::Interpreter, 1024 * 32);
+
+		let executed = {
+			let mut ex = Executive::new(&mut state, &info, &engine, &factory);
+			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false, step_logging: None, gas_profiling: false, state_accessing: true };
+			ex.transact(&t, opts).unwrap()
+		};
+
+		let accesses = executed.state_accesses.unwrap();
+		// gas, nonce and fee bookkeeping is summarised apart from `accounts`.
+		assert_eq!(accesses.sender, Some(sender));
+		assert_eq!(accesses.fee_recipient, Some(info.author));
+		assert!(!accesses.accounts.contains_key(&info.author));
+		// the (zero) endowment is still a transfer from the sender.
+		let sender_accesses = &accesses.accounts[&sender];
+		assert!(sender_accesses.account.written);
+		assert!(sender_accesses.storage.is_empty());
+		let contract_accesses = &accesses.accounts[&contract];
+		assert!(contract_accesses.account.written);
+		assert_eq!(contract_accesses.storage[&H256::from(0)], Access { read: true, written: false });
+		// SSTORE is priced by the value it replaces, so the written slot is read as well.
+		assert_eq!(contract_accesses.storage[&H256::from(1)], Access { read: true, written: true });
+
+		// an unrelated transaction paying the same author doesn't conflict.
+		let other = Transaction {
+			action: Action::Create,
+			value: U256::zero(),
+			data: "6000546001600155".from_hex().unwrap(),
+			gas: U256::from(100_000),
+			gas_price: U256::zero(),
+			nonce: U256::zero()
+		}.sign(Random.generate().unwrap().secret());
+		let other_executed = {
+			let mut ex = Executive::new(&mut state, &info, &engine, &factory);
+			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false, step_logging: None, gas_profiling: false, state_accessing: true };
+			ex.transact(&other, opts).unwrap()
+		};
+		let other_accesses = other_executed.state_accesses.unwrap();
+		assert!(!accesses.conflicts_with(&other_accesses));
+		assert!(!other_accesses.conflicts_with(&accesses));
+	}
+
 	evm_test!{test_transact_invalid_sender: test_transact_invalid_sender_jit, test_transact_invalid_sender_int}
 	fn test_transact_invalid_sender(factory: Factory) {
 		let t = Transaction {
@@ -1087,7 +1270,7 @@ mod tests {
 
 		let res = {
 			let mut ex = Executive::new(&mut state, &info, &engine, &factory);
-			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false };
+			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false, step_logging: None, gas_profiling: false, state_accessing: false };
 			ex.transact(&t, opts)
 		};
 
@@ -1119,7 +1302,7 @@ mod tests {
 
 		let res = {
 			let mut ex = Executive::new(&mut state, &info, &engine, &factory);
-			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false };
+			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false, step_logging: None, gas_profiling: false, state_accessing: false };
 			ex.transact(&t, opts)
 		};
 
@@ -1153,7 +1336,7 @@ mod tests {
 
 		let res = {
 			let mut ex = Executive::new(&mut state, &info, &engine, &factory);
-			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false };
+			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false, step_logging: None, gas_profiling: false, state_accessing: false };
 			ex.transact(&t, opts)
 		};
 
@@ -1187,7 +1370,7 @@ mod tests {
 
 		let res = {
 			let mut ex = Executive::new(&mut state, &info, &engine, &factory);
-			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false };
+			let opts = TransactOptions { check_nonce: true, tracing: false, vm_tracing: false, step_logging: None, gas_profiling: false, state_accessing: false };
 			ex.transact(&t, opts)
 		};
 
@@ -24,6 +24,7 @@ use executive::*;
 use evm::{self, Schedule, Ext, ContractCreateResult, MessageCallResult, Factory};
 use types::executed::CallType;
 use trace::{Tracer, VMTracer};
+use evm::interpreter::debugger::Step;
 
 /// Policy for handling output data on `RETURN` opcode.
 pub enum OutputPolicy<'a, 'b> {
@@ -303,6 +304,14 @@ impl<'a, T, V> Ext for Externalities<'a, T, V> where T: 'a + Tracer, V: 'a + VMT
 	fn trace_executed(&mut self, gas_used: U256, stack_push: &[U256], mem_diff: Option<(usize, &[u8])>, store_diff: Option<(U256, U256)>) {
 		self.vm_tracer.trace_executed(gas_used, stack_push, mem_diff, store_diff)
 	}
+
+	fn trace_steps(&self) -> bool {
+		self.vm_tracer.trace_steps()
+	}
+
+	fn trace_step(&mut self, step: &Step) {
+		self.vm_tracer.trace_step(step)
+	}
 }
 
 #[cfg(test)]
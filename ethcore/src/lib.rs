@@ -101,6 +101,8 @@ extern crate bit_set;
 extern crate rlp;
 extern crate ethcore_bloom_journal as bloom_journal;
 extern crate byteorder;
+extern crate num;
+extern crate bn;
 
 #[macro_use]
 extern crate log;
@@ -136,6 +138,8 @@ pub mod miner;
 pub mod snapshot;
 pub mod action_params;
 pub mod db;
+pub mod light;
+pub mod builtin;
 #[macro_use] pub mod evm;
 
 mod cache_manager;
@@ -146,7 +150,6 @@ mod pod_account;
 mod state;
 mod state_db;
 mod account_db;
-mod builtin;
 mod executive;
 mod externalities;
 mod verification;
@@ -162,3 +165,4 @@ mod json_tests;
 
 pub use types::*;
 pub use executive::contract_address;
+pub use state::{State, Savepoint};
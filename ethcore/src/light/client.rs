@@ -0,0 +1,226 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Light client: imports and verifies headers only.
+
+use std::sync::Arc;
+
+use block_import_error::BlockImportError;
+use client::{BlockStatus, BlockQueueInfo};
+use db::COL_STATE;
+use engines::Engine;
+use env_info::{EnvInfo, LastHashes};
+use error::{BlockError, ImportError, ExecutionError};
+use executive::{Executive, Executed, TransactOptions};
+use factory::Factories;
+use header::Header;
+use ids::BlockID;
+use account_db::Factory as AccountFactory;
+use service::ClientIoMessage;
+use spec::Spec;
+use state::State;
+use state_db::StateDB;
+use transaction::SignedTransaction;
+use types::blockchain_info::BlockChainInfo;
+use verification::queue::{self, HeaderQueue};
+use verification::verify_header_family;
+
+use io::IoChannel;
+use util::{Bytes, H256, U256, Uint, Mutex, Database, HashDB};
+use util::journaldb::{self, Algorithm};
+use rlp;
+
+use super::HeaderChain;
+
+// maximum number of headers to import from the queue at once.
+const MAX_IMPORT_BATCH: usize = 2048;
+
+/// Light client. Verifies headers and keeps a header-only chain.
+pub struct Client {
+	engine: Arc<Engine>,
+	db: Arc<Database>,
+	chain: HeaderChain,
+	queue: HeaderQueue,
+	import_lock: Mutex<()>,
+}
+
+impl Client {
+	/// Create a new light client for the given chain spec, backed by the given database.
+	pub fn new(config: queue::Config, spec: &Spec, db: Arc<Database>) -> Self {
+		let engine = spec.engine.clone();
+		let queue = HeaderQueue::new(config, engine.clone(), IoChannel::<ClientIoMessage>::disconnected(), true);
+		Client {
+			engine: engine,
+			db: db.clone(),
+			chain: HeaderChain::new(&spec.genesis_block(), db),
+			queue: queue,
+			import_lock: Mutex::new(()),
+		}
+	}
+
+	/// Queue a header for verification and import.
+	pub fn import_header(&self, header: Header) -> Result<H256, BlockImportError> {
+		if self.chain.is_known(&header.hash()) {
+			return Err(BlockImportError::Import(ImportError::AlreadyInChain));
+		}
+		if self.block_status(BlockID::Hash(header.parent_hash().clone())) == BlockStatus::Unknown {
+			return Err(BlockImportError::Block(BlockError::UnknownParent(header.parent_hash().clone())));
+		}
+		Ok(try!(self.queue.import(header)))
+	}
+
+	/// Import headers which have finished verification in the queue.
+	/// Returns the number of headers imported.
+	pub fn import_verified(&self) -> usize {
+		let _lock = self.import_lock.lock();
+		let mut good = Vec::new();
+		let mut bad = Vec::new();
+
+		for header in self.queue.drain(MAX_IMPORT_BATCH) {
+			let hash = header.hash();
+			let parent = self.chain.block_header(BlockID::Hash(header.parent_hash().clone()))
+				.map(|bytes| rlp::decode::<Header>(&bytes));
+
+			let result = match parent {
+				Some(parent) => verify_header_family(&header, &parent, &*self.engine)
+					.and_then(|_| self.chain.insert(&header)),
+				None => Err(BlockError::UnknownParent(header.parent_hash().clone()).into()),
+			};
+
+			match result {
+				Ok(()) => good.push(hash),
+				Err(e) => {
+					warn!(target: "client", "Stage 3 header verification failed for #{} ({})\nError: {:?}", header.number(), hash, e);
+					bad.push(hash);
+				}
+			}
+		}
+
+		self.queue.mark_as_bad(&bad);
+		self.queue.mark_as_good(&good);
+		if !good.is_empty() {
+			trace!(target: "client", "Imported {} headers, best #{}", good.len(), self.chain.best_block_number());
+		}
+		good.len()
+	}
+
+	/// Wait for the verification queue to empty and import everything in it.
+	pub fn flush_queue(&self) {
+		self.queue.flush();
+		while self.import_verified() != 0 {}
+	}
+
+	/// Get the verification queue information.
+	pub fn queue_info(&self) -> BlockQueueInfo {
+		self.queue.queue_info()
+	}
+
+	/// Get the status of a block.
+	pub fn block_status(&self, id: BlockID) -> BlockStatus {
+		let hash = match id {
+			BlockID::Hash(hash) => Some(hash),
+			other => self.chain.block_header(other).map(|bytes| ::util::sha3::Hashable::sha3(&bytes)),
+		};
+		match hash {
+			Some(ref hash) if self.chain.is_known(hash) => BlockStatus::InChain,
+			Some(hash) => self.queue.status(&hash).into(),
+			None => BlockStatus::Unknown,
+		}
+	}
+
+	/// Get the RLP-encoded header of a block.
+	pub fn block_header(&self, id: BlockID) -> Option<Bytes> {
+		self.chain.block_header(id)
+	}
+
+	/// Get the total difficulty of a block.
+	pub fn block_total_difficulty(&self, id: BlockID) -> Option<U256> {
+		self.chain.block_total_difficulty(id)
+	}
+
+	/// Get information about the header chain.
+	pub fn chain_info(&self) -> BlockChainInfo {
+		self.chain.chain_info()
+	}
+
+	/// Get the hashes of the given block and up to 255 of its ancestors, most recent first.
+	pub fn last_hashes(&self, hash: H256) -> LastHashes {
+		let mut last_hashes = LastHashes::new();
+		last_hashes.resize(256, H256::default());
+		last_hashes[0] = hash;
+		for i in 0..255 {
+			match self.chain.block_header(BlockID::Hash(last_hashes[i].clone())) {
+				Some(header) => last_hashes[i + 1] = rlp::decode::<Header>(&header).parent_hash().clone(),
+				None => break,
+			}
+		}
+		last_hashes
+	}
+
+	/// Execute a transaction on top of the state of the given block using only the
+	/// state trie nodes in `proof`, as `BlockChainClient::call` would on a full node.
+	/// Returns `None` if the proof doesn't contain all the state needed to execute it.
+	pub fn check_transaction_proof(&self, t: &SignedTransaction, header: &Header, proof: &[Bytes]) -> Option<Result<Executed, ExecutionError>> {
+		// proof nodes only ever live in the journal's memory overlay; it is never committed.
+		let mut db = journaldb::new(self.db.clone(), Algorithm::Archive, COL_STATE);
+		for node in proof {
+			db.insert(&node[..]);
+		}
+
+		// proof nodes are keyed by their hash alone, so account storage must not be mangled.
+		let factories = Factories { accountdb: AccountFactory::Plain, ..Default::default() };
+		let mut state = match State::from_partial(StateDB::new_without_bloom(db, 0), header.state_root().clone(), self.engine.account_start_nonce(), factories.clone()) {
+			Ok(state) => state,
+			Err(_) => return None,
+		};
+
+		let env_info = EnvInfo {
+			number: header.number(),
+			author: header.author().clone(),
+			timestamp: header.timestamp(),
+			difficulty: header.difficulty().clone(),
+			last_hashes: Arc::new(self.last_hashes(header.hash())),
+			gas_used: U256::zero(),
+			gas_limit: U256::max_value(),
+		};
+		let options = TransactOptions { tracing: false, vm_tracing: false, check_nonce: false, step_logging: None, gas_profiling: false, state_accessing: false };
+		let engine = &*self.engine;
+
+		let sender = match t.sender() {
+			Ok(sender) => sender,
+			Err(e) => return Some(Err(ExecutionError::TransactionMalformed(format!("Transaction malformed: {:?}", e)))),
+		};
+		let balance = state.balance(&sender);
+		let needed_balance = t.value + t.gas * t.gas_price;
+		if balance < needed_balance {
+			state.add_balance(&sender, &(needed_balance - balance));
+		}
+		let res = Executive::new(&mut state, &env_info, engine, &factories.vm).transact(t, options);
+
+		// any state missing from the proof was read as empty, so the result can't be trusted.
+		if state.is_incomplete() { None } else { Some(res) }
+	}
+
+	/// Get a reference to the header chain.
+	pub fn chain(&self) -> &HeaderChain {
+		&self.chain
+	}
+
+	/// Get a reference to the consensus engine.
+	pub fn engine(&self) -> &Engine {
+		&*self.engine
+	}
+}
@@ -0,0 +1,298 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Header-only blockchain.
+//!
+//! Stores verified headers along with their total difficulty, and keeps an
+//! index of canonical block hashes by number. No bodies, receipts or state
+//! are kept.
+
+use std::sync::Arc;
+
+use db::{COL_HEADERS, COL_EXTRA};
+use error::{BlockError, ImportError, Error};
+use header::{BlockNumber, Header};
+use ids::BlockID;
+use types::blockchain_info::BlockChainInfo;
+use views::{BlockView, HeaderView};
+
+use util::{Bytes, H256, U256, RwLock, Database, DBTransaction, UtilError};
+use rlp::{self, RlpStream, Stream, UntrustedRlp, View};
+
+// key of the best block hash in the extras column.
+const BEST_KEY: &'static [u8] = b"light_best";
+
+// key of the canonical hash for the given number in the extras column.
+fn canon_key(number: BlockNumber) -> [u8; 9] {
+	let mut key = [b'c'; 9];
+	for i in 0..8 {
+		key[i + 1] = (number >> ((7 - i) * 8)) as u8;
+	}
+	key
+}
+
+#[derive(Debug, Clone)]
+struct BestHeader {
+	hash: H256,
+	number: BlockNumber,
+	total_difficulty: U256,
+}
+
+/// Header-only chain. All inserted headers must already have passed verification.
+pub struct HeaderChain {
+	db: Arc<Database>,
+	genesis_hash: H256,
+	best: RwLock<BestHeader>,
+}
+
+impl HeaderChain {
+	/// Create a new header chain backed by the given database, inserting the
+	/// header of the given genesis block if the database is empty.
+	pub fn new(genesis: &[u8], db: Arc<Database>) -> Self {
+		let genesis = BlockView::new(genesis).header_view();
+		let genesis_hash = genesis.hash();
+
+		let best_hash = db.get(COL_EXTRA, BEST_KEY).expect("Low level database error.").map(|hash| H256::from_slice(&hash));
+		let best = match best_hash {
+			Some(hash) => {
+				let (number, total_difficulty) = Self::entry(&db, &hash).expect("Best header is always stored; qed");
+				BestHeader { hash: hash, number: number, total_difficulty: total_difficulty }
+			}
+			None => {
+				let mut batch = DBTransaction::new(&db);
+				batch.put(COL_HEADERS, &genesis_hash, genesis.rlp().as_raw());
+				batch.put(COL_EXTRA, &genesis_hash, &Self::encode_entry(0, &genesis.difficulty()));
+				batch.put(COL_EXTRA, &canon_key(0), &genesis_hash);
+				batch.put(COL_EXTRA, BEST_KEY, &genesis_hash);
+				db.write(batch).expect("Low level database error.");
+
+				BestHeader { hash: genesis_hash, number: 0, total_difficulty: genesis.difficulty() }
+			}
+		};
+
+		HeaderChain {
+			db: db,
+			genesis_hash: genesis_hash,
+			best: RwLock::new(best),
+		}
+	}
+
+	fn encode_entry(number: BlockNumber, total_difficulty: &U256) -> Bytes {
+		let mut stream = RlpStream::new_list(2);
+		stream.append(&number).append(total_difficulty);
+		stream.out()
+	}
+
+	// number and total difficulty of the header with the given hash.
+	fn entry(db: &Database, hash: &H256) -> Option<(BlockNumber, U256)> {
+		db.get(COL_EXTRA, hash).expect("Low level database error.").map(|entry| {
+			let rlp = UntrustedRlp::new(&entry);
+			let number = rlp.val_at(0).expect("Entries are always written by this module; qed");
+			let total_difficulty = rlp.val_at(1).expect("Entries are always written by this module; qed");
+			(number, total_difficulty)
+		})
+	}
+
+	/// Insert a verified header. Its parent must already be in the chain.
+	/// The header becomes the new best if its total difficulty is higher than the current best.
+	pub fn insert(&self, header: &Header) -> Result<(), Error> {
+		let hash = header.hash();
+		if self.is_known(&hash) {
+			return Err(ImportError::AlreadyInChain.into());
+		}
+
+		let parent_td = match Self::entry(&self.db, header.parent_hash()) {
+			Some((_, td)) => td,
+			None => return Err(BlockError::UnknownParent(header.parent_hash().clone()).into()),
+		};
+		let total_difficulty = parent_td + *header.difficulty();
+		let number = header.number();
+
+		let mut batch = DBTransaction::new(&self.db);
+		batch.put(COL_HEADERS, &hash, &rlp::encode(header));
+		batch.put(COL_EXTRA, &hash, &Self::encode_entry(number, &total_difficulty));
+
+		let mut best = self.best.write();
+		if total_difficulty > best.total_difficulty {
+			// re-point the canonical index at the new branch until it meets the old one.
+			batch.put(COL_EXTRA, &canon_key(number), &hash);
+			let mut cur_hash = header.parent_hash().clone();
+			let mut cur_number = number - 1;
+			while self.block_hash(cur_number).map_or(true, |h| h != cur_hash) {
+				batch.put(COL_EXTRA, &canon_key(cur_number), &cur_hash);
+				let parent = self.header_by_hash(&cur_hash).expect("Ancestors of inserted headers are always stored; qed");
+				cur_hash = HeaderView::new(&parent).parent_hash();
+				cur_number -= 1;
+			}
+
+			// a higher total difficulty doesn't imply a longer chain.
+			for stale in (number + 1)..(best.number + 1) {
+				batch.delete(COL_EXTRA, &canon_key(stale));
+			}
+
+			batch.put(COL_EXTRA, BEST_KEY, &hash);
+			*best = BestHeader { hash: hash, number: number, total_difficulty: total_difficulty };
+		}
+
+		try!(self.db.write(batch).map_err(UtilError::SimpleString));
+		Ok(())
+	}
+
+	/// Whether the header with the given hash is stored.
+	pub fn is_known(&self, hash: &H256) -> bool {
+		Self::entry(&self.db, hash).is_some()
+	}
+
+	/// Get the canonical block hash for the given number.
+	pub fn block_hash(&self, number: BlockNumber) -> Option<H256> {
+		self.db.get(COL_EXTRA, &canon_key(number)).expect("Low level database error.")
+			.map(|hash| H256::from_slice(&hash))
+	}
+
+	/// Get the number of the header with the given hash.
+	pub fn block_number(&self, hash: &H256) -> Option<BlockNumber> {
+		Self::entry(&self.db, hash).map(|(number, _)| number)
+	}
+
+	fn header_by_hash(&self, hash: &H256) -> Option<Bytes> {
+		self.db.get(COL_HEADERS, hash).expect("Low level database error.").map(|h| h.to_vec())
+	}
+
+	fn resolve(&self, id: BlockID) -> Option<H256> {
+		match id {
+			BlockID::Hash(hash) => Some(hash),
+			BlockID::Number(number) => self.block_hash(number),
+			BlockID::Earliest => Some(self.genesis_hash),
+			BlockID::Latest | BlockID::Pending => Some(self.best.read().hash),
+		}
+	}
+
+	/// Get the RLP-encoded header of a block.
+	pub fn block_header(&self, id: BlockID) -> Option<Bytes> {
+		self.resolve(id).and_then(|hash| self.header_by_hash(&hash))
+	}
+
+	/// Get the total difficulty of a block.
+	pub fn block_total_difficulty(&self, id: BlockID) -> Option<U256> {
+		self.resolve(id).and_then(|hash| Self::entry(&self.db, &hash)).map(|(_, td)| td)
+	}
+
+	/// Get the best block hash.
+	pub fn best_block_hash(&self) -> H256 {
+		self.best.read().hash
+	}
+
+	/// Get the best block number.
+	pub fn best_block_number(&self) -> BlockNumber {
+		self.best.read().number
+	}
+
+	/// Get information about the chain.
+	pub fn chain_info(&self) -> BlockChainInfo {
+		let best = self.best.read();
+		BlockChainInfo {
+			total_difficulty: best.total_difficulty,
+			pending_total_difficulty: best.total_difficulty,
+			genesis_hash: self.genesis_hash,
+			best_block_hash: best.hash,
+			best_block_number: best.number,
+			ancient_block_hash: None,
+			ancient_block_number: None,
+			first_block_hash: None,
+			first_block_number: None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use super::HeaderChain;
+	use db::NUM_COLUMNS;
+	use header::Header;
+	use ids::BlockID;
+	use spec::Spec;
+
+	use devtools::RandomTempPath;
+	use util::{H256, U256};
+	use util::kvdb::{Database, DatabaseConfig};
+
+	fn make_chain(path: &RandomTempPath) -> HeaderChain {
+		let db = Database::open(&DatabaseConfig::with_columns(NUM_COLUMNS), path.as_path().to_str().unwrap()).unwrap();
+		HeaderChain::new(&Spec::new_test().genesis_block(), Arc::new(db))
+	}
+
+	fn child_of(parent: &Header, difficulty: u64, nonce: u64) -> Header {
+		let mut header = Header::new();
+		header.set_parent_hash(parent.hash());
+		header.set_number(parent.number() + 1);
+		header.set_difficulty(U256::from(difficulty));
+		header.set_timestamp(parent.timestamp() + nonce);
+		header
+	}
+
+	#[test]
+	fn basic_insertion() {
+		let path = RandomTempPath::new();
+		let chain = make_chain(&path);
+		let genesis: Header = ::rlp::decode(&chain.block_header(BlockID::Earliest).unwrap());
+
+		let mut parent = genesis.clone();
+		for _ in 0..10 {
+			let header = child_of(&parent, 1000, 1);
+			chain.insert(&header).unwrap();
+			parent = header;
+		}
+
+		assert_eq!(chain.best_block_number(), 10);
+		assert_eq!(chain.best_block_hash(), parent.hash());
+		assert_eq!(chain.block_hash(10), Some(parent.hash()));
+		assert!(chain.insert(&parent).is_err());
+		assert!(chain.insert(&child_of(&child_of(&parent, 1, 1), 1, 1)).is_err());
+	}
+
+	#[test]
+	fn reorganises_to_heavier_branch() {
+		let path = RandomTempPath::new();
+		let chain = make_chain(&path);
+		let genesis: Header = ::rlp::decode(&chain.block_header(BlockID::Earliest).unwrap());
+
+		// long, light branch.
+		let mut parent = genesis.clone();
+		for _ in 0..5 {
+			let header = child_of(&parent, 10, 1);
+			chain.insert(&header).unwrap();
+			parent = header;
+		}
+		assert_eq!(chain.best_block_number(), 5);
+
+		// short, heavy branch.
+		let first = child_of(&genesis, 1000, 2);
+		let second = child_of(&first, 1000, 2);
+		chain.insert(&first).unwrap();
+		chain.insert(&second).unwrap();
+
+		assert_eq!(chain.best_block_hash(), second.hash());
+		assert_eq!(chain.best_block_number(), 2);
+		assert_eq!(chain.block_hash(1), Some(first.hash()));
+		assert_eq!(chain.block_hash(3), None);
+		assert!(chain.block_hash(5).is_none());
+		assert!(chain.is_known(&parent.hash()));
+		assert_eq!(chain.block_number(&parent.hash()), Some(5));
+		assert!(chain.block_header(BlockID::Hash(H256::from(1))).is_none());
+	}
+}
@@ -0,0 +1,286 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Merkle proof generation and checking for light clients.
+//!
+//! Full nodes record the trie nodes touched while answering a request and
+//! ship them to the light client, which rebuilds just enough of the state
+//! from them to check the answer against a header it already trusts.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use rlp::{UntrustedRlp, View, DecoderError};
+use util::{Bytes, H256, U256, Mutex, HashDB, MemoryDB, JournalDB, Database, DBTransaction};
+use util::{Trie, TrieDB, TrieError, UtilError};
+
+/// A `JournalDB` which records the value of every node read through it.
+///
+/// Wrapping the state database of a full node in this and executing
+/// something against it yields the nodes a light client needs to execute
+/// the same thing itself.
+pub struct RecordingDB {
+	inner: Box<JournalDB>,
+	recorded: Arc<Mutex<HashMap<H256, Bytes>>>,
+}
+
+impl RecordingDB {
+	/// Wrap a `JournalDB`.
+	pub fn new(inner: Box<JournalDB>) -> Self {
+		RecordingDB {
+			inner: inner,
+			recorded: Arc::new(Mutex::new(HashMap::new())),
+		}
+	}
+
+	/// Get a handle to the nodes recorded so far. Shared between clones of this database.
+	pub fn recorded(&self) -> Arc<Mutex<HashMap<H256, Bytes>>> {
+		self.recorded.clone()
+	}
+}
+
+impl HashDB for RecordingDB {
+	fn keys(&self) -> HashMap<H256, i32> {
+		self.inner.keys()
+	}
+
+	fn get(&self, key: &H256) -> Option<&[u8]> {
+		let val = self.inner.get(key);
+		if let Some(val) = val {
+			self.recorded.lock().entry(key.clone()).or_insert_with(|| val.to_vec());
+		}
+		val
+	}
+
+	fn contains(&self, key: &H256) -> bool {
+		self.get(key).is_some()
+	}
+
+	fn insert(&mut self, value: &[u8]) -> H256 {
+		self.inner.insert(value)
+	}
+
+	fn emplace(&mut self, key: H256, value: Bytes) {
+		self.inner.emplace(key, value)
+	}
+
+	fn remove(&mut self, key: &H256) {
+		self.inner.remove(key)
+	}
+}
+
+impl JournalDB for RecordingDB {
+	fn boxed_clone(&self) -> Box<JournalDB> {
+		Box::new(RecordingDB {
+			inner: self.inner.boxed_clone(),
+			recorded: self.recorded.clone(),
+		})
+	}
+
+	fn mem_used(&self) -> usize { self.inner.mem_used() }
+
+	fn is_empty(&self) -> bool { self.inner.is_empty() }
+
+	fn earliest_era(&self) -> Option<u64> { self.inner.earliest_era() }
+
+	fn latest_era(&self) -> Option<u64> { self.inner.latest_era() }
+
+	fn journal_under(&mut self, batch: &mut DBTransaction, now: u64, id: &H256) -> Result<u32, UtilError> {
+		self.inner.journal_under(batch, now, id)
+	}
+
+	fn mark_canonical(&mut self, batch: &mut DBTransaction, era: u64, id: &H256) -> Result<u32, UtilError> {
+		self.inner.mark_canonical(batch, era, id)
+	}
+
+	fn inject(&mut self, batch: &mut DBTransaction) -> Result<u32, UtilError> {
+		self.inner.inject(batch)
+	}
+
+	fn state(&self, id: &H256) -> Option<Bytes> { self.inner.state(id) }
+
+	fn is_pruned(&self) -> bool { self.inner.is_pruned() }
+
+	fn backing(&self) -> &Arc<Database> { self.inner.backing() }
+
+	fn flush(&self) { self.inner.flush() }
+
+	fn consolidate(&mut self, overlay: MemoryDB) { self.inner.consolidate(overlay) }
+}
+
+/// Errors which can occur when checking a proof.
+#[derive(Debug)]
+pub enum Error {
+	/// The proof was incomplete or didn't match the expected root.
+	Trie(Box<TrieError>),
+	/// A proven value was malformed.
+	Decoder(DecoderError),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Trie(ref err) => write!(f, "Bad proof: {}", err),
+			Error::Decoder(ref err) => write!(f, "Malformed proven value: {}", err),
+		}
+	}
+}
+
+impl From<Box<TrieError>> for Error {
+	fn from(err: Box<TrieError>) -> Self {
+		Error::Trie(err)
+	}
+}
+
+impl From<DecoderError> for Error {
+	fn from(err: DecoderError) -> Self {
+		Error::Decoder(err)
+	}
+}
+
+/// The basic fields of an account, as stored in the state trie.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicAccount {
+	/// The account's nonce.
+	pub nonce: U256,
+	/// The account's balance.
+	pub balance: U256,
+	/// The root of the account's storage trie.
+	pub storage_root: H256,
+	/// The hash of the account's code.
+	pub code_hash: H256,
+}
+
+impl BasicAccount {
+	/// Decode an account from its (untrusted) RLP representation.
+	pub fn from_rlp(rlp: &[u8]) -> Result<Self, DecoderError> {
+		let r = UntrustedRlp::new(rlp);
+		Ok(BasicAccount {
+			nonce: try!(r.val_at(0)),
+			balance: try!(r.val_at(1)),
+			storage_root: try!(r.val_at(2)),
+			code_hash: try!(r.val_at(3)),
+		})
+	}
+}
+
+// build an in-memory database from the nodes of a proof.
+fn proof_db(proof: &[Bytes]) -> MemoryDB {
+	let mut db = MemoryDB::new();
+	for node in proof {
+		db.insert(&node[..]);
+	}
+	db
+}
+
+/// Check a proof of an account's existence or nonexistence in the state trie
+/// with the given root. `address_hash` is the hash of the account's address.
+/// Yields `None` if the account is proven not to exist.
+pub fn check_account_proof(root: &H256, address_hash: &H256, proof: &[Bytes]) -> Result<Option<BasicAccount>, Error> {
+	let db = proof_db(proof);
+	let trie = try!(TrieDB::new(&db, root));
+	match try!(trie.get(address_hash)) {
+		Some(rlp) => Ok(Some(try!(BasicAccount::from_rlp(rlp)))),
+		None => Ok(None),
+	}
+}
+
+/// Check a proof of a storage value in the storage trie with the given root.
+/// `key_hash` is the hash of the storage key. Absent keys are proven as zero.
+pub fn check_storage_proof(storage_root: &H256, key_hash: &H256, proof: &[Bytes]) -> Result<H256, Error> {
+	let db = proof_db(proof);
+	let trie = try!(TrieDB::new(&db, storage_root));
+	match try!(trie.get(key_hash)) {
+		Some(rlp) => Ok(H256::from(try!(UntrustedRlp::new(rlp).as_val::<U256>()))),
+		None => Ok(H256::new()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use util::{Address, Hashable, H256, U256, Bytes};
+	use state::State;
+	use state_db::StateDB;
+	use factory::Factories;
+	use tests::helpers::{get_temp_state, get_temp_state_in};
+	use devtools::RandomTempPath;
+
+	fn prepare(state: &mut State) -> Address {
+		let address = Address::from(0x1234);
+		state.add_balance(&address, &U256::from(69u64));
+		state.set_storage(&address, H256::from(&U256::from(1u64)), H256::from(&U256::from(42u64)));
+		state.commit().unwrap();
+		address
+	}
+
+	#[test]
+	fn account_proof() {
+		let mut state_result = get_temp_state();
+		let state = state_result.reference_mut();
+		let address = prepare(state);
+		let proof = state.prove_account(address.sha3(), 0).unwrap();
+
+		let account = check_account_proof(state.root(), &address.sha3(), &proof).unwrap().unwrap();
+		assert_eq!(account.balance, U256::from(69u64));
+
+		let absent = Address::from(0x5678);
+		let proof = state.prove_account(absent.sha3(), 0).unwrap();
+		assert_eq!(check_account_proof(state.root(), &absent.sha3(), &proof).unwrap(), None);
+	}
+
+	#[test]
+	fn storage_proof() {
+		let mut state_result = get_temp_state();
+		let state = state_result.reference_mut();
+		let address = prepare(state);
+		let account_proof = state.prove_account(address.sha3(), 0).unwrap();
+		let account = check_account_proof(state.root(), &address.sha3(), &account_proof).unwrap().unwrap();
+
+		let key = H256::from(&U256::from(1u64)).sha3();
+		let proof = state.prove_storage(address.sha3(), key, 0).unwrap();
+		let value = check_storage_proof(&account.storage_root, &key, &proof).unwrap();
+		assert_eq!(value, H256::from(&U256::from(42u64)));
+	}
+
+	#[test]
+	fn incomplete_proof_rejected() {
+		let mut state_result = get_temp_state();
+		let state = state_result.reference_mut();
+		let address = prepare(state);
+		let mut proof = state.prove_account(address.sha3(), 0).unwrap();
+		proof.remove(0);
+		assert!(check_account_proof(state.root(), &address.sha3(), &proof).is_err());
+	}
+
+	#[test]
+	fn recording_db_records_reads() {
+		let path = RandomTempPath::new();
+		let mut state = get_temp_state_in(path.as_path());
+		let address = prepare(&mut state);
+		let (root, db) = state.drop();
+
+		let recorder = RecordingDB::new(db.journal_db().boxed_clone());
+		let recorded = recorder.recorded();
+		let proving_state = State::from_existing(StateDB::new(Box::new(recorder), 0), root, U256::zero(), Factories::default()).unwrap();
+		assert_eq!(proving_state.balance(&address), U256::from(69u64));
+
+		let proof: Vec<Bytes> = recorded.lock().values().cloned().collect();
+		let account = check_account_proof(&root, &address.sha3(), &proof).unwrap().unwrap();
+		assert_eq!(account.balance, U256::from(69u64));
+	}
+}
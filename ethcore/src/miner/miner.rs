@@ -22,7 +22,7 @@ use util::using_queue::{UsingQueue, GetAction};
 use account_provider::AccountProvider;
 use views::{BlockView, HeaderView};
 use state::State;
-use client::{MiningBlockChainClient, Executive, Executed, EnvInfo, TransactOptions, BlockID, CallAnalytics};
+use client::{MiningBlockChainClient, Executive, Executed, EnvInfo, TransactOptions, BlockID, CallAnalytics, StateOverride};
 use executive::contract_address;
 use block::{ClosedBlock, SealedBlock, IsBlock, Block};
 use error::*;
@@ -561,7 +561,7 @@ impl MinerService for Miner {
 		}
 	}
 
-	fn call(&self, chain: &MiningBlockChainClient, t: &SignedTransaction, analytics: CallAnalytics) -> Result<Executed, CallError> {
+	fn call(&self, chain: &MiningBlockChainClient, t: &SignedTransaction, state_override: &StateOverride, analytics: CallAnalytics) -> Result<Executed, CallError> {
 		let sealing_work = self.sealing_work.lock();
 		match sealing_work.queue.peek_last_ref() {
 			Some(work) => {
@@ -581,6 +581,7 @@ impl MinerService for Miner {
 				};
 				// that's just a copy of the state.
 				let mut state = block.state().clone();
+				state.apply_override(state_override);
 				let original_state = if analytics.state_diffing { Some(state.clone()) } else { None };
 
 				let sender = try!(t.sender().map_err(|e| {
@@ -593,7 +594,7 @@ impl MinerService for Miner {
 					// give the sender a sufficient balance
 					state.add_balance(&sender, &(needed_balance - balance));
 				}
-				let options = TransactOptions { tracing: analytics.transaction_tracing, vm_tracing: analytics.vm_tracing, check_nonce: false };
+				let options = TransactOptions { tracing: analytics.transaction_tracing, vm_tracing: analytics.vm_tracing, check_nonce: false, step_logging: analytics.step_logging, gas_profiling: analytics.gas_profiling, state_accessing: analytics.state_accessing };
 				let mut ret = try!(Executive::new(&mut state, &env_info, &*self.engine, chain.vm_factory()).transact(t, options));
 
 				// TODO gav move this into Executive.
@@ -602,7 +603,7 @@ impl MinerService for Miner {
 				Ok(ret)
 			},
 			None => {
-				chain.call(t, BlockID::Latest, analytics)
+				chain.call(t, BlockID::Latest, state_override, analytics)
 			}
 		}
 	}
@@ -781,6 +782,10 @@ impl MinerService for Miner {
 		imported
 	}
 
+	fn is_local_transaction(&self, hash: &H256) -> bool {
+		self.transaction_queue.lock().is_local_transaction(hash)
+	}
+
 	fn all_transactions(&self) -> Vec<SignedTransaction> {
 		let queue = self.transaction_queue.lock();
 		queue.top_transactions()
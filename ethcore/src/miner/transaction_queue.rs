@@ -833,6 +833,11 @@ impl TransactionQueue {
 		self.current.by_priority.iter().any(|tx| tx.origin == TransactionOrigin::Local)
 	}
 
+	/// Returns true if transaction with given hash is in the queue and was submitted to this node.
+	pub fn is_local_transaction(&self, hash: &H256) -> bool {
+		self.by_hash.get(hash).map_or(false, |tx| tx.origin == TransactionOrigin::Local)
+	}
+
 	/// Finds transaction in the queue by hash (if any)
 	pub fn find(&self, hash: &H256) -> Option<SignedTransaction> {
 		match self.by_hash.get(hash) { Some(transaction_ref) => Some(transaction_ref.transaction.clone()), None => None }
@@ -2167,6 +2172,23 @@ mod test {
 		assert_eq!(txq.has_local_pending_transactions(), true);
 	}
 
+	#[test]
+	fn should_tell_if_transaction_is_local() {
+		// given
+		let mut txq = TransactionQueue::default();
+		let (tx1, tx2) = new_tx_pair_default(1.into(), 0.into());
+		let (hash1, hash2) = (tx1.hash(), tx2.hash());
+
+		// when
+		txq.add(tx1, &default_account_details, TransactionOrigin::External).unwrap();
+		txq.add(tx2, &default_account_details, TransactionOrigin::Local).unwrap();
+
+		// then
+		assert_eq!(txq.is_local_transaction(&hash1), false);
+		assert_eq!(txq.is_local_transaction(&hash2), true);
+		assert_eq!(txq.is_local_transaction(&H256::default()), false);
+	}
+
 	#[test]
 	fn should_keep_right_order_in_future() {
 		// given
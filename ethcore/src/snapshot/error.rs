@@ -35,6 +35,8 @@ pub enum Error {
 	IncompleteChain,
 	/// Old starting block in a pruned database.
 	OldBlockPrunedDB,
+	/// Snapshot to replay from is not an ancestor of the target block.
+	ReplayBaseNotAncestor(H256),
 	/// Missing code.
 	MissingCode(Vec<H256>),
 	/// Unrecognized code encoding.
@@ -54,7 +56,9 @@ impl fmt::Display for Error {
 			Error::BlockNotFound(ref hash) => write!(f, "Block not found in chain: {}", hash),
 			Error::IncompleteChain => write!(f, "Cannot create snapshot due to incomplete chain."),
 			Error::OldBlockPrunedDB => write!(f, "Attempted to create a snapshot at an old block while using \
-				a pruned database. Please re-run with the --pruning archive flag."),
+				a pruned database. Please re-run with the --pruning archive or --replay flag."),
+			Error::ReplayBaseNotAncestor(ref hash) => write!(f, "Snapshot at block {} is not an ancestor of the \
+				requested block and cannot be replayed from.", hash),
 			Error::MissingCode(ref missing) => write!(f, "Incomplete snapshot: {} contract codes not found.", missing.len()),
 			Error::UnrecognizedCodeState(state) => write!(f, "Unrecognized code encoding ({})", state),
 			Error::Io(ref err) => err.fmt(f),
@@ -140,4 +140,81 @@ fn guards_delete_folders() {
 
 	drop(service);
 	assert!(!path.exists());
-}
\ No newline at end of file
+}
+
+#[test]
+fn replayed_snapshot_at_old_block() {
+	use snapshot::io::{PackedReader, PackedWriter, SnapshotReader};
+	use views::HeaderView;
+
+	const NUM_BLOCKS: u32 = 100;
+	const AT: u64 = 40;
+
+	let gas_prices = vec![1.into(), 2.into(), 3.into(), 999.into()];
+	let client = generate_dummy_client_with_spec_and_data(Spec::new_null, NUM_BLOCKS, 5, &gas_prices);
+
+	let path = RandomTempPath::create_dir();
+	let mut snapshot_path = path.as_path().clone();
+	let mut scratch_path = path.as_path().clone();
+	snapshot_path.push("snapshot");
+	scratch_path.push("scratch");
+
+	let writer = PackedWriter::new(&snapshot_path).unwrap();
+	let progress = snapshot::Progress::default();
+	client.take_snapshot_replayed(writer, BlockID::Number(AT), &Spec::new_null(), None, &scratch_path, &progress).unwrap();
+
+	let reader = PackedReader::new(&snapshot_path).unwrap().unwrap();
+	let header = client.block_header(BlockID::Number(AT)).unwrap();
+	let header = HeaderView::new(&header);
+
+	assert_eq!(reader.manifest().block_number, AT);
+	assert_eq!(reader.manifest().block_hash, header.hash());
+	assert_eq!(reader.manifest().state_root, header.state_root());
+}
+
+#[test]
+fn replayed_snapshot_from_base_snapshot() {
+	use snapshot::io::{PackedReader, PackedWriter, SnapshotReader};
+	use views::HeaderView;
+	use error::Error;
+
+	const NUM_BLOCKS: u32 = 100;
+	const BASE: u64 = 20;
+	const AT: u64 = 40;
+
+	let gas_prices = vec![1.into(), 2.into(), 3.into(), 999.into()];
+	let client = generate_dummy_client_with_spec_and_data(Spec::new_null, NUM_BLOCKS, 5, &gas_prices);
+
+	let path = RandomTempPath::create_dir();
+	let mut base_path = path.as_path().clone();
+	let mut snapshot_path = path.as_path().clone();
+	let mut scratch_path = path.as_path().clone();
+	base_path.push("base");
+	snapshot_path.push("snapshot");
+	scratch_path.push("scratch");
+	let progress = snapshot::Progress::default();
+
+	let writer = PackedWriter::new(&base_path).unwrap();
+	client.take_snapshot_replayed(writer, BlockID::Number(BASE), &Spec::new_null(), None, &scratch_path, &progress).unwrap();
+	let base = PackedReader::new(&base_path).unwrap().unwrap();
+
+	// the scratch database left by the first replay is not reused.
+	let writer = PackedWriter::new(&snapshot_path).unwrap();
+	client.take_snapshot_replayed(writer, BlockID::Number(AT), &Spec::new_null(), Some(&base), &scratch_path, &progress).unwrap();
+
+	let reader = PackedReader::new(&snapshot_path).unwrap().unwrap();
+	let header = client.block_header(BlockID::Number(AT)).unwrap();
+	let header = HeaderView::new(&header);
+	assert_eq!(reader.manifest().block_number, AT);
+	assert_eq!(reader.manifest().block_hash, header.hash());
+	assert_eq!(reader.manifest().state_root, header.state_root());
+
+	// a base after the target can't be replayed from.
+	let mut later_path = path.as_path().clone();
+	later_path.push("later");
+	let writer = PackedWriter::new(&later_path).unwrap();
+	match client.take_snapshot_replayed(writer, BlockID::Number(BASE - 1), &Spec::new_null(), Some(&base), &scratch_path, &progress) {
+		Err(Error::Snapshot(snapshot::Error::ReplayBaseNotAncestor(hash))) => assert_eq!(hash, base.manifest().block_hash),
+		other => panic!("unexpected result: {:?}", other.map(|_| ())),
+	}
+}
@@ -17,7 +17,9 @@
 //! Parameters for a block chain.
 
 use util::*;
-use builtin::Builtin;
+use builtin::{Builtin, BuiltinRegistry};
+use evm::{Schedule, ScheduleOverride};
+use env_info::EnvInfo;
 use engines::{Engine, NullEngine, InstantSeal, BasicAuthority};
 use pod_state::*;
 use account_db::*;
@@ -43,20 +45,129 @@ pub struct CommonParams {
 	pub subprotocol_name: String,
 	/// Minimum gas limit.
 	pub min_gas_limit: U256,
-	/// Fork block to check.
-	pub fork_block: Option<(BlockNumber, H256)>,
+	/// Fork blocks to check.
+	pub fork_blocks: Vec<(BlockNumber, H256)>,
+	/// Gas schedule overrides and the blocks they take effect from, in ascending block order.
+	pub schedule_overrides: Vec<(BlockNumber, ScheduleOverride)>,
 }
 
-impl From<ethjson::spec::Params> for CommonParams {
-	fn from(p: ethjson::spec::Params) -> Self {
-		CommonParams {
+impl CommonParams {
+	/// Convert the JSON parameters, checking the gas schedule overrides.
+	pub fn from_json(p: ethjson::spec::Params) -> Result<Self, String> {
+		let mut schedule_overrides = Vec::new();
+		if let Some(s) = p.schedule {
+			schedule_overrides.push((0, try!(Self::schedule_override(s))));
+		}
+		for t in p.schedule_transitions.unwrap_or_else(Vec::new) {
+			schedule_overrides.push((t.block.into(), try!(Self::schedule_override(t.schedule))));
+		}
+		schedule_overrides.sort_by_key(|&(n, _)| n);
+
+		Ok(CommonParams {
 			account_start_nonce: p.account_start_nonce.into(),
 			maximum_extra_data_size: p.maximum_extra_data_size.into(),
 			network_id: p.network_id.into(),
 			subprotocol_name: p.subprotocol_name.unwrap_or_else(|| "eth".to_owned()),
 			min_gas_limit: p.min_gas_limit.into(),
-			fork_block: if let (Some(n), Some(h)) = (p.fork_block, p.fork_hash) { Some((n.into(), h.into())) } else { None },
+			fork_blocks: {
+				let mut forks = Vec::new();
+				if let (Some(n), Some(h)) = (p.fork_block, p.fork_hash) {
+					forks.push((n.into(), h.into()));
+				}
+				forks.extend(p.fork_checkpoints.unwrap_or_else(Vec::new).into_iter().map(|c| (c.number.into(), c.hash.into())));
+				forks
+			},
+			schedule_overrides: schedule_overrides,
+		})
+	}
+
+	/// Apply the gas schedule overrides active at block `number` on top of the engine's `schedule`.
+	pub fn override_schedule(&self, number: BlockNumber, schedule: &mut Schedule) {
+		for &(_, ref o) in self.schedule_overrides.iter().take_while(|&&(n, _)| n <= number) {
+			o.apply(schedule);
+		}
+	}
+
+	fn schedule_override(o: ethjson::spec::Schedule) -> Result<ScheduleOverride, String> {
+		fn gas(name: &str, v: Option<ethjson::uint::Uint>) -> Result<Option<usize>, String> {
+			match v {
+				Some(v) => {
+					let v: U256 = v.into();
+					if v > U256::from(usize::max_value()) {
+						return Err(format!("Gas schedule override {} is too large: {}", name, v));
+					}
+					Ok(Some(v.low_u64() as usize))
+				},
+				None => Ok(None),
+			}
+		}
+
+		let tier_step_gas = match o.tier_step_gas {
+			Some(v) => {
+				let mut tiers = [0usize; 8];
+				for (gas_tier, v) in tiers.iter_mut().zip(v.iter()) {
+					*gas_tier = try!(gas("tierStepGas", Some(*v))).expect("value given; qed");
+				}
+				Some(tiers)
+			},
+			None => None,
+		};
+		let quad_coeff_div = try!(gas("quadCoeffDiv", o.quad_coeff_div));
+		if quad_coeff_div == Some(0) {
+			return Err("Gas schedule override quadCoeffDiv must not be zero".into());
+		}
+
+		Ok(ScheduleOverride {
+			exceptional_failed_code_deposit: o.exceptional_failed_code_deposit,
+			have_delegate_call: o.have_delegate_call,
+			stack_limit: try!(gas("stackLimit", o.stack_limit)),
+			max_depth: try!(gas("maxDepth", o.max_depth)),
+			tier_step_gas: tier_step_gas,
+			exp_gas: try!(gas("expGas", o.exp_gas)),
+			exp_byte_gas: try!(gas("expByteGas", o.exp_byte_gas)),
+			sha3_gas: try!(gas("sha3Gas", o.sha3_gas)),
+			sha3_word_gas: try!(gas("sha3WordGas", o.sha3_word_gas)),
+			sload_gas: try!(gas("sloadGas", o.sload_gas)),
+			sstore_set_gas: try!(gas("sstoreSetGas", o.sstore_set_gas)),
+			sstore_reset_gas: try!(gas("sstoreResetGas", o.sstore_reset_gas)),
+			sstore_refund_gas: try!(gas("sstoreRefundGas", o.sstore_refund_gas)),
+			jumpdest_gas: try!(gas("jumpdestGas", o.jumpdest_gas)),
+			log_gas: try!(gas("logGas", o.log_gas)),
+			log_data_gas: try!(gas("logDataGas", o.log_data_gas)),
+			log_topic_gas: try!(gas("logTopicGas", o.log_topic_gas)),
+			create_gas: try!(gas("createGas", o.create_gas)),
+			call_gas: try!(gas("callGas", o.call_gas)),
+			call_stipend: try!(gas("callStipend", o.call_stipend)),
+			call_value_transfer_gas: try!(gas("callValueTransferGas", o.call_value_transfer_gas)),
+			call_new_account_gas: try!(gas("callNewAccountGas", o.call_new_account_gas)),
+			suicide_refund_gas: try!(gas("suicideRefundGas", o.suicide_refund_gas)),
+			memory_gas: try!(gas("memoryGas", o.memory_gas)),
+			quad_coeff_div: quad_coeff_div,
+			create_data_gas: try!(gas("createDataGas", o.create_data_gas)),
+			tx_gas: try!(gas("txGas", o.tx_gas)),
+			tx_create_gas: try!(gas("txCreateGas", o.tx_create_gas)),
+			tx_data_zero_gas: try!(gas("txDataZeroGas", o.tx_data_zero_gas)),
+			tx_data_non_zero_gas: try!(gas("txDataNonZeroGas", o.tx_data_non_zero_gas)),
+			copy_gas: try!(gas("copyGas", o.copy_gas)),
+			extcodesize_gas: try!(gas("extcodesizeGas", o.extcodesize_gas)),
+			extcodecopy_base_gas: try!(gas("extcodecopyBaseGas", o.extcodecopy_base_gas)),
+			balance_gas: try!(gas("balanceGas", o.balance_gas)),
+			suicide_gas: try!(gas("suicideGas", o.suicide_gas)),
+			suicide_to_new_account_cost: try!(gas("suicideToNewAccountCost", o.suicide_to_new_account_cost)),
+			sub_gas_cap_divisor: try!(gas("subGasCapDivisor", o.sub_gas_cap_divisor)).map(|v| match v { 0 => None, v => Some(v) }),
+		})
+	}
+
+	/// Check the engine's schedule with the overrides applied wherever an override takes effect.
+	fn check_schedules(&self, engine: &Engine) -> Result<(), String> {
+		let blocks = ::std::iter::once(0).chain(self.schedule_overrides.iter().map(|&(n, _)| n));
+		for number in blocks {
+			let schedule = engine.schedule(&EnvInfo { number: number, ..Default::default() });
+			if schedule.call_stipend >= schedule.call_value_transfer_gas {
+				return Err(format!("Gas schedule at block {}: callStipend must be lower than callValueTransferGas", number));
+			}
 		}
+		Ok(())
 	}
 }
 
@@ -108,14 +219,26 @@ pub struct Spec {
 
 impl From<ethjson::spec::Spec> for Spec {
 	fn from(s: ethjson::spec::Spec) -> Self {
-		let builtins = s.accounts.builtins().into_iter().map(|p| (p.0.into(), From::from(p.1))).collect();
+		Spec::from_json(s, &BuiltinRegistry::default()).unwrap_or_else(|e| panic!("{}", e))
+	}
+}
+
+impl Spec {
+	/// Create the spec, building its builtin contracts from the given registry.
+	pub fn from_json(s: ethjson::spec::Spec, registry: &BuiltinRegistry) -> Result<Self, String> {
+		let mut builtins = BTreeMap::new();
+		for (address, builtin) in s.accounts.builtins() {
+			builtins.insert(address.into(), try!(registry.create(builtin)));
+		}
 		let g = Genesis::from(s.genesis);
 		let seal: GenericSeal = g.seal.into();
-		let params = CommonParams::from(s.params);
-		Spec {
+		let params = try!(CommonParams::from_json(s.params));
+		let engine = Spec::engine(s.engine, params.clone(), builtins);
+		try!(params.check_schedules(&*engine));
+		Ok(Spec {
 			name: s.name.into(),
-			params: params.clone(),
-			engine: Spec::engine(s.engine, params, builtins),
+			params: params,
+			engine: engine,
 			fork_name: s.fork_name.map(Into::into),
 			nodes: s.nodes.unwrap_or_else(Vec::new),
 			parent_hash: g.parent_hash,
@@ -131,11 +254,9 @@ impl From<ethjson::spec::Spec> for Spec {
 			seal_rlp: seal.rlp,
 			state_root_memo: RwLock::new(g.state_root),
 			genesis_state: From::from(s.accounts),
-		}
+		})
 	}
-}
 
-impl Spec {
 	/// Convert engine spec into a arc'd Engine of the right underlying type.
 	/// TODO avoid this hard-coded nastiness - use dynamic-linked plugin framework instead.
 	fn engine(engine_spec: ethjson::spec::Engine, params: CommonParams, builtins: BTreeMap<Address, Builtin>) -> Arc<Engine> {
@@ -165,8 +286,8 @@ impl Spec {
 	/// Get the configured Network ID.
 	pub fn subprotocol_name(&self) -> String { self.params.subprotocol_name.clone() }
 
-	/// Get the configured network fork block.
-	pub fn fork_block(&self) -> Option<(BlockNumber, H256)> { self.params.fork_block }
+	/// Get the configured network fork blocks.
+	pub fn fork_blocks(&self) -> &[(BlockNumber, H256)] { &self.params.fork_blocks }
 
 	/// Get the header of the genesis block.
 	pub fn genesis_header(&self) -> Header {
@@ -260,8 +381,13 @@ impl Spec {
 
 	/// Loads spec from json file.
 	pub fn load<R>(reader: R) -> Result<Self, String> where R: Read {
+		Spec::load_with_builtins(reader, &BuiltinRegistry::default())
+	}
+
+	/// Loads spec from json file, creating builtin contracts from the given registry.
+	pub fn load_with_builtins<R>(reader: R, registry: &BuiltinRegistry) -> Result<Self, String> where R: Read {
 		match ethjson::spec::Spec::load(reader) {
-			Ok(spec) => Ok(spec.into()),
+			Ok(spec) => Spec::from_json(spec, registry),
 			_ => Err("Spec json is invalid".into()),
 		}
 	}
@@ -304,4 +430,43 @@ mod tests {
 		let genesis = test_spec.genesis_block();
 		assert_eq!(BlockView::new(&genesis).header_view().sha3(), H256::from_str("0cd786a2425d16f152c658316c423e6ce1181e15c3295826d7c9904cba9ce303").unwrap());
 	}
+
+	#[test]
+	fn test_schedule_overrides() {
+		use evm::{Schedule, ScheduleOverride};
+
+		let params = CommonParams {
+			schedule_overrides: vec![
+				(0, ScheduleOverride { stack_limit: Some(256), ..Default::default() }),
+				(10, ScheduleOverride {
+					tx_gas: Some(5000),
+					sub_gas_cap_divisor: Some(None),
+					..Default::default()
+				}),
+			],
+			..Default::default()
+		};
+
+		let mut schedule = Schedule::new_homestead_gas_fix();
+		params.override_schedule(9, &mut schedule);
+		assert_eq!(schedule.stack_limit, 256);
+		assert_eq!(schedule.tx_gas, 21000);
+		assert_eq!(schedule.sub_gas_cap_divisor, Some(64));
+
+		let mut schedule = Schedule::new_homestead_gas_fix();
+		params.override_schedule(10, &mut schedule);
+		assert_eq!(schedule.stack_limit, 256);
+		assert_eq!(schedule.tx_gas, 5000);
+		assert_eq!(schedule.sub_gas_cap_divisor, None);
+	}
+
+	#[test]
+	fn rejects_call_stipend_above_transfer_gas() {
+		let json = String::from_utf8(include_bytes!("../../res/null_morden.json").to_vec()).unwrap();
+		let valid = json.replace("\"networkID\" : \"0x2\"", "\"networkID\" : \"0x2\", \"scheduleTransitions\": [{ \"block\": \"0x10\", \"schedule\": { \"callStipend\": \"0x2327\" } }]");
+		let invalid = json.replace("\"networkID\" : \"0x2\"", "\"networkID\" : \"0x2\", \"scheduleTransitions\": [{ \"block\": \"0x10\", \"schedule\": { \"callStipend\": \"0x2328\" } }]");
+		assert!(valid != json);
+		assert!(Spec::load(valid.as_bytes()).is_ok());
+		assert!(Spec::load(invalid.as_bytes()).is_err());
+	}
 }
@@ -163,21 +163,37 @@ impl Account {
 	/// Get (and cache) the contents of the trie's storage at `key`.
 	/// Takes modifed storage into account.
 	pub fn storage_at(&self, db: &HashDB, key: &H256) -> H256 {
+		self.try_storage_at(db, key).unwrap_or_else(|e| panic!("Encountered potential DB corruption: {}", e))
+	}
+
+	/// Get (and cache) the contents of the trie's storage at `key`, failing if
+	/// the database lacks any of the trie nodes needed.
+	pub fn try_storage_at(&self, db: &HashDB, key: &H256) -> Result<H256, Box<TrieError>> {
 		if let Some(value) = self.cached_storage_at(key) {
-			return value;
+			return Ok(value);
 		}
-		let db = SecTrieDB::new(db, &self.storage_root)
-			.expect("Account storage_root initially set to zero (valid) and only altered by SecTrieDBMut. \
-			SecTrieDBMut would not set it to an invalid state root. Therefore the root is valid and DB creation \
-			using it will not fail.");
-
-		let item: U256 = match db.get(key){
-			Ok(x) => x.map_or_else(U256::zero, decode),
-			Err(e) => panic!("Encountered potential DB corruption: {}", e),
-		};
+		let db = try!(SecTrieDB::new(db, &self.storage_root));
+		let item: U256 = try!(db.get(key)).map_or_else(U256::zero, decode);
 		let value: H256 = item.into();
 		self.storage_cache.borrow_mut().insert(key.clone(), value.clone());
-		value
+		Ok(value)
+	}
+
+	/// Prove a storage key's existence or nonexistence in the account's storage trie.
+	/// `storage_key` is the hash of the desired storage key, meaning this will only work
+	/// correctly under a secure trie. Nodes at depths below `from_level` are omitted.
+	pub fn prove_storage(&self, db: &HashDB, storage_key: H256, from_level: u32) -> Result<Vec<Bytes>, Box<TrieError>> {
+		use util::trie::recorder::{Recorder, BasicRecorder};
+
+		let mut recorder = BasicRecorder::with_depth(from_level);
+		let trie = try!(TrieDB::new(db, &self.storage_root));
+		let _ = try!(trie.get_recorded(&storage_key, &mut recorder));
+		Ok(recorder.drain().into_iter().map(|r| r.data).collect())
+	}
+
+	/// Whether the committed storage of the account is empty.
+	pub fn storage_trie_is_empty(&self) -> bool {
+		self.storage_root == SHA3_NULL_RLP
 	}
 
 	/// Get cached storage value if any. Returns `None` if the
@@ -309,6 +325,23 @@ impl Account {
 		self.nonce = self.nonce + U256::from(1u8);
 	}
 
+	/// Set the nonce of the account.
+	pub fn set_nonce(&mut self, nonce: U256) {
+		self.nonce = nonce;
+	}
+
+	/// Set the balance of the account.
+	pub fn set_balance(&mut self, balance: U256) {
+		self.balance = balance;
+	}
+
+	/// Remove all storage of the account, including uncommitted changes.
+	pub fn clear_storage(&mut self) {
+		self.storage_root = SHA3_NULL_RLP;
+		self.storage_cache.borrow_mut().clear();
+		self.storage_changes.clear();
+	}
+
 	/// Increase account balance.
 	pub fn add_balance(&mut self, x: &U256) {
 		self.balance = self.balance + *x;
@@ -14,7 +14,8 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::cell::{RefCell, RefMut};
+use std::cell::{Cell, RefCell, RefMut};
+use std::sync::Weak;
 use std::collections::hash_map::Entry;
 use util::*;
 use receipt::Receipt;
@@ -27,14 +28,18 @@ use trace::FlatTrace;
 use pod_account::*;
 use pod_state::{self, PodState};
 use types::state_diff::StateDiff;
+use types::state_override::StateOverride;
+use types::state_accesses::StateAccesses;
 use transaction::SignedTransaction;
 use state_db::StateDB;
 
 mod account;
 mod substate;
+mod savepoint;
 
 pub use self::account::Account;
 pub use self::substate::Substate;
+pub use self::savepoint::Savepoint;
 
 /// Used to return information about an `State::apply` operation.
 pub struct ApplyOutcome {
@@ -191,6 +196,11 @@ pub struct State {
 	checkpoints: RefCell<Vec<HashMap<Address, Option<AccountEntry>>>>,
 	account_start_nonce: U256,
 	factories: Factories,
+	access_log: Option<Weak<Mutex<StateAccesses>>>,
+	// whether missing trie nodes are expected rather than a sign of corruption.
+	partial: bool,
+	// set when a partial state was asked for data it does not hold.
+	incomplete: Cell<bool>,
 }
 
 #[derive(Copy, Clone)]
@@ -220,6 +230,9 @@ impl State {
 			checkpoints: RefCell::new(Vec::new()),
 			account_start_nonce: account_start_nonce,
 			factories: factories,
+			access_log: None,
+			partial: false,
+			incomplete: Cell::new(false),
 		}
 	}
 
@@ -235,13 +248,73 @@ impl State {
 			cache: RefCell::new(HashMap::new()),
 			checkpoints: RefCell::new(Vec::new()),
 			account_start_nonce: account_start_nonce,
-			factories: factories
+			factories: factories,
+			access_log: None,
+			partial: false,
+			incomplete: Cell::new(false),
 		};
 
 		Ok(state)
 	}
 
+	/// Creates a state over a database holding only part of the trie, such as
+	/// the nodes of a merkle proof. Missing nodes are treated as absent data
+	/// instead of corruption; check `is_incomplete` after use.
+	pub fn from_partial(db: StateDB, root: H256, account_start_nonce: U256, factories: Factories) -> Result<State, TrieError> {
+		let mut state = try!(State::from_existing(db, root, account_start_nonce, factories));
+		state.partial = true;
+		Ok(state)
+	}
+
+	/// Whether a partial state was asked for data missing from its database,
+	/// making any results computed from it unreliable.
+	pub fn is_incomplete(&self) -> bool {
+		self.incomplete.get()
+	}
+
+	fn missing_node(&self, e: &TrieError) {
+		if !self.partial {
+			panic!("Potential DB corruption encountered: {}", e);
+		}
+		self.incomplete.set(true);
+	}
+
+	fn account_storage_at(&self, account: &Account, address: &Address, key: &H256) -> H256 {
+		let account_db = self.factories.accountdb.readonly(self.db.as_hashdb(), account.address_hash(address));
+		account.try_storage_at(account_db.as_hashdb(), key).unwrap_or_else(|e| {
+			self.missing_node(&*e);
+			H256::new()
+		})
+	}
+
+	/// Record every account and storage slot accessed through this state into `accesses`
+	/// until it is dropped.
+	pub fn record_accesses(&mut self, accesses: &Arc<Mutex<StateAccesses>>) {
+		self.access_log = Some(Arc::downgrade(accesses));
+	}
+
+	/// Run `f` without recording its accesses into the log set by `record_accesses`.
+	pub fn without_access_recording<F, R>(&mut self, f: F) -> R where F: FnOnce(&mut State) -> R {
+		let log = self.access_log.take();
+		let result = f(self);
+		self.access_log = log;
+		result
+	}
+
+	fn note_access<F>(&self, f: F) where F: FnOnce(&mut StateAccesses) {
+		if let Some(accesses) = self.access_log.as_ref().and_then(Weak::upgrade) {
+			f(&mut *accesses.lock());
+		}
+	}
+
+	/// Take a savepoint of this state, reverting all changes made through it
+	/// unless it is committed.
+	pub fn savepoint(&mut self) -> Savepoint {
+		Savepoint::new(self)
+	}
+
 	/// Create a recoverable checkpoint of this state.
+	/// Prefer `savepoint`, which cannot be left unbalanced.
 	pub fn checkpoint(&mut self) {
 		self.checkpoints.get_mut().push(HashMap::new());
 	}
@@ -334,16 +407,24 @@ impl State {
 	/// Create a new contract at address `contract`. If there is already an account at the address
 	/// it will have its code reset, ready for `init_code()`.
 	pub fn new_contract(&mut self, contract: &Address, balance: U256) {
+		self.note_access(|accesses| accesses.write_account(contract));
 		self.insert_cache(contract, AccountEntry::new_dirty(Some(Account::new_contract(balance, self.account_start_nonce))));
 	}
 
 	/// Remove an existing account.
 	pub fn kill_account(&mut self, account: &Address) {
+		self.note_access(|accesses| accesses.write_account(account));
 		self.insert_cache(account, AccountEntry::new_dirty(None));
 	}
 
 	/// Determine whether an account exists.
 	pub fn exists(&self, a: &Address) -> bool {
+		self.note_access(|accesses| accesses.read_account(a));
+		self.account_exists(a)
+	}
+
+	// like `exists`, but not recorded as an access.
+	fn account_exists(&self, a: &Address) -> bool {
 		// Bloom filter does not contain empty accounts, so it is important here to
 		// check if account exists in the database directly before EIP-158 is in effect.
 		self.ensure_cached(a, RequireCache::None, false, |a| a.is_some())
@@ -351,18 +432,26 @@ impl State {
 
 	/// Get the balance of account `a`.
 	pub fn balance(&self, a: &Address) -> U256 {
+		self.note_access(|accesses| accesses.read_account(a));
 		self.ensure_cached(a, RequireCache::None, true,
 			|a| a.as_ref().map_or(U256::zero(), |account| *account.balance()))
 	}
 
 	/// Get the nonce of account `a`.
 	pub fn nonce(&self, a: &Address) -> U256 {
+		self.note_access(|accesses| accesses.read_account(a));
 		self.ensure_cached(a, RequireCache::None, true,
 			|a| a.as_ref().map_or(self.account_start_nonce, |account| *account.nonce()))
 	}
 
 	/// Mutate storage of account `address` so that it is `value` for `key`.
 	pub fn storage_at(&self, address: &Address, key: &H256) -> H256 {
+		self.note_access(|accesses| accesses.read_storage(address, key));
+		self.storage_value(address, key)
+	}
+
+	// like `storage_at`, but not recorded as an access.
+	fn storage_value(&self, address: &Address, key: &H256) -> H256 {
 		// Storage key search and update works like this:
 		// 1. If there's an entry for the account in the local cache check for the key and return it if found.
 		// 2. If there's an entry for the account in the global cache check for the key or load it into that account.
@@ -377,6 +466,9 @@ impl State {
 					Some(ref account) => {
 						if let Some(value) = account.cached_storage_at(key) {
 							return value;
+						} else if account.storage_trie_is_empty() {
+							// the global cache may still hold storage the account no longer has.
+							return H256::new();
 						} else {
 							local_account = Some(maybe_acc);
 						}
@@ -386,16 +478,12 @@ impl State {
 			}
 			// check the global cache and and cache storage key there if found,
 			// otherwise cache the account localy and cache storage key there.
-			if let Some(result) = self.db.get_cached(address, |acc| acc.map_or(H256::new(), |a| {
-					let account_db = self.factories.accountdb.readonly(self.db.as_hashdb(), a.address_hash(address));
-					a.storage_at(account_db.as_hashdb(), key)
-				})) {
+			if let Some(result) = self.db.get_cached(address, |acc| acc.map_or(H256::new(), |a| self.account_storage_at(a, address, key))) {
 				return result;
 			}
 			if let Some(ref mut acc) = local_account {
 				if let Some(ref account) = acc.account {
-					let account_db = self.factories.accountdb.readonly(self.db.as_hashdb(), account.address_hash(address));
-					return account.storage_at(account_db.as_hashdb(), key)
+					return self.account_storage_at(account, address, key)
 				} else {
 					return H256::new()
 				}
@@ -409,45 +497,90 @@ impl State {
 		let db = self.factories.trie.readonly(self.db.as_hashdb(), &self.root).expect(SEC_TRIE_DB_UNWRAP_STR);
 		let maybe_acc = match db.get(address) {
 			Ok(acc) => acc.map(Account::from_rlp),
-			Err(e) => panic!("Potential DB corruption encountered: {}", e),
+			Err(e) => {
+				self.missing_node(&*e);
+				None
+			}
 		};
-		let r = maybe_acc.as_ref().map_or(H256::new(), |a| {
-			let account_db = self.factories.accountdb.readonly(self.db.as_hashdb(), a.address_hash(address));
-			a.storage_at(account_db.as_hashdb(), key)
-		});
+		let r = maybe_acc.as_ref().map_or(H256::new(), |a| self.account_storage_at(a, address, key));
 		self.insert_cache(address, AccountEntry::new_clean(maybe_acc));
 		r
 	}
 
 	/// Get accounts' code.
 	pub fn code(&self, a: &Address) -> Option<Arc<Bytes>> {
+		self.note_access(|accesses| accesses.read_account(a));
 		self.ensure_cached(a, RequireCache::Code, true,
 			|a| a.as_ref().map_or(None, |a| a.code().clone()))
 	}
 
 	pub fn code_hash(&self, a: &Address) -> H256 {
+		self.note_access(|accesses| accesses.read_account(a));
 		self.ensure_cached(a, RequireCache::None, true,
 			|a| a.as_ref().map_or(SHA3_EMPTY, |a| a.code_hash()))
 	}
 
 	/// Get accounts' code size.
 	pub fn code_size(&self, a: &Address) -> Option<usize> {
+		self.note_access(|accesses| accesses.read_account(a));
 		self.ensure_cached(a, RequireCache::CodeSize, true,
 			|a| a.as_ref().and_then(|a| a.code_size()))
 	}
 
+	/// Prove an account's existence or nonexistence in the state trie.
+	/// `account_key` is the hash of the address. Returns the encoded nodes of the
+	/// merkle proof, omitting those at depths below `from_level`.
+	/// Only works when the state has no uncommitted changes.
+	pub fn prove_account(&self, account_key: H256, from_level: u32) -> Result<Vec<Bytes>, Box<TrieError>> {
+		use util::trie::recorder::{Recorder, BasicRecorder};
+
+		let mut recorder = BasicRecorder::with_depth(from_level);
+		let trie = try!(TrieDB::new(self.db.as_hashdb(), &self.root));
+		let _ = try!(trie.get_recorded(&account_key, &mut recorder));
+		Ok(recorder.drain().into_iter().map(|r| r.data).collect())
+	}
+
+	/// Prove a storage key's existence or nonexistence in an account's storage trie.
+	/// `account_key` is the hash of the address and `storage_key` the hash of the key.
+	/// If the account doesn't exist, the proof is empty.
+	/// Only works when the state has no uncommitted changes.
+	pub fn prove_storage(&self, account_key: H256, storage_key: H256, from_level: u32) -> Result<Vec<Bytes>, Box<TrieError>> {
+		let trie = try!(TrieDB::new(self.db.as_hashdb(), &self.root));
+		let account = match try!(trie.get(&account_key)) {
+			Some(rlp) => Account::from_rlp(rlp),
+			None => return Ok(Vec::new()),
+		};
+		let account_db = self.factories.accountdb.readonly(self.db.as_hashdb(), account_key);
+		account.prove_storage(&*account_db, storage_key, from_level)
+	}
+
+	/// Get the code of the account with the given address hash, if it exists.
+	/// Only works when the state has no uncommitted changes.
+	pub fn code_by_address_hash(&self, account_key: H256) -> Result<Option<Bytes>, Box<TrieError>> {
+		let trie = try!(TrieDB::new(self.db.as_hashdb(), &self.root));
+		let mut account = match try!(trie.get(&account_key)) {
+			Some(rlp) => Account::from_rlp(rlp),
+			None => return Ok(None),
+		};
+		let account_db = self.factories.accountdb.readonly(self.db.as_hashdb(), account_key);
+		account.cache_code(&*account_db);
+		Ok(account.code().map(|c| (*c).clone()))
+	}
+
 	/// Add `incr` to the balance of account `a`.
 	pub fn add_balance(&mut self, a: &Address, incr: &U256) {
-		trace!(target: "state", "add_balance({}, {}): {}", a, incr, self.balance(a));
-		if !incr.is_zero() || !self.exists(a) {
+		self.note_access(|accesses| accesses.write_account(a));
+		trace!(target: "state", "add_balance({}, {})", a, incr);
+		if !incr.is_zero() || !self.account_exists(a) {
 			self.require(a, false).add_balance(incr);
 		}
 	}
 
 	/// Subtract `decr` from the balance of account `a`.
 	pub fn sub_balance(&mut self, a: &Address, decr: &U256) {
-		trace!(target: "state", "sub_balance({}, {}): {}", a, decr, self.balance(a));
-		if !decr.is_zero() || !self.exists(a) {
+		self.note_access(|accesses| accesses.write_account(a));
+		trace!(target: "state", "sub_balance({}, {})", a, decr);
+		if !decr.is_zero() || !self.account_exists(a) {
 			self.require(a, false).sub_balance(decr);
 		}
 	}
@@ -460,12 +593,58 @@ impl State {
 
 	/// Increment the nonce of account `a` by 1.
 	pub fn inc_nonce(&mut self, a: &Address) {
+		self.note_access(|accesses| accesses.write_account(a));
 		self.require(a, false).inc_nonce()
 	}
 
+	/// Set the balance of account `a`.
+	pub fn set_balance(&mut self, a: &Address, balance: U256) {
+		self.note_access(|accesses| accesses.write_account(a));
+		self.require(a, false).set_balance(balance)
+	}
+
+	/// Set the nonce of account `a`.
+	pub fn set_nonce(&mut self, a: &Address, nonce: U256) {
+		self.note_access(|accesses| accesses.write_account(a));
+		self.require(a, false).set_nonce(nonce)
+	}
+
+	/// Remove all storage of account `a`.
+	pub fn clear_storage(&mut self, a: &Address) {
+		self.note_access(|accesses| accesses.write_account(a));
+		self.require(a, false).clear_storage()
+	}
+
+	/// Apply hypothetical changes to accounts.
+	pub fn apply_override(&mut self, state_override: &StateOverride) {
+		for (address, account) in &state_override.accounts {
+			if let Some(balance) = account.balance {
+				self.set_balance(address, balance);
+			}
+			if let Some(nonce) = account.nonce {
+				self.set_nonce(address, nonce);
+			}
+			if let Some(ref code) = account.code {
+				self.reset_code(address, code.clone());
+			}
+			if let Some(ref storage) = account.state {
+				self.clear_storage(address);
+				for (key, value) in storage {
+					self.set_storage(address, key.clone(), value.clone());
+				}
+			}
+			if let Some(ref storage) = account.state_diff {
+				for (key, value) in storage {
+					self.set_storage(address, key.clone(), value.clone());
+				}
+			}
+		}
+	}
+
 	/// Mutate storage of account `a` so that it is `value` for `key`.
 	pub fn set_storage(&mut self, a: &Address, key: H256, value: H256) {
-		if self.storage_at(a, &key) != value {
+		self.note_access(|accesses| accesses.write_storage(a, &key));
+		if self.storage_value(a, &key) != value {
 			self.require(a, false).set_storage(key, value)
 		}
 	}
@@ -473,11 +652,13 @@ impl State {
 	/// Initialise the code of account `a` so that it is `code`.
 	/// NOTE: Account should have been created with `new_contract`.
 	pub fn init_code(&mut self, a: &Address, code: Bytes) {
+		self.note_access(|accesses| accesses.write_account(a));
 		self.require_or_from(a, true, || Account::new_contract(0.into(), self.account_start_nonce), |_|{}).init_code(code);
 	}
 
 	/// Reset the code of account `a` so that it is `code`.
 	pub fn reset_code(&mut self, a: &Address, code: Bytes) {
+		self.note_access(|accesses| accesses.write_account(a));
 		self.require_or_from(a, true, || Account::new_contract(0.into(), self.account_start_nonce), |_|{}).reset_code(code);
 	}
 
@@ -486,7 +667,7 @@ impl State {
 	pub fn apply(&mut self, env_info: &EnvInfo, engine: &Engine, t: &SignedTransaction, tracing: bool) -> ApplyResult {
 //		let old = self.to_pod();
 
-		let options = TransactOptions { tracing: tracing, vm_tracing: false, check_nonce: true };
+		let options = TransactOptions { tracing: tracing, vm_tracing: false, check_nonce: true, step_logging: None, gas_profiling: false, state_accessing: false };
 		let vm_factory = self.factories.vm.clone();
 		let e = try!(Executive::new(self, env_info, engine, &vm_factory).transact(t, options));
 
@@ -571,9 +752,8 @@ impl State {
 		}
 	}
 
-	/// Populate a PodAccount map from this state.
+	/// Populate a PodAccount map from this state. Changes made since a checkpoint are included.
 	pub fn to_pod(&self) -> PodState {
-		assert!(self.checkpoints.borrow().is_empty());
 		// TODO: handle database rather than just the cache.
 		// will need fat db.
 		PodState::from(self.cache.borrow().iter().fold(BTreeMap::new(), |mut m, (add, opt)| {
@@ -605,15 +785,14 @@ impl State {
 		pod_state::diff_pod(&state_pre.to_pod(), &pod_state_post)
 	}
 
-	fn update_account_cache(require: RequireCache, account: &mut Account, db: &HashDB) {
-		match require {
-			RequireCache::None => {},
-			RequireCache::Code => {
-				account.cache_code(db);
-			}
-			RequireCache::CodeSize => {
-				account.cache_code_size(db);
-			}
+	fn update_account_cache(&self, require: RequireCache, account: &mut Account, db: &HashDB) {
+		let cached = match require {
+			RequireCache::None => true,
+			RequireCache::Code => account.cache_code(db),
+			RequireCache::CodeSize => account.cache_code_size(db),
+		};
+		if !cached && self.partial && account.code_hash() != SHA3_EMPTY {
+			self.incomplete.set(true);
 		}
 	}
 
@@ -626,7 +805,7 @@ impl State {
 		if let Some(ref mut maybe_acc) = self.cache.borrow_mut().get_mut(a) {
 			if let Some(ref mut account) = maybe_acc.account {
 				let accountdb = self.factories.accountdb.readonly(self.db.as_hashdb(), account.address_hash(a));
-				Self::update_account_cache(require, account, accountdb.as_hashdb());
+				self.update_account_cache(require, account, accountdb.as_hashdb());
 				return f(Some(account));
 			}
 			return f(None);
@@ -635,7 +814,7 @@ impl State {
 		let result = self.db.get_cached(a, |mut acc| {
 			if let Some(ref mut account) = acc {
 				let accountdb = self.factories.accountdb.readonly(self.db.as_hashdb(), account.address_hash(a));
-				Self::update_account_cache(require, account, accountdb.as_hashdb());
+				self.update_account_cache(require, account, accountdb.as_hashdb());
 			}
 			f(acc.map(|a| &*a))
 		});
@@ -649,11 +828,14 @@ impl State {
 				let db = self.factories.trie.readonly(self.db.as_hashdb(), &self.root).expect(SEC_TRIE_DB_UNWRAP_STR);
 				let mut maybe_acc = match db.get(a) {
 					Ok(acc) => acc.map(Account::from_rlp),
-					Err(e) => panic!("Potential DB corruption encountered: {}", e),
+					Err(e) => {
+						self.missing_node(&*e);
+						None
+					}
 				};
 				if let Some(ref mut account) = maybe_acc.as_mut() {
 					let accountdb = self.factories.accountdb.readonly(self.db.as_hashdb(), account.address_hash(a));
-					Self::update_account_cache(require, account, accountdb.as_hashdb());
+					self.update_account_cache(require, account, accountdb.as_hashdb());
 				}
 				let r = f(maybe_acc.as_ref());
 				self.insert_cache(a, AccountEntry::new_clean(maybe_acc));
@@ -682,7 +864,10 @@ impl State {
 						let maybe_acc = match db.get(a) {
 							Ok(Some(acc)) => AccountEntry::new_clean(Some(Account::from_rlp(acc))),
 							Ok(None) => AccountEntry::new_clean(None),
-							Err(e) => panic!("Potential DB corruption encountered: {}", e),
+							Err(e) => {
+								self.missing_node(&*e);
+								AccountEntry::new_clean(None)
+							}
 						};
 						maybe_acc
 					}
@@ -711,7 +896,7 @@ impl State {
 					if require_code {
 						let addr_hash = account.address_hash(a);
 						let accountdb = self.factories.accountdb.readonly(self.db.as_hashdb(), addr_hash);
-						account.cache_code(accountdb.as_hashdb());
+						self.update_account_cache(RequireCache::Code, account, accountdb.as_hashdb());
 					}
 					account
 				},
@@ -746,6 +931,9 @@ impl Clone for State {
 			checkpoints: RefCell::new(Vec::new()),
 			account_start_nonce: self.account_start_nonce.clone(),
 			factories: self.factories.clone(),
+			access_log: None,
+			partial: self.partial,
+			incomplete: Cell::new(self.incomplete.get()),
 		}
 	}
 }
@@ -764,6 +952,7 @@ use env_info::EnvInfo;
 use spec::*;
 use transaction::*;
 use util::log::init_log;
+use types::state_override::AccountOverride;
 use trace::{FlatTrace, TraceError, trace};
 use types::executed::CallType;
 
@@ -1836,4 +2025,52 @@ fn should_not_panic_on_state_diff_with_storage() {
 	new_state.diff_from(state);
 }
 
+#[test]
+fn should_apply_state_override() {
+	let mut state_result = get_temp_state();
+	let mut state = state_result.reference_mut();
+	let a: Address = 0xa.into();
+	state.init_code(&a, vec![0x60]);
+	state.set_storage(&a, 0xb.into(), 0xc.into());
+	state.set_storage(&a, 0xd.into(), 0xe.into());
+	state.commit().unwrap();
+
+	let mut state_override = StateOverride::default();
+	state_override.accounts.insert(a.clone(), AccountOverride {
+		balance: Some(5.into()),
+		nonce: Some(7.into()),
+		code: Some(vec![0x00]),
+		state: Some(vec![(0xd.into(), 0xf.into())].into_iter().collect()),
+		state_diff: Some(vec![(0x1.into(), 0x2.into())].into_iter().collect()),
+	});
+	state.apply_override(&state_override);
+
+	assert_eq!(state.balance(&a), 5.into());
+	assert_eq!(state.nonce(&a), 7.into());
+	assert_eq!(state.code(&a), Some(Arc::new(vec![0x00])));
+	assert_eq!(state.storage_at(&a, &0xb.into()), H256::zero());
+	assert_eq!(state.storage_at(&a, &0xd.into()), 0xf.into());
+	assert_eq!(state.storage_at(&a, &0x1.into()), 0x2.into());
+}
+
+#[test]
+fn should_not_record_writes_as_reads() {
+	use util::Mutex;
+	use types::state_accesses::{Access, StateAccesses};
+
+	let mut state_result = get_temp_state();
+	let mut state = state_result.reference_mut();
+	let a = Address::from(1);
+	let accesses = Arc::new(Mutex::new(StateAccesses::default()));
+	state.record_accesses(&accesses);
+
+	state.add_balance(&a, &U256::from(1));
+	state.sub_balance(&a, &U256::from(1));
+	state.set_storage(&a, H256::from(1), H256::from(2));
+
+	let accesses = accesses.lock();
+	assert_eq!(accesses.accounts[&a].account, Access { read: false, written: true });
+	assert_eq!(accesses.accounts[&a].storage[&H256::from(1)], Access { read: false, written: true });
+}
+
 }
@@ -0,0 +1,250 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Nestable state savepoints.
+
+use std::ops::Deref;
+use util::{U256, H256, Address, Bytes};
+use engines::Engine;
+use env_info::EnvInfo;
+use evm::Factory;
+use executive::Executive;
+use super::State;
+
+/// A savepoint of a `State`, giving access to the state it was taken of.
+///
+/// All changes made through the savepoint are reverted when it is dropped, unless it
+/// has been kept. Savepoints nest by taking a new one from an existing one; the
+/// inner savepoint must be kept or dropped before the outer one can be used again.
+///
+/// The state can be read through the savepoint, but only changed with the methods below,
+/// so that checkpoints stay balanced and nothing is committed while the savepoint is open.
+pub struct Savepoint<'a> {
+	state: &'a mut State,
+	kept: bool,
+}
+
+impl<'a> Savepoint<'a> {
+	/// Take a savepoint of the given state.
+	pub fn new(state: &'a mut State) -> Self {
+		state.checkpoint();
+		Savepoint {
+			state: state,
+			kept: false,
+		}
+	}
+
+	/// Keep all changes made since the savepoint was taken. If it is nested they are
+	/// still reverted along with the enclosing savepoint.
+	pub fn keep(mut self) {
+		self.state.discard_checkpoint();
+		self.kept = true;
+	}
+
+	/// Revert all changes made since the savepoint was taken.
+	pub fn revert(self) {}
+
+	/// Take a nested savepoint.
+	pub fn savepoint(&mut self) -> Savepoint {
+		Savepoint::new(self.state)
+	}
+
+	/// Create an executive changing the state within this savepoint.
+	pub fn executive<'b>(&'b mut self, info: &'b EnvInfo, engine: &'b Engine, vm_factory: &'b Factory) -> Executive<'b> {
+		Executive::new(self.state, info, engine, vm_factory)
+	}
+
+	/// See `State::new_contract`.
+	pub fn new_contract(&mut self, contract: &Address, balance: U256) {
+		self.state.new_contract(contract, balance)
+	}
+
+	/// See `State::kill_account`.
+	pub fn kill_account(&mut self, account: &Address) {
+		self.state.kill_account(account)
+	}
+
+	/// See `State::add_balance`.
+	pub fn add_balance(&mut self, a: &Address, incr: &U256) {
+		self.state.add_balance(a, incr)
+	}
+
+	/// See `State::sub_balance`.
+	pub fn sub_balance(&mut self, a: &Address, decr: &U256) {
+		self.state.sub_balance(a, decr)
+	}
+
+	/// See `State::transfer_balance`.
+	pub fn transfer_balance(&mut self, from: &Address, to: &Address, by: &U256) {
+		self.state.transfer_balance(from, to, by)
+	}
+
+	/// See `State::inc_nonce`.
+	pub fn inc_nonce(&mut self, a: &Address) {
+		self.state.inc_nonce(a)
+	}
+
+	/// See `State::set_balance`.
+	pub fn set_balance(&mut self, a: &Address, balance: U256) {
+		self.state.set_balance(a, balance)
+	}
+
+	/// See `State::set_nonce`.
+	pub fn set_nonce(&mut self, a: &Address, nonce: U256) {
+		self.state.set_nonce(a, nonce)
+	}
+
+	/// See `State::set_storage`.
+	pub fn set_storage(&mut self, a: &Address, key: H256, value: H256) {
+		self.state.set_storage(a, key, value)
+	}
+
+	/// See `State::clear_storage`.
+	pub fn clear_storage(&mut self, a: &Address) {
+		self.state.clear_storage(a)
+	}
+
+	/// See `State::init_code`.
+	pub fn init_code(&mut self, a: &Address, code: Bytes) {
+		self.state.init_code(a, code)
+	}
+
+	/// See `State::reset_code`.
+	pub fn reset_code(&mut self, a: &Address, code: Bytes) {
+		self.state.reset_code(a, code)
+	}
+}
+
+impl<'a> Deref for Savepoint<'a> {
+	type Target = State;
+
+	fn deref(&self) -> &State {
+		self.state
+	}
+}
+
+impl<'a> Drop for Savepoint<'a> {
+	fn drop(&mut self) {
+		if !self.kept {
+			self.state.revert_to_checkpoint();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use util::{U256, H256, Address, FromHex};
+	use action_params::{ActionParams, ActionValue};
+	use env_info::EnvInfo;
+	use evm::{Factory, VMType};
+	use state::Substate;
+	use trace::{NoopTracer, NoopVMTracer};
+	use tests::helpers::{get_temp_state, TestEngine};
+
+	#[test]
+	fn should_revert_when_dropped() {
+		let mut state_result = get_temp_state();
+		let state = state_result.reference_mut();
+		let a = Address::zero();
+		state.add_balance(&a, &U256::from(1u64));
+		{
+			let mut savepoint = state.savepoint();
+			savepoint.add_balance(&a, &U256::from(68u64));
+			assert_eq!(savepoint.balance(&a), U256::from(69u64));
+		}
+		assert_eq!(state.balance(&a), U256::from(1u64));
+	}
+
+	#[test]
+	fn should_keep_changes() {
+		let mut state_result = get_temp_state();
+		let state = state_result.reference_mut();
+		let a = Address::zero();
+		{
+			let mut savepoint = state.savepoint();
+			savepoint.add_balance(&a, &U256::from(69u64));
+			savepoint.keep();
+		}
+		assert_eq!(state.balance(&a), U256::from(69u64));
+		state.commit().unwrap();
+	}
+
+	#[test]
+	fn should_nest_savepoints() {
+		let mut state_result = get_temp_state();
+		let state = state_result.reference_mut();
+		let a = Address::zero();
+		{
+			let mut outer = state.savepoint();
+			outer.add_balance(&a, &U256::from(1u64));
+			{
+				let mut inner = outer.savepoint();
+				inner.add_balance(&a, &U256::from(2u64));
+				inner.revert();
+			}
+			assert_eq!(outer.balance(&a), U256::from(1u64));
+			{
+				let mut inner = outer.savepoint();
+				inner.add_balance(&a, &U256::from(4u64));
+				inner.keep();
+			}
+			assert_eq!(outer.balance(&a), U256::from(5u64));
+		}
+		assert_eq!(state.balance(&a), U256::zero());
+	}
+
+	#[test]
+	fn should_nest_savepoints_around_executive() {
+		let mut state_result = get_temp_state();
+		let state = state_result.reference_mut();
+		let sender = Address::from(1);
+		let address = Address::from(2);
+		state.add_balance(&sender, &U256::from(0x100u64));
+		let info = EnvInfo::default();
+		let engine = TestEngine::new(0);
+		let factory = Factory::new(VMType::Interpreter, 1024 * 32);
+		// stores the balance of the sender
+		let mut params = ActionParams::default();
+		params.address = address.clone();
+		params.sender = sender.clone();
+		params.gas = U256::from(100_000);
+		params.code = Some(Arc::new("3331600055".from_hex().unwrap()));
+		params.value = ActionValue::Transfer(U256::from(0x7));
+		{
+			let mut outer = state.savepoint();
+			{
+				let mut inner = outer.savepoint();
+				inner.executive(&info, &engine, &factory).create(params.clone(), &mut Substate::new(), &mut NoopTracer, &mut NoopVMTracer).unwrap();
+				assert_eq!(inner.balance(&address), U256::from(0x7));
+				assert_eq!(inner.storage_at(&address, &H256::new()), H256::from(&U256::from(0xf9u64)));
+				inner.revert();
+			}
+			assert_eq!(outer.balance(&address), U256::zero());
+			assert_eq!(outer.storage_at(&address, &H256::new()), H256::new());
+			{
+				let mut inner = outer.savepoint();
+				inner.executive(&info, &engine, &factory).create(params, &mut Substate::new(), &mut NoopTracer, &mut NoopVMTracer).unwrap();
+				inner.keep();
+			}
+			assert_eq!(outer.balance(&sender), U256::from(0xf9));
+			assert_eq!(outer.to_pod().get()[&address].balance, U256::from(0x7));
+		}
+		assert_eq!(state.balance(&sender), U256::from(0x100));
+		assert_eq!(state.balance(&address), U256::zero());
+		state.commit().unwrap();
+	}
+}
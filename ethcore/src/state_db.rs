@@ -111,6 +111,18 @@ impl StateDB {
 	// into the `AccountCache` structure as its own `LruCache<(Address, H256), H256>`.
 	pub fn new(db: Box<JournalDB>, cache_size: usize) -> StateDB {
 		let bloom = Self::load_bloom(db.backing());
+		Self::with_bloom(db, cache_size, bloom)
+	}
+
+	/// Create a new instance which does not consult the account bloom stored in the
+	/// backing database. Every account is assumed to possibly exist; used where the
+	/// backing database holds only a partial state, e.g. nodes of a merkle proof.
+	pub fn new_without_bloom(db: Box<JournalDB>, cache_size: usize) -> StateDB {
+		let bloom = Bloom::from_parts(&vec![!0u64; ACCOUNT_BLOOM_SPACE / 8], 1);
+		Self::with_bloom(db, cache_size, bloom)
+	}
+
+	fn with_bloom(db: Box<JournalDB>, cache_size: usize, bloom: Bloom) -> StateDB {
 		let cache_items = cache_size / ::std::mem::size_of::<Option<Account>>();
 
 		StateDB {
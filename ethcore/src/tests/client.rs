@@ -26,6 +26,10 @@ use miner::Miner;
 use rlp::{Rlp, View};
 use spec::Spec;
 use views::BlockView;
+use ethkey::KeyPair;
+use transaction::{Transaction, Action};
+use header::Header;
+use light::{Client as LightClient, QueueConfig};
 
 #[test]
 fn imports_from_empty() {
@@ -211,6 +215,28 @@ fn can_generate_gas_price_statistics() {
 	assert_eq!(s, vec_into![0, 1, 3, 5, 7, 9, 11, 13, 15]);
 }
 
+#[test]
+fn can_dry_run_transactions() {
+	let client_result = generate_dummy_client_with_data(2, 1, &vec_into![0]);
+	let client = client_result.reference();
+	let kp = KeyPair::from_secret("".sha3()).unwrap();
+	let transaction = |nonce: u64| Transaction {
+		nonce: nonce.into(),
+		gas_price: 0.into(),
+		gas: 21000.into(),
+		action: Action::Call(Address::zero()),
+		data: vec![],
+		value: U256::zero(),
+	}.sign(kp.secret());
+
+	let results = client.dry_run(&[transaction(2), transaction(3)], BlockID::Latest, Default::default()).unwrap();
+	assert_eq!(results.len(), 2);
+	assert!(results.iter().all(|r| r.gas_used == 21000.into()));
+	assert_eq!(client.latest_nonce(&kp.address()), 2.into());
+
+	assert!(client.dry_run(&[transaction(2), transaction(2)], BlockID::Latest, Default::default()).is_err());
+}
+
 #[test]
 fn can_handle_long_fork() {
 	let client_result = generate_dummy_client(1200);
@@ -264,3 +290,41 @@ fn change_history_size() {
 	let client = Client::new(config, &test_spec, dir.as_path(), Arc::new(Miner::with_spec(&test_spec)), IoChannel::disconnected(), &db_config).unwrap();
 	assert_eq!(client.state().balance(&address), 100.into());
 }
+
+#[test]
+fn transaction_proof_round_trip() {
+	let dir = RandomTempPath::new();
+	let light_dir = RandomTempPath::new();
+	let test_spec = Spec::new_null();
+	let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+	let client = Client::new(ClientConfig::default(), &test_spec, dir.as_path(), Arc::new(Miner::with_spec(&test_spec)), IoChannel::disconnected(), &db_config).unwrap();
+	let light_db = Database::open(&db_config, light_dir.as_path().to_str().unwrap()).unwrap();
+	let light = LightClient::new(QueueConfig::default(), &test_spec, Arc::new(light_db));
+
+	let address = Address::from(0x1234);
+	let mut b = client.prepare_open_block(Address::default(), (3141562.into(), 31415620.into()), vec![]);
+	b.block_mut().fields_mut().state.add_balance(&address, &5.into());
+	b.block_mut().fields_mut().state.commit().unwrap();
+	let b = b.close_and_lock().seal(&*test_spec.engine, vec![]).unwrap();
+	client.import_sealed_block(b).unwrap();
+
+	let header: Header = ::rlp::decode(&client.block_header(BlockID::Number(1)).unwrap());
+	let kp = KeyPair::from_secret("".sha3()).unwrap();
+	let transaction = |to: Address| Transaction {
+		nonce: 0.into(),
+		gas_price: 0.into(),
+		gas: 21000.into(),
+		action: Action::Call(to),
+		data: vec![],
+		value: 1.into(),
+	}.sign(kp.secret());
+
+	// neither the sender nor the recipient exist; the proof has to show their absence.
+	for to in vec![Address::from(0x5678), address] {
+		let t = transaction(to);
+		let proof = client.prove_transaction(t.clone(), header.hash()).unwrap();
+		let executed = light.check_transaction_proof(&t, &header, &proof).unwrap().unwrap();
+		assert_eq!(executed.gas_used, 21000.into());
+		assert!(light.check_transaction_proof(&t, &header, &[]).is_none());
+	}
+}
@@ -0,0 +1,148 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Gas profiling tracer.
+
+use std::sync::Arc;
+use util::{U256, H256, Mutex, Hashable};
+use evm::interpreter::debugger::Step;
+use trace::trace::{VMTrace, GasProfile};
+use trace::VMTracer;
+
+/// VM tracer attributing the gas spent by every executed instruction to its opcode,
+/// program counter and code. Gas a call or create forwards to its callee is attributed
+/// to the callee's instructions.
+///
+/// All frames record into the same shared profile, so it may be reused to aggregate
+/// several transactions.
+pub struct GasProfilerVMTracer {
+	profile: Arc<Mutex<GasProfile>>,
+	code_hash: H256,
+	pending: Option<(usize, u8, U256)>,
+	gas: U256,
+	sub_gas: U256,
+}
+
+impl GasProfilerVMTracer {
+	/// Create a new top-level instance recording into `profile`.
+	pub fn new(profile: Arc<Mutex<GasProfile>>) -> Self {
+		GasProfilerVMTracer {
+			profile: profile,
+			code_hash: H256::new(),
+			pending: None,
+			gas: U256::zero(),
+			sub_gas: U256::zero(),
+		}
+	}
+}
+
+impl VMTracer for GasProfilerVMTracer {
+	fn trace_steps(&self) -> bool { true }
+
+	fn trace_step(&mut self, step: &Step) {
+		self.pending = Some((step.pc, step.instruction, step.gas));
+	}
+
+	fn trace_prepare_execute(&mut self, _pc: usize, _instruction: u8, _gas_cost: &U256) -> bool { true }
+
+	fn trace_executed(&mut self, gas_left: U256, _stack_push: &[U256], _mem_diff: Option<(usize, &[u8])>, _store_diff: Option<(U256, U256)>) {
+		if let Some((pc, instruction, gas_before)) = self.pending.take() {
+			let used = match gas_before > gas_left {
+				true => gas_before - gas_left,
+				false => U256::zero(),
+			};
+			let own = match used > self.sub_gas {
+				true => used - self.sub_gas,
+				false => U256::zero(),
+			};
+			self.sub_gas = U256::zero();
+			self.gas = self.gas + used;
+			self.profile.lock().record(&self.code_hash, pc, instruction, own);
+		}
+	}
+
+	fn prepare_subtrace(&self, code: &[u8]) -> Self {
+		let code_hash = code.sha3();
+		self.profile.lock().record_call(&code_hash);
+		GasProfilerVMTracer {
+			code_hash: code_hash,
+			..GasProfilerVMTracer::new(self.profile.clone())
+		}
+	}
+
+	fn done_subtrace(&mut self, sub: Self) {
+		self.sub_gas = self.sub_gas + sub.gas;
+	}
+
+	fn drain(self) -> Option<VMTrace> { None }
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use util::{U256, Mutex, Hashable};
+	use evm::interpreter::debugger::Step;
+	use trace::VMTracer;
+	use trace::trace::GasProfile;
+	use super::GasProfilerVMTracer;
+
+	fn step(tracer: &mut GasProfilerVMTracer, pc: usize, instruction: u8, gas: u64, gas_left: u64) {
+		tracer.trace_step(&Step {
+			pc: pc,
+			instruction: instruction,
+			name: "",
+			gas: U256::from(gas),
+			gas_cost: U256::zero(),
+			depth: 0,
+			stack: &[],
+			memory: &[],
+		});
+		assert!(tracer.trace_prepare_execute(pc, instruction, &U256::zero()));
+		tracer.trace_executed(U256::from(gas_left), &[], None, None);
+	}
+
+	#[test]
+	fn attributes_gas_to_callee() {
+		let profile = Arc::new(Mutex::new(GasProfile::default()));
+		let root = GasProfilerVMTracer::new(profile.clone());
+		let mut caller = root.prepare_subtrace(&[0xf1]);
+
+		step(&mut caller, 0, 0x60, 1000, 997);
+		{
+			let mut callee = caller.prepare_subtrace(&[0x60, 0x00]);
+			step(&mut callee, 0, 0x60, 500, 497);
+			step(&mut callee, 2, 0x60, 497, 494);
+			caller.done_subtrace(callee);
+		}
+		// the call reserves gas for the callee and gets back what it did not use.
+		step(&mut caller, 2, 0xf1, 997, 300);
+
+		let profile = profile.lock();
+		assert_eq!(profile.gas, U256::from(700));
+		assert_eq!(profile.instructions.len(), 2);
+		assert_eq!(profile.instructions[0].instruction, 0x60);
+		assert_eq!(profile.instructions[0].count, 3);
+		assert_eq!(profile.instructions[0].gas, U256::from(9));
+		assert_eq!(profile.instructions[1].instruction, 0xf1);
+		assert_eq!(profile.instructions[1].gas, U256::from(691));
+
+		let callee = profile.codes.iter().find(|c| c.code_hash == [0x60u8, 0x00].sha3()).unwrap();
+		assert_eq!(callee.calls, 1);
+		assert_eq!(callee.gas, U256::from(6));
+		assert_eq!(callee.pcs.len(), 2);
+		assert_eq!(callee.pcs[1].pc, 2);
+	}
+}
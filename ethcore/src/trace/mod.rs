@@ -21,18 +21,23 @@ mod config;
 mod db;
 mod error;
 mod executive_tracer;
+mod gas_profiler;
 mod import;
 mod noop_tracer;
+mod step_log_tracer;
 
 pub use types::trace_types::{filter, flat, localized, trace};
 pub use types::trace_types::error::Error as TraceError;
 pub use self::config::Config;
 pub use self::db::TraceDB;
 pub use self::error::Error;
-pub use types::trace_types::trace::{VMTrace, VMOperation, VMExecutedOperation, MemoryDiff, StorageDiff};
+pub use types::trace_types::trace::{VMTrace, VMOperation, VMExecutedOperation, MemoryDiff, StorageDiff, StepLogEntry};
+pub use types::trace_types::trace::{GasProfile, CodeGasProfile, InstructionGas, PcGas};
 pub use types::trace_types::flat::{FlatTrace, FlatTransactionTraces, FlatBlockTraces};
 pub use self::noop_tracer::{NoopTracer, NoopVMTracer};
 pub use self::executive_tracer::{ExecutiveTracer, ExecutiveVMTracer};
+pub use self::step_log_tracer::{StepLogVMTracer, StepLogSink, JsonLines};
+pub use self::gas_profiler::GasProfilerVMTracer;
 pub use types::trace_types::filter::{Filter, AddressesFilter};
 pub use self::import::ImportRequest;
 pub use self::localized::LocalizedTrace;
@@ -40,6 +45,7 @@ use util::{Bytes, Address, U256, H256, DBTransaction};
 use self::trace::{Call, Create};
 use action_params::ActionParams;
 use header::BlockNumber;
+use evm::interpreter::debugger::Step;
 
 /// This trait is used by executive to build traces.
 pub trait Tracer: Send {
@@ -96,6 +102,12 @@ pub trait VMTracer: Send {
 	/// Trace the finalised execution of a single instruction.
 	fn trace_executed(&mut self, _gas_used: U256, _stack_push: &[U256], _mem_diff: Option<(usize, &[u8])>, _store_diff: Option<(U256, U256)>) {}
 
+	/// Returns true if `trace_step` should be called before every instruction.
+	fn trace_steps(&self) -> bool { false }
+
+	/// Trace the full interpreter state before executing a single instruction.
+	fn trace_step(&mut self, _step: &Step) {}
+
 	/// Spawn subtracer which will be used to trace deeper levels of execution.
 	fn prepare_subtrace(&self, code: &[u8]) -> Self where Self: Sized;
 
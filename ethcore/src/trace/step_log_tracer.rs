@@ -0,0 +1,202 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Step log tracer.
+
+use std::io::Write;
+use std::sync::Arc;
+use std::collections::BTreeMap;
+use util::{U256, Mutex};
+use util::bytes::ToPretty;
+use evm::interpreter::debugger::{Step, instruction_name};
+use trace::trace::{VMTrace, StepLogEntry, StorageDiff};
+use trace::VMTracer;
+use types::call_analytics::StepLogOptions;
+
+const SLOAD: u8 = 0x54;
+const SSTORE: u8 = 0x55;
+
+/// Receives step log entries.
+pub trait StepLogSink: Send {
+	/// Handle the entry of an instruction about to be executed.
+	fn log(&mut self, entry: StepLogEntry);
+}
+
+impl StepLogSink for Vec<StepLogEntry> {
+	fn log(&mut self, entry: StepLogEntry) {
+		self.push(entry);
+	}
+}
+
+/// Writes step log entries as JSON, one object per line.
+pub struct JsonLines<W: Write + Send>(pub W);
+
+impl<W: Write + Send> StepLogSink for JsonLines<W> {
+	fn log(&mut self, entry: StepLogEntry) {
+		let _ = writeln!(self.0, "{}", to_json(&entry));
+	}
+}
+
+fn hex(value: &U256) -> String {
+	match *value == U256::zero() {
+		true => "0x0".to_owned(),
+		false => format!("{:x}", value),
+	}
+}
+
+fn to_json(entry: &StepLogEntry) -> String {
+	let mut json = format!(r#"{{"pc":{},"op":{},"opName":"{}","gas":"{}","gasCost":"{}","depth":{},"stack":[{}],"memSize":{}"#,
+		entry.pc,
+		entry.instruction,
+		instruction_name(entry.instruction).unwrap_or(""),
+		hex(&entry.gas),
+		hex(&entry.gas_cost),
+		entry.depth,
+		entry.stack.iter().map(|s| format!("\"{}\"", hex(s))).collect::<Vec<_>>().join(","),
+		entry.mem_size,
+	);
+	if let Some(ref memory) = entry.memory {
+		json.push_str(&format!(r#","memory":"0x{}""#, memory.to_hex()));
+	}
+	if let Some(ref storage) = entry.storage {
+		json.push_str(&format!(r#","storage":{{{}}}"#, storage.iter()
+			.map(|s| format!("\"{}\":\"{}\"", hex(&s.location), hex(&s.value)))
+			.collect::<Vec<_>>()
+			.join(",")));
+	}
+	json.push('}');
+	json
+}
+
+/// VM tracer recording the interpreter state before every executed instruction.
+/// Entries of all call depths go to the same sink in execution order.
+pub struct StepLogVMTracer<S: StepLogSink> {
+	sink: Arc<Mutex<S>>,
+	options: StepLogOptions,
+	storage: BTreeMap<U256, U256>,
+	pending_load: Option<U256>,
+}
+
+impl<S: StepLogSink> StepLogVMTracer<S> {
+	/// Create a new top-level instance logging to `sink`.
+	pub fn new(sink: Arc<Mutex<S>>, options: StepLogOptions) -> Self {
+		StepLogVMTracer {
+			sink: sink,
+			options: options,
+			storage: BTreeMap::new(),
+			pending_load: None,
+		}
+	}
+}
+
+impl<S: StepLogSink> VMTracer for StepLogVMTracer<S> {
+	fn trace_steps(&self) -> bool { true }
+
+	fn trace_step(&mut self, step: &Step) {
+		if self.options.storage && step.instruction == SSTORE && step.stack.len() >= 2 {
+			let len = step.stack.len();
+			self.storage.insert(step.stack[len - 1], step.stack[len - 2]);
+		}
+		self.pending_load = match self.options.storage && step.instruction == SLOAD {
+			true => step.stack.last().cloned(),
+			false => None,
+		};
+		let storage = match self.options.storage {
+			true => Some(self.storage.iter().map(|(k, v)| StorageDiff { location: *k, value: *v }).collect()),
+			false => None,
+		};
+		self.sink.lock().log(StepLogEntry {
+			pc: step.pc,
+			instruction: step.instruction,
+			gas: step.gas,
+			gas_cost: step.gas_cost,
+			depth: step.depth,
+			stack: step.stack.to_vec(),
+			mem_size: step.memory.len(),
+			memory: match self.options.memory {
+				true => Some(step.memory.to_vec()),
+				false => None,
+			},
+			storage: storage,
+		});
+	}
+
+	fn trace_prepare_execute(&mut self, _pc: usize, _instruction: u8, _gas_cost: &U256) -> bool {
+		self.pending_load.is_some()
+	}
+
+	fn trace_executed(&mut self, _gas_used: U256, stack_push: &[U256], _mem_diff: Option<(usize, &[u8])>, _store_diff: Option<(U256, U256)>) {
+		if let (Some(key), Some(value)) = (self.pending_load.take(), stack_push.first()) {
+			self.storage.insert(key, *value);
+		}
+	}
+
+	fn prepare_subtrace(&self, _code: &[u8]) -> Self {
+		StepLogVMTracer::new(self.sink.clone(), self.options)
+	}
+
+	fn done_subtrace(&mut self, _sub: Self) {}
+
+	fn drain(self) -> Option<VMTrace> { None }
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use util::{U256, Mutex};
+	use evm::interpreter::debugger::Step;
+	use trace::VMTracer;
+	use types::call_analytics::StepLogOptions;
+	use super::{StepLogVMTracer, JsonLines, SLOAD};
+
+	#[test]
+	fn logs_steps_as_json() {
+		let out = Arc::new(Mutex::new(JsonLines(Vec::new())));
+		let mut tracer = StepLogVMTracer::new(out.clone(), StepLogOptions { memory: true, storage: true });
+		let stack = [U256::from(0x10), U256::from(1)];
+		let memory = [0xffu8, 0];
+
+		tracer.trace_step(&Step {
+			pc: 3,
+			instruction: SLOAD,
+			name: "SLOAD",
+			gas: U256::from(1000),
+			gas_cost: U256::from(50),
+			depth: 1,
+			stack: &stack,
+			memory: &memory,
+		});
+		assert!(tracer.trace_prepare_execute(3, SLOAD, &U256::from(50)));
+		tracer.trace_executed(U256::from(950), &[U256::from(7)], None, None);
+		tracer.trace_step(&Step {
+			pc: 4,
+			instruction: 0x00,
+			name: "STOP",
+			gas: U256::from(950),
+			gas_cost: U256::zero(),
+			depth: 1,
+			stack: &stack[..1],
+			memory: &memory,
+		});
+
+		let out = String::from_utf8(out.lock().0.clone()).unwrap();
+		let lines: Vec<_> = out.lines().collect();
+		assert_eq!(lines, vec![
+			r#"{"pc":3,"op":84,"opName":"SLOAD","gas":"0x3e8","gasCost":"0x32","depth":1,"stack":["0x10","0x1"],"memSize":2,"memory":"0xff00","storage":{}}"#,
+			r#"{"pc":4,"op":0,"opName":"STOP","gas":"0x3b6","gasCost":"0x0","depth":1,"stack":["0x10"],"memSize":2,"memory":"0xff00","storage":{"0x1":"0x7"}}"#,
+		]);
+	}
+}
@@ -25,4 +25,19 @@ pub struct CallAnalytics {
 	pub vm_tracing: bool,
 	/// Make a diff.
 	pub state_diffing: bool,
+	/// Make a step log.
+	pub step_logging: Option<StepLogOptions>,
+	/// Make a gas profile.
+	pub gas_profiling: bool,
+	/// Record accessed accounts and storage.
+	pub state_accessing: bool,
+}
+
+/// Optional contents of a step log.
+#[derive(Eq, PartialEq, Default, Clone, Copy, Debug, Binary)]
+pub struct StepLogOptions {
+	/// Include memory contents.
+	pub memory: bool,
+	/// Include storage read or written so far.
+	pub storage: bool,
 }
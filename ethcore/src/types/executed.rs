@@ -18,9 +18,10 @@
 
 use util::{Bytes, U256, Address, U512};
 use rlp::*;
-use trace::{VMTrace, FlatTrace};
+use trace::{VMTrace, FlatTrace, StepLogEntry, GasProfile};
 use types::log_entry::LogEntry;
 use types::state_diff::StateDiff;
+use types::state_accesses::StateAccesses;
 use std::fmt;
 
 /// The type of the call-like instruction.
@@ -98,6 +99,12 @@ pub struct Executed {
 	pub vm_trace: Option<VMTrace>,
 	/// The state diff, if we traced it.
 	pub state_diff: Option<StateDiff>,
+	/// The step log, if we recorded it.
+	pub step_log: Option<Vec<StepLogEntry>>,
+	/// The gas profile, if we recorded it.
+	pub gas_profile: Option<GasProfile>,
+	/// The accessed accounts and storage, if we recorded them.
+	pub state_accesses: Option<StateAccesses>,
 }
 
 /// Result of executing the transaction.
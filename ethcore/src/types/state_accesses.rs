@@ -0,0 +1,165 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Accounts and storage touched by a transaction.
+
+use std::collections::BTreeMap;
+use util::{H256, Address};
+
+/// Whether something was read, written or both.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Binary)]
+pub struct Access {
+	/// It was read.
+	pub read: bool,
+	/// It was written.
+	pub written: bool,
+}
+
+impl Access {
+	/// Whether this access and `other` can't be reordered, i.e. either of them writes.
+	pub fn conflicts_with(&self, other: &Access) -> bool {
+		(self.written && (other.read || other.written)) || (other.written && self.read)
+	}
+}
+
+/// Accesses of a single account.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Binary)]
+pub struct AccountAccesses {
+	/// Access of the account's balance, nonce, code or existence.
+	pub account: Access,
+	/// Accessed storage slots.
+	pub storage: BTreeMap<H256, Access>,
+}
+
+/// Every account and storage slot read or written during execution.
+///
+/// Gas payment, nonce increment, refund and fee payment are not part of `accounts`: every
+/// transaction performs them, so recording them as writes would make any two transactions
+/// conflict. They are summarised by `sender` and `fee_recipient` instead.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Binary)]
+pub struct StateAccesses {
+	/// Accesses by account address, excluding transaction bookkeeping.
+	pub accounts: BTreeMap<Address, AccountAccesses>,
+	/// Account whose nonce was incremented and which paid for gas.
+	pub sender: Option<Address>,
+	/// Account credited with the transaction fee. Fee credits commute, so two executions
+	/// paying the same recipient don't conflict.
+	pub fee_recipient: Option<Address>,
+}
+
+impl StateAccesses {
+	/// Note that account `a` was read.
+	pub fn read_account(&mut self, a: &Address) {
+		self.account_mut(a).account.read = true;
+	}
+
+	/// Note that account `a` was written.
+	pub fn write_account(&mut self, a: &Address) {
+		self.account_mut(a).account.written = true;
+	}
+
+	/// Note that storage `key` of account `a` was read.
+	pub fn read_storage(&mut self, a: &Address, key: &H256) {
+		self.account_mut(a).storage.entry(key.clone()).or_insert_with(Access::default).read = true;
+	}
+
+	/// Note that storage `key` of account `a` was written.
+	pub fn write_storage(&mut self, a: &Address, key: &H256) {
+		self.account_mut(a).storage.entry(key.clone()).or_insert_with(Access::default).written = true;
+	}
+
+	/// Whether executions with these accesses and `other` depend on their order.
+	///
+	/// Besides conflicting account and storage accesses, executions conflict if they share
+	/// a sender or if one touches the other's sender or fee recipient.
+	pub fn conflicts_with(&self, other: &StateAccesses) -> bool {
+		self.conflicts_with_bookkeeping_of(other) || other.conflicts_with_bookkeeping_of(self) || self.accounts.iter().any(|(address, accesses)| match other.accounts.get(address) {
+			Some(other) => accesses.account.conflicts_with(&other.account)
+				|| accesses.storage.iter().any(|(key, access)| other.storage.get(key).map_or(false, |o| access.conflicts_with(o))),
+			None => false,
+		})
+	}
+
+	fn conflicts_with_bookkeeping_of(&self, other: &StateAccesses) -> bool {
+		(self.sender.is_some() && self.sender == other.sender)
+			|| other.sender.map_or(false, |a| self.accounts.contains_key(&a))
+			|| other.fee_recipient.map_or(false, |a| self.accounts.contains_key(&a))
+	}
+
+	fn account_mut(&mut self, a: &Address) -> &mut AccountAccesses {
+		self.accounts.entry(a.clone()).or_insert_with(AccountAccesses::default)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use util::Address;
+	use super::StateAccesses;
+
+	#[test]
+	fn reads_do_not_conflict() {
+		let a = Address::from(1);
+		let mut first = StateAccesses::default();
+		first.read_account(&a);
+		first.read_storage(&a, &1.into());
+		let mut second = first.clone();
+		assert!(!first.conflicts_with(&second));
+
+		second.write_storage(&a, &2.into());
+		assert!(!first.conflicts_with(&second));
+
+		second.write_storage(&a, &1.into());
+		assert!(first.conflicts_with(&second));
+		assert!(second.conflicts_with(&first));
+	}
+
+	#[test]
+	fn account_writes_conflict() {
+		let a = Address::from(1);
+		let mut first = StateAccesses::default();
+		first.write_account(&a);
+		let mut second = StateAccesses::default();
+		second.write_storage(&a, &1.into());
+		assert!(!first.conflicts_with(&second));
+
+		second.read_account(&a);
+		assert!(first.conflicts_with(&second));
+	}
+
+	#[test]
+	fn bookkeeping_conflicts() {
+		let author = Address::from(1);
+		let mut first = StateAccesses::default();
+		first.sender = Some(Address::from(2));
+		first.fee_recipient = Some(author);
+		let mut second = StateAccesses::default();
+		second.sender = Some(Address::from(3));
+		second.fee_recipient = Some(author);
+		assert!(!first.conflicts_with(&second));
+
+		second.read_account(&author);
+		assert!(first.conflicts_with(&second));
+		assert!(second.conflicts_with(&first));
+
+		second.accounts.clear();
+		second.read_account(&Address::from(2));
+		assert!(first.conflicts_with(&second));
+
+		second.accounts.clear();
+		second.sender = Some(Address::from(2));
+		assert!(first.conflicts_with(&second));
+	}
+}
@@ -0,0 +1,49 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Hypothetical state to execute calls against.
+
+use std::collections::BTreeMap;
+use util::{U256, H256, Address, Bytes};
+
+/// Changes to an account made before executing a call.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Binary)]
+pub struct AccountOverride {
+	/// Balance to set.
+	pub balance: Option<U256>,
+	/// Nonce to set.
+	pub nonce: Option<U256>,
+	/// Code to set.
+	pub code: Option<Bytes>,
+	/// Storage replacing all of the account's storage.
+	pub state: Option<BTreeMap<H256, H256>>,
+	/// Storage slots to set, applied after `state`.
+	pub state_diff: Option<BTreeMap<H256, H256>>,
+}
+
+/// Changes to the state made before executing a call.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Binary)]
+pub struct StateOverride {
+	/// Changes of each account.
+	pub accounts: BTreeMap<Address, AccountOverride>,
+}
+
+impl StateOverride {
+	/// Whether there are no changes.
+	pub fn is_empty(&self) -> bool {
+		self.accounts.is_empty()
+	}
+}
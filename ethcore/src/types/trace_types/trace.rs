@@ -16,7 +16,7 @@
 
 //! Tracing datatypes.
 
-use util::{U256, Bytes, Address};
+use util::{U256, H256, Bytes, Address};
 use util::sha3::Hashable;
 use util::bloom::Bloomable;
 use rlp::*;
@@ -550,3 +550,132 @@ impl Decodable for VMTrace {
 	}
 }
 
+#[derive(Debug, Clone, PartialEq, Binary)]
+/// Interpreter state before executing a single instruction, as recorded in a step log.
+pub struct StepLogEntry {
+	/// The program counter.
+	pub pc: usize,
+	/// The instruction.
+	pub instruction: u8,
+	/// The gas left before executing the instruction.
+	pub gas: U256,
+	/// The gas cost of the instruction.
+	pub gas_cost: U256,
+	/// The call depth, 1 for the outermost call.
+	pub depth: usize,
+	/// The stack, bottom first.
+	pub stack: Vec<U256>,
+	/// The size of memory in bytes.
+	pub mem_size: usize,
+	/// The memory contents, if requested.
+	pub memory: Option<Bytes>,
+	/// The storage of the executing contract read or written so far, if requested.
+	pub storage: Option<Vec<StorageDiff>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Binary)]
+/// Gas spent on all executions of a single opcode.
+pub struct InstructionGas {
+	/// The instruction.
+	pub instruction: u8,
+	/// How many times it was executed.
+	pub count: u64,
+	/// The gas spent, not including gas spent by the calls it made.
+	pub gas: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Binary)]
+/// Gas spent on all executions of the instruction at a single program counter.
+pub struct PcGas {
+	/// The program counter.
+	pub pc: usize,
+	/// The instruction.
+	pub instruction: u8,
+	/// How many times it was executed.
+	pub count: u64,
+	/// The gas spent, not including gas spent by the calls it made.
+	pub gas: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Binary)]
+/// Gas spent in all call frames executing the same code.
+pub struct CodeGasProfile {
+	/// The hash of the code.
+	pub code_hash: H256,
+	/// How many frames executed it.
+	pub calls: u64,
+	/// The gas spent by its instructions.
+	pub gas: U256,
+	/// The gas spent per program counter, ordered by program counter.
+	pub pcs: Vec<PcGas>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Binary)]
+/// Gas consumed by executed code, attributed to opcodes, program counters and code.
+pub struct GasProfile {
+	/// The gas spent by all instructions.
+	pub gas: U256,
+	/// The gas spent per opcode, ordered by opcode.
+	pub instructions: Vec<InstructionGas>,
+	/// The gas spent per code, ordered by code hash.
+	pub codes: Vec<CodeGasProfile>,
+}
+
+impl GasProfile {
+	/// Note the start of a call frame executing the code with the given hash.
+	pub fn record_call(&mut self, code_hash: &H256) {
+		self.code(code_hash).calls += 1;
+	}
+
+	/// Note the execution of an instruction.
+	pub fn record(&mut self, code_hash: &H256, pc: usize, instruction: u8, gas: U256) {
+		self.record_many(code_hash, pc, instruction, 1, gas);
+	}
+
+	/// Add all of another profile to this one.
+	pub fn merge(&mut self, other: GasProfile) {
+		for code in other.codes {
+			self.code(&code.code_hash).calls += code.calls;
+			for pc in code.pcs {
+				self.record_many(&code.code_hash, pc.pc, pc.instruction, pc.count, pc.gas);
+			}
+		}
+	}
+
+	fn record_many(&mut self, code_hash: &H256, pc: usize, instruction: u8, count: u64, gas: U256) {
+		self.gas = self.gas + gas;
+
+		let index = match self.instructions.binary_search_by_key(&instruction, |i| i.instruction) {
+			Ok(index) => index,
+			Err(index) => {
+				self.instructions.insert(index, InstructionGas { instruction: instruction, ..Default::default() });
+				index
+			},
+		};
+		self.instructions[index].count += count;
+		self.instructions[index].gas = self.instructions[index].gas + gas;
+
+		let code = self.code(code_hash);
+		code.gas = code.gas + gas;
+		let index = match code.pcs.binary_search_by_key(&pc, |p| p.pc) {
+			Ok(index) => index,
+			Err(index) => {
+				code.pcs.insert(index, PcGas { pc: pc, instruction: instruction, ..Default::default() });
+				index
+			},
+		};
+		code.pcs[index].count += count;
+		code.pcs[index].gas = code.pcs[index].gas + gas;
+	}
+
+	fn code(&mut self, code_hash: &H256) -> &mut CodeGasProfile {
+		let index = match self.codes.binary_search_by(|c| c.code_hash.cmp(code_hash)) {
+			Ok(index) => index,
+			Err(index) => {
+				self.codes.insert(index, CodeGasProfile { code_hash: code_hash.clone(), ..Default::default() });
+				index
+			},
+		};
+		&mut self.codes[index]
+	}
+}
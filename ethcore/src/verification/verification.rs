@@ -176,6 +176,13 @@ pub fn verify_block_family(header: &Header, bytes: &[u8], engine: &Engine, bc: &
 	Ok(())
 }
 
+/// Phase 3 verification for headers without bodies. Check header information against its parent.
+pub fn verify_header_family(header: &Header, parent: &Header, engine: &Engine) -> Result<(), Error> {
+	try!(verify_parent(header, parent));
+	try!(engine.verify_block_family(header, parent, None));
+	Ok(())
+}
+
 /// Phase 4 verification. Check block information against transaction enactment results,
 pub fn verify_block_final(expected: &Header, got: &Header) -> Result<(), Error> {
 	if expected.gas_used() != got.gas_used() {
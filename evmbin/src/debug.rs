@@ -0,0 +1,178 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Interactive step-through debugger.
+
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use rustc_serialize::hex::ToHex;
+use util::{U256, H256};
+use ethcore::evm::Ext;
+use ethcore::evm::interpreter::debugger::{self, Debugger, Step, Breakpoints, Breakpoint};
+
+const HELP: &'static str = r#"Commands:
+    s, step                  Execute the next instruction
+    c, continue              Run until the next breakpoint
+    b, break PC|OPCODE       Set a breakpoint
+    d, delete PC|OPCODE      Remove a breakpoint
+    l, list                  List breakpoints
+    i, info                  Show the current instruction
+    st, stack                Show the stack, top first
+    m, memory [OFFSET [LEN]] Show memory (hex offsets)
+    sl, storage KEY          Show a storage value (hex key)
+    q, quit                  Stop debugging and exit
+    h, help                  Show this message"#;
+
+/// Parse a breakpoint given as a decimal or `0x`-prefixed program counter or an opcode name.
+pub fn parse_breakpoint(s: &str) -> Result<Breakpoint, String> {
+	if s.starts_with("0x") {
+		return usize::from_str_radix(&s[2..], 16).map(Breakpoint::Pc).map_err(|e| format!("Invalid program counter {}: {}", s, e));
+	}
+	if let Ok(pc) = usize::from_str(s) {
+		return Ok(Breakpoint::Pc(pc));
+	}
+	debugger::instruction_from_name(s)
+		.map(Breakpoint::Instruction)
+		.ok_or_else(|| format!("Unknown instruction {}", s))
+}
+
+fn format_breakpoint(breakpoint: &Breakpoint) -> String {
+	match *breakpoint {
+		Breakpoint::Pc(pc) => format!("pc {} (0x{:x})", pc, pc),
+		Breakpoint::Instruction(i) => debugger::instruction_name(i).unwrap_or("?").to_owned(),
+	}
+}
+
+/// Debugger reading commands from the standard input.
+pub struct Console {
+	breakpoints: Breakpoints,
+	detached: bool,
+}
+
+impl Console {
+	/// Create a console debugger. Single-steps from the start unless breakpoints are given.
+	pub fn new(breakpoints: Vec<Breakpoint>) -> Self {
+		let mut console = Console {
+			breakpoints: Breakpoints::default(),
+			detached: false,
+		};
+		if !breakpoints.is_empty() {
+			console.breakpoints.resume();
+		}
+		for b in breakpoints {
+			console.breakpoints.add(b);
+		}
+		console
+	}
+
+	fn print_step(step: &Step) {
+		println!("[{}] 0x{:04x} {:<12} gas: {} cost: {}", step.depth, step.pc, step.name, step.gas, step.gas_cost);
+	}
+
+	fn print_stack(step: &Step) {
+		if step.stack.is_empty() {
+			println!("Stack is empty");
+		}
+		for (i, item) in step.stack.iter().rev().enumerate() {
+			println!("{:4}: {}", i, H256::from(item));
+		}
+	}
+
+	fn print_memory(step: &Step, offset: usize, len: usize) {
+		let end = ::std::cmp::min(offset.saturating_add(len), step.memory.len());
+		if offset >= end {
+			println!("Memory size is 0x{:x}", step.memory.len());
+			return;
+		}
+		let mut pos = offset;
+		while pos < end {
+			let row_end = ::std::cmp::min(pos + 32, end);
+			println!("0x{:04x}: {}", pos, step.memory[pos..row_end].to_hex());
+			pos = row_end;
+		}
+	}
+
+	/// Handle a single command. Returns true if execution should go on.
+	fn command(&mut self, line: &str, step: &Step, ext: &Ext) -> bool {
+		let mut words = line.split_whitespace();
+		let command = match words.next() {
+			Some(command) => command,
+			None => return false,
+		};
+		let arg = words.next();
+		match (command, arg) {
+			("s", _) | ("step", _) => {
+				self.breakpoints.single_step();
+				return true;
+			},
+			("c", _) | ("continue", _) => {
+				self.breakpoints.resume();
+				return true;
+			},
+			("b", Some(arg)) | ("break", Some(arg)) => match parse_breakpoint(arg) {
+				Ok(b) => if !self.breakpoints.add(b) { println!("Breakpoint is already set") },
+				Err(e) => println!("{}", e),
+			},
+			("d", Some(arg)) | ("delete", Some(arg)) => match parse_breakpoint(arg) {
+				Ok(b) => if !self.breakpoints.remove(&b) { println!("No such breakpoint") },
+				Err(e) => println!("{}", e),
+			},
+			("l", _) | ("list", _) => for b in self.breakpoints.list() {
+				println!("{}", format_breakpoint(b));
+			},
+			("i", _) | ("info", _) => Self::print_step(step),
+			("st", _) | ("stack", _) => Self::print_stack(step),
+			("m", _) | ("memory", _) => {
+				let offset = arg.and_then(|o| usize::from_str_radix(o, 16).ok()).unwrap_or(0);
+				let len = words.next().and_then(|l| usize::from_str_radix(l, 16).ok()).unwrap_or(step.memory.len());
+				Self::print_memory(step, offset, len);
+			},
+			("sl", Some(arg)) | ("storage", Some(arg)) => match U256::from_str(arg.trim_left_matches("0x")) {
+				Ok(key) => println!("{}", ext.storage_at(&H256::from(key))),
+				Err(_) => println!("Invalid storage key {}", arg),
+			},
+			("q", _) | ("quit", _) => ::std::process::exit(0),
+			("h", _) | ("help", _) => println!("{}", HELP),
+			_ => println!("Unknown command. Type `help` for the list of commands."),
+		}
+		false
+	}
+}
+
+impl Debugger for Console {
+	fn step(&mut self, step: &Step, ext: &Ext) {
+		if self.detached || !self.breakpoints.should_break(step) {
+			return;
+		}
+		Self::print_step(step);
+		let stdin = io::stdin();
+		loop {
+			print!("(evm) ");
+			let _ = io::stdout().flush();
+			let mut line = String::new();
+			match stdin.lock().read_line(&mut line) {
+				Ok(0) | Err(_) => {
+					// No more input; run to completion.
+					self.detached = true;
+					return;
+				},
+				Ok(_) => if self.command(&line, step, ext) {
+					return;
+				},
+			}
+		}
+	}
+}
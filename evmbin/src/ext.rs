@@ -16,24 +16,51 @@
 
 //! Externalities implementation.
 
+use std::io::{self, Write};
 use std::sync::Arc;
 use std::collections::HashMap;
-use util::{U256, H256, Address, Bytes, FixedHash};
-use ethcore::client::EnvInfo;
+use util::{U256, H256, Address, Bytes, FixedHash, Uint, Mutex};
+use ethcore::client::{EnvInfo, BlockChainClient, BlockID, StepLogOptions};
 use ethcore::evm::{self, Ext, ContractCreateResult, MessageCallResult, Schedule, CallType};
+use ethcore::evm::interpreter::debugger::Step;
+use ethcore::trace::{VMTracer, StepLogVMTracer, JsonLines};
+
+/// Print a notice about an operation the externalities can't perform.
+fn unsupported(what: &str) {
+	let _ = writeln!(&mut io::stderr(), "{} not supported, the operation fails.", what);
+}
 
 pub struct FakeExt {
 	schedule: Schedule,
+	env_info: EnvInfo,
 	store: HashMap<H256, H256>,
 	depth: usize,
+	tracer: Option<Box<VMTracer>>,
 }
 
 impl Default for FakeExt {
 	fn default() -> Self {
 		FakeExt {
 			schedule: Schedule::new_homestead(),
+			env_info: EnvInfo::default(),
 			store: HashMap::new(),
-			depth: 1,
+			depth: 0,
+			tracer: None,
+		}
+	}
+}
+
+impl FakeExt {
+	/// Create externalities printing a JSON step log to the standard output.
+	pub fn with_step_log(options: StepLogOptions) -> Self {
+		FakeExt::with_tracer(StepLogVMTracer::new(Arc::new(Mutex::new(JsonLines(io::stdout()))), options))
+	}
+
+	/// Create externalities passing every executed instruction to the given tracer.
+	pub fn with_tracer<T: VMTracer + 'static>(tracer: T) -> Self {
+		FakeExt {
+			tracer: Some(Box::new(tracer)),
+			..Default::default()
 		}
 	}
 }
@@ -48,19 +75,20 @@ impl Ext for FakeExt {
 	}
 
 	fn exists(&self, _address: &Address) -> bool {
-		unimplemented!();
+		false
 	}
 
 	fn balance(&self, _address: &Address) -> U256 {
-		unimplemented!();
+		U256::zero()
 	}
 
 	fn blockhash(&self, _number: &U256) -> H256 {
-		unimplemented!();
+		H256::new()
 	}
 
 	fn create(&mut self, _gas: &U256, _value: &U256, _code: &[u8]) -> ContractCreateResult {
-		unimplemented!();
+		unsupported("Contract creation");
+		ContractCreateResult::Failed
 	}
 
 	fn call(&mut self,
@@ -72,19 +100,151 @@ impl Ext for FakeExt {
 			_code_address: &Address,
 			_output: &mut [u8],
 			_call_type: CallType) -> MessageCallResult {
-		unimplemented!();
+		unsupported("Message calls");
+		MessageCallResult::Failed
 	}
 
 	fn extcode(&self, _address: &Address) -> Arc<Bytes> {
-		unimplemented!();
+		Arc::new(Bytes::new())
 	}
 
 	fn extcodesize(&self, _address: &Address) -> usize {
-		unimplemented!();
+		0
+	}
+
+	fn log(&mut self, _topics: Vec<H256>, _data: &[u8]) {
+	}
+
+	fn ret(self, gas: &U256, _data: &[u8]) -> evm::Result<U256> {
+		Ok(*gas)
+	}
+
+	fn suicide(&mut self, _refund_address: &Address) {
+		unsupported("SUICIDE");
+	}
+
+	fn schedule(&self) -> &Schedule {
+		&self.schedule
+	}
+
+	fn env_info(&self) -> &EnvInfo {
+		&self.env_info
+	}
+
+	fn depth(&self) -> usize {
+		self.depth
+	}
+
+	fn inc_sstore_clears(&mut self) {
+	}
+
+	fn trace_prepare_execute(&mut self, pc: usize, instruction: u8, gas_cost: &U256) -> bool {
+		self.tracer.as_mut().map_or(false, |t| t.trace_prepare_execute(pc, instruction, gas_cost))
+	}
+
+	fn trace_executed(&mut self, gas_used: U256, stack_push: &[U256], mem_diff: Option<(usize, &[u8])>, store_diff: Option<(U256, U256)>) {
+		if let Some(ref mut tracer) = self.tracer {
+			tracer.trace_executed(gas_used, stack_push, mem_diff, store_diff);
+		}
+	}
+
+	fn trace_steps(&self) -> bool {
+		self.tracer.is_some()
+	}
+
+	fn trace_step(&mut self, step: &Step) {
+		if let Some(ref mut tracer) = self.tracer {
+			tracer.trace_step(step);
+		}
+	}
+}
+
+/// Externalities reading accounts from the state at a given block.
+/// Storage writes are kept in memory; calls, contract creation and suicides fail with a notice.
+pub struct StateExt {
+	client: Arc<BlockChainClient>,
+	block: BlockID,
+	address: Address,
+	env_info: EnvInfo,
+	schedule: Schedule,
+	store: HashMap<H256, H256>,
+	depth: usize,
+}
+
+impl StateExt {
+	/// Create externalities for code running at `address` on top of the state after `block`.
+	pub fn new(client: Arc<BlockChainClient>, block: BlockID, address: Address, env_info: EnvInfo, schedule: Schedule) -> Self {
+		StateExt {
+			client: client,
+			block: block,
+			address: address,
+			env_info: env_info,
+			schedule: schedule,
+			store: HashMap::new(),
+			depth: 0,
+		}
+	}
+}
+
+impl Ext for StateExt {
+	fn storage_at(&self, key: &H256) -> H256 {
+		match self.store.get(key) {
+			Some(value) => value.clone(),
+			None => self.client.storage_at(&self.address, key, self.block.clone()).unwrap_or_else(H256::new),
+		}
+	}
+
+	fn set_storage(&mut self, key: H256, value: H256) {
+		self.store.insert(key, value);
+	}
+
+	fn exists(&self, address: &Address) -> bool {
+		!self.balance(address).is_zero()
+			|| !self.client.nonce(address, self.block.clone()).unwrap_or_else(U256::zero).is_zero()
+			|| self.extcodesize(address) != 0
+	}
+
+	fn balance(&self, address: &Address) -> U256 {
+		self.client.balance(address, self.block.clone()).unwrap_or_else(U256::zero)
+	}
+
+	fn blockhash(&self, number: &U256) -> H256 {
+		match *number < U256::from(self.env_info.number) && number.low_u64() + 256 >= self.env_info.number {
+			true => {
+				let index = (self.env_info.number - number.low_u64() - 1) as usize;
+				self.env_info.last_hashes.get(index).cloned().unwrap_or_else(H256::new)
+			},
+			false => H256::new(),
+		}
+	}
+
+	fn create(&mut self, _gas: &U256, _value: &U256, _code: &[u8]) -> ContractCreateResult {
+		unsupported("Contract creation");
+		ContractCreateResult::Failed
+	}
+
+	fn call(&mut self,
+			_gas: &U256,
+			_sender_address: &Address,
+			_receive_address: &Address,
+			_value: Option<U256>,
+			_data: &[u8],
+			_code_address: &Address,
+			_output: &mut [u8],
+			_call_type: CallType) -> MessageCallResult {
+		unsupported("Message calls");
+		MessageCallResult::Failed
+	}
+
+	fn extcode(&self, address: &Address) -> Arc<Bytes> {
+		Arc::new(self.client.code(address, self.block.clone()).and_then(|c| c).unwrap_or_else(Vec::new))
+	}
+
+	fn extcodesize(&self, address: &Address) -> usize {
+		self.extcode(address).len()
 	}
 
 	fn log(&mut self, _topics: Vec<H256>, _data: &[u8]) {
-		unimplemented!();
 	}
 
 	fn ret(self, gas: &U256, _data: &[u8]) -> evm::Result<U256> {
@@ -92,7 +252,7 @@ impl Ext for FakeExt {
 	}
 
 	fn suicide(&mut self, _refund_address: &Address) {
-		unimplemented!();
+		unsupported("SUICIDE");
 	}
 
 	fn schedule(&self) -> &Schedule {
@@ -100,7 +260,7 @@ impl Ext for FakeExt {
 	}
 
 	fn env_info(&self) -> &EnvInfo {
-		unimplemented!()
+		&self.env_info
 	}
 
 	fn depth(&self) -> usize {
@@ -108,7 +268,5 @@ impl Ext for FakeExt {
 	}
 
 	fn inc_sstore_clears(&mut self) {
-		unimplemented!();
-		// self.sstore_clears += 1;
 	}
 }
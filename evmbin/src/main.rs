@@ -23,17 +23,32 @@ extern crate rustc_serialize;
 extern crate docopt;
 #[macro_use]
 extern crate ethcore_util as util;
+extern crate ethcore_io as io;
 
 mod ext;
+mod debug;
 
 use std::sync::Arc;
 use std::time::{Instant, Duration};
 use std::fmt;
+use std::fs::File;
+use std::path::Path;
 use std::str::FromStr;
 use docopt::Docopt;
-use util::{U256, FromHex, Uint, Bytes};
-use ethcore::evm::{self, Factory, VMType, Finalize};
+use util::{U256, H256, Address, FromHex, Uint, Bytes, Mutex};
+use util::kvdb::DatabaseConfig;
+use util::journaldb::Algorithm;
+use io::IoChannel;
+use ethcore::evm::{self, Factory, VMType, Finalize, Ext, GasLeft};
+use ethcore::evm::interpreter::{Interpreter, SharedCache};
+use ethcore::evm::interpreter::debugger::{Debugger, Breakpoint, instruction_name};
 use ethcore::action_params::ActionParams;
+use ethcore::client::{Client, ClientConfig, BlockChainClient, BlockID, EnvInfo, StepLogOptions};
+use ethcore::miner::Miner;
+use ethcore::trace::{VMTracer, GasProfile, GasProfilerVMTracer};
+use ethcore::spec::Spec;
+use ethcore::views::HeaderView;
+use ethcore::ethereum;
 
 const USAGE: &'static str = r#"
 EVM implementation for Parity.
@@ -41,6 +56,8 @@ EVM implementation for Parity.
 
 Usage:
     evmbin stats [options]
+    evmbin debug [options]
+    evmbin profile [options]
     evmbin [-h | --help]
 
 Transaction options:
@@ -48,6 +65,28 @@ Transaction options:
     --input DATA       Input data as hex (without 0x)
     --gas GAS          Supplied gas as hex (without 0x)
 
+Tracing options:
+    --json             Print the state before every instruction as JSON,
+                       one object per line.
+    --json-memory      Include memory contents in the JSON output.
+    --json-storage     Include storage read or written so far in the JSON
+                       output.
+
+Debugger options:
+    --break POINTS     Comma-separated breakpoints: program counters
+                       or opcode names (e.g. 0x1f,SSTORE). Single-steps
+                       from the first instruction if not given.
+    --db PATH          Run against the state of an existing database
+                       instead of an empty one.
+    --chain CHAIN      Chain of the database: frontier, classic, morden,
+                       expanse or a path to a JSON chain spec
+                       [default: frontier].
+    --pruning METHOD   Pruning method of the database: archive, fast,
+                       light or basic [default: fast].
+    --block NUMBER     Block to take the state from [default: latest].
+    --address ADDRESS  Address the code runs at (without 0x). With --db
+                       its code is used when --code is not given.
+
 General options:
     -h, --help         Display this message and exit.
 "#;
@@ -58,10 +97,39 @@ fn main() {
 
 	let mut params = ActionParams::default();
 	params.gas = args.gas();
-	params.code = Some(Arc::new(args.code()));
+	params.code = args.code().map(Arc::new);
 	params.data = args.data();
 
-	let result = run_vm(params);
+	if args.cmd_profile {
+		let code = params.code.clone().unwrap_or_else(|| die("Code is required."));
+		let profile = Arc::new(Mutex::new(GasProfile::default()));
+		let tracer = GasProfilerVMTracer::new(profile.clone()).prepare_subtrace(&code);
+		match run_vm_with_ext(params, ext::FakeExt::with_tracer(tracer)) {
+			Ok(success) => println!("{}", success),
+			Err(failure) => println!("{}", failure),
+		}
+		print!("{}", ProfileReport(&profile.lock()));
+		return;
+	}
+
+	let result = match args.cmd_debug {
+		true => {
+			let mut console = debug::Console::new(args.breakpoints());
+			params.address = args.address();
+			params.code_address = params.address.clone();
+			match args.flag_db {
+				Some(ref path) => {
+					let ext = open_state(&args, path, &mut params);
+					debug_vm(params, ext, &mut console)
+				},
+				None => debug_vm(params, ext::FakeExt::default(), &mut console),
+			}
+		},
+		false => match args.step_log_options() {
+			Some(options) => run_vm_with_ext(params, ext::FakeExt::with_step_log(options)),
+			None => run_vm(params),
+		},
+	};
 	match result {
 		Ok(success) => println!("{}", success),
 		Err(failure) => println!("{}", failure),
@@ -70,20 +138,26 @@ fn main() {
 
 /// Execute VM with given `ActionParams`
 pub fn run_vm(params: ActionParams) -> Result<Success, Failure> {
+	run_vm_with_ext(params, ext::FakeExt::default())
+}
+
+/// Execute VM with given `ActionParams` and externalities
+pub fn run_vm_with_ext<E: Ext>(params: ActionParams, mut ext: E) -> Result<Success, Failure> {
+	if params.code.is_none() {
+		die("Code is required.");
+	}
 	let initial_gas = params.gas;
 	let factory = Factory::new(VMType::Interpreter, 1024);
 	let mut vm = factory.create(params.gas);
-	let mut ext = ext::FakeExt::default();
 
 	let start = Instant::now();
-	let gas_left = vm.exec(params, &mut ext).finalize(ext);
+	let (gas_left, output) = finalize_with_output(vm.exec(params, &mut ext), ext);
 	let duration = start.elapsed();
 
 	match gas_left {
 		Ok(gas_left) => Ok(Success {
 			gas_used: initial_gas - gas_left,
-			// TODO [ToDr] get output from ext
-			output: Vec::new(),
+			output: output,
 			time: duration,
 		}),
 		Err(e) => Err(Failure {
@@ -93,6 +167,89 @@ pub fn run_vm(params: ActionParams) -> Result<Success, Failure> {
 	}
 }
 
+/// Execute VM with given `ActionParams` and externalities under a debugger.
+pub fn debug_vm<E: Ext>(params: ActionParams, mut ext: E, debugger: &mut Debugger) -> Result<Success, Failure> {
+	if params.code.is_none() {
+		die("Code is required.");
+	}
+	let initial_gas = params.gas;
+	let cache = Arc::new(SharedCache::default());
+
+	let start = Instant::now();
+	let (gas_left, output) = match params.gas == U256::from(params.gas.low_u64() as usize) {
+		true => {
+			let mut vm = Interpreter::<usize>::new(cache);
+			finalize_with_output(vm.exec_debug(params, &mut ext, debugger), ext)
+		},
+		false => {
+			let mut vm = Interpreter::<U256>::new(cache);
+			finalize_with_output(vm.exec_debug(params, &mut ext, debugger), ext)
+		},
+	};
+	let duration = start.elapsed();
+
+	match gas_left {
+		Ok(gas_left) => Ok(Success {
+			gas_used: initial_gas - gas_left,
+			output: output,
+			time: duration,
+		}),
+		Err(e) => Err(Failure {
+			error: e,
+			time: duration,
+		}),
+	}
+}
+
+/// Finalize the execution result, keeping the returned data.
+fn finalize_with_output<E: Ext>(result: evm::Result<GasLeft>, ext: E) -> (evm::Result<U256>, Vec<u8>) {
+	let output = match result {
+		Ok(GasLeft::NeedsReturn(_, data)) => data.to_vec(),
+		_ => Vec::new(),
+	};
+	(result.finalize(ext), output)
+}
+
+/// Open the database given with `--db` and prepare externalities over the state at `--block`.
+/// Takes the code from the state if it was not given.
+fn open_state(args: &Args, path: &str, params: &mut ActionParams) -> ext::StateExt {
+	let spec = args.spec();
+	let mut config = ClientConfig::default();
+	config.pruning = args.pruning();
+	let db_config = DatabaseConfig::with_columns(ethcore::db::NUM_COLUMNS);
+	let miner = Arc::new(Miner::with_spec(&spec));
+	let client = Client::new(config, &spec, Path::new(path), miner, IoChannel::disconnected(), &db_config)
+		.unwrap_or_else(|e| die(format!("Error opening database: {:?}", e)));
+
+	let number = match args.block_id() {
+		BlockID::Number(number) => number,
+		id => client.block_number(id).expect("latest block always exists; qed"),
+	};
+	let block = BlockID::Number(number);
+	let header = client.block_header(block.clone()).unwrap_or_else(|| die(format!("Unknown block {}.", number)));
+	if client.state_at(block.clone()).is_none() {
+		die(format!("State of block {} is not available.", number));
+	}
+	let header = HeaderView::new(&header);
+	let env_info = EnvInfo {
+		number: number,
+		author: header.author(),
+		timestamp: header.timestamp(),
+		difficulty: header.difficulty(),
+		gas_limit: header.gas_limit(),
+		last_hashes: Arc::new((1..::std::cmp::min(number + 1, 257))
+			.map(|i| client.block_hash(BlockID::Number(number - i)).unwrap_or_else(H256::new))
+			.collect()),
+		gas_used: U256::zero(),
+	};
+	let schedule = spec.engine.schedule(&env_info);
+
+	if params.code.is_none() {
+		params.code = client.code(&params.address, block.clone()).and_then(|c| c).map(Arc::new);
+	}
+	ext::StateExt::new(client, block, params.address.clone(), env_info, schedule)
+}
+
 /// Execution finished correctly
 pub struct Success {
 	/// Used gas
@@ -126,12 +283,55 @@ impl fmt::Display for Failure {
 	}
 }
 
+/// Gas profile of a run, formatted as per-opcode and per-program-counter tables.
+pub struct ProfileReport<'a>(&'a GasProfile);
+
+impl<'a> fmt::Display for ProfileReport<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+		let profile = self.0;
+		let mut instructions: Vec<_> = profile.instructions.iter().collect();
+		instructions.sort_by(|a, b| b.gas.cmp(&a.gas));
+
+		try!(writeln!(f, "Gas by opcode:"));
+		try!(writeln!(f, "{:<14} {:>8} {:>12}", "OPCODE", "COUNT", "GAS"));
+		for i in instructions {
+			try!(writeln!(f, "{:<14} {:>8} {:>12}", opcode_name(i.instruction), i.count, i.gas.low_u64()));
+		}
+
+		for code in &profile.codes {
+			try!(writeln!(f, ""));
+			try!(writeln!(f, "Gas by program counter in code {:?} ({} calls):", code.code_hash, code.calls));
+			try!(writeln!(f, "{:<8} {:<14} {:>8} {:>12}", "PC", "OPCODE", "COUNT", "GAS"));
+			for pc in &code.pcs {
+				try!(writeln!(f, "{:<8} {:<14} {:>8} {:>12}", format!("0x{:x}", pc.pc), opcode_name(pc.instruction), pc.count, pc.gas.low_u64()));
+			}
+		}
+		try!(writeln!(f, ""));
+		writeln!(f, "Total gas: {}", profile.gas.low_u64())
+	}
+}
+
+fn opcode_name(instruction: u8) -> String {
+	instruction_name(instruction).map_or_else(|| format!("0x{:02x}", instruction), str::to_owned)
+}
+
 #[derive(Debug, RustcDecodable)]
 struct Args {
 	cmd_stats: bool,
+	cmd_debug: bool,
+	cmd_profile: bool,
 	flag_code: Option<String>,
 	flag_gas: Option<String>,
 	flag_input: Option<String>,
+	flag_json: bool,
+	flag_json_memory: bool,
+	flag_json_storage: bool,
+	flag_break: Option<String>,
+	flag_db: Option<String>,
+	flag_chain: String,
+	flag_pruning: String,
+	flag_block: String,
+	flag_address: Option<String>,
 }
 
 impl Args {
@@ -142,11 +342,10 @@ impl Args {
 			.unwrap_or_else(|| !U256::zero())
 	}
 
-	pub fn code(&self) -> Bytes {
+	pub fn code(&self) -> Option<Bytes> {
 		self.flag_code
 			.clone()
-			.and_then(|c| c.from_hex().ok())
-			.unwrap_or_else(|| die("Code is required."))
+			.map(|c| c.from_hex().unwrap_or_else(|_| die("Invalid code.")))
 	}
 
 	pub fn data(&self) -> Option<Bytes> {
@@ -154,9 +353,52 @@ impl Args {
 			.clone()
 			.and_then(|d| d.from_hex().ok())
 	}
+
+	pub fn step_log_options(&self) -> Option<StepLogOptions> {
+		match self.flag_json || self.flag_json_memory || self.flag_json_storage {
+			true => Some(StepLogOptions { memory: self.flag_json_memory, storage: self.flag_json_storage }),
+			false => None,
+		}
+	}
+
+	pub fn breakpoints(&self) -> Vec<Breakpoint> {
+		self.flag_break.as_ref().map_or_else(Vec::new, |b| b.split(',')
+			.map(|b| debug::parse_breakpoint(b.trim()).unwrap_or_else(|e| die(e)))
+			.collect())
+	}
+
+	pub fn address(&self) -> Address {
+		self.flag_address
+			.as_ref()
+			.map_or_else(Address::default, |a| Address::from_str(a).unwrap_or_else(|_| die("Invalid address.")))
+	}
+
+	pub fn spec(&self) -> Spec {
+		match self.flag_chain.as_str() {
+			"frontier" | "homestead" | "mainnet" => ethereum::new_frontier(),
+			"classic" => ethereum::new_classic(),
+			"morden" | "testnet" => ethereum::new_morden(),
+			"expanse" => ethereum::new_expanse(),
+			path => {
+				let file = File::open(path).unwrap_or_else(|e| die(format!("Error opening chain spec {}: {}", path, e)));
+				Spec::load(file).unwrap_or_else(|e| die(e))
+			}
+		}
+	}
+
+	pub fn pruning(&self) -> Algorithm {
+		Algorithm::from_str(&self.flag_pruning).unwrap_or_else(|e| die(e))
+	}
+
+	pub fn block_id(&self) -> BlockID {
+		match self.flag_block.as_str() {
+			"latest" => BlockID::Latest,
+			number => BlockID::Number(u64::from_str(number).unwrap_or_else(|_| die("Invalid block number."))),
+		}
+	}
 }
 
-fn die(msg: &'static str) -> ! {
+fn die<T: fmt::Display>(msg: T) -> ! {
 	println!("{}", msg);
 	::std::process::exit(-1)
 }
@@ -16,6 +16,9 @@
 
 //! Spec builtin deserialization.
 
+use std::collections::BTreeMap;
+use uint::Uint;
+
 /// Linear pricing.
 #[derive(Debug, PartialEq, Deserialize, Clone)]
 pub struct Linear {
@@ -25,12 +28,24 @@ pub struct Linear {
 	pub word: usize,
 }
 
+/// Pricing by a pricer registered under the given name.
+#[derive(Debug, PartialEq, Deserialize, Clone)]
+pub struct Custom {
+	/// Registered pricer name.
+	pub name: String,
+	/// Pricer parameters.
+	pub params: Option<BTreeMap<String, Uint>>,
+}
+
 /// Pricing variants.
 #[derive(Debug, PartialEq, Deserialize, Clone)]
 pub enum Pricing {
 	/// Linear pricing.
 	#[serde(rename="linear")]
 	Linear(Linear),
+	/// Registered pricing.
+	#[serde(rename="custom")]
+	Custom(Custom),
 }
 
 /// Spec builtin.
@@ -40,12 +55,18 @@ pub struct Builtin {
 	pub name: String,
 	/// Builtin pricing.
 	pub pricing: Pricing,
+	/// Block number the builtin becomes active at.
+	#[serde(rename="activateAt")]
+	pub activate_at: Option<Uint>,
 }
 
 #[cfg(test)]
 mod tests {
 	use serde_json;
-	use spec::builtin::{Builtin, Pricing, Linear};
+	use std::collections::BTreeMap;
+	use util::U256;
+	use uint::Uint;
+	use spec::builtin::{Builtin, Pricing, Linear, Custom};
 
 	#[test]
 	fn builtin_deserialization() {
@@ -56,5 +77,21 @@ mod tests {
 		let deserialized: Builtin = serde_json::from_str(s).unwrap();
 		assert_eq!(deserialized.name, "ecrecover");
 		assert_eq!(deserialized.pricing, Pricing::Linear(Linear { base: 3000, word: 0 }));
+		assert_eq!(deserialized.activate_at, None);
+	}
+
+	#[test]
+	fn custom_builtin_deserialization() {
+		let s = r#"{
+			"name": "modexp",
+			"activateAt": "0x10",
+			"pricing": { "custom": { "name": "modexp", "params": { "divisor": 20 } } }
+		}"#;
+		let deserialized: Builtin = serde_json::from_str(s).unwrap();
+		let mut params = BTreeMap::new();
+		params.insert("divisor".to_owned(), Uint(U256::from(20)));
+		assert_eq!(deserialized.name, "modexp");
+		assert_eq!(deserialized.activate_at, Some(Uint(U256::from(0x10))));
+		assert_eq!(deserialized.pricing, Pricing::Custom(Custom { name: "modexp".to_owned(), params: Some(params) }));
 	}
 }
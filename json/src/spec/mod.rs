@@ -26,11 +26,13 @@ pub mod engine;
 pub mod state;
 pub mod ethash;
 pub mod basic_authority;
+pub mod schedule;
 
 pub use self::account::Account;
-pub use self::builtin::{Builtin, Pricing, Linear};
+pub use self::builtin::{Builtin, Pricing, Linear, Custom};
 pub use self::genesis::Genesis;
-pub use self::params::Params;
+pub use self::params::{Params, ForkCheckpoint};
+pub use self::schedule::{Schedule, ScheduleTransition};
 pub use self::spec::Spec;
 pub use self::seal::{Seal, Ethereum, Generic};
 pub use self::engine::Engine;
@@ -18,6 +18,7 @@
 
 use uint::Uint;
 use hash::H256;
+use spec::{Schedule, ScheduleTransition};
 
 /// Spec params.
 #[derive(Debug, PartialEq, Deserialize)]
@@ -43,12 +44,33 @@ pub struct Params {
 	/// Expected fork block hash.
 	#[serde(rename="forkCanonHash")]
 	pub fork_hash: Option<H256>,
+	/// Additional fork blocks to check.
+	#[serde(rename="forkCheckpoints")]
+	pub fork_checkpoints: Option<Vec<ForkCheckpoint>>,
+	/// Gas schedule overrides applied from the genesis block.
+	pub schedule: Option<Schedule>,
+	/// Gas schedule overrides applied from later blocks.
+	#[serde(rename="scheduleTransitions")]
+	pub schedule_transitions: Option<Vec<ScheduleTransition>>,
+}
+
+/// Block peers are expected to have in their chain.
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct ForkCheckpoint {
+	/// Block number.
+	pub number: Uint,
+	/// Expected block hash.
+	pub hash: H256,
 }
 
 #[cfg(test)]
 mod tests {
 	use serde_json;
 	use spec::params::Params;
+	use util::{U256, H256 as Eth256};
+	use uint::Uint;
+	use hash::H256;
+	use std::str::FromStr;
 
 	#[test]
 	fn params_deserialization() {
@@ -64,4 +86,44 @@ mod tests {
 		let _deserialized: Params = serde_json::from_str(s).unwrap();
 		// TODO: validate all fields
 	}
+
+	#[test]
+	fn fork_checkpoints_deserialization() {
+		let s = r#"{
+			"maximumExtraDataSize": "0x20",
+			"networkID" : "0x1",
+			"minGasLimit": "0x1388",
+			"accountStartNonce": "0x00",
+			"forkCheckpoints": [
+				{ "number": "0x1d4c00", "hash": "0x94365e3a8c0b35089c1d1195081fe7489b528a84b22199c916180db8b28ade7f" }
+			]
+		}"#;
+
+		let deserialized: Params = serde_json::from_str(s).unwrap();
+		let checkpoints = deserialized.fork_checkpoints.unwrap();
+		assert_eq!(checkpoints.len(), 1);
+		assert_eq!(checkpoints[0].number, Uint(U256::from(0x1d4c00)));
+		assert_eq!(checkpoints[0].hash, H256(Eth256::from_str("94365e3a8c0b35089c1d1195081fe7489b528a84b22199c916180db8b28ade7f").unwrap()));
+	}
+
+	#[test]
+	fn schedule_deserialization() {
+		let s = r#"{
+			"maximumExtraDataSize": "0x20",
+			"networkID" : "0x1",
+			"minGasLimit": "0x1388",
+			"accountStartNonce": "0x00",
+			"schedule": { "stackLimit": "0x100" },
+			"scheduleTransitions": [
+				{ "block": "0x0a", "schedule": { "txGas": "0x5208", "maxDepth": "0x20" } }
+			]
+		}"#;
+
+		let deserialized: Params = serde_json::from_str(s).unwrap();
+		assert_eq!(deserialized.schedule.unwrap().stack_limit, Some(Uint(U256::from(0x100))));
+		let transitions = deserialized.schedule_transitions.unwrap();
+		assert_eq!(transitions.len(), 1);
+		assert_eq!(transitions[0].block, Uint(U256::from(0x0a)));
+		assert_eq!(transitions[0].schedule.max_depth, Some(Uint(U256::from(0x20))));
+	}
 }
@@ -0,0 +1,181 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Gas schedule deserialization.
+
+use uint::Uint;
+
+/// Deserializable overrides of the EVM `Schedule`. Omitted fields keep the engine's value.
+#[derive(Debug, PartialEq, Clone, Default, Deserialize)]
+pub struct Schedule {
+	/// See main Schedule docs.
+	#[serde(rename="exceptionalFailedCodeDeposit")]
+	pub exceptional_failed_code_deposit: Option<bool>,
+	/// See main Schedule docs.
+	#[serde(rename="haveDelegateCall")]
+	pub have_delegate_call: Option<bool>,
+	/// See main Schedule docs.
+	#[serde(rename="stackLimit")]
+	pub stack_limit: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="maxDepth")]
+	pub max_depth: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="tierStepGas")]
+	pub tier_step_gas: Option<[Uint; 8]>,
+	/// See main Schedule docs.
+	#[serde(rename="expGas")]
+	pub exp_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="expByteGas")]
+	pub exp_byte_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="sha3Gas")]
+	pub sha3_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="sha3WordGas")]
+	pub sha3_word_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="sloadGas")]
+	pub sload_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="sstoreSetGas")]
+	pub sstore_set_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="sstoreResetGas")]
+	pub sstore_reset_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="sstoreRefundGas")]
+	pub sstore_refund_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="jumpdestGas")]
+	pub jumpdest_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="logGas")]
+	pub log_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="logDataGas")]
+	pub log_data_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="logTopicGas")]
+	pub log_topic_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="createGas")]
+	pub create_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="callGas")]
+	pub call_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="callStipend")]
+	pub call_stipend: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="callValueTransferGas")]
+	pub call_value_transfer_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="callNewAccountGas")]
+	pub call_new_account_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="suicideRefundGas")]
+	pub suicide_refund_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="memoryGas")]
+	pub memory_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="quadCoeffDiv")]
+	pub quad_coeff_div: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="createDataGas")]
+	pub create_data_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="txGas")]
+	pub tx_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="txCreateGas")]
+	pub tx_create_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="txDataZeroGas")]
+	pub tx_data_zero_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="txDataNonZeroGas")]
+	pub tx_data_non_zero_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="copyGas")]
+	pub copy_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="extcodesizeGas")]
+	pub extcodesize_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="extcodecopyBaseGas")]
+	pub extcodecopy_base_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="balanceGas")]
+	pub balance_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="suicideGas")]
+	pub suicide_gas: Option<Uint>,
+	/// See main Schedule docs.
+	#[serde(rename="suicideToNewAccountCost")]
+	pub suicide_to_new_account_cost: Option<Uint>,
+	/// See main Schedule docs. Zero disables the cap.
+	#[serde(rename="subGasCapDivisor")]
+	pub sub_gas_cap_divisor: Option<Uint>,
+}
+
+/// Schedule overrides taking effect from a given block.
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct ScheduleTransition {
+	/// First block the overrides apply to.
+	pub block: Uint,
+	/// Schedule overrides.
+	pub schedule: Schedule,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use util::U256;
+	use uint::Uint;
+	use spec::schedule::{Schedule, ScheduleTransition};
+
+	#[test]
+	fn schedule_deserialization() {
+		let s = r#"{
+			"haveDelegateCall": false,
+			"maxDepth": "0x40",
+			"tierStepGas": [0, 1, 2, 3, 4, 5, 6, 0],
+			"txGas": 5000
+		}"#;
+
+		let deserialized: Schedule = serde_json::from_str(s).unwrap();
+		assert_eq!(deserialized.have_delegate_call, Some(false));
+		assert_eq!(deserialized.max_depth, Some(Uint(U256::from(0x40))));
+		assert_eq!(deserialized.tier_step_gas.unwrap()[6], Uint(U256::from(6)));
+		assert_eq!(deserialized.tx_gas, Some(Uint(U256::from(5000))));
+		assert_eq!(deserialized.sload_gas, None);
+	}
+
+	#[test]
+	fn schedule_transition_deserialization() {
+		let s = r#"{
+			"block": "0x10",
+			"schedule": { "sloadGas": 100 }
+		}"#;
+
+		let deserialized: ScheduleTransition = serde_json::from_str(s).unwrap();
+		assert_eq!(deserialized.block, Uint(U256::from(0x10)));
+		assert_eq!(deserialized.schedule, Schedule { sload_gas: Some(Uint(U256::from(100))), ..Default::default() });
+	}
+}
@@ -108,6 +108,18 @@ usage! {
 			or |c: &Config| otry!(c.network).disable.clone(),
 		flag_warp: bool = false,
 			or |c: &Config| otry!(c.network).warp.clone(),
+		flag_light: bool = false, or |_| None,
+		flag_serve_light: bool = false, or |_| None,
+		flag_sync_reserved_only: bool = false, or |_| None,
+		flag_no_local_tx_relay: bool = false,
+			or |c: &Config| otry!(c.network).no_local_tx_relay.clone(),
+		flag_tx_relay_reserved_only: bool = false,
+			or |c: &Config| otry!(c.network).tx_relay_reserved_only.clone(),
+		flag_tx_relay_rate: Option<u32> = None,
+			or |c: &Config| otry!(c.network).tx_relay_rate.clone().map(Some),
+		flag_tx_announce_hashes: bool = false,
+			or |c: &Config| otry!(c.network).tx_announce_hashes.clone(),
+		flag_fork_checkpoints: Option<String> = None, or |_| None,
 		flag_port: u16 = 30303u16,
 			or |c: &Config| otry!(c.network).port.clone(),
 		flag_min_peers: u16 = 25u16,
@@ -134,6 +146,18 @@ usage! {
 			or |c: &Config| otry!(c.network).reserved_peers.clone().map(Some),
 		flag_reserved_only: bool = false,
 			or |c: &Config| otry!(c.network).reserved_only.clone(),
+		flag_max_upload_rate: Option<u32> = None,
+			or |c: &Config| otry!(c.network).max_upload_rate.clone().map(Some),
+		flag_max_download_rate: Option<u32> = None,
+			or |c: &Config| otry!(c.network).max_download_rate.clone().map(Some),
+		flag_max_peer_upload_rate: Option<u32> = None,
+			or |c: &Config| otry!(c.network).max_peer_upload_rate.clone().map(Some),
+		flag_max_peer_download_rate: Option<u32> = None,
+			or |c: &Config| otry!(c.network).max_peer_download_rate.clone().map(Some),
+		flag_allowlist: Option<String> = None,
+			or |c: &Config| otry!(c.network).allowlist.clone().map(Some),
+		flag_network_capture: Option<String> = None,
+			or |c: &Config| otry!(c.network).capture.clone().map(Some),
 
 		// -- API and Console Options
 		// RPC
@@ -231,6 +255,8 @@ usage! {
 			or |c: &Config| otry!(c.footprint).cache_size.clone().map(Some),
 		flag_fast_and_loose: bool = false,
 			or |c: &Config| otry!(c.footprint).fast_and_loose.clone(),
+		flag_persist_code_cache: bool = false,
+			or |c: &Config| otry!(c.footprint).persist_code_cache.clone(),
 		flag_db_compaction: String = "auto",
 			or |c: &Config| otry!(c.footprint).db_compaction.clone(),
 		flag_fat_db: String = "auto",
@@ -244,6 +270,7 @@ usage! {
 
 		// -- Snapshot Optons
 		flag_at: String = "latest", or |_| None,
+		flag_replay: bool = false, or |_| None,
 		flag_no_periodic_snapshot: bool = false,
 			or |c: &Config| otry!(c.snapshots).disable_periodic.clone(),
 
@@ -323,6 +350,16 @@ struct Network {
 	node_key: Option<String>,
 	reserved_peers: Option<String>,
 	reserved_only: Option<bool>,
+	max_upload_rate: Option<u32>,
+	max_download_rate: Option<u32>,
+	max_peer_upload_rate: Option<u32>,
+	max_peer_download_rate: Option<u32>,
+	allowlist: Option<String>,
+	capture: Option<String>,
+	no_local_tx_relay: Option<bool>,
+	tx_relay_reserved_only: Option<bool>,
+	tx_relay_rate: Option<u32>,
+	tx_announce_hashes: Option<bool>,
 }
 
 #[derive(Default, Debug, PartialEq, RustcDecodable)]
@@ -381,6 +418,7 @@ struct Footprint {
 	pruning: Option<String>,
 	pruning_history: Option<u64>,
 	fast_and_loose: Option<bool>,
+	persist_code_cache: Option<bool>,
 	cache_size: Option<u32>,
 	cache_size_db: Option<u32>,
 	cache_size_blocks: Option<u32>,
@@ -500,6 +538,14 @@ mod tests {
 			// -- Networking Options
 			flag_no_network: false,
 			flag_warp: true,
+			flag_light: false,
+			flag_serve_light: false,
+			flag_sync_reserved_only: false,
+			flag_no_local_tx_relay: false,
+			flag_tx_relay_reserved_only: false,
+			flag_tx_relay_rate: None,
+			flag_tx_announce_hashes: false,
+			flag_fork_checkpoints: None,
 			flag_port: 30303u16,
 			flag_min_peers: 25u16,
 			flag_max_peers: 50u16,
@@ -513,6 +559,12 @@ mod tests {
 			flag_node_key: None,
 			flag_reserved_peers: Some("./path_to_file".into()),
 			flag_reserved_only: false,
+			flag_max_upload_rate: None,
+			flag_max_download_rate: None,
+			flag_max_peer_upload_rate: None,
+			flag_max_peer_download_rate: None,
+			flag_allowlist: None,
+			flag_network_capture: None,
 
 			// -- API and Console Options
 			// RPC
@@ -567,6 +619,7 @@ mod tests {
 			flag_cache_size_state: 25u32,
 			flag_cache_size: Some(128),
 			flag_fast_and_loose: false,
+			flag_persist_code_cache: false,
 			flag_db_compaction: "ssd".into(),
 			flag_fat_db: "auto".into(),
 
@@ -578,6 +631,7 @@ mod tests {
 
 			// -- Snapshot Optons
 			flag_at: "latest".into(),
+			flag_replay: false,
 			flag_no_periodic_snapshot: false,
 
 			// -- Virtual Machine Options
@@ -675,6 +729,16 @@ mod tests {
 				node_key: None,
 				reserved_peers: Some("./path/to/reserved_peers".into()),
 				reserved_only: Some(true),
+				max_upload_rate: None,
+				max_download_rate: None,
+				max_peer_upload_rate: None,
+				max_peer_download_rate: None,
+				allowlist: None,
+				capture: None,
+				no_local_tx_relay: None,
+				tx_relay_reserved_only: None,
+				tx_relay_rate: None,
+				tx_announce_hashes: None,
 			}),
 			rpc: Some(Rpc {
 				disable: Some(true),
@@ -723,6 +787,7 @@ mod tests {
 				pruning: Some("fast".into()),
 				pruning_history: Some(64),
 				fast_and_loose: None,
+				persist_code_cache: None,
 				cache_size: None,
 				cache_size_db: Some(128),
 				cache_size_blocks: Some(16),
@@ -22,7 +22,7 @@ use std::cmp::max;
 use cli::{Args, ArgsError};
 use util::{Hashable, U256, Uint, Bytes, version_data, Secret, Address};
 use util::log::Colour;
-use ethsync::{NetworkConfiguration, is_valid_node_url, AllowIP};
+use ethsync::{NetworkConfiguration, TransactionPropagation, is_valid_node_url, AllowIP, NatMethod};
 use ethcore::client::{VMType, Mode};
 use ethcore::miner::MinerOptions;
 
@@ -30,7 +30,8 @@ use rpc::{IpcConfiguration, HttpConfiguration};
 use ethcore_rpc::NetworkSettings;
 use cache::CacheConfig;
 use helpers::{to_duration, to_mode, to_block_id, to_u256, to_pending_set, to_price, replace_home,
-geth_ipc_path, parity_ipc_path, to_bootnodes, to_addresses, to_address, to_gas_limit, to_queue_strategy};
+geth_ipc_path, parity_ipc_path, to_bootnodes, to_addresses, to_address, to_gas_limit, to_queue_strategy,
+to_fork_checkpoints, to_rate_limit};
 use params::{ResealPolicy, AccountsConfig, GasPricerConfig, MinerExtras};
 use ethcore_logger::Config as LogConfig;
 use dir::Directories;
@@ -193,6 +194,7 @@ impl Configuration {
 				wal: wal,
 				kind: snapshot::Kind::Take,
 				block_at: try!(to_block_id(&self.args.flag_at)),
+				replay: self.args.flag_replay,
 			};
 			Cmd::Snapshot(snapshot_cmd)
 		} else if self.args.cmd_restore {
@@ -211,6 +213,7 @@ impl Configuration {
 				wal: wal,
 				kind: snapshot::Kind::Restore,
 				block_at: try!(to_block_id("latest")), // unimportant.
+				replay: false,
 			};
 			Cmd::Snapshot(restore_cmd)
 		} else {
@@ -241,9 +244,15 @@ impl Configuration {
 				fat_db: fat_db,
 				compaction: compaction,
 				wal: wal,
+				persist_code_cache: self.args.flag_persist_code_cache,
 				vm_type: vm_type,
 				enable_network: enable_network,
 				warp_sync: warp_sync,
+				light: self.args.flag_light,
+				serve_light: self.args.flag_serve_light,
+				sync_reserved_only: self.args.flag_sync_reserved_only,
+				tx_propagation: self.tx_propagation(),
+				fork_checkpoints: try!(to_fork_checkpoints(&self.args.flag_fork_checkpoints)),
 				geth_compatibility: geth_compatibility,
 				signer_port: signer_port,
 				net_settings: self.network_settings(),
@@ -482,7 +491,12 @@ impl Configuration {
 
 	fn net_config(&self) -> Result<NetworkConfiguration, String> {
 		let mut ret = NetworkConfiguration::new();
-		ret.nat_enabled = self.args.flag_nat == "any" || self.args.flag_nat == "upnp";
+		ret.nat_enabled = self.args.flag_nat == "any" || self.args.flag_nat == "upnp" || self.args.flag_nat == "natpmp";
+		ret.nat_method = match self.args.flag_nat.as_str() {
+			"upnp" => NatMethod::Upnp,
+			"natpmp" => NatMethod::NatPmp,
+			_ => NatMethod::Any,
+		};
 		ret.boot_nodes = try!(to_bootnodes(&self.args.flag_bootnodes));
 		let (listen, public) = try!(self.net_addresses());
 		ret.listen_address = listen.map(|l| format!("{}", l));
@@ -499,6 +513,12 @@ impl Configuration {
 		ret.config_path = Some(net_path.to_str().unwrap().to_owned());
 		ret.reserved_nodes = try!(self.init_reserved_nodes());
 		ret.allow_non_reserved = !self.args.flag_reserved_only;
+		ret.max_upload_rate = to_rate_limit(self.args.flag_max_upload_rate);
+		ret.max_download_rate = to_rate_limit(self.args.flag_max_download_rate);
+		ret.max_peer_upload_rate = to_rate_limit(self.args.flag_max_peer_upload_rate);
+		ret.max_peer_download_rate = to_rate_limit(self.args.flag_max_peer_download_rate);
+		ret.allowlist_path = self.args.flag_allowlist.clone();
+		ret.capture_path = self.args.flag_network_capture.clone();
 		Ok(ret)
 	}
 
@@ -562,6 +582,15 @@ impl Configuration {
 		Ok(conf)
 	}
 
+	fn tx_propagation(&self) -> TransactionPropagation {
+		TransactionPropagation {
+			relay_local: !self.args.flag_no_local_tx_relay,
+			reserved_only: self.args.flag_tx_relay_reserved_only,
+			max_per_peer_rate: self.args.flag_tx_relay_rate,
+			announce_hashes: self.args.flag_tx_announce_hashes,
+		}
+	}
+
 	fn network_settings(&self) -> NetworkSettings {
 		NetworkSettings {
 			name: self.args.flag_identity.clone(),
@@ -839,6 +868,11 @@ mod tests {
 			net_conf: default_network_config(),
 			network_id: None,
 			warp_sync: false,
+			light: false,
+			serve_light: false,
+			sync_reserved_only: false,
+			tx_propagation: Default::default(),
+			fork_checkpoints: Vec::new(),
 			acc_conf: Default::default(),
 			gas_pricer: Default::default(),
 			miner_extras: Default::default(),
@@ -846,6 +880,7 @@ mod tests {
 			tracing: Default::default(),
 			compaction: Default::default(),
 			wal: true,
+			persist_code_cache: false,
 			vm_type: Default::default(),
 			enable_network: true,
 			geth_compatibility: false,
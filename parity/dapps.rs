@@ -182,7 +182,7 @@ mod server {
 				data: data,
 			}.fake_sign(from);
 
-			self.client.call(&transaction, BlockID::Latest, Default::default())
+			self.client.call(&transaction, BlockID::Latest, &Default::default(), Default::default())
 				.map_err(|e| format!("{:?}", e))
 				.map(|executed| {
 					executed.output
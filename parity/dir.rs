@@ -118,6 +118,13 @@ impl DatabaseDirectories {
 		dir.push("network");
 		dir
 	}
+
+	/// Get the path for the light client header database.
+	pub fn light_path(&self) -> PathBuf {
+		let mut dir = self.fork_path();
+		dir.push("light");
+		dir
+	}
 }
 
 #[cfg(test)]
@@ -19,9 +19,10 @@ use std::io::{Write, Read, BufReader, BufRead};
 use std::time::Duration;
 use std::path::Path;
 use std::fs::File;
-use util::{clean_0x, U256, Uint, Address, path, CompactionProfile};
+use util::{clean_0x, U256, H256, Uint, Address, path, CompactionProfile};
 use util::journaldb::Algorithm;
 use ethcore::client::{Mode, BlockID, VMType, DatabaseCompactionProfile, ClientConfig, VerifierType};
+use ethcore::header::BlockNumber;
 use ethcore::miner::{PendingSet, GasLimit, PrioritizationStrategy};
 use cache::CacheConfig;
 use dir::DatabaseDirectories;
@@ -126,6 +127,32 @@ pub fn to_addresses(s: &Option<String>) -> Result<Vec<Address>, String> {
 	}
 }
 
+/// Parses a comma-delimited list of `NUMBER:HASH` fork blocks.
+pub fn to_fork_checkpoints(s: &Option<String>) -> Result<Vec<(BlockNumber, H256)>, String> {
+	match *s {
+		Some(ref forks) if !forks.is_empty() => forks.split(',')
+			.map(|fork| {
+				let mut parts = fork.splitn(2, ':');
+				let number = parts.next().and_then(|n| n.parse::<BlockNumber>().ok());
+				let hash = parts.next().and_then(|h| clean_0x(h).parse::<H256>().ok());
+				match (number, hash) {
+					(Some(number), Some(hash)) => Ok((number, hash)),
+					_ => Err(format!("Invalid fork block, expected NUMBER:HASH: {:?}", fork)),
+				}
+			})
+			.collect(),
+		_ => Ok(Vec::new()),
+	}
+}
+
+/// Converts a rate limit in kilobytes per second to bytes per second. Zero means unlimited.
+pub fn to_rate_limit(kbps: Option<u32>) -> Option<usize> {
+	match kbps {
+		Some(0) | None => None,
+		Some(kbps) => Some(kbps as usize * 1024),
+	}
+}
+
 /// Tries to parse string as a price.
 pub fn to_price(s: &str) -> Result<f32, String> {
 	s.parse::<f32>().map_err(|_| format!("Invalid transaciton price 's' given. Must be a decimal number."))
@@ -185,7 +212,7 @@ pub fn to_bootnodes(bootnodes: &Option<String>) -> Result<Vec<String>, String> {
 
 #[cfg(test)]
 pub fn default_network_config() -> ::ethsync::NetworkConfiguration {
-	use ethsync::{NetworkConfiguration, AllowIP};
+	use ethsync::{NetworkConfiguration, AllowIP, NatMethod};
 	NetworkConfiguration {
 		config_path: Some(replace_home("$HOME/.parity/network")),
 		net_config_path: None,
@@ -193,6 +220,7 @@ pub fn default_network_config() -> ::ethsync::NetworkConfiguration {
 		public_address: None,
 		udp_port: None,
 		nat_enabled: true,
+		nat_method: NatMethod::Any,
 		discovery_enabled: true,
 		boot_nodes: Vec::new(),
 		use_secret: None,
@@ -203,6 +231,12 @@ pub fn default_network_config() -> ::ethsync::NetworkConfiguration {
 		allow_ips: AllowIP::All,
 		reserved_nodes: Vec::new(),
 		allow_non_reserved: true,
+		max_upload_rate: None,
+		max_download_rate: None,
+		max_peer_upload_rate: None,
+		max_peer_download_rate: None,
+		allowlist_path: None,
+		capture_path: None,
 	}
 }
 
@@ -329,7 +363,7 @@ mod tests {
 	use util::{U256};
 	use ethcore::client::{Mode, BlockID};
 	use ethcore::miner::PendingSet;
-	use super::{to_duration, to_mode, to_block_id, to_u256, to_pending_set, to_address, to_addresses, to_price, geth_ipc_path, to_bootnodes, password_from_file};
+	use super::{to_duration, to_mode, to_block_id, to_u256, to_pending_set, to_address, to_addresses, to_fork_checkpoints, to_rate_limit, to_price, geth_ipc_path, to_bootnodes, password_from_file};
 
 	#[test]
 	fn test_to_duration() {
@@ -412,6 +446,28 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn test_to_fork_checkpoints() {
+		let forks = to_fork_checkpoints(&Some("1920000:0x94365e3a8c0b35089c1d1195081fe7489b528a84b22199c916180db8b28ade7f,2675000:4985f5ca3d2afbec36529aa96f74de3cc10a2a4a6c44f2157a57d2c6059a11bb".into())).unwrap();
+		assert_eq!(
+			forks,
+			vec![
+				(1920000, "94365e3a8c0b35089c1d1195081fe7489b528a84b22199c916180db8b28ade7f".parse().unwrap()),
+				(2675000, "4985f5ca3d2afbec36529aa96f74de3cc10a2a4a6c44f2157a57d2c6059a11bb".parse().unwrap()),
+			]
+		);
+		assert_eq!(to_fork_checkpoints(&None).unwrap(), vec![]);
+		assert!(to_fork_checkpoints(&Some("1920000".into())).is_err());
+		assert!(to_fork_checkpoints(&Some("0x94365e3a8c0b35089c1d1195081fe7489b528a84b22199c916180db8b28ade7f".into())).is_err());
+	}
+
+	#[test]
+	fn test_to_rate_limit() {
+		assert_eq!(to_rate_limit(None), None);
+		assert_eq!(to_rate_limit(Some(0)), None);
+		assert_eq!(to_rate_limit(Some(2)), Some(2048));
+	}
+
 	#[test]
 	fn test_password() {
 		let path = RandomTempPath::new();
@@ -30,6 +30,7 @@ use ethcore::snapshot::service::Service as SnapshotService;
 use ethcore::snapshot::{RestorationStatus, SnapshotService as SS};
 use number_prefix::{binary_prefix, Standalone, Prefixed};
 use ethcore_rpc::is_major_importing;
+use rlog::LogLevel;
 
 pub struct Informant {
 	chain_info: RwLock<Option<BlockChainInfo>>,
@@ -167,6 +168,19 @@ impl Informant {
 			)
 		);
 
+		if let Some(ref sync) = self.sync {
+			if log_enabled!(target: "sync", LogLevel::Debug) {
+				let stats = sync.stats();
+				debug!(target: "sync", "Download {:.1} hdr/s {:.1} bodies/s {:.1} receipts/s, import {:.1} blk/s, queue {}/{}/{} unverified/verifying/verified",
+					stats.headers_per_sec, stats.bodies_per_sec, stats.receipts_per_sec, stats.blocks_per_sec,
+					stats.queue_unverified, stats.queue_verifying, stats.queue_verified);
+				for peer in stats.peers.iter().filter(|p| p.connected) {
+					debug!(target: "sync", "Peer {}: {} responses, {} ms avg latency, {} ms last, {} timeouts, {} blocks supplied",
+						peer.id.as_ref().map_or("unknown", |id| &id[..]), peer.responses, peer.avg_latency_ms, peer.last_latency_ms, peer.timeouts, peer.blocks_supplied);
+				}
+			}
+		}
+
 		*self.chain_info.write() = Some(chain_info);
 		*self.cache_info.write() = Some(cache_info);
 		*write_report = Some(report);
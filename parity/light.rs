@@ -0,0 +1,88 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Light client mode: syncs block headers only, fetching state on demand from full nodes.
+
+use std::sync::Arc;
+use std::fs;
+use ethcore::db::NUM_COLUMNS;
+use ethcore::light::{Client as LightClient, QueueConfig};
+use ethcore::spec::Spec;
+use ethcore_rpc::{RpcServer, Extendable};
+use ethsync::{EthLightSync, SyncConfig, NetworkConfiguration};
+use io::PanicHandler;
+use util::{Colour, version, Database, DatabaseConfig};
+use dir::DatabaseDirectories;
+use rpc::HttpConfiguration;
+use run::wait_for_exit;
+
+/// Run a header-only light client until interrupted.
+#[cfg_attr(feature="dev", allow(too_many_arguments))]
+pub fn execute(
+	spec: Spec,
+	db_dirs: &DatabaseDirectories,
+	sync_config: SyncConfig,
+	mut net_conf: NetworkConfiguration,
+	custom_bootnodes: bool,
+	enable_network: bool,
+	http_conf: HttpConfiguration,
+	panic_handler: Arc<PanicHandler>,
+) -> Result<(), String> {
+	info!("Starting {} in {} mode", Colour::White.bold().paint(version()), Colour::White.bold().paint("light"));
+
+	// set up bootnodes and network path.
+	if !custom_bootnodes {
+		net_conf.boot_nodes = spec.nodes.clone();
+	}
+	net_conf.net_config_path = Some(db_dirs.network_path().to_string_lossy().into_owned());
+
+	// open the header database.
+	let light_path = db_dirs.light_path();
+	try!(fs::create_dir_all(&light_path).map_err(|e| format!("Error creating light client directory: {}", e)));
+	let db = try!(Database::open(&DatabaseConfig::with_columns(NUM_COLUMNS), &light_path.to_string_lossy())
+		.map_err(|e| format!("Error opening light client database: {}", e)));
+
+	let client = Arc::new(LightClient::new(QueueConfig::default(), &spec, Arc::new(db)));
+	info!("Best header: #{}", client.chain_info().best_block_number);
+
+	let sync = try!(EthLightSync::new(sync_config, client.clone(), net_conf)
+		.map_err(|e| format!("Sync error: {}", e)));
+	if enable_network {
+		sync.start();
+	}
+
+	// only a minimal set of apis can be served from headers and on-demand state.
+	let http_server = if http_conf.enabled {
+		use ethcore_rpc::v1::*;
+
+		let server = RpcServer::new();
+		server.add_delegate(Web3Client::new().to_delegate());
+		server.add_delegate(NetClient::new(&sync).to_delegate());
+		server.add_delegate(LightEthClient::new(&client, &sync).to_delegate());
+
+		let url = format!("{}:{}", http_conf.interface, http_conf.port);
+		let addr = try!(url.parse().map_err(|_| format!("Invalid JSONRPC listen host/port given: {}", url)));
+		Some(try!(server.start_http(&addr, http_conf.cors, http_conf.hosts, panic_handler.clone())
+			.map_err(|e| format!("RPC error: {:?}", e))))
+	} else {
+		None
+	};
+
+	wait_for_exit(panic_handler, http_server, None, None, None);
+
+	sync.stop();
+	Ok(())
+}
@@ -104,6 +104,7 @@ mod blockchain;
 mod presale;
 mod snapshot;
 mod run;
+mod light;
 #[cfg(feature="ipc")]
 mod sync;
 #[cfg(feature="ipc")]
@@ -20,14 +20,15 @@ use fdlimit::raise_fd_limit;
 use ethcore_logger::{Config as LogConfig, setup_log};
 use ethcore_rpc::{NetworkSettings, is_major_importing};
 use ethsync::NetworkConfiguration;
-use util::{Colour, version, U256};
+use util::{Colour, version, U256, H256};
 use io::{MayPanic, ForwardPanic, PanicHandler};
 use ethcore::client::{Mode, DatabaseCompactionProfile, VMType, ChainNotify, BlockChainClient};
 use ethcore::service::ClientService;
 use ethcore::account_provider::AccountProvider;
 use ethcore::miner::{Miner, MinerService, ExternalMiner, MinerOptions};
 use ethcore::snapshot;
-use ethsync::SyncConfig;
+use ethcore::header::BlockNumber;
+use ethsync::{SyncConfig, TransactionPropagation, MAX_FORK_CHECKPOINTS};
 use informant::Informant;
 
 use rpc::{HttpServer, IpcServer, HttpConfiguration, IpcConfiguration};
@@ -46,6 +47,7 @@ use dapps;
 use signer;
 use modules;
 use rpc_apis;
+use light;
 use rpc;
 use url;
 
@@ -71,6 +73,11 @@ pub struct RunCmd {
 	pub net_conf: NetworkConfiguration,
 	pub network_id: Option<U256>,
 	pub warp_sync: bool,
+	pub light: bool,
+	pub serve_light: bool,
+	pub sync_reserved_only: bool,
+	pub tx_propagation: TransactionPropagation,
+	pub fork_checkpoints: Vec<(BlockNumber, H256)>,
 	pub acc_conf: AccountsConfig,
 	pub gas_pricer: GasPricerConfig,
 	pub miner_extras: MinerExtras,
@@ -79,6 +86,7 @@ pub struct RunCmd {
 	pub fat_db: Switch,
 	pub compaction: DatabaseCompactionProfile,
 	pub wal: bool,
+	pub persist_code_cache: bool,
 	pub vm_type: VMType,
 	pub enable_network: bool,
 	pub geth_compatibility: bool,
@@ -93,7 +101,7 @@ pub struct RunCmd {
 	pub check_seal: bool,
 }
 
-pub fn execute(cmd: RunCmd) -> Result<(), String> {
+pub fn execute(mut cmd: RunCmd) -> Result<(), String> {
 	// set up panic handler
 	let panic_handler = PanicHandler::new_in_arc();
 
@@ -172,8 +180,24 @@ pub fn execute(cmd: RunCmd) -> Result<(), String> {
 	} else {
 		sync_config.subprotocol_name.clone_from_slice(spec.subprotocol_name().as_bytes());
 	}
-	sync_config.fork_block = spec.fork_block();
+	for &(number, hash) in spec.fork_blocks().iter().chain(cmd.fork_checkpoints.iter()) {
+		if !sync_config.fork_blocks.push(number, hash) {
+			return Err(format!("Too many fork blocks to check: at most {} are supported, including those of the chain specification.", MAX_FORK_CHECKPOINTS));
+		}
+	}
 	sync_config.warp_sync = cmd.warp_sync;
+	sync_config.serve_light = cmd.serve_light;
+	sync_config.reserved_peers_only = cmd.sync_reserved_only;
+	if cmd.sync_reserved_only {
+		// don't let other peers take up slots we'd never sync from.
+		cmd.net_conf.allow_non_reserved = false;
+	}
+	sync_config.tx_propagation = cmd.tx_propagation;
+
+	// light clients don't need anything below.
+	if cmd.light {
+		return light::execute(spec, &db_dirs, sync_config, cmd.net_conf, cmd.custom_bootnodes, cmd.enable_network, cmd.http_conf, panic_handler);
+	}
 
 	// prepare account provider
 	let account_provider = Arc::new(try!(prepare_account_provider(&cmd.dirs, cmd.acc_conf)));
@@ -187,7 +211,7 @@ pub fn execute(cmd: RunCmd) -> Result<(), String> {
 	miner.set_transactions_limit(cmd.miner_extras.transactions_limit);
 
 	// create client config
-	let client_config = to_client_config(
+	let mut client_config = to_client_config(
 		&cmd.cache_config,
 		cmd.mode,
 		tracing,
@@ -200,6 +224,7 @@ pub fn execute(cmd: RunCmd) -> Result<(), String> {
 		cmd.pruning_history,
 		cmd.check_seal,
 	);
+	client_config.persist_code_cache = cmd.persist_code_cache;
 
 	// set up bootnodes
 	let mut net_conf = cmd.net_conf;
@@ -396,7 +421,7 @@ fn prepare_account_provider(dirs: &Directories, cfg: AccountsConfig) -> Result<A
 	Ok(account_service)
 }
 
-fn wait_for_exit(
+pub fn wait_for_exit(
 	panic_handler: Arc<PanicHandler>,
 	_http_server: Option<HttpServer>,
 	_ipc_server: Option<IpcServer>,
@@ -25,7 +25,7 @@ use ethcore::snapshot::{Progress, RestorationStatus, SnapshotService as SS};
 use ethcore::snapshot::io::{SnapshotReader, PackedReader, PackedWriter};
 use ethcore::snapshot::service::Service as SnapshotService;
 use ethcore::service::ClientService;
-use ethcore::client::{Mode, DatabaseCompactionProfile, VMType};
+use ethcore::client::{Mode, DatabaseCompactionProfile, VMType, BlockChainClient};
 use ethcore::miner::Miner;
 use ethcore::ids::BlockID;
 
@@ -64,6 +64,7 @@ pub struct SnapshotCommand {
 	pub wal: bool,
 	pub kind: Kind,
 	pub block_at: BlockID,
+	pub replay: bool,
 }
 
 // helper for reading chunks from arbitrary reader and feeding them into the
@@ -215,6 +216,9 @@ impl SnapshotCommand {
 		let file_path = try!(self.file_path.clone().ok_or("No file path provided.".to_owned()));
 		let file_path: PathBuf = file_path.into();
 		let block_at = self.block_at;
+		let replay = self.replay;
+		let spec = try!(self.spec.spec());
+		let scratch_path = self.dirs.database(spec.genesis_header().hash(), spec.fork_name.clone()).snapshot_path().join("replay");
 		let (service, _panic_handler) = try!(self.start_service());
 
 		warn!("Snapshots are currently experimental. File formats may be subject to change.");
@@ -240,7 +244,38 @@ impl SnapshotCommand {
 			}
  		});
 
-		if let Err(e) = service.client().take_snapshot(writer, block_at, &*progress) {
+		let res = match replay {
+			true => {
+				let client = service.client();
+				let snapshot = service.snapshot_service();
+				let reader = snapshot.reader();
+
+				// only replay from the local snapshot if it's an ancestor of the requested block.
+				let target = client.block_number(block_at.clone());
+				let base = reader.as_ref().and_then(|reader| {
+					let manifest = reader.manifest();
+					let is_ancestor = target.map_or(false, |n| manifest.block_number <= n) &&
+						client.block_hash(BlockID::Number(manifest.block_number)) == Some(manifest.block_hash);
+
+					match is_ancestor {
+						true => Some(reader as &SnapshotReader),
+						false => None,
+					}
+				});
+
+				match base {
+					Some(reader) => info!("Replaying from local snapshot at block #{}", reader.manifest().block_number),
+					None => info!("No usable local snapshot found. Replaying from genesis."),
+				}
+
+				let res = client.take_snapshot_replayed(writer, block_at, &spec, base, &scratch_path, &*progress);
+				let _ = ::std::fs::remove_dir_all(&scratch_path);
+				res
+			}
+			false => service.client().take_snapshot(writer, block_at, &*progress),
+		};
+
+		if let Err(e) = res {
 			let _ = ::std::fs::remove_file(&file_path);
 			return Err(format!("Encountered fatal error while creating snapshot: {}", e));
 		}
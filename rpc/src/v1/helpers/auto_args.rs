@@ -297,6 +297,39 @@ macro_rules! wrap_with_trailing {
 	}
 }
 
+// similar to `wrap_with_trailing!`, but handles two default trailing parameters.
+macro_rules! wrap_with_two_trailing {
+	($num: expr, $($x: ident),+) => {
+		impl <
+			BASE: Send + Sync + 'static,
+			OUT: Serialize,
+			$($x: Deserialize,)+
+			TRAILING1: Default + Deserialize,
+			TRAILING2: Default + Deserialize,
+		> Wrap<BASE> for fn(&BASE, $($x,)+ Trailing<TRAILING1>, Trailing<TRAILING2>) -> Result<OUT, Error> {
+			fn wrap_rpc(&self, base: &BASE, params: Params) -> Result<Value, Error> {
+				let len = match params {
+					Params::Array(ref v) => v.len(),
+					Params::None => 0,
+					_ => return Err(errors::invalid_params("not an array", "")),
+				};
+
+				let params = match len.checked_sub($num) {
+					Some(0) => from_params::<($($x,)+)>(params)
+						.map(|($($x,)+)| ($($x,)+ TRAILING1::default(), TRAILING2::default())),
+					Some(1) => from_params::<($($x,)+ TRAILING1)>(params)
+						.map(|($($x,)+ id1)| ($($x,)+ id1, TRAILING2::default())),
+					Some(2) => from_params::<($($x,)+ TRAILING1, TRAILING2)>(params),
+					_ => Err(Error::invalid_params()),
+				};
+
+				let ($($x,)+ id1, id2) = try!(params);
+				(self)(base, $($x,)+ Trailing(id1), Trailing(id2)).map(to_value)
+			}
+		}
+	}
+}
+
 wrap!(A, B, C, D, E);
 wrap!(A, B, C, D);
 wrap!(A, B, C);
@@ -307,4 +340,6 @@ wrap_with_trailing!(5, A, B, C, D, E);
 wrap_with_trailing!(4, A, B, C, D);
 wrap_with_trailing!(3, A, B, C);
 wrap_with_trailing!(2, A, B);
-wrap_with_trailing!(1, A);
\ No newline at end of file
+wrap_with_trailing!(1, A);
+
+wrap_with_two_trailing!(1, A);
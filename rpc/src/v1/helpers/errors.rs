@@ -35,6 +35,7 @@ mod codes {
 	pub const UNKNOWN_ERROR: i64 = -32009;
 	pub const TRANSACTION_ERROR: i64 = -32010;
 	pub const EXECUTION_ERROR: i64 = -32015;
+	pub const UNKNOWN_BLOCK: i64 = -32016;
 	pub const ACCOUNT_LOCKED: i64 = -32020;
 	pub const PASSWORD_INVALID: i64 = -32021;
 	pub const ACCOUNT_ERROR: i64 = -32023;
@@ -46,6 +47,7 @@ mod codes {
 	pub const COMPILATION_ERROR: i64 = -32050;
 	pub const ENCRYPTION_ERROR: i64 = -32055;
 	pub const FETCH_ERROR: i64 = -32060;
+	pub const ON_DEMAND_ERROR: i64 = -32065;
 }
 
 pub fn unimplemented() -> Error {
@@ -120,6 +122,14 @@ pub fn execution<T: fmt::Debug>(data: T) -> Error {
 	}
 }
 
+pub fn unknown_block<T: fmt::Debug>(id: T) -> Error {
+	Error {
+		code: ErrorCode::ServerError(codes::UNKNOWN_BLOCK),
+		message: "Unknown block.".into(),
+		data: Some(Value::String(format!("{:?}", id))),
+	}
+}
+
 pub fn state_pruned() -> Error {
 	Error {
 		code: ErrorCode::ServerError(codes::UNSUPPORTED_REQUEST),
@@ -128,6 +138,22 @@ pub fn state_pruned() -> Error {
 	}
 }
 
+pub fn on_demand_unavailable() -> Error {
+	Error {
+		code: ErrorCode::ServerError(codes::ON_DEMAND_ERROR),
+		message: "Could not fetch the requested state from the network. There may be no peers serving light clients.".into(),
+		data: None
+	}
+}
+
+pub fn bodies_unavailable() -> Error {
+	Error {
+		code: ErrorCode::ServerError(codes::UNSUPPORTED_REQUEST),
+		message: "Block bodies are not available to a light client; only headers are synced.".into(),
+		data: None
+	}
+}
+
 pub fn no_work() -> Error {
 	Error {
 		code: ErrorCode::ServerError(codes::NO_WORK),
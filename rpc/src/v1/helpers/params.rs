@@ -18,7 +18,7 @@
 
 use serde;
 use jsonrpc_core::{Error, Params, from_params};
-use v1::types::BlockNumber;
+use v1::types::{BlockNumber, StateOverride};
 use v1::helpers::errors;
 
 pub fn expect_no_params(params: Params) -> Result<(), Error> {
@@ -44,3 +44,10 @@ pub fn from_params_default_third<F1, F2>(params: Params) -> Result<(F1, F2, Bloc
 	}
 }
 
+pub fn from_params_default_third_fourth<F1, F2>(params: Params) -> Result<(F1, F2, BlockNumber, StateOverride), Error> where F1: serde::de::Deserialize, F2: serde::de::Deserialize {
+	match params_len(&params) {
+		2 => from_params::<(F1, F2, )>(params).map(|(f1, f2)| (f1, f2, BlockNumber::Latest, StateOverride::default())),
+		3 => from_params::<(F1, F2, BlockNumber)>(params).map(|(f1, f2, block)| (f1, f2, block, StateOverride::default())),
+		_ => from_params::<(F1, F2, BlockNumber, StateOverride)>(params)
+	}
+}
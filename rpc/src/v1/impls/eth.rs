@@ -44,7 +44,7 @@ use self::ethash::SeedHashCompute;
 use v1::traits::Eth;
 use v1::types::{
 	Block, BlockTransactions, BlockNumber, Bytes, SyncStatus, SyncInfo,
-	Transaction, CallRequest, Index, Filter, Log, Receipt, Work,
+	Transaction, CallRequest, Index, Filter, Log, Receipt, StateOverride, Work,
 	H64 as RpcH64, H256 as RpcH256, H160 as RpcH160, U256 as RpcU256,
 };
 use v1::helpers::{CallRequest as CRequest, errors, limit_logs};
@@ -256,13 +256,15 @@ impl<C, S: ?Sized, M, EM> Eth for EthClient<C, S, M, EM> where
 		try!(self.active());
 		let status = take_weak!(self.sync).status();
 		let client = take_weak!(self.client);
-		if is_major_importing(Some(status.state), client.queue_info()) {
+		let ancient_block = status.last_imported_old_block_number;
+		if is_major_importing(Some(status.state), client.queue_info()) || ancient_block.is_some() {
 			let current_block = U256::from(client.chain_info().best_block_number);
 			let highest_block = U256::from(status.highest_block_number.unwrap_or(status.start_block_number));
 			let info = SyncInfo {
 				starting_block: status.start_block_number.into(),
 				current_block: current_block.into(),
 				highest_block: highest_block.into(),
+				ancient_block: ancient_block.map(|n| U256::from(n).into()),
 			};
 			Ok(SyncStatus::Info(info))
 		} else {
@@ -576,15 +578,16 @@ impl<C, S: ?Sized, M, EM> Eth for EthClient<C, S, M, EM> where
 		}
 	}
 
-	fn call(&self, request: CallRequest, num: Trailing<BlockNumber>) -> Result<Bytes, Error> {
+	fn call(&self, request: CallRequest, num: Trailing<BlockNumber>, state_override: Trailing<StateOverride>) -> Result<Bytes, Error> {
 		try!(self.active());
 
 		let request = CallRequest::into(request);
 		let signed = try!(self.sign_call(request));
+		let state_override = state_override.0.into();
 
 		let r = match num.0 {
-			BlockNumber::Pending => take_weak!(self.miner).call(&*take_weak!(self.client), &signed, Default::default()),
-			num => take_weak!(self.client).call(&signed, num.into(), Default::default()),
+			BlockNumber::Pending => take_weak!(self.miner).call(&*take_weak!(self.client), &signed, &state_override, Default::default()),
+			num => take_weak!(self.client).call(&signed, num.into(), &state_override, Default::default()),
 		};
 
 		match r {
@@ -599,8 +602,8 @@ impl<C, S: ?Sized, M, EM> Eth for EthClient<C, S, M, EM> where
 		let request = CallRequest::into(request);
 		let signed = try!(self.sign_call(request));
 		let r = match num.0 {
-			BlockNumber::Pending => take_weak!(self.miner).call(&*take_weak!(self.client), &signed, Default::default()),
-			num => take_weak!(self.client).call(&signed, num.into(), Default::default()),
+			BlockNumber::Pending => take_weak!(self.miner).call(&*take_weak!(self.client), &signed, &Default::default(), Default::default()),
+			num => take_weak!(self.client).call(&signed, num.into(), &Default::default(), Default::default()),
 		};
 
 		Ok(RpcU256::from(r.map(|res| res.gas_used + res.refunded).unwrap_or(From::from(0))))
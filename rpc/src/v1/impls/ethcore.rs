@@ -33,7 +33,7 @@ use ethcore::ids::BlockID;
 
 use jsonrpc_core::Error;
 use v1::traits::Ethcore;
-use v1::types::{Bytes, U256, H160, H256, H512, Peers, Transaction, RpcSettings};
+use v1::types::{Bytes, U256, H160, H256, H512, Peers, BannedPeer, NatStatus, SyncStats, Transaction, RpcSettings};
 use v1::helpers::{errors, SigningQueue, SignerService, NetworkSettings};
 use v1::helpers::dispatch::DEFAULT_MAC;
 use v1::helpers::auto_args::Ready;
@@ -183,6 +183,30 @@ impl<C, M, S: ?Sized, F> Ethcore for EthcoreClient<C, M, S, F> where
 		})
 	}
 
+	fn banned_peers(&self) -> Result<Vec<BannedPeer>, Error> {
+		try!(self.active());
+
+		Ok(take_weak!(self.net).banned_peers().into_iter().map(Into::into).collect())
+	}
+
+	fn allowed_peers(&self) -> Result<Vec<String>, Error> {
+		try!(self.active());
+
+		take_weak!(self.net).allowed_peers().map_err(|e| errors::internal("Allowlist", e))
+	}
+
+	fn nat_status(&self) -> Result<NatStatus, Error> {
+		try!(self.active());
+
+		Ok(take_weak!(self.net).nat_status().into())
+	}
+
+	fn sync_stats(&self) -> Result<SyncStats, Error> {
+		try!(self.active());
+
+		Ok(take_weak!(self.sync).stats().into())
+	}
+
 	fn net_port(&self) -> Result<u16, Error> {
 		try!(self.active());
 
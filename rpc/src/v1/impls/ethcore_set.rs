@@ -124,6 +124,42 @@ impl<C, M> EthcoreSet for EthcoreSetClient<C, M> where
 		}
 	}
 
+	fn unban_peer(&self, peer: String) -> Result<bool, Error> {
+		try!(self.active());
+
+		match take_weak!(self.net).unban_peer(peer) {
+			Ok(()) => Ok(true),
+			Err(e) => Err(errors::invalid_params("Peer", e)),
+		}
+	}
+
+	fn add_allowed_peer(&self, peer: String) -> Result<bool, Error> {
+		try!(self.active());
+
+		match take_weak!(self.net).add_allowed_peer(peer) {
+			Ok(()) => Ok(true),
+			Err(e) => Err(errors::invalid_params("Peer", e)),
+		}
+	}
+
+	fn remove_allowed_peer(&self, peer: String) -> Result<bool, Error> {
+		try!(self.active());
+
+		match take_weak!(self.net).remove_allowed_peer(peer) {
+			Ok(()) => Ok(true),
+			Err(e) => Err(errors::invalid_params("Peer", e)),
+		}
+	}
+
+	fn reload_allowed_peers(&self) -> Result<bool, Error> {
+		try!(self.active());
+
+		match take_weak!(self.net).reload_allowed_peers() {
+			Ok(()) => Ok(true),
+			Err(e) => Err(errors::internal("Allowlist", e)),
+		}
+	}
+
 	fn drop_non_reserved_peers(&self) -> Result<bool, Error> {
 		try!(self.active());
 
@@ -0,0 +1,320 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Eth rpc implementation for light clients.
+//!
+//! Methods which can be answered from block headers are served locally.
+//! Balances, nonces, code and calls are fetched on demand from full nodes
+//! and checked against the headers. Blocks are built from their headers alone:
+//! without full transactions requested, the header fields are returned with
+//! empty transaction and uncle lists; blocks with transactions can't be
+//! returned in full. Everything else
+//! requiring block bodies or receipts returns an "unimplemented" error.
+
+use std::sync::{Arc, Weak};
+use ethsync::{EthLightSync, SyncProvider, MAX_TRANSACTION_PROOF_GAS};
+use ethcore::client::BlockID;
+use ethcore::light::Client as LightClient;
+use ethcore::light::proof::BasicAccount;
+use ethcore::transaction::{Transaction as EthTransaction, Action};
+use ethcore::views::HeaderView;
+use jsonrpc_core::*;
+use rlp;
+use util::{Address, FixedHash, H256, U256, Uint};
+use util::sha3::{SHA3_EMPTY, SHA3_NULL_RLP};
+use v1::traits::Eth;
+use v1::types::{
+	Block, BlockTransactions, BlockNumber, Bytes, SyncStatus, SyncInfo,
+	Transaction, CallRequest, Index, Filter, Log, Receipt, StateOverride, Work,
+	H64 as RpcH64, H256 as RpcH256, H160 as RpcH160, U256 as RpcU256,
+};
+use v1::helpers::{CallRequest as CRequest, errors};
+use v1::helpers::block_import::is_major_importing;
+use v1::helpers::auto_args::Trailing;
+
+/// Eth rpc implementation for light clients.
+pub struct LightEthClient {
+	client: Weak<LightClient>,
+	sync: Weak<EthLightSync>,
+}
+
+impl LightEthClient {
+	/// Creates new LightEthClient.
+	pub fn new(client: &Arc<LightClient>, sync: &Arc<EthLightSync>) -> Self {
+		LightEthClient {
+			client: Arc::downgrade(client),
+			sync: Arc::downgrade(sync),
+		}
+	}
+
+	/// Hash of the block state should be fetched for. There's no pending block.
+	fn block_hash(&self, num: BlockNumber) -> Result<H256, Error> {
+		let id = match num {
+			BlockNumber::Pending => BlockID::Latest,
+			num => num.into(),
+		};
+		take_weak!(self.client).block_header(id)
+			.map(|header| HeaderView::new(&header).hash())
+			.ok_or_else(errors::state_pruned)
+	}
+
+	/// Fetch an account from the network. Nonexistent accounts are empty.
+	fn account(&self, address: Address, num: BlockNumber) -> Result<BasicAccount, Error> {
+		let block = try!(self.block_hash(num));
+		match take_weak!(self.sync).account(block, &address) {
+			Some(Some(account)) => Ok(account),
+			Some(None) => Ok(BasicAccount {
+				nonce: take_weak!(self.client).engine().account_start_nonce(),
+				balance: U256::zero(),
+				storage_root: SHA3_NULL_RLP,
+				code_hash: SHA3_EMPTY,
+			}),
+			None => Err(errors::on_demand_unavailable()),
+		}
+	}
+
+	/// Build a block from its header. Only headers are synced, so this fails
+	/// for blocks with transactions or uncles, which would be incomplete.
+	fn block(&self, id: BlockID, include_txs: bool) -> Result<Option<Block>, Error> {
+		let client = take_weak!(self.client);
+		match (client.block_header(id.clone()), client.block_total_difficulty(id)) {
+			(Some(bytes), Some(total_difficulty)) => {
+				let view = HeaderView::new(&bytes);
+				if include_txs && view.transactions_root() != SHA3_NULL_RLP {
+					return Err(errors::bodies_unavailable());
+				}
+				let block = Block {
+					hash: Some(view.hash().into()),
+					size: None,
+					parent_hash: view.parent_hash().into(),
+					uncles_hash: view.uncles_hash().into(),
+					author: view.author().into(),
+					miner: view.author().into(),
+					state_root: view.state_root().into(),
+					transactions_root: view.transactions_root().into(),
+					receipts_root: view.receipts_root().into(),
+					number: Some(view.number().into()),
+					gas_used: view.gas_used().into(),
+					gas_limit: view.gas_limit().into(),
+					logs_bloom: view.log_bloom().into(),
+					timestamp: view.timestamp().into(),
+					difficulty: view.difficulty().into(),
+					total_difficulty: total_difficulty.into(),
+					seal_fields: view.seal().into_iter().map(|f| rlp::decode(&f)).map(Bytes::new).collect(),
+					// bodies aren't available, so only an empty body is listed in full.
+					uncles: vec![],
+					transactions: BlockTransactions::Hashes(vec![]),
+					extra_data: Bytes::new(view.extra_data())
+				};
+				Ok(Some(block))
+			},
+			_ => Ok(None)
+		}
+	}
+}
+
+impl Eth for LightEthClient {
+	fn protocol_version(&self) -> Result<String, Error> {
+		let version = take_weak!(self.sync).status().protocol_version.to_owned();
+		Ok(format!("{}", version))
+	}
+
+	fn syncing(&self) -> Result<SyncStatus, Error> {
+		let status = take_weak!(self.sync).status();
+		let client = take_weak!(self.client);
+		if is_major_importing(Some(status.state), client.queue_info()) {
+			let current_block = U256::from(client.chain_info().best_block_number);
+			let highest_block = U256::from(status.highest_block_number.unwrap_or(status.start_block_number));
+			let info = SyncInfo {
+				starting_block: status.start_block_number.into(),
+				current_block: current_block.into(),
+				highest_block: highest_block.into(),
+				ancient_block: None,
+			};
+			Ok(SyncStatus::Info(info))
+		} else {
+			Ok(SyncStatus::None)
+		}
+	}
+
+	fn author(&self) -> Result<RpcH160, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn is_mining(&self) -> Result<bool, Error> {
+		Ok(false)
+	}
+
+	fn hashrate(&self) -> Result<RpcU256, Error> {
+		Ok(RpcU256::from(0))
+	}
+
+	fn gas_price(&self) -> Result<RpcU256, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn accounts(&self) -> Result<Vec<RpcH160>, Error> {
+		Ok(vec![])
+	}
+
+	fn block_number(&self) -> Result<RpcU256, Error> {
+		Ok(RpcU256::from(take_weak!(self.client).chain_info().best_block_number))
+	}
+
+	fn balance(&self, address: RpcH160, num: Trailing<BlockNumber>) -> Result<RpcU256, Error> {
+		self.account(address.into(), num.0).map(|account| account.balance.into())
+	}
+
+	fn storage_at(&self, address: RpcH160, pos: RpcU256, num: Trailing<BlockNumber>) -> Result<RpcH256, Error> {
+		let block = try!(self.block_hash(num.0));
+		let address: Address = RpcH160::into(address);
+		let position: U256 = RpcU256::into(pos);
+		match take_weak!(self.sync).storage_at(block, &address, &H256::from(position)) {
+			Some(s) => Ok(s.into()),
+			None => Err(errors::on_demand_unavailable()),
+		}
+	}
+
+	fn transaction_count(&self, address: RpcH160, num: Trailing<BlockNumber>) -> Result<RpcU256, Error> {
+		self.account(address.into(), num.0).map(|account| account.nonce.into())
+	}
+
+	fn block_transaction_count_by_hash(&self, _hash: RpcH256) -> Result<Option<RpcU256>, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn block_transaction_count_by_number(&self, _num: BlockNumber) -> Result<Option<RpcU256>, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn block_uncles_count_by_hash(&self, _hash: RpcH256) -> Result<Option<RpcU256>, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn block_uncles_count_by_number(&self, _num: BlockNumber) -> Result<Option<RpcU256>, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn code_at(&self, address: RpcH160, num: Trailing<BlockNumber>) -> Result<Bytes, Error> {
+		let block = try!(self.block_hash(num.0));
+		take_weak!(self.sync).code(block, &address.into())
+			.map(Bytes::new)
+			.ok_or_else(errors::on_demand_unavailable)
+	}
+
+	fn block_by_hash(&self, hash: RpcH256, include_txs: bool) -> Result<Option<Block>, Error> {
+		self.block(BlockID::Hash(hash.into()), include_txs)
+	}
+
+	fn block_by_number(&self, num: BlockNumber, include_txs: bool) -> Result<Option<Block>, Error> {
+		self.block(num.into(), include_txs)
+	}
+
+	fn transaction_by_hash(&self, _hash: RpcH256) -> Result<Option<Transaction>, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn transaction_by_block_hash_and_index(&self, _hash: RpcH256, _index: Index) -> Result<Option<Transaction>, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn transaction_by_block_number_and_index(&self, _num: BlockNumber, _index: Index) -> Result<Option<Transaction>, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn transaction_receipt(&self, _hash: RpcH256) -> Result<Option<Receipt>, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn uncle_by_block_hash_and_index(&self, _hash: RpcH256, _index: Index) -> Result<Option<Block>, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn uncle_by_block_number_and_index(&self, _num: BlockNumber, _index: Index) -> Result<Option<Block>, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn compilers(&self) -> Result<Vec<String>, Error> {
+		Ok(vec![])
+	}
+
+	fn logs(&self, _filter: Filter) -> Result<Vec<Log>, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn work(&self, _no_new_work_timeout: Trailing<u64>) -> Result<Work, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn submit_work(&self, _nonce: RpcH64, _pow_hash: RpcH256, _mix_hash: RpcH256) -> Result<bool, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn submit_hashrate(&self, _rate: RpcU256, _id: RpcH256) -> Result<bool, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn send_raw_transaction(&self, _raw: Bytes) -> Result<RpcH256, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn call(&self, request: CallRequest, num: Trailing<BlockNumber>, state_override: Trailing<StateOverride>) -> Result<Bytes, Error> {
+		// overrides can't be applied to remotely executed calls.
+		if !(state_override.0).0.is_empty() {
+			return Err(errors::unimplemented());
+		}
+
+		let request: CRequest = request.into();
+		let from = request.from.unwrap_or(Address::zero());
+		let nonce = match request.nonce {
+			Some(nonce) => nonce,
+			None => try!(self.account(from, num.0.clone())).nonce,
+		};
+		let block = try!(self.block_hash(num.0));
+
+		// without a local transaction queue there's no sensible default gas price.
+		let signed = EthTransaction {
+			nonce: nonce,
+			action: request.to.map_or(Action::Create, Action::Call),
+			gas: request.gas.unwrap_or(U256::from(MAX_TRANSACTION_PROOF_GAS)),
+			gas_price: request.gas_price.unwrap_or_else(U256::zero),
+			value: request.value.unwrap_or_else(U256::zero),
+			data: request.data.map_or_else(Vec::new, |d| d.to_vec())
+		}.fake_sign(from);
+
+		match take_weak!(self.sync).call(block, signed) {
+			Some(Ok(executed)) => Ok(Bytes(executed.output)),
+			Some(Err(e)) => Err(errors::execution(e)),
+			None => Err(errors::on_demand_unavailable()),
+		}
+	}
+
+	fn estimate_gas(&self, _request: CallRequest, _num: Trailing<BlockNumber>) -> Result<RpcU256, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn compile_lll(&self, _: String) -> Result<Bytes, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn compile_serpent(&self, _: String) -> Result<Bytes, Error> {
+		Err(errors::unimplemented())
+	}
+
+	fn compile_solidity(&self, _: String) -> Result<Bytes, Error> {
+		Err(errors::unimplemented())
+	}
+}
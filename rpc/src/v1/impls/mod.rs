@@ -30,6 +30,7 @@ mod eth_filter;
 mod eth_signing;
 mod ethcore;
 mod ethcore_set;
+mod light_eth;
 mod net;
 mod personal;
 mod personal_signer;
@@ -39,6 +40,7 @@ mod web3;
 
 pub use self::web3::Web3Client;
 pub use self::eth::{EthClient, EthClientOptions};
+pub use self::light_eth::LightEthClient;
 pub use self::eth_filter::EthFilterClient;
 pub use self::eth_signing::{EthSigningUnsafeClient, EthSigningQueueClient};
 pub use self::net::NetClient;
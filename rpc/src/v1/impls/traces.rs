@@ -19,20 +19,38 @@
 use std::sync::{Weak, Arc};
 use jsonrpc_core::*;
 use rlp::{UntrustedRlp, View};
-use ethcore::client::{BlockChainClient, CallAnalytics, TransactionID, TraceId};
+use ethcore::client::{BlockChainClient, CallAnalytics, StepLogOptions, BlockID, TransactionID, TraceId};
 use ethcore::miner::MinerService;
 use ethcore::transaction::{Transaction as EthTransaction, SignedTransaction, Action};
+use ethcore::views::HeaderView;
 use v1::traits::Traces;
 use v1::helpers::{errors, CallRequest as CRequest};
-use v1::helpers::params::from_params_default_third;
-use v1::types::{TraceFilter, LocalizedTrace, BlockNumber, Index, CallRequest, Bytes, TraceResults, H256};
+use v1::helpers::params::{from_params_default_third, from_params_default_third_fourth};
+use v1::types::{TraceFilter, LocalizedTrace, BlockNumber, Index, CallRequest, Bytes, TraceResults, GasProfile, StateAccesses, H256};
 
-fn to_call_analytics(flags: Vec<String>) -> CallAnalytics {
-	CallAnalytics {
+/// Maximal number of blocks `trace_profile` replays in one request.
+const MAX_PROFILE_BLOCKS: u64 = 1000;
+
+fn to_call_analytics(flags: Vec<String>) -> Result<CallAnalytics, Error> {
+	let step_log_memory = flags.contains(&("stepLogMemory".to_owned()));
+	let step_log_storage = flags.contains(&("stepLogStorage".to_owned()));
+	let analytics = CallAnalytics {
 		transaction_tracing: flags.contains(&("trace".to_owned())),
 		vm_tracing: flags.contains(&("vmTrace".to_owned())),
 		state_diffing: flags.contains(&("stateDiff".to_owned())),
+		gas_profiling: flags.contains(&("gasProfile".to_owned())),
+		state_accessing: flags.contains(&("stateAccesses".to_owned())),
+		step_logging: match flags.contains(&("stepLog".to_owned())) || step_log_memory || step_log_storage {
+			true => Some(StepLogOptions { memory: step_log_memory, storage: step_log_storage }),
+			false => None,
+		},
+	};
+	// each of these needs its own VM tracer, only one can run at a time.
+	let vm_tracers = [analytics.vm_tracing, analytics.gas_profiling, analytics.step_logging.is_some()];
+	if vm_tracers.iter().filter(|t| **t).count() > 1 {
+		return Err(errors::invalid_params("Flags", "vmTrace, gasProfile and stepLog can't be combined"));
 	}
+	Ok(analytics)
 }
 
 /// Traces api implementation.
@@ -123,11 +141,12 @@ impl<C, M> Traces for TracesClient<C, M> where C: BlockChainClient + 'static, M:
 
 	fn call(&self, params: Params) -> Result<Value, Error> {
 		try!(self.active());
-		from_params_default_third(params)
-			.and_then(|(request, flags, block)| {
+		from_params_default_third_fourth(params)
+			.and_then(|(request, flags, block, state_override)| {
 				let request = CallRequest::into(request);
 				let signed = try!(self.sign_call(request));
-				match take_weak!(self.client).call(&signed, block.into(), to_call_analytics(flags)) {
+				let analytics = try!(to_call_analytics(flags));
+				match take_weak!(self.client).call(&signed, block.into(), &state_override.into(), analytics) {
 					Ok(e) => Ok(to_value(&TraceResults::from(e))),
 					_ => Ok(Value::Null),
 				}
@@ -139,8 +158,9 @@ impl<C, M> Traces for TracesClient<C, M> where C: BlockChainClient + 'static, M:
 		from_params_default_third(params)
 			.and_then(|(raw_transaction, flags, block)| {
 				let raw_transaction = Bytes::to_vec(raw_transaction);
+				let analytics = try!(to_call_analytics(flags));
 				match UntrustedRlp::new(&raw_transaction).as_val() {
-					Ok(signed) => match take_weak!(self.client).call(&signed, block.into(), to_call_analytics(flags)) {
+					Ok(signed) => match take_weak!(self.client).call(&signed, block.into(), &Default::default(), analytics) {
 						Ok(e) => Ok(to_value(&TraceResults::from(e))),
 						_ => Ok(Value::Null),
 					},
@@ -153,10 +173,44 @@ impl<C, M> Traces for TracesClient<C, M> where C: BlockChainClient + 'static, M:
 		try!(self.active());
 		from_params::<(H256, _)>(params)
 			.and_then(|(transaction_hash, flags)| {
-				match take_weak!(self.client).replay(TransactionID::Hash(transaction_hash.into()), to_call_analytics(flags)) {
+				let analytics = try!(to_call_analytics(flags));
+				match take_weak!(self.client).replay(TransactionID::Hash(transaction_hash.into()), analytics) {
 					Ok(e) => Ok(to_value(&TraceResults::from(e))),
 					_ => Ok(Value::Null),
 				}
 			})
 	}
+
+	fn profile(&self, params: Params) -> Result<Value, Error> {
+		try!(self.active());
+		from_params::<(BlockNumber, BlockNumber)>(params)
+			.and_then(|(from, to)| {
+				let client = take_weak!(self.client);
+				let number = |id: BlockID| client.block_header(id)
+					.map(|h| HeaderView::new(&h).number())
+					.ok_or_else(|| errors::unknown_block(id));
+				let (from, to) = (try!(number(from.into())), try!(number(to.into())));
+				if from > to {
+					return Err(errors::invalid_params("Block range", format!("from block {} is after to block {}", from, to)));
+				}
+				if to - from >= MAX_PROFILE_BLOCKS {
+					return Err(errors::invalid_params("Block range", format!("at most {} blocks can be profiled at once", MAX_PROFILE_BLOCKS)));
+				}
+				client.profile(BlockID::Number(from), BlockID::Number(to))
+					.map(|p| to_value(&GasProfile::from(p)))
+					.map_err(errors::from_call_error)
+			})
+	}
+
+	fn state_accesses(&self, params: Params) -> Result<Value, Error> {
+		try!(self.active());
+		from_params::<(H256,)>(params)
+			.and_then(|(transaction_hash,)| {
+				let analytics = CallAnalytics { state_accessing: true, ..Default::default() };
+				match take_weak!(self.client).replay(TransactionID::Hash(transaction_hash.into()), analytics) {
+					Ok(e) => Ok(to_value(&e.state_accesses.map(StateAccesses::from))),
+					_ => Ok(Value::Null),
+				}
+			})
+	}
 }
@@ -19,7 +19,7 @@
 use util::{Address, H256, Bytes, U256, FixedHash, Uint};
 use util::standard::*;
 use ethcore::error::{Error, CallError};
-use ethcore::client::{MiningBlockChainClient, Executed, CallAnalytics};
+use ethcore::client::{MiningBlockChainClient, Executed, CallAnalytics, StateOverride};
 use ethcore::block::{ClosedBlock, IsBlock};
 use ethcore::header::BlockNumber;
 use ethcore::transaction::SignedTransaction;
@@ -191,6 +191,10 @@ impl MinerService for TestMinerService {
 		self.pending_transactions.lock().get(hash).cloned()
 	}
 
+	fn is_local_transaction(&self, _hash: &H256) -> bool {
+		false
+	}
+
 	fn all_transactions(&self) -> Vec<SignedTransaction> {
 		self.pending_transactions.lock().values().cloned().collect()
 	}
@@ -235,7 +239,7 @@ impl MinerService for TestMinerService {
 		self.latest_closed_block.lock().as_ref().map_or_else(U256::zero, |b| b.block().fields().state.balance(address).clone())
 	}
 
-	fn call(&self, _chain: &MiningBlockChainClient, _t: &SignedTransaction, _analytics: CallAnalytics) -> Result<Executed, CallError> {
+	fn call(&self, _chain: &MiningBlockChainClient, _t: &SignedTransaction, _state_override: &StateOverride, _analytics: CallAnalytics) -> Result<Executed, CallError> {
 		unimplemented!();
 	}
 
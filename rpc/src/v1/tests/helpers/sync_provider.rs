@@ -17,7 +17,7 @@
 //! Test implementation of SyncProvider.
 
 use util::{RwLock, U256};
-use ethsync::{SyncProvider, SyncStatus, SyncState, PeerInfo};
+use ethsync::{SyncProvider, SyncStatus, SyncState, PeerInfo, PeerTrafficInfo, PeerProtocolTrafficInfo, SyncStats, PeerSyncStats, BlockSource};
 
 /// TestSyncProvider config.
 pub struct Config {
@@ -79,7 +79,12 @@ impl SyncProvider for TestSyncProvider {
 				local_address: "127.0.0.1:8888".to_owned(),
 				eth_version: 62,
 				eth_difficulty: Some(40.into()),
-				eth_head: 50.into()
+				eth_head: 50.into(),
+				traffic: PeerTrafficInfo { packets_sent: 4, bytes_sent: 300, packets_received: 5, bytes_received: 700 },
+				protocol_traffic: vec![PeerProtocolTrafficInfo {
+					protocol: "eth".to_owned(),
+					traffic: PeerTrafficInfo { packets_sent: 2, bytes_sent: 200, packets_received: 3, bytes_received: 600 },
+				}],
 			},
 			PeerInfo {
 				id: None,
@@ -89,9 +94,45 @@ impl SyncProvider for TestSyncProvider {
 				local_address: "127.0.0.1:3333".to_owned(),
 				eth_version: 64,
 				eth_difficulty: None,
-				eth_head: 60.into()
+				eth_head: 60.into(),
+				traffic: PeerTrafficInfo::default(),
+				protocol_traffic: Vec::new(),
 			}
 		]
 	}
+
+	fn stats(&self) -> SyncStats {
+		SyncStats {
+			headers_per_sec: 12.5,
+			bodies_per_sec: 10.5,
+			receipts_per_sec: 0.5,
+			blocks_per_sec: 9.5,
+			headers_total: 1024,
+			bodies_total: 512,
+			receipts_total: 5,
+			blocks_total: 500,
+			queue_unverified: 3,
+			queue_verifying: 2,
+			queue_verified: 1,
+			peers: vec![
+				PeerSyncStats {
+					id: Some("node1".to_owned()),
+					connected: true,
+					responses: 40,
+					timeouts: 1,
+					avg_latency_ms: 250,
+					last_latency_ms: 180,
+					blocks_supplied: 500,
+				}
+			],
+			recent_blocks: vec![
+				BlockSource {
+					hash: 5.into(),
+					number: 500,
+					peer: Some("node1".to_owned()),
+				}
+			],
+		}
+	}
 }
 
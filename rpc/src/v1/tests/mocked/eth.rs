@@ -21,7 +21,7 @@ use std::time::{Instant, Duration};
 use jsonrpc_core::IoHandler;
 use util::{Uint, U256, Address, H256, FixedHash, Mutex};
 use ethcore::account_provider::AccountProvider;
-use ethcore::client::{TestBlockChainClient, EachBlockWith, Executed, TransactionID};
+use ethcore::client::{TestBlockChainClient, EachBlockWith, Executed, TransactionID, AccountOverride};
 use ethcore::log_entry::{LocalizedLogEntry, LogEntry};
 use ethcore::receipt::LocalizedReceipt;
 use ethcore::transaction::{Transaction, Action};
@@ -29,7 +29,7 @@ use ethcore::miner::{ExternalMiner, MinerService};
 use ethsync::SyncState;
 use v1::{Eth, EthClient, EthClientOptions, EthFilter, EthFilterClient, EthSigning, EthSigningUnsafeClient};
 use v1::tests::helpers::{TestSyncProvider, Config, TestMinerService};
-use rustc_serialize::hex::ToHex;
+use rustc_serialize::hex::{ToHex, FromHex};
 use time::get_time;
 
 fn blockchain_client() -> Arc<TestBlockChainClient> {
@@ -140,6 +140,29 @@ fn rpc_eth_syncing() {
 	assert_eq!(tester.io.handle_request_sync(request), Some(false_res.to_owned()));
 }
 
+#[test]
+fn rpc_eth_syncing_ancient_blocks() {
+	let request = r#"{"jsonrpc": "2.0", "method": "eth_syncing", "params": [], "id": 1}"#;
+
+	let tester = EthTester::default();
+	tester.add_blocks(1000, EachBlockWith::Nothing);
+
+	{
+		let mut status = tester.sync.status.write();
+		status.highest_block_number = Some(1000);
+		status.last_imported_old_block_number = Some(500);
+	}
+
+	let res = r#"{"jsonrpc":"2.0","result":{"ancientBlock":"0x1f4","currentBlock":"0x3e8","highestBlock":"0x3e8","startingBlock":"0x0"},"id":1}"#;
+	assert_eq!(tester.io.handle_request_sync(request), Some(res.to_owned()));
+
+	// ancient blocks download complete
+	tester.sync.status.write().last_imported_old_block_number = None;
+
+	let false_res = r#"{"jsonrpc":"2.0","result":false,"id":1}"#;
+	assert_eq!(tester.io.handle_request_sync(request), Some(false_res.to_owned()));
+}
+
 #[test]
 fn rpc_eth_hashrate() {
 	let tester = EthTester::default();
@@ -526,6 +549,9 @@ fn rpc_eth_call_latest() {
 		trace: vec![],
 		vm_trace: None,
 		state_diff: None,
+		step_log: None,
+		gas_profile: None,
+		state_accesses: None,
 	}));
 
 	let request = r#"{
@@ -561,6 +587,9 @@ fn rpc_eth_call() {
 		trace: vec![],
 		vm_trace: None,
 		state_diff: None,
+		step_log: None,
+		gas_profile: None,
+		state_accesses: None,
 	}));
 
 	let request = r#"{
@@ -596,6 +625,9 @@ fn rpc_eth_call_default_block() {
 		trace: vec![],
 		vm_trace: None,
 		state_diff: None,
+		step_log: None,
+		gas_profile: None,
+		state_accesses: None,
 	}));
 
 	let request = r#"{
@@ -616,6 +648,56 @@ fn rpc_eth_call_default_block() {
 	assert_eq!(tester.io.handle_request_sync(request), Some(response.to_owned()));
 }
 
+#[test]
+fn rpc_eth_call_with_state_override() {
+	let tester = EthTester::default();
+	tester.client.set_execution_result(Ok(Executed {
+		gas: U256::zero(),
+		gas_used: U256::from(0xff30),
+		refunded: U256::from(0x5),
+		cumulative_gas_used: U256::zero(),
+		logs: vec![],
+		contracts_created: vec![],
+		output: vec![0x12, 0x34, 0xff],
+		trace: vec![],
+		vm_trace: None,
+		state_diff: None,
+		step_log: None,
+		gas_profile: None,
+		state_accesses: None,
+	}));
+
+	let request = r#"{
+		"jsonrpc": "2.0",
+		"method": "eth_call",
+		"params": [{
+			"from": "0xb60e8dd61c5d32be8058bb8eb970870f07233155",
+			"to": "0xd46e8dd67c5d32be8058bb8eb970870f07244567",
+			"data": "0xd46e8dd67c5d32be8d46e8dd67c5d32be8058bb8eb970870f072445675058bb8eb970870f072445675"
+		},
+		"latest",
+		{
+			"0xd46e8dd67c5d32be8058bb8eb970870f07244567": {
+				"balance": "0x9184e72a",
+				"code": "0x600160005260206000f3"
+			}
+		}],
+		"id": 1
+	}"#;
+	let response = r#"{"jsonrpc":"2.0","result":"0x1234ff","id":1}"#;
+
+	assert_eq!(tester.io.handle_request_sync(request), Some(response.to_owned()));
+
+	let state_override = tester.client.last_state_override.read().clone().expect("call was made; qed");
+	let address = Address::from_str("d46e8dd67c5d32be8058bb8eb970870f07244567").unwrap();
+	assert_eq!(state_override.accounts.len(), 1);
+	assert_eq!(state_override.accounts[&address], AccountOverride {
+		balance: Some(U256::from(0x9184e72au64)),
+		code: Some("600160005260206000f3".from_hex().unwrap()),
+		..Default::default()
+	});
+}
+
 #[test]
 fn rpc_eth_estimate_gas() {
 	let tester = EthTester::default();
@@ -630,6 +712,9 @@ fn rpc_eth_estimate_gas() {
 		trace: vec![],
 		vm_trace: None,
 		state_diff: None,
+		step_log: None,
+		gas_profile: None,
+		state_accesses: None,
 	}));
 
 	let request = r#"{
@@ -665,6 +750,9 @@ fn rpc_eth_estimate_gas_default_block() {
 		trace: vec![],
 		vm_trace: None,
 		state_diff: None,
+		step_log: None,
+		gas_profile: None,
+		state_accesses: None,
 	}));
 
 	let request = r#"{
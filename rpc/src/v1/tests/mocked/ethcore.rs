@@ -190,15 +190,65 @@ fn rpc_ethcore_net_peers() {
 
 	let request = r#"{"jsonrpc": "2.0", "method": "ethcore_netPeers", "params":[], "id": 1}"#;
 	let response = "{\"jsonrpc\":\"2.0\",\"result\":{\"active\":0,\"connected\":120,\"max\":50,\"peers\":[{\"caps\":[\"eth/62\",\"eth/63\"],\
-\"id\":\"node1\",\"name\":\"Parity/1\",\"network\":{\"localAddress\":\"127.0.0.1:8888\",\"remoteAddress\":\"127.0.0.1:7777\"}\
+\"id\":\"node1\",\"name\":\"Parity/1\",\"network\":{\"localAddress\":\"127.0.0.1:8888\",\"protocolTraffic\":{\"eth\":\
+{\"bytesReceived\":600,\"bytesSent\":200,\"packetsReceived\":3,\"packetsSent\":2}},\"remoteAddress\":\"127.0.0.1:7777\",\"traffic\":\
+{\"bytesReceived\":700,\"bytesSent\":300,\"packetsReceived\":5,\"packetsSent\":4}}\
 ,\"protocols\":{\"eth\":{\"difficulty\":\"0x28\",\"head\":\"0000000000000000000000000000000000000000000000000000000000000032\"\
 ,\"version\":62}}},{\"caps\":[\"eth/63\",\"eth/64\"],\"id\":null,\"name\":\"Parity/2\",\"network\":{\"localAddress\":\
-\"127.0.0.1:3333\",\"remoteAddress\":\"Handshake\"},\"protocols\":{\"eth\":{\"difficulty\":null,\"head\":\
+\"127.0.0.1:3333\",\"protocolTraffic\":{},\"remoteAddress\":\"Handshake\",\"traffic\":{\"bytesReceived\":0,\"bytesSent\":0,\
+\"packetsReceived\":0,\"packetsSent\":0}},\"protocols\":{\"eth\":{\"difficulty\":null,\"head\":\
 \"000000000000000000000000000000000000000000000000000000000000003c\",\"version\":64}}}]},\"id\":1}";
 
 	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
 }
 
+#[test]
+fn rpc_ethcore_banned_peers() {
+	let deps = Dependencies::new();
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "ethcore_bannedPeers", "params":[], "id": 1}"#;
+	let response = "{\"jsonrpc\":\"2.0\",\"result\":[{\"bannedUntil\":1476878400,\"bans\":2,\"id\":\
+\"a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c\",\
+\"reputation\":-100,\"url\":\"enode://a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c\
+@22.99.55.44:7770\"}],\"id\":1}";
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_ethcore_allowed_peers() {
+	let deps = Dependencies::new();
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "ethcore_allowedPeers", "params":[], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":["a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c"],"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_ethcore_nat_status() {
+	let deps = Dependencies::new();
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "ethcore_natStatus", "params":[], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":{"addressChanges":1,"enabled":true,"failures":0,"lifetime":3600,"protocol":"natpmp","publicAddress":"203.0.113.7:30303","publicUdpPort":30303,"renewedAt":1476878400},"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_ethcore_sync_stats() {
+	let deps = Dependencies::new();
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "ethcore_syncStats", "params":[], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":{"blocksPerSec":9.5,"blocksTotal":500,"bodiesPerSec":10.5,"bodiesTotal":512,"headersPerSec":12.5,"headersTotal":1024,"peers":[{"avgLatencyMs":250,"blocksSupplied":500,"connected":true,"id":"node1","lastLatencyMs":180,"responses":40,"timeouts":1}],"queue":{"unverified":3,"verified":1,"verifying":2},"receiptsPerSec":0.5,"receiptsTotal":5,"recentBlocks":[{"hash":"0x0000000000000000000000000000000000000000000000000000000000000005","number":"0x1f4","peer":"node1"}]},"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
 #[test]
 fn rpc_ethcore_net_port() {
 	let deps = Dependencies::new();
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use ethsync::{ManageNetwork, NetworkConfiguration};
+use ethsync::{ManageNetwork, NetworkConfiguration, BannedPeerInfo, NatStatusInfo};
 
 pub struct TestManageNetwork;
 
@@ -27,4 +27,36 @@ impl ManageNetwork for TestManageNetwork {
 	fn start_network(&self) {}
 	fn stop_network(&self) {}
 	fn network_config(&self) -> NetworkConfiguration { NetworkConfiguration::new_local() }
+	fn banned_peers(&self) -> Vec<BannedPeerInfo> {
+		vec![BannedPeerInfo {
+			id: "a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c".to_owned(),
+			url: "enode://a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c@22.99.55.44:7770".to_owned(),
+			reputation: -100,
+			bans: 2,
+			banned_until: 1476878400,
+		}]
+	}
+	fn unban_peer(&self, peer: String) -> Result<(), String> {
+		if peer.contains("a979fb57") { Ok(()) } else { Err("PeerNotFound".into()) }
+	}
+	fn allowed_peers(&self) -> Result<Vec<String>, String> {
+		Ok(vec!["a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c".to_owned()])
+	}
+	fn add_allowed_peer(&self, peer: String) -> Result<(), String> {
+		if peer.starts_with("enode://") || peer.len() == 128 { Ok(()) } else { Err("Invalid node id".into()) }
+	}
+	fn remove_allowed_peer(&self, _peer: String) -> Result<(), String> { Ok(()) }
+	fn reload_allowed_peers(&self) -> Result<(), String> { Ok(()) }
+	fn nat_status(&self) -> NatStatusInfo {
+		NatStatusInfo {
+			enabled: true,
+			protocol: Some("natpmp".to_owned()),
+			public_address: Some("203.0.113.7:30303".to_owned()),
+			public_udp_port: Some(30303),
+			lifetime: 3600,
+			renewed_at: 1476878400,
+			address_changes: 1,
+			failures: 0,
+		}
+	}
 }
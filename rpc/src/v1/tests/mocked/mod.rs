@@ -27,3 +27,4 @@ mod ethcore;
 mod ethcore_set;
 mod rpc;
 mod manage_network;
+mod traces;
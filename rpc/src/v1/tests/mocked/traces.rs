@@ -0,0 +1,107 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use jsonrpc_core::IoHandler;
+use util::U256;
+use ethcore::client::{TestBlockChainClient, EachBlockWith, Executed};
+use ethcore::error::CallError;
+use v1::{Traces, TracesClient};
+use v1::tests::helpers::TestMinerService;
+
+fn executed() -> Executed {
+	Executed {
+		gas: U256::zero(),
+		gas_used: U256::zero(),
+		refunded: U256::zero(),
+		cumulative_gas_used: U256::zero(),
+		logs: vec![],
+		contracts_created: vec![],
+		output: vec![],
+		trace: vec![],
+		vm_trace: None,
+		state_diff: None,
+		step_log: None,
+		gas_profile: None,
+		state_accesses: None,
+	}
+}
+
+fn io(client: &Arc<TestBlockChainClient>) -> IoHandler {
+	let miner = Arc::new(TestMinerService::default());
+	let io = IoHandler::new();
+	io.add_delegate(TracesClient::new(client, &miner).to_delegate());
+	io
+}
+
+#[test]
+fn rpc_trace_profile() {
+	let client = Arc::new(TestBlockChainClient::default());
+	client.add_blocks(3, EachBlockWith::Nothing);
+	client.set_execution_result(Ok(executed()));
+	let io = io(&client);
+
+	let request = r#"{"jsonrpc": "2.0", "method": "trace_profile", "params": ["0x1", "latest"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":{"gas":"0x0","instructions":[],"codes":[]},"id":1}"#;
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+
+	let request = r#"{"jsonrpc": "2.0", "method": "trace_profile", "params": ["0x2", "0x1"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: Block range","data":"\"from block 2 is after to block 1\""},"id":1}"#;
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+
+	let request = r#"{"jsonrpc": "2.0", "method": "trace_profile", "params": ["0x1", "0x10"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32016,"message":"Unknown block.","data":"Number(16)"},"id":1}"#;
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_trace_profile_limits_range() {
+	let client = Arc::new(TestBlockChainClient::default());
+	client.add_blocks(1001, EachBlockWith::Nothing);
+	client.set_execution_result(Ok(executed()));
+	let io = io(&client);
+
+	let request = r#"{"jsonrpc": "2.0", "method": "trace_profile", "params": ["0x0", "0x3e8"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: Block range","data":"\"at most 1000 blocks can be profiled at once\""},"id":1}"#;
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+
+	let request = r#"{"jsonrpc": "2.0", "method": "trace_profile", "params": ["0x1", "0x3e8"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":{"gas":"0x0","instructions":[],"codes":[]},"id":1}"#;
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_trace_profile_reports_client_errors() {
+	let client = Arc::new(TestBlockChainClient::default());
+	client.add_blocks(2, EachBlockWith::Nothing);
+	client.set_execution_result(Err(CallError::StatePruned));
+	let io = io(&client);
+
+	let request = r#"{"jsonrpc": "2.0", "method": "trace_profile", "params": ["0x0", "0x1"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"This request is not supported because your node is running with state pruning. Run with --pruning=archive.","data":null},"id":1}"#;
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_trace_replay_rejects_conflicting_vm_tracers() {
+	let client = Arc::new(TestBlockChainClient::default());
+	client.set_execution_result(Ok(executed()));
+	let io = io(&client);
+
+	let request = r#"{"jsonrpc": "2.0", "method": "trace_replayTransaction", "params": ["0x0000000000000000000000000000000000000000000000000000000000000005", ["vmTrace", "stepLog"]], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: Flags","data":"\"vmTrace, gasProfile and stepLog can't be combined\""},"id":1}"#;
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
@@ -18,7 +18,7 @@
 use jsonrpc_core::*;
 
 use v1::types::{Block, BlockNumber, Bytes, CallRequest, Filter, FilterChanges, Index};
-use v1::types::{Log, Receipt, SyncStatus, Transaction, Work};
+use v1::types::{Log, Receipt, StateOverride, SyncStatus, Transaction, Work};
 use v1::types::{H64, H160, H256, U256};
 
 use v1::helpers::auto_args::{Trailing, Wrap};
@@ -102,9 +102,10 @@ build_rpc_trait! {
 		#[rpc(name = "eth_sendRawTransaction")]
 		fn send_raw_transaction(&self, Bytes) -> Result<H256, Error>;
 
-		/// Call contract, returning the output data.
+		/// Call contract, returning the output data. The state may be overridden
+		/// for the duration of the call.
 		#[rpc(name = "eth_call")]
-		fn call(&self, CallRequest, Trailing<BlockNumber>) -> Result<Bytes, Error>;
+		fn call(&self, CallRequest, Trailing<BlockNumber>, Trailing<StateOverride>) -> Result<Bytes, Error>;
 
 		/// Estimate gas needed for execution of given contract.
 		#[rpc(name = "eth_estimateGas")]
@@ -18,7 +18,7 @@
 use jsonrpc_core::Error;
 
 use v1::helpers::auto_args::{Wrap, WrapAsync, Ready};
-use v1::types::{H160, H256, H512, U256, Bytes, Peers, Transaction, RpcSettings};
+use v1::types::{H160, H256, H512, U256, Bytes, Peers, BannedPeer, NatStatus, SyncStats, Transaction, RpcSettings};
 
 build_rpc_trait! {
 	/// Ethcore-specific rpc interface.
@@ -59,6 +59,22 @@ build_rpc_trait! {
 		#[rpc(name = "ethcore_netPeers")]
 		fn net_peers(&self) -> Result<Peers, Error>;
 
+		/// Returns peers banned because of low reputation
+		#[rpc(name = "ethcore_bannedPeers")]
+		fn banned_peers(&self) -> Result<Vec<BannedPeer>, Error>;
+
+		/// Returns ids of the peers allowed to connect in permissioned mode
+		#[rpc(name = "ethcore_allowedPeers")]
+		fn allowed_peers(&self) -> Result<Vec<String>, Error>;
+
+		/// Returns NAT traversal status
+		#[rpc(name = "ethcore_natStatus")]
+		fn nat_status(&self) -> Result<NatStatus, Error>;
+
+		/// Returns sync download rates, queue depths and per-peer request statistics
+		#[rpc(name = "ethcore_syncStats")]
+		fn sync_stats(&self) -> Result<SyncStats, Error>;
+
 		/// Returns network port
 		#[rpc(name = "ethcore_netPort")]
 		fn net_port(&self) -> Result<u16, Error>;
@@ -60,6 +60,22 @@ build_rpc_trait! {
 		#[rpc(name = "ethcore_removeReservedPeer")]
 		fn remove_reserved_peer(&self, String) -> Result<bool, Error>;
 
+		/// Lift the ban for a peer.
+		#[rpc(name = "ethcore_unbanPeer")]
+		fn unban_peer(&self, String) -> Result<bool, Error>;
+
+		/// Allow a peer to connect in permissioned mode.
+		#[rpc(name = "ethcore_addAllowedPeer")]
+		fn add_allowed_peer(&self, String) -> Result<bool, Error>;
+
+		/// Remove a peer from the allowlist and disconnect it.
+		#[rpc(name = "ethcore_removeAllowedPeer")]
+		fn remove_allowed_peer(&self, String) -> Result<bool, Error>;
+
+		/// Read the allowlist file again.
+		#[rpc(name = "ethcore_reloadAllowedPeers")]
+		fn reload_allowed_peers(&self) -> Result<bool, Error>;
+
 		/// Drop all non-reserved peers.
 		#[rpc(name = "ethcore_dropNonReservedPeers")]
 		fn drop_non_reserved_peers(&self) -> Result<bool, Error>;
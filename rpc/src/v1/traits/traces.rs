@@ -41,6 +41,13 @@ pub trait Traces: Sized + Send + Sync + 'static {
 	/// Executes the transaction with the given hash and returns a number of possible traces for it.
 	fn replay_transaction(&self, _: Params) -> Result<Value, Error>;
 
+	/// Replays all transactions in the given range of blocks and returns the gas spent per opcode, program counter and code.
+	/// Gas is summed over all executions of a code, so separate calls into the same contract can't be told apart.
+	fn profile(&self, _: Params) -> Result<Value, Error>;
+
+	/// Executes the transaction with the given hash and returns every account and storage slot it read or wrote.
+	fn state_accesses(&self, _: Params) -> Result<Value, Error>;
+
 	/// Should be used to convert object to io delegate.
 	fn to_delegate(self) -> IoDelegate<Self> {
 		let mut delegate = IoDelegate::new(Arc::new(self));
@@ -51,6 +58,8 @@ pub trait Traces: Sized + Send + Sync + 'static {
 		delegate.add_method("trace_call", Traces::call);
 		delegate.add_method("trace_rawTransaction", Traces::raw_transaction);
 		delegate.add_method("trace_replayTransaction", Traces::replay_transaction);
+		delegate.add_method("trace_profile", Traces::profile);
+		delegate.add_method("trace_stateAccesses", Traces::state_accesses);
 
 		delegate
 	}
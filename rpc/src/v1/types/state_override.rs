@@ -0,0 +1,109 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Deserializer};
+use ethcore::client::{StateOverride as EthStateOverride, AccountOverride as EthAccountOverride};
+use v1::types::{Bytes, H160, H256, U256};
+
+/// Changes to an account made before executing a call.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+pub struct AccountOverride {
+	/// Balance to set.
+	pub balance: Option<U256>,
+	/// Nonce to set.
+	pub nonce: Option<U256>,
+	/// Code to set.
+	pub code: Option<Bytes>,
+	/// Storage replacing all of the account's storage.
+	pub state: Option<BTreeMap<H256, H256>>,
+	/// Storage slots to set.
+	#[serde(rename="stateDiff")]
+	pub state_diff: Option<BTreeMap<H256, H256>>,
+}
+
+impl Into<EthAccountOverride> for AccountOverride {
+	fn into(self) -> EthAccountOverride {
+		EthAccountOverride {
+			balance: self.balance.map(Into::into),
+			nonce: self.nonce.map(Into::into),
+			code: self.code.map(Into::into),
+			state: self.state.map(|s| s.into_iter().map(|(k, v)| (k.into(), v.into())).collect()),
+			state_diff: self.state_diff.map(|s| s.into_iter().map(|(k, v)| (k.into(), v.into())).collect()),
+		}
+	}
+}
+
+/// Changes to the state made before executing a call, by account address.
+#[derive(Debug, Default, PartialEq)]
+pub struct StateOverride(pub BTreeMap<H160, AccountOverride>);
+
+impl Deserialize for StateOverride {
+	fn deserialize<D>(deserializer: &mut D) -> Result<StateOverride, D::Error>
+	where D: Deserializer {
+		BTreeMap::deserialize(deserializer).map(StateOverride)
+	}
+}
+
+impl Into<EthStateOverride> for StateOverride {
+	fn into(self) -> EthStateOverride {
+		EthStateOverride {
+			accounts: self.0.into_iter().map(|(a, o)| (a.into(), o.into())).collect(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use ethcore::client::StateOverride as EthStateOverride;
+	use v1::types::{H160, H256, U256};
+	use super::{StateOverride, AccountOverride};
+
+	#[test]
+	fn state_override_deserialize() {
+		let s = r#"{
+			"0x0000000000000000000000000000000000000001": {
+				"balance": "0x10",
+				"code": "0x6000",
+				"stateDiff": {
+					"0x0000000000000000000000000000000000000000000000000000000000000001": "0x0000000000000000000000000000000000000000000000000000000000000002"
+				}
+			},
+			"0x0000000000000000000000000000000000000002": {
+				"nonce": "0x1",
+				"state": {}
+			}
+		}"#;
+		let deserialized: StateOverride = serde_json::from_str(s).unwrap();
+
+		assert_eq!(deserialized.0.get(&H160::from(1)), Some(&AccountOverride {
+			balance: Some(U256::from(0x10)),
+			nonce: None,
+			code: Some(vec![0x60, 0x00].into()),
+			state: None,
+			state_diff: Some(vec![(H256::from(1), H256::from(2))].into_iter().collect()),
+		}));
+		assert_eq!(deserialized.0.get(&H160::from(2)), Some(&AccountOverride {
+			nonce: Some(U256::from(1)),
+			state: Some(Default::default()),
+			..Default::default()
+		}));
+
+		let state_override: EthStateOverride = deserialized.into();
+		assert_eq!(state_override.accounts.len(), 2);
+	}
+}
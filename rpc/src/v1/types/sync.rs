@@ -14,9 +14,11 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use ethsync::PeerInfo as SyncPeerInfo;
+use std::collections::BTreeMap;
+use ethsync::{PeerInfo as SyncPeerInfo, PeerTrafficInfo as SyncPeerTrafficInfo, BannedPeerInfo, NatStatusInfo};
+use ethsync::{SyncStats as EthSyncStats, PeerSyncStats as EthPeerSyncStats, BlockSource as EthBlockSource};
 use serde::{Serialize, Serializer};
-use v1::types::U256;
+use v1::types::{U256, H256};
 
 /// Sync info
 #[derive(Default, Debug, Serialize, PartialEq)]
@@ -30,6 +32,9 @@ pub struct SyncInfo {
 	/// Highest block seen so far
 	#[serde(rename="highestBlock")]
 	pub highest_block: U256,
+	/// Lowest block imported while downloading ancient blocks, if in progress
+	#[serde(rename="ancientBlock", skip_serializing_if="Option::is_none")]
+	pub ancient_block: Option<U256>,
 }
 
 /// Peers info
@@ -69,6 +74,28 @@ pub struct PeerNetworkInfo {
 	/// Local endpoint address
 	#[serde(rename="localAddress")]
 	pub local_address: String,
+	/// Traffic exchanged with the peer
+	pub traffic: PeerTrafficInfo,
+	/// Traffic exchanged with the peer by subprotocol
+	#[serde(rename="protocolTraffic")]
+	pub protocol_traffic: BTreeMap<String, PeerTrafficInfo>,
+}
+
+/// Peer traffic counters
+#[derive(Default, Debug, Serialize)]
+pub struct PeerTrafficInfo {
+	/// Number of packets sent
+	#[serde(rename="packetsSent")]
+	pub packets_sent: u64,
+	/// Number of packet bytes sent
+	#[serde(rename="bytesSent")]
+	pub bytes_sent: u64,
+	/// Number of packets received
+	#[serde(rename="packetsReceived")]
+	pub packets_received: u64,
+	/// Number of packet bytes received
+	#[serde(rename="bytesReceived")]
+	pub bytes_received: u64,
 }
 
 /// Peer protocols information
@@ -89,6 +116,200 @@ pub struct PeerEthereumProtocolInfo {
 	pub head: String,
 }
 
+/// Peer banned because of low reputation
+#[derive(Default, Debug, Serialize)]
+pub struct BannedPeer {
+	/// Public node id
+	pub id: String,
+	/// Node address
+	pub url: String,
+	/// Reputation score
+	pub reputation: i32,
+	/// Number of times the peer has been banned
+	pub bans: u32,
+	/// Unix time when the ban expires
+	#[serde(rename="bannedUntil")]
+	pub banned_until: u64,
+}
+
+impl From<BannedPeerInfo> for BannedPeer {
+	fn from(p: BannedPeerInfo) -> BannedPeer {
+		BannedPeer {
+			id: p.id,
+			url: p.url,
+			reputation: p.reputation,
+			bans: p.bans,
+			banned_until: p.banned_until,
+		}
+	}
+}
+
+/// NAT traversal status
+#[derive(Default, Debug, Serialize)]
+pub struct NatStatus {
+	/// Port mapping on the gateway is enabled
+	pub enabled: bool,
+	/// Protocol of the active port mapping
+	pub protocol: Option<String>,
+	/// Address advertised to other nodes
+	#[serde(rename="publicAddress")]
+	pub public_address: Option<String>,
+	/// UDP port advertised to other nodes
+	#[serde(rename="publicUdpPort")]
+	pub public_udp_port: Option<u16>,
+	/// Lifetime of the active port mapping in seconds
+	pub lifetime: u32,
+	/// Unix time the port mapping was last created or renewed
+	#[serde(rename="renewedAt")]
+	pub renewed_at: u64,
+	/// Number of times the external address has changed
+	#[serde(rename="addressChanges")]
+	pub address_changes: u32,
+	/// Number of failed mapping attempts
+	pub failures: u32,
+}
+
+impl From<NatStatusInfo> for NatStatus {
+	fn from(s: NatStatusInfo) -> NatStatus {
+		NatStatus {
+			enabled: s.enabled,
+			protocol: s.protocol,
+			public_address: s.public_address,
+			public_udp_port: s.public_udp_port,
+			lifetime: s.lifetime,
+			renewed_at: s.renewed_at,
+			address_changes: s.address_changes,
+			failures: s.failures,
+		}
+	}
+}
+
+/// Sync performance metrics
+#[derive(Default, Debug, Serialize)]
+pub struct SyncStats {
+	/// Block headers downloaded per second
+	#[serde(rename="headersPerSec")]
+	pub headers_per_sec: f64,
+	/// Block bodies downloaded per second
+	#[serde(rename="bodiesPerSec")]
+	pub bodies_per_sec: f64,
+	/// Block receipts downloaded per second
+	#[serde(rename="receiptsPerSec")]
+	pub receipts_per_sec: f64,
+	/// Blocks imported per second
+	#[serde(rename="blocksPerSec")]
+	pub blocks_per_sec: f64,
+	/// Total block headers downloaded
+	#[serde(rename="headersTotal")]
+	pub headers_total: u64,
+	/// Total block bodies downloaded
+	#[serde(rename="bodiesTotal")]
+	pub bodies_total: u64,
+	/// Total block receipts downloaded
+	#[serde(rename="receiptsTotal")]
+	pub receipts_total: u64,
+	/// Total blocks imported
+	#[serde(rename="blocksTotal")]
+	pub blocks_total: u64,
+	/// Verification queue depths
+	pub queue: SyncQueueStats,
+	/// Request statistics of connected and recently disconnected peers
+	pub peers: Vec<PeerSyncStats>,
+	/// Sources of recently imported blocks
+	#[serde(rename="recentBlocks")]
+	pub recent_blocks: Vec<BlockSource>,
+}
+
+/// Block verification queue depths
+#[derive(Default, Debug, Serialize)]
+pub struct SyncQueueStats {
+	/// Blocks waiting for verification
+	pub unverified: usize,
+	/// Blocks being verified
+	pub verifying: usize,
+	/// Verified blocks waiting for import
+	pub verified: usize,
+}
+
+/// Peer request statistics
+#[derive(Default, Debug, Serialize)]
+pub struct PeerSyncStats {
+	/// Public node id
+	pub id: Option<String>,
+	/// Peer is still connected
+	pub connected: bool,
+	/// Number of responses received
+	pub responses: u64,
+	/// Number of timed out requests
+	pub timeouts: u64,
+	/// Average response latency in milliseconds
+	#[serde(rename="avgLatencyMs")]
+	pub avg_latency_ms: u64,
+	/// Latency of the last response in milliseconds
+	#[serde(rename="lastLatencyMs")]
+	pub last_latency_ms: u64,
+	/// Number of imported blocks downloaded from this peer
+	#[serde(rename="blocksSupplied")]
+	pub blocks_supplied: u64,
+}
+
+/// Source of an imported block
+#[derive(Default, Debug, Serialize)]
+pub struct BlockSource {
+	/// Block hash
+	pub hash: H256,
+	/// Block number
+	pub number: U256,
+	/// Public id of the node the block was downloaded from
+	pub peer: Option<String>,
+}
+
+impl From<EthSyncStats> for SyncStats {
+	fn from(s: EthSyncStats) -> SyncStats {
+		SyncStats {
+			headers_per_sec: s.headers_per_sec,
+			bodies_per_sec: s.bodies_per_sec,
+			receipts_per_sec: s.receipts_per_sec,
+			blocks_per_sec: s.blocks_per_sec,
+			headers_total: s.headers_total,
+			bodies_total: s.bodies_total,
+			receipts_total: s.receipts_total,
+			blocks_total: s.blocks_total,
+			queue: SyncQueueStats {
+				unverified: s.queue_unverified,
+				verifying: s.queue_verifying,
+				verified: s.queue_verified,
+			},
+			peers: s.peers.into_iter().map(Into::into).collect(),
+			recent_blocks: s.recent_blocks.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+impl From<EthPeerSyncStats> for PeerSyncStats {
+	fn from(p: EthPeerSyncStats) -> PeerSyncStats {
+		PeerSyncStats {
+			id: p.id,
+			connected: p.connected,
+			responses: p.responses,
+			timeouts: p.timeouts,
+			avg_latency_ms: p.avg_latency_ms,
+			last_latency_ms: p.last_latency_ms,
+			blocks_supplied: p.blocks_supplied,
+		}
+	}
+}
+
+impl From<EthBlockSource> for BlockSource {
+	fn from(b: EthBlockSource) -> BlockSource {
+		BlockSource {
+			hash: b.hash.into(),
+			number: b.number.into(),
+			peer: b.peer,
+		}
+	}
+}
+
 /// Sync status
 #[derive(Debug, PartialEq)]
 pub enum SyncStatus {
@@ -117,6 +338,8 @@ impl From<SyncPeerInfo> for PeerInfo {
 			network: PeerNetworkInfo {
 				remote_address: p.remote_address,
 				local_address: p.local_address,
+				traffic: p.traffic.into(),
+				protocol_traffic: p.protocol_traffic.into_iter().map(|t| (t.protocol, t.traffic.into())).collect(),
 			},
 			protocols: PeerProtocolsInfo {
 				eth: Some(PeerEthereumProtocolInfo {
@@ -129,6 +352,17 @@ impl From<SyncPeerInfo> for PeerInfo {
 	}
 }
 
+impl From<SyncPeerTrafficInfo> for PeerTrafficInfo {
+	fn from(t: SyncPeerTrafficInfo) -> PeerTrafficInfo {
+		PeerTrafficInfo {
+			packets_sent: t.packets_sent,
+			bytes_sent: t.bytes_sent,
+			packets_received: t.packets_received,
+			bytes_received: t.bytes_received,
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use serde_json;
@@ -19,9 +19,11 @@ use serde::{Serialize, Serializer};
 use ethcore::trace::{FlatTrace, LocalizedTrace as EthLocalizedTrace, trace, TraceError};
 use ethcore::trace as et;
 use ethcore::state_diff;
+use ethcore::state_accesses;
 use ethcore::account_diff;
 use ethcore::executed;
 use ethcore::client::Executed;
+use ethcore::evm::interpreter::debugger::instruction_name;
 use util::Uint;
 use v1::types::{Bytes, H160, H256, U256};
 
@@ -553,6 +555,15 @@ pub struct TraceResults {
 	/// The transaction trace.
 	#[serde(rename="stateDiff")]
 	pub state_diff: Option<StateDiff>,
+	/// The step log.
+	#[serde(rename="stepLog", skip_serializing_if="Option::is_none")]
+	pub step_log: Option<Vec<StepLogEntry>>,
+	/// The gas profile.
+	#[serde(rename="gasProfile", skip_serializing_if="Option::is_none")]
+	pub gas_profile: Option<GasProfile>,
+	/// The accessed accounts and storage.
+	#[serde(rename="stateAccesses", skip_serializing_if="Option::is_none")]
+	pub state_accesses: Option<StateAccesses>,
 }
 
 impl From<Executed> for TraceResults {
@@ -562,10 +573,212 @@ impl From<Executed> for TraceResults {
 			trace: t.trace.into_iter().map(Into::into).collect(),
 			vm_trace: t.vm_trace.map(Into::into),
 			state_diff: t.state_diff.map(Into::into),
+			step_log: t.step_log.map(|log| log.into_iter().map(Into::into).collect()),
+			gas_profile: t.gas_profile.map(Into::into),
+			state_accesses: t.state_accesses.map(Into::into),
 		}
 	}
 }
 
+#[derive(Debug, Serialize)]
+/// Interpreter state before executing a single instruction.
+pub struct StepLogEntry {
+	/// The program counter.
+	pub pc: usize,
+	/// The instruction.
+	pub op: u8,
+	/// The instruction mnemonic.
+	#[serde(rename="opName")]
+	pub op_name: String,
+	/// The gas left.
+	pub gas: U256,
+	/// The gas cost of the instruction.
+	#[serde(rename="gasCost")]
+	pub gas_cost: U256,
+	/// The call depth.
+	pub depth: usize,
+	/// The stack, bottom first.
+	pub stack: Vec<U256>,
+	/// The size of memory in bytes.
+	#[serde(rename="memSize")]
+	pub mem_size: usize,
+	/// The memory contents.
+	#[serde(skip_serializing_if="Option::is_none")]
+	pub memory: Option<Bytes>,
+	/// The storage read or written so far.
+	#[serde(skip_serializing_if="Option::is_none")]
+	pub storage: Option<BTreeMap<H256, H256>>,
+}
+
+impl From<et::StepLogEntry> for StepLogEntry {
+	fn from(e: et::StepLogEntry) -> Self {
+		StepLogEntry {
+			pc: e.pc,
+			op: e.instruction,
+			op_name: instruction_name(e.instruction).unwrap_or("").to_owned(),
+			gas: e.gas.into(),
+			gas_cost: e.gas_cost.into(),
+			depth: e.depth,
+			stack: e.stack.into_iter().map(Into::into).collect(),
+			mem_size: e.mem_size,
+			memory: e.memory.map(Into::into),
+			storage: e.storage.map(|s| s.into_iter().map(|d| (d.location.into(), d.value.into())).collect()),
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+/// Gas spent on all executions of a single opcode.
+pub struct InstructionGas {
+	/// The instruction.
+	pub op: u8,
+	/// The instruction mnemonic.
+	#[serde(rename="opName")]
+	pub op_name: String,
+	/// How many times it was executed.
+	pub count: u64,
+	/// The gas spent, not including gas spent by the calls it made.
+	pub gas: U256,
+}
+
+impl From<et::InstructionGas> for InstructionGas {
+	fn from(i: et::InstructionGas) -> Self {
+		InstructionGas {
+			op: i.instruction,
+			op_name: instruction_name(i.instruction).unwrap_or("").to_owned(),
+			count: i.count,
+			gas: i.gas.into(),
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+/// Gas spent on all executions of the instruction at a single program counter.
+pub struct PcGas {
+	/// The program counter.
+	pub pc: usize,
+	/// The instruction.
+	pub op: u8,
+	/// The instruction mnemonic.
+	#[serde(rename="opName")]
+	pub op_name: String,
+	/// How many times it was executed.
+	pub count: u64,
+	/// The gas spent, not including gas spent by the calls it made.
+	pub gas: U256,
+}
+
+impl From<et::PcGas> for PcGas {
+	fn from(p: et::PcGas) -> Self {
+		PcGas {
+			pc: p.pc,
+			op: p.instruction,
+			op_name: instruction_name(p.instruction).unwrap_or("").to_owned(),
+			count: p.count,
+			gas: p.gas.into(),
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+/// Gas spent in all call frames executing the same code.
+pub struct CodeGasProfile {
+	/// The hash of the code.
+	#[serde(rename="codeHash")]
+	pub code_hash: H256,
+	/// How many frames executed it.
+	pub calls: u64,
+	/// The gas spent by its instructions.
+	pub gas: U256,
+	/// The gas spent per program counter.
+	pub pcs: Vec<PcGas>,
+}
+
+impl From<et::CodeGasProfile> for CodeGasProfile {
+	fn from(c: et::CodeGasProfile) -> Self {
+		CodeGasProfile {
+			code_hash: c.code_hash.into(),
+			calls: c.calls,
+			gas: c.gas.into(),
+			pcs: c.pcs.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+/// Gas consumed by executed code, attributed to opcodes, program counters and code.
+pub struct GasProfile {
+	/// The gas spent by all instructions.
+	pub gas: U256,
+	/// The gas spent per opcode.
+	pub instructions: Vec<InstructionGas>,
+	/// The gas spent per code.
+	pub codes: Vec<CodeGasProfile>,
+}
+
+impl From<et::GasProfile> for GasProfile {
+	fn from(p: et::GasProfile) -> Self {
+		GasProfile {
+			gas: p.gas.into(),
+			instructions: p.instructions.into_iter().map(Into::into).collect(),
+			codes: p.codes.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+/// Whether something was read, written or both.
+pub struct Access {
+	/// It was read.
+	pub read: bool,
+	/// It was written.
+	pub written: bool,
+}
+
+impl From<state_accesses::Access> for Access {
+	fn from(a: state_accesses::Access) -> Self {
+		Access {
+			read: a.read,
+			written: a.written,
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+/// Accesses of a single account.
+pub struct AccountAccesses {
+	/// Access of the balance, nonce, code or existence.
+	pub account: Access,
+	/// Accessed storage slots.
+	pub storage: BTreeMap<H256, Access>,
+}
+
+impl From<state_accesses::AccountAccesses> for AccountAccesses {
+	fn from(a: state_accesses::AccountAccesses) -> Self {
+		AccountAccesses {
+			account: a.account.into(),
+			storage: a.storage.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+		}
+	}
+}
+
+#[derive(Debug)]
+/// Serde-friendly `StateAccesses` shadow.
+pub struct StateAccesses(BTreeMap<H160, AccountAccesses>);
+
+impl Serialize for StateAccesses {
+	fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+	where S: Serializer {
+		Serialize::serialize(&self.0, serializer)
+	}
+}
+
+impl From<state_accesses::StateAccesses> for StateAccesses {
+	fn from(a: state_accesses::StateAccesses) -> Self {
+		StateAccesses(a.accounts.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use serde_json;
@@ -581,11 +794,52 @@ mod tests {
 			trace: vec![],
 			vm_trace: None,
 			state_diff: None,
+			step_log: None,
+			gas_profile: None,
+			state_accesses: None,
 		};
 		let serialized = serde_json::to_string(&r).unwrap();
 		assert_eq!(serialized, r#"{"output":"0x60","trace":[],"vmTrace":null,"stateDiff":null}"#);
 	}
 
+	#[test]
+	fn should_serialize_step_log_entry() {
+		let e: StepLogEntry = et::StepLogEntry {
+			pc: 4,
+			instruction: 0x55,
+			gas: 100.into(),
+			gas_cost: 20.into(),
+			depth: 1,
+			stack: vec![1.into(), 0.into()],
+			mem_size: 0,
+			memory: None,
+			storage: Some(vec![et::StorageDiff { location: 0.into(), value: 1.into() }]),
+		}.into();
+		let serialized = serde_json::to_string(&e).unwrap();
+		assert_eq!(serialized, r#"{"pc":4,"op":85,"opName":"SSTORE","gas":"0x64","gasCost":"0x14","depth":1,"stack":["0x1","0x0"],"memSize":0,"storage":{"0x0000000000000000000000000000000000000000000000000000000000000000":"0x0000000000000000000000000000000000000000000000000000000000000001"}}"#);
+	}
+
+	#[test]
+	fn should_serialize_gas_profile() {
+		let mut p = et::GasProfile::default();
+		p.record_call(&1.into());
+		p.record(&1.into(), 0, 0x60, 3.into());
+		p.record(&1.into(), 2, 0x60, 3.into());
+		let p: GasProfile = p.into();
+		let serialized = serde_json::to_string(&p).unwrap();
+		assert_eq!(serialized, r#"{"gas":"0x6","instructions":[{"op":96,"opName":"PUSH1","count":2,"gas":"0x6"}],"codes":[{"codeHash":"0x0000000000000000000000000000000000000000000000000000000000000001","calls":1,"gas":"0x6","pcs":[{"pc":0,"op":96,"opName":"PUSH1","count":1,"gas":"0x3"},{"pc":2,"op":96,"opName":"PUSH1","count":1,"gas":"0x3"}]}]}"#);
+	}
+
+	#[test]
+	fn should_serialize_state_accesses() {
+		let mut a = state_accesses::StateAccesses::default();
+		a.read_account(&1.into());
+		a.write_storage(&1.into(), &2.into());
+		let a: StateAccesses = a.into();
+		let serialized = serde_json::to_string(&a).unwrap();
+		assert_eq!(serialized, r#"{"0x0000000000000000000000000000000000000001":{"account":{"read":true,"written":false},"storage":{"0x0000000000000000000000000000000000000000000000000000000000000002":{"read":false,"written":true}}}}"#);
+	}
+
 	#[test]
 	fn test_trace_call_serialize() {
 		let t = LocalizedTrace {
@@ -0,0 +1,444 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+///
+/// Ancient blocks downloader. Fills the gap left in the chain after a snapshot restoration
+/// by downloading blocks backwards from the first restored block down to the best ancient block.
+///
+
+use util::*;
+use rlp::*;
+use ethcore::header::{BlockNumber, Header as BlockHeader};
+use ethcore::client::BlockImportError;
+use ethcore::block::Block;
+use ethcore::error::ImportError;
+use sync_io::SyncIo;
+use blocks::{HeaderId, empty_body};
+use block_sync::{BlockRequest, BlockDownloaderImportError};
+
+const MAX_HEADERS_TO_REQUEST: u64 = 128;
+const MAX_BODIES_TO_REQUEST: usize = 128;
+const MAX_RECEIPTS_TO_REQUEST: usize = 128;
+/// Maximum number of downloaded blocks waiting for bodies, receipts or import.
+const MAX_PENDING_BLOCKS: usize = 2048;
+
+/// Downloaded ancient block data.
+struct AncientBlock {
+	number: BlockNumber,
+	header: Bytes,
+	body: Option<Bytes>,
+	receipts: Option<Bytes>,
+}
+
+impl HeapSizeOf for AncientBlock {
+	fn heap_size_of_children(&self) -> usize {
+		self.header.heap_size_of_children() + self.body.heap_size_of_children() + self.receipts.heap_size_of_children()
+	}
+}
+
+/// Downloads blocks in descending order. Each header is checked to be the parent
+/// of the previously downloaded one, starting from the parent of the first block in the chain,
+/// so that every downloaded block is known to belong to the canonical chain.
+/// Bodies and receipts are matched to the headers by their trie roots.
+pub struct AncientDownloader {
+	/// Hash of the next header to download. This is the parent of the lowest downloaded header.
+	next_hash: H256,
+	/// Number of the next header to download.
+	next_number: BlockNumber,
+	/// Hash of the best ancient block, where the download stops.
+	target_hash: H256,
+	/// Number of the best ancient block.
+	target_number: BlockNumber,
+	/// Downloaded blocks.
+	blocks: HashMap<H256, AncientBlock>,
+	/// Hashes of downloaded blocks in descending order.
+	order: VecDeque<H256>,
+	/// Used to map body to header.
+	header_ids: HashMap<HeaderId, H256>,
+	/// Used to map receipts root to header.
+	receipt_ids: HashMap<H256, H256>,
+	/// Start of the headers request in progress.
+	downloading_headers: Option<H256>,
+	/// Set of block bodies being downloaded identified by block hash.
+	downloading_bodies: HashSet<H256>,
+	/// Set of block receipts being downloaded identified by block hash.
+	downloading_receipts: HashSet<H256>,
+	/// Lowest imported block number.
+	last_imported_block: BlockNumber,
+	/// Download is complete.
+	complete: bool,
+}
+
+impl AncientDownloader {
+	/// Create a new downloader filling the gap between the best ancient block and the first block.
+	pub fn new(first_parent_hash: &H256, first_number: BlockNumber, target_hash: &H256, target_number: BlockNumber) -> AncientDownloader {
+		AncientDownloader {
+			next_hash: first_parent_hash.clone(),
+			next_number: first_number - 1,
+			target_hash: target_hash.clone(),
+			target_number: target_number,
+			blocks: HashMap::new(),
+			order: VecDeque::new(),
+			header_ids: HashMap::new(),
+			receipt_ids: HashMap::new(),
+			downloading_headers: None,
+			downloading_bodies: HashSet::new(),
+			downloading_receipts: HashSet::new(),
+			last_imported_block: first_number,
+			complete: first_number <= target_number + 1,
+		}
+	}
+
+	/// Check if download is complete
+	pub fn is_complete(&self) -> bool {
+		self.complete
+	}
+
+	/// Unmark header as being downloaded.
+	pub fn clear_header_download(&mut self, hash: &H256) {
+		if self.downloading_headers.as_ref() == Some(hash) {
+			self.downloading_headers = None;
+		}
+	}
+
+	/// Unmark block body as being downloaded.
+	pub fn clear_body_download(&mut self, hashes: &[H256]) {
+		for h in hashes {
+			self.downloading_bodies.remove(h);
+		}
+	}
+
+	/// Unmark block receipt as being downloaded.
+	pub fn clear_receipt_download(&mut self, hashes: &[H256]) {
+		for h in hashes {
+			self.downloading_receipts.remove(h);
+		}
+	}
+
+	/// Returns used heap memory size.
+	pub fn heap_size(&self) -> usize {
+		self.blocks.heap_size_of_children()
+			+ self.order.heap_size_of_children()
+			+ self.header_ids.heap_size_of_children()
+			+ self.receipt_ids.heap_size_of_children()
+			+ self.downloading_bodies.heap_size_of_children()
+			+ self.downloading_receipts.heap_size_of_children()
+	}
+
+	/// Returns lowest imported block number.
+	pub fn last_imported_block_number(&self) -> BlockNumber {
+		self.last_imported_block
+	}
+
+	/// Add new block headers. Headers are expected in descending order starting with the requested one.
+	pub fn import_headers(&mut self, r: &UntrustedRlp, expected_hash: Option<H256>) -> Result<(), BlockDownloaderImportError> {
+		let item_count = r.item_count();
+		if item_count == 0 {
+			return Err(BlockDownloaderImportError::Useless);
+		}
+		if self.complete || expected_hash != Some(self.next_hash) {
+			trace!(target: "sync", "Ignored unexpected ancient block headers");
+			return Ok(());
+		}
+
+		for i in 0..item_count {
+			if self.next_number <= self.target_number {
+				break;
+			}
+			let hdr = try!(r.at(i).map_err(|e| {
+				trace!(target: "sync", "Error decoding block header RLP: {:?}", e);
+				BlockDownloaderImportError::Invalid
+			}));
+			let info: BlockHeader = try!(hdr.as_val().map_err(|e| {
+				trace!(target: "sync", "Error decoding block header RLP: {:?}", e);
+				BlockDownloaderImportError::Invalid
+			}));
+			let hash = info.hash();
+			if hash != self.next_hash || info.number() != self.next_number {
+				trace!(target: "sync", "Ancient block header {} ({}) does not match expected parent {} ({})", info.number(), hash, self.next_number, self.next_hash);
+				return Err(BlockDownloaderImportError::Invalid);
+			}
+
+			let header_id = HeaderId {
+				transactions_root: info.transactions_root().clone(),
+				uncles: info.uncles_hash().clone(),
+			};
+			let body = if header_id.is_empty() {
+				Some(empty_body())
+			} else {
+				self.header_ids.insert(header_id, hash.clone());
+				None
+			};
+			let receipts = if info.receipts_root() == &sha3::SHA3_NULL_RLP {
+				Some(RlpStream::new_list(0).out())
+			} else {
+				self.receipt_ids.insert(info.receipts_root().clone(), hash.clone());
+				None
+			};
+
+			self.blocks.insert(hash.clone(), AncientBlock {
+				number: info.number(),
+				header: hdr.as_raw().to_vec(),
+				body: body,
+				receipts: receipts,
+			});
+			self.order.push_back(hash);
+			self.next_hash = info.parent_hash().clone();
+			self.next_number -= 1;
+		}
+		trace!(target: "sync", "Downloaded ancient headers down to {} ({})", self.next_number + 1, self.next_hash);
+
+		if self.next_number == self.target_number && self.next_hash != self.target_hash {
+			warn!(target: "sync", "Ancient blocks do not connect to block {} ({}), expected parent {}. Aborting ancient block download.", self.target_number, self.target_hash, self.next_hash);
+			self.complete = true;
+		}
+		Ok(())
+	}
+
+	/// Called by peer once it has new block bodies
+	pub fn import_bodies(&mut self, r: &UntrustedRlp) -> Result<(), BlockDownloaderImportError> {
+		let item_count = r.item_count();
+		if item_count == 0 {
+			return Err(BlockDownloaderImportError::Useless);
+		}
+		let mut inserted = 0;
+		for i in 0..item_count {
+			let body = try!(r.at(i).map_err(|e| {
+				trace!(target: "sync", "Error decoding block bodies RLP: {:?}", e);
+				BlockDownloaderImportError::Invalid
+			}));
+			let header_id = try!(HeaderId::from_body(&body).map_err(|e| {
+				trace!(target: "sync", "Error decoding block bodies RLP: {:?}", e);
+				BlockDownloaderImportError::Invalid
+			}));
+			if let Some(h) = self.header_ids.remove(&header_id) {
+				self.downloading_bodies.remove(&h);
+				if let Some(ref mut block) = self.blocks.get_mut(&h) {
+					block.body = Some(body.as_raw().to_vec());
+					inserted += 1;
+				}
+			}
+		}
+		if inserted != item_count {
+			trace!(target: "sync", "Deactivating peer for giving invalid ancient block bodies");
+			return Err(BlockDownloaderImportError::Invalid);
+		}
+		Ok(())
+	}
+
+	/// Called by peer once it has new block receipts
+	pub fn import_receipts(&mut self, r: &UntrustedRlp) -> Result<(), BlockDownloaderImportError> {
+		let item_count = r.item_count();
+		if item_count == 0 {
+			return Err(BlockDownloaderImportError::Useless);
+		}
+		let mut inserted = 0;
+		for i in 0..item_count {
+			let receipts = try!(r.at(i).map_err(|e| {
+				trace!(target: "sync", "Error decoding block receipts RLP: {:?}", e);
+				BlockDownloaderImportError::Invalid
+			}));
+			let receipts_root = ordered_trie_root(receipts.iter().map(|r| r.as_raw().to_vec()));
+			if let Some(h) = self.receipt_ids.remove(&receipts_root) {
+				self.downloading_receipts.remove(&h);
+				if let Some(ref mut block) = self.blocks.get_mut(&h) {
+					block.receipts = Some(receipts.as_raw().to_vec());
+					inserted += 1;
+				}
+			}
+		}
+		if inserted != item_count {
+			trace!(target: "sync", "Deactivating peer for giving invalid ancient block receipts");
+			return Err(BlockDownloaderImportError::Invalid);
+		}
+		Ok(())
+	}
+
+	/// Find some headers or blocks to download for a peer.
+	pub fn request_blocks(&mut self) -> Option<BlockRequest> {
+		if self.complete {
+			return None;
+		}
+
+		let needed_bodies: Vec<H256> = self.order.iter()
+			.filter(|h| self.blocks[*h].body.is_none() && !self.downloading_bodies.contains(*h))
+			.take(MAX_BODIES_TO_REQUEST)
+			.cloned()
+			.collect();
+		if !needed_bodies.is_empty() {
+			self.downloading_bodies.extend(needed_bodies.iter().cloned());
+			return Some(BlockRequest::Bodies {
+				hashes: needed_bodies,
+			});
+		}
+
+		let needed_receipts: Vec<H256> = self.order.iter()
+			.filter(|h| self.blocks[*h].receipts.is_none() && !self.downloading_receipts.contains(*h))
+			.take(MAX_RECEIPTS_TO_REQUEST)
+			.cloned()
+			.collect();
+		if !needed_receipts.is_empty() {
+			self.downloading_receipts.extend(needed_receipts.iter().cloned());
+			return Some(BlockRequest::Receipts {
+				hashes: needed_receipts,
+			});
+		}
+
+		if self.downloading_headers.is_none() && self.next_number > self.target_number && self.blocks.len() < MAX_PENDING_BLOCKS {
+			self.downloading_headers = Some(self.next_hash.clone());
+			return Some(BlockRequest::Headers {
+				start: self.next_hash.clone(),
+				count: min(MAX_HEADERS_TO_REQUEST, self.next_number - self.target_number),
+				skip: 0,
+				reverse: true,
+			});
+		}
+		None
+	}
+
+	/// Imports downloaded blocks into the blockchain, highest first.
+	pub fn collect_blocks(&mut self, io: &mut SyncIo) -> Result<(), BlockDownloaderImportError> {
+		let mut imported = 0;
+		while let Some(hash) = self.order.front().cloned() {
+			if self.blocks.get(&hash).map_or(true, |b| b.body.is_none() || b.receipts.is_none()) {
+				break;
+			}
+			self.order.pop_front();
+			let block = self.blocks.remove(&hash).expect("hashes in `order` are always in `blocks`; qed");
+
+			let mut block_rlp = RlpStream::new_list(3);
+			block_rlp.append_raw(&block.header, 1);
+			{
+				let body = Rlp::new(block.body.as_ref().expect("only complete blocks are collected; qed"));
+				block_rlp.append_raw(body.at(0).as_raw(), 1);
+				block_rlp.append_raw(body.at(1).as_raw(), 1);
+			}
+			let block_bytes = block_rlp.out();
+
+			// Perform basic block verification
+			if !Block::is_good(&block_bytes) {
+				debug!(target: "sync", "Bad ancient block rlp {:?}", hash);
+				return Err(BlockDownloaderImportError::Invalid);
+			}
+
+			let receipts = block.receipts.expect("only complete blocks are collected; qed");
+			match io.chain().import_block_with_receipts(block_bytes, receipts) {
+				Ok(_) | Err(BlockImportError::Import(ImportError::AlreadyInChain)) => {
+					self.last_imported_block = block.number;
+					imported += 1;
+				},
+				Err(e) => {
+					debug!(target: "sync", "Bad ancient block {:?} : {:?}", hash, e);
+					return Err(BlockDownloaderImportError::Invalid);
+				}
+			}
+		}
+		trace!(target: "sync", "Imported {} ancient blocks, last imported {}", imported, self.last_imported_block);
+
+		if self.last_imported_block == self.target_number + 1 {
+			trace!(target: "sync", "Ancient block download complete");
+			self.complete = true;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::AncientDownloader;
+	use ethcore::client::{TestBlockChainClient, EachBlockWith, BlockID, BlockChainClient};
+	use ethcore::views::HeaderView;
+	use block_sync::{BlockRequest, BlockDownloaderImportError};
+	use util::*;
+	use rlp::*;
+
+	fn headers(client: &TestBlockChainClient, from: u64, to: u64) -> Bytes {
+		let mut rlp = RlpStream::new_list((from - to + 1) as usize);
+		for i in (to..from + 1).rev() {
+			rlp.append_raw(&(client as &BlockChainClient).block_header(BlockID::Number(i)).unwrap(), 1);
+		}
+		rlp.out()
+	}
+
+	#[test]
+	fn downloads_headers_backwards() {
+		let client = TestBlockChainClient::new();
+		client.add_blocks(300, EachBlockWith::Nothing);
+		let hash = |n| (&client as &BlockChainClient).block_hash(BlockID::Number(n)).unwrap();
+		let mut downloader = AncientDownloader::new(&hash(199), 200, &hash(0), 0);
+
+		match downloader.request_blocks() {
+			Some(BlockRequest::Headers { start, count, skip, reverse }) => {
+				assert_eq!(start, hash(199));
+				assert_eq!(count, 128);
+				assert_eq!(skip, 0);
+				assert!(reverse);
+			},
+			_ => panic!("Expected headers request"),
+		}
+		// only one headers request at a time.
+		assert!(downloader.request_blocks().is_none());
+
+		let headers = headers(&client, 199, 72);
+		downloader.import_headers(&UntrustedRlp::new(&headers), Some(hash(199))).unwrap();
+		assert_eq!(downloader.next_number, 71);
+		assert_eq!(downloader.next_hash, hash(71));
+		assert_eq!(downloader.order.len(), 128);
+		assert_eq!(HeaderView::new(&downloader.blocks[&hash(72)].header).sha3(), hash(72));
+
+		downloader.clear_header_download(&hash(199));
+		match downloader.request_blocks() {
+			Some(BlockRequest::Headers { start, count, .. }) => {
+				assert_eq!(start, hash(71));
+				assert_eq!(count, 71);
+			},
+			_ => panic!("Expected headers request"),
+		}
+	}
+
+	#[test]
+	fn rejects_unlinked_headers() {
+		let client = TestBlockChainClient::new();
+		client.add_blocks(300, EachBlockWith::Nothing);
+		let hash = |n| (&client as &BlockChainClient).block_hash(BlockID::Number(n)).unwrap();
+		let mut downloader = AncientDownloader::new(&hash(199), 200, &hash(0), 0);
+		downloader.request_blocks();
+
+		// headers in ascending order do not link.
+		let mut rlp = RlpStream::new_list(2);
+		rlp.append_raw(&(&client as &BlockChainClient).block_header(BlockID::Number(199)).unwrap(), 1);
+		rlp.append_raw(&(&client as &BlockChainClient).block_header(BlockID::Number(200)).unwrap(), 1);
+		let headers = rlp.out();
+		assert_eq!(downloader.import_headers(&UntrustedRlp::new(&headers), Some(hash(199))), Err(BlockDownloaderImportError::Invalid));
+		assert_eq!(downloader.order.len(), 1);
+	}
+
+	#[test]
+	fn completes_at_target() {
+		let client = TestBlockChainClient::new();
+		client.add_blocks(10, EachBlockWith::Nothing);
+		let hash = |n| (&client as &BlockChainClient).block_hash(BlockID::Number(n)).unwrap();
+		let mut downloader = AncientDownloader::new(&hash(4), 5, &hash(0), 0);
+		downloader.request_blocks();
+		let headers = headers(&client, 4, 1);
+		downloader.import_headers(&UntrustedRlp::new(&headers), Some(hash(4))).unwrap();
+		assert!(!downloader.is_complete());
+		assert_eq!(downloader.next_hash, hash(0));
+		downloader.clear_header_download(&hash(4));
+		// empty blocks need neither bodies nor receipts and there is nothing left to download.
+		assert!(downloader.request_blocks().is_none());
+	}
+}
@@ -20,8 +20,9 @@ use std::io;
 use util::Bytes;
 use network::{NetworkProtocolHandler, NetworkService, NetworkContext, PeerId, ProtocolId,
 	NetworkConfiguration as BasicNetworkConfiguration, NonReservedPeerMode, NetworkError,
-	AllowIP as NetworkAllowIP};
-use util::{U256, H256};
+	AllowIP as NetworkAllowIP, NatMethod as NetworkNatMethod, NatProtocol, SessionStats, TrafficCounters};
+use util::{U256, H256, Uint, Hashable};
+use rlp::{RlpStream, Stream};
 use io::{TimerToken};
 use ethcore::client::{BlockChainClient, ChainNotify};
 use ethcore::snapshot::SnapshotService;
@@ -32,7 +33,8 @@ use std::net::{SocketAddr, AddrParseError};
 use ipc::{BinaryConvertable, BinaryConvertError, IpcConfig};
 use std::str::FromStr;
 use parking_lot::RwLock;
-use chain::{ETH_PACKET_COUNT, SNAPSHOT_SYNC_PACKET_COUNT};
+use chain::{ETH_PACKET_COUNT, WARP_SYNC_PROTOCOL_VERSIONS};
+use light_protocol::{LightProtocol, FlowParams, LIGHT_PROTOCOL_ID, LIGHT_PROTOCOL_VERSION, LIGHT_PACKET_COUNT};
 
 pub const WARP_SYNC_PROTOCOL_ID: ProtocolId = *b"bam";
 
@@ -45,10 +47,16 @@ pub struct SyncConfig {
 	pub network_id: U256,
 	/// Main "eth" subprotocol name.
 	pub subprotocol_name: [u8; 3],
-	/// Fork block to check
-	pub fork_block: Option<(BlockNumber, H256)>,
+	/// Fork blocks to check
+	pub fork_blocks: ForkCheckpoints,
 	/// Enable snapshot sync
 	pub warp_sync: bool,
+	/// Serve light clients over the light protocol
+	pub serve_light: bool,
+	/// Only sync with reserved peers, using large block requests
+	pub reserved_peers_only: bool,
+	/// Transaction propagation policy
+	pub tx_propagation: TransactionPropagation,
 }
 
 impl Default for SyncConfig {
@@ -57,8 +65,87 @@ impl Default for SyncConfig {
 			max_download_ahead_blocks: 20000,
 			network_id: U256::from(1),
 			subprotocol_name: *b"eth",
-			fork_block: None,
+			fork_blocks: ForkCheckpoints::default(),
 			warp_sync: true,
+			serve_light: false,
+			reserved_peers_only: false,
+			tx_propagation: TransactionPropagation::default(),
+		}
+	}
+}
+
+/// Maximum number of fork blocks that can be checked
+pub const MAX_FORK_CHECKPOINTS: usize = 8;
+
+/// A list of blocks peers must have in their chain to be synced with.
+/// Has fixed capacity so that `SyncConfig` can be passed over IPC as is.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ForkCheckpoints {
+	checkpoints: [(BlockNumber, H256); MAX_FORK_CHECKPOINTS],
+	len: usize,
+}
+
+impl ForkCheckpoints {
+	/// Adds a block to check, keeping the list sorted by block number. Returns `false` if the list is full.
+	pub fn push(&mut self, number: BlockNumber, hash: H256) -> bool {
+		if self.as_slice().contains(&(number, hash)) {
+			return true;
+		}
+		if self.len == MAX_FORK_CHECKPOINTS {
+			return false;
+		}
+		let pos = self.as_slice().iter().position(|c| *c > (number, hash)).unwrap_or(self.len);
+		for i in (pos..self.len).rev() {
+			self.checkpoints[i + 1] = self.checkpoints[i];
+		}
+		self.checkpoints[pos] = (number, hash);
+		self.len += 1;
+		true
+	}
+
+	/// Returns the blocks to check in ascending block number order.
+	pub fn as_slice(&self) -> &[(BlockNumber, H256)] {
+		&self.checkpoints[..self.len]
+	}
+
+	/// Returns true if there is nothing to check.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+}
+
+/// Identifies the chain in discovery node records. Derived from the genesis hash and the fork
+/// checkpoints, so that chains sharing a network id, or split at a fork, are told apart.
+pub fn chain_id(genesis_hash: &H256, forks: &ForkCheckpoints) -> u64 {
+	let forks = forks.as_slice();
+	let mut stream = RlpStream::new_list(forks.len() + 1);
+	stream.append(genesis_hash);
+	for &(ref number, ref hash) in forks {
+		stream.begin_list(2).append(number).append(hash);
+	}
+	U256::from(stream.out().sha3()).low_u64()
+}
+
+/// Controls how pending transactions are relayed to peers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransactionPropagation {
+	/// Relay transactions submitted to this node
+	pub relay_local: bool,
+	/// Relay transactions to reserved peers only
+	pub reserved_only: bool,
+	/// Max number of transactions sent to a single peer per second
+	pub max_per_peer_rate: Option<u32>,
+	/// Announce transaction hashes instead of bodies to peers that support it and let them request what they miss
+	pub announce_hashes: bool,
+}
+
+impl Default for TransactionPropagation {
+	fn default() -> TransactionPropagation {
+		TransactionPropagation {
+			relay_local: true,
+			reserved_only: false,
+			max_per_peer_rate: None,
+			announce_hashes: false,
 		}
 	}
 }
@@ -73,6 +160,9 @@ pub trait SyncProvider: Send + Sync {
 
 	/// Get peers information
 	fn peers(&self) -> Vec<PeerInfo>;
+
+	/// Get sync performance metrics
+	fn stats(&self) -> SyncStats;
 }
 
 /// Peer connection information
@@ -94,6 +184,116 @@ pub struct PeerInfo {
 	pub eth_head: H256,
 	/// Peer total difficulty if known
 	pub eth_difficulty: Option<U256>,
+	/// Traffic exchanged with the peer
+	pub traffic: PeerTrafficInfo,
+	/// Traffic exchanged with the peer for each subprotocol
+	pub protocol_traffic: Vec<PeerProtocolTrafficInfo>,
+}
+
+/// Peer traffic counters
+#[derive(Debug, Default, Clone, PartialEq, Binary)]
+pub struct PeerTrafficInfo {
+	/// Packets sent
+	pub packets_sent: u64,
+	/// Packet bytes sent
+	pub bytes_sent: u64,
+	/// Packets received
+	pub packets_received: u64,
+	/// Packet bytes received
+	pub bytes_received: u64,
+}
+
+impl<'a> From<&'a TrafficCounters> for PeerTrafficInfo {
+	fn from(c: &'a TrafficCounters) -> PeerTrafficInfo {
+		PeerTrafficInfo {
+			packets_sent: c.packets_sent,
+			bytes_sent: c.bytes_sent,
+			packets_received: c.packets_received,
+			bytes_received: c.bytes_received,
+		}
+	}
+}
+
+/// Peer traffic counters for a single subprotocol
+#[derive(Debug, Clone, PartialEq, Binary)]
+pub struct PeerProtocolTrafficInfo {
+	/// Subprotocol name
+	pub protocol: String,
+	/// Traffic counters
+	pub traffic: PeerTrafficInfo,
+}
+
+impl PeerProtocolTrafficInfo {
+	/// Collect per-protocol traffic of a session, ordered by protocol name.
+	pub fn from_session(stats: &SessionStats) -> Vec<PeerProtocolTrafficInfo> {
+		let mut traffic: Vec<_> = stats.protocols.iter().map(|(p, c)| PeerProtocolTrafficInfo {
+			protocol: String::from_utf8_lossy(&p[..]).into_owned(),
+			traffic: c.into(),
+		}).collect();
+		traffic.sort_by(|a, b| a.protocol.cmp(&b.protocol));
+		traffic
+	}
+}
+
+/// Sync performance metrics
+#[derive(Debug, Default, Binary)]
+pub struct SyncStats {
+	/// Block headers downloaded per second
+	pub headers_per_sec: f64,
+	/// Block bodies downloaded per second
+	pub bodies_per_sec: f64,
+	/// Block receipts downloaded per second
+	pub receipts_per_sec: f64,
+	/// Blocks verified and imported per second
+	pub blocks_per_sec: f64,
+	/// Total number of block headers downloaded
+	pub headers_total: u64,
+	/// Total number of block bodies downloaded
+	pub bodies_total: u64,
+	/// Total number of block receipts downloaded
+	pub receipts_total: u64,
+	/// Total number of blocks imported
+	pub blocks_total: u64,
+	/// Blocks waiting for verification
+	pub queue_unverified: usize,
+	/// Blocks being verified
+	pub queue_verifying: usize,
+	/// Verified blocks waiting for import
+	pub queue_verified: usize,
+	/// Request statistics of connected and recently disconnected peers
+	pub peers: Vec<PeerSyncStats>,
+	/// Recently imported blocks and peers that supplied them
+	pub recent_blocks: Vec<BlockSource>,
+}
+
+/// Request statistics of a single peer
+#[derive(Debug, Clone, PartialEq, Binary)]
+pub struct PeerSyncStats {
+	/// Public node id
+	pub id: Option<String>,
+	/// Peer is still connected
+	pub connected: bool,
+	/// Number of responses received
+	pub responses: u64,
+	/// Number of requests that timed out
+	pub timeouts: u64,
+	/// Average response time in milliseconds
+	pub avg_latency_ms: u64,
+	/// Last response time in milliseconds
+	pub last_latency_ms: u64,
+	/// Number of imported blocks the peer supplied
+	pub blocks_supplied: u64,
+}
+
+/// Imported block and the peer that supplied it
+#[derive(Debug, Clone, PartialEq, Binary)]
+pub struct BlockSource {
+	/// Block hash
+	pub hash: H256,
+	/// Block number
+	pub number: BlockNumber,
+	/// Public node id of the peer that sent the block header
+	pub peer: Option<String>,
 }
 
 /// Ethereum network protocol handler
@@ -106,23 +306,27 @@ pub struct EthSync {
 	subprotocol_name: [u8; 3],
 	/// Configuration
 	config: NetworkConfiguration,
+	/// Light protocol handler, if serving light clients
+	light_protocol: Option<Arc<LightProtocol<BlockChainClient>>>,
 }
 
 impl EthSync {
 	/// Creates and register protocol with the network service
 	pub fn new(config: SyncConfig, chain: Arc<BlockChainClient>, snapshot_service: Arc<SnapshotService>, network_config: NetworkConfiguration) -> Result<Arc<EthSync>, NetworkError> {
-		let chain_sync = ChainSync::new(config, &*chain);
-		let service = try!(NetworkService::new(try!(network_config.clone().into_basic())));
+		let mut basic_config = try!(network_config.clone().into_basic());
+		// advertise our chain in discovery so that peers on other chains are not dialled
+		basic_config.chain_id = Some(chain_id(&chain.chain_info().genesis_hash, &config.fork_blocks));
+		let service = try!(NetworkService::new(basic_config));
+		let light_protocol = match config.serve_light {
+			true => Some(Arc::new(LightProtocol::new(chain.clone(), config.network_id, Some(FlowParams::default())))),
+			false => None,
+		};
 		let sync = Arc::new(EthSync{
 			network: service,
-			handler: Arc::new(SyncProtocolHandler {
-				sync: RwLock::new(chain_sync),
-				chain: chain,
-				snapshot_service: snapshot_service,
-				overlay: RwLock::new(HashMap::new()),
-			}),
+			handler: Arc::new(SyncProtocolHandler::new(config, chain, snapshot_service)),
 			subprotocol_name: config.subprotocol_name,
 			config: network_config,
+			light_protocol: light_protocol,
 		});
 
 		Ok(sync)
@@ -143,9 +347,18 @@ impl SyncProvider for EthSync {
 			self.handler.sync.write().peers(&sync_io)
 		}).unwrap_or(Vec::new())
 	}
+
+	/// Get sync metrics
+	fn stats(&self) -> SyncStats {
+		self.network.with_context_eval(self.subprotocol_name, |context| {
+			let sync_io = NetSyncIo::new(context, &*self.handler.chain, &*self.handler.snapshot_service, &self.handler.overlay);
+			self.handler.sync.read().stats(&sync_io)
+		}).unwrap_or_else(Default::default)
+	}
 }
 
-struct SyncProtocolHandler {
+/// Drives `ChainSync` for both the main and the warp sync subprotocols.
+pub struct SyncProtocolHandler {
 	/// Shared blockchain client.
 	chain: Arc<BlockChainClient>,
 	/// Shared snapshot service.
@@ -156,6 +369,24 @@ struct SyncProtocolHandler {
 	overlay: RwLock<HashMap<BlockNumber, Bytes>>,
 }
 
+impl SyncProtocolHandler {
+	/// Creates a handler syncing the given client.
+	pub fn new(config: SyncConfig, chain: Arc<BlockChainClient>, snapshot_service: Arc<SnapshotService>) -> SyncProtocolHandler {
+		SyncProtocolHandler {
+			sync: RwLock::new(ChainSync::new(config, &*chain)),
+			chain: chain,
+			snapshot_service: snapshot_service,
+			overlay: RwLock::new(HashMap::new()),
+		}
+	}
+
+	/// Notifies the sync about blocks imported into the client.
+	pub fn chain_new_blocks(&self, io: &NetworkContext, imported: &[H256], invalid: &[H256], enacted: &[H256], retracted: &[H256], sealed: &[H256]) {
+		let mut sync_io = NetSyncIo::new(io, &*self.chain, &*self.snapshot_service, &self.overlay);
+		self.sync.write().chain_new_blocks(&mut sync_io, imported, invalid, enacted, retracted, sealed);
+	}
+}
+
 impl NetworkProtocolHandler for SyncProtocolHandler {
 	fn initialize(&self, io: &NetworkContext) {
 		if io.subprotocol_name() != WARP_SYNC_PROTOCOL_ID {
@@ -199,15 +430,13 @@ impl ChainNotify for EthSync {
 		_duration: u64)
 	{
 		self.network.with_context(self.subprotocol_name, |context| {
-			let mut sync_io = NetSyncIo::new(context, &*self.handler.chain, &*self.handler.snapshot_service, &self.handler.overlay);
-			self.handler.sync.write().chain_new_blocks(
-				&mut sync_io,
-				&imported,
-				&invalid,
-				&enacted,
-				&retracted,
-				&sealed);
+			self.handler.chain_new_blocks(context, &imported, &invalid, &enacted, &retracted, &sealed);
 		});
+		if let Some(ref light_protocol) = self.light_protocol {
+			if !enacted.is_empty() {
+				self.network.with_context(LIGHT_PROTOCOL_ID, |context| light_protocol.announce(context));
+			}
+		}
 	}
 
 	fn start(&self) {
@@ -219,8 +448,13 @@ impl ChainNotify for EthSync {
 		self.network.register_protocol(self.handler.clone(), self.subprotocol_name, ETH_PACKET_COUNT, &[62u8, 63u8])
 			.unwrap_or_else(|e| warn!("Error registering ethereum protocol: {:?}", e));
 		// register the warp sync subprotocol
-		self.network.register_protocol(self.handler.clone(), WARP_SYNC_PROTOCOL_ID, SNAPSHOT_SYNC_PACKET_COUNT, &[1u8])
+		self.network.register_protocol_versions(self.handler.clone(), WARP_SYNC_PROTOCOL_ID, &WARP_SYNC_PROTOCOL_VERSIONS)
 			.unwrap_or_else(|e| warn!("Error registering snapshot sync protocol: {:?}", e));
+		// register the light client subprotocol
+		if let Some(ref light_protocol) = self.light_protocol {
+			self.network.register_protocol(light_protocol.clone(), LIGHT_PROTOCOL_ID, LIGHT_PACKET_COUNT, &[LIGHT_PROTOCOL_VERSION])
+				.unwrap_or_else(|e| warn!("Error registering light protocol: {:?}", e));
+		}
 	}
 
 	fn stop(&self) {
@@ -247,6 +481,20 @@ pub trait ManageNetwork : Send + Sync {
 	fn stop_network(&self);
 	/// Query the current configuration of the network
 	fn network_config(&self) -> NetworkConfiguration;
+	/// Returns peers banned because of low reputation
+	fn banned_peers(&self) -> Vec<BannedPeerInfo>;
+	/// Lift the ban for a peer
+	fn unban_peer(&self, peer: String) -> Result<(), String>;
+	/// Returns ids of the nodes allowed to connect in permissioned mode
+	fn allowed_peers(&self) -> Result<Vec<String>, String>;
+	/// Add a node to the allowlist
+	fn add_allowed_peer(&self, peer: String) -> Result<(), String>;
+	/// Remove a node from the allowlist
+	fn remove_allowed_peer(&self, peer: String) -> Result<(), String>;
+	/// Read the allowlist file again
+	fn reload_allowed_peers(&self) -> Result<(), String>;
+	/// Returns NAT traversal status
+	fn nat_status(&self) -> NatStatusInfo;
 }
 
 
@@ -283,6 +531,103 @@ impl ManageNetwork for EthSync {
 	fn network_config(&self) -> NetworkConfiguration {
 		NetworkConfiguration::from(self.network.config().clone())
 	}
+
+	fn banned_peers(&self) -> Vec<BannedPeerInfo> {
+		self.network.banned_nodes().into_iter().map(|n| BannedPeerInfo {
+			id: n.id.hex(),
+			url: n.url,
+			reputation: n.score,
+			bans: n.bans,
+			banned_until: n.banned_until,
+		}).collect()
+	}
+
+	fn unban_peer(&self, peer: String) -> Result<(), String> {
+		self.network.unban_node(&peer).map_err(|e| format!("{:?}", e))
+	}
+
+	fn allowed_peers(&self) -> Result<Vec<String>, String> {
+		self.network.allowed_nodes()
+			.map(|nodes| nodes.into_iter().map(|id| id.hex()).collect())
+			.map_err(|e| format!("{:?}", e))
+	}
+
+	fn add_allowed_peer(&self, peer: String) -> Result<(), String> {
+		self.network.add_allowed_node(&peer).map_err(|e| format!("{:?}", e))
+	}
+
+	fn remove_allowed_peer(&self, peer: String) -> Result<(), String> {
+		self.network.remove_allowed_node(&peer).map_err(|e| format!("{:?}", e))
+	}
+
+	fn reload_allowed_peers(&self) -> Result<(), String> {
+		self.network.reload_allowlist().map_err(|e| format!("{:?}", e))
+	}
+
+	fn nat_status(&self) -> NatStatusInfo {
+		let status = self.network.nat_status();
+		NatStatusInfo {
+			enabled: status.enabled,
+			protocol: status.protocol.map(|p| match p {
+				NatProtocol::Upnp => "upnp",
+				NatProtocol::NatPmp => "natpmp",
+				NatProtocol::Pcp => "pcp",
+			}.to_owned()),
+			public_address: status.public_address.map(|a| format!("{}", a)),
+			public_udp_port: status.public_udp_port,
+			lifetime: status.lifetime,
+			renewed_at: status.renewed_at,
+			address_changes: status.address_changes,
+			failures: status.failures,
+		}
+	}
+}
+
+/// NAT traversal status
+#[derive(Debug, Clone, PartialEq, Binary)]
+pub struct NatStatusInfo {
+	/// Port mapping on the gateway is enabled
+	pub enabled: bool,
+	/// Protocol of the active port mapping: "upnp", "natpmp" or "pcp"
+	pub protocol: Option<String>,
+	/// Address advertised to other nodes
+	pub public_address: Option<String>,
+	/// UDP port advertised to other nodes
+	pub public_udp_port: Option<u16>,
+	/// Lifetime of the active port mapping in seconds
+	pub lifetime: u32,
+	/// Unix time the port mapping was last created or renewed
+	pub renewed_at: u64,
+	/// Number of times the external address has changed
+	pub address_changes: u32,
+	/// Number of failed mapping attempts
+	pub failures: u32,
+}
+
+/// Information on a peer banned because of low reputation
+#[derive(Debug, Clone, PartialEq, Binary)]
+pub struct BannedPeerInfo {
+	/// Public node id
+	pub id: String,
+	/// Node address
+	pub url: String,
+	/// Reputation score
+	pub reputation: i32,
+	/// Number of times the peer has been banned
+	pub bans: u32,
+	/// Unix time when the ban expires
+	pub banned_until: u64,
+}
+
+/// NAT traversal method
+#[derive(Binary, Clone, Debug, PartialEq, Eq)]
+pub enum NatMethod {
+	/// Try UPnP, then NAT-PMP and PCP
+	Any,
+	/// UPnP only
+	Upnp,
+	/// NAT-PMP or PCP only
+	NatPmp,
 }
 
 /// IP fiter
@@ -323,6 +668,8 @@ pub struct NetworkConfiguration {
 	pub udp_port: Option<u16>,
 	/// Enable NAT configuration
 	pub nat_enabled: bool,
+	/// NAT traversal method
+	pub nat_method: NatMethod,
 	/// Enable discovery
 	pub discovery_enabled: bool,
 	/// List of initial node addresses
@@ -343,6 +690,18 @@ pub struct NetworkConfiguration {
 	pub allow_non_reserved: bool,
 	/// IP Filtering
 	pub allow_ips: AllowIP,
+	/// Maximum total upload rate in bytes per second
+	pub max_upload_rate: Option<usize>,
+	/// Maximum total download rate in bytes per second
+	pub max_download_rate: Option<usize>,
+	/// Maximum upload rate for a single peer in bytes per second
+	pub max_peer_upload_rate: Option<usize>,
+	/// Maximum download rate for a single peer in bytes per second
+	pub max_peer_download_rate: Option<usize>,
+	/// File listing the only nodes allowed to connect
+	pub allowlist_path: Option<String>,
+	/// File to record subprotocol packets to
+	pub capture_path: Option<String>,
 }
 
 impl NetworkConfiguration {
@@ -373,6 +732,11 @@ impl NetworkConfiguration {
 			public_address:  match self.public_address { None => None, Some(addr) => Some(try!(SocketAddr::from_str(&addr))) },
 			udp_port: self.udp_port,
 			nat_enabled: self.nat_enabled,
+			nat_method: match self.nat_method {
+				NatMethod::Any => NetworkNatMethod::Any,
+				NatMethod::Upnp => NetworkNatMethod::Upnp,
+				NatMethod::NatPmp => NetworkNatMethod::NatPmp,
+			},
 			discovery_enabled: self.discovery_enabled,
 			boot_nodes: self.boot_nodes,
 			use_secret: self.use_secret,
@@ -387,6 +751,13 @@ impl NetworkConfiguration {
 				AllowIP::Public => NetworkAllowIP::Public,
 			},
 			non_reserved_mode: if self.allow_non_reserved { NonReservedPeerMode::Accept } else { NonReservedPeerMode::Deny },
+			max_upload_rate: self.max_upload_rate,
+			max_download_rate: self.max_download_rate,
+			max_peer_upload_rate: self.max_peer_upload_rate,
+			max_peer_download_rate: self.max_peer_download_rate,
+			chain_id: None,
+			allowlist_path: self.allowlist_path,
+			capture_path: self.capture_path,
 		})
 	}
 }
@@ -400,6 +771,11 @@ impl From<BasicNetworkConfiguration> for NetworkConfiguration {
 			public_address: other.public_address.and_then(|addr| Some(format!("{}", addr))),
 			udp_port: other.udp_port,
 			nat_enabled: other.nat_enabled,
+			nat_method: match other.nat_method {
+				NetworkNatMethod::Any => NatMethod::Any,
+				NetworkNatMethod::Upnp => NatMethod::Upnp,
+				NetworkNatMethod::NatPmp => NatMethod::NatPmp,
+			},
 			discovery_enabled: other.discovery_enabled,
 			boot_nodes: other.boot_nodes,
 			use_secret: other.use_secret,
@@ -414,6 +790,12 @@ impl From<BasicNetworkConfiguration> for NetworkConfiguration {
 				NetworkAllowIP::Public => AllowIP::Public,
 			},
 			allow_non_reserved: match other.non_reserved_mode { NonReservedPeerMode::Accept => true, _ => false } ,
+			max_upload_rate: other.max_upload_rate,
+			max_download_rate: other.max_download_rate,
+			max_peer_upload_rate: other.max_peer_upload_rate,
+			max_peer_download_rate: other.max_peer_download_rate,
+			allowlist_path: other.allowlist_path,
+			capture_path: other.capture_path,
 		}
 	}
 }
@@ -31,6 +31,8 @@ use blocks::BlockCollection;
 const MAX_HEADERS_TO_REQUEST: usize = 128;
 const MAX_BODIES_TO_REQUEST: usize = 128;
 const MAX_RECEPITS_TO_REQUEST: usize = 128;
+const MAX_BULK_BODIES_TO_REQUEST: usize = 256;
+const MAX_BULK_RECEIPTS_TO_REQUEST: usize = 256;
 const SUBCHAIN_SIZE: u64 = 256;
 const MAX_ROUND_PARENTS: usize = 32;
 
@@ -53,6 +55,7 @@ pub enum BlockRequest {
 		start: H256,
 		count: u64,
 		skip: u64,
+		reverse: bool,
 	},
 	Bodies {
 		hashes: Vec<H256>,
@@ -89,8 +92,8 @@ pub struct BlockDownloader {
 	round_parents: VecDeque<(H256, H256)>,
 	/// Do we need to download block recetips.
 	download_receipts: bool,
-	/// Sync up to the block with this hash.
-	target_hash: Option<H256>,
+	/// Request as many bodies and receipts at once as peers are willing to send.
+	bulk_requests: bool,
 }
 
 impl BlockDownloader {
@@ -105,7 +108,7 @@ impl BlockDownloader {
 			imported_this_round: None,
 			round_parents: VecDeque::new(),
 			download_receipts: sync_receipts,
-			target_hash: None,
+			bulk_requests: false,
 		}
 	}
 
@@ -133,9 +136,10 @@ impl BlockDownloader {
 		self.blocks.is_downloading(hash)
 	}
 
-	/// Set starting sync block
-	pub fn set_target(&mut self, hash: &H256) {
-		self.target_hash = Some(hash.clone());
+	/// Enable requesting bodies and receipts in large batches.
+	/// Suitable for trusted peers on a fast link.
+	pub fn set_bulk_requests(&mut self, bulk: bool) {
+		self.bulk_requests = bulk;
 	}
 
 	/// Set starting sync block
@@ -357,11 +361,13 @@ impl BlockDownloader {
 					start: self.last_imported_hash.clone(),
 					count: SUBCHAIN_SIZE,
 					skip: (MAX_HEADERS_TO_REQUEST - 2) as u64,
+					reverse: false,
 				});
 			},
 			State::Blocks => {
 				// check to see if we need to download any block bodies first
-				let needed_bodies = self.blocks.needed_bodies(MAX_BODIES_TO_REQUEST, false);
+				let max_bodies = if self.bulk_requests { MAX_BULK_BODIES_TO_REQUEST } else { MAX_BODIES_TO_REQUEST };
+				let needed_bodies = self.blocks.needed_bodies(max_bodies, false);
 				if !needed_bodies.is_empty() {
 					return Some(BlockRequest::Bodies {
 						hashes: needed_bodies,
@@ -369,7 +375,8 @@ impl BlockDownloader {
 				}
 
 				if self.download_receipts {
-					let needed_receipts = self.blocks.needed_receipts(MAX_RECEPITS_TO_REQUEST, false);
+					let max_receipts = if self.bulk_requests { MAX_BULK_RECEIPTS_TO_REQUEST } else { MAX_RECEPITS_TO_REQUEST };
+					let needed_receipts = self.blocks.needed_receipts(max_receipts, false);
 					if !needed_receipts.is_empty() {
 						return Some(BlockRequest::Receipts {
 							hashes: needed_receipts,
@@ -383,6 +390,7 @@ impl BlockDownloader {
 						start: h,
 						count: count as u64,
 						skip: 0,
+						reverse: false,
 					});
 				}
 			},
@@ -412,12 +420,6 @@ impl BlockDownloader {
 				break;
 			}
 
-			if self.target_hash.as_ref().map_or(false, |t| t == &h) {
-				self.state = State::Complete;
-				trace!(target: "sync", "Sync target reached");
-				return Ok(());
-			}
-
 			let result = if let Some(receipts) = receipts {
 				io.chain().import_block_with_receipts(block, receipts)
 			} else {
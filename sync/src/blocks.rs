@@ -44,9 +44,35 @@ impl HeapSizeOf for SyncBlock {
 
 /// Used to identify header by transactions and uncles hashes
 #[derive(Eq, PartialEq, Hash)]
-struct HeaderId {
-	transactions_root: H256,
-	uncles: H256
+pub struct HeaderId {
+	pub transactions_root: H256,
+	pub uncles: H256
+}
+
+impl HeaderId {
+	/// Identify the header a block body belongs to.
+	pub fn from_body(body: &UntrustedRlp) -> Result<HeaderId, DecoderError> {
+		let tx = try!(body.at(0));
+		let tx_root = ordered_trie_root(tx.iter().map(|r| r.as_raw().to_vec())); //TODO: get rid of vectors here
+		let uncles = try!(body.at(1)).as_raw().sha3();
+		Ok(HeaderId {
+			transactions_root: tx_root,
+			uncles: uncles
+		})
+	}
+
+	/// Check if the header has an empty body.
+	pub fn is_empty(&self) -> bool {
+		self.transactions_root == sha3::SHA3_NULL_RLP && self.uncles == sha3::SHA3_EMPTY_LIST_RLP
+	}
+}
+
+/// Returns RLP of an empty block body.
+pub fn empty_body() -> Bytes {
+	let mut body_stream = RlpStream::new_list(2);
+	body_stream.append_raw(&::rlp::EMPTY_LIST_RLP, 1);
+	body_stream.append_raw(&::rlp::EMPTY_LIST_RLP, 1);
+	body_stream.out()
 }
 
 /// A collection of blocks and subchain pointers being downloaded. This keeps track of
@@ -322,16 +348,7 @@ impl BlockCollection {
 	}
 
 	fn insert_body(&mut self, b: Bytes) -> Result<(), NetworkError> {
-		let header_id = {
-			let body = UntrustedRlp::new(&b);
-			let tx = try!(body.at(0));
-			let tx_root = ordered_trie_root(tx.iter().map(|r| r.as_raw().to_vec())); //TODO: get rid of vectors here
-			let uncles = try!(body.at(1)).as_raw().sha3();
-			HeaderId {
-				transactions_root: tx_root,
-				uncles: uncles
-			}
-		};
+		let header_id = try!(HeaderId::from_body(&UntrustedRlp::new(&b)));
 
 		match self.header_ids.get(&header_id).cloned() {
 			Some(h) => {
@@ -407,12 +424,9 @@ impl BlockCollection {
 			transactions_root: info.transactions_root().clone(),
 			uncles: info.uncles_hash().clone(),
 		};
-		if header_id.transactions_root == sha3::SHA3_NULL_RLP && header_id.uncles == sha3::SHA3_EMPTY_LIST_RLP {
+		if header_id.is_empty() {
 			// empty body, just mark as downloaded
-			let mut body_stream = RlpStream::new_list(2);
-			body_stream.append_raw(&::rlp::EMPTY_LIST_RLP, 1);
-			body_stream.append_raw(&::rlp::EMPTY_LIST_RLP, 1);
-			block.body = Some(body_stream.out());
+			block.body = Some(empty_body());
 		}
 		else {
 			trace!("Queueing body tx_root = {:?}, uncles = {:?}, block = {:?}, number = {}", header_id.transactions_root, header_id.uncles, hash, info.number());
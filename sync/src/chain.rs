@@ -96,21 +96,23 @@ use ethcore::client::{BlockChainClient, BlockStatus, BlockID, BlockChainInfo, Bl
 use ethcore::error::*;
 use ethcore::snapshot::{ManifestData, RestorationStatus};
 use sync_io::SyncIo;
-use time;
 use super::SyncConfig;
 use block_sync::{BlockDownloader, BlockRequest, BlockDownloaderImportError as DownloaderImportError};
+use ancient_sync::AncientDownloader;
 use snapshot::{Snapshot, ChunkType};
 use rand::{thread_rng, Rng};
-use api::{PeerInfo as PeerInfoDigest, WARP_SYNC_PROTOCOL_ID};
+use api::{PeerInfo as PeerInfoDigest, PeerProtocolTrafficInfo, ForkCheckpoints, TransactionPropagation, SyncStats, WARP_SYNC_PROTOCOL_ID};
+use metrics::SyncMetrics;
 
 known_heap_size!(0, PeerInfo);
 
 type PacketDecodeError = DecoderError;
 
-const PROTOCOL_VERSION_63: u8 = 63;
+pub const PROTOCOL_VERSION_63: u8 = 63;
 const PROTOCOL_VERSION_1: u8 = 1;
+const PROTOCOL_VERSION_2: u8 = 2;
 const MAX_BODIES_TO_SEND: usize = 256;
-const MAX_HEADERS_TO_SEND: usize = 512;
+pub const MAX_HEADERS_TO_SEND: usize = 512;
 const MAX_NODE_DATA_TO_SEND: usize = 1024;
 const MAX_RECEIPTS_TO_SEND: usize = 1024;
 const MAX_RECEIPTS_HEADERS_TO_SEND: usize = 256;
@@ -124,19 +126,19 @@ const MAX_TRANSACTION_SIZE: usize = 300*1024;
 // Min number of blocks to be behind for a snapshot sync
 const SNAPSHOT_RESTORE_THRESHOLD: BlockNumber = 100000;
 
-const STATUS_PACKET: u8 = 0x00;
-const NEW_BLOCK_HASHES_PACKET: u8 = 0x01;
+pub const STATUS_PACKET: u8 = 0x00;
+pub const NEW_BLOCK_HASHES_PACKET: u8 = 0x01;
 const TRANSACTIONS_PACKET: u8 = 0x02;
-const GET_BLOCK_HEADERS_PACKET: u8 = 0x03;
-const BLOCK_HEADERS_PACKET: u8 = 0x04;
-const GET_BLOCK_BODIES_PACKET: u8 = 0x05;
-const BLOCK_BODIES_PACKET: u8 = 0x06;
-const NEW_BLOCK_PACKET: u8 = 0x07;
+pub const GET_BLOCK_HEADERS_PACKET: u8 = 0x03;
+pub const BLOCK_HEADERS_PACKET: u8 = 0x04;
+pub const GET_BLOCK_BODIES_PACKET: u8 = 0x05;
+pub const BLOCK_BODIES_PACKET: u8 = 0x06;
+pub const NEW_BLOCK_PACKET: u8 = 0x07;
 
-const GET_NODE_DATA_PACKET: u8 = 0x0d;
-const NODE_DATA_PACKET: u8 = 0x0e;
-const GET_RECEIPTS_PACKET: u8 = 0x0f;
-const RECEIPTS_PACKET: u8 = 0x10;
+pub const GET_NODE_DATA_PACKET: u8 = 0x0d;
+pub const NODE_DATA_PACKET: u8 = 0x0e;
+pub const GET_RECEIPTS_PACKET: u8 = 0x0f;
+pub const RECEIPTS_PACKET: u8 = 0x10;
 
 pub const ETH_PACKET_COUNT: u8 = 0x11;
 
@@ -147,12 +149,27 @@ const SNAPSHOT_DATA_PACKET: u8 = 0x14;
 
 pub const SNAPSHOT_SYNC_PACKET_COUNT: u8 = 0x15;
 
-const HEADERS_TIMEOUT_SEC: f64 = 15f64;
+// Transaction announcements by hash, added in version 2 of the warp protocol.
+const TRANSACTION_HASHES_PACKET: u8 = 0x15;
+const GET_TRANSACTIONS_PACKET: u8 = 0x16;
+
+pub const SNAPSHOT_SYNC_V2_PACKET_COUNT: u8 = 0x17;
+
+/// Supported versions of the warp sync protocol and the number of packet ids each reserves.
+pub const WARP_SYNC_PROTOCOL_VERSIONS: [(u8, u8); 2] = [
+	(PROTOCOL_VERSION_1, SNAPSHOT_SYNC_PACKET_COUNT),
+	(PROTOCOL_VERSION_2, SNAPSHOT_SYNC_V2_PACKET_COUNT),
+];
+
+pub const HEADERS_TIMEOUT_SEC: f64 = 15f64;
 const BODIES_TIMEOUT_SEC: f64 = 10f64;
 const RECEIPTS_TIMEOUT_SEC: f64 = 10f64;
 const FORK_HEADER_TIMEOUT_SEC: f64 = 3f64;
 const SNAPSHOT_MANIFEST_TIMEOUT_SEC: f64 = 3f64;
 const SNAPSHOT_DATA_TIMEOUT_SEC: f64 = 60f64;
+const TRANSACTIONS_TIMEOUT_SEC: f64 = 10f64;
+// Interval for checking fork blocks again with peers that were too short to confirm them
+const FORK_RECHECK_INTERVAL_SEC: f64 = 300f64;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 /// Sync state
@@ -202,7 +219,7 @@ pub struct SyncStatus {
 	pub num_snapshot_chunks: usize,
 	/// Snapshot chunks downloaded
 	pub snapshot_chunks_done: usize,
-	/// Last fully downloaded and imported ancient block number (if any).
+	/// Lowest ancient block number downloaded and imported so far, while ancient blocks are being downloaded.
 	pub last_imported_old_block_number: Option<BlockNumber>,
 }
 
@@ -283,12 +300,22 @@ struct PeerInfo {
 	expired: bool,
 	/// Peer fork confirmation status
 	confirmation: ForkConfirmation,
+	/// Number of fork blocks confirmed so far
+	fork_checks: usize,
+	/// Time the last fork block check was started
+	fork_check_time: f64,
 	/// Best snapshot hash
 	snapshot_hash: Option<H256>,
 	/// Best snapshot block number
 	snapshot_number: Option<BlockNumber>,
 	/// Block set requested
 	block_set: Option<BlockSet>,
+	/// Peer accepts transaction hash announcements
+	transaction_hashes: bool,
+	/// Number of transactions that can be sent to this peer under the rate limit
+	tx_allowance: f64,
+	/// Time `tx_allowance` was last updated
+	tx_allowance_time: f64,
 }
 
 impl PeerInfo {
@@ -317,17 +344,25 @@ pub struct ChainSync {
 	/// Block download process for new blocks
 	new_blocks: BlockDownloader,
 	/// Block download process for ancient blocks
-	old_blocks: Option<BlockDownloader>,
+	old_blocks: Option<AncientDownloader>,
 	/// Last propagated block number
 	last_sent_block_number: BlockNumber,
 	/// Network ID
 	network_id: U256,
-	/// Optional fork block to check
-	fork_block: Option<(BlockNumber, H256)>,
+	/// Fork blocks to check
+	fork_blocks: ForkCheckpoints,
 	/// Snapshot sync allowed.
 	snapshot_sync_enabled: bool,
 	/// Snapshot downloader.
 	snapshot: Snapshot,
+	/// Only sync with reserved peers.
+	reserved_peers_only: bool,
+	/// Transaction propagation policy.
+	tx_propagation: TransactionPropagation,
+	/// Announced transactions requested from peers, with the time of the request.
+	requested_transactions: HashMap<H256, f64>,
+	/// Download rates and per-peer request statistics.
+	metrics: SyncMetrics,
 }
 
 type RlpResponseResult = Result<Option<(PacketId, RlpStream)>, PacketDecodeError>;
@@ -346,9 +381,13 @@ impl ChainSync {
 			old_blocks: None,
 			last_sent_block_number: 0,
 			network_id: config.network_id,
-			fork_block: config.fork_block,
+			fork_blocks: config.fork_blocks,
 			snapshot_sync_enabled: config.warp_sync,
 			snapshot: Snapshot::new(),
+			reserved_peers_only: config.reserved_peers_only,
+			tx_propagation: config.tx_propagation,
+			requested_transactions: HashMap::new(),
+			metrics: SyncMetrics::default(),
 		};
 		sync.init_downloaders(chain);
 		sync
@@ -378,6 +417,27 @@ impl ChainSync {
 		}
 	}
 
+	/// Returns sync performance metrics
+	pub fn stats(&self, io: &SyncIo) -> SyncStats {
+		let now = io.now();
+		let queue = io.chain().queue_info();
+		SyncStats {
+			headers_per_sec: self.metrics.headers.rate(now),
+			bodies_per_sec: self.metrics.bodies.rate(now),
+			receipts_per_sec: self.metrics.receipts.rate(now),
+			blocks_per_sec: self.metrics.blocks.rate(now),
+			headers_total: self.metrics.headers.total(),
+			bodies_total: self.metrics.bodies.total(),
+			receipts_total: self.metrics.receipts.total(),
+			blocks_total: self.metrics.blocks.total(),
+			queue_unverified: queue.unverified_queue_size,
+			queue_verifying: queue.verifying_queue_size,
+			queue_verified: queue.verified_queue_size,
+			peers: self.metrics.peer_stats(|peer| io.peer_session_info(peer).and_then(|info| info.id).map(|id| id.hex())),
+			recent_blocks: self.metrics.recent_blocks(),
+		}
+	}
+
 	/// Returns information on peers connections
 	pub fn peers(&self, io: &SyncIo) -> Vec<PeerInfoDigest> {
 		self.peers.iter()
@@ -392,6 +452,8 @@ impl ChainSync {
 						eth_version: peer_data.protocol_version as u32,
 						eth_difficulty: peer_data.difficulty,
 						eth_head: peer_data.latest_hash,
+						traffic: (&session_info.stats.total).into(),
+						protocol_traffic: PeerProtocolTrafficInfo::from_session(&session_info.stats),
 				})
 			)
 			.collect()
@@ -455,28 +517,28 @@ impl ChainSync {
 	}
   
 	/// Restart sync after bad block has been detected. May end up re-downloading up to QUEUE_SIZE blocks
-	fn init_downloaders(&mut self, chain: &BlockChainClient) {
+	fn init_downloaders(&mut self, client: &BlockChainClient) {
 		// Do not assume that the block queue/chain still has our last_imported_block
-		let chain = chain.chain_info();
+		let chain = client.chain_info();
 		self.new_blocks = BlockDownloader::new(false, &chain.best_block_hash, chain.best_block_number);
-		if let (Some(ancient_block_hash), Some(ancient_block_number)) = (chain.ancient_block_hash, chain.ancient_block_number) {
-
-			trace!(target: "sync", "Downloading old blocks from {:?} (#{}) till {:?} (#{:?})", ancient_block_hash, ancient_block_number, chain.first_block_hash, chain.first_block_number);
-			let mut downloader = BlockDownloader::new(true, &ancient_block_hash, ancient_block_number);
-			if let Some(hash) = chain.first_block_hash {
-				trace!(target: "sync", "Downloader target set to {:?}", hash);
-				downloader.set_target(&hash);
+		self.new_blocks.set_bulk_requests(self.reserved_peers_only);
+		let first_parent = chain.first_block_hash.and_then(|h| client.block_header(BlockID::Hash(h))).map(|h| HeaderView::new(&h).parent_hash());
+		match (chain.ancient_block_hash, chain.ancient_block_number, first_parent, chain.first_block_number) {
+			(Some(ancient_block_hash), Some(ancient_block_number), Some(first_parent), Some(first_block_number)) => {
+				trace!(target: "sync", "Downloading old blocks from {:?} (#{}) down to {:?} (#{})", chain.first_block_hash, first_block_number, ancient_block_hash, ancient_block_number);
+				self.old_blocks = Some(AncientDownloader::new(&first_parent, first_block_number, &ancient_block_hash, ancient_block_number));
+			},
+			_ => {
+				self.old_blocks = None;
 			}
-			self.old_blocks = Some(downloader);
-		} else {
-			self.old_blocks = None;
 		}
 	}
 
 	/// Called by peer to report status
 	fn on_peer_status(&mut self, io: &mut SyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
 		let protocol_version: u8 = try!(r.val_at(0));
-		let warp_protocol = io.protocol_version(&WARP_SYNC_PROTOCOL_ID, peer_id) != 0;
+		let warp_version = io.protocol_version(&WARP_SYNC_PROTOCOL_ID, peer_id);
+		let warp_protocol = warp_version != 0;
 		let peer = PeerInfo {
 			protocol_version: protocol_version,
 			network_id: try!(r.val_at(1)),
@@ -489,11 +551,16 @@ impl ChainSync {
 			ask_time: 0f64,
 			last_sent_transactions: HashSet::new(),
 			expired: false,
-			confirmation: if self.fork_block.is_none() { ForkConfirmation::Confirmed } else { ForkConfirmation::Unconfirmed },
+			confirmation: if self.fork_blocks.is_empty() { ForkConfirmation::Confirmed } else { ForkConfirmation::Unconfirmed },
+			fork_checks: 0,
+			fork_check_time: 0f64,
 			asking_snapshot_data: None,
 			snapshot_hash: if warp_protocol { Some(try!(r.val_at(5))) } else { None },
 			snapshot_number: if warp_protocol { Some(try!(r.val_at(6))) } else { None },
 			block_set: None,
+			transaction_hashes: warp_version >= PROTOCOL_VERSION_2,
+			tx_allowance: 0f64,
+			tx_allowance_time: 0f64,
 		};
 
 		trace!(target: "sync", "New peer {} (protocol: {}, network: {:?}, difficulty: {:?}, latest:{}, genesis:{})", peer_id, peer.protocol_version, peer.network_id, peer.difficulty, peer.latest_hash, peer.genesis);
@@ -517,7 +584,7 @@ impl ChainSync {
 			trace!(target: "sync", "Peer {} network id mismatch (ours: {}, theirs: {})", peer_id, self.network_id, peer.network_id);
 			return Ok(());
 		}
-		if (warp_protocol && peer.protocol_version != PROTOCOL_VERSION_1) || (!warp_protocol && peer.protocol_version != PROTOCOL_VERSION_63) {
+		if (warp_protocol && peer.protocol_version != warp_version) || (!warp_protocol && peer.protocol_version != PROTOCOL_VERSION_63) {
 			io.disable_peer(peer_id);
 			trace!(target: "sync", "Peer {} unsupported eth protocol ({})", peer_id, peer.protocol_version);
 			return Ok(());
@@ -526,10 +593,10 @@ impl ChainSync {
 		self.peers.insert(peer_id.clone(), peer);
 		self.active_peers.insert(peer_id.clone());
 		debug!(target: "sync", "Connected {}:{}", peer_id, io.peer_info(peer_id));
-		if let Some((fork_block, _)) = self.fork_block {
-			self.request_fork_header_by_number(io, peer_id, fork_block);
-		} else {
+		if self.fork_blocks.is_empty() {
 			self.sync_peer(io, peer_id, false);
+		} else {
+			self.request_fork_header(io, peer_id);
 		}
 		Ok(())
 	}
@@ -537,35 +604,50 @@ impl ChainSync {
 	#[cfg_attr(feature="dev", allow(cyclomatic_complexity))]
 	/// Called by peer once it has new block headers during sync
 	fn on_peer_block_headers(&mut self, io: &mut SyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
-		let confirmed = match self.peers.get_mut(&peer_id) {
+		// `Some(true)` if there are more fork blocks to check with this peer
+		let fork_check = match self.peers.get_mut(&peer_id) {
 			Some(ref mut peer) if peer.asking == PeerAsking::ForkHeader => {
 				peer.asking = PeerAsking::Nothing;
 				let item_count = r.item_count();
-				let (fork_number, fork_hash) = self.fork_block.expect("ForkHeader request is sent only fork block is Some; qed").clone();
+				let (fork_number, fork_hash) = *self.fork_blocks.as_slice().get(peer.fork_checks)
+					.expect("ForkHeader request is sent only for a fork block not confirmed yet; qed");
 				if item_count == 0 || item_count != 1 {
-					trace!(target: "sync", "{}: Chain is too short to confirm the block", peer_id);
+					trace!(target: "sync", "{}: Chain is too short to confirm the block {}", peer_id, fork_number);
 					peer.confirmation = ForkConfirmation::TooShort;
+					Some(false)
 				} else {
 					let header = try!(r.at(0)).as_raw();
 					if header.sha3() == fork_hash {
-						trace!(target: "sync", "{}: Confirmed peer", peer_id);
-						peer.confirmation = ForkConfirmation::Confirmed;
 						if !io.chain_overlay().read().contains_key(&fork_number) {
 							io.chain_overlay().write().insert(fork_number, header.to_vec());
 						}
+						peer.fork_checks += 1;
+						if peer.fork_checks == self.fork_blocks.as_slice().len() {
+							trace!(target: "sync", "{}: Confirmed peer", peer_id);
+							peer.confirmation = ForkConfirmation::Confirmed;
+							Some(false)
+						} else {
+							Some(true)
+						}
 					} else {
-						trace!(target: "sync", "{}: Fork mismatch", peer_id);
+						trace!(target: "sync", "{}: Fork mismatch at {}", peer_id, fork_number);
 						io.disconnect_peer(peer_id);
 						return Ok(());
 					}
 				}
-				true
 			},
-			_ => false,
+			_ => None,
 		};
-		if confirmed {
-			self.sync_peer(io, peer_id, false);
-			return Ok(());
+		match fork_check {
+			Some(true) => {
+				self.request_fork_header(io, peer_id);
+				return Ok(());
+			},
+			Some(false) => {
+				self.sync_peer(io, peer_id, false);
+				return Ok(());
+			},
+			None => (),
 		}
 
 		self.clear_peer_download(peer_id);
@@ -589,21 +671,18 @@ impl ChainSync {
 			return Ok(());
 		}
 
-		let result =  {
-			let mut downloader = match block_set {
-				BlockSet::NewBlocks => &mut self.new_blocks,
-				BlockSet::OldBlocks => {
-					match self.old_blocks {
-						None => {
-							trace!(target: "sync", "Ignored block headers while block download is inactive");
-							self.continue_sync(io);
-							return Ok(());
-						},
-						Some(ref mut blocks) => blocks,
-					}
+		let result = match block_set {
+			BlockSet::NewBlocks => self.new_blocks.import_headers(io, r, expected_hash),
+			BlockSet::OldBlocks => {
+				match self.old_blocks {
+					None => {
+						trace!(target: "sync", "Ignored block headers while block download is inactive");
+						self.continue_sync(io);
+						return Ok(());
+					},
+					Some(ref mut blocks) => blocks.import_headers(r, expected_hash),
 				}
-			};
-			downloader.import_headers(io, r, expected_hash)
+			}
 		};
 
 		match result {
@@ -611,12 +690,13 @@ impl ChainSync {
 				self.deactivate_peer(io, peer_id);
 			},
 			Err(DownloaderImportError::Invalid) => {
+				io.report_peer(peer_id, PeerEvent::BadBlock);
 				io.disable_peer(peer_id);
 				self.deactivate_peer(io, peer_id);
 				self.continue_sync(io);
 				return Ok(());
 			},
-			Ok(()) => (),
+			Ok(()) => io.report_peer(peer_id, PeerEvent::UsefulData),
 		}
 
 		self.collect_blocks(io, block_set);
@@ -646,23 +726,21 @@ impl ChainSync {
 		}
 		else
 		{
-			let result = {
-				let mut downloader = match block_set {
-					BlockSet::NewBlocks => &mut self.new_blocks,
-					BlockSet::OldBlocks => match self.old_blocks {
-						None => {
-							trace!(target: "sync", "Ignored block headers while block download is inactive");
-							self.continue_sync(io);
-							return Ok(());
-						},
-						Some(ref mut blocks) => blocks,
-					}
-				};
-				downloader.import_bodies(io, r)
+			let result = match block_set {
+				BlockSet::NewBlocks => self.new_blocks.import_bodies(io, r),
+				BlockSet::OldBlocks => match self.old_blocks {
+					None => {
+						trace!(target: "sync", "Ignored block bodies while block download is inactive");
+						self.continue_sync(io);
+						return Ok(());
+					},
+					Some(ref mut blocks) => blocks.import_bodies(r),
+				}
 			};
 
 			match result {
 				Err(DownloaderImportError::Invalid) => {
+					io.report_peer(peer_id, PeerEvent::BadBlock);
 					io.disable_peer(peer_id);
 					self.deactivate_peer(io, peer_id);
 					self.continue_sync(io);
@@ -671,7 +749,7 @@ impl ChainSync {
 				Err(DownloaderImportError::Useless) => {
 					self.deactivate_peer(io, peer_id);
 				},
-				Ok(()) => (),
+				Ok(()) => io.report_peer(peer_id, PeerEvent::UsefulData),
 			}
 
 			self.collect_blocks(io, block_set);
@@ -700,23 +778,21 @@ impl ChainSync {
 		}
 		else
 		{
-			let result = {
-				let mut downloader = match block_set {
-					BlockSet::NewBlocks => &mut self.new_blocks,
-					BlockSet::OldBlocks => match self.old_blocks {
-						None => {
-							trace!(target: "sync", "Ignored block headers while block download is inactive");
-							self.continue_sync(io);
-							return Ok(());
-						},
-						Some(ref mut blocks) => blocks,
-					}
-				};
-				downloader.import_receipts(io, r)
+			let result = match block_set {
+				BlockSet::NewBlocks => self.new_blocks.import_receipts(io, r),
+				BlockSet::OldBlocks => match self.old_blocks {
+					None => {
+						trace!(target: "sync", "Ignored block receipts while block download is inactive");
+						self.continue_sync(io);
+						return Ok(());
+					},
+					Some(ref mut blocks) => blocks.import_receipts(r),
+				}
 			};
 
 			match result {
 				Err(DownloaderImportError::Invalid) => {
+					io.report_peer(peer_id, PeerEvent::BadBlock);
 					io.disable_peer(peer_id);
 					self.deactivate_peer(io, peer_id);
 					self.continue_sync(io);
@@ -725,7 +801,7 @@ impl ChainSync {
 				Err(DownloaderImportError::Useless) => {
 					self.deactivate_peer(io, peer_id);
 				},
-				Ok(()) => (),
+				Ok(()) => io.report_peer(peer_id, PeerEvent::UsefulData),
 			}
 
 			self.collect_blocks(io, block_set);
@@ -779,6 +855,7 @@ impl ChainSync {
 			},
 			Err(e) => {
 				debug!(target: "sync", "Bad new block {:?} : {:?}", h, e);
+				io.report_peer(peer_id, PeerEvent::BadBlock);
 				io.disable_peer(peer_id);
 			}
 		};
@@ -853,6 +930,7 @@ impl ChainSync {
 				},
 				BlockStatus::Bad => {
 					debug!(target: "sync", "Bad new block hash {:?}", hash);
+					io.report_peer(peer_id, PeerEvent::BadBlock);
 					io.disable_peer(peer_id);
 					return Ok(());
 				}
@@ -884,6 +962,7 @@ impl ChainSync {
 		let manifest = match ManifestData::from_rlp(manifest_rlp.as_raw()) {
 			Err(e) => {
 				trace!(target: "sync", "{}: Ignored bad manifest: {:?}", peer_id, e);
+				io.report_peer(peer_id, PeerEvent::BadSnapshotData);
 				io.disconnect_peer(peer_id);
 				self.continue_sync(io);
 				return Ok(());
@@ -940,6 +1019,7 @@ impl ChainSync {
 			}
 			Err(()) => {
 				trace!(target: "sync", "{}: Got bad snapshot chunk", peer_id);
+				io.report_peer(peer_id, PeerEvent::BadSnapshotData);
 				io.disconnect_peer(peer_id);
 				self.continue_sync(io);
 				return Ok(());
@@ -962,6 +1042,7 @@ impl ChainSync {
 		trace!(target: "sync", "== Disconnecting {}: {}", peer, io.peer_info(peer));
 		if self.peers.contains_key(&peer) {
 			debug!(target: "sync", "Disconnected {}", peer);
+			self.metrics.on_peer_aborting(peer, io.peer_session_info(peer).and_then(|info| info.id).map(|id| id.hex()));
 			self.clear_peer_download(peer);
 			self.peers.remove(&peer);
 			self.active_peers.remove(&peer);
@@ -972,6 +1053,10 @@ impl ChainSync {
 	/// Called when a new peer is connected
 	pub fn on_peer_connected(&mut self, io: &mut SyncIo, peer: PeerId) {
 		trace!(target: "sync", "== Connected {}: {}", peer, io.peer_info(peer));
+		if self.reserved_peers_only && !io.is_reserved_peer(peer) {
+			trace!(target: "sync", "Ignoring non-reserved peer {}", peer);
+			return;
+		}
 		if let Err(e) = self.send_status(io, peer) {
 			debug!(target:"sync", "Error sending status request: {:?}", e);
 			io.disable_peer(peer);
@@ -1064,7 +1149,7 @@ impl ChainSync {
 						}
 					}
 
-					if let Some(request) = self.old_blocks.as_mut().and_then(|d| d.request_blocks(io)) {
+					if let Some(request) = self.old_blocks.as_mut().and_then(|d| d.request_blocks()) {
 						self.request_blocks(io, peer_id, request, BlockSet::OldBlocks);
 						return;
 					}
@@ -1083,8 +1168,8 @@ impl ChainSync {
 	/// Perofrm block download request`
 	fn request_blocks(&mut self, io: &mut SyncIo, peer_id: PeerId, request: BlockRequest, block_set: BlockSet) {
 		match request {
-			BlockRequest::Headers { start, count, skip } => {
-				self.request_headers_by_hash(io, peer_id, &start, count, skip, false, block_set);
+			BlockRequest::Headers { start, count, skip, reverse } => {
+				self.request_headers_by_hash(io, peer_id, &start, count, skip, reverse, block_set);
 			},
 			BlockRequest::Bodies { hashes } => {
 				self.request_bodies(io, peer_id, hashes, block_set);
@@ -1150,7 +1235,7 @@ impl ChainSync {
 				}
 			},
 			BlockSet::OldBlocks => {
-				 if self.old_blocks.as_mut().map_or(false, |downloader| { downloader.collect_blocks(io, false) == Err(DownloaderImportError::Invalid) }) {
+				 if self.old_blocks.as_mut().map_or(false, |downloader| { downloader.collect_blocks(io) == Err(DownloaderImportError::Invalid) }) {
 					 self.restart(io);
 				 } else if self.old_blocks.as_ref().map_or(false, |downloader| { downloader.is_complete() }) {
 					trace!(target: "sync", "Background block download is complete");
@@ -1175,6 +1260,21 @@ impl ChainSync {
 		peer.block_set = Some(set);
 	}
 
+	/// Request the header of the next fork block to check from a peer
+	fn request_fork_header(&mut self, sync: &mut SyncIo, peer_id: PeerId) {
+		let number = match self.peers.get_mut(&peer_id) {
+			Some(ref mut peer) => {
+				peer.fork_check_time = sync.now();
+				match self.fork_blocks.as_slice().get(peer.fork_checks) {
+					Some(&(number, _)) => number,
+					None => return,
+				}
+			},
+			None => return,
+		};
+		self.request_fork_header_by_number(sync, peer_id, number);
+	}
+
 	/// Request headers from a peer by block number
 	#[cfg_attr(feature="dev", allow(too_many_arguments))]
 	fn request_fork_header_by_number(&mut self, sync: &mut SyncIo, peer_id: PeerId, n: BlockNumber) {
@@ -1252,7 +1352,7 @@ impl ChainSync {
 				warn!(target:"sync", "Asking {:?} while requesting {:?}", peer.asking, asking);
 			}
 			peer.asking = asking;
-			peer.ask_time = time::precise_time_s();
+			peer.ask_time = sync.now();
 			let result = if packet_id >= ETH_PACKET_COUNT {
 				sync.send_protocol(WARP_SYNC_PROTOCOL_ID, peer_id, packet_id, packet)
 			} else {
@@ -1267,7 +1367,12 @@ impl ChainSync {
 
 	/// Generic packet sender
 	fn send_packet(&mut self, sync: &mut SyncIo, peer_id: PeerId, packet_id: PacketId, packet: Bytes) {
-		if let Err(e) = sync.send(peer_id, packet_id, packet) {
+		let result = if packet_id >= ETH_PACKET_COUNT {
+			sync.send_protocol(WARP_SYNC_PROTOCOL_ID, peer_id, packet_id, packet)
+		} else {
+			sync.send(peer_id, packet_id, packet)
+		};
+		if let Err(e) = result {
 			debug!(target:"sync", "Error sending packet: {:?}", e);
 			sync.disable_peer(peer_id);
 		}
@@ -1276,7 +1381,7 @@ impl ChainSync {
 	/// Called when peer sends us new transactions
 	fn on_peer_transactions(&mut self, io: &mut SyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
 		// Accept transactions only when fully synced
-		if !io.is_chain_queue_empty() || self.state != SyncState::Idle || self.state != SyncState::NewBlocks {
+		if !io.is_chain_queue_empty() || (self.state != SyncState::Idle && self.state != SyncState::NewBlocks) {
 			trace!(target: "sync", "{} Ignoring transactions while syncing", peer_id);
 			return Ok(());
 		}
@@ -1295,16 +1400,99 @@ impl ChainSync {
 				continue;
 			}
 			let tx = rlp.as_raw().to_vec();
+			self.requested_transactions.remove(&tx.sha3());
 			transactions.push(tx);
 		}
 		io.chain().queue_transactions(transactions);
 		Ok(())
 	}
 
+	/// Called when peer announces hashes of new transactions
+	fn on_peer_transaction_hashes(&mut self, io: &mut SyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
+		if !io.is_chain_queue_empty() || (self.state != SyncState::Idle && self.state != SyncState::NewBlocks) {
+			trace!(target: "sync", "{} Ignoring transaction hashes while syncing", peer_id);
+			return Ok(());
+		}
+		if !self.peers.get(&peer_id).map_or(false, |p| p.can_sync()) {
+			trace!(target: "sync", "{} Ignoring transaction hashes from unconfirmed/unknown peer", peer_id);
+			return Ok(());
+		}
+
+		let item_count = min(r.item_count(), MAX_TX_TO_IMPORT);
+		trace!(target: "sync", "{} -> TransactionHashes ({} entries)", peer_id, item_count);
+		let now = io.now();
+		self.requested_transactions.retain(|_, asked| now - *asked < TRANSACTIONS_TIMEOUT_SEC);
+		let known = io.chain().pending_transactions().into_iter().map(|tx| tx.hash()).collect::<HashSet<_>>();
+		let mut unknown = Vec::new();
+		let mut requesting = Vec::new();
+		for i in 0 .. item_count {
+			let hash: H256 = try!(r.val_at(i));
+			if known.contains(&hash) {
+				continue;
+			}
+			// don't ask for transactions already requested from another peer
+			if !self.requested_transactions.contains_key(&hash) {
+				self.requested_transactions.insert(hash.clone(), now);
+				requesting.push(hash.clone());
+			}
+			unknown.push(hash);
+		}
+		if let Some(ref mut peer) = self.peers.get_mut(&peer_id) {
+			// the peer has these already, no need to send them back
+			peer.last_sent_transactions.extend(unknown.iter().cloned());
+		}
+		if requesting.is_empty() {
+			return Ok(());
+		}
+
+		let mut packet = RlpStream::new_list(requesting.len());
+		for hash in &requesting {
+			packet.append(hash);
+		}
+		trace!(target: "sync", "{} <- GetTransactions ({} entries)", peer_id, requesting.len());
+		self.send_packet(io, peer_id, GET_TRANSACTIONS_PACKET, packet.out());
+		Ok(())
+	}
+
+	/// Called when peer requests transactions previously announced by hash
+	fn on_peer_get_transactions(&mut self, io: &mut SyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
+		if !self.peers.get(&peer_id).map_or(false, |p| p.can_sync()) {
+			trace!(target: "sync", "{} Ignoring transactions request from unconfirmed/unknown peer", peer_id);
+			return Ok(());
+		}
+		if self.tx_propagation.reserved_only && !io.is_reserved_peer(peer_id) {
+			trace!(target: "sync", "{} Ignoring transactions request from non-reserved peer", peer_id);
+			return Ok(());
+		}
+
+		let item_count = min(r.item_count(), MAX_TX_TO_IMPORT);
+		trace!(target: "sync", "{} -> GetTransactions ({} entries)", peer_id, item_count);
+		let mut requested = HashSet::new();
+		for i in 0 .. item_count {
+			requested.insert(try!(r.val_at::<H256>(i)));
+		}
+		let relay_local = self.tx_propagation.relay_local;
+		let transactions = io.chain().pending_transactions().into_iter()
+			.filter(|tx| requested.contains(&tx.hash()))
+			.filter(|tx| relay_local || !io.chain().is_local_transaction(&tx.hash()))
+			.collect::<Vec<_>>();
+		if transactions.is_empty() {
+			return Ok(());
+		}
+
+		let mut packet = RlpStream::new_list(transactions.len());
+		for tx in &transactions {
+			packet.append(tx);
+		}
+		self.send_packet(io, peer_id, TRANSACTIONS_PACKET, packet.out());
+		Ok(())
+	}
+
 	/// Send Status message
 	fn send_status(&mut self, io: &mut SyncIo, peer: PeerId) -> Result<(), NetworkError> {
-		let warp_protocol = io.protocol_version(&WARP_SYNC_PROTOCOL_ID, peer) != 0;
-		let protocol = if warp_protocol { PROTOCOL_VERSION_1 } else { PROTOCOL_VERSION_63 };
+		let warp_version = io.protocol_version(&WARP_SYNC_PROTOCOL_ID, peer);
+		let warp_protocol = warp_version != 0;
+		let protocol = if warp_protocol { warp_version } else { PROTOCOL_VERSION_63 };
 		trace!(target: "sync", "Sending status to {}, protocol version {}", peer, protocol);
 		let mut packet = RlpStream::new_list(if warp_protocol { 7 } else { 5 });
 		let chain = io.chain().chain_info();
@@ -1523,6 +1711,10 @@ impl ChainSync {
 
 	/// Dispatch incoming requests and responses
 	pub fn dispatch_packet(sync: &RwLock<ChainSync>, io: &mut SyncIo, peer: PeerId, packet_id: u8, data: &[u8]) {
+		if sync.read().reserved_peers_only && !io.is_reserved_peer(peer) {
+			trace!(target: "sync", "{}: Ignored packet {} from non-reserved peer", peer, packet_id);
+			return;
+		}
 		let rlp = UntrustedRlp::new(data);
 		let result = match packet_id {
 			GET_BLOCK_BODIES_PACKET => ChainSync::return_rlp(io, &rlp, peer,
@@ -1565,9 +1757,12 @@ impl ChainSync {
 			return;
 		}
 		let rlp = UntrustedRlp::new(data);
+		self.record_response(io, peer, packet_id, &rlp);
 		let result = match packet_id {
 			STATUS_PACKET => self.on_peer_status(io, peer, &rlp),
 			TRANSACTIONS_PACKET => self.on_peer_transactions(io, peer, &rlp),
+			TRANSACTION_HASHES_PACKET => self.on_peer_transaction_hashes(io, peer, &rlp),
+			GET_TRANSACTIONS_PACKET => self.on_peer_get_transactions(io, peer, &rlp),
 			BLOCK_HEADERS_PACKET => self.on_peer_block_headers(io, peer, &rlp),
 			BLOCK_BODIES_PACKET => self.on_peer_block_bodies(io, peer, &rlp),
 			RECEIPTS_PACKET => self.on_peer_block_receipts(io, peer, &rlp),
@@ -1585,9 +1780,38 @@ impl ChainSync {
 		})
 	}
 
+	/// Update download metrics with a packet received from a peer.
+	fn record_response(&mut self, io: &SyncIo, peer_id: PeerId, packet_id: u8, r: &UntrustedRlp) {
+		let now = io.now();
+		match packet_id {
+			BLOCK_HEADERS_PACKET | BLOCK_BODIES_PACKET | RECEIPTS_PACKET | SNAPSHOT_MANIFEST_PACKET | SNAPSHOT_DATA_PACKET => {
+				if let Some(peer) = self.peers.get(&peer_id) {
+					if peer.asking != PeerAsking::Nothing {
+						self.metrics.on_response(peer_id, now - peer.ask_time);
+					}
+				}
+			},
+			_ => {},
+		}
+		match packet_id {
+			BLOCK_HEADERS_PACKET => {
+				let hashes: Vec<H256> = r.iter().map(|h| h.as_raw().sha3()).collect();
+				self.metrics.on_headers(now, peer_id, &hashes);
+			},
+			BLOCK_BODIES_PACKET => self.metrics.bodies.record(now, r.item_count()),
+			RECEIPTS_PACKET => self.metrics.receipts.record(now, r.item_count()),
+			NEW_BLOCK_PACKET => {
+				if let Ok(header) = r.at(0).and_then(|b| b.at(0)) {
+					self.metrics.on_headers(now, peer_id, &[header.as_raw().sha3()]);
+				}
+			},
+			_ => {},
+		}
+	}
+
 	#[cfg_attr(feature="dev", allow(match_same_arms))]
 	pub fn maintain_peers(&mut self, io: &mut SyncIo) {
-		let tick = time::precise_time_s();
+		let tick = io.now();
 		let mut aborting = Vec::new();
 		for (peer_id, peer) in &self.peers {
 			let timeout = match peer.asking {
@@ -1601,6 +1825,8 @@ impl ChainSync {
 			};
 			if timeout {
 				trace!(target:"sync", "Timeout {}", peer_id);
+				self.metrics.on_timeout(*peer_id);
+				io.report_peer(*peer_id, PeerEvent::SlowResponse);
 				io.disconnect_peer(*peer_id);
 				aborting.push(*peer_id);
 			}
@@ -1608,6 +1834,16 @@ impl ChainSync {
 		for p in aborting {
 			self.on_peer_aborting(io, p);
 		}
+
+		// Peers that were behind the fork blocks may have caught up by now
+		let recheck = self.peers.iter()
+			.filter(|&(_, p)| p.confirmation == ForkConfirmation::TooShort && p.asking == PeerAsking::Nothing)
+			.filter(|&(_, p)| (tick - p.fork_check_time) > FORK_RECHECK_INTERVAL_SEC)
+			.map(|(peer_id, _)| *peer_id)
+			.collect::<Vec<_>>();
+		for peer_id in recheck {
+			self.request_fork_header(io, peer_id);
+		}
 	}
 
 	fn check_resume(&mut self, io: &mut SyncIo) {
@@ -1745,11 +1981,23 @@ impl ChainSync {
 			return 0;
 		}
 
-		let transactions = io.chain().pending_transactions();
+		let policy = self.tx_propagation;
+		let mut transactions = io.chain().pending_transactions();
+		if !policy.relay_local {
+			transactions.retain(|tx| !io.chain().is_local_transaction(&tx.hash()));
+		}
 		if transactions.is_empty() {
 			return 0;
 		}
 
+		let peers = self.peers.keys()
+			.filter(|peer_id| !policy.reserved_only || io.is_reserved_peer(**peer_id))
+			.cloned()
+			.collect::<HashSet<_>>();
+		if peers.is_empty() {
+			return 0;
+		}
+
 		let all_transactions_hashes = transactions.iter().map(|tx| tx.hash()).collect::<HashSet<H256>>();
 		let all_transactions_rlp = {
 			let mut packet = RlpStream::new_list(transactions.len());
@@ -1758,17 +2006,37 @@ impl ChainSync {
 		};
 
 		// sqrt(x)/x scaled to max u32
-		let fraction = (self.peers.len() as f64).powf(-0.5).mul(u32::max_value() as f64).round() as u32;
-		let small = self.peers.len() < MIN_PEERS_PROPAGATION;
+		let fraction = (peers.len() as f64).powf(-0.5).mul(u32::max_value() as f64).round() as u32;
+		let small = peers.len() < MIN_PEERS_PROPAGATION;
+		let now = io.now();
 
 		let lucky_peers = self.peers.iter_mut()
+			.filter(|&(peer_id, _)| peers.contains(peer_id))
 			.filter(|_| small || ::rand::random::<u32>() < fraction)
 			.take(MAX_PEERS_PROPAGATION)
 			.filter_map(|(peer_id, mut peer_info)| {
+				let hashes_only = policy.announce_hashes && peer_info.transaction_hashes;
+				let rate_limited = policy.max_per_peer_rate.is_some();
+				let limit = match policy.max_per_peer_rate {
+					Some(rate) => {
+						let rate = rate as f64;
+						peer_info.tx_allowance = (peer_info.tx_allowance + (now - peer_info.tx_allowance_time) * rate).min(rate);
+						peer_info.tx_allowance_time = now;
+						peer_info.tx_allowance as usize
+					},
+					None => transactions.len(),
+				};
+				if limit == 0 {
+					return None;
+				}
+
 				// Send all transactions
-				if peer_info.last_sent_transactions.is_empty() {
+				if peer_info.last_sent_transactions.is_empty() && !hashes_only && limit >= transactions.len() {
 					peer_info.last_sent_transactions = all_transactions_hashes.clone();
-					return Some((*peer_id, all_transactions_rlp.clone()));
+					if rate_limited {
+						peer_info.tx_allowance -= transactions.len() as f64;
+					}
+					return Some((*peer_id, TRANSACTIONS_PACKET, all_transactions_rlp.clone()));
 				}
 
 				// Get hashes of all transactions to send to this peer
@@ -1777,24 +2045,35 @@ impl ChainSync {
 					return None;
 				}
 
+				// Transactions over the rate limit are left for the next round
+				let sending = transactions.iter().filter(|tx| to_send.contains(&tx.hash())).take(limit).collect::<Vec<_>>();
+				if rate_limited {
+					peer_info.tx_allowance -= sending.len() as f64;
+				}
+				peer_info.last_sent_transactions = if sending.len() == to_send.len() {
+					all_transactions_hashes.clone()
+				} else {
+					all_transactions_hashes.difference(&to_send).cloned().chain(sending.iter().map(|tx| tx.hash())).collect()
+				};
+
 				// Construct RLP
-				let mut packet = RlpStream::new_list(to_send.len());
-				for tx in &transactions {
-					if to_send.contains(&tx.hash()) {
-						packet.append(tx);
+				let mut packet = RlpStream::new_list(sending.len());
+				for tx in &sending {
+					if hashes_only {
+						packet.append(&tx.hash());
+					} else {
+						packet.append(*tx);
 					}
 				}
-
-				peer_info.last_sent_transactions = all_transactions_hashes.clone();
-				Some((*peer_id, packet.out()))
+				Some((*peer_id, if hashes_only { TRANSACTION_HASHES_PACKET } else { TRANSACTIONS_PACKET }, packet.out()))
 			})
 			.collect::<Vec<_>>();
 
 		// Send RLPs
 		let sent = lucky_peers.len();
 		if sent > 0 {
-			for (peer_id, rlp) in lucky_peers.into_iter() {
-				self.send_packet(io, peer_id, TRANSACTIONS_PACKET, rlp);
+			for (peer_id, packet_id, rlp) in lucky_peers.into_iter() {
+				self.send_packet(io, peer_id, packet_id, rlp);
 			}
 
 			trace!(target: "sync", "Sent up to {} transactions to {} peers.", transactions.len(), sent);
@@ -1829,7 +2108,15 @@ impl ChainSync {
 	}
 
 	/// called when block is imported to chain - propagates the blocks and updates transactions sent to peers
-	pub fn chain_new_blocks(&mut self, io: &mut SyncIo, _imported: &[H256], invalid: &[H256], _enacted: &[H256], _retracted: &[H256], sealed: &[H256]) {
+	pub fn chain_new_blocks(&mut self, io: &mut SyncIo, imported: &[H256], invalid: &[H256], _enacted: &[H256], _retracted: &[H256], sealed: &[H256]) {
+		self.metrics.blocks.record(io.now(), imported.len());
+		for hash in imported {
+			if let Some(peer) = self.metrics.on_imported(hash) {
+				let number = io.chain().block_header(BlockID::Hash(hash.clone())).map_or(0, |h| HeaderView::new(&h).number());
+				let id = io.peer_session_info(peer).and_then(|info| info.id).map(|id| id.hex());
+				self.metrics.push_recent(hash.clone(), number, id);
+			}
+		}
 		if io.is_chain_queue_empty() {
 			self.propagate_latest_blocks(io, sealed);
 		}
@@ -1856,6 +2143,7 @@ mod tests {
 	use ethcore::header::*;
 	use ethcore::client::*;
 	use ethcore::miner::MinerService;
+	use ethcore::transaction::SignedTransaction;
 
 	fn get_dummy_block(order: u32, parent_hash: H256) -> Bytes {
 		let mut header = Header::new();
@@ -2049,10 +2337,15 @@ mod tests {
 				last_sent_transactions: HashSet::new(),
 				expired: false,
 				confirmation: super::ForkConfirmation::Confirmed,
+				fork_checks: 0,
+				fork_check_time: 0f64,
 				snapshot_number: None,
 				snapshot_hash: None,
 				asking_snapshot_data: None,
 				block_set: None,
+				transaction_hashes: false,
+				tx_allowance: 0f64,
+				tx_allowance_time: 0f64,
 			});
 		sync
 	}
@@ -2230,6 +2523,157 @@ mod tests {
 		assert_eq!(0x02, io.queue[1].packet_id);
 	}
 
+	#[test]
+	fn does_not_propagate_local_transactions_if_disabled() {
+		let mut client = TestBlockChainClient::new();
+		client.add_blocks(100, EachBlockWith::Uncle);
+		client.insert_local_transaction_to_queue();
+		let mut sync = dummy_sync_with_peer(client.block_hash_delta_minus(1), &client);
+		sync.tx_propagation.relay_local = false;
+		let mut queue = VecDeque::new();
+		let ss = TestSnapshotService::new();
+		{
+			let mut io = TestIo::new(&mut client, &ss, &mut queue, None);
+			assert_eq!(0, sync.propagate_new_transactions(&mut io));
+		}
+		client.insert_transaction_to_queue();
+		let mut io = TestIo::new(&mut client, &ss, &mut queue, None);
+		let peer_count = sync.propagate_new_transactions(&mut io);
+
+		assert_eq!(1, peer_count);
+		assert_eq!(1, io.queue.len());
+		// only the external transaction is sent
+		assert_eq!(1, UntrustedRlp::new(&io.queue[0].data).item_count());
+	}
+
+	#[test]
+	fn propagates_transactions_to_reserved_peers_only() {
+		let mut client = TestBlockChainClient::new();
+		client.add_blocks(100, EachBlockWith::Uncle);
+		client.insert_transaction_to_queue();
+		let mut sync = dummy_sync_with_peer(client.block_hash_delta_minus(1), &client);
+		sync.tx_propagation.reserved_only = true;
+		let mut queue = VecDeque::new();
+		let ss = TestSnapshotService::new();
+		{
+			let mut io = TestIo::new(&mut client, &ss, &mut queue, None);
+			assert_eq!(0, sync.propagate_new_transactions(&mut io));
+		}
+		let reserved = vec![0].into_iter().collect();
+		let mut io = TestIo::new(&mut client, &ss, &mut queue, None).with_reserved_peers(&reserved);
+		let peer_count = sync.propagate_new_transactions(&mut io);
+
+		assert_eq!(1, peer_count);
+		assert_eq!(1, io.queue.len());
+		assert_eq!(0x02, io.queue[0].packet_id);
+	}
+
+	#[test]
+	fn rate_limits_transactions_per_peer() {
+		let mut client = TestBlockChainClient::new();
+		client.add_blocks(100, EachBlockWith::Uncle);
+		client.insert_transaction_to_queue();
+		client.insert_transaction_to_queue();
+		let mut sync = dummy_sync_with_peer(client.block_hash_delta_minus(1), &client);
+		sync.tx_propagation.max_per_peer_rate = Some(1);
+		let mut queue = VecDeque::new();
+		let ss = TestSnapshotService::new();
+		{
+			let mut io = TestIo::new(&mut client, &ss, &mut queue, None).with_clock(10f64);
+			assert_eq!(1, sync.propagate_new_transactions(&mut io));
+			assert_eq!(0, sync.propagate_new_transactions(&mut io));
+		}
+		let mut io = TestIo::new(&mut client, &ss, &mut queue, None).with_clock(11f64);
+		assert_eq!(1, sync.propagate_new_transactions(&mut io));
+		assert_eq!(0, sync.propagate_new_transactions(&mut io));
+
+		// one transaction per packet
+		assert_eq!(2, io.queue.len());
+		assert_eq!(1, UntrustedRlp::new(&io.queue[0].data).item_count());
+		assert_eq!(1, UntrustedRlp::new(&io.queue[1].data).item_count());
+		assert!(io.queue[0].data != io.queue[1].data);
+	}
+
+	#[test]
+	fn accepts_transaction_hashes_from_warp_v2_peers_only() {
+		let mut client = TestBlockChainClient::new();
+		client.add_blocks(10, EachBlockWith::Nothing);
+		let chain_info = client.chain_info();
+		let ss = TestSnapshotService::new();
+
+		for &(version, hashes) in &[(1u8, false), (2u8, true)] {
+			let mut sync = ChainSync::new(SyncConfig::default(), &client);
+			let mut status = RlpStream::new_list(7);
+			status.append(&(version as u32));
+			status.append(&U256::from(1));
+			status.append(&chain_info.total_difficulty);
+			status.append(&chain_info.best_block_hash);
+			status.append(&chain_info.genesis_hash);
+			status.append(&H256::new());
+			status.append(&0u64);
+			let status = status.out();
+
+			let mut queue = VecDeque::new();
+			let mut io = TestIo::new(&mut client, &ss, &mut queue, Some(0)).with_warp_version(version);
+			sync.on_packet(&mut io, 0, super::STATUS_PACKET, &status);
+			assert_eq!(sync.peers[&0].transaction_hashes, hashes);
+		}
+	}
+
+	#[test]
+	fn announces_transaction_hashes() {
+		let mut client = TestBlockChainClient::new();
+		client.add_blocks(100, EachBlockWith::Uncle);
+		client.insert_transaction_to_queue();
+		let hash = client.pending_transactions()[0].hash();
+		let mut sync = dummy_sync_with_peer(client.block_hash_delta_minus(1), &client);
+		sync.tx_propagation.announce_hashes = true;
+		sync.peers.get_mut(&0).unwrap().transaction_hashes = true;
+		let mut queue = VecDeque::new();
+		let ss = TestSnapshotService::new();
+		let mut io = TestIo::new(&mut client, &ss, &mut queue, None);
+		assert_eq!(1, sync.propagate_new_transactions(&mut io));
+
+		assert_eq!(1, io.queue.len());
+		assert_eq!(super::TRANSACTION_HASHES_PACKET, io.queue[0].packet_id);
+		assert_eq!(vec![hash], UntrustedRlp::new(&io.queue[0].data).as_val::<Vec<H256>>().unwrap());
+
+		// the peer pulls the transaction body
+		let request = io.queue.pop_front().unwrap().data;
+		sync.on_packet(&mut io, 0, super::GET_TRANSACTIONS_PACKET, &request);
+		assert_eq!(1, io.queue.len());
+		assert_eq!(0x02, io.queue[0].packet_id);
+		assert_eq!(hash, UntrustedRlp::new(&io.queue[0].data).val_at::<SignedTransaction>(0).unwrap().hash());
+	}
+
+	#[test]
+	fn requests_unknown_announced_transactions() {
+		let mut client = TestBlockChainClient::new();
+		client.add_blocks(100, EachBlockWith::Uncle);
+		client.insert_transaction_to_queue();
+		let known = client.pending_transactions()[0].hash();
+		let unknown = H256::from(42);
+		let mut sync = dummy_sync_with_peer(client.block_hash_delta_minus(1), &client);
+		let mut queue = VecDeque::new();
+		let ss = TestSnapshotService::new();
+		let mut io = TestIo::new(&mut client, &ss, &mut queue, None);
+
+		let mut announcement = RlpStream::new_list(2);
+		announcement.append(&known);
+		announcement.append(&unknown);
+		let announcement = announcement.out();
+		sync.on_packet(&mut io, 0, super::TRANSACTION_HASHES_PACKET, &announcement);
+
+		assert_eq!(1, io.queue.len());
+		assert_eq!(super::GET_TRANSACTIONS_PACKET, io.queue[0].packet_id);
+		assert_eq!(vec![unknown], UntrustedRlp::new(&io.queue[0].data).as_val::<Vec<H256>>().unwrap());
+
+		// not requested again while the first request is pending
+		io.queue.clear();
+		sync.on_packet(&mut io, 0, super::TRANSACTION_HASHES_PACKET, &announcement);
+		assert!(io.queue.is_empty());
+	}
+
 	#[test]
 	fn handles_peer_new_block_malformed() {
 		let mut client = TestBlockChainClient::new();
@@ -2250,6 +2694,22 @@ mod tests {
 		assert!(result.is_err());
 	}
 
+	#[test]
+	fn reports_slow_peers() {
+		let mut client = TestBlockChainClient::new();
+		client.add_blocks(10, EachBlockWith::Uncle);
+		let mut queue = VecDeque::new();
+		let mut sync = dummy_sync_with_peer(client.block_hash_delta_minus(5), &client);
+		sync.peers.get_mut(&0).unwrap().asking = PeerAsking::BlockHeaders;
+		let ss = TestSnapshotService::new();
+		let mut io = TestIo::new(&mut client, &ss, &mut queue, None);
+
+		sync.maintain_peers(&mut io);
+
+		assert_eq!(io.reports, vec![(0, PeerEvent::SlowResponse)]);
+		assert!(io.to_disconnect.contains(&0));
+	}
+
 	#[test]
 	fn handles_peer_new_block() {
 		let mut client = TestBlockChainClient::new();
@@ -2411,6 +2871,32 @@ mod tests {
 		assert_eq!(status.transactions_in_future_queue, 0);
 	}
 
+	#[test]
+	fn imports_peer_transactions_when_synced() {
+		let source = TestBlockChainClient::new();
+		source.insert_transaction_to_queue();
+		let tx = source.pending_transactions()[0].clone();
+
+		let mut client = TestBlockChainClient::new();
+		client.add_blocks(100, EachBlockWith::Uncle);
+		client.set_balance(tx.sender().unwrap(), U256::from(10_000_000));
+		let mut sync = dummy_sync_with_peer(client.block_hash_delta_minus(1), &client);
+		let mut queue = VecDeque::new();
+		let ss = TestSnapshotService::new();
+		let mut io = TestIo::new(&mut client, &ss, &mut queue, None);
+		let mut packet = RlpStream::new_list(1);
+		packet.append(&tx);
+		let packet = packet.out();
+
+		sync.state = SyncState::Blocks;
+		sync.on_packet(&mut io, 0, super::TRANSACTIONS_PACKET, &packet);
+		assert!(io.chain.pending_transactions().is_empty());
+
+		sync.state = SyncState::Idle;
+		sync.on_packet(&mut io, 0, super::TRANSACTIONS_PACKET, &packet);
+		assert_eq!(vec![tx.hash()], io.chain.pending_transactions().into_iter().map(|t| t.hash()).collect::<Vec<_>>());
+	}
+
 	#[test]
 	fn should_not_add_transactions_to_queue_if_not_synced() {
 		// given
@@ -2438,4 +2924,36 @@ mod tests {
 		assert_eq!(status.transactions_in_pending_queue, 0);
 		assert_eq!(status.transactions_in_future_queue, 0);
 	}
+
+	#[test]
+	fn collects_download_metrics() {
+		let mut client = TestBlockChainClient::new();
+		client.add_blocks(10, EachBlockWith::Nothing);
+		let mut sync = dummy_sync_with_peer(client.block_hash_delta_minus(1), &client);
+		sync.peers.get_mut(&0).unwrap().asking = PeerAsking::BlockHeaders;
+		sync.peers.get_mut(&0).unwrap().ask_time = 1f64;
+
+		let hashes = vec![client.block_hash(BlockID::Number(5)).unwrap(), client.block_hash(BlockID::Number(6)).unwrap()];
+		let mut rlp = RlpStream::new_list(2);
+		rlp.append_raw(&client.block_header(BlockID::Number(5)).unwrap(), 1);
+		rlp.append_raw(&client.block_header(BlockID::Number(6)).unwrap(), 1);
+		let data = rlp.out();
+
+		let mut queue = VecDeque::new();
+		let ss = TestSnapshotService::new();
+		let mut io = TestIo::new(&mut client, &ss, &mut queue, None).with_clock(3f64);
+		sync.on_packet(&mut io, 0, BLOCK_HEADERS_PACKET, &data);
+		sync.chain_new_blocks(&mut io, &hashes[0..1], &[], &[], &[], &[]);
+
+		let stats = sync.stats(&io);
+		assert_eq!(stats.headers_total, 2);
+		assert_eq!(stats.blocks_total, 1);
+		assert_eq!(stats.peers.len(), 1);
+		assert_eq!(stats.peers[0].responses, 1);
+		assert_eq!(stats.peers[0].last_latency_ms, 2000);
+		assert_eq!(stats.peers[0].blocks_supplied, 1);
+		assert_eq!(stats.recent_blocks.len(), 1);
+		assert_eq!(stats.recent_blocks[0].hash, hashes[0]);
+		assert_eq!(stats.recent_blocks[0].number, 5);
+	}
 }
@@ -45,12 +45,18 @@ extern crate ethcore_util as util;
 extern crate heapsize;
 #[macro_use]
 extern crate ethcore_ipc as ipc;
+#[cfg(test)]
+extern crate ethcore_devtools as devtools;
 
 mod chain;
+mod metrics;
 mod blocks;
 mod block_sync;
+mod ancient_sync;
 mod sync_io;
 mod snapshot;
+mod light_sync;
+mod light_protocol;
 
 #[cfg(test)]
 mod tests;
@@ -60,9 +66,13 @@ mod api {
 	include!(concat!(env!("OUT_DIR"), "/api.rs"));
 }
 
-pub use api::{EthSync, SyncProvider, SyncClient, NetworkManagerClient, ManageNetwork, SyncConfig,
-	ServiceConfiguration, NetworkConfiguration, PeerInfo, AllowIP};
+pub use api::{EthSync, SyncProvider, SyncClient, NetworkManagerClient, ManageNetwork, SyncConfig, ForkCheckpoints, TransactionPropagation,
+	ServiceConfiguration, NetworkConfiguration, PeerInfo, PeerTrafficInfo, PeerProtocolTrafficInfo, BannedPeerInfo, NatStatusInfo, AllowIP, NatMethod,
+	SyncStats, PeerSyncStats, BlockSource, MAX_FORK_CHECKPOINTS};
 pub use chain::{SyncStatus, SyncState};
+pub use light_sync::EthLightSync;
+pub use light_protocol::{LightProtocol, Provider as LightProvider, Request as LightRequest, Response as LightResponse,
+	ProofRequest, CodeRequest, HeadersStart, FlowParams, MAX_TRANSACTION_PROOF_GAS};
 pub use network::{is_valid_node_url, NonReservedPeerMode, NetworkError};
 
 /// IPC interfaces
@@ -0,0 +1,284 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Flow control for the light protocol.
+//!
+//! Every peer a node serves gets a credit buffer with a fixed limit which
+//! recharges at a constant rate. Each request has a cost, taken from a cost
+//! table the server announces in its status, and is deducted from the buffer
+//! before it's served. Clients mirror the server's bookkeeping so they never
+//! send requests they can't afford; servers disable peers which do.
+
+use rlp::{DecoderError, RlpStream, Stream, UntrustedRlp, View};
+use time;
+use util::{U256, Uint};
+
+fn saturating_add(a: U256, b: U256) -> U256 {
+	match a.overflowing_add(b) {
+		(_, true) => U256::max_value(),
+		(val, false) => val,
+	}
+}
+
+fn saturating_mul(a: U256, b: U256) -> U256 {
+	match a.overflowing_mul(b) {
+		(_, true) => U256::max_value(),
+		(val, false) => val,
+	}
+}
+
+/// The kinds of request a client can make, for costing purposes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+	/// Block headers, costed per header.
+	Headers,
+	/// Block receipts, costed per block.
+	Receipts,
+	/// Account or storage proofs, costed per proof.
+	StateProofs,
+	/// Contract code, costed per contract.
+	Codes,
+	/// A transaction execution proof, costed per thousand gas.
+	TransactionProof,
+}
+
+/// The cost of a request: a base cost plus a cost per item requested.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cost(pub U256, pub U256);
+
+impl Cost {
+	fn for_amount(&self, amount: usize) -> U256 {
+		saturating_add(self.0, saturating_mul(self.1, U256::from(amount)))
+	}
+}
+
+/// Costs of each kind of request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostTable {
+	/// Cost of a headers request.
+	pub headers: Cost,
+	/// Cost of a receipts request.
+	pub receipts: Cost,
+	/// Cost of a state proofs request.
+	pub state_proofs: Cost,
+	/// Cost of a contract code request.
+	pub codes: Cost,
+	/// Cost of a transaction proof request.
+	pub transaction_proof: Cost,
+}
+
+impl Default for CostTable {
+	fn default() -> Self {
+		CostTable {
+			headers: Cost(100_000.into(), 10_000.into()),
+			receipts: Cost(100_000.into(), 20_000.into()),
+			state_proofs: Cost(100_000.into(), 40_000.into()),
+			codes: Cost(100_000.into(), 20_000.into()),
+			transaction_proof: Cost(500_000.into(), 1_000.into()),
+		}
+	}
+}
+
+impl CostTable {
+	fn get(&self, kind: RequestKind) -> &Cost {
+		match kind {
+			RequestKind::Headers => &self.headers,
+			RequestKind::Receipts => &self.receipts,
+			RequestKind::StateProofs => &self.state_proofs,
+			RequestKind::Codes => &self.codes,
+			RequestKind::TransactionProof => &self.transaction_proof,
+		}
+	}
+}
+
+/// A peer's credit buffer, as estimated by either side of the connection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credits {
+	estimate: U256,
+	recharge_point: f64,
+}
+
+impl Credits {
+	/// Current estimate of the buffer value.
+	pub fn current(&self) -> U256 {
+		self.estimate
+	}
+
+	/// Overwrite the estimate with a value reported by the server,
+	/// which is authoritative as of the moment it answered.
+	pub fn update_to(&mut self, value: U256) {
+		self.estimate = value;
+		self.recharge_point = time::precise_time_s();
+	}
+
+	/// Attempt to deduct the given cost. Leaves the buffer untouched and
+	/// fails if there aren't enough credits.
+	pub fn deduct_cost(&mut self, cost: U256) -> Result<(), ()> {
+		if cost > self.estimate {
+			Err(())
+		} else {
+			self.estimate = self.estimate - cost;
+			Ok(())
+		}
+	}
+}
+
+/// Flow control parameters a server serves a peer under.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowParams {
+	limit: U256,
+	recharge: U256,
+	costs: CostTable,
+}
+
+impl Default for FlowParams {
+	fn default() -> Self {
+		FlowParams::new(50_000_000.into(), CostTable::default(), 100_000.into())
+	}
+}
+
+impl FlowParams {
+	/// Create new flow parameters: the buffer limit, the cost table and the
+	/// number of credits recharged per second.
+	pub fn new(limit: U256, costs: CostTable, recharge: U256) -> Self {
+		FlowParams {
+			limit: limit,
+			recharge: recharge,
+			costs: costs,
+		}
+	}
+
+	/// Maximum credits a buffer can hold.
+	pub fn limit(&self) -> &U256 { &self.limit }
+
+	/// Credits recharged per second.
+	pub fn recharge_rate(&self) -> &U256 { &self.recharge }
+
+	/// Create a full credit buffer.
+	pub fn create_credits(&self) -> Credits {
+		Credits {
+			estimate: self.limit,
+			recharge_point: time::precise_time_s(),
+		}
+	}
+
+	/// Compute the cost of requesting `amount` items of the given kind.
+	pub fn compute_cost(&self, kind: RequestKind, amount: usize) -> U256 {
+		self.costs.get(kind).for_amount(amount)
+	}
+
+	/// Recharge the buffer for the time elapsed since it was last recharged.
+	pub fn recharge(&self, credits: &mut Credits) {
+		let now = time::precise_time_s();
+		let elapsed = if now > credits.recharge_point { now - credits.recharge_point } else { 0.0 };
+		// recharge in whole milliseconds to stay in integer arithmetic.
+		let elapsed_ms = U256::from((elapsed * 1000.0) as u64);
+		let recharged = saturating_mul(self.recharge, elapsed_ms) / U256::from(1000);
+
+		credits.estimate = ::std::cmp::min(self.limit, saturating_add(credits.estimate, recharged));
+		credits.recharge_point = now;
+	}
+
+	/// Append the parameters to an RLP stream as a list.
+	pub fn append_to(&self, s: &mut RlpStream) {
+		let costs = [
+			&self.costs.headers,
+			&self.costs.receipts,
+			&self.costs.state_proofs,
+			&self.costs.codes,
+			&self.costs.transaction_proof,
+		];
+
+		s.begin_list(3);
+		s.append(&self.limit);
+		s.append(&self.recharge);
+		s.begin_list(costs.len());
+		for cost in &costs {
+			s.begin_list(2).append(&cost.0).append(&cost.1);
+		}
+	}
+
+	/// Decode parameters encoded by `append_to`.
+	pub fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+		fn cost(rlp: &UntrustedRlp, i: usize) -> Result<Cost, DecoderError> {
+			let item = try!(rlp.at(i));
+			Ok(Cost(try!(item.val_at(0)), try!(item.val_at(1))))
+		}
+
+		let costs = try!(rlp.at(2));
+		Ok(FlowParams {
+			limit: try!(rlp.val_at(0)),
+			recharge: try!(rlp.val_at(1)),
+			costs: CostTable {
+				headers: try!(cost(&costs, 0)),
+				receipts: try!(cost(&costs, 1)),
+				state_proofs: try!(cost(&costs, 2)),
+				codes: try!(cost(&costs, 3)),
+				transaction_proof: try!(cost(&costs, 4)),
+			},
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rlp::{RlpStream, UntrustedRlp};
+	use util::U256;
+
+	#[test]
+	fn should_compute_cost() {
+		let params = FlowParams::default();
+		assert_eq!(params.compute_cost(RequestKind::Headers, 10), U256::from(200_000));
+		assert_eq!(params.compute_cost(RequestKind::StateProofs, 0), U256::from(100_000));
+	}
+
+	#[test]
+	fn should_deduct_only_affordable_costs() {
+		let params = FlowParams::new(100.into(), CostTable::default(), 0.into());
+		let mut credits = params.create_credits();
+		assert!(credits.deduct_cost(60.into()).is_ok());
+		assert!(credits.deduct_cost(60.into()).is_err());
+		assert_eq!(credits.current(), U256::from(40));
+	}
+
+	#[test]
+	fn should_recharge_up_to_limit() {
+		let params = FlowParams::new(1000.into(), CostTable::default(), 100.into());
+		let mut credits = params.create_credits();
+		credits.deduct_cost(1000.into()).unwrap();
+
+		credits.recharge_point -= 5.0;
+		params.recharge(&mut credits);
+		let recharged = credits.current();
+		assert!(recharged >= U256::from(500) && recharged < U256::from(510));
+
+		credits.recharge_point -= 60.0;
+		params.recharge(&mut credits);
+		assert_eq!(credits.current(), U256::from(1000));
+	}
+
+	#[test]
+	fn should_encode_and_decode() {
+		let params = FlowParams::default();
+		let mut stream = RlpStream::new();
+		params.append_to(&mut stream);
+		let encoded = stream.out();
+
+		let decoded = FlowParams::decode(&UntrustedRlp::new(&encoded)).unwrap();
+		assert_eq!(decoded, params);
+	}
+}
@@ -0,0 +1,743 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Light client subprotocol.
+//!
+//! Lets light clients fetch headers, receipts, merkle proofs of state, contract
+//! code and transaction execution proofs on demand from full nodes which opt in
+//! to serving them. Every request carries an id which is echoed in the response
+//! along with the client's remaining flow control credits (see `flow_control`).
+//!
+//! Packet layouts:
+//!
+//! ```text
+//! Status:              [ version, network_id, head_td, head_hash, head_num, genesis_hash, flow_params | [] ]
+//! Announce:            [ head_hash, head_num, head_td ]
+//! GetBlockHeaders:     [ req_id, block: { P, B_32 }, max, skip, reverse ]
+//! GetReceipts:         [ req_id, [ block_hash, ... ] ]
+//! GetProofs:           [ req_id, [ [ block_hash, account_key, [ key ] | [], from_level ], ... ] ]
+//! GetCode:             [ req_id, [ [ block_hash, account_key ], ... ] ]
+//! GetTransactionProof: [ req_id, block_hash, transaction ]
+//! Responses:           [ req_id, credits, [ item, ... ] ]
+//! ```
+
+mod flow_control;
+mod provider;
+
+use std::cmp::min;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender, Receiver};
+
+use ethcore::client::BlockID;
+use ethcore::header::BlockNumber;
+use ethcore::transaction::SignedTransaction;
+use ethcore::views::HeaderView;
+use io::TimerToken;
+use network::{NetworkProtocolHandler, NetworkContext, PeerId, PacketId, ProtocolId};
+use parking_lot::{Mutex, RwLock};
+use rlp::{DecoderError, RlpStream, Stream, UntrustedRlp, View};
+use time;
+use util::{Bytes, H256, U256, Uint};
+
+use chain::MAX_HEADERS_TO_SEND;
+
+pub use self::flow_control::{FlowParams, CostTable, Cost, Credits, RequestKind};
+pub use self::provider::Provider;
+
+/// Light protocol subprotocol id.
+pub const LIGHT_PROTOCOL_ID: ProtocolId = *b"plp";
+/// Current light protocol version.
+pub const LIGHT_PROTOCOL_VERSION: u8 = 1;
+/// Number of packets in the light protocol.
+pub const LIGHT_PACKET_COUNT: u8 = 0x0c;
+
+const STATUS_PACKET: u8 = 0x00;
+const ANNOUNCE_PACKET: u8 = 0x01;
+const GET_BLOCK_HEADERS_PACKET: u8 = 0x02;
+const BLOCK_HEADERS_PACKET: u8 = 0x03;
+const GET_RECEIPTS_PACKET: u8 = 0x04;
+const RECEIPTS_PACKET: u8 = 0x05;
+const GET_PROOFS_PACKET: u8 = 0x06;
+const PROOFS_PACKET: u8 = 0x07;
+const GET_CODE_PACKET: u8 = 0x08;
+const CODE_PACKET: u8 = 0x09;
+const GET_TRANSACTION_PROOF_PACKET: u8 = 0x0a;
+const TRANSACTION_PROOF_PACKET: u8 = 0x0b;
+
+/// Maximum number of items served for a single receipts, proofs or code request.
+const MAX_REQUEST_ITEMS: usize = 256;
+/// Transactions using more gas than this won't be proven.
+pub const MAX_TRANSACTION_PROOF_GAS: u64 = 10_000_000;
+/// Time to wait for a response before giving up on a request.
+const REQUEST_TIMEOUT_SEC: f64 = 10.0;
+
+const TICK_TIMER: TimerToken = 0;
+
+/// Where a headers request should start.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeadersStart {
+	/// Start at the block with this hash.
+	Hash(H256),
+	/// Start at the canonical block with this number.
+	Number(BlockNumber),
+}
+
+/// A request for a merkle proof of an account or of one of its storage values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProofRequest {
+	/// Hash of the block whose state should be used.
+	pub block: H256,
+	/// Hash of the account's address.
+	pub account_key: H256,
+	/// Hash of the storage key, or `None` to prove the account itself.
+	pub key: Option<H256>,
+	/// Trie depth to start the proof from; nodes above it are assumed known.
+	pub from_level: u32,
+}
+
+/// A request for an account's code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeRequest {
+	/// Hash of the block whose state should be used.
+	pub block: H256,
+	/// Hash of the account's address.
+	pub account_key: H256,
+}
+
+/// A request a light client can make of a serving peer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+	/// Block headers.
+	Headers {
+		/// The first header.
+		start: HeadersStart,
+		/// Maximum number of headers.
+		max: usize,
+		/// Number of blocks to skip between headers.
+		skip: u64,
+		/// Whether to walk towards the genesis.
+		reverse: bool,
+	},
+	/// Receipts of the blocks with the given hashes.
+	Receipts(Vec<H256>),
+	/// State proofs.
+	Proofs(Vec<ProofRequest>),
+	/// Contract code.
+	Codes(Vec<CodeRequest>),
+	/// The state needed to execute a transaction on top of a block.
+	TransactionProof {
+		/// Hash of the block whose state should be used.
+		block: H256,
+		/// The transaction to execute.
+		transaction: SignedTransaction,
+	},
+}
+
+impl Request {
+	fn kind(&self) -> RequestKind {
+		match *self {
+			Request::Headers { .. } => RequestKind::Headers,
+			Request::Receipts(_) => RequestKind::Receipts,
+			Request::Proofs(_) => RequestKind::StateProofs,
+			Request::Codes(_) => RequestKind::Codes,
+			Request::TransactionProof { .. } => RequestKind::TransactionProof,
+		}
+	}
+
+	fn amount(&self) -> usize {
+		match *self {
+			Request::Headers { max, .. } => max,
+			Request::Receipts(ref hashes) => hashes.len(),
+			Request::Proofs(ref reqs) => reqs.len(),
+			Request::Codes(ref reqs) => reqs.len(),
+			Request::TransactionProof { ref transaction, .. } => transaction_proof_amount(transaction),
+		}
+	}
+
+	fn packet_id(&self) -> PacketId {
+		match *self {
+			Request::Headers { .. } => GET_BLOCK_HEADERS_PACKET,
+			Request::Receipts(_) => GET_RECEIPTS_PACKET,
+			Request::Proofs(_) => GET_PROOFS_PACKET,
+			Request::Codes(_) => GET_CODE_PACKET,
+			Request::TransactionProof { .. } => GET_TRANSACTION_PROOF_PACKET,
+		}
+	}
+
+	fn encode(&self, req_id: usize) -> Bytes {
+		let mut s;
+		match *self {
+			Request::Headers { ref start, max, skip, reverse } => {
+				s = RlpStream::new_list(5);
+				s.append(&req_id);
+				match *start {
+					HeadersStart::Hash(ref hash) => s.append(hash),
+					HeadersStart::Number(ref number) => s.append(number),
+				};
+				s.append(&max).append(&skip).append(&reverse);
+			}
+			Request::Receipts(ref hashes) => {
+				s = RlpStream::new_list(2);
+				s.append(&req_id).append(hashes);
+			}
+			Request::Proofs(ref reqs) => {
+				s = RlpStream::new_list(2);
+				s.append(&req_id).begin_list(reqs.len());
+				for req in reqs {
+					s.begin_list(4).append(&req.block).append(&req.account_key).append(&req.key).append(&req.from_level);
+				}
+			}
+			Request::Codes(ref reqs) => {
+				s = RlpStream::new_list(2);
+				s.append(&req_id).begin_list(reqs.len());
+				for req in reqs {
+					s.begin_list(2).append(&req.block).append(&req.account_key);
+				}
+			}
+			Request::TransactionProof { ref block, ref transaction } => {
+				s = RlpStream::new_list(3);
+				s.append(&req_id).append(block).append(transaction);
+			}
+		}
+		s.out()
+	}
+}
+
+// transaction proofs are costed per thousand gas.
+fn transaction_proof_amount(t: &SignedTransaction) -> usize {
+	(min(t.gas.low_u64(), MAX_TRANSACTION_PROOF_GAS) / 1000) as usize + 1
+}
+
+/// A response to a `Request`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+	/// RLP-encoded block headers.
+	Headers(Vec<Bytes>),
+	/// RLP-encoded receipts of each requested block. Empty lists for unknown blocks.
+	Receipts(Vec<Bytes>),
+	/// Proof nodes for each requested proof. Empty for unavailable state.
+	Proofs(Vec<Vec<Bytes>>),
+	/// Code of each requested account. Empty for unavailable state.
+	Codes(Vec<Bytes>),
+	/// State trie nodes for executing the transaction. Empty for unavailable state.
+	TransactionProof(Vec<Bytes>),
+}
+
+/// A connected peer which has sent its status.
+struct Peer {
+	head_hash: H256,
+	head_num: BlockNumber,
+	head_td: U256,
+	/// Flow control parameters the peer serves us under, along with our
+	/// estimate of our credits with it. `None` if it doesn't serve.
+	remote_flow: Option<(FlowParams, Credits)>,
+	/// The peer's credits with us, if we serve.
+	local_credits: Option<Credits>,
+}
+
+/// A request awaiting a response.
+struct Pending {
+	peer: PeerId,
+	kind: RequestKind,
+	sent: f64,
+	sender: Sender<Response>,
+}
+
+/// Light protocol handler. Serves requests from the `Provider` if configured
+/// with flow control parameters, and makes requests of serving peers.
+/// See module documentation for the wire format.
+pub struct LightProtocol<P: ?Sized + Provider> {
+	provider: Arc<P>,
+	network_id: U256,
+	genesis_hash: H256,
+	flow_params: Option<FlowParams>,
+	peers: RwLock<HashMap<PeerId, Peer>>,
+	pending: Mutex<HashMap<usize, Pending>>,
+	next_req_id: AtomicUsize,
+}
+
+impl<P: ?Sized + Provider> LightProtocol<P> {
+	/// Create a new handler. Requests are served under `flow_params` if given;
+	/// otherwise incoming requests are ignored.
+	pub fn new(provider: Arc<P>, network_id: U256, flow_params: Option<FlowParams>) -> Self {
+		let genesis_hash = provider.chain_info().genesis_hash;
+		LightProtocol {
+			provider: provider,
+			network_id: network_id,
+			genesis_hash: genesis_hash,
+			flow_params: flow_params,
+			peers: RwLock::new(HashMap::new()),
+			pending: Mutex::new(HashMap::new()),
+			next_req_id: AtomicUsize::new(0),
+		}
+	}
+
+	/// Number of connected peers which serve requests.
+	pub fn serving_peers(&self) -> usize {
+		self.peers.read().values().filter(|p| p.remote_flow.is_some()).count()
+	}
+
+	/// Highest block number announced by a serving peer.
+	pub fn best_serving_block(&self) -> Option<BlockNumber> {
+		self.peers.read().values().filter(|p| p.remote_flow.is_some()).map(|p| p.head_num).max()
+	}
+
+	/// Send a request about block `block_number` to a serving peer whose head is at least that
+	/// high and which can afford it. The response arrives on the returned channel; the channel
+	/// is closed without a response if the request times out or the peer disconnects.
+	/// Returns `None` if no peer could be asked.
+	pub fn request(&self, io: &NetworkContext, request: Request, block_number: BlockNumber) -> Option<Receiver<Response>> {
+		let kind = request.kind();
+		let amount = request.amount();
+		let peer_id = {
+			let mut peers = self.peers.write();
+			let mut chosen = None;
+			for (id, peer) in peers.iter_mut().filter(|&(_, ref peer)| peer.head_num >= block_number) {
+				if let Some((ref params, ref mut credits)) = peer.remote_flow {
+					params.recharge(credits);
+					if credits.deduct_cost(params.compute_cost(kind, amount)).is_ok() {
+						chosen = Some(*id);
+						break;
+					}
+				}
+			}
+			match chosen {
+				Some(id) => id,
+				None => {
+					debug!(target: "sync", "No light protocol peer can serve {:?} request for block {}", kind, block_number);
+					return None;
+				}
+			}
+		};
+
+		let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+		if let Err(e) = io.send(peer_id, request.packet_id(), request.encode(req_id)) {
+			debug!(target: "sync", "Error sending light request to {}: {:?}", peer_id, e);
+			return None;
+		}
+
+		let (sender, receiver) = mpsc::channel();
+		self.pending.lock().insert(req_id, Pending {
+			peer: peer_id,
+			kind: kind,
+			sent: time::precise_time_s(),
+			sender: sender,
+		});
+		Some(receiver)
+	}
+
+	/// Announce our best block to all connected peers.
+	pub fn announce(&self, io: &NetworkContext) {
+		let info = self.provider.chain_info();
+		let mut s = RlpStream::new_list(3);
+		s.append(&info.best_block_hash).append(&info.best_block_number).append(&info.total_difficulty);
+		let data = s.out();
+		for peer in self.peers.read().keys() {
+			io.send(*peer, ANNOUNCE_PACKET, data.clone())
+				.unwrap_or_else(|e| debug!(target: "sync", "Error sending announcement to {}: {:?}", peer, e));
+		}
+	}
+
+	fn send_status(&self, io: &NetworkContext, peer: PeerId) {
+		let info = self.provider.chain_info();
+		let mut s = RlpStream::new_list(7);
+		s.append(&(LIGHT_PROTOCOL_VERSION as u32));
+		s.append(&self.network_id);
+		s.append(&info.total_difficulty);
+		s.append(&info.best_block_hash);
+		s.append(&info.best_block_number);
+		s.append(&self.genesis_hash);
+		match self.flow_params {
+			Some(ref params) => params.append_to(&mut s),
+			None => { s.begin_list(0); },
+		}
+		io.send(peer, STATUS_PACKET, s.out())
+			.unwrap_or_else(|e| debug!(target: "sync", "Error sending light status to {}: {:?}", peer, e));
+	}
+
+	fn on_status(&self, io: &NetworkContext, peer: PeerId, r: &UntrustedRlp) -> Result<(), DecoderError> {
+		let version: u32 = try!(r.val_at(0));
+		let network_id: U256 = try!(r.val_at(1));
+		let genesis_hash: H256 = try!(r.val_at(5));
+		if version != LIGHT_PROTOCOL_VERSION as u32 || network_id != self.network_id || genesis_hash != self.genesis_hash {
+			trace!(target: "sync", "{}: Light peer on a different protocol version, network or chain; disabling", peer);
+			io.disable_peer(peer);
+			return Ok(());
+		}
+
+		let flow = try!(r.at(6));
+		let remote_flow = if flow.item_count() == 0 {
+			None
+		} else {
+			let params = try!(FlowParams::decode(&flow));
+			let credits = params.create_credits();
+			Some((params, credits))
+		};
+
+		trace!(target: "sync", "{}: Light peer status, serving: {}", peer, remote_flow.is_some());
+		self.peers.write().insert(peer, Peer {
+			head_td: try!(r.val_at(2)),
+			head_hash: try!(r.val_at(3)),
+			head_num: try!(r.val_at(4)),
+			remote_flow: remote_flow,
+			local_credits: self.flow_params.as_ref().map(FlowParams::create_credits),
+		});
+		Ok(())
+	}
+
+	fn on_announce(&self, peer: PeerId, r: &UntrustedRlp) -> Result<(), DecoderError> {
+		if let Some(info) = self.peers.write().get_mut(&peer) {
+			info.head_hash = try!(r.val_at(0));
+			info.head_num = try!(r.val_at(1));
+			info.head_td = try!(r.val_at(2));
+		}
+		Ok(())
+	}
+
+	/// Charge a peer for a request, disabling it if it can't pay.
+	/// Returns the peer's remaining credits, or `None` if the request shouldn't be served.
+	fn charge(&self, io: &NetworkContext, peer: PeerId, kind: RequestKind, amount: usize) -> Option<U256> {
+		let params = match self.flow_params {
+			Some(ref params) => params,
+			None => {
+				trace!(target: "sync", "{}: Ignoring light request; not serving", peer);
+				return None;
+			}
+		};
+		let mut peers = self.peers.write();
+		let credits = match peers.get_mut(&peer).and_then(|p| p.local_credits.as_mut()) {
+			Some(credits) => credits,
+			None => {
+				debug!(target: "sync", "{}: Light request before status", peer);
+				return None;
+			}
+		};
+
+		params.recharge(credits);
+		match credits.deduct_cost(params.compute_cost(kind, amount)) {
+			Ok(()) => Some(credits.current()),
+			Err(()) => {
+				debug!(target: "sync", "{}: Light request exceeds flow control credits; disabling", peer);
+				io.disable_peer(peer);
+				None
+			}
+		}
+	}
+
+	fn respond(io: &NetworkContext, packet_id: PacketId, s: RlpStream) {
+		io.respond(packet_id, s.out())
+			.unwrap_or_else(|e| debug!(target: "sync", "Error sending light response: {:?}", e));
+	}
+
+	fn response_stream(req_id: usize, credits: U256) -> RlpStream {
+		let mut s = RlpStream::new_list(3);
+		s.append(&req_id).append(&credits);
+		s
+	}
+
+	fn get_block_headers(&self, io: &NetworkContext, peer: PeerId, r: &UntrustedRlp) -> Result<(), DecoderError> {
+		let req_id: usize = try!(r.val_at(0));
+		let max = min(MAX_HEADERS_TO_SEND, try!(r.val_at::<usize>(2)));
+		let skip: u64 = try!(r.val_at(3));
+		let reverse: bool = try!(r.val_at(4));
+		let credits = match self.charge(io, peer, RequestKind::Headers, max) {
+			Some(credits) => credits,
+			None => return Ok(()),
+		};
+
+		let mut headers = Vec::new();
+		let start = if try!(r.at(1)).size() == 32 {
+			let hash: H256 = try!(r.val_at(1));
+			match self.provider.block_header(BlockID::Hash(hash.clone())) {
+				Some(header) => {
+					let number = HeaderView::new(&header).number();
+					let canonical = self.provider.block_header(BlockID::Number(number))
+						.map_or(false, |h| HeaderView::new(&h).hash() == hash);
+					if !canonical {
+						// only canonical chains can be walked by number.
+						headers.push(header);
+					}
+					Some(number)
+				}
+				None => None,
+			}
+		} else {
+			Some(try!(r.val_at::<BlockNumber>(1)))
+		};
+
+		if let (Some(mut number), true) = (start, headers.is_empty()) {
+			let best = self.provider.chain_info().best_block_number;
+			let inc = skip + 1;
+			while headers.len() < max && number <= best {
+				match self.provider.block_header(BlockID::Number(number)) {
+					Some(header) => headers.push(header),
+					None => break,
+				}
+				if reverse {
+					if number < inc {
+						break;
+					}
+					number -= inc;
+				} else {
+					number += inc;
+				}
+			}
+		}
+
+		trace!(target: "sync", "{} -> Light GetBlockHeaders: returning {} headers", peer, headers.len());
+		let mut s = Self::response_stream(req_id, credits);
+		s.begin_list(headers.len());
+		for header in &headers {
+			s.append_raw(header, 1);
+		}
+		Self::respond(io, BLOCK_HEADERS_PACKET, s);
+		Ok(())
+	}
+
+	fn get_receipts(&self, io: &NetworkContext, peer: PeerId, r: &UntrustedRlp) -> Result<(), DecoderError> {
+		let req_id: usize = try!(r.val_at(0));
+		let mut hashes: Vec<H256> = try!(r.val_at(1));
+		hashes.truncate(MAX_REQUEST_ITEMS);
+		let credits = match self.charge(io, peer, RequestKind::Receipts, hashes.len()) {
+			Some(credits) => credits,
+			None => return Ok(()),
+		};
+
+		let mut s = Self::response_stream(req_id, credits);
+		s.begin_list(hashes.len());
+		for hash in &hashes {
+			match self.provider.block_receipts(hash) {
+				Some(receipts) => { s.append_raw(&receipts, 1); },
+				None => { s.begin_list(0); },
+			}
+		}
+		Self::respond(io, RECEIPTS_PACKET, s);
+		Ok(())
+	}
+
+	fn get_proofs(&self, io: &NetworkContext, peer: PeerId, r: &UntrustedRlp) -> Result<(), DecoderError> {
+		let req_id: usize = try!(r.val_at(0));
+		let list = try!(r.at(1));
+		let count = min(list.item_count(), MAX_REQUEST_ITEMS);
+		let credits = match self.charge(io, peer, RequestKind::StateProofs, count) {
+			Some(credits) => credits,
+			None => return Ok(()),
+		};
+
+		let mut s = Self::response_stream(req_id, credits);
+		s.begin_list(count);
+		for i in 0..count {
+			let req = try!(list.at(i));
+			let block: H256 = try!(req.val_at(0));
+			let account_key: H256 = try!(req.val_at(1));
+			let key: Option<H256> = try!(req.val_at(2));
+			let from_level: u32 = try!(req.val_at(3));
+
+			let proof = match key {
+				Some(key) => self.provider.storage_proof(block, account_key, key, from_level),
+				None => self.provider.account_proof(block, account_key, from_level),
+			};
+			s.append(&proof.unwrap_or_else(Vec::new));
+		}
+		Self::respond(io, PROOFS_PACKET, s);
+		Ok(())
+	}
+
+	fn get_code(&self, io: &NetworkContext, peer: PeerId, r: &UntrustedRlp) -> Result<(), DecoderError> {
+		let req_id: usize = try!(r.val_at(0));
+		let list = try!(r.at(1));
+		let count = min(list.item_count(), MAX_REQUEST_ITEMS);
+		let credits = match self.charge(io, peer, RequestKind::Codes, count) {
+			Some(credits) => credits,
+			None => return Ok(()),
+		};
+
+		let mut s = Self::response_stream(req_id, credits);
+		s.begin_list(count);
+		for i in 0..count {
+			let req = try!(list.at(i));
+			let code = self.provider.contract_code(try!(req.val_at(0)), try!(req.val_at(1)));
+			s.append(&code.unwrap_or_else(Bytes::new));
+		}
+		Self::respond(io, CODE_PACKET, s);
+		Ok(())
+	}
+
+	fn get_transaction_proof(&self, io: &NetworkContext, peer: PeerId, r: &UntrustedRlp) -> Result<(), DecoderError> {
+		let req_id: usize = try!(r.val_at(0));
+		let block: H256 = try!(r.val_at(1));
+		let transaction: SignedTransaction = try!(r.val_at(2));
+		let credits = match self.charge(io, peer, RequestKind::TransactionProof, transaction_proof_amount(&transaction)) {
+			Some(credits) => credits,
+			None => return Ok(()),
+		};
+
+		let proof = if transaction.gas > U256::from(MAX_TRANSACTION_PROOF_GAS) {
+			trace!(target: "sync", "{}: Refusing to prove transaction with gas {}", peer, transaction.gas);
+			Vec::new()
+		} else {
+			self.provider.transaction_proof(transaction, block).unwrap_or_else(Vec::new)
+		};
+
+		let mut s = Self::response_stream(req_id, credits);
+		s.append(&proof);
+		Self::respond(io, TRANSACTION_PROOF_PACKET, s);
+		Ok(())
+	}
+
+	fn on_response<F>(&self, peer: PeerId, kind: RequestKind, r: &UntrustedRlp, decode: F) -> Result<(), DecoderError>
+		where F: FnOnce(&UntrustedRlp) -> Result<Response, DecoderError>
+	{
+		let req_id: usize = try!(r.val_at(0));
+		let credits: U256 = try!(r.val_at(1));
+
+		let pending = {
+			let mut pending = self.pending.lock();
+			match pending.get(&req_id) {
+				Some(p) if p.peer == peer && p.kind == kind => {},
+				_ => {
+					debug!(target: "sync", "{}: Unexpected light response {} ({:?})", peer, req_id, kind);
+					return Ok(());
+				}
+			}
+			pending.remove(&req_id).expect("presence checked above; qed")
+		};
+
+		if let Some(&mut (_, ref mut remote_credits)) = self.peers.write().get_mut(&peer).and_then(|p| p.remote_flow.as_mut()) {
+			remote_credits.update_to(credits);
+		}
+
+		let response = try!(decode(&try!(r.at(2))));
+		// the requester may have given up waiting.
+		let _ = pending.sender.send(response);
+		Ok(())
+	}
+
+	/// Handle an incoming packet.
+	pub fn handle_packet(&self, io: &NetworkContext, peer: PeerId, packet_id: u8, data: &[u8]) {
+		let rlp = UntrustedRlp::new(data);
+		if packet_id != STATUS_PACKET && !self.peers.read().contains_key(&peer) {
+			debug!(target: "sync", "{}: Light packet {} before status", peer, packet_id);
+			return;
+		}
+
+		let result = match packet_id {
+			STATUS_PACKET => self.on_status(io, peer, &rlp),
+			ANNOUNCE_PACKET => self.on_announce(peer, &rlp),
+			GET_BLOCK_HEADERS_PACKET => self.get_block_headers(io, peer, &rlp),
+			GET_RECEIPTS_PACKET => self.get_receipts(io, peer, &rlp),
+			GET_PROOFS_PACKET => self.get_proofs(io, peer, &rlp),
+			GET_CODE_PACKET => self.get_code(io, peer, &rlp),
+			GET_TRANSACTION_PROOF_PACKET => self.get_transaction_proof(io, peer, &rlp),
+			BLOCK_HEADERS_PACKET => self.on_response(peer, RequestKind::Headers, &rlp,
+				|r| Ok(Response::Headers(r.iter().map(|h| h.as_raw().to_vec()).collect()))),
+			RECEIPTS_PACKET => self.on_response(peer, RequestKind::Receipts, &rlp,
+				|r| Ok(Response::Receipts(r.iter().map(|h| h.as_raw().to_vec()).collect()))),
+			PROOFS_PACKET => self.on_response(peer, RequestKind::StateProofs, &rlp,
+				|r| Ok(Response::Proofs(try!(r.as_val())))),
+			CODE_PACKET => self.on_response(peer, RequestKind::Codes, &rlp,
+				|r| Ok(Response::Codes(try!(r.as_val())))),
+			TRANSACTION_PROOF_PACKET => self.on_response(peer, RequestKind::TransactionProof, &rlp,
+				|r| Ok(Response::TransactionProof(try!(r.as_val())))),
+			_ => {
+				debug!(target: "sync", "{}: Unknown light packet {}", peer, packet_id);
+				Ok(())
+			}
+		};
+
+		result.unwrap_or_else(|e| {
+			debug!(target: "sync", "{} -> Malformed light packet {} : {}", peer, packet_id, e);
+			io.disable_peer(peer);
+		})
+	}
+
+	/// Drop requests which haven't been answered in time.
+	pub fn tick(&self) {
+		let now = time::precise_time_s();
+		self.pending.lock().retain(|id, p| {
+			let alive = now - p.sent <= REQUEST_TIMEOUT_SEC;
+			if !alive {
+				debug!(target: "sync", "{}: Light request {} timed out", p.peer, id);
+			}
+			alive
+		});
+	}
+
+	fn on_disconnect(&self, peer: PeerId) {
+		self.peers.write().remove(&peer);
+		self.pending.lock().retain(|_, p| p.peer != peer);
+	}
+}
+
+impl<P: ?Sized + Provider> NetworkProtocolHandler for LightProtocol<P> {
+	fn initialize(&self, io: &NetworkContext) {
+		io.register_timer(TICK_TIMER, 1000).expect("Error registering light protocol timer");
+	}
+
+	fn read(&self, io: &NetworkContext, peer: &PeerId, packet_id: u8, data: &[u8]) {
+		self.handle_packet(io, *peer, packet_id, data);
+	}
+
+	fn connected(&self, io: &NetworkContext, peer: &PeerId) {
+		self.send_status(io, *peer);
+	}
+
+	fn disconnected(&self, _io: &NetworkContext, peer: &PeerId) {
+		self.on_disconnect(*peer);
+	}
+
+	fn timeout(&self, _io: &NetworkContext, _timer: TimerToken) {
+		self.tick();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rlp::{UntrustedRlp, View};
+	use util::H256;
+
+	#[test]
+	fn proofs_request_roundtrip() {
+		let req = Request::Proofs(vec![
+			ProofRequest { block: H256::from(1), account_key: H256::from(2), key: None, from_level: 0 },
+			ProofRequest { block: H256::from(1), account_key: H256::from(2), key: Some(H256::from(3)), from_level: 2 },
+		]);
+		let encoded = req.encode(7);
+		let r = UntrustedRlp::new(&encoded);
+		assert_eq!(r.val_at::<usize>(0).unwrap(), 7);
+
+		let list = r.at(1).unwrap();
+		assert_eq!(list.item_count(), 2);
+		assert_eq!(list.at(0).unwrap().val_at::<Option<H256>>(2).unwrap(), None);
+		assert_eq!(list.at(1).unwrap().val_at::<Option<H256>>(2).unwrap(), Some(H256::from(3)));
+		assert_eq!(list.at(1).unwrap().val_at::<u32>(3).unwrap(), 2);
+	}
+
+	#[test]
+	fn headers_request_by_hash_and_number() {
+		let by_hash = Request::Headers { start: HeadersStart::Hash(H256::from(5)), max: 10, skip: 0, reverse: true };
+		let encoded = by_hash.encode(0);
+		assert_eq!(UntrustedRlp::new(&encoded).at(1).unwrap().size(), 32);
+
+		let by_number = Request::Headers { start: HeadersStart::Number(5), max: 10, skip: 0, reverse: false };
+		let encoded = by_number.encode(0);
+		assert_eq!(UntrustedRlp::new(&encoded).val_at::<u64>(1).unwrap(), 5);
+		assert_eq!(by_number.amount(), 10);
+	}
+}
@@ -0,0 +1,109 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sources of data served over the light protocol.
+
+use ethcore::client::{BlockChainClient, BlockID};
+use ethcore::light::Client as LightClient;
+use ethcore::transaction::SignedTransaction;
+use ethcore::blockchain_info::BlockChainInfo;
+use util::{Bytes, H256};
+
+/// Everything a light protocol server needs from its chain.
+/// Methods return `None` when the data is unknown or unavailable.
+pub trait Provider: Send + Sync {
+	/// Get information about the best block.
+	fn chain_info(&self) -> BlockChainInfo;
+
+	/// Get the RLP-encoded header of a block.
+	fn block_header(&self, id: BlockID) -> Option<Bytes>;
+
+	/// Get the RLP-encoded receipts of a block.
+	fn block_receipts(&self, hash: &H256) -> Option<Bytes>;
+
+	/// Prove an account in the state of the given block. See `BlockChainClient::prove_account`.
+	fn account_proof(&self, block: H256, account_key: H256, from_level: u32) -> Option<Vec<Bytes>>;
+
+	/// Prove a storage value in the state of the given block. See `BlockChainClient::prove_storage`.
+	fn storage_proof(&self, block: H256, account_key: H256, key: H256, from_level: u32) -> Option<Vec<Bytes>>;
+
+	/// Get an account's code in the state of the given block.
+	fn contract_code(&self, block: H256, account_key: H256) -> Option<Bytes>;
+
+	/// Get the state needed to execute a transaction on top of the given block.
+	fn transaction_proof(&self, t: SignedTransaction, block: H256) -> Option<Vec<Bytes>>;
+}
+
+impl Provider for BlockChainClient {
+	fn chain_info(&self) -> BlockChainInfo {
+		BlockChainClient::chain_info(self)
+	}
+
+	fn block_header(&self, id: BlockID) -> Option<Bytes> {
+		BlockChainClient::block_header(self, id)
+	}
+
+	fn block_receipts(&self, hash: &H256) -> Option<Bytes> {
+		BlockChainClient::block_receipts(self, hash)
+	}
+
+	fn account_proof(&self, block: H256, account_key: H256, from_level: u32) -> Option<Vec<Bytes>> {
+		self.prove_account(block, account_key, from_level)
+	}
+
+	fn storage_proof(&self, block: H256, account_key: H256, key: H256, from_level: u32) -> Option<Vec<Bytes>> {
+		self.prove_storage(block, account_key, key, from_level)
+	}
+
+	fn contract_code(&self, block: H256, account_key: H256) -> Option<Bytes> {
+		BlockChainClient::contract_code(self, block, account_key)
+	}
+
+	fn transaction_proof(&self, t: SignedTransaction, block: H256) -> Option<Vec<Bytes>> {
+		self.prove_transaction(t, block)
+	}
+}
+
+// light clients only have headers to offer.
+impl Provider for LightClient {
+	fn chain_info(&self) -> BlockChainInfo {
+		LightClient::chain_info(self)
+	}
+
+	fn block_header(&self, id: BlockID) -> Option<Bytes> {
+		LightClient::block_header(self, id)
+	}
+
+	fn block_receipts(&self, _hash: &H256) -> Option<Bytes> {
+		None
+	}
+
+	fn account_proof(&self, _block: H256, _account_key: H256, _from_level: u32) -> Option<Vec<Bytes>> {
+		None
+	}
+
+	fn storage_proof(&self, _block: H256, _account_key: H256, _key: H256, _from_level: u32) -> Option<Vec<Bytes>> {
+		None
+	}
+
+	fn contract_code(&self, _block: H256, _account_key: H256) -> Option<Bytes> {
+		None
+	}
+
+	fn transaction_proof(&self, _t: SignedTransaction, _block: H256) -> Option<Vec<Bytes>> {
+		None
+	}
+}
@@ -0,0 +1,728 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Header-only synchronization strategy for light clients.
+//!
+//! Speaks eth protocol v63 but only ever requests block headers. Headers are
+//! downloaded sequentially from the best connected peer and imported into a
+//! `light::Client`. Incoming `GetBlockHeaders` requests are served from the
+//! local header chain; requests for bodies, receipts and state are answered
+//! with empty responses.
+//!
+//! Account state and call results are fetched on demand from full nodes over
+//! the light protocol and checked against the synced headers.
+
+use std::cmp::{min, max};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use ethcore::client::{BlockID, BlockImportError, Executed};
+use ethcore::error::{BlockError, ImportError, ExecutionError};
+use ethcore::header::{BlockNumber, Header as BlockHeader};
+use ethcore::light::Client as LightClient;
+use ethcore::light::proof::{self, BasicAccount};
+use ethcore::transaction::SignedTransaction;
+use ethcore::views::HeaderView;
+use io::TimerToken;
+use network::{NetworkProtocolHandler, NetworkService, NetworkContext, NetworkError, PeerId, PacketId, SessionInfo};
+use parking_lot::RwLock;
+use rlp::{DecoderError, RlpStream, Stream, UntrustedRlp, View};
+use time;
+use util::{Bytes, H256, U256, Address, Hashable, SHA3_EMPTY, SHA3_NULL_RLP};
+
+use api::{SyncConfig, SyncProvider, SyncStats, NetworkConfiguration, PeerInfo as PeerInfoDigest, PeerProtocolTrafficInfo};
+use chain::{SyncStatus, SyncState, PROTOCOL_VERSION_63, MAX_HEADERS_TO_SEND, ETH_PACKET_COUNT, HEADERS_TIMEOUT_SEC,
+	STATUS_PACKET, NEW_BLOCK_HASHES_PACKET, GET_BLOCK_HEADERS_PACKET, BLOCK_HEADERS_PACKET, GET_BLOCK_BODIES_PACKET,
+	BLOCK_BODIES_PACKET, NEW_BLOCK_PACKET, GET_NODE_DATA_PACKET, NODE_DATA_PACKET, GET_RECEIPTS_PACKET, RECEIPTS_PACKET};
+use light_protocol::{LightProtocol, Request, Response, ProofRequest, CodeRequest,
+	LIGHT_PROTOCOL_ID, LIGHT_PROTOCOL_VERSION, LIGHT_PACKET_COUNT};
+
+type PacketDecodeError = DecoderError;
+
+const MAX_HEADERS_TO_REQUEST: u64 = 128;
+
+/// IO interface for the header syncing handler.
+pub trait LightSyncIo {
+	/// Disable a peer
+	fn disable_peer(&mut self, peer_id: PeerId);
+	/// Disconnect peer
+	fn disconnect_peer(&mut self, peer_id: PeerId);
+	/// Respond to current request with a packet. Can be called from an IO handler for incoming packet.
+	fn respond(&mut self, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError>;
+	/// Send a packet to a peer.
+	fn send(&mut self, peer_id: PeerId, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError>;
+	/// Returns peer identifier string
+	fn peer_info(&self, peer_id: PeerId) -> String {
+		peer_id.to_string()
+	}
+	/// Returns information on p2p session
+	fn peer_session_info(&self, peer_id: PeerId) -> Option<SessionInfo>;
+	/// Check if the session is expired
+	fn is_expired(&self) -> bool;
+}
+
+/// Wraps `NetworkContext` for the header syncing handler.
+pub struct NetLightSyncIo<'s> {
+	network: &'s NetworkContext,
+}
+
+impl<'s> NetLightSyncIo<'s> {
+	/// Creates a new instance from the `NetworkContext`.
+	pub fn new(network: &'s NetworkContext) -> NetLightSyncIo<'s> {
+		NetLightSyncIo {
+			network: network,
+		}
+	}
+}
+
+impl<'s> LightSyncIo for NetLightSyncIo<'s> {
+	fn disable_peer(&mut self, peer_id: PeerId) {
+		self.network.disable_peer(peer_id);
+	}
+
+	fn disconnect_peer(&mut self, peer_id: PeerId) {
+		self.network.disconnect_peer(peer_id);
+	}
+
+	fn respond(&mut self, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
+		self.network.respond(packet_id, data)
+	}
+
+	fn send(&mut self, peer_id: PeerId, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
+		self.network.send(peer_id, packet_id, data)
+	}
+
+	fn peer_info(&self, peer_id: PeerId) -> String {
+		self.network.peer_client_version(peer_id)
+	}
+
+	fn peer_session_info(&self, peer_id: PeerId) -> Option<SessionInfo> {
+		self.network.session_info(peer_id)
+	}
+
+	fn is_expired(&self) -> bool {
+		self.network.is_expired()
+	}
+}
+
+#[derive(Clone)]
+/// Light syncing peer information
+struct LightPeer {
+	/// Peer best block hash
+	latest_hash: H256,
+	/// Peer total difficulty if known
+	difficulty: Option<U256>,
+	/// First block number of the pending headers request, if any.
+	asking: Option<BlockNumber>,
+	/// Request timestamp
+	ask_time: f64,
+}
+
+/// Header-only blockchain sync handler.
+/// See module documentation for more details.
+pub struct LightSync {
+	/// Sync state
+	state: SyncState,
+	/// Light client headers are imported into.
+	client: Arc<LightClient>,
+	/// Best block number at the start of sync
+	starting_block: BlockNumber,
+	/// Highest block number seen
+	highest_block: Option<BlockNumber>,
+	/// All connected peers
+	peers: HashMap<PeerId, LightPeer>,
+	/// Network ID
+	network_id: U256,
+}
+
+impl LightSync {
+	/// Create a new instance of the header syncing strategy.
+	pub fn new(config: SyncConfig, client: Arc<LightClient>) -> LightSync {
+		LightSync {
+			state: SyncState::Idle,
+			starting_block: client.chain_info().best_block_number,
+			highest_block: None,
+			peers: HashMap::new(),
+			network_id: config.network_id,
+			client: client,
+		}
+	}
+
+	/// Returns synchonization status
+	pub fn status(&self) -> SyncStatus {
+		let best = self.client.chain_info().best_block_number;
+		SyncStatus {
+			state: self.state,
+			protocol_version: PROTOCOL_VERSION_63,
+			network_id: self.network_id,
+			start_block_number: self.starting_block,
+			last_imported_block_number: Some(best),
+			last_imported_old_block_number: None,
+			highest_block_number: self.highest_block.map(|n| max(n, best)),
+			blocks_received: if best > self.starting_block { best - self.starting_block } else { 0 },
+			blocks_total: match self.highest_block { Some(x) if x > self.starting_block => x - self.starting_block, _ => 0 },
+			num_peers: self.peers.len(),
+			num_active_peers: self.peers.values().filter(|p| p.asking.is_some()).count(),
+			num_snapshot_chunks: 0,
+			snapshot_chunks_done: 0,
+			mem_used: 0,
+		}
+	}
+
+	/// Returns information on peers connections
+	pub fn peers(&self, io: &LightSyncIo) -> Vec<PeerInfoDigest> {
+		self.peers.iter()
+			.filter_map(|(&peer_id, peer_data)|
+				io.peer_session_info(peer_id).map(|session_info|
+					PeerInfoDigest {
+						id: session_info.id.map(|id| id.hex()),
+						client_version: session_info.client_version,
+						capabilities: session_info.peer_capabilities.into_iter().map(|c| c.to_string()).collect(),
+						remote_address: session_info.remote_address,
+						local_address: session_info.local_address,
+						eth_version: PROTOCOL_VERSION_63 as u32,
+						eth_difficulty: peer_data.difficulty,
+						eth_head: peer_data.latest_hash,
+						traffic: (&session_info.stats.total).into(),
+						protocol_traffic: PeerProtocolTrafficInfo::from_session(&session_info.stats),
+				})
+			)
+			.collect()
+	}
+
+	/// Abort all sync activity
+	pub fn abort(&mut self) {
+		self.peers.clear();
+		self.state = SyncState::Idle;
+	}
+
+	/// Called when a new peer is connected
+	pub fn on_peer_connected(&mut self, io: &mut LightSyncIo, peer: PeerId) {
+		trace!(target: "sync", "== Connected {}: {}", peer, io.peer_info(peer));
+		if let Err(e) = self.send_status(io) {
+			debug!(target:"sync", "Error sending status request: {:?}", e);
+			io.disable_peer(peer);
+		}
+	}
+
+	/// Called by peer when it is disconnecting
+	pub fn on_peer_aborting(&mut self, io: &mut LightSyncIo, peer: PeerId) {
+		trace!(target: "sync", "== Disconnecting {}: {}", peer, io.peer_info(peer));
+		if let Some(peer) = self.peers.remove(&peer) {
+			if peer.asking.is_some() {
+				self.state = SyncState::Idle;
+			}
+		}
+		self.continue_sync(io);
+	}
+
+	/// Send Status message
+	fn send_status(&self, io: &mut LightSyncIo) -> Result<(), NetworkError> {
+		let chain = self.client.chain_info();
+		let mut packet = RlpStream::new_list(5);
+		packet.append(&(PROTOCOL_VERSION_63 as u32));
+		packet.append(&self.network_id);
+		packet.append(&chain.total_difficulty);
+		packet.append(&chain.best_block_hash);
+		packet.append(&chain.genesis_hash);
+		io.respond(STATUS_PACKET, packet.out())
+	}
+
+	/// Called by peer to report status
+	fn on_peer_status(&mut self, io: &mut LightSyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
+		let protocol_version: u8 = try!(r.val_at(0));
+		let network_id: U256 = try!(r.val_at(1));
+		let genesis: H256 = try!(r.val_at(4));
+		let peer = LightPeer {
+			difficulty: Some(try!(r.val_at(2))),
+			latest_hash: try!(r.val_at(3)),
+			asking: None,
+			ask_time: 0f64,
+		};
+
+		if io.is_expired() || self.peers.contains_key(&peer_id) {
+			debug!(target: "sync", "Unexpected status packet from {}", peer_id);
+			return Ok(());
+		}
+		let chain_info = self.client.chain_info();
+		if genesis != chain_info.genesis_hash || network_id != self.network_id || protocol_version != PROTOCOL_VERSION_63 {
+			trace!(target: "sync", "Peer {} incompatible (genesis: {}, network: {}, protocol: {})", peer_id, genesis, network_id, protocol_version);
+			io.disable_peer(peer_id);
+			return Ok(());
+		}
+
+		self.peers.insert(peer_id, peer);
+		debug!(target: "sync", "Connected light sync peer {}", peer_id);
+		self.continue_sync(io);
+		Ok(())
+	}
+
+	/// Pick the peer with the highest total difficulty which is better than ours and request the next batch of headers.
+	fn continue_sync(&mut self, io: &mut LightSyncIo) {
+		if self.state == SyncState::Blocks {
+			return;
+		}
+		let chain_info = self.client.chain_info();
+		let best_peer = self.peers.iter()
+			.filter(|&(_, p)| p.difficulty.map_or(false, |d| d > chain_info.total_difficulty))
+			.max_by_key(|&(_, p)| p.difficulty)
+			.map(|(id, _)| *id);
+
+		match best_peer {
+			Some(peer_id) => self.request_headers(io, peer_id, chain_info.best_block_number + 1),
+			None => self.state = SyncState::Idle,
+		}
+	}
+
+	/// Request a batch of headers starting from the given block number.
+	fn request_headers(&mut self, io: &mut LightSyncIo, peer_id: PeerId, from: BlockNumber) {
+		trace!(target: "sync", "{} <- GetBlockHeaders: {} entries starting from #{}", peer_id, MAX_HEADERS_TO_REQUEST, from);
+		let mut rlp = RlpStream::new_list(4);
+		rlp.append(&from);
+		rlp.append(&MAX_HEADERS_TO_REQUEST);
+		rlp.append(&0u32);
+		rlp.append(&0u32);
+		if let Some(ref mut peer) = self.peers.get_mut(&peer_id) {
+			peer.asking = Some(from);
+			peer.ask_time = time::precise_time_s();
+		}
+		self.state = SyncState::Blocks;
+		if let Err(e) = io.send(peer_id, GET_BLOCK_HEADERS_PACKET, rlp.out()) {
+			debug!(target:"sync", "Error sending request: {:?}", e);
+			io.disable_peer(peer_id);
+		}
+	}
+
+	/// Raise the highest seen block once a header has passed verification and was queued.
+	/// Announced numbers are not trusted on their own.
+	fn note_imported(&mut self, number: BlockNumber) {
+		if number > self.highest_block.unwrap_or(0) {
+			self.highest_block = Some(number);
+		}
+	}
+
+	/// Called by peer once it has new block headers
+	fn on_peer_block_headers(&mut self, io: &mut LightSyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
+		let from = match self.peers.get_mut(&peer_id) {
+			Some(peer) => match peer.asking.take() {
+				Some(from) => from,
+				None => {
+					trace!(target: "sync", "{}: Ignored unexpected headers", peer_id);
+					return Ok(());
+				}
+			},
+			None => return Ok(()),
+		};
+		self.state = SyncState::Idle;
+
+		let item_count = r.item_count();
+		trace!(target: "sync", "{} -> BlockHeaders ({} entries)", peer_id, item_count);
+
+		let mut imported = 0;
+		let mut unknown_parent = false;
+		for i in 0..item_count {
+			let header: BlockHeader = try!(r.val_at(i));
+			let number = header.number();
+			match self.client.import_header(header) {
+				Ok(_) => {
+					imported += 1;
+					self.note_imported(number);
+				},
+				Err(BlockImportError::Import(ImportError::AlreadyInChain)) |
+				Err(BlockImportError::Import(ImportError::AlreadyQueued)) => {},
+				Err(BlockImportError::Block(BlockError::UnknownParent(_))) if imported == 0 => {
+					unknown_parent = true;
+					break;
+				},
+				Err(e) => {
+					debug!(target: "sync", "{}: Bad header: {:?}", peer_id, e);
+					io.disable_peer(peer_id);
+					self.peers.remove(&peer_id);
+					self.client.flush_queue();
+					self.continue_sync(io);
+					return Ok(());
+				}
+			}
+		}
+		self.client.flush_queue();
+
+		if unknown_parent && from > 1 {
+			// our best header is not on the peer's chain. step back to find the common ancestor.
+			let retreat_to = max(1, from.saturating_sub(MAX_HEADERS_TO_REQUEST));
+			trace!(target: "sync", "{}: Unknown parent at #{}, retreating to #{}", peer_id, from, retreat_to);
+			self.request_headers(io, peer_id, retreat_to);
+			return Ok(());
+		}
+
+		if imported == 0 {
+			// nothing useful from this peer; don't ask it again until it announces something new.
+			if let Some(ref mut peer) = self.peers.get_mut(&peer_id) {
+				peer.difficulty = None;
+			}
+		}
+		self.continue_sync(io);
+		Ok(())
+	}
+
+	/// Called by peer once it has a new block
+	fn on_peer_new_block(&mut self, io: &mut LightSyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
+		let header: BlockHeader = try!(try!(r.at(0)).val_at(0));
+		let difficulty: U256 = try!(r.val_at(1));
+		trace!(target: "sync", "{} -> NewBlock ({})", peer_id, header.hash());
+		if let Some(ref mut peer) = self.peers.get_mut(&peer_id) {
+			peer.latest_hash = header.hash();
+			peer.difficulty = Some(difficulty);
+		}
+		if self.state != SyncState::Idle {
+			return Ok(());
+		}
+		let number = header.number();
+		match self.client.import_header(header) {
+			Ok(_) => {
+				self.note_imported(number);
+				self.client.flush_queue();
+			},
+			Err(BlockImportError::Block(BlockError::UnknownParent(_))) => self.continue_sync(io),
+			Err(_) => {},
+		}
+		Ok(())
+	}
+
+	/// Handles `NewHashes` packet. Requests headers from the announcing peer.
+	fn on_peer_new_hashes(&mut self, io: &mut LightSyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
+		trace!(target: "sync", "{} -> NewHashes ({} entries)", peer_id, r.item_count());
+		let mut new_hashes = false;
+		for item in r.iter() {
+			let hash: H256 = try!(item.val_at(0));
+			if !self.client.chain().is_known(&hash) {
+				new_hashes = true;
+				if let Some(ref mut peer) = self.peers.get_mut(&peer_id) {
+					peer.latest_hash = hash;
+				}
+			}
+		}
+		if new_hashes && self.state == SyncState::Idle {
+			let from = self.client.chain_info().best_block_number + 1;
+			self.request_headers(io, peer_id, from);
+		}
+		Ok(())
+	}
+
+	/// Respond to GetBlockHeaders request from the header chain
+	fn return_block_headers(&self, io: &mut LightSyncIo, r: &UntrustedRlp, peer_id: PeerId) -> Result<(), PacketDecodeError> {
+		// Packet layout:
+		// [ block: { P , B_32 }, maxHeaders: P, skip: P, reverse: P in { 0 , 1 } ]
+		let max_headers: usize = try!(r.val_at(1));
+		let skip: usize = try!(r.val_at(2));
+		let reverse: bool = try!(r.val_at(3));
+		let chain = self.client.chain();
+		let last = chain.best_block_number();
+		let number = if try!(r.at(0)).size() == 32 {
+			let hash: H256 = try!(r.val_at(0));
+			match chain.block_header(BlockID::Hash(hash)) {
+				Some(hdr) => HeaderView::new(&hdr).number(),
+				None => last + 1,
+			}
+		} else {
+			try!(r.val_at(0))
+		};
+
+		let max_count = min(MAX_HEADERS_TO_SEND, max_headers);
+		let inc = (skip + 1) as BlockNumber;
+		let mut number = if reverse { min(last, number) } else { number };
+		let mut count = 0;
+		let mut data = Bytes::new();
+		while number <= last && count < max_count {
+			match chain.block_header(BlockID::Number(number)) {
+				Some(mut hdr) => {
+					data.append(&mut hdr);
+					count += 1;
+				}
+				None => break,
+			}
+			if reverse {
+				if number < inc {
+					break;
+				}
+				number -= inc;
+			} else {
+				number += inc;
+			}
+		}
+		let mut rlp = RlpStream::new_list(count);
+		rlp.append_raw(&data, count);
+		trace!(target: "sync", "{} -> GetBlockHeaders: returned {} entries", peer_id, count);
+		if let Err(e) = io.respond(BLOCK_HEADERS_PACKET, rlp.out()) {
+			debug!(target: "sync", "Error sending block headers: {:?}", e);
+			io.disable_peer(peer_id);
+		}
+		Ok(())
+	}
+
+	/// Dispatch incoming requests and responses
+	pub fn on_packet(&mut self, io: &mut LightSyncIo, peer: PeerId, packet_id: u8, data: &[u8]) {
+		if packet_id != STATUS_PACKET && !self.peers.contains_key(&peer) {
+			debug!(target:"sync", "Unexpected packet from unregistered peer: {}", peer);
+			return;
+		}
+		let rlp = UntrustedRlp::new(data);
+		let result = match packet_id {
+			STATUS_PACKET => self.on_peer_status(io, peer, &rlp),
+			BLOCK_HEADERS_PACKET => self.on_peer_block_headers(io, peer, &rlp),
+			NEW_BLOCK_PACKET => self.on_peer_new_block(io, peer, &rlp),
+			NEW_BLOCK_HASHES_PACKET => self.on_peer_new_hashes(io, peer, &rlp),
+			GET_BLOCK_HEADERS_PACKET => self.return_block_headers(io, &rlp, peer),
+			GET_BLOCK_BODIES_PACKET => Self::return_empty(io, BLOCK_BODIES_PACKET),
+			GET_RECEIPTS_PACKET => Self::return_empty(io, RECEIPTS_PACKET),
+			GET_NODE_DATA_PACKET => Self::return_empty(io, NODE_DATA_PACKET),
+			_ => {
+				trace!(target: "sync", "{}: Ignored packet {}", peer, packet_id);
+				Ok(())
+			}
+		};
+		result.unwrap_or_else(|e| {
+			debug!(target:"sync", "{} -> Malformed packet {} : {}", peer, packet_id, e);
+		})
+	}
+
+	/// We don't have bodies, receipts or state; tell the peer so.
+	fn return_empty(io: &mut LightSyncIo, packet_id: PacketId) -> Result<(), PacketDecodeError> {
+		io.respond(packet_id, RlpStream::new_list(0).out())
+			.unwrap_or_else(|e| debug!(target: "sync", "Error sending empty response: {:?}", e));
+		Ok(())
+	}
+
+	/// Disconnect peers which haven't answered a headers request in time.
+	pub fn maintain_peers(&mut self, io: &mut LightSyncIo) {
+		let tick = time::precise_time_s();
+		let aborting: Vec<PeerId> = self.peers.iter()
+			.filter(|&(_, p)| p.asking.is_some() && (tick - p.ask_time) > HEADERS_TIMEOUT_SEC)
+			.map(|(id, _)| *id)
+			.collect();
+		for peer_id in aborting {
+			trace!(target:"sync", "Timeout {}", peer_id);
+			io.disconnect_peer(peer_id);
+			self.on_peer_aborting(io, peer_id);
+		}
+	}
+
+	/// Import any verified headers and resume syncing if idle.
+	pub fn maintain_sync(&mut self, io: &mut LightSyncIo) {
+		self.client.import_verified();
+		self.continue_sync(io);
+	}
+}
+
+/// Header-only network sync service for light clients
+pub struct EthLightSync {
+	/// Network service
+	network: NetworkService,
+	/// Protocol handler
+	handler: Arc<LightSyncHandler>,
+	/// The main subprotocol name
+	subprotocol_name: [u8; 3],
+	/// Light protocol handler used for on-demand requests
+	light_protocol: Arc<LightProtocol<LightClient>>,
+	/// Light client, for checking on-demand responses against known headers
+	client: Arc<LightClient>,
+}
+
+impl EthLightSync {
+	/// Creates the service and registers the protocol handler
+	pub fn new(config: SyncConfig, client: Arc<LightClient>, network_config: NetworkConfiguration) -> Result<Arc<EthLightSync>, NetworkError> {
+		let service = try!(NetworkService::new(try!(network_config.into_basic())));
+		Ok(Arc::new(EthLightSync {
+			network: service,
+			handler: Arc::new(LightSyncHandler {
+				sync: RwLock::new(LightSync::new(config, client.clone())),
+			}),
+			subprotocol_name: config.subprotocol_name,
+			light_protocol: Arc::new(LightProtocol::new(client.clone(), config.network_id, None)),
+			client: client,
+		}))
+	}
+
+	/// Start the network and begin syncing headers.
+	pub fn start(&self) {
+		match self.network.start() {
+			Err(NetworkError::StdIo(ref e)) if e.kind() == io::ErrorKind::AddrInUse => warn!("Network port {:?} is already in use, make sure that another instance of an Ethereum client is not running or change the port using the --port option.", self.network.config().listen_address.expect("Listen address is not set.")),
+			Err(err) => warn!("Error starting network: {}", err),
+			_ => {},
+		}
+		self.network.register_protocol(self.handler.clone(), self.subprotocol_name, ETH_PACKET_COUNT, &[PROTOCOL_VERSION_63])
+			.unwrap_or_else(|e| warn!("Error registering ethereum protocol: {:?}", e));
+		self.network.register_protocol(self.light_protocol.clone(), LIGHT_PROTOCOL_ID, LIGHT_PACKET_COUNT, &[LIGHT_PROTOCOL_VERSION])
+			.unwrap_or_else(|e| warn!("Error registering light protocol: {:?}", e));
+	}
+
+	/// Make a request about the block with the given header of a serving peer which has it,
+	/// and wait for the response. The light protocol gives up on requests which aren't answered in time.
+	fn on_demand(&self, request: Request, header: &BlockHeader) -> Option<Response> {
+		let receiver = self.network.with_context_eval(LIGHT_PROTOCOL_ID, |context| {
+			self.light_protocol.request(context, request.clone(), header.number())
+		});
+		match receiver {
+			Some(Some(receiver)) => receiver.recv().ok(),
+			_ => None,
+		}
+	}
+
+	fn header(&self, block: &H256) -> Option<BlockHeader> {
+		self.client.block_header(BlockID::Hash(block.clone())).map(|bytes| ::rlp::decode(&bytes))
+	}
+
+	/// Fetch an account's state at the given block from the network.
+	/// Returns `None` if it couldn't be fetched or proven, and `Some(None)` if
+	/// the account was proven not to exist.
+	pub fn account(&self, block: H256, address: &Address) -> Option<Option<BasicAccount>> {
+		let header = match self.header(&block) {
+			Some(header) => header,
+			None => return None,
+		};
+		let account_key = address.sha3();
+		let request = Request::Proofs(vec![ProofRequest { block: block, account_key: account_key, key: None, from_level: 0 }]);
+		match self.on_demand(request, &header) {
+			Some(Response::Proofs(ref proofs)) if proofs.len() == 1 => {
+				proof::check_account_proof(header.state_root(), &account_key, &proofs[0])
+					.map_err(|e| debug!(target: "sync", "Bad account proof for {}: {}", address, e))
+					.ok()
+			}
+			_ => None,
+		}
+	}
+
+	/// Fetch the value of an account's storage slot at the given block from the network.
+	/// Slots of accounts which don't exist are zero.
+	pub fn storage_at(&self, block: H256, address: &Address, key: &H256) -> Option<H256> {
+		let header = match self.header(&block) {
+			Some(header) => header,
+			None => return None,
+		};
+		let storage_root = match self.account(block.clone(), address) {
+			Some(Some(account)) => account.storage_root,
+			Some(None) => return Some(H256::zero()),
+			None => return None,
+		};
+		if storage_root == SHA3_NULL_RLP {
+			return Some(H256::zero());
+		}
+
+		let key_hash = key.sha3();
+		let request = Request::Proofs(vec![ProofRequest { block: block, account_key: address.sha3(), key: Some(key_hash.clone()), from_level: 0 }]);
+		match self.on_demand(request, &header) {
+			Some(Response::Proofs(ref proofs)) if proofs.len() == 1 => {
+				proof::check_storage_proof(&storage_root, &key_hash, &proofs[0])
+					.map_err(|e| debug!(target: "sync", "Bad storage proof for {}: {}", address, e))
+					.ok()
+			}
+			_ => None,
+		}
+	}
+
+	/// Fetch an account's code at the given block from the network.
+	/// Accounts which don't exist have empty code.
+	pub fn code(&self, block: H256, address: &Address) -> Option<Bytes> {
+		let header = match self.header(&block) {
+			Some(header) => header,
+			None => return None,
+		};
+		let code_hash = match self.account(block.clone(), address) {
+			Some(Some(account)) => account.code_hash,
+			Some(None) => return Some(Bytes::new()),
+			None => return None,
+		};
+		if code_hash == SHA3_EMPTY {
+			return Some(Bytes::new());
+		}
+
+		let request = Request::Codes(vec![CodeRequest { block: block, account_key: address.sha3() }]);
+		match self.on_demand(request, &header) {
+			Some(Response::Codes(mut codes)) => match codes.pop() {
+				Some(code) if codes.is_empty() && code.sha3() == code_hash => Some(code),
+				_ => {
+					debug!(target: "sync", "Bad code response for {}", address);
+					None
+				}
+			},
+			_ => None,
+		}
+	}
+
+	/// Execute a transaction on top of the given block using state fetched from the network.
+	/// Returns `None` if the state couldn't be fetched or was incomplete.
+	pub fn call(&self, block: H256, transaction: SignedTransaction) -> Option<Result<Executed, ExecutionError>> {
+		let header = match self.header(&block) {
+			Some(header) => header,
+			None => return None,
+		};
+		let request = Request::TransactionProof { block: block, transaction: transaction.clone() };
+		match self.on_demand(request, &header) {
+			Some(Response::TransactionProof(proof)) => self.client.check_transaction_proof(&transaction, &header, &proof),
+			_ => None,
+		}
+	}
+
+	/// Stop syncing and shut down the network.
+	pub fn stop(&self) {
+		self.handler.sync.write().abort();
+		self.network.stop().unwrap_or_else(|e| warn!("Error stopping network: {:?}", e));
+	}
+}
+
+impl SyncProvider for EthLightSync {
+	fn status(&self) -> SyncStatus {
+		self.handler.sync.read().status()
+	}
+
+	fn peers(&self) -> Vec<PeerInfoDigest> {
+		self.network.with_context_eval(self.subprotocol_name, |context| {
+			self.handler.sync.read().peers(&NetLightSyncIo::new(context))
+		}).unwrap_or(Vec::new())
+	}
+
+	fn stats(&self) -> SyncStats {
+		SyncStats::default()
+	}
+}
+
+struct LightSyncHandler {
+	/// Sync strategy
+	sync: RwLock<LightSync>,
+}
+
+impl NetworkProtocolHandler for LightSyncHandler {
+	fn initialize(&self, io: &NetworkContext) {
+		io.register_timer(0, 1000).expect("Error registering sync timer");
+	}
+
+	fn read(&self, io: &NetworkContext, peer: &PeerId, packet_id: u8, data: &[u8]) {
+		self.sync.write().on_packet(&mut NetLightSyncIo::new(io), *peer, packet_id, data);
+	}
+
+	fn connected(&self, io: &NetworkContext, peer: &PeerId) {
+		self.sync.write().on_peer_connected(&mut NetLightSyncIo::new(io), *peer);
+	}
+
+	fn disconnected(&self, io: &NetworkContext, peer: &PeerId) {
+		self.sync.write().on_peer_aborting(&mut NetLightSyncIo::new(io), *peer);
+	}
+
+	fn timeout(&self, io: &NetworkContext, _timer: TimerToken) {
+		self.sync.write().maintain_peers(&mut NetLightSyncIo::new(io));
+		self.sync.write().maintain_sync(&mut NetLightSyncIo::new(io));
+	}
+}
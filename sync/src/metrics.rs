@@ -0,0 +1,249 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sync metrics collection.
+
+use std::collections::{HashMap, VecDeque};
+use util::H256;
+use network::PeerId;
+use ethcore::header::BlockNumber;
+use api::{PeerSyncStats, BlockSource};
+
+/// Period download and import rates are averaged over, in seconds.
+const RATE_WINDOW_SEC: f64 = 10f64;
+/// Number of imported blocks to keep the source of.
+const MAX_RECENT_BLOCKS: usize = 64;
+/// Max number of downloaded blocks waiting for import to keep the source of.
+/// Matches the default `max_download_ahead_blocks`.
+const MAX_PENDING_SOURCES: usize = 20000;
+/// Number of disconnected peers to keep request statistics of.
+const MAX_DISCONNECTED_PEERS: usize = 32;
+
+/// Counts events and reports their rate over the last `RATE_WINDOW_SEC` seconds.
+#[derive(Default)]
+pub struct RateMeter {
+	samples: VecDeque<(f64, u64)>,
+	total: u64,
+}
+
+impl RateMeter {
+	/// Record `count` events happening at `now`.
+	pub fn record(&mut self, now: f64, count: usize) {
+		self.total += count as u64;
+		self.samples.push_back((now, count as u64));
+		while self.samples.front().map_or(false, |&(time, _)| now - time > RATE_WINDOW_SEC) {
+			self.samples.pop_front();
+		}
+	}
+
+	/// Events per second in the window ending at `now`.
+	pub fn rate(&self, now: f64) -> f64 {
+		let count = self.samples.iter()
+			.filter(|&&(time, _)| now - time <= RATE_WINDOW_SEC)
+			.fold(0u64, |sum, &(_, count)| sum + count);
+		count as f64 / RATE_WINDOW_SEC
+	}
+
+	/// Total number of events recorded.
+	pub fn total(&self) -> u64 {
+		self.total
+	}
+}
+
+/// Request statistics for a single peer.
+#[derive(Default)]
+struct PeerMetrics {
+	responses: u64,
+	timeouts: u64,
+	total_latency: f64,
+	last_latency: f64,
+	blocks_supplied: u64,
+}
+
+impl PeerMetrics {
+	fn to_stats(&self, id: Option<String>, connected: bool) -> PeerSyncStats {
+		PeerSyncStats {
+			id: id,
+			connected: connected,
+			responses: self.responses,
+			timeouts: self.timeouts,
+			avg_latency_ms: if self.responses == 0 { 0 } else { (self.total_latency * 1000f64 / self.responses as f64) as u64 },
+			last_latency_ms: (self.last_latency * 1000f64) as u64,
+			blocks_supplied: self.blocks_supplied,
+		}
+	}
+}
+
+/// Sync metrics.
+#[derive(Default)]
+pub struct SyncMetrics {
+	/// Block headers downloaded
+	pub headers: RateMeter,
+	/// Block bodies downloaded
+	pub bodies: RateMeter,
+	/// Block receipts downloaded
+	pub receipts: RateMeter,
+	/// Blocks imported
+	pub blocks: RateMeter,
+	peers: HashMap<PeerId, PeerMetrics>,
+	disconnected: VecDeque<PeerSyncStats>,
+	/// Peers that supplied headers of blocks not imported yet, with the order they arrived in
+	sources: HashMap<H256, (PeerId, u64)>,
+	source_order: VecDeque<(u64, H256)>,
+	next_source: u64,
+	recent: VecDeque<BlockSource>,
+}
+
+impl SyncMetrics {
+	/// Called when a peer responds to a request after `latency` seconds.
+	pub fn on_response(&mut self, peer: PeerId, latency: f64) {
+		let metrics = self.peers.entry(peer).or_insert_with(Default::default);
+		metrics.responses += 1;
+		metrics.total_latency += latency;
+		metrics.last_latency = latency;
+	}
+
+	/// Called when a request to a peer times out.
+	pub fn on_timeout(&mut self, peer: PeerId) {
+		self.peers.entry(peer).or_insert_with(Default::default).timeouts += 1;
+	}
+
+	/// Called when a peer sends block headers.
+	pub fn on_headers(&mut self, now: f64, peer: PeerId, hashes: &[H256]) {
+		self.headers.record(now, hashes.len());
+		for hash in hashes {
+			let seq = self.next_source;
+			self.next_source += 1;
+			self.sources.insert(hash.clone(), (peer, seq));
+			self.source_order.push_back((seq, hash.clone()));
+		}
+		// forget the oldest sources; entries already imported or replaced are skipped
+		while self.source_order.len() > MAX_PENDING_SOURCES {
+			let (seq, hash) = self.source_order.pop_front().expect("queue is longer than the limit; qed");
+			if self.sources.get(&hash).map_or(false, |&(_, s)| s == seq) {
+				self.sources.remove(&hash);
+			}
+		}
+	}
+
+	/// Called when a block is imported. Returns the peer that supplied it, if the block was downloaded.
+	pub fn on_imported(&mut self, hash: &H256) -> Option<PeerId> {
+		let peer = self.sources.remove(hash).map(|(peer, _)| peer);
+		if let Some(peer) = peer {
+			self.peers.entry(peer).or_insert_with(Default::default).blocks_supplied += 1;
+		}
+		peer
+	}
+
+	/// Remember the source of an imported block.
+	pub fn push_recent(&mut self, hash: H256, number: BlockNumber, peer: Option<String>) {
+		if self.recent.len() == MAX_RECENT_BLOCKS {
+			self.recent.pop_front();
+		}
+		self.recent.push_back(BlockSource {
+			hash: hash,
+			number: number,
+			peer: peer,
+		});
+	}
+
+	/// Called when a peer disconnects. Its statistics are kept for a while.
+	/// Blocks it supplied that are not imported yet are no longer attributed to it.
+	pub fn on_peer_aborting(&mut self, peer: PeerId, id: Option<String>) {
+		self.sources.retain(|_, &mut (p, _)| p != peer);
+		if let Some(metrics) = self.peers.remove(&peer) {
+			if self.disconnected.len() == MAX_DISCONNECTED_PEERS {
+				self.disconnected.pop_front();
+			}
+			self.disconnected.push_back(metrics.to_stats(id, false));
+		}
+	}
+
+	/// Request statistics for connected peers followed by recently disconnected ones.
+	/// `node_id` resolves a connected peer's public node id.
+	pub fn peer_stats<F>(&self, node_id: F) -> Vec<PeerSyncStats> where F: Fn(PeerId) -> Option<String> {
+		self.peers.iter()
+			.map(|(peer, m)| m.to_stats(node_id(*peer), true))
+			.chain(self.disconnected.iter().cloned())
+			.collect()
+	}
+
+	/// Most recently imported blocks that were downloaded from peers, oldest first.
+	pub fn recent_blocks(&self) -> Vec<BlockSource> {
+		self.recent.iter().cloned().collect()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use util::H256;
+
+	#[test]
+	fn rate_is_averaged_over_window() {
+		let mut meter = RateMeter::default();
+		meter.record(0f64, 50);
+		meter.record(5f64, 50);
+		assert_eq!(meter.rate(5f64), 10f64);
+		assert_eq!(meter.rate(12f64), 5f64);
+		assert_eq!(meter.rate(20f64), 0f64);
+		assert_eq!(meter.total(), 100);
+	}
+
+	#[test]
+	fn tracks_block_sources() {
+		let mut metrics = SyncMetrics::default();
+		metrics.on_response(3, 0.5);
+		metrics.on_response(3, 1.5);
+		metrics.on_timeout(3);
+		metrics.on_headers(0f64, 3, &[H256::from(1), H256::from(2)]);
+
+		assert_eq!(metrics.on_imported(&H256::from(1)), Some(3));
+		assert_eq!(metrics.on_imported(&H256::from(1)), None);
+		assert_eq!(metrics.on_imported(&H256::from(5)), None);
+
+		let stats = metrics.peer_stats(|_| None);
+		assert_eq!(stats.len(), 1);
+		assert!(stats[0].connected);
+		assert_eq!(stats[0].responses, 2);
+		assert_eq!(stats[0].timeouts, 1);
+		assert_eq!(stats[0].avg_latency_ms, 1000);
+		assert_eq!(stats[0].last_latency_ms, 1500);
+		assert_eq!(stats[0].blocks_supplied, 1);
+
+		metrics.on_peer_aborting(3, Some("node".to_owned()));
+		let stats = metrics.peer_stats(|_| None);
+		assert_eq!(stats.len(), 1);
+		assert!(!stats[0].connected);
+		assert_eq!(stats[0].id, Some("node".to_owned()));
+		assert_eq!(stats[0].timeouts, 1);
+	}
+
+	#[test]
+	fn evicts_oldest_sources() {
+		let mut metrics = SyncMetrics::default();
+		let hashes: Vec<H256> = (0..MAX_PENDING_SOURCES as u64 + 1).map(H256::from).collect();
+		metrics.on_headers(0f64, 1, &hashes[..10]);
+		metrics.on_headers(0f64, 2, &hashes[10..]);
+		assert_eq!(metrics.on_imported(&hashes[0]), None);
+		assert_eq!(metrics.on_imported(&hashes[1]), Some(1));
+		assert_eq!(metrics.on_imported(&hashes[MAX_PENDING_SOURCES]), Some(2));
+
+		metrics.on_peer_aborting(1, None);
+		assert_eq!(metrics.on_imported(&hashes[2]), None);
+		assert_eq!(metrics.on_imported(&hashes[10]), Some(2));
+	}
+}
@@ -15,21 +15,23 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::HashMap;
-use network::{NetworkContext, PeerId, PacketId, NetworkError, SessionInfo, ProtocolId};
+use network::{NetworkContext, PeerId, PacketId, NetworkError, SessionInfo, ProtocolId, PeerEvent};
 use util::Bytes;
 use ethcore::client::BlockChainClient;
 use ethcore::header::BlockNumber;
 use ethcore::snapshot::SnapshotService;
 use parking_lot::RwLock;
+use time;
 
 /// IO interface for the syning handler.
 /// Provides peer connection management and an interface to the blockchain client.
-// TODO: ratings
 pub trait SyncIo {
 	/// Disable a peer
 	fn disable_peer(&mut self, peer_id: PeerId);
 	/// Disconnect peer
 	fn disconnect_peer(&mut self, peer_id: PeerId);
+	/// Report peer behaviour affecting its reputation
+	fn report_peer(&mut self, peer_id: PeerId, event: PeerEvent);
 	/// Respond to current request with a packet. Can be called from an IO handler for incoming packet.
 	fn respond(&mut self, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError>;
 	/// Send a packet to a peer.
@@ -46,6 +48,8 @@ pub trait SyncIo {
 	}
 	/// Returns information on p2p session
 	fn peer_session_info(&self, peer_id: PeerId) -> Option<SessionInfo>;
+	/// Check if the peer is one of the reserved nodes
+	fn is_reserved_peer(&self, peer_id: PeerId) -> bool;
 	/// Maximum mutually supported ETH protocol version
 	fn eth_protocol_version(&self, peer_id: PeerId) -> u8;
 	/// Maximum mutually supported version of a gien protocol.
@@ -58,22 +62,26 @@ pub trait SyncIo {
 	fn is_expired(&self) -> bool;
 	/// Return sync overlay
 	fn chain_overlay(&self) -> &RwLock<HashMap<BlockNumber, Bytes>>;
+	/// Current time in seconds, used to time out requests.
+	fn now(&self) -> f64 {
+		time::precise_time_s()
+	}
 }
 
 /// Wraps `NetworkContext` and the blockchain client
-pub struct NetSyncIo<'s, 'h> where 'h: 's {
-	network: &'s NetworkContext<'h>,
+pub struct NetSyncIo<'s> {
+	network: &'s NetworkContext,
 	chain: &'s BlockChainClient,
 	snapshot_service: &'s SnapshotService,
 	chain_overlay: &'s RwLock<HashMap<BlockNumber, Bytes>>,
 }
 
-impl<'s, 'h> NetSyncIo<'s, 'h> {
+impl<'s> NetSyncIo<'s> {
 	/// Creates a new instance from the `NetworkContext` and the blockchain client reference.
-	pub fn new(network: &'s NetworkContext<'h>, 
+	pub fn new(network: &'s NetworkContext, 
 		chain: &'s BlockChainClient,
 		snapshot_service: &'s SnapshotService,
-		chain_overlay: &'s RwLock<HashMap<BlockNumber, Bytes>>) -> NetSyncIo<'s, 'h> {
+		chain_overlay: &'s RwLock<HashMap<BlockNumber, Bytes>>) -> NetSyncIo<'s> {
 		NetSyncIo {
 			network: network,
 			chain: chain,
@@ -83,7 +91,7 @@ impl<'s, 'h> NetSyncIo<'s, 'h> {
 	}
 }
 
-impl<'s, 'h> SyncIo for NetSyncIo<'s, 'h> {
+impl<'s> SyncIo for NetSyncIo<'s> {
 	fn disable_peer(&mut self, peer_id: PeerId) {
 		self.network.disable_peer(peer_id);
 	}
@@ -92,6 +100,10 @@ impl<'s, 'h> SyncIo for NetSyncIo<'s, 'h> {
 		self.network.disconnect_peer(peer_id);
 	}
 
+	fn report_peer(&mut self, peer_id: PeerId, event: PeerEvent) {
+		self.network.report_peer(peer_id, event);
+	}
+
 	fn respond(&mut self, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError>{
 		self.network.respond(packet_id, data)
 	}
@@ -120,6 +132,10 @@ impl<'s, 'h> SyncIo for NetSyncIo<'s, 'h> {
 		self.network.session_info(peer_id)
 	}
 
+	fn is_reserved_peer(&self, peer_id: PeerId) -> bool {
+		self.network.is_reserved_peer(peer_id)
+	}
+
 	fn is_expired(&self) -> bool {
 		self.network.is_expired()
 	}
@@ -131,6 +147,10 @@ impl<'s, 'h> SyncIo for NetSyncIo<'s, 'h> {
 	fn protocol_version(&self, protocol: &ProtocolId, peer_id: PeerId) -> u8 {
 		self.network.protocol_version(*protocol, peer_id).unwrap_or(0)
 	}
+
+	fn now(&self) -> f64 {
+		self.network.now()
+	}
 }
 
 
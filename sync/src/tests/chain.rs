@@ -122,19 +122,45 @@ fn net_hard_fork() {
 	let ref_client = TestBlockChainClient::new();
 	ref_client.add_blocks(50, EachBlockWith::Uncle);
 	{
-		let mut net = TestNet::new_with_fork(2, Some((50, ref_client.block_hash(BlockID::Number(50)).unwrap())));
+		let mut net = TestNet::new_with_forks(2, &[(50, ref_client.block_hash(BlockID::Number(50)).unwrap())]);
 		net.peer_mut(0).chain.add_blocks(100, EachBlockWith::Uncle);
 		net.sync();
 		assert_eq!(net.peer(1).chain.chain_info().best_block_number, 100);
 	}
 	{
-		let mut net = TestNet::new_with_fork(2, Some((50, ref_client.block_hash(BlockID::Number(50)).unwrap())));
+		let mut net = TestNet::new_with_forks(2, &[(50, ref_client.block_hash(BlockID::Number(50)).unwrap())]);
 		net.peer_mut(0).chain.add_blocks(100, EachBlockWith::Nothing);
 		net.sync();
 		assert_eq!(net.peer(1).chain.chain_info().best_block_number, 0);
 	}
 }
 
+#[test]
+fn net_multiple_hard_forks() {
+	::env_logger::init().ok();
+	let ref_client = TestBlockChainClient::new();
+	ref_client.add_blocks(50, EachBlockWith::Uncle);
+	ref_client.add_blocks(50, EachBlockWith::Nothing);
+	let forks = [
+		(50, ref_client.block_hash(BlockID::Number(50)).unwrap()),
+		(80, ref_client.block_hash(BlockID::Number(80)).unwrap()),
+	];
+	{
+		let mut net = TestNet::new_with_forks(2, &forks);
+		net.peer_mut(0).chain.add_blocks(50, EachBlockWith::Uncle);
+		net.peer_mut(0).chain.add_blocks(50, EachBlockWith::Nothing);
+		net.sync();
+		assert_eq!(net.peer(1).chain.chain_info().best_block_number, 100);
+	}
+	{
+		// same side of the first fork, wrong side of the second one
+		let mut net = TestNet::new_with_forks(2, &forks);
+		net.peer_mut(0).chain.add_blocks(100, EachBlockWith::Uncle);
+		net.sync();
+		assert_eq!(net.peer(1).chain.chain_info().best_block_number, 0);
+	}
+}
+
 #[test]
 fn restart() {
 	::env_logger::init().ok();
@@ -17,37 +17,110 @@
 use util::*;
 use network::*;
 use tests::snapshot::*;
-use ethcore::client::{TestBlockChainClient, BlockChainClient};
+use ethcore::client::{TestBlockChainClient, BlockChainClient, Client as EthcoreClient, ClientConfig};
 use ethcore::header::BlockNumber;
 use ethcore::snapshot::SnapshotService;
+use ethcore::spec::Spec;
+use ethcore::miner::Miner;
+use io::IoChannel;
+use devtools::RandomTempPath;
 use sync_io::SyncIo;
 use api::WARP_SYNC_PROTOCOL_ID;
-use chain::ChainSync;
+use chain::{ChainSync, ETH_PACKET_COUNT};
 use ::SyncConfig;
 
-pub struct TestIo<'p> {
-	pub chain: &'p mut TestBlockChainClient,
+/// Blockchain client used by a test peer.
+pub trait TestClient {
+	/// Returns the client interface used by the sync.
+	fn as_client(&self) -> &BlockChainClient;
+	/// Imports any blocks waiting in the import queue.
+	fn flush(&self) {}
+}
+
+impl TestClient for TestBlockChainClient {
+	fn as_client(&self) -> &BlockChainClient {
+		self
+	}
+}
+
+impl TestClient for Arc<EthcoreClient> {
+	fn as_client(&self) -> &BlockChainClient {
+		&**self
+	}
+
+	fn flush(&self) {
+		self.flush_queue();
+		self.import_verified_blocks();
+	}
+}
+
+/// Creates a full client for the given spec with a database in the given temporary directory.
+pub fn new_ethcore_client(spec: &Spec, dir: &RandomTempPath) -> Arc<EthcoreClient> {
+	let db_config = DatabaseConfig::with_columns(::ethcore::db::NUM_COLUMNS);
+	EthcoreClient::new(
+		ClientConfig::default(),
+		spec,
+		dir.as_path(),
+		Arc::new(Miner::with_spec(spec)),
+		IoChannel::disconnected(),
+		&db_config
+	).unwrap()
+}
+
+pub struct TestIo<'p, C> where C: TestClient + 'p {
+	pub chain: &'p mut C,
 	pub snapshot_service: &'p TestSnapshotService,
 	pub queue: &'p mut VecDeque<TestPacket>,
 	pub sender: Option<PeerId>,
 	pub to_disconnect: HashSet<PeerId>,
+	pub reserved_peers: HashSet<PeerId>,
+	pub reports: Vec<(PeerId, PeerEvent)>,
 	overlay: RwLock<HashMap<BlockNumber, Bytes>>,
+	clock: Option<f64>,
+	warp_version: u8,
 }
 
-impl<'p> TestIo<'p> {
-	pub fn new(chain: &'p mut TestBlockChainClient, ss: &'p TestSnapshotService, queue: &'p mut VecDeque<TestPacket>, sender: Option<PeerId>) -> TestIo<'p> {
+impl<'p, C> TestIo<'p, C> where C: TestClient + 'p {
+	pub fn new(chain: &'p mut C, ss: &'p TestSnapshotService, queue: &'p mut VecDeque<TestPacket>, sender: Option<PeerId>) -> TestIo<'p, C> {
 		TestIo {
 			chain: chain,
 			snapshot_service: ss,
 			queue: queue,
 			sender: sender,
 			to_disconnect: HashSet::new(),
+			reserved_peers: HashSet::new(),
+			reports: Vec::new(),
 			overlay: RwLock::new(HashMap::new()),
+			clock: None,
+			warp_version: 1,
 		}
 	}
+
+	pub fn with_reserved_peers(mut self, reserved_peers: &HashSet<PeerId>) -> TestIo<'p, C> {
+		self.reserved_peers = reserved_peers.clone();
+		self
+	}
+
+	/// Pretend the sender doesn't speak the warp sync protocol.
+	pub fn eth_only(mut self) -> TestIo<'p, C> {
+		self.warp_version = 0;
+		self
+	}
+
+	/// Pretend the sender speaks the given version of the warp sync protocol.
+	pub fn with_warp_version(mut self, version: u8) -> TestIo<'p, C> {
+		self.warp_version = version;
+		self
+	}
+
+	/// Use a fixed time in seconds instead of the system clock.
+	pub fn with_clock(mut self, now: f64) -> TestIo<'p, C> {
+		self.clock = Some(now);
+		self
+	}
 }
 
-impl<'p> SyncIo for TestIo<'p> {
+impl<'p, C> SyncIo for TestIo<'p, C> where C: TestClient + 'p {
 	fn disable_peer(&mut self, peer_id: PeerId) {
 		self.disconnect_peer(peer_id);
 	}
@@ -56,6 +129,10 @@ impl<'p> SyncIo for TestIo<'p> {
 		self.to_disconnect.insert(peer_id);
 	}
 
+	fn report_peer(&mut self, peer_id: PeerId, event: PeerEvent) {
+		self.reports.push((peer_id, event));
+	}
+
 	fn is_expired(&self) -> bool {
 		false
 	}
@@ -83,7 +160,7 @@ impl<'p> SyncIo for TestIo<'p> {
 	}
 
 	fn chain(&self) -> &BlockChainClient {
-		self.chain
+		self.chain.as_client()
 	}
 
 	fn snapshot_service(&self) -> &SnapshotService {
@@ -94,17 +171,32 @@ impl<'p> SyncIo for TestIo<'p> {
 		None
 	}
 
+	fn is_reserved_peer(&self, peer_id: PeerId) -> bool {
+		self.reserved_peers.contains(&peer_id)
+	}
+
 	fn eth_protocol_version(&self, _peer: PeerId) -> u8 {
 		63
 	}
 
 	fn protocol_version(&self, protocol: &ProtocolId, peer_id: PeerId) -> u8 {
-		if protocol == &WARP_SYNC_PROTOCOL_ID { 1 } else { self.eth_protocol_version(peer_id) }
+		if protocol == &WARP_SYNC_PROTOCOL_ID {
+			self.warp_version
+		} else {
+			self.eth_protocol_version(peer_id)
+		}
 	}
 
 	fn chain_overlay(&self) -> &RwLock<HashMap<BlockNumber, Bytes>> {
 		&self.overlay
 	}
+
+	fn now(&self) -> f64 {
+		match self.clock {
+			Some(now) => now,
+			None => ::time::precise_time_s(),
+		}
+	}
 }
 
 pub struct TestPacket {
@@ -113,59 +205,88 @@ pub struct TestPacket {
 	pub recipient: PeerId,
 }
 
-pub struct TestPeer {
-	pub chain: TestBlockChainClient,
+pub struct TestPeer<C> where C: TestClient {
+	pub chain: C,
 	pub snapshot_service: Arc<TestSnapshotService>,
 	pub sync: RwLock<ChainSync>,
 	pub queue: VecDeque<TestPacket>,
+	pub reserved_peers: HashSet<PeerId>,
+}
+
+impl<C> TestPeer<C> where C: TestClient {
+	pub fn new(chain: C, config: SyncConfig) -> TestPeer<C> {
+		let sync = ChainSync::new(config, chain.as_client());
+		TestPeer {
+			sync: RwLock::new(sync),
+			snapshot_service: Arc::new(TestSnapshotService::new()),
+			chain: chain,
+			queue: VecDeque::new(),
+			reserved_peers: HashSet::new(),
+		}
+	}
 }
 
-pub struct TestNet {
-	pub peers: Vec<TestPeer>,
+pub struct TestNet<C> where C: TestClient {
+	pub peers: Vec<TestPeer<C>>,
 	pub started: bool,
+	/// Peer whose traffic is recorded and the recorder to write it to.
+	capture: Option<(usize, Arc<PacketRecorder>)>,
 }
 
-impl TestNet {
-	pub fn new(n: usize) -> TestNet {
-		Self::new_with_fork(n, None)
+impl TestNet<TestBlockChainClient> {
+	pub fn new(n: usize) -> TestNet<TestBlockChainClient> {
+		Self::new_with_forks(n, &[])
 	}
 
-	pub fn new_with_fork(n: usize, fork: Option<(BlockNumber, H256)>) -> TestNet {
+	pub fn new_with_forks(n: usize, forks: &[(BlockNumber, H256)]) -> TestNet<TestBlockChainClient> {
+		let mut config = SyncConfig::default();
+		for &(number, hash) in forks {
+			config.fork_blocks.push(number, hash);
+		}
+		Self::with_clients((0..n).map(|_| TestBlockChainClient::new()).collect(), config)
+	}
+}
+
+impl<C> TestNet<C> where C: TestClient {
+	/// Creates a network of peers connected over an in-process loopback, one for each client.
+	pub fn with_clients(clients: Vec<C>, config: SyncConfig) -> TestNet<C> {
 		let mut net = TestNet {
 			peers: Vec::new(),
 			started: false,
+			capture: None,
 		};
-		for _ in 0..n {
-			let chain = TestBlockChainClient::new();
-			let mut config = SyncConfig::default();
-			config.fork_block = fork;
-			let ss = Arc::new(TestSnapshotService::new());
-			let sync = ChainSync::new(config, &chain);
-			net.peers.push(TestPeer {
-				sync: RwLock::new(sync),
-				snapshot_service: ss,
-				chain: chain,
-				queue: VecDeque::new(),
-			});
+		for chain in clients {
+			net.peers.push(TestPeer::new(chain, config.clone()));
 		}
 		net
 	}
 
-	pub fn peer(&self, i: usize) -> &TestPeer {
+	pub fn peer(&self, i: usize) -> &TestPeer<C> {
 		self.peers.get(i).unwrap()
 	}
 
-	pub fn peer_mut(&mut self, i: usize) -> &mut TestPeer {
+	pub fn peer_mut(&mut self, i: usize) -> &mut TestPeer<C> {
 		self.peers.get_mut(i).unwrap()
 	}
 
+	/// Marks a pair of peers as reserved for each other.
+	pub fn reserve(&mut self, a: usize, b: usize) {
+		self.peer_mut(a).reserved_peers.insert(b as PeerId);
+		self.peer_mut(b).reserved_peers.insert(a as PeerId);
+	}
+
+	/// Records all packets sent and received by the given peer.
+	pub fn capture(&mut self, peer: usize, recorder: Arc<PacketRecorder>) {
+		self.capture = Some((peer, recorder));
+	}
+
 	pub fn start(&mut self) {
 		for peer in 0..self.peers.len() {
 			for client in 0..self.peers.len() {
 				if peer != client {
 					let mut p = self.peers.get_mut(peer).unwrap();
-					p.sync.write().restart(&mut TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(client as PeerId)));
-					p.sync.write().on_peer_connected(&mut TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(client as PeerId)), client as PeerId);
+					p.sync.write().restart(&mut TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(client as PeerId)).with_reserved_peers(&p.reserved_peers));
+					p.sync.write().on_peer_connected(&mut TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(client as PeerId)).with_reserved_peers(&p.reserved_peers), client as PeerId);
 				}
 			}
 		}
@@ -174,17 +295,26 @@ impl TestNet {
 	pub fn sync_step(&mut self) {
 		for peer in 0..self.peers.len() {
 			if let Some(packet) = self.peers[peer].queue.pop_front() {
+				if let Some((captured, ref recorder)) = self.capture {
+					let protocol = if packet.packet_id >= ETH_PACKET_COUNT { WARP_SYNC_PROTOCOL_ID } else { *b"eth" };
+					if captured == packet.recipient {
+						recorder.record(peer as PeerId, None, PacketDirection::Inbound, protocol, packet.packet_id, &packet.data);
+					} else if captured == peer {
+						recorder.record(packet.recipient, None, PacketDirection::Outbound, protocol, packet.packet_id, &packet.data);
+					}
+				}
 				let disconnecting = {
 					let mut p = self.peers.get_mut(packet.recipient).unwrap();
 					trace!("--- {} -> {} ---", peer, packet.recipient);
 					let to_disconnect = {
-						let mut io = TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(peer as PeerId));
+						let mut io = TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(peer as PeerId)).with_reserved_peers(&p.reserved_peers);
 						ChainSync::dispatch_packet(&p.sync, &mut io, peer as PeerId, packet.packet_id, &packet.data);
 						io.to_disconnect
 					};
+					p.chain.flush();
 					for d in &to_disconnect {
 						// notify this that disconnecting peers are disconnecting
-						let mut io = TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(*d));
+						let mut io = TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(*d)).with_reserved_peers(&p.reserved_peers);
 						p.sync.write().on_peer_aborting(&mut io, *d);
 					}
 					to_disconnect
@@ -192,7 +322,7 @@ impl TestNet {
 				for d in &disconnecting {
 					// notify other peers that this peer is disconnecting
 					let mut p = self.peers.get_mut(*d).unwrap();
-					let mut io = TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(peer as PeerId));
+					let mut io = TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(peer as PeerId)).with_reserved_peers(&p.reserved_peers);
 					p.sync.write().on_peer_aborting(&mut io, peer as PeerId);
 				}
 			}
@@ -203,12 +333,12 @@ impl TestNet {
 
 	pub fn sync_step_peer(&mut self, peer_num: usize) {
 		let mut peer = self.peer_mut(peer_num);
-		peer.sync.write().maintain_sync(&mut TestIo::new(&mut peer.chain, &peer.snapshot_service, &mut peer.queue, None));
+		peer.sync.write().maintain_sync(&mut TestIo::new(&mut peer.chain, &peer.snapshot_service, &mut peer.queue, None).with_reserved_peers(&peer.reserved_peers));
 	}
 
 	pub fn restart_peer(&mut self, i: usize) {
 		let peer = self.peer_mut(i);
-		peer.sync.write().restart(&mut TestIo::new(&mut peer.chain, &peer.snapshot_service, &mut peer.queue, None));
+		peer.sync.write().restart(&mut TestIo::new(&mut peer.chain, &peer.snapshot_service, &mut peer.queue, None).with_reserved_peers(&peer.reserved_peers));
 	}
 
 	pub fn sync(&mut self) -> u32 {
@@ -237,6 +367,6 @@ impl TestNet {
 
 	pub fn trigger_chain_new_blocks(&mut self, peer_id: usize) {
 		let mut peer = self.peer_mut(peer_id);
-		peer.sync.write().chain_new_blocks(&mut TestIo::new(&mut peer.chain, &peer.snapshot_service, &mut peer.queue, None), &[], &[], &[], &[], &[]);
+		peer.sync.write().chain_new_blocks(&mut TestIo::new(&mut peer.chain, &peer.snapshot_service, &mut peer.queue, None).with_reserved_peers(&peer.reserved_peers), &[], &[], &[], &[], &[]);
 	}
 }
@@ -0,0 +1,200 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use util::*;
+use rlp::{RlpStream, Stream};
+use network::*;
+use ethcore::client::{BlockChainClient, MiningBlockChainClient, Client as EthcoreClient, BlockID};
+use ethcore::db::NUM_COLUMNS;
+use ethcore::light::{Client as LightClient, QueueConfig};
+use ethcore::spec::Spec;
+use devtools::RandomTempPath;
+use chain::{ChainSync, NEW_BLOCK_HASHES_PACKET};
+use light_sync::{LightSync, LightSyncIo};
+use super::helpers::*;
+use ::SyncConfig;
+
+/// Id of the light node as seen by the full nodes.
+const LIGHT_PEER: PeerId = 1000;
+
+struct LightIo<'p> {
+	queue: &'p mut VecDeque<TestPacket>,
+	sender: Option<PeerId>,
+}
+
+impl<'p> LightSyncIo for LightIo<'p> {
+	fn disable_peer(&mut self, _peer_id: PeerId) {}
+
+	fn disconnect_peer(&mut self, _peer_id: PeerId) {}
+
+	fn respond(&mut self, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
+		self.queue.push_back(TestPacket {
+			data: data,
+			packet_id: packet_id,
+			recipient: self.sender.unwrap(),
+		});
+		Ok(())
+	}
+
+	fn send(&mut self, peer_id: PeerId, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
+		self.queue.push_back(TestPacket {
+			data: data,
+			packet_id: packet_id,
+			recipient: peer_id,
+		});
+		Ok(())
+	}
+
+	fn peer_session_info(&self, _peer_id: PeerId) -> Option<SessionInfo> {
+		None
+	}
+
+	fn is_expired(&self) -> bool {
+		false
+	}
+}
+
+/// A light node syncing headers from the full nodes of a `TestNet`.
+struct LightNet {
+	net: TestNet<Arc<EthcoreClient>>,
+	client: Arc<LightClient>,
+	sync: LightSync,
+	queue: VecDeque<TestPacket>,
+	_dirs: Vec<RandomTempPath>,
+}
+
+impl LightNet {
+	fn new(spec: &Spec, full_nodes: usize) -> LightNet {
+		let dirs = (0..full_nodes + 1).map(|_| RandomTempPath::new()).collect::<Vec<_>>();
+		let clients = dirs[1..].iter().map(|dir| new_ethcore_client(spec, dir)).collect();
+		let db = Database::open(&DatabaseConfig::with_columns(NUM_COLUMNS), dirs[0].as_path().to_str().unwrap()).unwrap();
+		let client = Arc::new(LightClient::new(QueueConfig::default(), spec, Arc::new(db)));
+		LightNet {
+			net: TestNet::with_clients(clients, SyncConfig::default()),
+			sync: LightSync::new(SyncConfig::default(), client.clone()),
+			client: client,
+			queue: VecDeque::new(),
+			_dirs: dirs,
+		}
+	}
+
+	/// Connects the light node to a full node.
+	fn connect(&mut self, full: usize) {
+		{
+			let p = self.net.peer_mut(full);
+			let mut io = TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(LIGHT_PEER)).eth_only();
+			p.sync.write().on_peer_connected(&mut io, LIGHT_PEER);
+		}
+		self.sync.on_peer_connected(&mut LightIo { queue: &mut self.queue, sender: Some(full as PeerId) }, full as PeerId);
+	}
+
+	/// Delivers packets until no node has anything left to send.
+	fn sync(&mut self) {
+		while !self.done() {
+			self.sync_step();
+		}
+	}
+
+	fn sync_step(&mut self) {
+		// full nodes only talk to the light node here.
+		for full in 0..self.net.peers.len() {
+			while let Some(packet) = self.net.peer_mut(full).queue.pop_front() {
+				assert_eq!(packet.recipient, LIGHT_PEER);
+				let mut io = LightIo { queue: &mut self.queue, sender: Some(full as PeerId) };
+				self.sync.on_packet(&mut io, full as PeerId, packet.packet_id, &packet.data);
+			}
+		}
+		while let Some(packet) = self.queue.pop_front() {
+			let p = self.net.peer_mut(packet.recipient);
+			let mut io = TestIo::new(&mut p.chain, &p.snapshot_service, &mut p.queue, Some(LIGHT_PEER)).eth_only();
+			ChainSync::dispatch_packet(&p.sync, &mut io, LIGHT_PEER, packet.packet_id, &packet.data);
+		}
+	}
+
+	fn done(&self) -> bool {
+		self.queue.is_empty() && self.net.peers.iter().all(|p| p.queue.is_empty())
+	}
+}
+
+fn add_blocks(client: &MiningBlockChainClient, spec: &Spec, author: Address, count: usize) {
+	for _ in 0..count {
+		let b = client.prepare_open_block(author.clone(), (3141562.into(), 31415620.into()), vec![]);
+		let b = b.close_and_lock().seal(&*spec.engine, vec![]).unwrap();
+		client.import_sealed_block(b).unwrap();
+	}
+}
+
+#[test]
+fn downloads_headers_from_full_node() {
+	::env_logger::init().ok();
+	let spec = Spec::new_null();
+	let mut net = LightNet::new(&spec, 1);
+	add_blocks(&*net.net.peer(0).chain, &spec, Address::default(), 300);
+
+	net.connect(0);
+	net.sync();
+
+	let full = net.net.peer(0).chain.chain_info();
+	let light = net.client.chain_info();
+	assert_eq!(light.best_block_number, 300);
+	assert_eq!(light.best_block_hash, full.best_block_hash);
+	assert_eq!(light.total_difficulty, full.total_difficulty);
+	assert_eq!(net.client.block_header(BlockID::Number(150)), net.net.peer(0).chain.block_header(BlockID::Number(150)));
+}
+
+#[test]
+fn reorganizes_to_heavier_fork() {
+	::env_logger::init().ok();
+	let spec = Spec::new_null();
+	let mut net = LightNet::new(&spec, 2);
+	add_blocks(&*net.net.peer(0).chain, &spec, Address::from(1), 10);
+	add_blocks(&*net.net.peer(1).chain, &spec, Address::from(2), 15);
+
+	net.connect(0);
+	net.sync();
+	assert_eq!(net.client.chain_info().best_block_hash, net.net.peer(0).chain.chain_info().best_block_hash);
+
+	// the second node's chain forks at genesis, so the light node has to step back to find the common ancestor.
+	net.connect(1);
+	net.sync();
+
+	let fork = net.net.peer(1).chain.chain_info();
+	let light = net.client.chain_info();
+	assert_eq!(light.best_block_number, 15);
+	assert_eq!(light.best_block_hash, fork.best_block_hash);
+	assert_eq!(net.client.block_header(BlockID::Number(5)), net.net.peer(1).chain.block_header(BlockID::Number(5)));
+}
+
+#[test]
+fn ignores_unverified_announcements_for_highest_block() {
+	::env_logger::init().ok();
+	let spec = Spec::new_null();
+	let mut net = LightNet::new(&spec, 1);
+	add_blocks(&*net.net.peer(0).chain, &spec, Address::default(), 10);
+
+	net.connect(0);
+	net.sync();
+	assert_eq!(net.sync.status().highest_block_number, Some(10));
+
+	// announce a block the peer can't deliver.
+	let mut rlp = RlpStream::new_list(1);
+	rlp.begin_list(2).append(&H256::from(1)).append(&1_000_000u64);
+	net.sync.on_packet(&mut LightIo { queue: &mut net.queue, sender: Some(0) }, 0, NEW_BLOCK_HASHES_PACKET, &rlp.out());
+	net.sync();
+
+	assert_eq!(net.client.chain_info().best_block_number, 10);
+	assert_eq!(net.sync.status().highest_block_number, Some(10));
+}
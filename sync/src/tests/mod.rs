@@ -18,3 +18,7 @@ pub mod helpers;
 pub mod snapshot;
 mod chain;
 mod rpc;
+mod replication;
+mod simulator;
+mod replay;
+mod light;
@@ -0,0 +1,125 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Replay of captured network traffic.
+//!
+//! Feeds the inbound packets of a capture made with `--network-capture` through the sync protocol
+//! handler of a simulated node, so that a sync issue seen on a live node can be reproduced.
+//! A capture file can be replayed into an empty test chain with
+//!
+//! ```text
+//! SYNC_REPLAY_CAPTURE=/path/to/capture RUST_LOG=sync=trace cargo test -p ethsync replay_capture_file -- --ignored --nocapture
+//! ```
+
+use std::env;
+use util::*;
+use network::{PeerId, CapturedPacket, PacketDirection, PacketRecorder, read_capture};
+use ethcore::client::{TestBlockChainClient, BlockChainClient, EachBlockWith};
+use devtools::RandomTempPath;
+use api::WARP_SYNC_PROTOCOL_ID;
+use super::helpers::*;
+use super::simulator::SimNode;
+use super::snapshot::TestSnapshotService;
+use ::SyncConfig;
+
+/// Replay inbound sync packets of a capture into the given node.
+///
+/// A captured peer is connected before its first packet is delivered. The sync clock follows
+/// the capture timestamps. Packets the sync sends in response are left in the node queue.
+/// Block import notifications are not part of a capture and are not replayed.
+pub fn replay(node: &mut SimNode, packets: &[CapturedPacket]) {
+	let eth_protocol = SyncConfig::default().subprotocol_name;
+	let mut connected = HashSet::new();
+	for packet in packets {
+		if packet.direction != PacketDirection::Inbound || (packet.protocol != eth_protocol && packet.protocol != WARP_SYNC_PROTOCOL_ID) {
+			continue;
+		}
+		let now = packet.timestamp as f64 / 1000.0;
+		let peer = packet.peer;
+		let mut to_disconnect = HashSet::new();
+		if connected.insert(peer) {
+			// peers speak the warp protocol, whose handshake takes over the main one.
+			to_disconnect = node.with_context(WARP_SYNC_PROTOCOL_ID, Some(peer), now, |handler, io| handler.connected(io, &peer));
+		}
+		to_disconnect.extend(node.with_context(packet.protocol, Some(peer), now, |handler, io| {
+			handler.read(io, &peer, packet.packet_id, &packet.data);
+		}));
+		for d in to_disconnect {
+			node.with_context(eth_protocol, Some(d), now, |handler, io| handler.disconnected(io, &d));
+			connected.remove(&d);
+		}
+	}
+}
+
+/// Read a capture file and replay it into the given node.
+pub fn replay_file(node: &mut SimNode, path: &str) {
+	let packets = read_capture(path).expect("Error reading capture");
+	replay(node, &packets);
+}
+
+fn new_node() -> SimNode {
+	SimNode::new(Arc::new(TestBlockChainClient::new()), Arc::new(TestSnapshotService::new()), SyncConfig::default())
+}
+
+#[test]
+fn replays_captured_sync() {
+	::env_logger::init().ok();
+	let temp = RandomTempPath::new();
+	let mut net = TestNet::new(2);
+	net.peer_mut(1).chain.add_blocks(300, EachBlockWith::Uncle);
+	net.capture(0, Arc::new(PacketRecorder::new(temp.as_str()).unwrap()));
+	net.sync();
+	assert_eq!(net.peer(0).chain.chain_info().best_block_number, 300);
+
+	let mut node = new_node();
+	replay_file(&mut node, temp.as_str());
+	assert_eq!(node.chain.chain_info().best_block_number, 300);
+	assert_eq!(*node.chain.blocks.read(), *net.peer(0).chain.blocks.read());
+}
+
+#[test]
+fn replay_sends_captured_requests() {
+	let temp = RandomTempPath::new();
+	let mut net = TestNet::new(2);
+	net.peer_mut(1).chain.add_blocks(100, EachBlockWith::Nothing);
+	net.capture(0, Arc::new(PacketRecorder::new(temp.as_str()).unwrap()));
+	net.sync();
+
+	let packets = read_capture(temp.as_str()).unwrap();
+	let sent: Vec<(PeerId, u8, Bytes)> = packets.iter()
+		.filter(|p| p.direction == PacketDirection::Outbound)
+		.map(|p| (p.peer, p.packet_id, p.data.clone()))
+		.collect();
+	assert!(!sent.is_empty());
+
+	let mut node = new_node();
+	// the test network speaks warp protocol version 1
+	node.warp_version = 1;
+	replay(&mut node, &packets);
+	let replayed: Vec<(PeerId, u8, Bytes)> = node.queue.iter().map(|p| (p.recipient, p.packet_id, p.data.clone())).collect();
+	assert_eq!(replayed, sent);
+}
+
+#[test]
+#[ignore]
+fn replay_capture_file() {
+	::env_logger::init().ok();
+	let path = env::var("SYNC_REPLAY_CAPTURE").expect("SYNC_REPLAY_CAPTURE must be set to the capture to replay");
+	let mut node = new_node();
+	replay_file(&mut node, &path);
+	let info = node.chain.chain_info();
+	println!("Replayed {}: best block #{} {}, {} packets sent", path, info.best_block_number, info.best_block_hash, node.queue.len());
+}
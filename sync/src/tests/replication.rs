@@ -0,0 +1,71 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use util::*;
+use ethcore::client::{BlockChainClient, MiningBlockChainClient, BlockID};
+use ethcore::spec::Spec;
+use devtools::RandomTempPath;
+use super::helpers::*;
+use ::SyncConfig;
+
+fn reserved_only_config() -> SyncConfig {
+	let mut config = SyncConfig::default();
+	config.reserved_peers_only = true;
+	config
+}
+
+fn add_blocks(client: &MiningBlockChainClient, spec: &Spec, count: usize) {
+	for _ in 0..count {
+		let b = client.prepare_open_block(Address::default(), (3141562.into(), 31415620.into()), vec![]);
+		let b = b.close_and_lock().seal(&*spec.engine, vec![]).unwrap();
+		client.import_sealed_block(b).unwrap();
+	}
+}
+
+#[test]
+fn replicates_chain_between_reserved_peers() {
+	::env_logger::init().ok();
+	let spec = Spec::new_null();
+	let dirs = [RandomTempPath::new(), RandomTempPath::new()];
+	let clients = dirs.iter().map(|dir| new_ethcore_client(&spec, dir)).collect::<Vec<_>>();
+	let mut net = TestNet::with_clients(clients, reserved_only_config());
+	net.reserve(0, 1);
+	add_blocks(&*net.peer(1).chain, &spec, 100);
+
+	net.sync();
+
+	let source = net.peer(1).chain.chain_info();
+	let replica = net.peer(0).chain.chain_info();
+	assert_eq!(replica.best_block_number, 100);
+	assert_eq!(replica.best_block_hash, source.best_block_hash);
+	assert!(net.peer(0).chain.block(BlockID::Number(50)).is_some());
+}
+
+#[test]
+fn ignores_peers_that_are_not_reserved() {
+	::env_logger::init().ok();
+	let spec = Spec::new_null();
+	let dirs = [RandomTempPath::new(), RandomTempPath::new(), RandomTempPath::new()];
+	let clients = dirs.iter().map(|dir| new_ethcore_client(&spec, dir)).collect::<Vec<_>>();
+	let mut net = TestNet::with_clients(clients, reserved_only_config());
+	net.reserve(0, 1);
+	add_blocks(&*net.peer(2).chain, &spec, 20);
+
+	net.sync();
+
+	assert_eq!(net.peer(0).chain.chain_info().best_block_number, 0);
+	assert_eq!(net.peer(1).chain.chain_info().best_block_number, 0);
+}
@@ -18,6 +18,8 @@ use super::super::NetworkConfiguration;
 use network::NetworkConfiguration as BasicNetworkConfiguration;
 use std::convert::From;
 use ipc::binary::{serialize, deserialize};
+use util::H256;
+use api::{ForkCheckpoints, chain_id};
 
 #[test]
 fn network_settings_serialize() {
@@ -27,3 +29,37 @@ fn network_settings_serialize() {
 
 	assert_eq!(net_cfg.udp_port, deserialized.udp_port);
 }
+
+#[test]
+fn chain_id_depends_on_genesis_and_forks() {
+	let mut forks = ForkCheckpoints::default();
+	let plain = chain_id(&H256::from(1), &forks);
+	assert!(plain != chain_id(&H256::from(2), &forks));
+
+	forks.push(10, H256::from(10));
+	forks.push(5, H256::from(5));
+	let forked = chain_id(&H256::from(1), &forks);
+	assert!(forked != plain);
+
+	let mut reordered = ForkCheckpoints::default();
+	reordered.push(5, H256::from(5));
+	reordered.push(10, H256::from(10));
+	assert_eq!(chain_id(&H256::from(1), &reordered), forked);
+}
+
+#[test]
+fn fork_checkpoints_are_sorted() {
+	let spec_forks = vec![(1_920_000, H256::from(3)), (2_463_000, H256::from(4))];
+	let cli_forks = vec![(2_675_000, H256::from(5)), (1_150_000, H256::from(1)), (1_920_000, H256::from(3)), (1_920_000, H256::from(2))];
+	let mut forks = ForkCheckpoints::default();
+	for &(number, hash) in spec_forks.iter().chain(cli_forks.iter()) {
+		assert!(forks.push(number, hash));
+	}
+	assert_eq!(forks.as_slice(), &[
+		(1_150_000, H256::from(1)),
+		(1_920_000, H256::from(2)),
+		(1_920_000, H256::from(3)),
+		(2_463_000, H256::from(4)),
+		(2_675_000, H256::from(5)),
+	][..]);
+}
@@ -0,0 +1,586 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Deterministic in-process network simulator.
+//!
+//! Nodes run the sync protocol handler over simulated links with configurable latency, packet loss
+//! and partitions. Time only advances with the simulation and link conditions are drawn from a seeded
+//! generator, so a scenario plays out the same way every time it is run.
+//!
+//! Snapshots come from `TestSnapshotService`, so warp sync only covers fetching the manifest and
+//! chunks; restoring them and syncing the blocks after the snapshot is not simulated.
+
+use std::cell::RefCell;
+use util::*;
+use io::{IoChannel, TimerToken};
+use network::{NetworkProtocolHandler, NetworkContext, NetworkIoMessage, NetworkError, PeerId, PacketId, ProtocolId, PeerEvent, SessionInfo};
+use rand::{Rng, SeedableRng, XorShiftRng};
+use ethcore::client::{TestBlockChainClient, BlockChainClient, BlockID, EachBlockWith};
+use api::{SyncProtocolHandler, WARP_SYNC_PROTOCOL_ID};
+use chain::{PROTOCOL_VERSION_63, WARP_SYNC_PROTOCOL_VERSIONS};
+use super::helpers::TestPacket;
+use super::snapshot::TestSnapshotService;
+use ::SyncConfig;
+
+/// Interval of the sync maintenance timer.
+const MAINTAIN_INTERVAL_MS: u64 = 1000;
+/// Time it takes for a connection that lost a packet to be dropped.
+const STALL_TIMEOUT_MS: u64 = 10_000;
+/// Delay before a dropped connection is re-established.
+const RECONNECT_DELAY_MS: u64 = 5_000;
+
+/// Conditions of a link between two nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkConfig {
+	/// One-way delay in milliseconds.
+	pub latency_ms: u64,
+	/// Maximum random delay added to the latency.
+	pub jitter_ms: u64,
+	/// Probability of a packet being lost, between 0 and 1.
+	pub loss: f64,
+}
+
+impl Default for LinkConfig {
+	fn default() -> Self {
+		LinkConfig {
+			latency_ms: 0,
+			jitter_ms: 0,
+			loss: 0.0,
+		}
+	}
+}
+
+/// Simulation statistics.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SimStats {
+	/// Packets delivered.
+	pub delivered: usize,
+	/// Packets lost or blocked by a partition.
+	pub dropped: usize,
+	/// Connections dropped.
+	pub disconnects: usize,
+}
+
+enum Event {
+	/// Deliver a packet sent over a connection.
+	Deliver(usize, u64, TestPacket),
+	/// Drop a stalled connection.
+	Reset(usize, usize, u64),
+	/// Re-establish a dropped connection.
+	Reconnect(usize, usize),
+	/// Run the sync maintenance timer on all nodes.
+	Maintain,
+}
+
+/// Context the handler of a simulated node is called with. Sent packets and disconnects are
+/// collected for the simulator to act on; all peers speak eth/63 and the node's warp protocol version.
+struct SimContext<'a> {
+	protocol: ProtocolId,
+	sender: Option<PeerId>,
+	reserved_peers: &'a HashSet<PeerId>,
+	warp_version: u8,
+	now: f64,
+	sent: RefCell<Vec<TestPacket>>,
+	to_disconnect: RefCell<HashSet<PeerId>>,
+}
+
+impl<'a> SimContext<'a> {
+	fn new(protocol: ProtocolId, sender: Option<PeerId>, reserved_peers: &'a HashSet<PeerId>, warp_version: u8, now: f64) -> SimContext<'a> {
+		SimContext {
+			protocol: protocol,
+			sender: sender,
+			reserved_peers: reserved_peers,
+			warp_version: warp_version,
+			now: now,
+			sent: RefCell::new(Vec::new()),
+			to_disconnect: RefCell::new(HashSet::new()),
+		}
+	}
+}
+
+impl<'a> NetworkContext for SimContext<'a> {
+	fn send(&self, peer: PeerId, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
+		self.sent.borrow_mut().push(TestPacket { data: data, packet_id: packet_id, recipient: peer });
+		Ok(())
+	}
+
+	fn send_protocol(&self, _protocol: ProtocolId, peer: PeerId, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
+		self.send(peer, packet_id, data)
+	}
+
+	fn respond(&self, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
+		self.send(self.sender.expect("respond is only called when handling a packet; qed"), packet_id, data)
+	}
+
+	fn io_channel(&self) -> IoChannel<NetworkIoMessage> {
+		IoChannel::disconnected()
+	}
+
+	fn disable_peer(&self, peer: PeerId) {
+		self.disconnect_peer(peer);
+	}
+
+	fn disconnect_peer(&self, peer: PeerId) {
+		self.to_disconnect.borrow_mut().insert(peer);
+	}
+
+	fn report_peer(&self, _peer: PeerId, _event: PeerEvent) {}
+
+	fn is_expired(&self) -> bool {
+		false
+	}
+
+	fn register_timer(&self, _token: TimerToken, _ms: u64) -> Result<(), NetworkError> {
+		Ok(())
+	}
+
+	fn peer_client_version(&self, peer: PeerId) -> String {
+		format!("sim/{}", peer)
+	}
+
+	fn session_info(&self, _peer: PeerId) -> Option<SessionInfo> {
+		None
+	}
+
+	fn is_reserved_peer(&self, peer: PeerId) -> bool {
+		self.reserved_peers.contains(&peer)
+	}
+
+	fn protocol_version(&self, protocol: ProtocolId, _peer: PeerId) -> Option<u8> {
+		if protocol == WARP_SYNC_PROTOCOL_ID {
+			Some(self.warp_version)
+		} else {
+			Some(PROTOCOL_VERSION_63)
+		}
+	}
+
+	fn subprotocol_name(&self) -> ProtocolId {
+		self.protocol
+	}
+
+	fn now(&self) -> f64 {
+		self.now
+	}
+}
+
+/// A simulated node.
+pub struct SimNode {
+	pub chain: Arc<TestBlockChainClient>,
+	pub snapshot_service: Arc<TestSnapshotService>,
+	pub reserved_peers: HashSet<PeerId>,
+	/// Warp protocol version spoken by all peers.
+	pub warp_version: u8,
+	/// Packets sent by the handler and not yet put on a link.
+	pub queue: VecDeque<TestPacket>,
+	handler: SyncProtocolHandler,
+}
+
+impl SimNode {
+	pub fn new(chain: Arc<TestBlockChainClient>, snapshot_service: Arc<TestSnapshotService>, config: SyncConfig) -> SimNode {
+		SimNode {
+			handler: SyncProtocolHandler::new(config, chain.clone(), snapshot_service.clone()),
+			chain: chain,
+			snapshot_service: snapshot_service,
+			reserved_peers: HashSet::new(),
+			warp_version: WARP_SYNC_PROTOCOL_VERSIONS.iter().map(|&(version, _)| version).max().unwrap_or(0),
+			queue: VecDeque::new(),
+		}
+	}
+
+	/// Runs `f` against the handler with a context for the given subprotocol. Returns the peers to disconnect.
+	pub fn with_context<F>(&mut self, protocol: ProtocolId, sender: Option<PeerId>, now: f64, f: F) -> HashSet<PeerId> where F: FnOnce(&SyncProtocolHandler, &NetworkContext) {
+		let (sent, to_disconnect) = {
+			let context = SimContext::new(protocol, sender, &self.reserved_peers, self.warp_version, now);
+			f(&self.handler, &context);
+			(context.sent.into_inner(), context.to_disconnect.into_inner())
+		};
+		self.queue.extend(sent);
+		to_disconnect
+	}
+}
+
+/// A network of nodes connected over simulated links.
+///
+/// The transport is reliable like TCP: packets on a link arrive in order, and a lost packet stalls
+/// the connection which is then dropped after `STALL_TIMEOUT_MS` and re-established later.
+pub struct SimNetwork {
+	pub peers: Vec<SimNode>,
+	pub stats: SimStats,
+	clock_ms: u64,
+	seq: u64,
+	events: BTreeMap<(u64, u64), Event>,
+	default_link: LinkConfig,
+	links: HashMap<(usize, usize), LinkConfig>,
+	/// Arrival time of the last packet sent on a directed link.
+	link_clock: HashMap<(usize, usize), u64>,
+	/// Open connections and their session numbers.
+	sessions: HashMap<(usize, usize), u64>,
+	next_session: u64,
+	/// Partition group of each node.
+	groups: Vec<usize>,
+	rng: XorShiftRng,
+}
+
+impl SimNetwork {
+	/// Creates a network of `n` nodes with empty test chains.
+	pub fn new(n: usize, seed: u32) -> SimNetwork {
+		Self::with_clients((0..n).map(|_| TestBlockChainClient::new()).collect(), SyncConfig::default(), seed)
+	}
+
+	/// Creates a network with a node for each client.
+	pub fn with_clients(clients: Vec<TestBlockChainClient>, config: SyncConfig, seed: u32) -> SimNetwork {
+		let nodes = clients.into_iter().map(|c| SimNode::new(Arc::new(c), Arc::new(TestSnapshotService::new()), config.clone())).collect();
+		Self::with_nodes(nodes, seed)
+	}
+
+	/// Creates a network of the given nodes. Nodes are not connected until `start` is called.
+	pub fn with_nodes(nodes: Vec<SimNode>, seed: u32) -> SimNetwork {
+		let n = nodes.len();
+		SimNetwork {
+			peers: nodes,
+			stats: SimStats::default(),
+			clock_ms: 0,
+			seq: 0,
+			events: BTreeMap::new(),
+			default_link: LinkConfig::default(),
+			links: HashMap::new(),
+			link_clock: HashMap::new(),
+			sessions: HashMap::new(),
+			next_session: 0,
+			groups: vec![0; n],
+			rng: XorShiftRng::from_seed([seed, 0x193a_6754, 0xa8a7_d469, 0x9783_0e05]),
+		}
+	}
+
+	pub fn peer(&self, i: usize) -> &SimNode {
+		&self.peers[i]
+	}
+
+	pub fn peer_mut(&mut self, i: usize) -> &mut SimNode {
+		&mut self.peers[i]
+	}
+
+	/// Current simulated time in milliseconds.
+	pub fn now_ms(&self) -> u64 {
+		self.clock_ms
+	}
+
+	/// Sets conditions for all links without explicit configuration.
+	pub fn set_default_link(&mut self, link: LinkConfig) {
+		self.default_link = link;
+	}
+
+	/// Sets conditions for the link between two nodes, in both directions.
+	pub fn set_link(&mut self, a: usize, b: usize, link: LinkConfig) {
+		self.links.insert((a, b), link.clone());
+		self.links.insert((b, a), link);
+	}
+
+	/// Splits the network. Packets between nodes in different groups are lost.
+	/// Nodes not listed in any group are put together in one more group.
+	pub fn partition(&mut self, groups: &[&[usize]]) {
+		self.groups = vec![0; self.peers.len()];
+		for (g, nodes) in groups.iter().enumerate() {
+			for n in nodes.iter() {
+				self.groups[*n] = g + 1;
+			}
+		}
+	}
+
+	/// Removes all partitions.
+	pub fn heal(&mut self) {
+		self.groups = vec![0; self.peers.len()];
+	}
+
+	/// Connects every pair of nodes and starts the maintenance timer.
+	pub fn start(&mut self) {
+		for a in 0..self.peers.len() {
+			for b in (a + 1)..self.peers.len() {
+				self.connect(a, b);
+			}
+		}
+		self.schedule(MAINTAIN_INTERVAL_MS, Event::Maintain);
+	}
+
+	/// Opens a connection between two nodes.
+	pub fn connect(&mut self, a: usize, b: usize) {
+		let key = (min(a, b), max(a, b));
+		if self.sessions.contains_key(&key) {
+			return;
+		}
+		self.sessions.insert(key, self.next_session);
+		self.next_session += 1;
+		self.link_clock.remove(&(a, b));
+		self.link_clock.remove(&(b, a));
+		self.on_connected(a, b);
+		self.on_connected(b, a);
+	}
+
+	/// Closes a connection between two nodes. It is re-established after `RECONNECT_DELAY_MS`.
+	pub fn disconnect(&mut self, a: usize, b: usize) {
+		if self.sessions.remove(&(min(a, b), max(a, b))).is_none() {
+			return;
+		}
+		trace!(target: "sync", "--- {} x {} at {}ms ---", a, b, self.clock_ms);
+		self.stats.disconnects += 1;
+		self.on_aborting(a, b);
+		self.on_aborting(b, a);
+		self.schedule(RECONNECT_DELAY_MS, Event::Reconnect(a, b));
+	}
+
+	/// Runs the simulation for the given time.
+	pub fn run_for(&mut self, duration_ms: u64) {
+		let end = self.clock_ms + duration_ms;
+		self.run_until(|_| false, duration_ms);
+		self.clock_ms = end;
+	}
+
+	/// Runs the simulation until the condition holds or `max_ms` of simulated time have passed.
+	/// Returns `true` if the condition was met.
+	pub fn run_until<F>(&mut self, condition: F, max_ms: u64) -> bool where F: Fn(&SimNetwork) -> bool {
+		let end = self.clock_ms + max_ms;
+		loop {
+			if condition(self) {
+				return true;
+			}
+			let key = match self.events.keys().next() {
+				Some(key) if key.0 <= end => key.clone(),
+				_ => return false,
+			};
+			let event = self.events.remove(&key).expect("key was just read from the map; qed");
+			self.clock_ms = key.0;
+			self.process(event);
+		}
+	}
+
+	fn schedule(&mut self, delay_ms: u64, event: Event) {
+		self.schedule_at(self.clock_ms + delay_ms, event);
+	}
+
+	fn schedule_at(&mut self, at_ms: u64, event: Event) {
+		self.events.insert((at_ms, self.seq), event);
+		self.seq += 1;
+	}
+
+	fn session(&self, a: usize, b: usize) -> Option<u64> {
+		self.sessions.get(&(min(a, b), max(a, b))).cloned()
+	}
+
+	fn process(&mut self, event: Event) {
+		match event {
+			Event::Deliver(from, session, packet) => {
+				if self.session(from, packet.recipient) == Some(session) {
+					self.deliver(from, packet);
+				}
+			},
+			Event::Reset(a, b, session) => {
+				if self.session(a, b) == Some(session) {
+					self.disconnect(a, b);
+				}
+			},
+			Event::Reconnect(a, b) => {
+				if self.groups[a] == self.groups[b] {
+					self.connect(a, b);
+				} else {
+					self.schedule(RECONNECT_DELAY_MS, Event::Reconnect(a, b));
+				}
+			},
+			Event::Maintain => {
+				for i in 0..self.peers.len() {
+					self.maintain(i);
+				}
+				self.schedule(MAINTAIN_INTERVAL_MS, Event::Maintain);
+			},
+		}
+	}
+
+	fn deliver(&mut self, from: usize, packet: TestPacket) {
+		let to = packet.recipient;
+		trace!(target: "sync", "--- {} -> {} at {}ms ---", from, to, self.clock_ms);
+		self.stats.delivered += 1;
+		let now = self.now_s();
+		let mut to_disconnect = {
+			let p = &mut self.peers[to];
+			let best = best_hash(p);
+			let mut to_disconnect = p.with_context(WARP_SYNC_PROTOCOL_ID, Some(from as PeerId), now, |handler, io| {
+				handler.read(io, &(from as PeerId), packet.packet_id, &packet.data);
+			});
+			if best_hash(p) != best {
+				// the client notifies sync about imported blocks
+				to_disconnect.extend(p.with_context(*b"eth", None, now, |handler, io| {
+					handler.chain_new_blocks(io, &[], &[], &[], &[], &[]);
+				}));
+			}
+			to_disconnect
+		};
+		self.send_queued(to);
+		for peer in to_disconnect.drain() {
+			self.disconnect(to, peer);
+		}
+	}
+
+	fn maintain(&mut self, i: usize) {
+		let now = self.now_s();
+		let to_disconnect = self.peers[i].with_context(*b"eth", None, now, |handler, io| handler.timeout(io, 0));
+		self.send_queued(i);
+		for peer in to_disconnect {
+			self.disconnect(i, peer);
+		}
+	}
+
+	fn on_connected(&mut self, local: usize, remote: usize) {
+		let now = self.now_s();
+		// peers speak the warp protocol, whose handshake takes over the main one.
+		self.peers[local].with_context(WARP_SYNC_PROTOCOL_ID, Some(remote as PeerId), now, |handler, io| handler.connected(io, &(remote as PeerId)));
+		self.send_queued(local);
+	}
+
+	fn on_aborting(&mut self, local: usize, remote: usize) {
+		let now = self.now_s();
+		self.peers[local].with_context(*b"eth", Some(remote as PeerId), now, |handler, io| handler.disconnected(io, &(remote as PeerId)));
+		self.send_queued(local);
+	}
+
+	/// Moves packets queued by a node onto the links.
+	fn send_queued(&mut self, from: usize) {
+		while let Some(packet) = self.peers[from].queue.pop_front() {
+			let to = packet.recipient;
+			let session = match self.session(from, to) {
+				Some(session) => session,
+				None => continue,
+			};
+			let link = self.links.get(&(from, to)).unwrap_or(&self.default_link).clone();
+			if self.groups[from] != self.groups[to] || (link.loss > 0.0 && self.rng.gen::<f64>() < link.loss) {
+				self.stats.dropped += 1;
+				self.schedule(STALL_TIMEOUT_MS, Event::Reset(from, to, session));
+				continue;
+			}
+			let jitter = if link.jitter_ms > 0 { self.rng.gen_range(0, link.jitter_ms + 1) } else { 0 };
+			let earliest = self.link_clock.get(&(from, to)).cloned().unwrap_or(0);
+			let at = max(self.clock_ms + link.latency_ms + jitter, earliest);
+			self.link_clock.insert((from, to), at);
+			self.schedule_at(at, Event::Deliver(from, session, packet));
+		}
+	}
+
+	fn now_s(&self) -> f64 {
+		self.clock_ms as f64 / 1000.0
+	}
+}
+
+fn best_hash(peer: &SimNode) -> H256 {
+	peer.chain.chain_info().best_block_hash
+}
+
+fn all_synced(net: &SimNetwork) -> bool {
+	let best = best_hash(net.peer(0));
+	net.peers.iter().all(|p| best_hash(p) == best)
+}
+
+#[test]
+fn syncs_many_nodes_with_latency() {
+	::env_logger::init().ok();
+	let mut net = SimNetwork::new(20, 1);
+	net.peer_mut(0).chain.add_blocks(300, EachBlockWith::Uncle);
+	net.set_default_link(LinkConfig { latency_ms: 50, jitter_ms: 30, loss: 0.0 });
+	net.start();
+	assert!(net.run_until(all_synced, 60_000));
+	assert!(net.peers.iter().all(|p| p.chain.block(BlockID::Number(300)).is_some()));
+	assert_eq!(net.stats.dropped, 0);
+}
+
+#[test]
+fn recovers_from_packet_loss() {
+	::env_logger::init().ok();
+	let mut net = SimNetwork::new(5, 2);
+	net.peer_mut(0).chain.add_blocks(200, EachBlockWith::Nothing);
+	net.set_default_link(LinkConfig { latency_ms: 20, jitter_ms: 10, loss: 0.05 });
+	net.set_link(0, 1, LinkConfig { latency_ms: 200, jitter_ms: 100, loss: 0.3 });
+	net.start();
+	assert!(net.run_until(all_synced, 30 * 60_000));
+	assert!(net.stats.dropped > 0);
+	assert!(net.stats.disconnects > 0);
+}
+
+#[test]
+fn same_seed_replays_same_run() {
+	let run = |seed| {
+		let mut net = SimNetwork::new(2, seed);
+		net.peer_mut(0).chain.add_blocks(100, EachBlockWith::Nothing);
+		net.set_default_link(LinkConfig { latency_ms: 30, jitter_ms: 30, loss: 0.1 });
+		net.start();
+		assert!(net.run_until(all_synced, 30 * 60_000));
+		(net.now_ms(), net.stats.clone())
+	};
+	assert_eq!(run(7), run(7));
+}
+
+#[test]
+fn fetches_snapshot_over_lossy_links() {
+	::env_logger::init().ok();
+	let mut net = SimNetwork::with_nodes(vec![
+		SimNode::new(Arc::new(TestBlockChainClient::new()), Arc::new(TestSnapshotService::new_with_snapshot(16, H256::new(), 500000)), SyncConfig::default()),
+		SimNode::new(Arc::new(TestBlockChainClient::new()), Arc::new(TestSnapshotService::new()), SyncConfig::default()),
+	], 4);
+	net.peer_mut(0).chain.add_blocks(1, EachBlockWith::Nothing);
+	net.set_default_link(LinkConfig { latency_ms: 50, jitter_ms: 30, loss: 0.1 });
+	net.start();
+	assert!(net.run_until(|net| net.peer(1).snapshot_service.restoration_complete(), 30 * 60_000));
+}
+
+#[test]
+fn reorgs_after_partition_heals() {
+	::env_logger::init().ok();
+	let mut net = SimNetwork::new(6, 3);
+	for i in 0..6 {
+		net.peer_mut(i).chain.add_blocks(50, EachBlockWith::Nothing);
+	}
+	// each side has its own fork, the second one is heavier
+	net.peer_mut(0).chain.add_blocks(20, EachBlockWith::Nothing);
+	net.peer_mut(3).chain.add_blocks(30, EachBlockWith::Uncle);
+	net.set_default_link(LinkConfig { latency_ms: 40, jitter_ms: 20, loss: 0.0 });
+	net.partition(&[&[0, 1, 2], &[3, 4, 5]]);
+	net.start();
+	net.run_for(60_000);
+	assert_eq!(best_hash(net.peer(1)), best_hash(net.peer(0)));
+	assert_eq!(best_hash(net.peer(4)), best_hash(net.peer(3)));
+	assert!(best_hash(net.peer(0)) != best_hash(net.peer(3)));
+
+	net.heal();
+	let heavy = best_hash(net.peer(3));
+	assert!(net.run_until(all_synced, 5 * 60_000));
+	assert_eq!(best_hash(net.peer(0)), heavy);
+}
+
+#[test]
+fn disconnects_peers_after_they_pass_a_fork() {
+	::env_logger::init().ok();
+	let ref_client = TestBlockChainClient::new();
+	ref_client.add_blocks(50, EachBlockWith::Uncle);
+	let mut config = SyncConfig::default();
+	config.fork_blocks.push(50, ref_client.block_hash(BlockID::Number(50)).unwrap());
+	let mut net = SimNetwork::with_clients(vec![TestBlockChainClient::new(), TestBlockChainClient::new()], config, 4);
+	net.peer_mut(0).chain.add_blocks(10, EachBlockWith::Nothing);
+	net.start();
+	net.run_for(10_000);
+	// too short to tell yet
+	assert_eq!(net.stats.disconnects, 0);
+
+	net.peer_mut(0).chain.add_blocks(90, EachBlockWith::Nothing);
+	net.run_for(6 * 60_000);
+	assert!(net.stats.disconnects > 0);
+	assert_eq!(net.peer(1).chain.chain_info().best_block_number, 0);
+}
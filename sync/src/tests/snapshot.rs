@@ -62,6 +62,15 @@ impl TestSnapshotService {
 			block_restoration_chunks: Mutex::new(HashMap::new()),
 		}
 	}
+
+	/// Whether a restoration was started and has been fed all of its chunks.
+	pub fn restoration_complete(&self) -> bool {
+		match *self.restoration_manifest.lock() {
+			Some(ref manifest) => self.state_restoration_chunks.lock().len() == manifest.state_hashes.len() &&
+				self.block_restoration_chunks.lock().len() == manifest.block_hashes.len(),
+			None => false,
+		}
+	}
 }
 
 impl SnapshotService for TestSnapshotService {
@@ -0,0 +1,177 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Node allowlist for permissioned networks
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use node_table::{NodeId, node_id_from_str};
+use error::NetworkError;
+
+/// Set of nodes allowed to connect, backed by a file.
+///
+/// The file lists one node per line, either as an enode URL or as a hex node id.
+/// Empty lines and lines starting with `#` are ignored.
+pub struct Allowlist {
+	path: PathBuf,
+	nodes: HashSet<NodeId>,
+}
+
+impl Allowlist {
+	/// Load the allowlist from a file. Fails if the file doesn't exist.
+	pub fn load(path: &str) -> Result<Allowlist, NetworkError> {
+		let mut allowlist = Allowlist {
+			path: PathBuf::from(path),
+			nodes: HashSet::new(),
+		};
+		try!(allowlist.reload());
+		Ok(allowlist)
+	}
+
+	/// Read the file again. The current list is kept if the file can't be parsed.
+	pub fn reload(&mut self) -> Result<(), NetworkError> {
+		let mut content = String::new();
+		let mut file = try!(File::open(&self.path));
+		try!(file.read_to_string(&mut content));
+		let mut nodes = HashSet::new();
+		for line in content.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+			nodes.insert(try!(node_id_from_str(line)));
+		}
+		self.nodes = nodes;
+		Ok(())
+	}
+
+	/// Check if a node is allowed to connect.
+	pub fn contains(&self, id: &NodeId) -> bool {
+		self.nodes.contains(id)
+	}
+
+	/// Allowed nodes.
+	pub fn nodes(&self) -> &HashSet<NodeId> {
+		&self.nodes
+	}
+
+	/// Allow a node and save the list. Returns `false` if the node was already allowed.
+	/// The list is left unchanged if it can't be saved.
+	pub fn insert(&mut self, id: NodeId) -> Result<bool, NetworkError> {
+		if self.nodes.contains(&id) {
+			return Ok(false);
+		}
+		let mut nodes = self.nodes.clone();
+		nodes.insert(id);
+		try!(self.save(&nodes));
+		self.nodes = nodes;
+		Ok(true)
+	}
+
+	/// Remove a node and save the list. Returns `false` if the node was not allowed.
+	/// The list is left unchanged if it can't be saved.
+	pub fn remove(&mut self, id: &NodeId) -> Result<bool, NetworkError> {
+		if !self.nodes.contains(id) {
+			return Ok(false);
+		}
+		let mut nodes = self.nodes.clone();
+		nodes.remove(id);
+		try!(self.save(&nodes));
+		self.nodes = nodes;
+		Ok(true)
+	}
+
+	fn save(&self, nodes: &HashSet<NodeId>) -> Result<(), NetworkError> {
+		let mut ids: Vec<String> = nodes.iter().map(|id| id.hex()).collect();
+		ids.sort();
+		let mut file = try!(File::create(&self.path));
+		try!(file.write_all(b"# Node ids allowed to connect\n"));
+		for id in ids {
+			try!(writeln!(file, "{}", id));
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs::{self, File};
+	use std::io::Write;
+	use std::str::FromStr;
+	use node_table::NodeId;
+	use devtools::RandomTempPath;
+
+	const NODE1: &'static str = "a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c";
+	const NODE2: &'static str = "b979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c";
+
+	fn write(path: &str, content: &str) {
+		File::create(path).unwrap().write_all(content.as_bytes()).unwrap();
+	}
+
+	#[test]
+	fn loads_and_reloads_allowlist() {
+		let dir = RandomTempPath::create_dir();
+		let path = dir.new_in("allowlist");
+		write(&path, &format!("# consortium nodes\n\nenode://{}@22.99.55.44:7770\n", NODE1));
+		let mut allowlist = Allowlist::load(&path).unwrap();
+		assert!(allowlist.contains(&NodeId::from_str(NODE1).unwrap()));
+		assert!(!allowlist.contains(&NodeId::from_str(NODE2).unwrap()));
+
+		write(&path, &format!("{}\n{}\n", NODE1, NODE2));
+		allowlist.reload().unwrap();
+		assert_eq!(allowlist.nodes().len(), 2);
+
+		// a broken file keeps the current list
+		write(&path, "not a node\n");
+		assert!(allowlist.reload().is_err());
+		assert_eq!(allowlist.nodes().len(), 2);
+	}
+
+	#[test]
+	fn saves_changes() {
+		let dir = RandomTempPath::create_dir();
+		let path = dir.new_in("allowlist");
+		write(&path, "");
+		let mut allowlist = Allowlist::load(&path).unwrap();
+		assert!(allowlist.nodes().is_empty());
+		assert!(allowlist.insert(NodeId::from_str(NODE1).unwrap()).unwrap());
+		assert!(!allowlist.insert(NodeId::from_str(NODE1).unwrap()).unwrap());
+		assert!(allowlist.insert(NodeId::from_str(NODE2).unwrap()).unwrap());
+		assert!(allowlist.remove(&NodeId::from_str(NODE2).unwrap()).unwrap());
+
+		let loaded = Allowlist::load(&path).unwrap();
+		assert_eq!(loaded.nodes().len(), 1);
+		assert!(loaded.contains(&NodeId::from_str(NODE1).unwrap()));
+	}
+
+	#[test]
+	fn keeps_list_when_save_fails() {
+		let dir = RandomTempPath::create_dir();
+		let subdir = dir.new_in("sub");
+		fs::create_dir(&subdir).unwrap();
+		let path = dir.new_in("sub/allowlist");
+		write(&path, "");
+		let mut allowlist = Allowlist::load(&path).unwrap();
+		fs::remove_dir_all(&subdir).unwrap();
+		assert!(allowlist.insert(NodeId::from_str(NODE1).unwrap()).is_err());
+		assert!(allowlist.nodes().is_empty());
+	}
+
+	#[test]
+	fn fails_on_missing_file() {
+		let dir = RandomTempPath::create_dir();
+		assert!(Allowlist::load(&dir.new_in("allowlist")).is_err());
+	}
+}
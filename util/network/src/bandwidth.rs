@@ -0,0 +1,202 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Upload and download rate limiting
+
+use std::cmp::min;
+use std::sync::Arc;
+use std::usize;
+use parking_lot::Mutex;
+use time;
+use host::NetworkConfiguration;
+
+const NS_PER_SEC: u64 = 1_000_000_000;
+
+/// Token bucket limiting the number of bytes transferred per second.
+/// Allows bursts of up to one second worth of traffic.
+#[derive(Debug)]
+pub struct RateLimiter {
+	/// Bytes per second
+	rate: usize,
+	/// Bytes that can be transferred right now
+	allowance: usize,
+	/// Last time the allowance was refilled, in nanoseconds
+	last_refill_ns: u64,
+}
+
+impl RateLimiter {
+	/// Create a new limiter for the given rate in bytes per second.
+	pub fn new(rate: usize) -> RateLimiter {
+		RateLimiter {
+			rate: rate,
+			allowance: rate,
+			last_refill_ns: time::precise_time_ns(),
+		}
+	}
+
+	fn refill(&mut self, now_ns: u64) {
+		let elapsed = now_ns.saturating_sub(self.last_refill_ns);
+		let earned = (elapsed as f64 * self.rate as f64 / NS_PER_SEC as f64) as usize;
+		if earned > 0 {
+			self.allowance = min(self.rate, self.allowance.saturating_add(earned));
+			self.last_refill_ns = now_ns;
+		}
+	}
+
+	fn available_at(&mut self, now_ns: u64) -> usize {
+		self.refill(now_ns);
+		self.allowance
+	}
+
+	/// Number of bytes that can be transferred right now.
+	pub fn available(&mut self) -> usize {
+		self.available_at(time::precise_time_ns())
+	}
+
+	/// Account for transferred bytes.
+	pub fn consume(&mut self, size: usize) {
+		self.allowance = self.allowance.saturating_sub(size);
+	}
+}
+
+/// Bandwidth limits shared by all connections of the host.
+#[derive(Debug, Default)]
+pub struct BandwidthLimits {
+	upload: Option<Mutex<RateLimiter>>,
+	download: Option<Mutex<RateLimiter>>,
+	peer_upload: Option<usize>,
+	peer_download: Option<usize>,
+}
+
+impl BandwidthLimits {
+	/// Create limits from the network configuration.
+	pub fn new(config: &NetworkConfiguration) -> BandwidthLimits {
+		BandwidthLimits {
+			upload: config.max_upload_rate.map(|r| Mutex::new(RateLimiter::new(r))),
+			download: config.max_download_rate.map(|r| Mutex::new(RateLimiter::new(r))),
+			peer_upload: config.max_peer_upload_rate,
+			peer_download: config.max_peer_download_rate,
+		}
+	}
+
+	/// Check if any limit is configured.
+	pub fn is_limited(&self) -> bool {
+		self.upload.is_some() || self.download.is_some() || self.peer_upload.is_some() || self.peer_download.is_some()
+	}
+}
+
+/// Per-connection throttle combining the global and per-peer limits.
+#[derive(Debug)]
+pub struct Throttle {
+	limits: Arc<BandwidthLimits>,
+	upload: Option<RateLimiter>,
+	download: Option<RateLimiter>,
+}
+
+impl Default for Throttle {
+	fn default() -> Self {
+		Throttle::new(Arc::new(BandwidthLimits::default()))
+	}
+}
+
+impl Throttle {
+	/// Create a new throttle for a connection.
+	pub fn new(limits: Arc<BandwidthLimits>) -> Throttle {
+		Throttle {
+			upload: limits.peer_upload.map(RateLimiter::new),
+			download: limits.peer_download.map(RateLimiter::new),
+			limits: limits,
+		}
+	}
+
+	/// Number of bytes that can be sent right now.
+	pub fn send_allowance(&mut self) -> usize {
+		Self::allowance(&self.limits.upload, &mut self.upload)
+	}
+
+	/// Number of bytes that can be received right now.
+	pub fn recv_allowance(&mut self) -> usize {
+		Self::allowance(&self.limits.download, &mut self.download)
+	}
+
+	/// Account for sent bytes.
+	pub fn on_sent(&mut self, size: usize) {
+		Self::consume(&self.limits.upload, &mut self.upload, size);
+	}
+
+	/// Account for received bytes.
+	pub fn on_received(&mut self, size: usize) {
+		Self::consume(&self.limits.download, &mut self.download, size);
+	}
+
+	fn allowance(global: &Option<Mutex<RateLimiter>>, peer: &mut Option<RateLimiter>) -> usize {
+		let global = global.as_ref().map_or(usize::MAX, |l| l.lock().available());
+		let peer = peer.as_mut().map_or(usize::MAX, |l| l.available());
+		min(global, peer)
+	}
+
+	fn consume(global: &Option<Mutex<RateLimiter>>, peer: &mut Option<RateLimiter>, size: usize) {
+		if let Some(ref l) = *global {
+			l.lock().consume(size);
+		}
+		if let Some(ref mut l) = *peer {
+			l.consume(size);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::usize;
+	use super::*;
+
+	#[test]
+	fn limiter_refills_over_time() {
+		let mut limiter = RateLimiter::new(1000);
+		let start = limiter.last_refill_ns;
+		assert_eq!(limiter.available_at(start), 1000);
+		limiter.consume(1000);
+		assert_eq!(limiter.available_at(start), 0);
+		assert_eq!(limiter.available_at(start + 500_000_000), 500);
+		assert_eq!(limiter.available_at(start + 10_000_000_000), 1000);
+	}
+
+	#[test]
+	fn throttle_uses_lowest_limit() {
+		let limits = Arc::new(BandwidthLimits {
+			upload: None,
+			download: Some(Mutex::new(RateLimiter::new(100))),
+			peer_upload: Some(50),
+			peer_download: Some(200),
+		});
+		let mut throttle = Throttle::new(limits.clone());
+		assert_eq!(throttle.send_allowance(), 50);
+		assert_eq!(throttle.recv_allowance(), 100);
+		throttle.on_received(60);
+		assert!(throttle.recv_allowance() <= 40);
+		// global download limit is shared with other connections
+		assert!(Throttle::new(limits).recv_allowance() <= 40);
+	}
+
+	#[test]
+	fn unlimited_throttle() {
+		let mut throttle = Throttle::default();
+		throttle.on_sent(1000);
+		assert_eq!(throttle.send_allowance(), usize::MAX);
+		assert_eq!(throttle.recv_allowance(), usize::MAX);
+	}
+}
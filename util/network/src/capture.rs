@@ -0,0 +1,258 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Subprotocol packet capture
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use util::Bytes;
+use rlp::*;
+use time;
+use host::{PeerId, PacketId, ProtocolId};
+use node_table::NodeId;
+use error::NetworkError;
+use parking_lot::Mutex;
+
+/// Size at which a capture file is rotated.
+pub const CAPTURE_SIZE_LIMIT: u64 = 256 * 1024 * 1024;
+
+/// Direction of a captured packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+	/// Packet received from the peer.
+	Inbound,
+	/// Packet sent to the peer.
+	Outbound,
+}
+
+/// Decrypted subprotocol packet stored in a capture file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedPacket {
+	/// Milliseconds since the unix epoch.
+	pub timestamp: u64,
+	/// Session the packet was exchanged on.
+	pub peer: PeerId,
+	/// Id of the remote node, if known.
+	pub node: Option<NodeId>,
+	/// Whether the packet was received or sent.
+	pub direction: PacketDirection,
+	/// Subprotocol id.
+	pub protocol: ProtocolId,
+	/// Packet id within the subprotocol.
+	pub packet_id: PacketId,
+	/// Packet payload.
+	pub data: Bytes,
+}
+
+impl CapturedPacket {
+	fn to_rlp(&self) -> Bytes {
+		let mut rlp = RlpStream::new_list(7);
+		rlp.append(&self.timestamp);
+		rlp.append(&(self.peer as u64));
+		rlp.append(&match self.direction { PacketDirection::Inbound => 0u8, PacketDirection::Outbound => 1u8 });
+		rlp.append(&&self.protocol[..]);
+		rlp.append(&self.packet_id);
+		rlp.append(&self.data);
+		match self.node {
+			Some(ref node) => { rlp.begin_list(1).append(node); },
+			None => { rlp.begin_list(0); },
+		}
+		rlp.out()
+	}
+
+	fn from_rlp(rlp: &UntrustedRlp) -> Result<CapturedPacket, NetworkError> {
+		let p: Vec<u8> = try!(rlp.val_at(3));
+		if p.len() != 3 {
+			return Err(NetworkError::BadProtocol);
+		}
+		let mut protocol: ProtocolId = [0u8; 3];
+		protocol.clone_from_slice(&p);
+		let direction = match try!(rlp.val_at::<u8>(2)) {
+			0 => PacketDirection::Inbound,
+			1 => PacketDirection::Outbound,
+			_ => return Err(NetworkError::BadProtocol),
+		};
+		// captures made before node ids were recorded have no seventh item
+		let node = match rlp.item_count() {
+			7 => {
+				let node = try!(rlp.at(6));
+				if node.item_count() == 0 { None } else { Some(try!(node.val_at(0))) }
+			},
+			_ => None,
+		};
+		Ok(CapturedPacket {
+			timestamp: try!(rlp.val_at(0)),
+			peer: try!(rlp.val_at::<u64>(1)) as PeerId,
+			node: node,
+			direction: direction,
+			protocol: protocol,
+			packet_id: try!(rlp.val_at(4)),
+			data: try!(rlp.val_at(5)),
+		})
+	}
+}
+
+struct CaptureFile {
+	file: File,
+	size: u64,
+}
+
+/// Appends subprotocol packets to a capture file.
+///
+/// Each packet is written as a separate RLP list, so a capture can be read back
+/// even if the client was killed while recording. Once the file would grow past the size limit
+/// it is moved to `<path>.1`, replacing the previous one, and a new capture is started.
+pub struct PacketRecorder {
+	path: String,
+	limit: u64,
+	file: Mutex<CaptureFile>,
+}
+
+impl PacketRecorder {
+	/// Open a capture file for appending, rotated at `CAPTURE_SIZE_LIMIT`.
+	pub fn new(path: &str) -> Result<PacketRecorder, NetworkError> {
+		Self::with_limit(path, CAPTURE_SIZE_LIMIT)
+	}
+
+	/// Open a capture file for appending, rotated when it would exceed `limit` bytes.
+	pub fn with_limit(path: &str, limit: u64) -> Result<PacketRecorder, NetworkError> {
+		let file = try!(Self::open(path));
+		Ok(PacketRecorder {
+			path: path.to_owned(),
+			limit: limit,
+			file: Mutex::new(file),
+		})
+	}
+
+	fn open(path: &str) -> io::Result<CaptureFile> {
+		let file = try!(OpenOptions::new().create(true).append(true).open(path));
+		let size = try!(file.metadata()).len();
+		Ok(CaptureFile {
+			file: file,
+			size: size,
+		})
+	}
+
+	fn write(&self, data: &[u8]) -> io::Result<()> {
+		let mut capture = self.file.lock();
+		if capture.size > 0 && capture.size + data.len() as u64 > self.limit {
+			try!(fs::rename(&self.path, format!("{}.1", self.path)));
+			*capture = try!(Self::open(&self.path));
+		}
+		try!(capture.file.write_all(data));
+		capture.size += data.len() as u64;
+		Ok(())
+	}
+
+	/// Record a packet. Write errors are logged and otherwise ignored.
+	pub fn record(&self, peer: PeerId, node: Option<&NodeId>, direction: PacketDirection, protocol: ProtocolId, packet_id: PacketId, data: &[u8]) {
+		let now = time::get_time();
+		let packet = CapturedPacket {
+			timestamp: now.sec as u64 * 1000 + now.nsec as u64 / 1_000_000,
+			peer: peer,
+			node: node.cloned(),
+			direction: direction,
+			protocol: protocol,
+			packet_id: packet_id,
+			data: data.to_vec(),
+		};
+		if let Err(e) = self.write(&packet.to_rlp()) {
+			warn!(target: "network", "Error writing packet capture: {:?}", e);
+		}
+	}
+}
+
+/// Read all packets from a capture file in the order they were recorded.
+pub fn read_capture(path: &str) -> Result<Vec<CapturedPacket>, NetworkError> {
+	let mut bytes = Vec::new();
+	try!(try!(File::open(path)).read_to_end(&mut bytes));
+	let mut packets = Vec::new();
+	let mut offset = 0;
+	while offset < bytes.len() {
+		let len = try!(PayloadInfo::from(&bytes[offset..])).total();
+		if offset + len > bytes.len() {
+			return Err(From::from(DecoderError::RlpIsTooShort));
+		}
+		packets.push(try!(CapturedPacket::from_rlp(&UntrustedRlp::new(&bytes[offset..offset + len]))));
+		offset += len;
+	}
+	Ok(packets)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs::OpenOptions;
+	use std::io::Write;
+	use devtools::RandomTempPath;
+	use node_table::NodeId;
+
+	#[test]
+	fn records_and_reads_packets() {
+		let temp = RandomTempPath::new();
+		let path = temp.as_str();
+		{
+			let recorder = PacketRecorder::new(path).unwrap();
+			recorder.record(1, Some(&NodeId::from(7)), PacketDirection::Inbound, *b"eth", 0x02, &[0xc0]);
+			recorder.record(3, None, PacketDirection::Outbound, *b"par", 0x05, &[0xc2, 0x01, 0x02]);
+		}
+		// reopening appends to the existing capture
+		PacketRecorder::new(path).unwrap().record(1, Some(&NodeId::from(7)), PacketDirection::Inbound, *b"eth", 0x07, &[]);
+
+		let packets = read_capture(path).unwrap();
+		assert_eq!(packets.len(), 3);
+		assert_eq!(packets[0].peer, 1);
+		assert_eq!(packets[0].node, Some(NodeId::from(7)));
+		assert_eq!(packets[0].direction, PacketDirection::Inbound);
+		assert_eq!(&packets[0].protocol, b"eth");
+		assert_eq!(packets[0].data, vec![0xc0]);
+		assert_eq!(packets[1].peer, 3);
+		assert_eq!(packets[1].node, None);
+		assert_eq!(packets[1].direction, PacketDirection::Outbound);
+		assert_eq!(packets[1].packet_id, 0x05);
+		assert_eq!(packets[1].data, vec![0xc2, 0x01, 0x02]);
+		assert_eq!(packets[2].packet_id, 0x07);
+		assert!(packets[2].data.is_empty());
+		assert!(packets[0].timestamp <= packets[2].timestamp);
+	}
+
+	#[test]
+	fn rotates_capture_file() {
+		let temp = RandomTempPath::new();
+		let path = temp.as_str();
+		let rotated = format!("{}.1", path);
+		{
+			let recorder = PacketRecorder::with_limit(path, 20).unwrap();
+			recorder.record(1, None, PacketDirection::Inbound, *b"eth", 0x01, &[0xc0]);
+			recorder.record(1, None, PacketDirection::Inbound, *b"eth", 0x02, &[0xc0]);
+			recorder.record(1, None, PacketDirection::Inbound, *b"eth", 0x03, &[0xc0]);
+		}
+		let old = read_capture(&rotated).unwrap();
+		let new = read_capture(path).unwrap();
+		assert_eq!(old.iter().map(|p| p.packet_id).collect::<Vec<_>>(), vec![0x02]);
+		assert_eq!(new.iter().map(|p| p.packet_id).collect::<Vec<_>>(), vec![0x03]);
+		::std::fs::remove_file(&rotated).unwrap();
+	}
+
+	#[test]
+	fn rejects_truncated_capture() {
+		let temp = RandomTempPath::new();
+		let path = temp.as_str();
+		PacketRecorder::new(path).unwrap().record(1, None, PacketDirection::Inbound, *b"eth", 0x02, &[0xc3, 0x01, 0x02, 0x03]);
+		OpenOptions::new().append(true).open(path).unwrap().write_all(&[0xc5, 0x01]).unwrap();
+		assert!(read_capture(path).is_err());
+	}
+}
@@ -29,6 +29,7 @@ use error::*;
 use io::{IoContext, StreamToken};
 use handshake::Handshake;
 use stats::NetworkStats;
+use bandwidth::Throttle;
 use rcrypto::blockmodes::*;
 use rcrypto::aessafe::*;
 use rcrypto::symmetriccipher::*;
@@ -60,6 +61,12 @@ pub struct GenericConnection<Socket: GenericSocket> {
 	interest: EventSet,
 	/// Shared network statistics
 	stats: Arc<NetworkStats>,
+	/// Upload and download rate limits
+	throttle: Throttle,
+	/// Reading has been postponed because of the download rate limit
+	read_throttled: bool,
+	/// Writing has been postponed because of the upload rate limit
+	write_throttled: bool,
 	/// Registered flag
 	registered: AtomicBool,
 }
@@ -78,12 +85,20 @@ impl<Socket: GenericSocket> GenericConnection<Socket> {
 		if self.rec_size == 0 || self.rec_buf.len() >= self.rec_size {
 			return Ok(None);
 		}
+		self.read_throttled = false;
 		let sock_ref = <Socket as Read>::by_ref(&mut self.socket);
 		loop {
-			let max = self.rec_size - self.rec_buf.len();
+			let allowance = self.throttle.recv_allowance();
+			if allowance == 0 {
+				trace!(target:"network", "{}: Download rate limit reached", self.token);
+				self.read_throttled = true;
+				return Ok(None);
+			}
+			let max = ::std::cmp::min(self.rec_size - self.rec_buf.len(), allowance);
 			match sock_ref.take(max as u64).try_read_buf(&mut self.rec_buf) {
 				Ok(Some(size)) if size != 0  => {
 					self.stats.inc_recv(size);
+					self.throttle.on_received(size);
 					trace!(target:"network", "{}: Read {} of {} bytes", self.token, self.rec_buf.len(), self.rec_size);
 					if self.rec_size != 0 && self.rec_buf.len() == self.rec_size {
 						self.rec_size = 0;
@@ -120,29 +135,57 @@ impl<Socket: GenericSocket> GenericConnection<Socket> {
 		self.interest.is_writable()
 	}
 
+	/// Replace the rate limits for this connection.
+	pub fn set_throttle(&mut self, throttle: Throttle) {
+		self.throttle = throttle;
+	}
+
+	/// Check if reading has been postponed because of the download rate limit.
+	pub fn is_read_throttled(&self) -> bool {
+		self.read_throttled
+	}
+
+	/// Check if writing has been postponed because of the upload rate limit.
+	pub fn is_write_throttled(&self) -> bool {
+		self.write_throttled
+	}
+
 	/// Writable IO handler. Called when the socket is ready to send.
 	pub fn writable<Message>(&mut self, io: &IoContext<Message>) -> Result<WriteStatus, NetworkError> where Message: Send + Clone + Sync + 'static {
 		if self.send_queue.is_empty() {
 			return Ok(WriteStatus::Complete)
 		}
+		self.write_throttled = false;
+		let allowance = self.throttle.send_allowance();
+		if allowance == 0 {
+			trace!(target:"network", "{}: Upload rate limit reached", self.token);
+			self.write_throttled = true;
+			return Ok(WriteStatus::Ongoing)
+		}
 		{
 			let buf = self.send_queue.front_mut().unwrap();
 			let send_size = buf.get_ref().len();
-			if (buf.position() as usize) >= send_size {
+			let position = buf.position() as usize;
+			if position >= send_size {
 				warn!(target:"net", "Unexpected connection data");
 				return Ok(WriteStatus::Complete)
 			}
-			match self.socket.try_write_buf(buf) {
-				Ok(Some(size)) if (buf.position() as usize) < send_size => {
+			let end = ::std::cmp::min(send_size, position.saturating_add(allowance));
+			match self.socket.try_write(&buf.get_ref()[position..end]) {
+				Ok(Some(size)) => {
+					buf.set_position((position + size) as u64);
 					self.stats.inc_send(size);
-					Ok(WriteStatus::Ongoing)
-				},
-				Ok(Some(size)) if (buf.position() as usize) == send_size => {
-					self.stats.inc_send(size);
-					trace!(target:"network", "{}: Wrote {} bytes", self.token, send_size);
-					Ok(WriteStatus::Complete)
+					self.throttle.on_sent(size);
+					if position + size < send_size {
+						if end < send_size && position + size == end {
+							self.write_throttled = true;
+						}
+						Ok(WriteStatus::Ongoing)
+					} else {
+						trace!(target:"network", "{}: Wrote {} bytes", self.token, send_size);
+						Ok(WriteStatus::Complete)
+					}
 				},
-				Ok(Some(_)) => { panic!("Wrote past buffer");},
 				Ok(None) => Ok(WriteStatus::Ongoing),
 				Err(e) => try!(Err(e))
 			}
@@ -173,6 +216,9 @@ impl Connection {
 			rec_size: 0,
 			interest: EventSet::hup() | EventSet::readable(),
 			stats: stats,
+			throttle: Throttle::default(),
+			read_throttled: false,
+			write_throttled: false,
 			registered: AtomicBool::new(false),
 		}
 	}
@@ -207,6 +253,9 @@ impl Connection {
 			send_queue: self.send_queue.clone(),
 			interest: EventSet::hup(),
 			stats: self.stats.clone(),
+			throttle: Throttle::default(),
+			read_throttled: false,
+			write_throttled: false,
 			registered: AtomicBool::new(false),
 		})
 	}
@@ -498,6 +547,8 @@ mod tests {
 	use std::sync::Arc;
 	use std::sync::atomic::AtomicBool;
 	use super::super::stats::*;
+	use bandwidth::{BandwidthLimits, Throttle};
+	use host::NetworkConfiguration;
 	use std::io::{Read, Write, Error, Cursor, ErrorKind};
 	use mio::{EventSet};
 	use std::collections::VecDeque;
@@ -547,6 +598,9 @@ mod tests {
 				rec_size: 0,
 				interest: EventSet::hup() | EventSet::readable(),
 				stats: Arc::<NetworkStats>::new(NetworkStats::new()),
+				throttle: Throttle::default(),
+				read_throttled: false,
+				write_throttled: false,
 				registered: AtomicBool::new(false),
 			}
 		}
@@ -570,6 +624,9 @@ mod tests {
 				rec_size: 0,
 				interest: EventSet::hup() | EventSet::readable(),
 				stats: Arc::<NetworkStats>::new(NetworkStats::new()),
+				throttle: Throttle::default(),
+				read_throttled: false,
+				write_throttled: false,
 				registered: AtomicBool::new(false),
 			}
 		}
@@ -620,6 +677,40 @@ mod tests {
 		assert_eq!(1024, connection.socket.write_buffer.len());
 	}
 
+	#[test]
+	fn connection_write_is_throttled() {
+		let mut config = NetworkConfiguration::new();
+		config.max_peer_upload_rate = Some(1024);
+		let mut connection = TestConnection::new();
+		connection.set_throttle(Throttle::new(Arc::new(BandwidthLimits::new(&config))));
+		let data = Cursor::new(vec![0; 10240]);
+		connection.send_queue.push_back(data);
+
+		let status = connection.writable(&test_io());
+
+		assert!(status.is_ok());
+		assert!(WriteStatus::Ongoing == status.unwrap());
+		assert_eq!(1024, connection.socket.write_buffer.len());
+		assert!(connection.is_write_throttled());
+	}
+
+	#[test]
+	fn connection_read_is_throttled() {
+		let mut config = NetworkConfiguration::new();
+		config.max_peer_download_rate = Some(1024);
+		let mut connection = TestConnection::new();
+		connection.set_throttle(Throttle::new(Arc::new(BandwidthLimits::new(&config))));
+		connection.rec_size = 2048;
+		connection.socket.read_buffer = vec![99; 2048];
+
+		let status = connection.readable();
+
+		assert!(status.is_ok());
+		assert!(status.unwrap().is_none());
+		assert!(connection.socket.cursor < 2048);
+		assert!(connection.is_read_throttled());
+	}
+
 	#[test]
 	fn connection_write_to_broken() {
 		let mut connection = TestBrokenConnection::new();
@@ -29,6 +29,7 @@ use node_table::*;
 use error::NetworkError;
 use io::{StreamToken, IoContext};
 use ethkey::{Secret, KeyPair, sign, recover};
+use node_record::{NodeRecord, RecordCapability, Topic};
 use AllowIP;
 
 use PROTOCOL_VERSION;
@@ -45,9 +46,15 @@ const PACKET_PING: u8 = 1;
 const PACKET_PONG: u8 = 2;
 const PACKET_FIND_NODE: u8 = 3;
 const PACKET_NEIGHBOURS: u8 = 4;
+const PACKET_RECORD_REQUEST: u8 = 5;
+const PACKET_RECORD: u8 = 6;
+const PACKET_TOPIC_QUERY: u8 = 7;
+const PACKET_TOPIC_NODES: u8 = 8;
 
 const PING_TIMEOUT_MS: u64 = 300;
 const MAX_NODES_PING: usize = 32; // Max nodes to add/ping at once
+const MAX_TOPIC_PAYLOAD: usize = MAX_DATAGRAM_SIZE - 160; // Space left for records in a TopicNodes packet
+const MAX_RECORDS: usize = 1024; // Max node records kept
 
 #[derive(Clone, Debug)]
 pub struct NodeEntry {
@@ -97,6 +104,12 @@ pub struct Discovery {
 	check_timestamps: bool,
 	adding_nodes: Vec<NodeEntry>,
 	allow_ips: AllowIP,
+	record: NodeRecord,
+	records: HashMap<NodeId, NodeRecord>,
+	record_requests: HashSet<NodeId>,
+	topic_queries: HashSet<SocketAddr>,
+	topics: Vec<Topic>,
+	allowlist: Option<HashSet<NodeId>>,
 }
 
 pub struct TableUpdates {
@@ -107,6 +120,9 @@ pub struct TableUpdates {
 impl Discovery {
 	pub fn new(key: &KeyPair, listen: SocketAddr, public: NodeEndpoint, token: StreamToken, allow_ips: AllowIP) -> Discovery {
 		let socket = UdpSocket::bound(&listen).expect("Error binding UDP socket");
+		// Use current time as the initial sequence number so that records issued after a restart replace older ones.
+		let seq = time::get_time().sec as u64;
+		let record = NodeRecord::new(key.secret(), key.public().clone(), seq, public.clone(), Vec::new(), None).expect("Error signing node record");
 		Discovery {
 			id: key.public().clone(),
 			secret: key.secret().clone(),
@@ -121,12 +137,62 @@ impl Discovery {
 			check_timestamps: true,
 			adding_nodes: Vec::new(),
 			allow_ips: allow_ips,
+			record: record,
+			records: HashMap::new(),
+			record_requests: HashSet::new(),
+			topic_queries: HashSet::new(),
+			topics: Vec::new(),
+			allowlist: None,
 		}
 	}
 
+	/// Restrict discovery to the given nodes. Packets from other nodes are ignored.
+	pub fn set_allowlist(&mut self, allowlist: Option<HashSet<NodeId>>) {
+		self.allowlist = allowlist;
+	}
+
+	fn is_allowed(&self, entry: &NodeEntry) -> bool {
+		entry.endpoint.is_allowed(self.allow_ips) && self.allowlist.as_ref().map_or(true, |a| a.contains(&entry.id))
+	}
+
+	/// Update capabilities and chain advertised in our node record. Topics we advertise are also looked up.
+	pub fn set_capabilities(&mut self, capabilities: Vec<RecordCapability>, chain_id: Option<u64>) {
+		if capabilities == self.record.capabilities && chain_id == self.record.chain_id {
+			return;
+		}
+		match NodeRecord::new(&self.secret, self.id.clone(), self.record.seq + 1, self.public_endpoint.clone(), capabilities, chain_id) {
+			Ok(record) => {
+				self.topics = record.topics();
+				self.record = record;
+			},
+			Err(e) => warn!("Error signing node record: {:?}", e),
+		}
+	}
+
+	/// Update the endpoint advertised to other nodes. Known nodes are pinged so that they learn it.
+	pub fn set_public_endpoint(&mut self, endpoint: NodeEndpoint) {
+		if endpoint == self.public_endpoint {
+			return;
+		}
+		self.public_endpoint = endpoint;
+		match NodeRecord::new(&self.secret, self.id.clone(), self.record.seq + 1, self.public_endpoint.clone(), self.record.capabilities.clone(), self.record.chain_id) {
+			Ok(record) => self.record = record,
+			Err(e) => warn!("Error signing node record: {:?}", e),
+		}
+		let nodes: Vec<NodeEndpoint> = self.node_buckets.iter().flat_map(|b| b.nodes.iter().map(|n| n.address.endpoint.clone())).collect();
+		for node in nodes {
+			self.ping(&node);
+		}
+	}
+
+	/// Latest known record of a node.
+	pub fn node_record(&self, id: &NodeId) -> Option<&NodeRecord> {
+		self.records.get(id)
+	}
+
 	/// Add a new node to discovery table. Pings the node.
 	pub fn add_node(&mut self, e: NodeEntry) {
-		if e.endpoint.is_allowed(self.allow_ips) {
+		if self.is_allowed(&e) {
 			let endpoint = e.endpoint.clone();
 			self.update_node(e);
 			self.ping(&endpoint);
@@ -142,7 +208,7 @@ impl Discovery {
 	/// Add a list of known nodes to the table.
 	pub fn init_node_list(&mut self, mut nodes: Vec<NodeEntry>) {
 		for n in nodes.drain(..) {
-			if n.endpoint.is_allowed(self.allow_ips) {
+			if self.is_allowed(&n) {
 				self.update_node(n);
 			}
 		}
@@ -185,6 +251,8 @@ impl Discovery {
 		self.discovery_round = 0;
 		self.discovery_id.randomize(); //TODO: use cryptographic nonce
 		self.discovery_nodes.clear();
+		self.record_requests.clear();
+		self.topic_queries.clear();
 	}
 
 	fn update_new_nodes(&mut self) {
@@ -202,6 +270,9 @@ impl Discovery {
 			return;
 		}
 		trace!(target: "discovery", "Starting round {:?}", self.discovery_round);
+		if self.discovery_round == 0 {
+			self.query_topics();
+		}
 		let mut tried_count = 0;
 		{
 			let nearest = Discovery::nearest_node_entries(&self.discovery_id, &self.node_buckets).into_iter();
@@ -224,6 +295,27 @@ impl Discovery {
 		self.discovery_round += 1;
 	}
 
+	fn query_topics(&mut self) {
+		for topic in self.topics.clone() {
+			// Ask nodes that advertise the topic first, they are likely to know other nodes on the same chain.
+			let mut targets: Vec<NodeEndpoint> = self.records.values().filter(|r| r.has_topic(&topic)).take(ALPHA).map(|r| r.endpoint.clone()).collect();
+			if targets.len() < ALPHA {
+				let nearest = Discovery::nearest_node_entries(&self.id, &self.node_buckets);
+				let count = ALPHA - targets.len();
+				targets.extend(nearest.into_iter().filter(|n| !self.records.get(&n.id).map_or(false, |r| r.has_topic(&topic))).take(count).map(|n| n.endpoint));
+			}
+			let mut payload = RlpStream::new_list(1);
+			payload.append(&topic);
+			let payload = payload.out();
+			for endpoint in targets {
+				trace!(target: "discovery", "Sent TopicQuery to {:?}", &endpoint);
+				let address = endpoint.udp_address();
+				self.send_packet(PACKET_TOPIC_QUERY, &address, &payload);
+				self.topic_queries.insert(address);
+			}
+		}
+	}
+
 	fn distance(a: &NodeId, b: &NodeId) -> u32 {
 		let d = a.sha3() ^ b.sha3();
 		let mut ret:u32 = 0;
@@ -370,6 +462,10 @@ impl Discovery {
 		let signed = &packet[(32 + 65)..];
 		let signature = H520::from_slice(&packet[32..(32 + 65)]);
 		let node_id = try!(recover(&signature.into(), &signed.sha3()));
+		if self.allowlist.as_ref().map_or(false, |a| !a.contains(&node_id)) {
+			trace!(target: "discovery", "Ignoring packet from {:?}: not in the allowlist", &from);
+			return Ok(None);
+		}
 
 		let packet_id = signed[0];
 		let rlp = UntrustedRlp::new(&signed[1..]);
@@ -378,6 +474,10 @@ impl Discovery {
 			PACKET_PONG => self.on_pong(&rlp, &node_id, &from),
 			PACKET_FIND_NODE => self.on_find_node(&rlp, &node_id, &from),
 			PACKET_NEIGHBOURS => self.on_neighbours(&rlp, &node_id, &from),
+			PACKET_RECORD_REQUEST => self.on_record_request(&rlp, &node_id, &from),
+			PACKET_RECORD => self.on_record(&rlp, &node_id, &from),
+			PACKET_TOPIC_QUERY => self.on_topic_query(&rlp, &node_id, &from),
+			PACKET_TOPIC_NODES => self.on_topic_nodes(&rlp, &node_id, &from),
 			_ => {
 				debug!("Unknown UDP packet: {}", packet_id);
 				Ok(None)
@@ -430,11 +530,137 @@ impl Discovery {
 			entry.endpoint.address = from.clone();
 		}
 		self.clear_ping(node);
+		if self.record_requests.insert(node.clone()) {
+			self.send_packet(PACKET_RECORD_REQUEST, from, &RlpStream::new_list(0).out());
+		}
 		let mut added_map = HashMap::new();
 		added_map.insert(node.clone(), entry);
 		Ok(None)
 	}
 
+	fn on_record_request(&mut self, rlp: &UntrustedRlp, _node: &NodeId, from: &SocketAddr) -> Result<Option<TableUpdates>, NetworkError> {
+		trace!(target: "discovery", "Got RecordRequest from {:?}", &from);
+		let timestamp: u64 = try!(rlp.val_at(0));
+		try!(self.check_timestamp(timestamp));
+		let mut response = RlpStream::new_list(1);
+		self.record.to_rlp(&mut response);
+		self.send_packet(PACKET_RECORD, from, &response.out());
+		Ok(None)
+	}
+
+	fn on_record(&mut self, rlp: &UntrustedRlp, node: &NodeId, from: &SocketAddr) -> Result<Option<TableUpdates>, NetworkError> {
+		trace!(target: "discovery", "Got Record from {:?}", &from);
+		let record = try!(NodeRecord::from_rlp(&try!(rlp.at(0))));
+		let timestamp: u64 = try!(rlp.val_at(1));
+		try!(self.check_timestamp(timestamp));
+		if !self.record_requests.contains(node) {
+			debug!(target: "discovery", "Unexpected Record from {:?}", &from);
+			return Ok(None);
+		}
+		if &record.id != node {
+			debug!(target: "discovery", "Record for {:?} sent by {:?}", record.id, node);
+			return Err(NetworkError::BadProtocol);
+		}
+		self.insert_record(record);
+		Ok(None)
+	}
+
+	fn insert_record(&mut self, record: NodeRecord) {
+		let newer = match self.records.get(&record.id) {
+			Some(r) => r.seq < record.seq,
+			None if self.records.len() >= MAX_RECORDS => {
+				trace!(target: "discovery", "Too many records, ignoring {:?}", record.id);
+				false
+			},
+			None => true,
+		};
+		if newer {
+			self.records.insert(record.id.clone(), record);
+		}
+	}
+
+	fn on_topic_query(&mut self, rlp: &UntrustedRlp, _node: &NodeId, from: &SocketAddr) -> Result<Option<TableUpdates>, NetworkError> {
+		trace!(target: "discovery", "Got TopicQuery from {:?}", &from);
+		let topic: Topic = try!(rlp.val_at(0));
+		let timestamp: u64 = try!(rlp.val_at(1));
+		try!(self.check_timestamp(timestamp));
+		let (count, mut packets) = {
+			let mut found: Vec<&NodeRecord> = self.records.values().filter(|r| r.has_topic(&topic)).take(BUCKET_SIZE).collect();
+			if self.record.has_topic(&topic) {
+				found.push(&self.record);
+			}
+			(found.len(), Discovery::prepare_topic_packets(&topic, &found))
+		};
+		if count == 0 {
+			return Ok(None);
+		}
+		for p in packets.drain(..) {
+			self.send_packet(PACKET_TOPIC_NODES, from, &p);
+		}
+		trace!(target: "discovery", "Sent {} TopicNodes to {:?}", count, &from);
+		Ok(None)
+	}
+
+	fn prepare_topic_packets(topic: &Topic, records: &[&NodeRecord]) -> Vec<Bytes> {
+		let mut packets = Vec::new();
+		let mut chunk: Vec<Bytes> = Vec::new();
+		let mut size = 0;
+		for r in records {
+			let mut s = RlpStream::new();
+			r.to_rlp(&mut s);
+			let encoded = s.out();
+			if !chunk.is_empty() && size + encoded.len() > MAX_TOPIC_PAYLOAD {
+				packets.push(Discovery::topic_packet(topic, &chunk));
+				chunk.clear();
+				size = 0;
+			}
+			size += encoded.len();
+			chunk.push(encoded);
+		}
+		if !chunk.is_empty() {
+			packets.push(Discovery::topic_packet(topic, &chunk));
+		}
+		packets
+	}
+
+	fn topic_packet(topic: &Topic, records: &[Bytes]) -> Bytes {
+		let mut rlp = RlpStream::new_list(2);
+		rlp.append(topic);
+		rlp.begin_list(records.len());
+		for r in records {
+			rlp.append_raw(r, 1);
+		}
+		rlp.out()
+	}
+
+	fn on_topic_nodes(&mut self, rlp: &UntrustedRlp, _node: &NodeId, from: &SocketAddr) -> Result<Option<TableUpdates>, NetworkError> {
+		let topic: Topic = try!(rlp.val_at(0));
+		let timestamp: u64 = try!(rlp.val_at(2));
+		try!(self.check_timestamp(timestamp));
+		if !self.topics.contains(&topic) || !self.topic_queries.contains(from) {
+			debug!(target: "discovery", "Unexpected TopicNodes from {:?}", &from);
+			return Ok(None);
+		}
+		let mut added = HashMap::new();
+		trace!(target: "discovery", "Got {} TopicNodes from {:?}", try!(rlp.at(1)).item_count(), &from);
+		for r in try!(rlp.at(1)).iter() {
+			let record = try!(NodeRecord::from_rlp(&r));
+			if record.id == self.id || !record.has_topic(&topic) {
+				continue;
+			}
+			let entry = record.entry();
+			if !entry.endpoint.is_valid() || !self.is_allowed(&entry) {
+				debug!(target: "discovery", "Bad or disallowed node: {:?}", entry);
+				continue;
+			}
+			self.insert_record(record);
+			added.insert(entry.id.clone(), entry.clone());
+			self.ping(&entry.endpoint);
+			self.update_node(entry);
+		}
+		Ok(Some(TableUpdates { added: added, removed: HashSet::new() }))
+	}
+
 	fn on_find_node(&mut self, rlp: &UntrustedRlp, _node: &NodeId, from: &SocketAddr) -> Result<Option<TableUpdates>, NetworkError> {
 		trace!(target: "discovery", "Got FindNode from {:?}", &from);
 		let target: NodeId = try!(rlp.val_at(0));
@@ -487,6 +713,9 @@ impl Discovery {
 				continue;
 			}
 			let entry = NodeEntry { id: node_id.clone(), endpoint: endpoint };
+			if !self.is_allowed(&entry) {
+				continue;
+			}
 			added.insert(node_id, entry.clone());
 			self.ping(&entry.endpoint);
 			self.update_node(entry);
@@ -511,6 +740,9 @@ impl Discovery {
 				} else { true }
 			});
 		}
+		for id in &removed {
+			self.records.remove(id);
+		}
 		removed
 	}
 
@@ -551,6 +783,7 @@ mod tests {
 	use std::str::FromStr;
 	use rustc_serialize::hex::FromHex;
 	use ethkey::{Random, Generator};
+	use node_record::{RecordCapability, Topic, topic};
 	use AllowIP;
 
 	#[test]
@@ -605,6 +838,67 @@ mod tests {
 		assert_eq!(Discovery::nearest_node_entries(&NodeId::new(), &discovery2.node_buckets).len(), 3)
 	}
 
+	fn topic_nodes(discovery: &Discovery, topic: &Topic) -> Vec<NodeId> {
+		discovery.records.values().filter(|r| r.has_topic(topic)).map(|r| r.id.clone()).collect()
+	}
+
+	fn deliver(nodes: &mut [Discovery], endpoints: &[NodeEndpoint]) {
+		for _ in 0 .. 10 {
+			for i in 0 .. nodes.len() {
+				while let Some(datagramm) = nodes[i].send_queue.pop_front() {
+					if let Some(j) = endpoints.iter().position(|e| e.address == datagramm.address) {
+						nodes[j].on_packet(&datagramm.payload, endpoints[i].address.clone()).ok();
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn finds_nodes_by_topic() {
+		let keys: Vec<_> = (0..3).map(|_| Random.generate().unwrap()).collect();
+		let endpoints: Vec<_> = (0..3).map(|i| NodeEndpoint { address: SocketAddr::from_str(&format!("127.0.0.1:{}", 40450 + i)).unwrap(), udp_port: 40450 + i }).collect();
+		let mut nodes: Vec<_> = (0..3).map(|i| Discovery::new(&keys[i], endpoints[i].address.clone(), endpoints[i].clone(), 0, AllowIP::All)).collect();
+		let eth = RecordCapability { protocol: *b"eth", version: 63 };
+		let par = RecordCapability { protocol: *b"par", version: 1 };
+		nodes[0].set_capabilities(vec![eth.clone()], Some(1));
+		nodes[1].set_capabilities(vec![eth.clone(), par.clone()], Some(1));
+		nodes[2].set_capabilities(vec![eth.clone(), par.clone()], Some(1));
+
+		// node 0 learns the record of node 1
+		nodes[0].add_node(NodeEntry { id: keys[1].public().clone(), endpoint: endpoints[1].clone() });
+		deliver(&mut nodes, &endpoints);
+		assert_eq!(nodes[0].node_record(keys[1].public()).unwrap().seq, nodes[1].record.seq);
+
+		// node 2 only knows node 0 and finds node 1 with a topic lookup
+		nodes[2].add_node(NodeEntry { id: keys[0].public().clone(), endpoint: endpoints[0].clone() });
+		deliver(&mut nodes, &endpoints);
+		assert!(topic_nodes(&nodes[2], &topic(b"par", 1)).is_empty());
+		nodes[2].round();
+		deliver(&mut nodes, &endpoints);
+		assert_eq!(topic_nodes(&nodes[2], &topic(b"par", 1)), vec![keys[1].public().clone()]);
+		assert!(topic_nodes(&nodes[2], &topic(b"par", 2)).is_empty());
+	}
+
+	#[test]
+	fn ignores_unsolicited_records() {
+		let keys: Vec<_> = (0..2).map(|_| Random.generate().unwrap()).collect();
+		let endpoints: Vec<_> = (0..2).map(|i| NodeEndpoint { address: SocketAddr::from_str(&format!("127.0.0.1:{}", 40460 + i)).unwrap(), udp_port: 40460 + i }).collect();
+		let mut nodes: Vec<_> = (0..2).map(|i| Discovery::new(&keys[i], endpoints[i].address.clone(), endpoints[i].clone(), 0, AllowIP::All)).collect();
+		let par = RecordCapability { protocol: *b"par", version: 1 };
+		nodes[0].set_capabilities(vec![par.clone()], Some(1));
+		nodes[1].set_capabilities(vec![par.clone()], Some(1));
+
+		let mut record = RlpStream::new_list(1);
+		nodes[1].record.to_rlp(&mut record);
+		let address = endpoints[0].address.clone();
+		nodes[1].send_packet(PACKET_RECORD, &address, &record.out());
+		let topic_nodes_packet = Discovery::prepare_topic_packets(&topic(b"par", 1), &[&nodes[1].record]).pop().unwrap();
+		nodes[1].send_packet(PACKET_TOPIC_NODES, &address, &topic_nodes_packet);
+		deliver(&mut nodes, &endpoints);
+		assert!(nodes[0].node_record(keys[1].public()).is_none());
+	}
+
 	#[test]
 	fn removes_expired() {
 		let key = Random.generate().unwrap();
@@ -618,6 +912,29 @@ mod tests {
 		assert!(removed > 0);
 	}
 
+	#[test]
+	fn advertises_new_public_endpoint() {
+		let key = Random.generate().unwrap();
+		let ep = NodeEndpoint { address: SocketAddr::from_str("127.0.0.1:40448").unwrap(), udp_port: 40448 };
+		let mut discovery = Discovery::new(&key, ep.address.clone(), ep.clone(), 0, AllowIP::All);
+		for i in 0..3 {
+			let node = NodeEndpoint { address: SocketAddr::from_str(&format!("127.0.0.1:{}", 40450 + i)).unwrap(), udp_port: 40450 + i };
+			discovery.add_node(NodeEntry { id: NodeId::random(), endpoint: node });
+		}
+		discovery.send_queue.clear();
+		let seq = discovery.record.seq;
+
+		discovery.set_public_endpoint(ep.clone());
+		assert!(discovery.send_queue.is_empty());
+
+		let external = NodeEndpoint { address: SocketAddr::from_str("203.0.113.7:30303").unwrap(), udp_port: 30303 };
+		discovery.set_public_endpoint(external.clone());
+		assert_eq!(discovery.record.endpoint, external);
+		assert_eq!(discovery.record.seq, seq + 1);
+		assert!(discovery.record.verify().is_ok());
+		assert_eq!(discovery.send_queue.len(), 3);
+	}
+
 	#[test]
 	fn find_nearest_saturated() {
 		use super::*;
@@ -94,6 +94,10 @@ pub enum NetworkError {
 	Expired,
 	/// Peer not found.
 	PeerNotFound,
+	/// Node allowlist is not enabled.
+	AllowlistDisabled,
+	/// Network host is not running.
+	NotRunning,
 	/// Peer is diconnected.
 	Disconnect(DisconnectReason),
 	/// Util error.
@@ -117,6 +121,8 @@ impl fmt::Display for NetworkError {
 			BadProtocol => "Bad protocol".into(),
 			Expired => "Expired message".into(),
 			PeerNotFound => "Peer not found".into(),
+			AllowlistDisabled => "Node allowlist is not enabled".into(),
+			NotRunning => "Network is not running".into(),
 			Disconnect(ref reason) => format!("Peer disconnected: {}", reason),
 			Io(ref err) => format!("Socket I/O error: {}", err),
 			AddressParse(ref err) => format!("{}", err),
@@ -146,6 +146,12 @@ impl Handshake {
 				}
 				if self.state == HandshakeState::StartSession {
 					io.clear_timer(self.connection.token).ok();
+					// The remote id of an incoming connection is first known here. Drop the connection
+					// before the queued ack is flushed and a session is set up.
+					if !self.originated && !host.is_allowed(&self.id) {
+						debug!(target: "network", "Rejecting handshake from {:?}: not in the allowlist", self.connection.remote_addr_str());
+						return Err(NetworkError::Disconnect(DisconnectReason::DisconnectRequested));
+					}
 					break;
 				}
 			}
@@ -34,13 +34,19 @@ use rlp::*;
 use session::{Session, SessionInfo, SessionData};
 use error::*;
 use io::*;
-use {NetworkProtocolHandler, NonReservedPeerMode, AllowIP, PROTOCOL_VERSION};
+use {NetworkProtocolHandler, NetworkContext as NetworkContextTrait, NonReservedPeerMode, AllowIP, NatMethod, PROTOCOL_VERSION};
 use node_table::*;
 use stats::NetworkStats;
+use bandwidth::BandwidthLimits;
+use reputation::PeerEvent;
+use allowlist::Allowlist;
+use capture::PacketRecorder;
 use discovery::{Discovery, TableUpdates, NodeEntry};
-use ip_utils::{map_external_address, select_public_address};
+use node_record::{RecordCapability, topic};
+use ip_utils::{map_external_address, select_public_address, NatMapping, NatProtocol};
 use util::path::restrict_permissions_owner;
 use parking_lot::{Mutex, RwLock};
+use time;
 
 type Slab<T> = ::slab::Slab<T, usize>;
 
@@ -54,6 +60,8 @@ const DISCOVERY: usize = SYS_TIMER + 3;
 const DISCOVERY_REFRESH: usize = SYS_TIMER + 4;
 const DISCOVERY_ROUND: usize = SYS_TIMER + 5;
 const NODE_TABLE: usize = SYS_TIMER + 6;
+const THROTTLE: usize = SYS_TIMER + 7;
+const NAT_RENEWAL: usize = SYS_TIMER + 8;
 const FIRST_SESSION: usize = 0;
 const LAST_SESSION: usize = FIRST_SESSION + MAX_SESSIONS - 1;
 const USER_TIMER: usize = LAST_SESSION + 256;
@@ -64,6 +72,10 @@ const MAINTENANCE_TIMEOUT: u64 = 1000;
 const DISCOVERY_REFRESH_TIMEOUT: u64 = 7200;
 const DISCOVERY_ROUND_TIMEOUT: u64 = 300;
 const NODE_TABLE_TIMEOUT: u64 = 300_000;
+const THROTTLE_TIMEOUT: u64 = 100;
+const NAT_RENEWAL_TIMEOUT: u64 = 60_000;
+/// Maximum interval between port mapping attempts in seconds. Renewals also pick up external address changes.
+const NAT_CHECK_INTERVAL: u64 = 600;
 
 #[derive(Debug, PartialEq, Clone)]
 /// Network service configuration
@@ -80,6 +92,8 @@ pub struct NetworkConfiguration {
 	pub udp_port: Option<u16>,
 	/// Enable NAT configuration
 	pub nat_enabled: bool,
+	/// NAT traversal method used when NAT configuration is enabled
+	pub nat_method: NatMethod,
 	/// Enable discovery
 	pub discovery_enabled: bool,
 	/// List of initial node addresses
@@ -100,6 +114,20 @@ pub struct NetworkConfiguration {
 	pub non_reserved_mode: NonReservedPeerMode,
 	/// IP filter
 	pub allow_ips: AllowIP,
+	/// Maximum total upload rate in bytes per second. None means unlimited.
+	pub max_upload_rate: Option<usize>,
+	/// Maximum total download rate in bytes per second. None means unlimited.
+	pub max_download_rate: Option<usize>,
+	/// Maximum upload rate for a single peer in bytes per second. None means unlimited.
+	pub max_peer_upload_rate: Option<usize>,
+	/// Maximum download rate for a single peer in bytes per second. None means unlimited.
+	pub max_peer_download_rate: Option<usize>,
+	/// Chain id advertised in the discovery node record. Nodes advertising a different chain are not connected to.
+	pub chain_id: Option<u64>,
+	/// Path to a file listing the nodes allowed to connect. When set, only these nodes are accepted and discovered.
+	pub allowlist_path: Option<String>,
+	/// Path to a file to record all subprotocol packets to. None means packets are not recorded.
+	pub capture_path: Option<String>,
 }
 
 impl Default for NetworkConfiguration {
@@ -118,6 +146,7 @@ impl NetworkConfiguration {
 			public_address: None,
 			udp_port: None,
 			nat_enabled: true,
+			nat_method: NatMethod::Any,
 			discovery_enabled: true,
 			boot_nodes: Vec::new(),
 			use_secret: None,
@@ -128,6 +157,13 @@ impl NetworkConfiguration {
 			allow_ips: AllowIP::All,
 			reserved_nodes: Vec::new(),
 			non_reserved_mode: NonReservedPeerMode::Accept,
+			max_upload_rate: None,
+			max_download_rate: None,
+			max_peer_upload_rate: None,
+			max_peer_download_rate: None,
+			chain_id: None,
+			allowlist_path: None,
+			capture_path: None,
 		}
 	}
 
@@ -161,10 +197,8 @@ pub enum NetworkIoMessage {
 		handler: Arc<NetworkProtocolHandler + Sync>,
 		/// Protocol Id.
 		protocol: ProtocolId,
-		/// Supported protocol versions.
-		versions: Vec<u8>,
-		/// Number of packet IDs reserved by the protocol.
-		packet_count: u8,
+		/// Supported protocol versions and the number of packet IDs each reserves.
+		versions: Vec<(u8, u8)>,
 	},
 	/// Register a new protocol timer
 	AddTimer {
@@ -181,6 +215,8 @@ pub enum NetworkIoMessage {
 	Disconnect(PeerId),
 	/// Disconnect and temporary disable peer.
 	DisablePeer(PeerId),
+	/// Update peer reputation.
+	ReportPeer(PeerId, PeerEvent),
 	/// Network has been started with the host as the given enode.
 	NetworkStarted(String),
 }
@@ -205,14 +241,14 @@ impl Encodable for CapabilityInfo {
 	}
 }
 
-/// IO access point. This is passed to all IO handlers and provides an interface to the IO subsystem.
+/// `NetworkContext` implementation backed by the host's sessions and IO event loop.
 pub struct NetworkContext<'s> {
 	io: &'s IoContext<NetworkIoMessage>,
 	protocol: ProtocolId,
 	sessions: Arc<RwLock<Slab<SharedSession>>>,
 	session: Option<SharedSession>,
 	session_id: Option<StreamToken>,
-	_reserved_peers: &'s HashSet<NodeId>,
+	reserved_peers: &'s HashSet<NodeId>,
 }
 
 impl<'s> NetworkContext<'s> {
@@ -228,7 +264,7 @@ impl<'s> NetworkContext<'s> {
 			session_id: id,
 			session: session,
 			sessions: sessions,
-			_reserved_peers: reserved_peers,
+			reserved_peers: reserved_peers,
 		}
 	}
 
@@ -238,14 +274,14 @@ impl<'s> NetworkContext<'s> {
 			_ => self.sessions.read().get(peer).cloned(),
 		}
 	}
+}
 
-	/// Send a packet over the network to another peer.
-	pub fn send(&self, peer: PeerId, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
+impl<'s> NetworkContextTrait for NetworkContext<'s> {
+	fn send(&self, peer: PeerId, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
 		self.send_protocol(self.protocol, peer, packet_id, data)
 	}
 
-	/// Send a packet over the network to another peer using specified protocol.
-	pub fn send_protocol(&self, protocol: ProtocolId, peer: PeerId, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
+	fn send_protocol(&self, protocol: ProtocolId, peer: PeerId, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
 		let session = self.resolve_session(peer);
 		if let Some(session) = session {
 			try!(session.lock().send_packet(self.io, protocol, packet_id as u8, &data));
@@ -255,36 +291,35 @@ impl<'s> NetworkContext<'s> {
 		Ok(())
 	}
 
-	/// Respond to a current network message. Panics if no there is no packet in the context. If the session is expired returns nothing.
-	pub fn respond(&self, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
+	fn respond(&self, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError> {
 		assert!(self.session.is_some(), "Respond called without network context");
 		self.send(self.session_id.unwrap(), packet_id, data)
 	}
 
-	/// Get an IoChannel.
-	pub fn io_channel(&self) -> IoChannel<NetworkIoMessage> {
+	fn io_channel(&self) -> IoChannel<NetworkIoMessage> {
 		self.io.channel()
 	}
 
-	/// Disconnect a peer and prevent it from connecting again.
-	pub fn disable_peer(&self, peer: PeerId) {
+	fn disable_peer(&self, peer: PeerId) {
 		self.io.message(NetworkIoMessage::DisablePeer(peer))
 			.unwrap_or_else(|e| warn!("Error sending network IO message: {:?}", e));
 	}
 
-	/// Disconnect peer. Reconnect can be attempted later.
-	pub fn disconnect_peer(&self, peer: PeerId) {
+	fn disconnect_peer(&self, peer: PeerId) {
 		self.io.message(NetworkIoMessage::Disconnect(peer))
 			.unwrap_or_else(|e| warn!("Error sending network IO message: {:?}", e));
 	}
 
-	/// Check if the session is still active.
-	pub fn is_expired(&self) -> bool {
+	fn report_peer(&self, peer: PeerId, event: PeerEvent) {
+		self.io.message(NetworkIoMessage::ReportPeer(peer, event))
+			.unwrap_or_else(|e| warn!("Error sending network IO message: {:?}", e));
+	}
+
+	fn is_expired(&self) -> bool {
 		self.session.as_ref().map_or(false, |s| s.lock().expired())
 	}
 
-	/// Register a new IO timer. 'IoHandler::timeout' will be called with the token.
-	pub fn register_timer(&self, token: TimerToken, ms: u64) -> Result<(), NetworkError> {
+	fn register_timer(&self, token: TimerToken, ms: u64) -> Result<(), NetworkError> {
 		self.io.message(NetworkIoMessage::AddTimer {
 			token: token,
 			delay: ms,
@@ -293,24 +328,24 @@ impl<'s> NetworkContext<'s> {
 		Ok(())
 	}
 
-	/// Returns peer identification string
-	pub fn peer_client_version(&self, peer: PeerId) -> String {
+	fn peer_client_version(&self, peer: PeerId) -> String {
 		self.resolve_session(peer).map_or("unknown".to_owned(), |s| s.lock().info.client_version.clone())
 	}
 
-	/// Returns information on p2p session
-	pub fn session_info(&self, peer: PeerId) -> Option<SessionInfo> {
+	fn session_info(&self, peer: PeerId) -> Option<SessionInfo> {
 		self.resolve_session(peer).map(|s| s.lock().info.clone())
 	}
 
-	/// Returns max version for a given protocol.
-	pub fn protocol_version(&self, protocol: ProtocolId, peer: PeerId) -> Option<u8> {
+	fn is_reserved_peer(&self, peer: PeerId) -> bool {
+		self.resolve_session(peer).map_or(false, |s| s.lock().id().map_or(false, |id| self.reserved_peers.contains(id)))
+	}
+
+	fn protocol_version(&self, protocol: ProtocolId, peer: PeerId) -> Option<u8> {
 		let session = self.resolve_session(peer);
 		session.and_then(|s| s.lock().capability_version(protocol))
 	}
 
-	/// Returns this object's subprotocol name.
-	pub fn subprotocol_name(&self) -> ProtocolId { self.protocol }
+	fn subprotocol_name(&self) -> ProtocolId { self.protocol }
 }
 
 /// Shared host information
@@ -331,6 +366,8 @@ pub struct HostInfo {
 	pub local_endpoint: NodeEndpoint,
 	/// Public address + discovery port
 	pub public_endpoint: Option<NodeEndpoint>,
+	/// Nodes allowed to connect, shared with the host.
+	allowlist: Arc<RwLock<Option<Allowlist>>>,
 }
 
 impl HostInfo {
@@ -349,10 +386,49 @@ impl HostInfo {
 		self.nonce = self.nonce.sha3();
 		self.nonce.clone()
 	}
+
+	/// Checks if a node may connect. Always true when no allowlist is configured.
+	pub fn is_allowed(&self, id: &NodeId) -> bool {
+		self.allowlist.read().as_ref().map_or(true, |a| a.contains(id))
+	}
 }
 
 type SharedSession = Arc<Mutex<Session>>;
 
+/// NAT traversal status.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NatStatus {
+	/// Port mapping on the gateway is enabled.
+	pub enabled: bool,
+	/// Protocol of the active port mapping.
+	pub protocol: Option<NatProtocol>,
+	/// Address advertised to other nodes.
+	pub public_address: Option<SocketAddr>,
+	/// UDP port advertised to other nodes.
+	pub public_udp_port: Option<u16>,
+	/// Lifetime of the active port mapping in seconds.
+	pub lifetime: u32,
+	/// Unix time the port mapping was last created or renewed.
+	pub renewed_at: u64,
+	/// Number of times the external address has changed.
+	pub address_changes: u32,
+	/// Number of failed mapping attempts.
+	pub failures: u32,
+}
+
+#[derive(Default)]
+struct NatState {
+	mapping: Option<NatMapping>,
+	/// Unix time of the last mapping attempt.
+	attempted_at: u64,
+	/// Unix time the mapping was last created or renewed.
+	renewed_at: u64,
+	address_changes: u32,
+	failures: u32,
+	/// Whether a mapping request is under way.
+	mapping_in_progress: bool,
+}
+
 #[derive(Copy, Clone)]
 struct ProtocolTimer {
 	pub protocol: ProtocolId,
@@ -370,7 +446,11 @@ pub struct Host {
 	timers: RwLock<HashMap<TimerToken, ProtocolTimer>>,
 	timer_counter: RwLock<usize>,
 	stats: Arc<NetworkStats>,
+	bandwidth_limits: Arc<BandwidthLimits>,
 	reserved_nodes: RwLock<HashSet<NodeId>>,
+	allowlist: Arc<RwLock<Option<Allowlist>>>,
+	recorder: Option<Arc<PacketRecorder>>,
+	nat: Mutex<NatState>,
 	num_sessions: AtomicUsize,
 	stopping: AtomicBool,
 }
@@ -407,6 +487,15 @@ impl Host {
 
 		let boot_nodes = config.boot_nodes.clone();
 		let reserved_nodes = config.reserved_nodes.clone();
+		let bandwidth_limits = Arc::new(BandwidthLimits::new(&config));
+		let allowlist = Arc::new(RwLock::new(match config.allowlist_path {
+			Some(ref path) => Some(try!(Allowlist::load(path))),
+			None => None,
+		}));
+		let recorder = match config.capture_path {
+			Some(ref path) => Some(Arc::new(try!(PacketRecorder::new(path)))),
+			None => None,
+		};
 		config.max_handshakes = min(config.max_handshakes, MAX_HANDSHAKES as u32);
 
 		let mut host = Host {
@@ -419,6 +508,7 @@ impl Host {
 				capabilities: Vec::new(),
 				public_endpoint: None,
 				local_endpoint: local_endpoint,
+				allowlist: allowlist.clone(),
 			}),
 			discovery: Mutex::new(None),
 			tcp_listener: Mutex::new(tcp_listener),
@@ -428,7 +518,11 @@ impl Host {
 			timers: RwLock::new(HashMap::new()),
 			timer_counter: RwLock::new(USER_TIMER),
 			stats: stats,
+			bandwidth_limits: bandwidth_limits,
 			reserved_nodes: RwLock::new(HashSet::new()),
+			allowlist: allowlist,
+			recorder: recorder,
+			nat: Mutex::new(NatState::default()),
 			num_sessions: AtomicUsize::new(0),
 			stopping: AtomicBool::new(false),
 		};
@@ -510,6 +604,105 @@ impl Host {
 		Ok(())
 	}
 
+	/// Returns nodes that are currently banned.
+	pub fn banned_nodes(&self) -> Vec<BannedNode> {
+		self.nodes.read().banned_nodes()
+	}
+
+	/// Current NAT traversal status.
+	pub fn nat_status(&self) -> NatStatus {
+		let info = self.info.read();
+		let nat = self.nat.lock();
+		NatStatus {
+			enabled: info.config.nat_enabled && info.config.public_address.is_none(),
+			protocol: nat.mapping.as_ref().map(|m| m.protocol),
+			public_address: info.public_endpoint.as_ref().map(|e| e.address),
+			public_udp_port: info.public_endpoint.as_ref().map(|e| e.udp_port),
+			lifetime: nat.mapping.as_ref().map_or(0, |m| m.lifetime),
+			renewed_at: nat.renewed_at,
+			address_changes: nat.address_changes,
+			failures: nat.failures,
+		}
+	}
+
+	/// Lift the ban for a node given either as an enode URL or as a node id.
+	pub fn unban_node(&self, id: &str) -> Result<(), NetworkError> {
+		let id = try!(node_id_from_str(id));
+		if !self.nodes.write().unban(&id) {
+			return Err(NetworkError::PeerNotFound);
+		}
+		Ok(())
+	}
+
+	/// Returns nodes allowed to connect.
+	pub fn allowed_nodes(&self) -> Result<Vec<NodeId>, NetworkError> {
+		match *self.allowlist.read() {
+			Some(ref allowlist) => Ok(allowlist.nodes().iter().cloned().collect()),
+			None => Err(NetworkError::AllowlistDisabled),
+		}
+	}
+
+	/// Allow a node given either as an enode URL or as a node id.
+	pub fn add_allowed_node(&self, id: &str) -> Result<(), NetworkError> {
+		let id = try!(node_id_from_str(id));
+		match *self.allowlist.write() {
+			Some(ref mut allowlist) => { try!(allowlist.insert(id)); },
+			None => return Err(NetworkError::AllowlistDisabled),
+		}
+		self.update_discovery_allowlist();
+		Ok(())
+	}
+
+	/// Remove a node from the allowlist and disconnect it.
+	pub fn remove_allowed_node(&self, id: &str, io: &IoContext<NetworkIoMessage>) -> Result<(), NetworkError> {
+		let id = try!(node_id_from_str(id));
+		match *self.allowlist.write() {
+			Some(ref mut allowlist) => { try!(allowlist.remove(&id)); },
+			None => return Err(NetworkError::AllowlistDisabled),
+		}
+		self.update_discovery_allowlist();
+		self.drop_disallowed_sessions(io);
+		Ok(())
+	}
+
+	/// Read the allowlist file again and disconnect nodes that are no longer allowed.
+	pub fn reload_allowlist(&self, io: &IoContext<NetworkIoMessage>) -> Result<(), NetworkError> {
+		match *self.allowlist.write() {
+			Some(ref mut allowlist) => try!(allowlist.reload()),
+			None => return Err(NetworkError::AllowlistDisabled),
+		}
+		self.update_discovery_allowlist();
+		self.drop_disallowed_sessions(io);
+		Ok(())
+	}
+
+	fn is_allowed(&self, id: &NodeId) -> bool {
+		self.allowlist.read().as_ref().map_or(true, |a| a.contains(id))
+	}
+
+	fn update_discovery_allowlist(&self) {
+		let nodes = self.allowlist.read().as_ref().map(|a| a.nodes().clone());
+		if let Some(ref mut discovery) = *self.discovery.lock() {
+			discovery.set_allowlist(nodes);
+		}
+	}
+
+	fn drop_disallowed_sessions(&self, io: &IoContext<NetworkIoMessage>) {
+		let mut to_kill = Vec::new();
+		for e in self.sessions.write().iter_mut() {
+			let mut s = e.lock();
+			let allowed = s.id().map_or(true, |id| self.is_allowed(id));
+			if !allowed {
+				info!(target: "network", "Disconnecting {:?}: removed from the allowlist", s.id());
+				s.disconnect(io, DisconnectReason::DisconnectRequested);
+				to_kill.push(s.token());
+			}
+		}
+		for p in to_kill {
+			self.kill_connection(p, io, false);
+		}
+	}
+
 	pub fn client_version() -> String {
 		version()
 	}
@@ -552,7 +745,8 @@ impl Host {
 				let public_address = select_public_address(local_endpoint.address.port());
 				let public_endpoint = NodeEndpoint { address: public_address, udp_port: local_endpoint.udp_port };
 				if self.info.read().config.nat_enabled {
-					match map_external_address(&local_endpoint) {
+					try!(io.register_timer(NAT_RENEWAL, NAT_RENEWAL_TIMEOUT));
+					match self.map_nat(&local_endpoint) {
 						Some(endpoint) => {
 							info!("NAT mapped to external address {}", endpoint.address);
 							endpoint
@@ -583,6 +777,11 @@ impl Host {
 		};
 
 		if let Some(mut discovery) = discovery {
+			{
+				let info = self.info.read();
+				discovery.set_capabilities(Host::record_capabilities(&info.capabilities), info.config.chain_id);
+			}
+			discovery.set_allowlist(self.allowlist.read().as_ref().map(|a| a.nodes().clone()));
 			discovery.init_node_list(self.nodes.read().unordered_entries());
 			discovery.add_node_list(self.nodes.read().unordered_entries());
 			*self.discovery.lock() = Some(discovery);
@@ -595,6 +794,78 @@ impl Host {
 		Ok(())
 	}
 
+	/// Create or renew the port mapping. Returns the mapped external endpoint.
+	fn map_nat(&self, local_endpoint: &NodeEndpoint) -> Option<NodeEndpoint> {
+		let method = self.info.read().config.nat_method;
+		let now = time::get_time().sec as u64;
+		let previous = {
+			let mut nat = self.nat.lock();
+			if nat.mapping_in_progress {
+				return None;
+			}
+			nat.mapping_in_progress = true;
+			nat.attempted_at = now;
+			nat.mapping.clone()
+		};
+		// gateway requests block for a while, so don't hold the lock during them.
+		let mapping = map_external_address(local_endpoint, method, previous.as_ref());
+		let mut nat = self.nat.lock();
+		nat.mapping_in_progress = false;
+		match mapping {
+			Some(mapping) => {
+				let external = mapping.external.clone();
+				nat.mapping = Some(mapping);
+				nat.renewed_at = now;
+				Some(external)
+			},
+			None => {
+				nat.failures += 1;
+				// Keep the mapping around until it expires so that a renewal can still reuse it
+				let expired = nat.mapping.as_ref().map_or(false, |m| now >= nat.renewed_at + m.lifetime as u64);
+				if expired {
+					nat.mapping = None;
+				}
+				None
+			},
+		}
+	}
+
+	/// Renew the port mapping when due and advertise the new external endpoint if it has changed.
+	fn renew_nat(&self, io: &IoContext<NetworkIoMessage>) {
+		let now = time::get_time().sec as u64;
+		{
+			let nat = self.nat.lock();
+			let due = match nat.mapping {
+				Some(ref m) => now >= nat.renewed_at + min(m.lifetime as u64 / 2, NAT_CHECK_INTERVAL),
+				None => now >= nat.attempted_at + NAT_CHECK_INTERVAL,
+			};
+			if !due {
+				return;
+			}
+		}
+		let local_endpoint = self.info.read().local_endpoint.clone();
+		let endpoint = match self.map_nat(&local_endpoint) {
+			Some(endpoint) => endpoint,
+			None => {
+				debug!(target: "network", "Error renewing NAT port mapping");
+				return;
+			},
+		};
+		if self.info.read().public_endpoint.as_ref() == Some(&endpoint) {
+			return;
+		}
+		info!(target: "network", "External address changed to {}", endpoint.address);
+		self.nat.lock().address_changes += 1;
+		self.info.write().public_endpoint = Some(endpoint.clone());
+		if let Some(ref mut discovery) = *self.discovery.lock() {
+			discovery.set_public_endpoint(endpoint);
+			io.update_registration(DISCOVERY).unwrap_or_else(|e| debug!("Error updating discovery registration: {:?}", e));
+		}
+		if let Some(url) = self.external_url() {
+			io.message(NetworkIoMessage::NetworkStarted(url)).unwrap_or_else(|e| warn!("Error sending IO notification: {:?}", e));
+		}
+	}
+
 	fn maintain_network(&self, io: &IoContext<NetworkIoMessage>) {
 		self.keep_alive(io);
 		self.connect_peers(io);
@@ -664,14 +935,14 @@ impl Host {
 		// iterate over all nodes, reserved ones coming first.
 		// if we are pinned to only reserved nodes, ignore all others.
 		let nodes = reserved_nodes.iter().cloned().chain(if !pin {
-			self.nodes.read().nodes(allow_ips)
+			self.prioritized_nodes(allow_ips)
 		} else {
 			Vec::new()
 		});
 
 		let max_handshakes_per_round = max_handshakes / 2;
 		let mut started: usize = 0;
-		for id in nodes.filter(|ref id| !self.have_session(id) && !self.connecting_to(id))
+		for id in nodes.filter(|ref id| !self.have_session(id) && !self.connecting_to(id) && self.is_allowed(id) && (reserved_nodes.contains(id) || !self.nodes.read().is_banned(id)))
 			.take(min(max_handshakes_per_round, max_handshakes - handshake_count)) {
 			self.connect_peer(&id, io);
 			started += 1;
@@ -679,6 +950,55 @@ impl Host {
 		debug!(target: "network", "Connecting peers: {} sessions, {} pending, {} started", self.session_count(), self.handshake_count(), started);
 	}
 
+	/// Known nodes in the order they should be connected to. Nodes that advertise a reserved protocol on our chain
+	/// come first, followed by other nodes on our chain and nodes without a record. Nodes on other chains are skipped.
+	fn prioritized_nodes(&self, allow_ips: AllowIP) -> Vec<NodeId> {
+		let nodes = self.nodes.read().nodes(allow_ips);
+		let (chain_id, preferred) = {
+			let info = self.info.read();
+			let chain_id = match info.config.chain_id {
+				Some(id) => id,
+				None => return nodes,
+			};
+			(chain_id, info.config.reserved_protocols.keys().map(|p| topic(p, chain_id)).collect::<Vec<_>>())
+		};
+		let discovery = self.discovery.lock();
+		let discovery = match *discovery {
+			Some(ref discovery) => discovery,
+			None => return nodes,
+		};
+		let mut ranked: Vec<(usize, NodeId)> = nodes.into_iter().filter_map(|id| {
+			let rank = match discovery.node_record(&id) {
+				Some(record) => match record.chain_id {
+					Some(c) if c != chain_id => return None,
+					_ if preferred.iter().any(|t| record.has_topic(t)) => 0,
+					_ => 1,
+				},
+				None => 2,
+			};
+			Some((rank, id))
+		}).collect();
+		// stable sort keeps the node table order within each rank
+		ranked.sort_by_key(|&(rank, _)| rank);
+		ranked.into_iter().map(|(_, id)| id).collect()
+	}
+
+	/// Update the reputation of a connected node. Returns true if the node got banned as a result.
+	fn note_peer_event(&self, id: &NodeId, address: Option<SocketAddr>, event: PeerEvent) -> bool {
+		let mut nodes = self.nodes.write();
+		// the node may have dropped out of the table while connected; it still has to be tracked to be banned.
+		if nodes.get_mut(id).is_none() {
+			if let Some(address) = address {
+				nodes.add_node(Node::new(id.clone(), NodeEndpoint { address: address, udp_port: address.port() }));
+			}
+		}
+		nodes.note_event(id, event)
+	}
+
+	fn record_capabilities(capabilities: &[CapabilityInfo]) -> Vec<RecordCapability> {
+		capabilities.iter().map(|c| RecordCapability { protocol: c.protocol, version: c.version }).collect()
+	}
+
 	#[cfg_attr(feature="dev", allow(single_match))]
 	fn connect_peer(&self, id: &NodeId, io: &IoContext<NetworkIoMessage>) {
 		if self.have_session(id)
@@ -722,7 +1042,7 @@ impl Host {
 		let mut sessions = self.sessions.write();
 
 		let token = sessions.insert_with_opt(|token| {
-			match Session::new(io, socket, token, id, &nonce, self.stats.clone(), &self.info.read()) {
+			match Session::new(io, socket, token, id, &nonce, self.stats.clone(), self.bandwidth_limits.clone(), self.recorder.clone(), &self.info.read()) {
 				Ok(s) => Some(Arc::new(Mutex::new(s))),
 				Err(e) => {
 					debug!(target: "network", "Session create error: {:?}", e);
@@ -771,6 +1091,26 @@ impl Host {
 		}
 	}
 
+	/// Resume reading and writing for sessions postponed by the rate limits.
+	fn resume_throttled(&self, io: &IoContext<NetworkIoMessage>) {
+		let (mut readable, mut writable) = (Vec::new(), Vec::new());
+		for e in self.sessions.read().iter() {
+			let s = e.lock();
+			if s.is_read_throttled() {
+				readable.push(s.token());
+			}
+			if s.is_write_throttled() {
+				writable.push(s.token());
+			}
+		}
+		for token in writable {
+			self.session_writable(token, io);
+		}
+		for token in readable {
+			self.session_readable(token, io);
+		}
+	}
+
 	fn connection_closed(&self, token: TimerToken, io: &IoContext<NetworkIoMessage>) {
 		trace!(target: "network", "Connection closed: {}", token);
 		self.kill_connection(token, io, true);
@@ -801,6 +1141,16 @@ impl Host {
 					},
 					Ok(SessionData::Ready) => {
 						self.num_sessions.fetch_add(1, AtomicOrdering::SeqCst);
+						if !self.is_allowed(s.id().unwrap()) {
+							info!(target: "network", "Rejecting node {}: not in the allowlist", s.id().unwrap());
+							s.disconnect(io, DisconnectReason::DisconnectRequested);
+							return;
+						}
+						if self.nodes.read().is_banned(s.id().unwrap()) && !self.reserved_nodes.read().contains(s.id().unwrap()) {
+							trace!(target: "network", "Rejecting banned node {}", s.id().unwrap());
+							s.disconnect(io, DisconnectReason::DisconnectRequested);
+							return;
+						}
 						if !s.info.originated {
 							let session_count = self.session_count();
 							let (max_peers, reserved_only) = {
@@ -935,14 +1285,14 @@ impl Host {
 		self.nodes.write().update(node_changes, &*self.reserved_nodes.read());
 	}
 
-	pub fn with_context<F>(&self, protocol: ProtocolId, io: &IoContext<NetworkIoMessage>, action: F) where F: Fn(&NetworkContext) {
+	pub fn with_context<F>(&self, protocol: ProtocolId, io: &IoContext<NetworkIoMessage>, action: F) where F: Fn(&NetworkContextTrait) {
 		let reserved = { self.reserved_nodes.read() };
 
 		let context = NetworkContext::new(io, protocol, None, self.sessions.clone(), &reserved);
 		action(&context);
 	}
 
-	pub fn with_context_eval<F, T>(&self, protocol: ProtocolId, io: &IoContext<NetworkIoMessage>, action: F) -> T where F: Fn(&NetworkContext) -> T {
+	pub fn with_context_eval<F, T>(&self, protocol: ProtocolId, io: &IoContext<NetworkIoMessage>, action: F) -> T where F: Fn(&NetworkContextTrait) -> T {
 		let reserved = { self.reserved_nodes.read() };
 
 		let context = NetworkContext::new(io, protocol, None, self.sessions.clone(), &reserved);
@@ -954,6 +1304,9 @@ impl IoHandler<NetworkIoMessage> for Host {
 	/// Initialize networking
 	fn initialize(&self, io: &IoContext<NetworkIoMessage>) {
 		io.register_timer(IDLE, MAINTENANCE_TIMEOUT).expect("Error registering Network idle timer");
+		if self.bandwidth_limits.is_limited() {
+			io.register_timer(THROTTLE, THROTTLE_TIMEOUT).expect("Error registering Network throttle timer");
+		}
 		io.message(NetworkIoMessage::InitPublicInterface).unwrap_or_else(|e| warn!("Error sending IO notification: {:?}", e));
 		self.maintain_network(io)
 	}
@@ -1002,6 +1355,8 @@ impl IoHandler<NetworkIoMessage> for Host {
 		}
 		match token {
 			IDLE => self.maintain_network(io),
+			THROTTLE => self.resume_throttled(io),
+			NAT_RENEWAL => self.renew_nat(io),
 			FIRST_SESSION ... LAST_SESSION => self.connection_timeout(token, io),
 			DISCOVERY_REFRESH => {
 				self.discovery.lock().as_mut().unwrap().refresh();
@@ -1041,15 +1396,17 @@ impl IoHandler<NetworkIoMessage> for Host {
 				ref handler,
 				ref protocol,
 				ref versions,
-				ref packet_count,
 			} => {
 				let h = handler.clone();
 				let reserved = self.reserved_nodes.read();
 				h.initialize(&NetworkContext::new(io, *protocol, None, self.sessions.clone(), &reserved));
 				self.handlers.write().insert(*protocol, h);
 				let mut info = self.info.write();
-				for v in versions {
-					info.capabilities.push(CapabilityInfo { protocol: *protocol, version: *v, packet_count: *packet_count });
+				for &(version, packet_count) in versions {
+					info.capabilities.push(CapabilityInfo { protocol: *protocol, version: version, packet_count: packet_count });
+				}
+				if let Some(ref mut discovery) = *self.discovery.lock() {
+					discovery.set_capabilities(Host::record_capabilities(&info.capabilities), info.config.chain_id);
 				}
 			},
 			NetworkIoMessage::AddTimer {
@@ -1086,6 +1443,23 @@ impl IoHandler<NetworkIoMessage> for Host {
 				trace!(target: "network", "Disabling peer {}", peer);
 				self.kill_connection(*peer, io, false);
 			},
+			NetworkIoMessage::ReportPeer(ref peer, ref event) => {
+				let session = { self.sessions.read().get(*peer).cloned() };
+				if let Some(session) = session {
+					let (id, address) = {
+						let s = session.lock();
+						(s.id().cloned(), s.remote_addr().ok())
+					};
+					if let Some(id) = id {
+						// reserved nodes are trusted and never banned
+						if !self.reserved_nodes.read().contains(&id) && self.note_peer_event(&id, address, *event) {
+							session.lock().disconnect(io, DisconnectReason::DisconnectRequested);
+							trace!(target: "network", "Banning peer {}", peer);
+							self.kill_connection(*peer, io, false);
+						}
+					}
+				}
+			},
 			NetworkIoMessage::InitPublicInterface =>
 				self.init_public_interface(io).unwrap_or_else(|e| warn!("Error initializing public interface: {:?}", e)),
 			_ => {}	// ignore others.
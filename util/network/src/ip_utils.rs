@@ -16,11 +16,32 @@
 
 // Based on original work by David Levy https://raw.githubusercontent.com/dlevy47/rust-interfaces
 
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket};
 use std::io;
-use igd::{PortMappingProtocol, search_gateway_from_timeout};
+use std::cmp::min;
+use igd::{Gateway, PortMappingProtocol, AddPortError, AddAnyPortError, search_gateway_from_timeout};
 use std::time::Duration;
 use node_table::{NodeEndpoint};
+use NatMethod;
+
+/// UDP port of NAT-PMP and PCP servers.
+const NAT_PMP_PORT: u16 = 5351;
+/// Number of requests sent to the gateway before giving up. The timeout doubles after each request.
+const NAT_PMP_ATTEMPTS: u32 = 4;
+/// Timeout for the first gateway request in milliseconds.
+const NAT_PMP_TIMEOUT_MS: u64 = 250;
+const NAT_PMP_OP_EXTERNAL_ADDRESS: u8 = 0;
+const NAT_PMP_OP_MAP_UDP: u8 = 1;
+const NAT_PMP_OP_MAP_TCP: u8 = 2;
+const PCP_VERSION: u8 = 2;
+const PCP_OP_MAP: u8 = 1;
+const PCP_PROTOCOL_TCP: u8 = 6;
+const PCP_PROTOCOL_UDP: u8 = 17;
+/// Result code for an unsupported protocol version, shared by NAT-PMP and PCP.
+const RESULT_UNSUPPORTED_VERSION: u16 = 1;
+
+/// Requested lifetime of port mappings in seconds.
+pub const NAT_MAPPING_LIFETIME: u32 = 3600;
 
 /// Socket address extension for rustc beta. To be replaces with now unstable API
 pub trait SocketAddrExt {
@@ -179,39 +200,324 @@ pub fn select_public_address(port: u16) -> SocketAddr {
 	SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), port))
 }
 
-pub fn map_external_address(local: &NodeEndpoint) -> Option<NodeEndpoint> {
-	if let SocketAddr::V4(ref local_addr) = local.address {
-		match search_gateway_from_timeout(local_addr.ip().clone(), Duration::new(5, 0)) {
-			Err(ref err) => debug!("Gateway search error: {}", err),
-			Ok(gateway) => {
-				match gateway.get_external_ip() {
-					Err(ref err) => {
-						debug!("IP request error: {}", err);
-					},
-					Ok(external_addr) => {
-						match gateway.add_any_port(PortMappingProtocol::TCP, SocketAddrV4::new(local_addr.ip().clone(), local_addr.port()), 0, "Parity Node/TCP") {
-							Err(ref err) => {
-								debug!("Port mapping error: {}", err);
-							},
-							Ok(tcp_port) => {
-								match gateway.add_any_port(PortMappingProtocol::UDP, SocketAddrV4::new(local_addr.ip().clone(), local.udp_port), 0, "Parity Node/UDP") {
-									Err(ref err) => {
-										debug!("Port mapping error: {}", err);
-									},
-									Ok(udp_port) => {
-										return Some(NodeEndpoint { address: SocketAddr::V4(SocketAddrV4::new(external_addr, tcp_port)), udp_port: udp_port });
-									},
-								}
-							},
-						}
-					},
-				}
-			},
+/// Protocol a port mapping was created with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NatProtocol {
+	/// UPnP Internet Gateway Device.
+	Upnp,
+	/// NAT Port Mapping Protocol.
+	NatPmp,
+	/// Port Control Protocol.
+	Pcp,
+}
+
+/// TCP and UDP port mappings on the gateway.
+#[derive(Debug, Clone)]
+pub struct NatMapping {
+	/// Protocol the mappings were created with.
+	pub protocol: NatProtocol,
+	/// External endpoint forwarded to the local one.
+	pub external: NodeEndpoint,
+	/// Lifetime of the mappings in seconds.
+	pub lifetime: u32,
+	/// Nonce identifying PCP mappings. Must be reused when renewing.
+	nonce: [u8; 12],
+}
+
+#[derive(Debug)]
+enum GatewayError {
+	Io(io::Error),
+	/// The gateway does not support the protocol version.
+	UnsupportedVersion,
+	/// The gateway refused the request with the given result code.
+	Refused(u16),
+	/// The response could not be parsed.
+	Malformed,
+}
+
+impl From<io::Error> for GatewayError {
+	fn from(err: io::Error) -> GatewayError {
+		GatewayError::Io(err)
+	}
+}
+
+/// Map the local endpoint on the gateway. When `previous` is given the mapping is renewed,
+/// keeping the same external ports if the gateway allows it.
+pub fn map_external_address(local: &NodeEndpoint, method: NatMethod, previous: Option<&NatMapping>) -> Option<NatMapping> {
+	let local_addr = match local.address {
+		SocketAddr::V4(ref addr) => addr.clone(),
+		SocketAddr::V6(_) => return None,
+	};
+	match method {
+		NatMethod::Upnp => map_upnp(&local_addr, local.udp_port, previous),
+		NatMethod::NatPmp => map_nat_pmp(&local_addr, local.udp_port, previous),
+		NatMethod::Any => match previous.map(|m| m.protocol) {
+			Some(NatProtocol::NatPmp) | Some(NatProtocol::Pcp) => map_nat_pmp(&local_addr, local.udp_port, previous)
+				.or_else(|| map_upnp(&local_addr, local.udp_port, previous)),
+			_ => map_upnp(&local_addr, local.udp_port, previous)
+				.or_else(|| map_nat_pmp(&local_addr, local.udp_port, previous)),
+		},
+	}
+}
+
+fn map_upnp(local: &SocketAddrV4, udp_port: u16, previous: Option<&NatMapping>) -> Option<NatMapping> {
+	let gateway = match search_gateway_from_timeout(local.ip().clone(), Duration::new(5, 0)) {
+		Ok(gateway) => gateway,
+		Err(ref err) => {
+			debug!("Gateway search error: {}", err);
+			return None;
+		},
+	};
+	let external_addr = match gateway.get_external_ip() {
+		Ok(addr) => addr,
+		Err(ref err) => {
+			debug!("IP request error: {}", err);
+			return None;
+		},
+	};
+	let previous = previous.and_then(|m| if m.protocol == NatProtocol::Upnp { Some(&m.external) } else { None });
+	let tcp_local = SocketAddrV4::new(local.ip().clone(), local.port());
+	let udp_local = SocketAddrV4::new(local.ip().clone(), udp_port);
+	let tcp_port = match upnp_add_port(&gateway, PortMappingProtocol::TCP, previous.map(|e| e.address.port()), tcp_local, "Parity Node/TCP") {
+		Some(port) => port,
+		None => return None,
+	};
+	upnp_add_port(&gateway, PortMappingProtocol::UDP, previous.map(|e| e.udp_port), udp_local, "Parity Node/UDP").map(|udp_port| NatMapping {
+		protocol: NatProtocol::Upnp,
+		external: NodeEndpoint { address: SocketAddr::V4(SocketAddrV4::new(external_addr, tcp_port)), udp_port: udp_port },
+		lifetime: NAT_MAPPING_LIFETIME,
+		nonce: [0u8; 12],
+	})
+}
+
+/// Add a port mapping, preferring the given external port.
+/// Gateways that only support permanent leases (UPnP error 725) are asked for one instead.
+fn upnp_add_port(gateway: &Gateway, protocol: PortMappingProtocol, external: Option<u16>, local: SocketAddrV4, description: &str) -> Option<u16> {
+	if let Some(port) = external {
+		let result = match gateway.add_port(protocol, port, local, NAT_MAPPING_LIFETIME, description) {
+			Err(AddPortError::OnlyPermanentLeasesSupported) => gateway.add_port(protocol, port, local, 0, description),
+			result => result,
+		};
+		match result {
+			Ok(()) => return Some(port),
+			Err(ref err) => debug!("Port mapping renewal error: {}", err),
 		}
 	}
+	let result = match gateway.add_any_port(protocol, local, NAT_MAPPING_LIFETIME, description) {
+		Err(AddAnyPortError::OnlyPermanentLeasesSupported) => gateway.add_any_port(protocol, local, 0, description),
+		result => result,
+	};
+	match result {
+		Ok(port) => Some(port),
+		Err(ref err) => {
+			debug!("Port mapping error: {}", err);
+			None
+		},
+	}
+}
+
+fn map_nat_pmp(local: &SocketAddrV4, udp_port: u16, previous: Option<&NatMapping>) -> Option<NatMapping> {
+	let gateway = default_gateway().unwrap_or_else(|| {
+		// Most home routers use the first address of the subnet
+		let octets = local.ip().octets();
+		Ipv4Addr::new(octets[0], octets[1], octets[2], 1)
+	});
+	match map_gateway(local, udp_port, SocketAddr::V4(SocketAddrV4::new(gateway, NAT_PMP_PORT)), previous) {
+		Ok(mapping) => Some(mapping),
+		Err(e) => {
+			debug!("NAT-PMP mapping error: {:?}", e);
+			None
+		},
+	}
+}
+
+/// Map ports with NAT-PMP, falling back to PCP if the gateway only supports the latter.
+fn map_gateway(local: &SocketAddrV4, udp_port: u16, gateway: SocketAddr, previous: Option<&NatMapping>) -> Result<NatMapping, GatewayError> {
+	let socket = try!(UdpSocket::bind(SocketAddrV4::new(local.ip().clone(), 0)));
+	try!(socket.connect(gateway));
+	let previous = previous.and_then(|m| if m.protocol != NatProtocol::Upnp { Some(m) } else { None });
+	let (tcp_hint, udp_hint) = previous.map_or((local.port(), udp_port), |m| (m.external.address.port(), m.external.udp_port));
+	if previous.map_or(true, |m| m.protocol == NatProtocol::NatPmp) {
+		match nat_pmp_mapping(&socket, local.port(), tcp_hint, udp_port, udp_hint) {
+			Err(GatewayError::UnsupportedVersion) => debug!("Gateway does not support NAT-PMP, trying PCP"),
+			result => return result,
+		}
+	}
+	let nonce = previous.map_or_else(::rand::random, |m| m.nonce);
+	pcp_mapping(&socket, local, tcp_hint, udp_port, udp_hint, nonce)
+}
+
+/// Send a request to the gateway and wait for the response, retrying on timeout.
+fn gateway_request(socket: &UdpSocket, request: &[u8], response: &mut [u8]) -> Result<usize, GatewayError> {
+	let mut timeout = NAT_PMP_TIMEOUT_MS;
+	for _ in 0..NAT_PMP_ATTEMPTS {
+		try!(socket.send(request));
+		try!(socket.set_read_timeout(Some(Duration::from_millis(timeout))));
+		match socket.recv(response) {
+			Ok(len) => return Ok(len),
+			Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => timeout *= 2,
+			Err(e) => return Err(e.into()),
+		}
+	}
+	Err(GatewayError::Io(io::Error::new(io::ErrorKind::TimedOut, "No response from gateway")))
+}
+
+fn nat_pmp_mapping(socket: &UdpSocket, tcp_port: u16, tcp_hint: u16, udp_port: u16, udp_hint: u16) -> Result<NatMapping, GatewayError> {
+	let mut response = [0u8; 16];
+	let len = try!(gateway_request(socket, &[0, NAT_PMP_OP_EXTERNAL_ADDRESS], &mut response));
+	let external_addr = try!(parse_nat_pmp_address_response(&response[..len]));
+	let len = try!(gateway_request(socket, &nat_pmp_map_request(NAT_PMP_OP_MAP_TCP, tcp_port, tcp_hint, NAT_MAPPING_LIFETIME), &mut response));
+	let (external_tcp, tcp_lifetime) = try!(parse_nat_pmp_map_response(&response[..len], NAT_PMP_OP_MAP_TCP));
+	let len = try!(gateway_request(socket, &nat_pmp_map_request(NAT_PMP_OP_MAP_UDP, udp_port, udp_hint, NAT_MAPPING_LIFETIME), &mut response));
+	let (external_udp, udp_lifetime) = try!(parse_nat_pmp_map_response(&response[..len], NAT_PMP_OP_MAP_UDP));
+	Ok(NatMapping {
+		protocol: NatProtocol::NatPmp,
+		external: NodeEndpoint { address: SocketAddr::V4(SocketAddrV4::new(external_addr, external_tcp)), udp_port: external_udp },
+		lifetime: min(tcp_lifetime, udp_lifetime),
+		nonce: [0u8; 12],
+	})
+}
+
+fn pcp_mapping(socket: &UdpSocket, local: &SocketAddrV4, tcp_hint: u16, udp_port: u16, udp_hint: u16, nonce: [u8; 12]) -> Result<NatMapping, GatewayError> {
+	let mut response = [0u8; 1100];
+	let len = try!(gateway_request(socket, &pcp_map_request(local.ip(), &nonce, PCP_PROTOCOL_TCP, local.port(), tcp_hint, NAT_MAPPING_LIFETIME), &mut response));
+	let (external_tcp, tcp_lifetime) = try!(parse_pcp_map_response(&response[..len], &nonce, PCP_PROTOCOL_TCP));
+	let len = try!(gateway_request(socket, &pcp_map_request(local.ip(), &nonce, PCP_PROTOCOL_UDP, udp_port, udp_hint, NAT_MAPPING_LIFETIME), &mut response));
+	let (external_udp, udp_lifetime) = try!(parse_pcp_map_response(&response[..len], &nonce, PCP_PROTOCOL_UDP));
+	Ok(NatMapping {
+		protocol: NatProtocol::Pcp,
+		external: NodeEndpoint { address: SocketAddr::V4(external_tcp), udp_port: external_udp.port() },
+		lifetime: min(tcp_lifetime, udp_lifetime),
+		nonce: nonce,
+	})
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+	(bytes[0] as u16) << 8 | bytes[1] as u16
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+	(bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+	out.push((value >> 8) as u8);
+	out.push(value as u8);
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+	push_u16(out, (value >> 16) as u16);
+	push_u16(out, value as u16);
+}
+
+/// Check the header of a NAT-PMP response and return the result code.
+fn check_nat_pmp_response(response: &[u8], op: u8, len: usize) -> Result<(), GatewayError> {
+	// PCP servers answer NAT-PMP requests with their own version
+	if response.len() >= 4 && response[0] != 0 {
+		return Err(GatewayError::UnsupportedVersion);
+	}
+	if response.len() < 4 || response[1] != 0x80 | op {
+		return Err(GatewayError::Malformed);
+	}
+	match read_u16(&response[2..]) {
+		0 if response.len() >= len => Ok(()),
+		0 => Err(GatewayError::Malformed),
+		RESULT_UNSUPPORTED_VERSION => Err(GatewayError::UnsupportedVersion),
+		code => Err(GatewayError::Refused(code)),
+	}
+}
+
+fn parse_nat_pmp_address_response(response: &[u8]) -> Result<Ipv4Addr, GatewayError> {
+	try!(check_nat_pmp_response(response, NAT_PMP_OP_EXTERNAL_ADDRESS, 12));
+	Ok(Ipv4Addr::new(response[8], response[9], response[10], response[11]))
+}
+
+fn nat_pmp_map_request(op: u8, internal: u16, external: u16, lifetime: u32) -> Vec<u8> {
+	let mut request = vec![0, op, 0, 0];
+	push_u16(&mut request, internal);
+	push_u16(&mut request, external);
+	push_u32(&mut request, lifetime);
+	request
+}
+
+/// Returns mapped external port and lifetime.
+fn parse_nat_pmp_map_response(response: &[u8], op: u8) -> Result<(u16, u32), GatewayError> {
+	try!(check_nat_pmp_response(response, op, 16));
+	Ok((read_u16(&response[10..]), read_u32(&response[12..])))
+}
+
+fn ipv4_mapped(ip: &Ipv4Addr) -> [u8; 16] {
+	let o = ip.octets();
+	[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, o[0], o[1], o[2], o[3]]
+}
+
+fn pcp_map_request(client: &Ipv4Addr, nonce: &[u8; 12], protocol: u8, internal: u16, external: u16, lifetime: u32) -> Vec<u8> {
+	let mut request = vec![PCP_VERSION, PCP_OP_MAP, 0, 0];
+	push_u32(&mut request, lifetime);
+	request.extend_from_slice(&ipv4_mapped(client));
+	request.extend_from_slice(nonce);
+	request.extend_from_slice(&[protocol, 0, 0, 0]);
+	push_u16(&mut request, internal);
+	push_u16(&mut request, external);
+	request.extend_from_slice(&ipv4_mapped(&Ipv4Addr::new(0, 0, 0, 0)));
+	request
+}
+
+/// Returns assigned external address and lifetime.
+fn parse_pcp_map_response(response: &[u8], nonce: &[u8; 12], protocol: u8) -> Result<(SocketAddrV4, u32), GatewayError> {
+	if response.len() < 4 {
+		return Err(GatewayError::Malformed);
+	}
+	if response[0] != PCP_VERSION {
+		return Err(GatewayError::UnsupportedVersion);
+	}
+	if response[1] != 0x80 | PCP_OP_MAP {
+		return Err(GatewayError::Malformed);
+	}
+	match response[3] as u16 {
+		0 => {},
+		RESULT_UNSUPPORTED_VERSION => return Err(GatewayError::UnsupportedVersion),
+		code => return Err(GatewayError::Refused(code)),
+	}
+	if response.len() < 60 || &response[24..36] != &nonce[..] || response[36] != protocol {
+		return Err(GatewayError::Malformed);
+	}
+	let ip = &response[44..60];
+	Ok((SocketAddrV4::new(Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]), read_u16(&response[42..])), read_u32(&response[4..])))
+}
+
+/// Default IPv4 gateway from the kernel routing table.
+#[cfg(target_os = "linux")]
+fn default_gateway() -> Option<Ipv4Addr> {
+	use std::fs::File;
+	use std::io::Read;
+
+	let mut routes = String::new();
+	match File::open("/proc/net/route").and_then(|mut f| f.read_to_string(&mut routes)) {
+		Ok(_) => parse_default_route(&routes),
+		Err(_) => None,
+	}
+}
+
+#[cfg(not(target_os = "linux"))]
+fn default_gateway() -> Option<Ipv4Addr> {
 	None
 }
 
+/// Find the default route in the `/proc/net/route` format.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_default_route(routes: &str) -> Option<Ipv4Addr> {
+	routes.lines().skip(1).filter_map(|line| {
+		let fields: Vec<&str> = line.split_whitespace().collect();
+		if fields.len() > 2 && fields[1] == "00000000" {
+			// Addresses are printed as native-endian words holding network order bytes
+			u32::from_str_radix(fields[2], 16).ok().map(|gateway| Ipv4Addr::from(u32::from_be(gateway)))
+		} else {
+			None
+		}
+	}).next()
+}
+
 #[test]
 fn can_select_public_address() {
 	let pub_address = select_public_address(40477);
@@ -222,7 +528,125 @@ fn can_select_public_address() {
 #[test]
 fn can_map_external_address_or_fail() {
 	let pub_address = select_public_address(40478);
-	let _ = map_external_address(&NodeEndpoint { address: pub_address, udp_port: 40478 });
+	let _ = map_external_address(&NodeEndpoint { address: pub_address, udp_port: 40478 }, NatMethod::Any, None);
+}
+
+#[test]
+fn parses_default_route() {
+	let routes = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+		eth0\t0001A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n\
+		eth0\t00000000\t0101A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n";
+	let gateway = parse_default_route(routes);
+	if cfg!(target_endian = "little") {
+		assert_eq!(gateway, Some(Ipv4Addr::new(192, 168, 1, 1)));
+	}
+	assert_eq!(parse_default_route("Iface\tDestination\tGateway\n"), None);
+}
+
+#[test]
+fn encodes_nat_pmp_packets() {
+	assert_eq!(nat_pmp_map_request(NAT_PMP_OP_MAP_TCP, 30303, 30304, 3600), vec![0, 2, 0, 0, 0x76, 0x5f, 0x76, 0x60, 0, 0, 0x0e, 0x10]);
+	let response = [0, 0x82, 0, 0, 0, 0, 0, 1, 0x76, 0x5f, 0x76, 0x61, 0, 0, 0x07, 0x08];
+	assert_eq!(parse_nat_pmp_map_response(&response, NAT_PMP_OP_MAP_TCP).unwrap(), (30305, 1800));
+	match parse_nat_pmp_map_response(&response, NAT_PMP_OP_MAP_UDP) {
+		Err(GatewayError::Malformed) => {},
+		other => panic!("Unexpected result: {:?}", other),
+	}
+	match parse_nat_pmp_map_response(&[0, 0x82, 0, 3, 0, 0, 0, 1], NAT_PMP_OP_MAP_TCP) {
+		Err(GatewayError::Refused(3)) => {},
+		other => panic!("Unexpected result: {:?}", other),
+	}
+	let address = [0, 0x80, 0, 0, 0, 0, 0, 1, 203, 0, 113, 7];
+	assert_eq!(parse_nat_pmp_address_response(&address).unwrap(), Ipv4Addr::new(203, 0, 113, 7));
+}
+
+#[test]
+fn encodes_pcp_packets() {
+	let nonce = [7u8; 12];
+	let request = pcp_map_request(&Ipv4Addr::new(192, 168, 1, 10), &nonce, PCP_PROTOCOL_UDP, 30303, 30303, 3600);
+	assert_eq!(request.len(), 60);
+	assert_eq!(&request[..8], &[2, 1, 0, 0, 0, 0, 0x0e, 0x10]);
+	assert_eq!(&request[18..24], &[0xff, 0xff, 192, 168, 1, 10]);
+	assert_eq!(&request[24..36], &nonce);
+	assert_eq!(&request[36..44], &[17, 0, 0, 0, 0x76, 0x5f, 0x76, 0x5f]);
+
+	let mut response = request.clone();
+	response[1] = 0x81;
+	response[42] = 0x76;
+	response[43] = 0x60;
+	response[44..60].copy_from_slice(&ipv4_mapped(&Ipv4Addr::new(203, 0, 113, 7)));
+	assert_eq!(parse_pcp_map_response(&response, &nonce, PCP_PROTOCOL_UDP).unwrap(), (SocketAddrV4::new(Ipv4Addr::new(203, 0, 113, 7), 30304), 3600));
+	assert!(parse_pcp_map_response(&response, &[8u8; 12], PCP_PROTOCOL_UDP).is_err());
+	response[3] = 8;
+	match parse_pcp_map_response(&response, &nonce, PCP_PROTOCOL_UDP) {
+		Err(GatewayError::Refused(8)) => {},
+		other => panic!("Unexpected result: {:?}", other),
+	}
+}
+
+/// Gateway answering NAT-PMP or, when `pcp` is set, only PCP requests on a loopback port.
+#[cfg(test)]
+fn test_gateway(pcp: bool, requests: usize) -> SocketAddr {
+	use std::thread;
+
+	let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+	let address = socket.local_addr().unwrap();
+	thread::spawn(move || {
+		let mut buf = [0u8; 1100];
+		for _ in 0..requests {
+			let (len, from) = socket.recv_from(&mut buf).unwrap();
+			let request = &buf[..len];
+			let response = match (request[0], pcp) {
+				(0, false) if request[1] == NAT_PMP_OP_EXTERNAL_ADDRESS => vec![0, 0x80, 0, 0, 0, 0, 0, 1, 203, 0, 113, 7],
+				(0, false) => {
+					let mut response = vec![0, 0x80 | request[1], 0, 0, 0, 0, 0, 1, request[4], request[5]];
+					push_u16(&mut response, read_u16(&request[6..]) + 1000);
+					response.extend_from_slice(&request[8..12]);
+					response
+				},
+				(0, true) => vec![PCP_VERSION, 0x80 | request[1], 0, RESULT_UNSUPPORTED_VERSION as u8],
+				_ => {
+					let mut response = request.to_vec();
+					response[1] = 0x80 | PCP_OP_MAP;
+					let port = read_u16(&request[42..]) + 2000;
+					response[42] = (port >> 8) as u8;
+					response[43] = port as u8;
+					response[44..60].copy_from_slice(&ipv4_mapped(&Ipv4Addr::new(203, 0, 113, 8)));
+					response
+				},
+			};
+			socket.send_to(&response, from).unwrap();
+		}
+	});
+	address
+}
+
+#[test]
+fn maps_ports_with_nat_pmp() {
+	let gateway = test_gateway(false, 6);
+	let local = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 30303);
+	let mapping = map_gateway(&local, 30304, gateway, None).unwrap();
+	assert_eq!(mapping.protocol, NatProtocol::NatPmp);
+	assert_eq!(mapping.external, NodeEndpoint { address: "203.0.113.7:31303".parse().unwrap(), udp_port: 31304 });
+	assert_eq!(mapping.lifetime, NAT_MAPPING_LIFETIME);
+
+	// renewal asks for the same external ports
+	let renewed = map_gateway(&local, 30304, gateway, Some(&mapping)).unwrap();
+	assert_eq!(renewed.external, NodeEndpoint { address: "203.0.113.7:32303".parse().unwrap(), udp_port: 32304 });
+}
+
+#[test]
+fn falls_back_to_pcp() {
+	let gateway = test_gateway(true, 5);
+	let local = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 30303);
+	let mapping = map_gateway(&local, 30304, gateway, None).unwrap();
+	assert_eq!(mapping.protocol, NatProtocol::Pcp);
+	assert_eq!(mapping.external, NodeEndpoint { address: "203.0.113.8:32303".parse().unwrap(), udp_port: 32304 });
+
+	// renewal goes straight to PCP with the same nonce
+	let renewed = map_gateway(&local, 30304, gateway, Some(&mapping)).unwrap();
+	assert_eq!(renewed.protocol, NatProtocol::Pcp);
+	assert_eq!(renewed.nonce, mapping.nonce);
 }
 
 #[test]
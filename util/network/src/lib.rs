@@ -82,26 +82,71 @@ mod connection;
 mod handshake;
 mod session;
 mod discovery;
+mod node_record;
 mod service;
 mod error;
 mod node_table;
 mod stats;
+mod bandwidth;
+mod reputation;
+mod allowlist;
+mod capture;
 mod ip_utils;
 
 #[cfg(test)]
 mod tests;
 
-pub use host::{PeerId, PacketId, ProtocolId, NetworkContext, NetworkIoMessage, NetworkConfiguration};
+pub use host::{PeerId, PacketId, ProtocolId, NetworkIoMessage, NetworkConfiguration, NatStatus};
 pub use service::NetworkService;
 pub use error::NetworkError;
-pub use stats::NetworkStats;
+pub use stats::{NetworkStats, SessionStats, TrafficCounters};
 pub use session::SessionInfo;
+pub use reputation::PeerEvent;
+pub use node_table::BannedNode;
+pub use ip_utils::NatProtocol;
+pub use capture::{CapturedPacket, PacketDirection, PacketRecorder, read_capture};
 
-use io::TimerToken;
+use io::{TimerToken, IoChannel};
 pub use node_table::is_valid_node_url;
 
 const PROTOCOL_VERSION: u32 = 4;
 
+/// IO access point. This is passed to all IO handlers and provides an interface to the IO subsystem.
+pub trait NetworkContext {
+	/// Send a packet over the network to another peer.
+	fn send(&self, peer: PeerId, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError>;
+	/// Send a packet over the network to another peer using specified protocol.
+	fn send_protocol(&self, protocol: ProtocolId, peer: PeerId, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError>;
+	/// Respond to a current network message. Panics if no there is no packet in the context. If the session is expired returns nothing.
+	fn respond(&self, packet_id: PacketId, data: Vec<u8>) -> Result<(), NetworkError>;
+	/// Get an IoChannel.
+	fn io_channel(&self) -> IoChannel<NetworkIoMessage>;
+	/// Disconnect a peer and prevent it from connecting again.
+	fn disable_peer(&self, peer: PeerId);
+	/// Disconnect peer. Reconnect can be attempted later.
+	fn disconnect_peer(&self, peer: PeerId);
+	/// Report peer behaviour. Peers with low reputation get disconnected and banned.
+	fn report_peer(&self, peer: PeerId, event: PeerEvent);
+	/// Check if the session is still active.
+	fn is_expired(&self) -> bool;
+	/// Register a new IO timer. 'IoHandler::timeout' will be called with the token.
+	fn register_timer(&self, token: TimerToken, ms: u64) -> Result<(), NetworkError>;
+	/// Returns peer identification string
+	fn peer_client_version(&self, peer: PeerId) -> String;
+	/// Returns information on p2p session
+	fn session_info(&self, peer: PeerId) -> Option<SessionInfo>;
+	/// Returns true if the peer is one of the reserved nodes.
+	fn is_reserved_peer(&self, peer: PeerId) -> bool;
+	/// Returns max version for a given protocol.
+	fn protocol_version(&self, protocol: ProtocolId, peer: PeerId) -> Option<u8>;
+	/// Returns this object's subprotocol name.
+	fn subprotocol_name(&self) -> ProtocolId;
+	/// Current time in seconds. Handlers time out requests against it, so that they can be driven by a simulated clock.
+	fn now(&self) -> f64 {
+		::time::precise_time_s()
+	}
+}
+
 /// Network IO protocol handler. This needs to be implemented for each new subprotocol.
 /// All the handler function are called from within IO event loop.
 /// `Message` is the type for message data.
@@ -138,6 +183,17 @@ impl NonReservedPeerMode {
 	}
 }
 
+/// NAT traversal methods.
+#[derive(Clone, Debug, PartialEq, Eq, Copy)]
+pub enum NatMethod {
+	/// Try UPnP first, then NAT-PMP and PCP. This is the default.
+	Any,
+	/// UPnP only.
+	Upnp,
+	/// NAT-PMP, falling back to PCP if the gateway only supports the latter.
+	NatPmp,
+}
+
 /// IP fiter
 #[derive(Clone, Debug, PartialEq, Eq, Copy)]
 pub enum AllowIP {
@@ -0,0 +1,222 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Signed node records advertised over discovery
+
+use util::hash::*;
+use util::sha3::Hashable;
+use util::bytes::Bytes;
+use rlp::*;
+use ethkey::{Secret, sign, recover};
+use node_table::{NodeId, NodeEndpoint};
+use discovery::NodeEntry;
+use error::NetworkError;
+use ProtocolId;
+
+/// Topic identifier. Nodes advertise one topic per supported subprotocol on their chain.
+pub type Topic = H256;
+
+/// Returns the topic for a subprotocol running on a given chain.
+pub fn topic(protocol: &ProtocolId, chain_id: u64) -> Topic {
+	let mut rlp = RlpStream::new_list(2);
+	rlp.append(&&protocol[..]);
+	rlp.append(&chain_id);
+	rlp.out().sha3()
+}
+
+/// Subprotocol advertised in a node record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordCapability {
+	/// Protocol id.
+	pub protocol: ProtocolId,
+	/// Protocol version.
+	pub version: u8,
+}
+
+/// Versioned node description signed with the node key.
+#[derive(Debug, Clone)]
+pub struct NodeRecord {
+	/// Sequence number. Records with a higher number replace older ones.
+	pub seq: u64,
+	/// Node public key.
+	pub id: NodeId,
+	/// Node endpoint.
+	pub endpoint: NodeEndpoint,
+	/// Supported subprotocols.
+	pub capabilities: Vec<RecordCapability>,
+	/// Chain the node is following, if advertised.
+	pub chain_id: Option<u64>,
+	signature: H520,
+}
+
+impl NodeRecord {
+	/// Create and sign a new record.
+	pub fn new(secret: &Secret, id: NodeId, seq: u64, endpoint: NodeEndpoint, capabilities: Vec<RecordCapability>, chain_id: Option<u64>) -> Result<NodeRecord, NetworkError> {
+		let mut record = NodeRecord {
+			seq: seq,
+			id: id,
+			endpoint: endpoint,
+			capabilities: capabilities,
+			chain_id: chain_id,
+			signature: H520::new(),
+		};
+		let signature = try!(sign(secret, &record.content().sha3()));
+		record.signature = H520::from_slice(&*signature);
+		Ok(record)
+	}
+
+	/// Decode a record and check its signature.
+	pub fn from_rlp(rlp: &UntrustedRlp) -> Result<NodeRecord, NetworkError> {
+		let mut capabilities = Vec::new();
+		for c in try!(rlp.at(4)).iter() {
+			let p: Vec<u8> = try!(c.val_at(0));
+			if p.len() != 3 {
+				return Err(NetworkError::BadProtocol);
+			}
+			let mut protocol: ProtocolId = [0u8; 3];
+			protocol.clone_from_slice(&p);
+			capabilities.push(RecordCapability { protocol: protocol, version: try!(c.val_at(1)) });
+		}
+		let chain = try!(rlp.at(5));
+		let chain_id = match chain.item_count() {
+			0 => None,
+			_ => Some(try!(chain.val_at(0))),
+		};
+		let record = NodeRecord {
+			signature: try!(rlp.val_at(0)),
+			seq: try!(rlp.val_at(1)),
+			id: try!(rlp.val_at(2)),
+			endpoint: try!(NodeEndpoint::from_rlp(&try!(rlp.at(3)))),
+			capabilities: capabilities,
+			chain_id: chain_id,
+		};
+		try!(record.verify());
+		Ok(record)
+	}
+
+	/// Append the record to the stream as a list.
+	pub fn to_rlp(&self, rlp: &mut RlpStream) {
+		rlp.begin_list(6);
+		rlp.append(&self.signature);
+		self.append_fields(rlp);
+	}
+
+	/// Check that the record is signed by the node it describes.
+	pub fn verify(&self) -> Result<(), NetworkError> {
+		let signer = try!(recover(&self.signature.clone().into(), &self.content().sha3()));
+		if signer != self.id {
+			return Err(NetworkError::BadProtocol);
+		}
+		Ok(())
+	}
+
+	/// Topics advertised by this record.
+	pub fn topics(&self) -> Vec<Topic> {
+		let chain_id = match self.chain_id {
+			Some(id) => id,
+			None => return Vec::new(),
+		};
+		let mut topics: Vec<Topic> = self.capabilities.iter().map(|c| topic(&c.protocol, chain_id)).collect();
+		topics.sort();
+		topics.dedup();
+		topics
+	}
+
+	/// Check if the record advertises the given topic.
+	pub fn has_topic(&self, t: &Topic) -> bool {
+		self.topics().contains(t)
+	}
+
+	/// Discovery table entry for this record.
+	pub fn entry(&self) -> NodeEntry {
+		NodeEntry { id: self.id.clone(), endpoint: self.endpoint.clone() }
+	}
+
+	fn append_fields(&self, rlp: &mut RlpStream) {
+		rlp.append(&self.seq);
+		rlp.append(&self.id);
+		self.endpoint.to_rlp_list(rlp);
+		rlp.begin_list(self.capabilities.len());
+		for c in &self.capabilities {
+			rlp.begin_list(2);
+			rlp.append(&&c.protocol[..]);
+			rlp.append(&c.version);
+		}
+		match self.chain_id {
+			Some(ref id) => { rlp.begin_list(1).append(id); },
+			None => { rlp.begin_list(0); },
+		}
+	}
+
+	fn content(&self) -> Bytes {
+		let mut rlp = RlpStream::new_list(5);
+		self.append_fields(&mut rlp);
+		rlp.out()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::str::FromStr;
+	use rlp::*;
+	use ethkey::{Random, Generator};
+	use node_table::NodeEndpoint;
+
+	fn record(chain_id: Option<u64>) -> NodeRecord {
+		let key = Random.generate().unwrap();
+		let endpoint = NodeEndpoint::from_str("127.0.0.1:30303").unwrap();
+		let caps = vec![
+			RecordCapability { protocol: *b"eth", version: 63 },
+			RecordCapability { protocol: *b"par", version: 1 },
+		];
+		NodeRecord::new(key.secret(), key.public().clone(), 1, endpoint, caps, chain_id).unwrap()
+	}
+
+	#[test]
+	fn record_roundtrip() {
+		let r = record(Some(1));
+		let mut s = RlpStream::new();
+		r.to_rlp(&mut s);
+		let bytes = s.out();
+		let decoded = NodeRecord::from_rlp(&UntrustedRlp::new(&bytes)).unwrap();
+		assert_eq!(decoded.seq, 1);
+		assert_eq!(decoded.id, r.id);
+		assert_eq!(decoded.capabilities, r.capabilities);
+		assert_eq!(decoded.chain_id, Some(1));
+	}
+
+	#[test]
+	fn tampered_record_is_rejected() {
+		let mut r = record(Some(1));
+		r.chain_id = Some(2);
+		assert!(r.verify().is_err());
+		let mut s = RlpStream::new();
+		r.to_rlp(&mut s);
+		let bytes = s.out();
+		assert!(NodeRecord::from_rlp(&UntrustedRlp::new(&bytes)).is_err());
+	}
+
+	#[test]
+	fn topics_depend_on_chain() {
+		let r = record(Some(1));
+		assert_eq!(r.topics().len(), 2);
+		assert!(r.has_topic(&topic(b"par", 1)));
+		assert!(!r.has_topic(&topic(b"par", 2)));
+		assert!(!r.has_topic(&topic(b"les", 1)));
+		assert!(record(None).topics().is_empty());
+	}
+}
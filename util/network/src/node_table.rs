@@ -15,6 +15,7 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::mem;
+use std::cmp::Ordering;
 use std::slice::from_raw_parts;
 use std::net::{SocketAddr, ToSocketAddrs, SocketAddrV4, SocketAddrV6, Ipv4Addr, Ipv6Addr};
 use std::hash::{Hash, Hasher};
@@ -33,12 +34,13 @@ use error::NetworkError;
 use AllowIP;
 use discovery::{TableUpdates, NodeEntry};
 use ip_utils::*;
+use reputation::{self, Reputation, PeerEvent};
 pub use rustc_serialize::json::Json;
 
 /// Node public key
 pub type NodeId = H512;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 /// Node address info
 pub struct NodeEndpoint {
 	/// IP(V4 or V6) address
@@ -136,6 +138,7 @@ pub struct Node {
 	pub peer_type: PeerType,
 	pub failures: u32,
 	pub last_attempted: Option<Tm>,
+	pub reputation: Reputation,
 }
 
 impl Node {
@@ -146,10 +149,26 @@ impl Node {
 			peer_type: PeerType::Optional,
 			failures: 0,
 			last_attempted: None,
+			reputation: Reputation::default(),
 		}
 	}
 }
 
+/// Information on a banned node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BannedNode {
+	/// Node id
+	pub id: NodeId,
+	/// Node address
+	pub url: String,
+	/// Reputation score
+	pub score: i32,
+	/// Number of times the node has been banned
+	pub bans: u32,
+	/// Unix time when the ban expires
+	pub banned_until: u64,
+}
+
 impl Display for Node {
 	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
 		if self.endpoint.udp_port != self.endpoint.address.port() {
@@ -177,6 +196,7 @@ impl FromStr for Node {
 			peer_type: PeerType::Optional,
 			last_attempted: None,
 			failures: 0,
+			reputation: Reputation::default(),
 		})
 	}
 }
@@ -212,17 +232,31 @@ impl NodeTable {
 
 	/// Add a node to table
 	pub fn add_node(&mut self, mut node: Node) {
-		// preserve failure counter
-		let failures = self.nodes.get(&node.id).map_or(0, |n| n.failures);
-		node.failures = failures;
+		// preserve failure counter and reputation
+		if let Some(n) = self.nodes.get(&node.id) {
+			node.failures = n.failures;
+			node.reputation = n.reputation.clone();
+		}
 		self.nodes.insert(node.id.clone(), node);
 	}
 
-	/// Returns node ids sorted by number of failures
+	/// Returns nodes sorted by number of failures, then by reputation
+	fn ordered_entries(&self) -> Vec<&Node> {
+		let mut refs: Vec<&Node> = self.nodes.values().collect();
+		refs.sort_by(|a, b| match a.failures.cmp(&b.failures) {
+			Ordering::Equal => b.reputation.score.cmp(&a.reputation.score),
+			o => o,
+		});
+		refs
+	}
+
+	/// Returns ids of nodes that are not useless or banned sorted by number of failures
 	pub fn nodes(&self, filter: AllowIP) -> Vec<NodeId> {
-		let mut refs: Vec<&Node> = self.nodes.values().filter(|n| !self.useless_nodes.contains(&n.id) && n.endpoint.is_allowed(filter)).collect();
-		refs.sort_by(|a, b| a.failures.cmp(&b.failures));
-		refs.iter().map(|n| n.id.clone()).collect()
+		let now = reputation::now();
+		self.ordered_entries().into_iter()
+			.filter(|n| !self.useless_nodes.contains(&n.id) && !n.reputation.is_banned(now) && n.endpoint.is_allowed(filter))
+			.map(|n| n.id.clone())
+			.collect()
 	}
 
 	/// Unordered list of all entries
@@ -236,14 +270,15 @@ impl NodeTable {
 		self.nodes.get_mut(id)
 	}
 
-	/// Apply table changes coming from discovery
+	/// Apply table changes coming from discovery. Banned nodes are kept so that the ban outlives
+	/// the node dropping out of discovery.
 	pub fn update(&mut self, mut update: TableUpdates, reserved: &HashSet<NodeId>) {
 		for (_, node) in update.added.drain() {
 			let mut entry = self.nodes.entry(node.id.clone()).or_insert_with(|| Node::new(node.id.clone(), node.endpoint.clone()));
 			entry.endpoint = node.endpoint;
 		}
 		for r in update.removed {
-			if !reserved.contains(&r) {
+			if !reserved.contains(&r) && !self.is_banned(&r) {
 				self.nodes.remove(&r);
 			}
 		}
@@ -256,6 +291,55 @@ impl NodeTable {
 		}
 	}
 
+	/// Update node reputation. Returns true if the node got banned as a result.
+	pub fn note_event(&mut self, id: &NodeId, event: PeerEvent) -> bool {
+		match self.nodes.get_mut(id) {
+			Some(node) => {
+				let banned = node.reputation.note(event, reputation::now());
+				if banned {
+					debug!(target: "network", "Banned node {} until {:?}", id, node.reputation.banned_until);
+				}
+				banned
+			},
+			None => false,
+		}
+	}
+
+	/// Check if the node is currently banned.
+	pub fn is_banned(&self, id: &NodeId) -> bool {
+		self.nodes.get(id).map_or(false, |n| n.reputation.is_banned(reputation::now()))
+	}
+
+	/// Returns currently banned nodes.
+	pub fn banned_nodes(&self) -> Vec<BannedNode> {
+		let now = reputation::now();
+		self.ordered_entries().into_iter()
+			.filter(|n| n.reputation.is_banned(now))
+			.map(|n| BannedNode {
+				id: n.id.clone(),
+				url: format!("{}", n),
+				score: n.reputation.score,
+				bans: n.reputation.bans,
+				banned_until: n.reputation.banned_until.unwrap_or(0),
+			})
+			.collect()
+	}
+
+	/// Lift the ban for a node. Returns false if the node is not banned.
+	pub fn unban(&mut self, id: &NodeId) -> bool {
+		let now = reputation::now();
+		match self.nodes.get_mut(id) {
+			Some(node) => {
+				if !node.reputation.is_banned(now) {
+					return false;
+				}
+				node.reputation.unban(now);
+				true
+			},
+			None => false,
+		}
+	}
+
 	/// Mark as useless, no furter attempts to connect until next call to `clear_useless`.
 	pub fn mark_as_useless(&mut self, id: &NodeId) {
 		self.useless_nodes.insert(id.clone());
@@ -278,10 +362,18 @@ impl NodeTable {
 			let mut json = String::new();
 			json.push_str("{\n");
 			json.push_str("\"nodes\": [\n");
-			let node_ids = self.nodes(AllowIP::All);
-			for i in 0 .. node_ids.len() {
-				let node = self.nodes.get(&node_ids[i]).unwrap();
-				json.push_str(&format!("\t{{ \"url\": \"{}\", \"failures\": {} }}{}\n", node, node.failures, if i == node_ids.len() - 1 {""} else {","}))
+			let now = reputation::now();
+			let nodes = self.ordered_entries();
+			for i in 0 .. nodes.len() {
+				let node = nodes[i];
+				let mut reputation = node.reputation.clone();
+				reputation.decay(now);
+				let banned_until = match reputation.banned_until {
+					Some(until) => format!(", \"banned_until\": {}", until),
+					None => String::new(),
+				};
+				json.push_str(&format!("\t{{ \"url\": \"{}\", \"failures\": {}, \"reputation\": {}, \"bans\": {}{} }}{}\n",
+					node, node.failures, reputation.score, reputation.bans, banned_until, if i == nodes.len() - 1 {""} else {","}))
 			}
 			json.push_str("]\n");
 			json.push_str("}");
@@ -332,6 +424,13 @@ impl NodeTable {
 							if let Some(failures) = n.get("failures").and_then(|f| f.as_u64()) {
 								node.failures = failures as u32;
 							}
+							if let Some(score) = n.get("reputation").and_then(|r| r.as_i64()) {
+								node.reputation.score = score as i32;
+							}
+							if let Some(bans) = n.get("bans").and_then(|b| b.as_u64()) {
+								node.reputation.bans = bans as u32;
+							}
+							node.reputation.banned_until = n.get("banned_until").and_then(|b| b.as_u64());
 							nodes.insert(node.id.clone(), node);
 						}
 					}
@@ -354,6 +453,14 @@ pub fn is_valid_node_url(url: &str) -> bool {
 	Node::from_str(url).is_ok()
 }
 
+/// Parse a node id given either as an enode URL or as a hex encoded public key.
+pub fn node_id_from_str(s: &str) -> Result<NodeId, NetworkError> {
+	match Node::from_str(s) {
+		Ok(n) => Ok(n.id),
+		Err(_) => Ok(try!(NodeId::from_str(s).map_err(UtilError::from))),
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -361,6 +468,9 @@ mod tests {
 	use std::net::*;
 	use util::hash::*;
 	use devtools::*;
+	use std::collections::{HashMap, HashSet};
+	use reputation::PeerEvent;
+	use discovery::TableUpdates;
 	use AllowIP;
 
 	#[test]
@@ -434,4 +544,56 @@ mod tests {
 			assert_eq!(r[1][..], id2[..]);
 		}
 	}
+
+	#[test]
+	fn table_bans_and_persists_reputation() {
+		let temp_path = RandomTempPath::create_dir();
+		let node1 = Node::from_str("enode://a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c@22.99.55.44:7770").unwrap();
+		let node2 = Node::from_str("enode://b979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c@22.99.55.44:7770").unwrap();
+		let id1 = H512::from_str("a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c").unwrap();
+		let id2 = H512::from_str("b979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c").unwrap();
+		{
+			let mut table = NodeTable::new(Some(temp_path.as_path().to_str().unwrap().to_owned()));
+			table.add_node(node1);
+			table.add_node(node2);
+			assert!(!table.note_event(&id2, PeerEvent::BadBlock));
+			assert!(table.note_event(&id2, PeerEvent::BadBlock));
+			assert!(table.is_banned(&id2));
+			assert_eq!(table.nodes(AllowIP::All), vec![id1.clone()]);
+		}
+
+		{
+			let mut table = NodeTable::new(Some(temp_path.as_path().to_str().unwrap().to_owned()));
+			assert!(table.is_banned(&id2));
+			let banned = table.banned_nodes();
+			assert_eq!(banned.len(), 1);
+			assert_eq!(banned[0].id, id2);
+			assert_eq!(banned[0].bans, 1);
+
+			assert!(table.unban(&id2));
+			assert!(!table.unban(&id2));
+			assert_eq!(table.nodes(AllowIP::All).len(), 2);
+		}
+	}
+
+	#[test]
+	fn table_update_keeps_banned_nodes() {
+		let node1 = Node::from_str("enode://a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c@22.99.55.44:7770").unwrap();
+		let node2 = Node::from_str("enode://b979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c@22.99.55.44:7770").unwrap();
+		let id1 = node1.id.clone();
+		let id2 = node2.id.clone();
+		let mut table = NodeTable::new(None);
+		table.add_node(node1);
+		table.add_node(node2);
+		table.note_event(&id2, PeerEvent::BadBlock);
+		table.note_event(&id2, PeerEvent::BadBlock);
+
+		let mut removed = HashSet::new();
+		removed.insert(id1.clone());
+		removed.insert(id2.clone());
+		table.update(TableUpdates { added: HashMap::new(), removed: removed }, &HashSet::new());
+
+		assert!(table.get_mut(&id1).is_none());
+		assert!(table.is_banned(&id2));
+	}
 }
@@ -0,0 +1,207 @@
+// Copyright 2015, 2016 Ethcore (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Peer reputation tracking
+
+use std::cmp::{min, max};
+use time;
+
+/// Reputation score a node starts with.
+const INITIAL_SCORE: i32 = 0;
+/// Highest possible reputation score.
+const MAX_SCORE: i32 = 100;
+/// Nodes reaching this score get banned.
+const BAN_SCORE: i32 = -100;
+/// Score given to a node once its ban expires.
+const UNBAN_SCORE: i32 = BAN_SCORE / 2;
+/// Seconds it takes for a score to move one point towards `INITIAL_SCORE`.
+const DECAY_INTERVAL_SEC: u64 = 60;
+/// Duration of the first ban.
+const BASE_BAN_SEC: u64 = 10 * 60;
+/// Longest possible ban.
+const MAX_BAN_SEC: u64 = 7 * 24 * 60 * 60;
+
+/// Peer behaviour reported by protocol handlers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerEvent {
+	/// Peer provided useful data.
+	UsefulData,
+	/// Peer did not respond to a request in time.
+	SlowResponse,
+	/// Peer sent invalid snapshot data.
+	BadSnapshotData,
+	/// Peer sent an invalid block or header.
+	BadBlock,
+	/// Peer violated the protocol.
+	BadProtocol,
+}
+
+impl PeerEvent {
+	/// Reputation score change for this event.
+	pub fn score(&self) -> i32 {
+		match *self {
+			PeerEvent::UsefulData => 1,
+			PeerEvent::SlowResponse => -5,
+			PeerEvent::BadSnapshotData => -25,
+			PeerEvent::BadBlock => -50,
+			PeerEvent::BadProtocol => -50,
+		}
+	}
+}
+
+/// Reputation of a single node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reputation {
+	/// Current score.
+	pub score: i32,
+	/// Number of times the node has been banned.
+	pub bans: u32,
+	/// Unix time when the current ban expires.
+	pub banned_until: Option<u64>,
+	/// Unix time the score was last decayed.
+	pub updated: u64,
+}
+
+impl Default for Reputation {
+	fn default() -> Self {
+		Reputation {
+			score: INITIAL_SCORE,
+			bans: 0,
+			banned_until: None,
+			updated: now(),
+		}
+	}
+}
+
+impl Reputation {
+	/// Move the score towards the initial value according to the time passed and lift expired bans.
+	pub fn decay(&mut self, now: u64) {
+		if let Some(until) = self.banned_until {
+			if now < until {
+				return;
+			}
+			self.banned_until = None;
+			self.score = UNBAN_SCORE;
+			self.updated = until;
+		}
+		let steps = (now.saturating_sub(self.updated) / DECAY_INTERVAL_SEC) as i32;
+		if steps > 0 {
+			self.score = if self.score > INITIAL_SCORE {
+				max(INITIAL_SCORE, self.score.saturating_sub(steps))
+			} else {
+				min(INITIAL_SCORE, self.score.saturating_add(steps))
+			};
+			self.updated += steps as u64 * DECAY_INTERVAL_SEC;
+		}
+	}
+
+	/// Apply an event. Returns true if the node got banned as a result.
+	pub fn note(&mut self, event: PeerEvent, now: u64) -> bool {
+		self.decay(now);
+		if self.is_banned(now) {
+			return false;
+		}
+		self.score = max(BAN_SCORE, min(MAX_SCORE, self.score + event.score()));
+		if self.score > BAN_SCORE {
+			return false;
+		}
+		let duration = if self.bans < 16 { min(BASE_BAN_SEC << self.bans, MAX_BAN_SEC) } else { MAX_BAN_SEC };
+		self.bans += 1;
+		self.banned_until = Some(now + duration);
+		true
+	}
+
+	/// Check if the node is banned at the given time.
+	pub fn is_banned(&self, now: u64) -> bool {
+		self.banned_until.map_or(false, |until| now < until)
+	}
+
+	/// Lift the ban and reset the score.
+	pub fn unban(&mut self, now: u64) {
+		self.banned_until = None;
+		self.score = INITIAL_SCORE;
+		self.updated = now;
+	}
+}
+
+/// Current unix time in seconds.
+pub fn now() -> u64 {
+	time::get_time().sec as u64
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn reputation(now: u64) -> Reputation {
+		Reputation { score: 0, bans: 0, banned_until: None, updated: now }
+	}
+
+	#[test]
+	fn bad_blocks_get_node_banned() {
+		let mut r = reputation(1000);
+		assert!(!r.note(PeerEvent::BadBlock, 1000));
+		assert!(r.note(PeerEvent::BadBlock, 1000));
+		assert!(r.is_banned(1000));
+		assert!(r.is_banned(1000 + BASE_BAN_SEC - 1));
+		assert!(!r.is_banned(1000 + BASE_BAN_SEC));
+	}
+
+	#[test]
+	fn score_decays_towards_initial() {
+		let mut r = reputation(0);
+		r.note(PeerEvent::BadBlock, 0);
+		assert_eq!(r.score, -50);
+		r.decay(10 * DECAY_INTERVAL_SEC);
+		assert_eq!(r.score, -40);
+		r.decay(100 * DECAY_INTERVAL_SEC);
+		assert_eq!(r.score, 0);
+		for _ in 0..5 {
+			r.note(PeerEvent::UsefulData, 100 * DECAY_INTERVAL_SEC);
+		}
+		assert_eq!(r.score, 5);
+		r.decay(103 * DECAY_INTERVAL_SEC);
+		assert_eq!(r.score, 2);
+	}
+
+	#[test]
+	fn repeated_bans_last_longer() {
+		let mut r = reputation(0);
+		r.note(PeerEvent::BadBlock, 0);
+		r.note(PeerEvent::BadBlock, 0);
+		assert_eq!(r.banned_until, Some(BASE_BAN_SEC));
+		// ignored while banned
+		assert!(!r.note(PeerEvent::BadBlock, 1));
+
+		let now = BASE_BAN_SEC;
+		r.decay(now);
+		assert_eq!(r.score, UNBAN_SCORE);
+		assert!(r.note(PeerEvent::BadBlock, now));
+		assert_eq!(r.banned_until, Some(now + 2 * BASE_BAN_SEC));
+	}
+
+	#[test]
+	fn unban_resets_score() {
+		let mut r = reputation(0);
+		r.note(PeerEvent::BadProtocol, 0);
+		r.note(PeerEvent::BadProtocol, 0);
+		assert!(r.is_banned(0));
+		r.unban(0);
+		assert!(!r.is_banned(0));
+		assert_eq!(r.score, 0);
+		assert_eq!(r.bans, 1);
+	}
+}
@@ -14,10 +14,11 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use {NetworkProtocolHandler, NetworkConfiguration, NonReservedPeerMode};
+use {NetworkProtocolHandler, NetworkContext, NetworkConfiguration, NonReservedPeerMode};
 use error::NetworkError;
-use host::{Host, NetworkContext, NetworkIoMessage, ProtocolId};
+use host::{Host, NetworkIoMessage, ProtocolId, NatStatus};
 use stats::NetworkStats;
+use node_table::{BannedNode, NodeId};
 use io::*;
 use parking_lot::RwLock;
 use std::sync::Arc;
@@ -74,11 +75,17 @@ impl NetworkService {
 
 	/// Regiter a new protocol handler with the event loop.
 	pub fn register_protocol(&self, handler: Arc<NetworkProtocolHandler + Send + Sync>, protocol: ProtocolId, packet_count: u8, versions: &[u8]) -> Result<(), NetworkError> {
+		let versions: Vec<_> = versions.iter().map(|v| (*v, packet_count)).collect();
+		self.register_protocol_versions(handler, protocol, &versions)
+	}
+
+	/// Register a new protocol handler with the event loop, giving the number of packet IDs
+	/// reserved by each of its versions.
+	pub fn register_protocol_versions(&self, handler: Arc<NetworkProtocolHandler + Send + Sync>, protocol: ProtocolId, versions: &[(u8, u8)]) -> Result<(), NetworkError> {
 		try!(self.io_service.send_message(NetworkIoMessage::AddHandler {
 			handler: handler,
 			protocol: protocol,
 			versions: versions.to_vec(),
-			packet_count: packet_count,
 		}));
 		Ok(())
 	}
@@ -152,6 +159,78 @@ impl NetworkService {
 		}
 	}
 
+	/// Returns nodes that are currently banned.
+	pub fn banned_nodes(&self) -> Vec<BannedNode> {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			host.banned_nodes()
+		} else {
+			Vec::new()
+		}
+	}
+
+	/// Lift the ban for a node.
+	pub fn unban_node(&self, node: &str) -> Result<(), NetworkError> {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			host.unban_node(node)
+		} else {
+			Err(NetworkError::PeerNotFound)
+		}
+	}
+
+	/// Returns NAT traversal status.
+	pub fn nat_status(&self) -> NatStatus {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			host.nat_status()
+		} else {
+			NatStatus::default()
+		}
+	}
+
+	/// Returns nodes allowed to connect in permissioned mode.
+	pub fn allowed_nodes(&self) -> Result<Vec<NodeId>, NetworkError> {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			host.allowed_nodes()
+		} else {
+			Err(NetworkError::NotRunning)
+		}
+	}
+
+	/// Add a node to the allowlist.
+	pub fn add_allowed_node(&self, node: &str) -> Result<(), NetworkError> {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			host.add_allowed_node(node)
+		} else {
+			Err(NetworkError::NotRunning)
+		}
+	}
+
+	/// Remove a node from the allowlist and disconnect it.
+	pub fn remove_allowed_node(&self, node: &str) -> Result<(), NetworkError> {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			let io_ctxt = IoContext::new(self.io_service.channel(), 0);
+			host.remove_allowed_node(node, &io_ctxt)
+		} else {
+			Err(NetworkError::NotRunning)
+		}
+	}
+
+	/// Read the allowlist file again.
+	pub fn reload_allowlist(&self) -> Result<(), NetworkError> {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			let io_ctxt = IoContext::new(self.io_service.channel(), 0);
+			host.reload_allowlist(&io_ctxt)
+		} else {
+			Err(NetworkError::NotRunning)
+		}
+	}
+
 	/// Try to remove a reserved peer.
 	pub fn remove_reserved_peer(&self, peer: &str) -> Result<(), NetworkError> {
 		let host = self.host.read();
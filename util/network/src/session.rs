@@ -28,7 +28,9 @@ use io::{IoContext, StreamToken};
 use error::{NetworkError, DisconnectReason};
 use host::*;
 use node_table::NodeId;
-use stats::NetworkStats;
+use stats::{NetworkStats, SessionStats};
+use bandwidth::{BandwidthLimits, Throttle};
+use capture::{PacketRecorder, PacketDirection};
 use time;
 
 const PING_TIMEOUT_SEC: u64 = 65;
@@ -47,6 +49,8 @@ pub struct Session {
 	ping_time_ns: u64,
 	pong_time_ns: Option<u64>,
 	state: State,
+	/// Records subprotocol packets when capture is enabled.
+	recorder: Option<Arc<PacketRecorder>>,
 }
 
 enum State {
@@ -93,6 +97,8 @@ pub struct SessionInfo {
 	pub remote_address: String,
 	/// Local endpoint address of the session
 	pub local_address: String,
+	/// Session traffic statistics
+	pub stats: SessionStats,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -161,10 +167,11 @@ impl Session {
 	/// Create a new session out of comepleted handshake. This clones the handshake connection object
 	/// and leaves the handhsake in limbo to be deregistered from the event loop.
 	pub fn new<Message>(io: &IoContext<Message>, socket: TcpStream, token: StreamToken, id: Option<&NodeId>,
-		nonce: &H256, stats: Arc<NetworkStats>, host: &HostInfo) -> Result<Session, NetworkError>
+		nonce: &H256, stats: Arc<NetworkStats>, limits: Arc<BandwidthLimits>, recorder: Option<Arc<PacketRecorder>>, host: &HostInfo) -> Result<Session, NetworkError>
 		where Message: Send + Clone + Sync + 'static {
 		let originated = id.is_some();
 		let mut handshake = Handshake::new(token, id, socket, nonce, stats).expect("Can't create handshake");
+		handshake.connection.set_throttle(Throttle::new(limits));
 		let local_addr = handshake.connection.local_addr_str();
 		try!(handshake.start(io, host, originated));
 		Ok(Session {
@@ -180,10 +187,12 @@ impl Session {
 				originated: originated,
 				remote_address: "Handshake".to_owned(),
 				local_address: local_addr,
+				stats: SessionStats::default(),
 			},
 			ping_time_ns: 0,
 			pong_time_ns: None,
 			expired: false,
+			recorder: recorder,
 		})
 	}
 
@@ -236,6 +245,16 @@ impl Session {
 		self.expired() && !self.connection().is_sending()
 	}
 
+	/// Check if reading has been postponed because of the download rate limit.
+	pub fn is_read_throttled(&self) -> bool {
+		self.connection().is_read_throttled()
+	}
+
+	/// Check if writing has been postponed because of the upload rate limit.
+	pub fn is_write_throttled(&self) -> bool {
+		self.connection().is_write_throttled()
+	}
+
 	/// Get remote peer address
 	pub fn remote_addr(&self) -> io::Result<SocketAddr> {
 		self.connection().remote_addr()
@@ -329,11 +348,14 @@ impl Session {
 				return Ok(())
 			}
 		}
+		if let Some(ref recorder) = self.recorder {
+			recorder.record(self.token(), self.id(), PacketDirection::Outbound, protocol, packet_id, data);
+		}
 		let pid = self.info.capabilities[i].id_offset + packet_id;
 		let mut rlp = RlpStream::new();
 		rlp.append(&(pid as u32));
 		rlp.append_raw(data, 1);
-		self.send(io, rlp)
+		self.send(io, Some(protocol), rlp)
 	}
 
 	/// Keep this session alive. Returns false if ping timeout happened
@@ -368,6 +390,9 @@ impl Session {
 		if packet_id != PACKET_HELLO && packet_id != PACKET_DISCONNECT && !self.had_hello {
 			return Err(From::from(NetworkError::BadProtocol));
 		}
+		if packet_id < PACKET_USER || packet_id > PACKET_LAST {
+			self.info.stats.on_received(None, packet.data.len());
+		}
 		match packet_id {
 			PACKET_HELLO => {
 				let rlp = UntrustedRlp::new(&packet.data[1..]); //TODO: validate rlp expected size
@@ -399,6 +424,7 @@ impl Session {
 					i += 1;
 					if i == self.info.capabilities.len() {
 						debug!(target: "network", "Unknown packet: {:?}", packet_id);
+						self.info.stats.on_received(None, packet.data.len());
 						return Ok(SessionData::Continue)
 					}
 				}
@@ -406,6 +432,10 @@ impl Session {
 				// map to protocol
 				let protocol = self.info.capabilities[i].protocol;
 				let pid = packet_id - self.info.capabilities[i].id_offset;
+				self.info.stats.on_received(Some(protocol), packet.data.len());
+				if let Some(ref recorder) = self.recorder {
+					recorder.record(self.token(), self.id(), PacketDirection::Inbound, protocol, pid, &packet.data[1..]);
+				}
 				Ok(SessionData::Packet { data: packet.data, protocol: protocol, packet_id: pid } )
 			},
 			_ => {
@@ -424,7 +454,7 @@ impl Session {
 			.append(&host.capabilities)
 			.append(&host.local_endpoint.address.port())
 			.append(host.id());
-		self.send(io, rlp)
+		self.send(io, None, rlp)
 	}
 
 	fn read_hello<Message>(&mut self, io: &IoContext<Message>, rlp: &UntrustedRlp, host: &HostInfo) -> Result<(), NetworkError>
@@ -488,14 +518,14 @@ impl Session {
 
 	/// Senf ping packet
 	pub fn send_ping<Message>(&mut self, io: &IoContext<Message>) -> Result<(), NetworkError> where Message: Send + Sync + Clone {
-		try!(self.send(io, try!(Session::prepare(PACKET_PING))));
+		try!(self.send(io, None, try!(Session::prepare(PACKET_PING))));
 		self.ping_time_ns = time::precise_time_ns();
 		self.pong_time_ns = None;
 		Ok(())
 	}
 
 	fn send_pong<Message>(&mut self, io: &IoContext<Message>) -> Result<(), NetworkError> where Message: Send + Sync + Clone {
-		self.send(io, try!(Session::prepare(PACKET_PONG)))
+		self.send(io, None, try!(Session::prepare(PACKET_PONG)))
 	}
 
 	/// Disconnect this session
@@ -505,7 +535,7 @@ impl Session {
 			rlp.append(&(PACKET_DISCONNECT as u32));
 			rlp.begin_list(1);
 			rlp.append(&(reason as u32));
-			self.send(io, rlp).ok();
+			self.send(io, None, rlp).ok();
 		}
 		NetworkError::Disconnect(reason)
 	}
@@ -517,13 +547,15 @@ impl Session {
 		Ok(rlp)
 	}
 
-	fn send<Message>(&mut self, io: &IoContext<Message>, rlp: RlpStream) -> Result<(), NetworkError> where Message: Send + Sync + Clone {
+	fn send<Message>(&mut self, io: &IoContext<Message>, protocol: Option<ProtocolId>, rlp: RlpStream) -> Result<(), NetworkError> where Message: Send + Sync + Clone {
 		match self.state {
 			State::Handshake(_) => {
 				warn!(target:"network", "Unexpected send request");
 			},
 			State::Session(ref mut s) => {
-				try!(s.send_packet(io, &rlp.out()))
+				let data = rlp.out();
+				try!(s.send_packet(io, &data));
+				self.info.stats.on_sent(protocol, data.len());
 			},
 		}
 		Ok(())
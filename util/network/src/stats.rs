@@ -16,6 +16,8 @@
 
 //! Network Statistics
 use std::sync::atomic::*;
+use std::collections::HashMap;
+use host::ProtocolId;
 
 /// Network statistics structure
 #[derive(Default, Debug)]
@@ -74,3 +76,57 @@ impl NetworkStats {
 		}
 	}
 }
+
+/// Packet and byte counters.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct TrafficCounters {
+	/// Packets sent
+	pub packets_sent: u64,
+	/// Packet bytes sent
+	pub bytes_sent: u64,
+	/// Packets received
+	pub packets_received: u64,
+	/// Packet bytes received
+	pub bytes_received: u64,
+}
+
+impl TrafficCounters {
+	/// Count a sent packet of given size.
+	pub fn on_sent(&mut self, size: usize) {
+		self.packets_sent += 1;
+		self.bytes_sent += size as u64;
+	}
+
+	/// Count a received packet of given size.
+	pub fn on_received(&mut self, size: usize) {
+		self.packets_received += 1;
+		self.bytes_received += size as u64;
+	}
+}
+
+/// Traffic statistics of a single session.
+#[derive(Default, Debug, Clone)]
+pub struct SessionStats {
+	/// All packets, including the base `RLPx` protocol.
+	pub total: TrafficCounters,
+	/// Subprotocol packets.
+	pub protocols: HashMap<ProtocolId, TrafficCounters>,
+}
+
+impl SessionStats {
+	/// Count a sent packet. `protocol` is `None` for base protocol packets.
+	pub fn on_sent(&mut self, protocol: Option<ProtocolId>, size: usize) {
+		self.total.on_sent(size);
+		if let Some(protocol) = protocol {
+			self.protocols.entry(protocol).or_insert_with(Default::default).on_sent(size);
+		}
+	}
+
+	/// Count a received packet. `protocol` is `None` for base protocol packets.
+	pub fn on_received(&mut self, protocol: Option<ProtocolId>, size: usize) {
+		self.total.on_received(size);
+		if let Some(protocol) = protocol {
+			self.protocols.entry(protocol).or_insert_with(Default::default).on_received(size);
+		}
+	}
+}
@@ -149,6 +149,44 @@ fn net_disconnect() {
 	assert!(handler2.got_disconnect());
 }
 
+#[test]
+fn net_rejects_nodes_not_in_allowlist() {
+	use std::fs::File;
+	use std::io::Write;
+	use devtools::RandomTempPath;
+
+	let dir = RandomTempPath::create_dir();
+	let allowlist = dir.new_in("allowlist");
+	let allowed = Random.generate().unwrap();
+	File::create(&allowlist).unwrap().write_all(format!("{}\n", allowed.public().hex()).as_bytes()).unwrap();
+
+	let mut config1 = NetworkConfiguration::new_local();
+	config1.allowlist_path = Some(allowlist);
+	let mut service1 = NetworkService::new(config1).unwrap();
+	service1.start().unwrap();
+	let handler1 = TestProtocol::register(&mut service1, false);
+	let mut config2 = NetworkConfiguration::new_local();
+	config2.boot_nodes = vec![ service1.local_url().unwrap() ];
+	let mut service2 = NetworkService::new(config2).unwrap();
+	service2.start().unwrap();
+	let handler2 = TestProtocol::register(&mut service2, false);
+	thread::sleep(Duration::from_millis(2000));
+	assert!(!handler1.got_packet());
+	assert!(!handler2.got_packet());
+	assert_eq!(service1.allowed_nodes().unwrap(), vec![allowed.public().clone()]);
+}
+
+#[test]
+fn net_fails_to_start_without_allowlist_file() {
+	use devtools::RandomTempPath;
+
+	let dir = RandomTempPath::create_dir();
+	let mut config = NetworkConfiguration::new_local();
+	config.allowlist_path = Some(dir.new_in("allowlist"));
+	let service = NetworkService::new(config).unwrap();
+	assert!(service.start().is_err());
+}
+
 #[test]
 fn net_timeout() {
 	let config = NetworkConfiguration::new_local();